@@ -0,0 +1,21 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=proto/clamav.proto");
+
+    #[cfg(feature = "tonic")]
+    compile_protos();
+}
+
+/// Generates the messages, server and client of the gRPC API in
+/// `proto/clamav.proto`, using the vendored `protoc` unless `PROTOC` is set
+#[cfg(feature = "tonic")]
+fn compile_protos() {
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc is vendored");
+        std::env::set_var("PROTOC", protoc);
+    }
+    tonic_prost_build::configure()
+        .build_transport(false)
+        .compile_protos(&["proto/clamav.proto"], &["proto"])
+        .expect("the gRPC API compiles");
+}
@@ -0,0 +1,393 @@
+//! Virus scanning for upload endpoints of [`actix-web`](https://docs.rs/actix-web)
+//!
+//! The extractors [`ScannedBytes`] and [`ScannedMultipart`] scan the request
+//! payload of a handler while it is received and reject infected uploads with
+//! a [`ScanRejection`]. The [`ScanMiddleware`] scans the payloads of all
+//! requests it wraps before they reach the handlers. The extractors get the
+//! [`Scanner`] from the app data, where it is added as `web::Data<Scanner>`.
+//!
+//! Payloads are kept in memory, so limit their size using
+//! [`ScanOptions::max_stream_size`](crate::ScanOptions::max_stream_size).
+//! Payloads that exceed it are rejected with `413 Payload Too Large`.
+//!
+//! Use the feature flag "actix" to enable this module.
+//!
+//! # Example
+//!
+//! ```
+//! use actix_web::{web, App};
+//! use clamav_client::{
+//!     actix::{ScanMiddleware, ScannedBytes, Scanner},
+//!     ScanOptions,
+//! };
+//!
+//! async fn upload(ScannedBytes(data): ScannedBytes) -> String {
+//!     format!("Received {} clean bytes", data.len())
+//! }
+//!
+//! let options = ScanOptions::new().max_stream_size(10_000_000);
+//! let scanner = Scanner::tcp("localhost:3310").options(options);
+//! let app = App::new()
+//!     .app_data(web::Data::new(scanner.clone()))
+//!     .route("/upload", web::post().to(upload));
+//!
+//! // Or scan the payloads of all requests
+//! let app = App::new()
+//!     .wrap(ScanMiddleware::new(scanner))
+//!     .route("/files", web::post().to(|body: String| async move { body }));
+//! ```
+//!
+
+use std::{
+    future::{ready, Future, Ready},
+    io,
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorInternalServerError,
+    http::{header::CONTENT_LENGTH, StatusCode},
+    web::{Bytes, Data},
+    FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use tokio_stream::StreamExt;
+
+use super::{
+    tokio::{scan_field, MultipartField, TransportProtocol},
+    ClamdError, ScanOptions, ScanOutcome,
+};
+
+pub use super::web::{ScanRejection, Scanner};
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+impl ResponseError for ScanRejection {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(ScanRejection::status_code(self)).expect("the status code is valid")
+    }
+}
+
+/// Scans the file fields of an `actix-multipart` form for viruses as they
+/// stream in
+///
+/// This is the `actix-multipart` counterpart of
+/// [`tokio::scan_multipart`](crate::tokio::scan_multipart), see there for
+/// details. The contents of the other fields are kept in memory without a
+/// limit.
+///
+/// Use the feature flag "actix" to enable this function.
+///
+/// # Arguments
+///
+/// * `multipart`: The multipart form to read, e.g. extracted by a handler
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// A [`MultipartField`] with the result and, if clean, the contents of each
+/// field in the order of the form. If the form is malformed, the scan fails
+/// with an [`io::Error`] of kind [`InvalidData`](io::ErrorKind::InvalidData).
+///
+/// # Example
+///
+/// ```
+/// use actix_multipart::Multipart;
+///
+/// async fn upload(multipart: Multipart) -> String {
+///     let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+///     let fields = clamav_client::actix::scan_multipart(multipart, clamd_tcp, None).await;
+///     match fields {
+///         Ok(fields) if fields.iter().all(|field| field.is_clean()) => "OK".to_owned(),
+///         Ok(_) => "Rejected".to_owned(),
+///         Err(err) => err.to_string(),
+///     }
+/// }
+/// ```
+///
+pub async fn scan_multipart<T, O>(
+    mut multipart: Multipart,
+    connection: T,
+    options: O,
+) -> Result<Vec<MultipartField>, ClamdError>
+where
+    T: TransportProtocol + Clone,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let mut fields = Vec::new();
+    while let Some(field) = multipart.next().await {
+        let field = field.map_err(multipart_error)?;
+        let name = field.name().map(str::to_owned);
+        let file_name = field
+            .content_disposition()
+            .and_then(|content_disposition| content_disposition.get_filename())
+            .map(str::to_owned);
+        let content_type = field.content_type().map(ToString::to_string);
+        // A field must not be polled again after it ended
+        let mut field = field.fuse();
+
+        let mut chunks = Vec::new();
+        let result = if file_name.is_some() {
+            let mut parse_error = None;
+            let stream = (&mut field).map(|chunk| match chunk {
+                Ok(chunk) => {
+                    chunks.push(chunk.clone());
+                    Ok(chunk)
+                }
+                Err(err) => {
+                    let io_err = io::Error::new(io::ErrorKind::InvalidData, err.to_string());
+                    parse_error = Some(err);
+                    Err(io_err)
+                }
+            });
+            let result = scan_field(stream, connection.clone(), &options).await;
+            if let Some(err) = parse_error {
+                return Err(multipart_error(err));
+            }
+            Some(result)
+        } else {
+            None
+        };
+
+        // Read the rest of a file field that could not be scanned, or all of
+        // a field that is not scanned
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(multipart_error)?;
+            if result.is_none() {
+                chunks.push(chunk);
+            }
+        }
+        let data = match &result {
+            None | Some(Ok(ScanOutcome::Clean)) => Some(chunks.concat().into()),
+            Some(_) => None,
+        };
+        fields.push(MultipartField {
+            name,
+            file_name,
+            content_type,
+            result,
+            data,
+        });
+    }
+    Ok(fields)
+}
+
+fn multipart_error(err: MultipartError) -> ClamdError {
+    let err = match err {
+        MultipartError::Payload(err) => io::Error::new(io::ErrorKind::InvalidData, err),
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    };
+    err.into()
+}
+
+/// Returns the scanner added to the app data
+fn app_scanner(request: &HttpRequest) -> Result<Data<Scanner>, actix_web::Error> {
+    request
+        .app_data::<Data<Scanner>>()
+        .cloned()
+        .ok_or_else(|| ErrorInternalServerError("Scanner is not added to the app data"))
+}
+
+/// An extractor that scans the request payload and returns it if it is clean
+///
+/// # Example
+///
+/// ```
+/// use actix_web::{web, App};
+/// use clamav_client::actix::{ScannedBytes, Scanner};
+///
+/// async fn upload(ScannedBytes(data): ScannedBytes) -> String {
+///     format!("Received {} clean bytes", data.len())
+/// }
+///
+/// let app = App::new()
+///     .app_data(web::Data::new(Scanner::tcp("localhost:3310")))
+///     .route("/upload", web::post().to(upload));
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ScannedBytes(pub Bytes);
+
+impl FromRequest for ScannedBytes {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let scanner = app_scanner(request);
+        let payload = payload.take();
+        Box::pin(async move {
+            let data = scanner?.scan_body(payload).await?;
+            Ok(ScannedBytes(data))
+        })
+    }
+}
+
+/// An extractor that scans the file fields of a `multipart/form-data` request
+/// payload and returns all fields if the file fields are clean
+///
+/// See [`scan_multipart`] for how the fields are scanned.
+///
+/// # Example
+///
+/// ```
+/// use actix_web::{web, App};
+/// use clamav_client::actix::{ScannedMultipart, Scanner};
+///
+/// async fn upload(ScannedMultipart(fields): ScannedMultipart) -> String {
+///     format!("Received {} clean fields", fields.len())
+/// }
+///
+/// let app = App::new()
+///     .app_data(web::Data::new(Scanner::tcp("localhost:3310")))
+///     .route("/upload", web::post().to(upload));
+/// ```
+///
+#[derive(Debug)]
+pub struct ScannedMultipart(pub Vec<MultipartField>);
+
+impl FromRequest for ScannedMultipart {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let scanner = app_scanner(request);
+        let multipart = Multipart::new(request.headers(), payload.take());
+        Box::pin(async move {
+            let fields = scanner?.scan_actix_form(multipart).await?;
+            Ok(ScannedMultipart(fields))
+        })
+    }
+}
+
+type RejectionHandler = Rc<dyn Fn(ScanRejection) -> HttpResponse>;
+
+/// A middleware that scans request payloads before they reach the wrapped
+/// service and short-circuits infected requests
+///
+/// Requests without a payload are passed through without scanning.
+///
+/// # Example
+///
+/// ```
+/// use actix_web::{web, App, HttpResponse, ResponseError};
+/// use clamav_client::actix::{ScanMiddleware, ScanRejection, Scanner};
+///
+/// let middleware = ScanMiddleware::new(Scanner::tcp("localhost:3310"))
+///     .min_size(1024)
+///     .on_rejection(|rejection| match rejection {
+///         ScanRejection::Infected { .. } => HttpResponse::Forbidden().finish(),
+///         rejection => rejection.error_response(),
+///     });
+/// let app = App::new()
+///     .wrap(middleware)
+///     .route("/upload", web::post().to(|body: String| async move { body }));
+/// ```
+///
+#[derive(Clone)]
+pub struct ScanMiddleware {
+    scanner: Scanner,
+    min_size: u64,
+    on_rejection: RejectionHandler,
+}
+
+impl ScanMiddleware {
+    /// Creates a middleware that scans request payloads using `scanner`
+    pub fn new(scanner: Scanner) -> Self {
+        ScanMiddleware {
+            scanner,
+            min_size: 0,
+            on_rejection: Rc::new(|rejection| rejection.error_response()),
+        }
+    }
+
+    /// Passes requests with a `Content-Length` below `min_size` bytes through
+    /// without scanning them
+    ///
+    /// Requests without a `Content-Length`, e.g. chunked requests, are always
+    /// scanned.
+    pub fn min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Sets the function that creates the response for a rejected request,
+    /// instead of the response of the [`ScanRejection`]
+    pub fn on_rejection<F>(mut self, on_rejection: F) -> Self
+    where
+        F: Fn(ScanRejection) -> HttpResponse + 'static,
+    {
+        self.on_rejection = Rc::new(on_rejection);
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ScanMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = ScanService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ScanService {
+            service: Rc::new(service),
+            middleware: self.clone(),
+        }))
+    }
+}
+
+/// The [`Service`] created by [`ScanMiddleware`]
+pub struct ScanService<S> {
+    service: Rc<S>,
+    middleware: ScanMiddleware,
+}
+
+impl<S, B> Service<ServiceRequest> for ScanService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut request: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let middleware = self.middleware.clone();
+        Box::pin(async move {
+            let content_length = request
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok());
+            let payload = request.take_payload();
+            let payload = match payload {
+                Payload::None => Payload::None,
+                payload if content_length.is_some_and(|len| len < middleware.min_size) => payload,
+                payload => match middleware.scanner.scan_body(payload).await {
+                    Ok(data) => Payload::from(data),
+                    Err(rejection) => {
+                        let response = (middleware.on_rejection)(rejection);
+                        return Ok(request.into_response(response).map_into_right_body());
+                    }
+                },
+            };
+            request.set_payload(payload);
+            service
+                .call(request)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
@@ -0,0 +1,116 @@
+use super::{ScanOutcome, VerdictPolicy};
+
+/// A [`VerdictPolicy`] that tolerates detections of known signatures
+///
+/// Detections of signatures that are allowed become [`ScanOutcome::Clean`],
+/// e.g. for an archive that legitimately contains EICAR test files, and
+/// detections of signatures that only warn become [`ScanOutcome::Warning`],
+/// e.g. for known-benign heuristic hits. Allowed signatures take precedence
+/// over signatures that only warn. All other outcomes are returned as they
+/// are.
+///
+/// Signature names are matched exactly and case-sensitively, except that `*`
+/// in a pattern matches any sequence of characters and `?` any single
+/// character.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{ScanOutcome, SignatureAllowlist, VerdictPolicy};
+///
+/// let allowlist = SignatureAllowlist::new()
+///     .allow("Win.Test.EICAR_HDB-1")
+///     .warn("Heuristics.*");
+///
+/// let infected = |signature: &str| ScanOutcome::Infected {
+///     signature: signature.to_owned(),
+/// };
+/// assert!(allowlist.apply(infected("Win.Test.EICAR_HDB-1")).is_clean());
+/// assert!(allowlist.apply(infected("Heuristics.Encrypted.PDF")).is_warning());
+/// assert!(allowlist.apply(infected("Win.Trojan.Agent-1")).is_infected());
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureAllowlist {
+    allowed: Vec<String>,
+    warned: Vec<String>,
+}
+
+impl SignatureAllowlist {
+    /// Creates an empty allowlist, which tolerates no signatures
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treats detections of signatures matching `pattern` as clean
+    pub fn allow<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.allowed.push(pattern.into());
+        self
+    }
+
+    /// Treats detections of signatures matching `pattern` as warnings
+    pub fn warn<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.warned.push(pattern.into());
+        self
+    }
+
+    /// Returns `true` if detections of `signature` are treated as clean
+    pub fn is_allowed(&self, signature: &str) -> bool {
+        matches_any(&self.allowed, signature)
+    }
+
+    /// Returns `true` if detections of `signature` are treated as warnings
+    pub fn is_warned(&self, signature: &str) -> bool {
+        !self.is_allowed(signature) && matches_any(&self.warned, signature)
+    }
+}
+
+impl VerdictPolicy for SignatureAllowlist {
+    fn apply(&self, outcome: ScanOutcome) -> ScanOutcome {
+        match outcome {
+            ScanOutcome::Infected { signature } if self.is_allowed(&signature) => {
+                ScanOutcome::Clean
+            }
+            ScanOutcome::Infected { signature } if self.is_warned(&signature) => {
+                ScanOutcome::Warning { signature }
+            }
+            outcome => outcome,
+        }
+    }
+}
+
+fn matches_any(patterns: &[String], signature: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| wildcard_match(pattern.as_bytes(), signature.as_bytes()))
+}
+
+/// Matches `name` against `pattern`, in which `*` matches any sequence of
+/// bytes and `?` any single byte
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` in the pattern and of the byte of the name
+    // that it matched up to, to backtrack to
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&byte) if byte == b'?' || byte == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&byte| byte == b'*')
+}
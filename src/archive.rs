@@ -0,0 +1,352 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use super::{wipe::DataBuffer, ClamdError};
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZIP_LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const ZIP_END: &[u8] = b"PK\x05\x06";
+const ZIP64_END: &[u8] = b"PK\x06\x06";
+const ZIP64_LOCATOR: &[u8] = b"PK\x06\x07";
+const ZIP_CENTRAL_HEADER: &[u8] = b"PK\x01\x02";
+
+/// The size of the end of central directory record without its comment
+const ZIP_END_SIZE: u64 = 22;
+/// The largest size of the comment of a ZIP archive
+const ZIP_MAX_COMMENT_SIZE: u64 = 0xffff;
+const ZIP64_END_SIZE: usize = 56;
+const ZIP64_LOCATOR_SIZE: usize = 20;
+const ZIP_CENTRAL_HEADER_SIZE: usize = 46;
+/// The largest size of a central directory header with its name, extra field
+/// and comment
+const ZIP_MAX_CENTRAL_HEADER_SIZE: u64 = ZIP_CENTRAL_HEADER_SIZE as u64 + 3 * 0xffff;
+/// The number of bytes of the central directory read at once, which holds at
+/// least one header of any size
+const ZIP_DIRECTORY_READ_SIZE: u64 = 1024 * 1024;
+/// The ID of the extra field with the 64-bit sizes of a ZIP64 member
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+/// The smallest gzip file, a header and a trailer around an empty stream
+const GZIP_MIN_SIZE: u64 = 18;
+
+/// Limits for archives that are checked locally before they are sent to
+/// ClamAV, see [`ScanOptions::archive_limits`](crate::ScanOptions::archive_limits)
+///
+/// ZIP archives are checked using their central directory, which lists the
+/// members and their compressed and uncompressed sizes, and gzip files using
+/// their trailer, which contains the uncompressed size modulo 2^32. The
+/// compression ratio is the total uncompressed size divided by the size of
+/// the input. Inputs that are neither ZIP nor gzip, or whose structure cannot
+/// be read, are left to ClamAV.
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::ScanOptions::new().archive_limits(
+///     clamav_client::ArchiveLimits {
+///         max_ratio: 50,
+///         max_members: 1_000,
+///     },
+/// );
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveLimits {
+    /// The largest ratio of the uncompressed size to the compressed size
+    pub max_ratio: u64,
+    /// The largest number of members of a ZIP archive
+    pub max_members: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        ArchiveLimits {
+            max_ratio: 100,
+            max_members: 10_000,
+        }
+    }
+}
+
+impl ArchiveLimits {
+    fn check_ratio(&self, uncompressed_size: u64, size: u64) -> Result<(), ClamdError> {
+        if uncompressed_size <= size.saturating_mul(self.max_ratio) {
+            return Ok(());
+        }
+        Err(ClamdError::ArchiveLimitExceeded {
+            reason: format!(
+                "compression ratio of {} exceeds the limit of {}",
+                uncompressed_size / size.max(1),
+                self.max_ratio
+            ),
+        })
+    }
+
+    fn check_members(&self, members: u64) -> Result<(), ClamdError> {
+        if members <= self.max_members {
+            return Ok(());
+        }
+        Err(ClamdError::ArchiveLimitExceeded {
+            reason: format!(
+                "{} archive members exceed the limit of {}",
+                members, self.max_members
+            ),
+        })
+    }
+}
+
+/// What an [`ArchiveCheck`] reads next
+enum Step {
+    Header,
+    GzipTrailer,
+    ZipEnd {
+        len: u64,
+    },
+    Zip64End {
+        offset: u64,
+    },
+    CentralDirectory {
+        offset: u64,
+        len: u64,
+        members: u64,
+        count: u64,
+        uncompressed_size: u64,
+    },
+    Done,
+}
+
+/// The check of an input against the [`ArchiveLimits`]
+///
+/// The check only asks for the ranges of the input it needs, so that the scan
+/// functions of all runtimes only seek, read and pass the bytes to it.
+pub(crate) struct ArchiveCheck {
+    limits: ArchiveLimits,
+    size: u64,
+    step: Step,
+}
+
+impl ArchiveCheck {
+    /// Starts the check of an input of `size` bytes, or returns `None` if no
+    /// limits are configured
+    pub(crate) fn start(limits: Option<ArchiveLimits>, size: u64) -> Option<Self> {
+        Some(ArchiveCheck {
+            limits: limits?,
+            size,
+            step: Step::Header,
+        })
+    }
+
+    /// Returns the offset and length of the bytes to read next, or `None` if
+    /// the check passed
+    pub(crate) fn next_read(&self) -> Option<(u64, usize)> {
+        let (offset, len) = match self.step {
+            Step::Header => (0, self.size.min(4)),
+            Step::GzipTrailer => (self.size - 4, 4),
+            Step::ZipEnd { len } => (self.size - len, len),
+            Step::Zip64End { offset } => (offset, ZIP64_END_SIZE as u64),
+            Step::CentralDirectory { offset, len, .. } => {
+                (offset, len.min(ZIP_DIRECTORY_READ_SIZE))
+            }
+            Step::Done => return None,
+        };
+        Some((offset, len as usize))
+    }
+
+    /// Checks the bytes read at the offset returned by
+    /// [`next_read`](Self::next_read)
+    pub(crate) fn read(&mut self, bytes: &[u8]) -> Result<(), ClamdError> {
+        self.step = match self.step {
+            Step::Header if bytes.starts_with(GZIP_MAGIC) && self.size >= GZIP_MIN_SIZE => {
+                Step::GzipTrailer
+            }
+            Step::Header
+                if (bytes.starts_with(ZIP_LOCAL_HEADER) || bytes.starts_with(ZIP_END))
+                    && self.size >= ZIP_END_SIZE =>
+            {
+                Step::ZipEnd {
+                    len: self.size.min(ZIP_END_SIZE + ZIP_MAX_COMMENT_SIZE),
+                }
+            }
+            Step::Header => Step::Done,
+            Step::GzipTrailer => {
+                self.limits
+                    .check_ratio(u64::from(le_u32(bytes, 0)), self.size)?;
+                Step::Done
+            }
+            Step::ZipEnd { len } => self.zip_end(bytes, self.size - len)?,
+            Step::Zip64End { .. } => self.zip64_end(bytes)?,
+            Step::CentralDirectory {
+                offset,
+                len,
+                members,
+                count,
+                uncompressed_size,
+            } => {
+                // The directory is read in parts, so that a huge declared
+                // length does not allocate a huge buffer
+                let (headers, size, consumed) = central_directory(bytes);
+                let count = count + headers;
+                let uncompressed_size = uncompressed_size.saturating_add(size);
+                self.limits.check_members(members.max(count))?;
+                self.limits.check_ratio(uncompressed_size, self.size)?;
+                match len - consumed {
+                    // Nothing could be read from the part, i.e. the directory
+                    // ends early or is invalid
+                    len if headers == 0 || len == 0 => Step::Done,
+                    len => Step::CentralDirectory {
+                        offset: offset + consumed,
+                        len,
+                        members,
+                        count,
+                        uncompressed_size,
+                    },
+                }
+            }
+            Step::Done => Step::Done,
+        };
+        Ok(())
+    }
+
+    /// Reads the end of central directory record from the end of a ZIP
+    /// archive, which starts at `offset`
+    fn zip_end(&self, tail: &[u8], offset: u64) -> Result<Step, ClamdError> {
+        let end = match (0..=tail.len() - ZIP_END_SIZE as usize)
+            .rev()
+            .find(|&pos| tail[pos..].starts_with(ZIP_END))
+        {
+            Some(end) => end,
+            None => return Ok(Step::Done),
+        };
+        let members = le_u16(tail, end + 10);
+        let directory_len = le_u32(tail, end + 12);
+        let directory_offset = le_u32(tail, end + 16);
+        if members == u16::MAX || directory_len == u32::MAX || directory_offset == u32::MAX {
+            // The values are in the ZIP64 end of central directory record,
+            // which the locator before this record points to
+            let locator = match end.checked_sub(ZIP64_LOCATOR_SIZE) {
+                Some(locator) if tail[locator..].starts_with(ZIP64_LOCATOR) => locator,
+                _ => return Ok(Step::Done),
+            };
+            let zip64_end = le_u64(tail, locator + 8);
+            return Ok(
+                match (offset + locator as u64).checked_sub(ZIP64_END_SIZE as u64) {
+                    Some(last) if zip64_end <= last => Step::Zip64End { offset: zip64_end },
+                    _ => Step::Done,
+                },
+            );
+        }
+        self.central_directory(
+            u64::from(members),
+            u64::from(directory_offset),
+            u64::from(directory_len),
+        )
+    }
+
+    /// Reads the ZIP64 end of central directory record
+    fn zip64_end(&self, record: &[u8]) -> Result<Step, ClamdError> {
+        if !record.starts_with(ZIP64_END) {
+            return Ok(Step::Done);
+        }
+        self.central_directory(le_u64(record, 32), le_u64(record, 48), le_u64(record, 40))
+    }
+
+    /// Checks the number of members declared by the archive and returns the
+    /// step that reads its central directory
+    ///
+    /// Since the headers of one member more than the limit allows fit in
+    /// `max_len` bytes, the rest of a longer directory is not read.
+    fn central_directory(&self, members: u64, offset: u64, len: u64) -> Result<Step, ClamdError> {
+        self.limits.check_members(members)?;
+        let max_len = self
+            .limits
+            .max_members
+            .saturating_add(1)
+            .saturating_mul(ZIP_MAX_CENTRAL_HEADER_SIZE);
+        match offset.checked_add(len) {
+            Some(end) if end <= self.size => Ok(Step::CentralDirectory {
+                offset,
+                len: len.min(max_len),
+                members,
+                count: 0,
+                uncompressed_size: 0,
+            }),
+            _ => Ok(Step::Done),
+        }
+    }
+}
+
+/// Returns the number of members listed in a part of a central directory, the
+/// sum of their uncompressed sizes and the length of their headers
+///
+/// The part ends before the first header that is incomplete or invalid.
+fn central_directory(mut directory: &[u8]) -> (u64, u64, u64) {
+    let (mut members, mut uncompressed_size, mut consumed) = (0u64, 0u64, 0u64);
+    while directory.len() >= ZIP_CENTRAL_HEADER_SIZE && directory.starts_with(ZIP_CENTRAL_HEADER) {
+        let name_len = usize::from(le_u16(directory, 28));
+        let extra_len = usize::from(le_u16(directory, 30));
+        let comment_len = usize::from(le_u16(directory, 32));
+        let header_len = ZIP_CENTRAL_HEADER_SIZE + name_len + extra_len + comment_len;
+        if directory.len() < header_len {
+            break;
+        }
+        let extra = &directory[ZIP_CENTRAL_HEADER_SIZE + name_len..][..extra_len];
+        let size = match le_u32(directory, 24) {
+            u32::MAX => zip64_uncompressed_size(extra).unwrap_or(u64::MAX),
+            size => u64::from(size),
+        };
+        members += 1;
+        uncompressed_size = uncompressed_size.saturating_add(size);
+        consumed += header_len as u64;
+        directory = &directory[header_len..];
+    }
+    (members, uncompressed_size, consumed)
+}
+
+/// Returns the uncompressed size from the ZIP64 extra field of a member, which
+/// comes first in the field
+fn zip64_uncompressed_size(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let id = le_u16(extra, 0);
+        let len = usize::from(le_u16(extra, 2));
+        let data = extra.get(4..4 + len)?;
+        if id == ZIP64_EXTRA_ID {
+            return (data.len() >= 8).then(|| le_u64(data, 0));
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+fn le_u16(bytes: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([bytes[pos], bytes[pos + 1]])
+}
+
+fn le_u32(bytes: &[u8], pos: usize) -> u32 {
+    let mut value = [0; 4];
+    value.copy_from_slice(&bytes[pos..pos + 4]);
+    u32::from_le_bytes(value)
+}
+
+fn le_u64(bytes: &[u8], pos: usize) -> u64 {
+    let mut value = [0; 8];
+    value.copy_from_slice(&bytes[pos..pos + 8]);
+    u64::from_le_bytes(value)
+}
+
+/// Checks an input of `size` bytes against the limits and rewinds it
+pub(crate) fn check<R: Read + Seek>(
+    limits: Option<ArchiveLimits>,
+    mut input: R,
+    size: u64,
+) -> io::Result<()> {
+    let mut check = match ArchiveCheck::start(limits, size) {
+        Some(check) => check,
+        None => return Ok(()),
+    };
+    let mut buffer = DataBuffer::default();
+    while let Some((offset, len)) = check.next_read() {
+        input.seek(SeekFrom::Start(offset))?;
+        let bytes = buffer.get(len);
+        input.read_exact(bytes)?;
+        check.read(bytes)?;
+    }
+    input.seek(SeekFrom::Start(0))?;
+    Ok(())
+}
@@ -1,112 +1,497 @@
+use std::{
+    mem,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use async_std::{
     fs::File,
-    io::{self, ReadExt, WriteExt},
+    io::{self, BufReadExt, BufReader, BufWriter, ReadExt, SeekExt, WriteExt},
     net::{TcpStream, ToSocketAddrs},
-    path::Path,
+    path::{Path, PathBuf},
     stream::{Stream, StreamExt},
 };
 
 #[cfg(unix)]
 use async_std::os::unix::net::UnixStream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "cache")]
+use sha2::{Digest, Sha256};
 
-use super::{IoResult, DEFAULT_CHUNK_SIZE, END_OF_STREAM, INSTREAM, PING, PONG, SHUTDOWN, VERSION};
+use super::{
+    archive::ArchiveCheck,
+    concurrency,
+    context::{self, Addresses, ResultExt},
+    database,
+    dedup::Duplicates,
+    directory, error,
+    health::EICAR,
+    latency::LatencySamples,
+    outcome,
+    path_scan::path_command,
+    pipeline::Pipeline,
+    protocol::{Exchange, Frame, Instream, ResponseReader},
+    report::ReportTimer,
+    response,
+    spool::{self, SpoolPath},
+    stats::StatsCounters,
+    verdict,
+    wipe::DataBuffer,
+    wire, ClamdError, ClientStats, CommittedScan, CrossCheck, DetectionSink, DirectoryScanOptions,
+    ErrorPhase, FailurePolicy, HealthCheckOptions, HealthReport, IoResult, IpFamily, LatencyStats,
+    PathCommand, ResponseBuffer, ScanDecision, ScanOptions, ScanOutcome, ScanResponse, ScanResults,
+    ServerStats, VerdictPolicy, VersionInfo, END, END_OF_STREAM, IDSESSION, INSTREAM, PING, PONG,
+    RELOAD, SHUTDOWN, STATS, VERSION,
+};
+
+#[cfg(feature = "audit")]
+use super::{audit::AuditScan, AuditLog};
+#[cfg(feature = "cache")]
+use super::{
+    cache::{self, CachedScan, ScanCache},
+    DEFAULT_CHUNK_SIZE,
+};
 
 async fn send_command<RW: ReadExt + WriteExt + Unpin>(
     mut stream: RW,
     command: &[u8],
     expected_response_length: Option<usize>,
 ) -> IoResult {
-    stream.write_all(command).await?;
-    stream.flush().await?;
+    let command = Exchange::new(command);
+    stream
+        .write_all(command.bytes())
+        .await
+        .phase(ErrorPhase::Command)?;
+    stream.flush().await.phase(ErrorPhase::Command)?;
+    command.sent();
 
-    let mut response = match expected_response_length {
-        Some(len) => Vec::with_capacity(len),
-        None => Vec::new(),
-    };
+    let mut response = Vec::new();
+    read_response(
+        stream,
+        &mut response,
+        &ResponseBuffer::expecting(expected_response_length),
+    )
+    .await
+    .phase(ErrorPhase::Response)?;
+    command.received(&response);
+    Ok(response)
+}
+
+/// Reads the response of ClamAV into `response`, which is cleared first
+async fn read_response<R: ReadExt + Unpin>(
+    mut stream: R,
+    response: &mut Vec<u8>,
+    buffer: &ResponseBuffer,
+) -> io::Result<()> {
+    let mut reader = ResponseReader::new(response, buffer);
+    loop {
+        let read = stream.read(reader.unfilled()).await;
+        if reader.advance(read)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Writes a length-prefixed chunk, combining the 4-byte header and the payload
+/// into vectored writes
+async fn write_chunk<W: WriteExt + Unpin>(stream: &mut W, chunk: &[u8]) -> io::Result<()> {
+    for mut frame in Frame::split(chunk) {
+        while !frame.is_written() {
+            frame.advance(stream.write_vectored(&frame.remaining()).await)?;
+        }
+    }
+    Ok(())
+}
+
+/// Guards a stream to ClamAV while data is streamed to it
+///
+/// If the scan is dropped, e.g. because it lost a race against a timeout, or
+/// fails before the stream was terminated, the stream is terminated with a
+/// zero-length chunk, so that ClamAV stops waiting for the rest of the stream.
+/// The streams of async-std cannot shut down their write half, unlike those of
+/// Tokio. The data is written through the write buffer of the scan options.
+struct StreamGuard<'a, W: WriteExt + Unpin> {
+    stream: BufWriter<&'a mut W>,
+    in_chunk: bool,
+    streamed: bool,
+}
+
+impl<'a, W: WriteExt + Unpin> StreamGuard<'a, W> {
+    fn new(stream: &'a mut W, options: &ScanOptions) -> Self {
+        StreamGuard {
+            stream: BufWriter::with_capacity(options.write_buffer_size_or_default(), stream),
+            in_chunk: false,
+            streamed: false,
+        }
+    }
+
+    async fn write_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.in_chunk = true;
+        write_chunk(&mut self.stream, chunk)
+            .await
+            .phase(ErrorPhase::Stream)?;
+        self.in_chunk = false;
+        Ok(())
+    }
+
+    /// Writes the end of the stream, after which the connection is left open
+    /// for the response
+    async fn finish(mut self) -> io::Result<()> {
+        self.in_chunk = true;
+        self.stream
+            .write_all(END_OF_STREAM)
+            .await
+            .phase(ErrorPhase::Stream)?;
+        self.stream.flush().await.phase(ErrorPhase::Stream)?;
+        self.streamed = true;
+        Ok(())
+    }
+}
+
+impl<'a, W: WriteExt + Unpin> std::ops::Deref for StreamGuard<'a, W> {
+    type Target = BufWriter<&'a mut W>;
 
-    stream.read_to_end(&mut response).await?;
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl<W: WriteExt + Unpin> std::ops::DerefMut for StreamGuard<'_, W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.stream
+    }
+}
+
+impl<W: WriteExt + Unpin> Drop for StreamGuard<'_, W> {
+    fn drop(&mut self) {
+        // A chunk that was only partly written cannot be completed here, but
+        // the connection is closed when the stream is dropped, too
+        if self.streamed || self.in_chunk {
+            return;
+        }
+        // Writing cannot be awaited here, so the end of the stream is only
+        // written as far as the socket accepts it without waiting
+        let waker = concurrency::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut stream = std::pin::Pin::new(&mut self.stream);
+        let mut written = 0;
+        while written < END_OF_STREAM.len() {
+            match io::Write::poll_write(stream.as_mut(), &mut cx, &END_OF_STREAM[written..]) {
+                std::task::Poll::Ready(Ok(n)) if n > 0 => written += n,
+                _ => return,
+            }
+        }
+        let _ = io::Write::poll_flush(stream, &mut cx);
+    }
+}
+
+async fn scan<R: ReadExt, RW: ReadExt + WriteExt + Unpin>(
+    input: R,
+    input_size: Option<u64>,
+    options: &ScanOptions,
+    mut stream: RW,
+    mut report: ReportTimer<'_>,
+) -> IoResult {
+    let mut buffer = DataBuffer::default();
+    write_instream(
+        input,
+        input_size,
+        options,
+        &mut stream,
+        &mut buffer,
+        &mut report,
+    )
+    .await?;
+
+    let mut response = Vec::new();
+    read_response(&mut stream, &mut response, &options.response_buffer)
+        .await
+        .phase(ErrorPhase::Response)?;
+    report.finish(&response);
     Ok(response)
 }
 
-async fn scan<R: ReadExt + Unpin, RW: ReadExt + WriteExt + Unpin>(
-    mut input: R,
-    chunk_size: Option<usize>,
+/// Streams `data` to ClamAV without copying it into a chunk buffer
+async fn scan_slice<RW: ReadExt + WriteExt + Unpin>(
+    data: &[u8],
+    options: &ScanOptions,
     mut stream: RW,
+    mut report: ReportTimer<'_>,
 ) -> IoResult {
-    stream.write_all(INSTREAM).await?;
+    let mut instream = Instream::start(options, Some(data.len() as u64))?;
+    let mut guard = StreamGuard::new(&mut stream, options);
+    guard.write_all(INSTREAM).await.phase(ErrorPhase::Stream)?;
+    instream.started();
+
+    for chunk in data.chunks(instream.chunk_size()) {
+        instream.input(chunk.len())?;
+        if let Some(delay) = instream.delay(chunk.len()) {
+            guard.flush().await.phase(ErrorPhase::Stream)?;
+            async_std::task::sleep(delay).await;
+        }
+        guard.write_chunk(chunk).await?;
+        instream.chunk_sent(chunk, &mut report);
+    }
+    guard.finish().await?;
+    instream.finished(&mut report);
+
+    let mut response = Vec::new();
+    read_response(&mut stream, &mut response, &options.response_buffer)
+        .await
+        .phase(ErrorPhase::Response)?;
+    report.finish(&response);
+    Ok(response)
+}
 
-    let chunk_size = chunk_size
-        .unwrap_or(DEFAULT_CHUNK_SIZE)
-        .min(u32::MAX as usize);
+/// Scans `input_size` bytes of `input` in parts of at most `split_size` bytes,
+/// each with its own connection, and returns the response to the first part
+/// that is not clean or to the last part
+async fn scan_split<R: ReadExt, T: TransportProtocol>(
+    input: R,
+    input_size: u64,
+    split_size: u64,
+    connection: &T,
+    options: &ScanOptions,
+) -> IoResult {
+    let mut input = std::pin::pin!(input);
+    // A split size of zero would never make progress
+    options.validate()?;
+    let mut remaining = input_size;
+    loop {
+        let part_size = remaining.min(split_size);
+        remaining -= part_size;
+        let mut report = ReportTimer::start(options);
+        let stream = report.connect(connection.connect().await)?;
+        let part = input.as_mut().take(part_size);
+        let response = scan(part, Some(part_size), options, stream, report).await?;
+        if remaining == 0 || !outcome::is_clean_response(&response) {
+            return Ok(response);
+        }
+    }
+}
 
-    let mut buffer = vec![0; chunk_size];
+async fn write_instream<R: ReadExt, W: WriteExt + Unpin>(
+    input: R,
+    input_size: Option<u64>,
+    options: &ScanOptions,
+    stream: &mut W,
+    buffer: &mut DataBuffer,
+    report: &mut ReportTimer<'_>,
+) -> io::Result<()> {
+    // Pinning the input here lets callers pass readers that are not `Unpin`
+    let mut input = std::pin::pin!(input);
+    let mut instream = Instream::start(options, input_size)?;
+    let mut stream = StreamGuard::new(stream, options);
+    stream.write_all(INSTREAM).await.phase(ErrorPhase::Stream)?;
+    instream.started();
 
+    let chunk_size = instream.chunk_size();
+    let buffer = buffer.get(chunk_size);
     loop {
-        let len = input.read(&mut buffer[..]).await?;
+        let len = input.read(buffer).await.phase(ErrorPhase::Input)?;
         if len != 0 {
-            stream.write_all(&(len as u32).to_be_bytes()).await?;
-            stream.write_all(&buffer[..len]).await?;
+            instream.input(len)?;
+            if let Some(delay) = instream.delay(len) {
+                stream.flush().await.phase(ErrorPhase::Stream)?;
+                async_std::task::sleep(delay).await;
+            }
+            stream.write_chunk(&buffer[..len]).await?;
+            instream.chunk_sent(&buffer[..len], report);
         } else {
-            stream.write_all(END_OF_STREAM).await?;
-            stream.flush().await?;
-            break;
+            instream.end()?;
+            stream.finish().await?;
+            instream.finished(report);
+            return Ok(());
         }
     }
-
-    let mut response = Vec::new();
-    stream.read_to_end(&mut response).await?;
-    Ok(response)
 }
 
-async fn _scan_stream<
-    S: Stream<Item = Result<bytes::Bytes, std::io::Error>>,
-    RW: ReadExt + WriteExt + Unpin,
->(
+async fn _scan_stream<S, B, E, RW>(
     input_stream: S,
-    chunk_size: Option<usize>,
+    options: &ScanOptions,
     mut output_stream: RW,
-) -> IoResult {
-    output_stream.write_all(INSTREAM).await?;
-
-    let chunk_size = chunk_size
-        .unwrap_or(DEFAULT_CHUNK_SIZE)
-        .min(u32::MAX as usize);
+    mut report: ReportTimer<'_>,
+) -> IoResult
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    RW: ReadExt + WriteExt + Unpin,
+{
+    let mut instream = Instream::start(options, None)?;
+    let mut stream = StreamGuard::new(&mut output_stream, options);
+    stream.write_all(INSTREAM).await.phase(ErrorPhase::Stream)?;
+    instream.started();
 
     let mut input_stream = std::pin::pin!(input_stream);
-
     while let Some(bytes) = input_stream.next().await {
-        let bytes = bytes?;
+        let bytes = bytes
+            .map_err(error::stream_error)
+            .phase(ErrorPhase::Input)?;
         let bytes = bytes.as_ref();
-        for chunk in bytes.chunks(chunk_size) {
-            let len = chunk.len();
-            output_stream.write_all(&(len as u32).to_be_bytes()).await?;
-            output_stream.write_all(chunk).await?;
+        instream.input(bytes.len())?;
+        for chunk in bytes.chunks(instream.chunk_size()) {
+            if let Some(delay) = instream.delay(chunk.len()) {
+                stream.flush().await.phase(ErrorPhase::Stream)?;
+                async_std::task::sleep(delay).await;
+            }
+            stream.write_chunk(chunk).await?;
+            instream.chunk_sent(chunk, &mut report);
         }
     }
 
-    output_stream.write_all(END_OF_STREAM).await?;
-    output_stream.flush().await?;
+    instream.end()?;
+    stream.finish().await?;
+    instream.finished(&mut report);
 
     let mut response = Vec::new();
-    output_stream.read_to_end(&mut response).await?;
+    read_response(&mut output_stream, &mut response, &options.response_buffer)
+        .await
+        .phase(ErrorPhase::Response)?;
+    report.finish(&response);
     Ok(response)
 }
 
 /// Use a TCP connection to communicate with a ClamAV server
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Tcp<A: ToSocketAddrs> {
     /// The address (host and port) of the ClamAV server
     pub host_address: A,
 }
 
+/// Use a TCP connection from a local address to communicate with a ClamAV
+/// server
+///
+/// The connection is bound to `local_address` before connecting, e.g. to send
+/// it from a specific interface of a multi-homed host or from the subnet that
+/// the ACLs of ClamAV allow. A port of `0` lets the operating system choose
+/// the local port. Only addresses of the ClamAV server of the same family as
+/// the local address, IPv4 or IPv6, are tried.
+///
+/// Use the feature flag "bind" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::BoundTcp {
+///     host_address: "localhost:3310",
+///     local_address: "127.0.0.1:0".parse().unwrap(),
+/// };
+/// let response = clamav_client::async_std::ping(clamd_tcp).await.unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// # }
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg(feature = "bind")]
+pub struct BoundTcp<A: ToSocketAddrs> {
+    /// The address (host and port) of the ClamAV server
+    pub host_address: A,
+    /// The local address to bind the connection to
+    pub local_address: SocketAddr,
+}
+
+/// Use a TCP connection to communicate with a ClamAV server, preferring or
+/// requiring IPv4 or IPv6 addresses
+///
+/// See [`IpFamily`](crate::IpFamily).
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::FamilyTcp {
+///     host_address: "localhost:3310",
+///     ip_family: clamav_client::IpFamily::Ipv4Only,
+/// };
+/// let response = clamav_client::async_std::ping(clamd_tcp).await.unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// # }
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FamilyTcp<A: ToSocketAddrs> {
+    /// The address (host and port) of the ClamAV server
+    pub host_address: A,
+    /// The IP family of the addresses to connect to
+    pub ip_family: IpFamily,
+}
+
+/// Use the Unix socket of a local ClamAV server if it accepts connections, or
+/// a TCP connection to a ClamAV server otherwise
+///
+/// See [`SocketOrTcp`](crate::SocketOrTcp).
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd = clamav_client::async_std::SocketOrTcp {
+///     socket_path: "/tmp/clamd.sock",
+///     host_address: "localhost:3310",
+/// };
+/// let response = clamav_client::async_std::ping(clamd).await.unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// # }
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg(unix)]
+pub struct SocketOrTcp<P: AsRef<Path>, A: ToSocketAddrs> {
+    /// The socket file path of the local ClamAV server
+    pub socket_path: P,
+    /// The address (host and port) of the ClamAV server to fall back to
+    pub host_address: A,
+}
+
+/// The stream of a [`SocketOrTcp`] connection
+#[derive(Debug)]
+#[cfg(unix)]
+pub enum SocketOrTcpStream {
+    /// A connection to the Unix socket
+    Socket(UnixStream),
+    /// A connection to the TCP address
+    Tcp(TcpStream),
+}
+
 /// Use a Unix socket connection to communicate with a ClamAV server
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg(unix)]
 pub struct Socket<P: AsRef<Path>> {
     /// The socket file path of the ClamAV server
     pub socket_path: P,
 }
 
+/// Converts the blocking [`Tcp`](crate::Tcp), e.g. to construct transports in
+/// configuration code independently of the runtime
+impl<A: ToSocketAddrs + std::net::ToSocketAddrs> From<crate::Tcp<A>> for Tcp<A> {
+    fn from(transport: crate::Tcp<A>) -> Self {
+        Tcp {
+            host_address: transport.host_address,
+        }
+    }
+}
+
+/// Converts the blocking [`Socket`](crate::Socket), e.g. to construct
+/// transports in configuration code independently of the runtime
+#[cfg(unix)]
+impl<P: AsRef<Path> + AsRef<std::path::Path>> From<crate::Socket<P>> for Socket<P> {
+    fn from(transport: crate::Socket<P>) -> Self {
+        Socket {
+            socket_path: transport.socket_path,
+        }
+    }
+}
+
 /// The communication protocol to use
 pub trait TransportProtocol {
     /// Bidirectional stream
@@ -114,13 +499,54 @@ pub trait TransportProtocol {
 
     /// Converts the protocol instance into the corresponding stream
     fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>>;
+
+    /// Returns `true` if ClamAV runs on the same host and can read its files,
+    /// which is assumed for Unix sockets
+    ///
+    /// The default implementation returns `false`.
+    fn is_local(&self) -> bool {
+        false
+    }
 }
 
 impl<A: ToSocketAddrs> TransportProtocol for Tcp<A> {
     type Stream = TcpStream;
 
-    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
-        TcpStream::connect(&self.host_address)
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        let addresses = context::resolved(self.host_address.to_socket_addrs().await)?;
+        TcpStream::connect(&addresses[..])
+            .await
+            .phase_of(ErrorPhase::Connect, Addresses(&addresses))
+    }
+}
+
+#[cfg(feature = "bind")]
+impl<A: ToSocketAddrs> TransportProtocol for BoundTcp<A> {
+    type Stream = TcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        let addresses: Vec<SocketAddr> = self.host_address.to_socket_addrs().await?.collect();
+        let local_address = self.local_address;
+        // socket2 only connects blocking sockets
+        let stream = async_std::task::spawn_blocking(move || {
+            super::bind::connect_bound(addresses, local_address, None)
+        })
+        .await?;
+        Ok(TcpStream::from(stream))
+    }
+}
+
+impl<A: ToSocketAddrs> TransportProtocol for FamilyTcp<A> {
+    type Stream = TcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        let addresses = self
+            .ip_family
+            .apply(self.host_address.to_socket_addrs().await?);
+        if addresses.is_empty() {
+            return Err(IpFamily::no_addresses());
+        }
+        TcpStream::connect(&addresses[..]).await
     }
 }
 
@@ -128,119 +554,1038 @@ impl<A: ToSocketAddrs> TransportProtocol for Tcp<A> {
 impl<P: AsRef<Path>> TransportProtocol for Socket<P> {
     type Stream = UnixStream;
 
-    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
+    async fn connect(&self) -> io::Result<Self::Stream> {
         UnixStream::connect(&self.socket_path)
+            .await
+            .phase_of(ErrorPhase::Connect, self.socket_path.as_ref().display())
+    }
+
+    fn is_local(&self) -> bool {
+        true
     }
 }
 
-/// Sends a ping request to ClamAV
-///
-/// This function establishes a connection to a ClamAV server and sends the PING
-/// command to it. If the server is available, it responds with [`PONG`].
+#[cfg(unix)]
+impl<P: AsRef<Path>, A: ToSocketAddrs> TransportProtocol for SocketOrTcp<P, A> {
+    type Stream = SocketOrTcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        match UnixStream::connect(&self.socket_path).await {
+            Ok(stream) => Ok(SocketOrTcpStream::Socket(stream)),
+            Err(err) if super::fallback::falls_back(&err) => TcpStream::connect(&self.host_address)
+                .await
+                .map(SocketOrTcpStream::Tcp),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl io::Read for SocketOrTcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            SocketOrTcpStream::Socket(stream) => Pin::new(stream).poll_read(cx, buf),
+            SocketOrTcpStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl io::Write for SocketOrTcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            SocketOrTcpStream::Socket(stream) => Pin::new(stream).poll_write(cx, buf),
+            SocketOrTcpStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SocketOrTcpStream::Socket(stream) => Pin::new(stream).poll_flush(cx),
+            SocketOrTcpStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SocketOrTcpStream::Socket(stream) => Pin::new(stream).poll_close(cx),
+            SocketOrTcpStream::Tcp(stream) => Pin::new(stream).poll_close(cx),
+        }
+    }
+}
+
+impl<T: TransportProtocol + ?Sized> TransportProtocol for &T {
+    type Stream = T::Stream;
+
+    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
+        (**self).connect()
+    }
+
+    fn is_local(&self) -> bool {
+        (**self).is_local()
+    }
+}
+
+/// Resolves host names into socket addresses for [`ResolvingTcp`]
 ///
-/// # Arguments
+/// Implement this trait to control how the host name of a ClamAV server is
+/// resolved, e.g. to use a specific name server, a cache or a fixed address in
+/// tests.
 ///
-/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// # Example
 ///
-/// # Returns
+/// ```
+/// use std::{io, net::SocketAddr};
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// struct Localhost;
 ///
-/// # Example
+/// impl clamav_client::async_std::Resolve for Localhost {
+///     async fn resolve(&self, _host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+///         Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+///     }
+/// }
 ///
-/// ```
 /// # #[async_std::main]
 /// # async fn main() {
-/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
-/// let clamd_available = match clamav_client::async_std::ping(clamd_tcp).await {
-///     Ok(ping_response) => ping_response == clamav_client::PONG,
-///     Err(_) => false,
-/// };
-/// # assert!(clamd_available);
+/// let clamd_tcp = clamav_client::async_std::ResolvingTcp::new("clamd.internal", 3310, Localhost);
+/// let response = clamav_client::async_std::ping(clamd_tcp).await.unwrap();
+/// assert_eq!(&response, clamav_client::PONG);
 /// # }
 /// ```
 ///
-pub async fn ping<T: TransportProtocol>(connection: T) -> IoResult {
-    let stream = connection.connect().await?;
-    send_command(stream, PING, Some(PONG.len())).await
+pub trait Resolve {
+    /// Returns the socket addresses of `host` with `port`, in the order in
+    /// which they are tried
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl std::future::Future<Output = io::Result<Vec<SocketAddr>>>;
 }
 
-/// Gets the version number from ClamAV
-///
-/// This function establishes a connection to a ClamAV server and sends the
-/// VERSION command to it. If the server is available, it responds with its
-/// version number.
-///
-/// # Arguments
-///
-/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+impl<R: Resolve + ?Sized> Resolve for &R {
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl std::future::Future<Output = io::Result<Vec<SocketAddr>>> {
+        (**self).resolve(host, port)
+    }
+}
+
+/// Resolves host names with the resolver of the operating system, like
+/// [`Tcp`] does, on the blocking thread pool of async-std
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs().await?.collect())
+    }
+}
+
+impl<R: Resolve> Resolve for crate::CachingResolver<R> {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if let Some(addresses) = self.cached(host, port) {
+            return Ok(addresses);
+        }
+        let addresses = self.resolver.resolve(host, port).await?;
+        self.store(host, port, &addresses);
+        Ok(addresses)
+    }
+}
+
+impl<R: Resolve> Resolve for crate::TimeoutResolver<R> {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        async_std::future::timeout(self.timeout, self.resolver.resolve(host, port))
+            .await
+            .unwrap_or_else(|_| self.timed_out(host))
+    }
+}
+
+/// Use a TCP connection to communicate with a ClamAV server whose host name is
+/// resolved with a [`Resolve`] implementation
 ///
-/// # Returns
+/// The host name is resolved again for each connection, and the addresses are
+/// tried in order until a connection succeeds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvingTcp<H: AsRef<str>, R: Resolve> {
+    /// The host name or IP address of the ClamAV server
+    pub host: H,
+    /// The port of the ClamAV server
+    pub port: u16,
+    /// The resolver of the host name
+    pub resolver: R,
+}
+
+impl<H: AsRef<str>, R: Resolve> ResolvingTcp<H, R> {
+    /// Creates a TCP connection to `host` and `port` that resolves `host` with
+    /// `resolver`
+    pub fn new(host: H, port: u16, resolver: R) -> Self {
+        ResolvingTcp {
+            host,
+            port,
+            resolver,
+        }
+    }
+}
+
+impl<H: AsRef<str>, R: Resolve> TransportProtocol for ResolvingTcp<H, R> {
+    type Stream = TcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        let addresses = self.resolver.resolve(self.host.as_ref(), self.port).await?;
+        TcpStream::connect(&addresses[..]).await
+    }
+}
+
+/// A transport whose concrete type is erased (see
+/// [`BoxedTransport`](crate::BoxedTransport))
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// The TCP and Unix socket transports of this crate convert into a
+/// `BoxedTransport` with [`From`]. Other transports, e.g. a TLS wrapper, are
+/// erased with [`from_fn`](Self::from_fn), since the futures of a generic
+/// transport cannot be shown to be [`Send`], which is needed to scan from
+/// spawned tasks. The transport is cheap to clone, since clones share the
+/// wrapped transport.
 ///
 /// # Example
 ///
 /// ```
 /// # #[async_std::main]
 /// # async fn main() {
-/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
-/// let version = clamav_client::async_std::get_version(clamd_tcp).await.unwrap();
-/// # assert!(version.starts_with(b"ClamAV"));
+/// use clamav_client::async_std::BoxedTransport;
+///
+/// let transport = if let Ok(socket_path) = std::env::var("CLAMD_SOCKET") {
+///     BoxedTransport::from(clamav_client::async_std::Socket { socket_path })
+/// } else {
+///     BoxedTransport::from_fn(|| async {
+///         // E.g. wrap the stream in TLS here
+///         async_std::net::TcpStream::connect("localhost:3310").await
+///     })
+/// };
+/// let client = clamav_client::async_std::Client::new(transport);
+/// # assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
 /// # }
 /// ```
 ///
-pub async fn get_version<T: TransportProtocol>(connection: T) -> IoResult {
-    let stream = connection.connect().await?;
-    send_command(stream, VERSION, None).await
+#[derive(Clone)]
+pub struct BoxedTransport {
+    connect: Arc<dyn Fn() -> BoxedConnect + Send + Sync>,
+    is_local: bool,
 }
 
-/// Scans a file for viruses
-///
-/// This function reads data from a file located at the specified `file_path`
-/// and streams it to a ClamAV server for scanning.
-///
-/// # Arguments
-///
-/// * `file_path`: The path to the file to be scanned
-/// * `connection`: The connection type to use - either TCP or a Unix socket connection
-/// * `chunk_size`: An optional chunk size for reading data. If [`None`], a default chunk size is used
-///
-/// # Returns
-///
-/// An [`IoResult`] containing the server's response as a vector of bytes
-///
-pub async fn scan_file<P: AsRef<Path>, T: TransportProtocol>(
-    file_path: P,
-    connection: T,
-    chunk_size: Option<usize>,
-) -> IoResult {
-    let file = File::open(file_path).await?;
-    let stream = connection.connect().await?;
-    scan(file, chunk_size, stream).await
-}
+type BoxedConnect = Pin<Box<dyn std::future::Future<Output = io::Result<BoxedStream>> + Send>>;
 
-/// Scans a data buffer for viruses
+impl BoxedTransport {
+    /// Creates a transport that connects with the future returned by
+    /// `connect`
+    pub fn from_fn<F, C, S>(connect: F) -> Self
+    where
+        F: Fn() -> C + Send + Sync + 'static,
+        C: std::future::Future<Output = io::Result<S>> + Send + 'static,
+        S: io::Read + io::Write + Send + Unpin + 'static,
+    {
+        BoxedTransport {
+            connect: Arc::new(move || {
+                let connecting = connect();
+                Box::pin(async move { connecting.await.map(BoxedStream::new) })
+            }),
+            is_local: false,
+        }
+    }
+
+    /// Sets whether ClamAV runs on the same host and can read its files (see
+    /// [`TransportProtocol::is_local`])
+    pub fn local(mut self, is_local: bool) -> Self {
+        self.is_local = is_local;
+        self
+    }
+}
+
+impl std::fmt::Debug for BoxedTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxedTransport")
+            .field("is_local", &self.is_local)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TransportProtocol for BoxedTransport {
+    type Stream = BoxedStream;
+
+    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
+        (self.connect)()
+    }
+
+    fn is_local(&self) -> bool {
+        self.is_local
+    }
+}
+
+impl<A: ToSocketAddrs + Send + Sync + 'static> From<Tcp<A>> for BoxedTransport
+where
+    A::Iter: Send,
+{
+    fn from(transport: Tcp<A>) -> Self {
+        let transport = Arc::new(transport);
+        BoxedTransport::from_fn(move || {
+            let transport = Arc::clone(&transport);
+            async move { transport.connect().await }
+        })
+    }
+}
+
+#[cfg(unix)]
+impl<P: AsRef<Path> + Send + Sync + 'static> From<Socket<P>> for BoxedTransport {
+    fn from(transport: Socket<P>) -> Self {
+        let transport = Arc::new(transport);
+        BoxedTransport::from_fn(move || {
+            let transport = Arc::clone(&transport);
+            async move { transport.connect().await }
+        })
+        .local(true)
+    }
+}
+
+/// The stream of a [`BoxedTransport`], whose concrete type is erased
+pub struct BoxedStream {
+    stream: Pin<Box<dyn ReadWrite + Send>>,
+}
+
+impl BoxedStream {
+    /// Erases the type of `stream`
+    pub fn new<S: io::Read + io::Write + Send + 'static>(stream: S) -> Self {
+        BoxedStream {
+            stream: Box::pin(stream),
+        }
+    }
+}
+
+impl std::fmt::Debug for BoxedStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxedStream").finish_non_exhaustive()
+    }
+}
+
+trait ReadWrite: io::Read + io::Write {}
+
+impl<S: io::Read + io::Write> ReadWrite for S {}
+
+impl io::Read for BoxedStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().stream.as_mut().poll_read(cx, buf)
+    }
+}
+
+impl io::Write for BoxedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().stream.as_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().stream.as_mut().poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().stream.as_mut().poll_close(cx)
+    }
+}
+
+/// Sends a ping request to ClamAV
+///
+/// This function establishes a connection to a ClamAV server and sends the PING
+/// command to it. If the server is available, it responds with [`PONG`].
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let clamd_available = match clamav_client::async_std::ping(clamd_tcp).await {
+///     Ok(ping_response) => clamav_client::is_pong(&ping_response),
+///     Err(_) => false,
+/// };
+/// # assert!(clamd_available);
+/// # }
+/// ```
+///
+pub async fn ping<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect().await?;
+    send_command(stream, PING, Some(PONG.len())).await
+}
+
+/// Checks that ClamAV responds to a ping request with [`PONG`]
+///
+/// A response that differs from [`PONG`] fails with
+/// [`ClamdError::InvalidResponse`].
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// clamav_client::async_std::ping_ok(clamd_tcp).await.unwrap();
+/// # }
+/// ```
+///
+pub async fn ping_ok<T: TransportProtocol>(connection: T) -> Result<(), ClamdError> {
+    response::expect_pong(&ping(connection).await?)
+}
+
+/// Checks whether ClamAV is available, i.e. whether it responds to a ping
+/// request with [`PONG`]
+///
+/// This function works like [`ping_ok`], but returns `false` instead of an
+/// error, e.g. if the connection fails, for readiness checks that only need a
+/// yes or no.
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// if !clamav_client::async_std::is_available(clamd_tcp).await {
+///     eprintln!("Cannot ping clamd at {}", clamd_tcp.host_address);
+/// }
+/// # assert!(clamav_client::async_std::is_available(clamd_tcp).await);
+/// # }
+/// ```
+///
+pub async fn is_available<T: TransportProtocol>(connection: T) -> bool {
+    ping_ok(connection).await.is_ok()
+}
+
+/// Gets the version number from ClamAV
+///
+/// This function establishes a connection to a ClamAV server and sends the
+/// VERSION command to it. If the server is available, it responds with its
+/// version number.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let version = clamav_client::async_std::get_version(clamd_tcp).await.unwrap();
+/// # assert!(version.starts_with(b"ClamAV"));
+/// # }
+/// ```
+///
+pub async fn get_version<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect().await?;
+    send_command(stream, VERSION, None).await
+}
+
+/// Gets the version of ClamAV and of its signature databases
+///
+/// This function works like [`get_version`], but parses the response into a
+/// [`VersionInfo`].
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let version = clamav_client::async_std::version(clamd_tcp).await.unwrap();
+/// println!("ClamAV {}", version.clamav_version);
+/// # }
+/// ```
+///
+pub async fn version<T: TransportProtocol>(connection: T) -> Result<VersionInfo, ClamdError> {
+    VersionInfo::from_response(&get_version(connection).await?)
+}
+
+/// Reloads the virus databases of ClamAV
+///
+/// This function establishes a connection to a ClamAV server and sends the
+/// RELOAD command to it. The server responds with `RELOADING` and reloads its
+/// virus databases in the background.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::async_std::reload(clamd_tcp).await.unwrap();
+/// # assert_eq!(&response, b"RELOADING\0");
+/// # }
+/// ```
+///
+pub async fn reload<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect().await?;
+    send_command(stream, RELOAD, None).await
+}
+
+/// Gets statistics about the scan queue, threads and memory usage of ClamAV
+///
+/// This function establishes a connection to a ClamAV server and sends the
+/// STATS command to it. The server responds with a multi-line report that
+/// ends with `END`.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::async_std::get_stats(clamd_tcp).await.unwrap();
+/// # assert!(stats.starts_with(b"POOLS:"));
+/// # }
+/// ```
+///
+pub async fn get_stats<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect().await?;
+    send_command(stream, STATS, None).await
+}
+
+/// Gets the queue length, threads and memory usage of ClamAV
+///
+/// This function works like [`get_stats`], but parses the response into
+/// [`ServerStats`].
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::async_std::server_stats(clamd_tcp).await.unwrap();
+/// println!("{} of {} threads busy", stats.busy_threads(), stats.max_threads);
+/// # }
+/// ```
+///
+pub async fn server_stats<T: TransportProtocol>(connection: T) -> Result<ServerStats, ClamdError> {
+    ServerStats::from_response(&get_stats(connection).await?)
+}
+
+/// Gets the age of the signature databases of ClamAV
+///
+/// This function sends the VERSION command to ClamAV and returns the time
+/// elapsed since the date of the databases in the response (see
+/// [`database_date`](crate::database_date)). A date in the future results in
+/// an age of zero.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// The age of the databases, or a [`ClamdError`] if the connection fails or the
+/// response has no date
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let age = clamav_client::async_std::database_age(clamd_tcp).await.unwrap();
+/// println!("The signatures are {} hours old", age.as_secs() / 3600);
+/// # }
+/// ```
+///
+pub async fn database_age<T: TransportProtocol>(connection: T) -> Result<Duration, ClamdError> {
+    let response = get_version(connection).await?;
+    database::age_of(&response)
+}
+
+/// Checks whether the signature databases of ClamAV are older than `max_age`
+/// (see [`is_database_stale`](crate::is_database_stale))
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `max_age`: The maximum age of up-to-date databases
+///
+/// # Returns
+///
+/// `true` if the databases are older than `max_age`, or a [`ClamdError`] if the
+/// age cannot be determined (see [`database_age`])
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// use std::time::Duration;
+///
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let max_age = Duration::from_secs(48 * 60 * 60);
+/// if clamav_client::async_std::is_database_stale(clamd_tcp, max_age).await.unwrap() {
+///     eprintln!("The signatures of ClamAV are outdated");
+/// }
+/// # }
+/// ```
+///
+pub async fn is_database_stale<T: TransportProtocol>(
+    connection: T,
+    max_age: Duration,
+) -> Result<bool, ClamdError> {
+    database_age(connection).await.map(|age| age > max_age)
+}
+
+/// Checks whether ClamAV is healthy, e.g. for a readiness probe
+///
+/// This function pings ClamAV, gets its version to determine the age of the
+/// signature databases and, if enabled in the options, scans the EICAR test
+/// file, each over its own connection (see
+/// [`health_check`](crate::health_check)).
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::HealthCheckOptions::new().self_scan(true);
+/// let report = clamav_client::async_std::health_check(clamd_tcp, &options).await;
+/// if !report.is_ready() {
+///     eprintln!("ClamAV is not ready: {:?}", report.problems);
+/// }
+/// # assert!(report.is_ready());
+/// # }
+/// ```
+///
+pub async fn health_check<T: TransportProtocol>(
+    connection: T,
+    options: &HealthCheckOptions,
+) -> HealthReport {
+    let mut report = HealthReport::new();
+    let start = Instant::now();
+    let response = ping(&connection).await;
+    if !report.ping(options, response, start.elapsed()) {
+        return report;
+    }
+    report.version(options, get_version(&connection).await);
+    if options.self_scan {
+        report.self_scan(scan_buffer(EICAR, &connection, None).await);
+    }
+    report
+}
+
+/// Measures the latency of ClamAV by pinging it `samples` times, e.g. to pick
+/// the closest of several servers at startup (see
+/// [`measure_latency`](crate::measure_latency))
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::async_std::measure_latency(clamd_tcp, 10).await.unwrap();
+/// println!("{:?} on average, {:?} at the 95th percentile", stats.avg, stats.p95);
+/// # assert_eq!(stats.samples, 10);
+/// # }
+/// ```
+///
+pub async fn measure_latency<T: TransportProtocol>(
+    connection: T,
+    samples: usize,
+) -> Result<LatencyStats, ClamdError> {
+    let mut measured = LatencySamples::new(samples.max(1));
+    for _ in 0..samples.max(1) {
+        let start = Instant::now();
+        let stream = connection.connect().await?;
+        let connected = Instant::now();
+        let response = send_command(stream, PING, Some(PONG.len())).await?;
+        measured.push(connected - start, connected.elapsed(), &response)?;
+    }
+    Ok(measured.stats())
+}
+
+/// Scans a data buffer on several ClamAV servers at the same time and combines
+/// their verdicts (see
+/// [`scan_buffer_cross_checked`](crate::scan_buffer_cross_checked))
+///
+/// The scans run concurrently within the current task.
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let endpoints = [
+///     clamav_client::async_std::Tcp{ host_address: "localhost:3310" },
+///     clamav_client::async_std::Tcp{ host_address: "127.0.0.1:3310" },
+/// ];
+/// let check = clamav_client::async_std::scan_buffer_cross_checked(b"clean data", endpoints, None).await;
+/// assert!(check.all_clean());
+/// # }
+/// ```
+///
+pub async fn scan_buffer_cross_checked<B, E, T, O>(
+    buffer: B,
+    endpoints: E,
+    options: O,
+) -> CrossCheck
+where
+    B: AsRef<[u8]>,
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let buffer = buffer.as_ref();
+    let options = options.into();
+    let endpoints: Vec<T> = endpoints.into_iter().collect();
+    let options = &options;
+    let scans = endpoints.iter().map(|connection| async move {
+        scan_buffer(buffer, connection, options)
+            .await
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response))
+    });
+    CrossCheck::new(concurrency::join_bounded(scans, endpoints.len()).await)
+}
+
+/// Scans a file on several ClamAV servers at the same time and combines their
+/// verdicts (see [`scan_file_cross_checked`](crate::scan_file_cross_checked))
+///
+/// The file is opened once per endpoint, and the scans run concurrently within
+/// the current task.
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let endpoints = [
+///     clamav_client::async_std::Tcp{ host_address: "localhost:3310" },
+///     clamav_client::async_std::Tcp{ host_address: "127.0.0.1:3310" },
+/// ];
+/// let check =
+///     clamav_client::async_std::scan_file_cross_checked("tests/data/eicar.txt", endpoints, None).await;
+/// assert!(check.all_infected());
+/// # }
+/// ```
+///
+pub async fn scan_file_cross_checked<P, E, T, O>(
+    file_path: P,
+    endpoints: E,
+    options: O,
+) -> CrossCheck
+where
+    P: AsRef<Path>,
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let file_path = file_path.as_ref();
+    let options = options.into();
+    let endpoints: Vec<T> = endpoints.into_iter().collect();
+    let options = &options;
+    let scans = endpoints.iter().map(|connection| async move {
+        scan_file(file_path, connection, options)
+            .await
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response))
+    });
+    CrossCheck::new(concurrency::join_bounded(scans, endpoints.len()).await)
+}
+
+/// Scans a file for viruses
+///
+/// This function reads data from a file located at the specified `file_path`
+/// and streams it to a ClamAV server for scanning.
+///
+/// # Arguments
+///
+/// * `file_path`: The path to the file to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the file exceeds the configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded)
+/// without connecting to the server, unless it is scanned by its path (see
+/// [`ScanOptions::path_fallback`]).
+///
+pub async fn scan_file<P: AsRef<Path>, T: TransportProtocol, O: Into<ScanOptions>>(
+    file_path: P,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    let file_path = file_path.as_ref();
+    let mut file = File::open(file_path)
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?;
+    let file_size = file
+        .metadata()
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?
+        .len();
+    if let Err(err) = options.check_input_size(file_size) {
+        // An empty file is rejected rather than scanned by its path
+        if file_size > 0 && options.path_fallback && connection.is_local() {
+            let path: std::path::PathBuf = async_std::fs::canonicalize(file_path)
+                .await
+                .phase_of(ErrorPhase::Input, file_path.display())?
+                .into();
+            let command = path_command(PathCommand::Scan, &path)?;
+            let stream = connection.connect().await?;
+            return send_command(stream, &command, None).await;
+        }
+        return Err(err);
+    }
+    check_archive(&mut file, file_size, &options).await?;
+    if let Some(split_size) = options.split_size.filter(|&size| file_size > size) {
+        return scan_split(file, file_size, split_size, &connection, &options).await;
+    }
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+    scan(file, Some(file_size), &options, stream, report).await
+}
+
+/// Scans a region of a file for viruses
+///
+/// This function streams the `length` bytes of the file at `file_path` that
+/// start at `offset` to a ClamAV server, as if they were a file of their own,
+/// e.g. to scan an object embedded in a container without extracting it
+/// first.
+///
+/// # Arguments
+///
+/// * `file_path`: The path to the file that contains the region
+/// * `offset`: The offset of the first byte of the region
+/// * `length`: The number of bytes of the region
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the region extends beyond the end of the file, the scan fails with an
+/// [`io::Error`] of kind [`InvalidInput`](io::ErrorKind::InvalidInput), and if
+/// it exceeds the configured maximum stream size, with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded),
+/// both without connecting to the server.
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::async_std::scan_file_range("README.md", 0, 64, clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// # }
+/// ```
+///
+pub async fn scan_file_range<P: AsRef<Path>, T: TransportProtocol, O: Into<ScanOptions>>(
+    file_path: P,
+    offset: u64,
+    length: u64,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    let file_path = file_path.as_ref();
+    let mut file = File::open(file_path)
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?;
+    let file_size = file
+        .metadata()
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?
+        .len();
+    super::check_file_range(file_size, offset, length)?;
+    options.check_input_size(length)?;
+    file.seek(io::SeekFrom::Start(offset))
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?;
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+    scan(file.take(length), Some(length), &options, stream, report).await
+}
+
+/// Checks a file of `file_size` bytes against the archive limits of the
+/// options and rewinds it
+async fn check_archive(file: &mut File, file_size: u64, options: &ScanOptions) -> io::Result<()> {
+    let mut check = match ArchiveCheck::start(options.archive_limits, file_size) {
+        Some(check) => check,
+        None => return Ok(()),
+    };
+    let mut buffer = DataBuffer::default();
+    while let Some((offset, len)) = check.next_read() {
+        file.seek(io::SeekFrom::Start(offset)).await?;
+        let bytes = buffer.get(len);
+        file.read_exact(bytes).await?;
+        check.read(bytes)?;
+    }
+    file.seek(io::SeekFrom::Start(0)).await?;
+    Ok(())
+}
+
+/// Scans a data buffer for viruses
+///
+/// This function streams the provided `buffer` data to a ClamAV server. The
+/// chunks are written directly from `buffer`, without copying them into a
+/// chunk buffer.
+///
+/// # Arguments
+///
+/// * `buffer`: The data to be scanned, e.g. a `&[u8]`, `Vec<u8>`, `String` or `Bytes`
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the buffer exceeds the configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded)
+/// without connecting to the server.
+///
+pub async fn scan_buffer<B: AsRef<[u8]>, T: TransportProtocol, O: Into<ScanOptions>>(
+    buffer: B,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let buffer = buffer.as_ref();
+    let options = options.into();
+    let buffer_size = buffer.len() as u64;
+    options.check_input_size(buffer_size)?;
+    options.check_archive(std::io::Cursor::new(buffer), buffer_size)?;
+    if let Some(split_size) = options.split_size.filter(|&size| buffer_size > size) {
+        return scan_split(buffer, buffer_size, split_size, &connection, &options).await;
+    }
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+    scan_slice(buffer, &options, stream, report).await
+}
+
+/// Scans shared bytes for viruses
+///
+/// This function works like [`scan_buffer`], but takes the [`Bytes`](bytes::Bytes)
+/// of e.g. a request body by value, so that the scan owns a reference to the
+/// shared buffer instead of borrowing it. The chunks are written as slices of
+/// the shared buffer, so the data is never copied.
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let bytes = bytes::Bytes::from_static(b"clean data");
+/// let response = clamav_client::async_std::scan_bytes(bytes.clone(), clamd_tcp, None).await.unwrap();
+/// # assert_eq!(clamav_client::clean(&response), Ok(true));
+/// # }
+/// ```
 ///
-/// This function streams the provided `buffer` data to a ClamAV server
+pub async fn scan_bytes<T: TransportProtocol, O: Into<ScanOptions>>(
+    bytes: bytes::Bytes,
+    connection: T,
+    options: O,
+) -> IoResult {
+    scan_buffer(bytes, connection, options).await
+}
+
+/// Scans the data of a reader for viruses
+///
+/// This function streams the data read from `reader` to a ClamAV server for
+/// scanning until the reader reaches its end. The reader is pinned internally,
+/// so it does not need to be [`Unpin`].
 ///
 /// # Arguments
 ///
-/// * `buffer`: The data to be scanned
+/// * `reader`: The reader of the data to be scanned
 /// * `connection`: The connection type to use - either TCP or a Unix socket connection
-/// * `chunk_size`: An optional chunk size for reading data. If [`None`], a default chunk size is used
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
 ///
 /// # Returns
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the data exceeds the configured maximum stream size, the scan is aborted
+/// with [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded).
+///
+/// # Example
 ///
-pub async fn scan_buffer<T: TransportProtocol>(
-    buffer: &[u8],
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let file = async_std::fs::File::open("README.md").await.unwrap();
+/// let response = clamav_client::async_std::scan_reader(file, clamd_tcp, None).await.unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// # }
+/// ```
+///
+pub async fn scan_reader<R: io::Read, T: TransportProtocol, O: Into<ScanOptions>>(
+    reader: R,
     connection: T,
-    chunk_size: Option<usize>,
+    options: O,
 ) -> IoResult {
-    let stream = connection.connect().await?;
-    scan(buffer, chunk_size, stream).await
+    let options = options.into();
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+    scan(reader, None, &options, stream, report).await
 }
 
 /// Scans a stream for viruses
@@ -249,26 +1594,214 @@ pub async fn scan_buffer<T: TransportProtocol>(
 ///
 /// # Arguments
 ///
-/// * `input_stream`: The stream to be scanned
+/// * `input_stream`: The stream to be scanned, e.g. a request body of `axum`, `hyper` or `reqwest`, with items of any buffer type, e.g. `Bytes`, `Vec<u8>` or `&[u8]`, and any error type that converts into `Box<dyn Error + Send + Sync>`
 /// * `connection`: The connection type to use - either TCP or a Unix socket connection
-/// * `chunk_size`: An optional chunk size for reading data. If [`None`], a default chunk size is used
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
 ///
 /// # Returns
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the stream exceeds the configured maximum stream size, the scan is aborted
+/// with [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded).
+/// If the stream fails, the scan is aborted with its error, which is wrapped
+/// in an [`io::Error`] of kind [`Other`](io::ErrorKind::Other) unless it is an
+/// [`io::Error`] or a [`ClamdError`](crate::ClamdError).
 ///
-pub async fn scan_stream<
-    S: Stream<Item = Result<bytes::Bytes, io::Error>>,
+pub async fn scan_stream<S, B, E, T, O>(input_stream: S, connection: T, options: O) -> IoResult
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
     T: TransportProtocol,
->(
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let output_stream = report.connect(connection.connect().await)?;
+    _scan_stream(input_stream, &options, output_stream, report).await
+}
+
+/// Scans a stream for viruses while writing it to a spool file, and commits
+/// the file to `destination` only if the stream is clean
+///
+/// This function writes each item of the stream to a hidden spool file in the
+/// directory of `destination` while it is streamed to ClamAV, e.g. an upload
+/// that must not be persisted before it has been scanned. If ClamAV finds no
+/// virus, or only a tolerated signature, the spool file is synced and renamed
+/// to `destination`, which replaces an existing file atomically. Otherwise, or
+/// if the scan fails or is dropped, the spool file is removed.
+///
+/// # Arguments
+///
+/// * `input_stream`: The stream to be scanned and persisted, with the item types accepted by [`scan_stream`]
+/// * `destination`: The path of the file to commit the data to
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// The [`CommittedScan`] with the outcome of the scan and the path of the
+/// committed file, if any, or a [`ClamdError`] if the stream could not be
+/// scanned or written
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let upload = futures_util::stream::iter([Ok::<_, std::io::Error>(&b"clean data"[..])]);
+/// let destination = std::env::temp_dir().join("upload.txt");
+/// let scan = clamav_client::async_std::scan_and_commit(upload, &destination, clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// assert_eq!(scan.path, Some(destination));
+/// # }
+/// ```
+///
+pub async fn scan_and_commit<S, B, E, P, T, O>(
     input_stream: S,
+    destination: P,
     connection: T,
-    chunk_size: Option<usize>,
-) -> IoResult {
-    let output_stream = connection.connect().await?;
-    _scan_stream(input_stream, chunk_size, output_stream).await
+    options: O,
+) -> Result<CommittedScan, ClamdError>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]> + Unpin,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    P: AsRef<Path>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let destination: &std::path::Path = destination.as_ref().as_ref();
+    let spool_path = SpoolPath::next_to(destination)?;
+    let mut file = async_std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(spool_path.path())
+        .await?;
+    let mut spool_error = None;
+    let spooled = SpoolStream {
+        input: std::pin::pin!(input_stream),
+        file: &mut file,
+        pending: None,
+        error: &mut spool_error,
+    };
+    let result = scan_stream(spooled, connection, options).await;
+    if let Some(err) = spool_error {
+        return Err(err.into());
+    }
+    let outcome = ScanOutcome::from_response(&result?)?;
+    if !spool::is_committable(&outcome) {
+        return Ok(CommittedScan::new(outcome, None));
+    }
+    file.sync_all().await?;
+    drop(file);
+    async_std::fs::rename(spool_path.path(), destination).await?;
+    spool_path.committed();
+    Ok(CommittedScan::new(outcome, Some(destination.to_owned())))
+}
+
+/// A stream that writes each item of its input to a spool file before passing
+/// it on
+struct SpoolStream<'a, S, B> {
+    input: Pin<&'a mut S>,
+    file: &'a mut File,
+    /// An item and how much of it has been written
+    pending: Option<(B, usize)>,
+    error: &'a mut Option<io::Error>,
+}
+
+impl<S, B, E> Stream for SpoolStream<'_, S, B>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]> + Unpin,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Item = Result<B, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((bytes, written)) = &mut this.pending {
+                while *written < bytes.as_ref().len() {
+                    let result = match io::Write::poll_write(
+                        Pin::new(&mut *this.file),
+                        cx,
+                        &bytes.as_ref()[*written..],
+                    ) {
+                        Poll::Ready(Ok(0)) => Err(io::Error::from(io::ErrorKind::WriteZero)),
+                        Poll::Ready(result) => result,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    match result {
+                        Ok(len) => *written += len,
+                        Err(err) => {
+                            // The error is returned instead of the error of the scan
+                            *this.error = Some(err);
+                            this.pending = None;
+                            return Poll::Ready(Some(Err(
+                                "the spool file could not be written".into()
+                            )));
+                        }
+                    }
+                }
+                return Poll::Ready(this.pending.take().map(|(bytes, _)| Ok(bytes)));
+            }
+            match this.input.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(bytes))) => this.pending = Some((bytes, 0)),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err.into()))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Extends streams of buffers with [`virus_scan`](ScanStreamExt::virus_scan),
+/// to scan them at the end of a chain of stream adapters
+///
+/// The trait is implemented for all streams. Its method only applies to
+/// streams whose items are results of buffers, like those of [`scan_stream`].
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// use clamav_client::async_std::ScanStreamExt;
+///
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let chunks = vec![Ok::<_, std::io::Error>(&b"clean "[..]), Ok(&b"data"[..])];
+/// let response = async_std::stream::from_iter(chunks)
+///     .virus_scan(clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// assert_eq!(clamav_client::clean(&response), Ok(true));
+/// # }
+/// ```
+///
+pub trait ScanStreamExt: Stream {
+    /// Scans the stream for viruses, like [`scan_stream`] does
+    fn virus_scan<B, E, T, O>(
+        self,
+        connection: T,
+        options: O,
+    ) -> impl std::future::Future<Output = IoResult>
+    where
+        Self: Stream<Item = Result<B, E>> + Sized,
+        B: AsRef<[u8]>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+        T: TransportProtocol,
+        O: Into<ScanOptions>,
+    {
+        scan_stream(self, connection, options)
+    }
 }
 
+impl<S: Stream + ?Sized> ScanStreamExt for S {}
+
 /// Shuts down a ClamAV server
 ///
 /// This function establishes a connection to a ClamAV server and sends the
@@ -287,3 +1820,728 @@ pub async fn shutdown<T: TransportProtocol>(connection: T) -> IoResult {
     let stream = connection.connect().await?;
     send_command(stream, SHUTDOWN, None).await
 }
+
+/// Scans multiple data buffers for viruses using a single connection
+///
+/// This function establishes one connection to a ClamAV server, starts a
+/// session (IDSESSION) and streams each buffer to the server in turn. This
+/// avoids a new connection per buffer when scanning many small buffers. With
+/// a [pipeline depth](ScanOptions::pipeline_depth) greater than one, the next
+/// buffers are streamed while responses are still outstanding.
+///
+/// # Arguments
+///
+/// * `buffers`: The data buffers to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// The [`ScanOutcome`] of each buffer, in the order of the input. If any buffer
+/// exceeds the configured maximum stream size, the batch fails with
+/// [`ClamdError::SizeLimitExceeded`] without connecting to the server.
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let buffers = [&b"clean data"[..], &b"more clean data"[..]];
+/// let outcomes = clamav_client::async_std::scan_buffers(buffers, clamd_tcp, None).await.unwrap();
+/// # assert!(outcomes.iter().all(|outcome| outcome.is_clean()));
+/// # }
+/// ```
+///
+pub async fn scan_buffers<I, B, T, O>(
+    buffers: I,
+    connection: T,
+    options: O,
+) -> Result<Vec<ScanOutcome>, ClamdError>
+where
+    I: IntoIterator<Item = B>,
+    B: AsRef<[u8]>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    // A pipeline depth of zero would wait for a response before the first
+    // buffer is sent
+    options.validate()?;
+    let buffers: Vec<B> = buffers.into_iter().collect();
+    for buffer in &buffers {
+        let buffer_size = buffer.as_ref().len() as u64;
+        options.check_input_size(buffer_size)?;
+        options.check_archive(std::io::Cursor::new(buffer.as_ref()), buffer_size)?;
+    }
+    if buffers.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut stream = BufReader::new(connection.connect().await?);
+    stream
+        .get_mut()
+        .write_all(IDSESSION)
+        .await
+        .phase(ErrorPhase::Handshake)?;
+    wire::command(IDSESSION);
+
+    let mut chunk_buffer = DataBuffer::default();
+    let mut response = Vec::new();
+    let duplicates = Duplicates::of_buffers(&buffers, options.deduplicate);
+    let mut pipeline = Pipeline::new(&options, duplicates.distinct().len());
+    for &index in duplicates.distinct() {
+        if pipeline.is_full() {
+            receive_pipelined(&mut stream, &mut response, &mut pipeline).await?;
+        }
+        let buffer = buffers[index].as_ref();
+        let buffer_size = Some(buffer.len() as u64);
+        let mut report = ReportTimer::start(&options);
+        report.connected();
+        write_instream(
+            buffer,
+            buffer_size,
+            &options,
+            stream.get_mut(),
+            &mut chunk_buffer,
+            &mut report,
+        )
+        .await?;
+        pipeline.sent(report);
+    }
+    while !pipeline.is_empty() {
+        receive_pipelined(&mut stream, &mut response, &mut pipeline).await?;
+    }
+
+    stream
+        .get_mut()
+        .write_all(END)
+        .await
+        .phase(ErrorPhase::Command)?;
+    wire::command(END);
+    stream.get_mut().flush().await.phase(ErrorPhase::Command)?;
+    Ok(duplicates.fan_out(pipeline.into_outcomes()))
+}
+
+/// Reads the next response within a session and passes it to `pipeline`
+async fn receive_pipelined<R: ReadExt + Unpin>(
+    stream: &mut BufReader<R>,
+    response: &mut Vec<u8>,
+    pipeline: &mut Pipeline<'_>,
+) -> Result<(), ClamdError> {
+    response.clear();
+    match stream.read_until(0, response).await {
+        Ok(0) => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+        result => result.map(drop),
+    }
+    .phase(ErrorPhase::Response)?;
+    pipeline.receive(response)
+}
+
+/// Scans multiple files for viruses concurrently
+///
+/// This function scans each file over its own connection, with at most
+/// `max_concurrency` scans in flight at any time.
+///
+/// # Arguments
+///
+/// * `file_paths`: The paths to the files to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `max_concurrency`: The maximum number of concurrent scans (and connections)
+///
+/// # Returns
+///
+/// The [`ScanResults`] with the path and the result of each scan, in the order
+/// of the input, and a [`ScanSummary`](crate::ScanSummary) of all scans
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let file_paths = ["README.md", "tests/data/eicar.txt"];
+/// let results = clamav_client::async_std::scan_files(file_paths, clamd_tcp, None, 4).await;
+/// for (path, result) in results {
+///     match result {
+///         Ok(outcome) if outcome.is_infected() => println!("{} is infected!", path.display()),
+///         Ok(_) => println!("No virus found in {}", path.display()),
+///         Err(err) => println!("Could not scan {}: {}", path.display(), err),
+///     }
+/// }
+/// # }
+/// ```
+///
+pub async fn scan_files<I, P, T, O>(
+    file_paths: I,
+    connection: T,
+    options: O,
+    max_concurrency: usize,
+) -> ScanResults<PathBuf>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    T: TransportProtocol + Clone,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let start = Instant::now();
+    let scans = file_paths.into_iter().map(|file_path| {
+        let file_path = PathBuf::from(file_path.as_ref());
+        let connection = connection.clone();
+        let options = &options;
+        async move {
+            let result = scan_file(&file_path, connection, options)
+                .await
+                .map_err(ClamdError::from)
+                .and_then(|response| ScanOutcome::from_response(&response));
+            (file_path, result)
+        }
+    });
+    let results = concurrency::join_bounded(scans, max_concurrency).await;
+    ScanResults::from_file_results(results, start.elapsed())
+}
+
+/// Scans all files in a directory tree for viruses, yielding the results as a
+/// stream
+///
+/// This function walks the directory tree at `dir_path` on the client side and
+/// streams each file to a ClamAV server for scanning, using a new connection
+/// per file. The results are yielded in the order of the blocking
+/// [`scan_directory`](crate::scan_directory). By default, files are scanned one
+/// after another, and each file is only scanned once the result of the
+/// previous one has been consumed;
+/// [`max_concurrency`](DirectoryScanOptions::max_concurrency) scans several
+/// files at the same time. The scans run within the stream, so dropping it
+/// cancels the scans in flight and closes their connections. Directory entries
+/// are listed using blocking file system calls.
+///
+/// # Arguments
+///
+/// * `dir_path`: The path to the directory to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `directory_options`: The [`DirectoryScanOptions`] that control which files are scanned
+///
+/// # Returns
+///
+/// A stream of the path of each scanned file together with its
+/// [`ScanOutcome`], or the error that occurred while reading or scanning it
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// use async_std::stream::StreamExt;
+///
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+/// let mut results =
+///     clamav_client::async_std::scan_directory("tests/data", clamd_tcp, None, directory_options);
+/// while let Some((path, result)) = results.next().await {
+///     match result {
+///         Ok(outcome) if outcome.is_infected() => println!("{} is infected!", path.display()),
+///         Ok(_) => println!("No virus found in {}", path.display()),
+///         Err(err) => println!("Could not scan {}: {}", path.display(), err),
+///     }
+/// }
+/// # }
+/// ```
+///
+pub fn scan_directory<P, T, O>(
+    dir_path: P,
+    connection: T,
+    options: O,
+    directory_options: DirectoryScanOptions,
+) -> impl Stream<Item = (PathBuf, Result<ScanOutcome, ClamdError>)> + Unpin
+where
+    P: AsRef<Path>,
+    T: TransportProtocol + Clone,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let walk = directory::Walk::new(dir_path.as_ref().as_ref(), directory_options);
+    directory::DirectoryScan::new(walk, move |file_path| {
+        let file_path = PathBuf::from(file_path);
+        let connection = connection.clone();
+        let options = options.clone();
+        async move {
+            let result = scan_file(&file_path, connection, options)
+                .await
+                .map_err(ClamdError::from)
+                .and_then(|response| ScanOutcome::from_response(&response));
+            (file_path, result)
+        }
+    })
+}
+
+/// Runs a scan until it finishes or `stop` completes
+///
+/// This function cancels `scan`, e.g. a call of [`scan_file`] or
+/// [`Client::scan_buffer`], as soon as `stop` completes. The `stop` future can
+/// be anything that completes on cancellation, e.g. receiving from a channel
+/// when the client of a request disconnects, or [`async_std::task::sleep`]
+/// for a request deadline. The cancelled scan is dropped immediately, which
+/// terminates its stream to ClamAV.
+///
+/// The functions of this module can be run by any executor, so this function
+/// aborts scans in applications using `smol` as well.
+///
+/// # Arguments
+///
+/// * `scan`: The scan to run
+/// * `stop`: The future that cancels the scan when it completes
+///
+/// # Returns
+///
+/// The result of `scan`, or [`ClamdError::Cancelled`] if `stop` completed
+/// first. For functions returning an [`IoResult`], the error is wrapped in an
+/// [`io::Error`].
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let (abort, aborted) = async_std::channel::bounded::<()>(1);
+/// let scan = clamav_client::async_std::scan_file("README.md", clamd_tcp, None);
+/// let response = clamav_client::async_std::cancellable(scan, aborted.recv())
+///     .await
+///     .unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// # drop(abort);
+/// # }
+/// ```
+///
+pub async fn cancellable<F, S, R, E>(scan: F, stop: S) -> Result<R, E>
+where
+    F: std::future::Future<Output = Result<R, E>>,
+    S: std::future::Future,
+    E: From<ClamdError>,
+{
+    concurrency::cancellable(scan, stop).await
+}
+
+/// A reusable client for a ClamAV server
+///
+/// The client keeps the connection type and the [`ScanOptions`] of all scans,
+/// and returns a typed [`ScanOutcome`] instead of the raw server response.
+/// With the feature flag "cache", it can consult a
+/// [`ScanCache`](crate::ScanCache) before sending content to the server.
+///
+/// # Example
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::ScanOptions::new().chunk_size(8192);
+/// let client = clamav_client::async_std::Client::new(clamd_tcp).options(options);
+///
+/// let outcome = client.scan_file("README.md").await.unwrap();
+/// assert!(outcome.is_clean());
+/// # }
+/// ```
+///
+#[derive(Clone)]
+pub struct Client<T> {
+    connection: T,
+    options: ScanOptions,
+    stats: Arc<StatsCounters>,
+    timeout: Option<Duration>,
+    failure_policy: FailurePolicy,
+    verdict_policy: Option<Arc<dyn VerdictPolicy>>,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<dyn ScanCache>>,
+    #[cfg(feature = "audit")]
+    audit_log: Option<Arc<AuditLog>>,
+}
+
+impl<T: TransportProtocol> Client<T> {
+    /// Creates a client that uses `connection` and the default scan options
+    pub fn new(connection: T) -> Self {
+        let stats = Arc::new(StatsCounters::new());
+        let mut options = ScanOptions::default();
+        options.hooks.stats = Some(Arc::clone(&stats));
+        Client {
+            connection,
+            options,
+            stats,
+            timeout: None,
+            failure_policy: FailurePolicy::default(),
+            verdict_policy: None,
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "audit")]
+            audit_log: None,
+        }
+    }
+
+    /// Sets the [`ScanOptions`] used by all scans of this client
+    ///
+    /// The lifecycle hooks of the client are kept.
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        let hooks = mem::take(&mut self.options.hooks);
+        self.options = options.into();
+        self.options.hooks = hooks;
+        self
+    }
+
+    /// Calls `on_connect` with the time it took to connect to ClamAV, after
+    /// the connection of each scan is established
+    ///
+    /// Like all hooks, it is called from the task that performs the scan, so
+    /// it should return quickly.
+    pub fn on_connect<F>(mut self, on_connect: F) -> Self
+    where
+        F: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.options.hooks.connect = Some(Arc::new(on_connect));
+        self
+    }
+
+    /// Calls `on_chunk_sent` with the size in bytes of each chunk sent to
+    /// ClamAV
+    pub fn on_chunk_sent<F>(mut self, on_chunk_sent: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.options.hooks.chunk_sent = Some(Arc::new(on_chunk_sent));
+        self
+    }
+
+    /// Calls `on_response` with the [`ScanOutcome`] of each response of
+    /// ClamAV
+    ///
+    /// It is not called for outcomes found in the cache.
+    pub fn on_response<F>(mut self, on_response: F) -> Self
+    where
+        F: Fn(&ScanOutcome) + Send + Sync + 'static,
+    {
+        self.options.hooks.response = Some(Arc::new(on_response));
+        self
+    }
+
+    /// Calls `on_error` with the [`ClamdError`] of each failed scan, e.g. if
+    /// ClamAV is not available
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: Fn(&ClamdError) + Send + Sync + 'static,
+    {
+        self.options.hooks.error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Passes each virus found by ClamAV to `sink`, in addition to the sinks
+    /// added before
+    ///
+    /// Detections of outcomes found in the cache are not passed.
+    pub fn detection_sink<S: DetectionSink + 'static>(mut self, sink: S) -> Self {
+        self.options.hooks.detection_sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Attaches `correlation_id` to all scans of this client, e.g. to a clone
+    /// of the client that scans the uploads of a single request (see
+    /// [`ScanOptions::correlation_id`])
+    ///
+    /// Options set afterwards replace it.
+    pub fn correlation_id<I: Into<String>>(mut self, correlation_id: I) -> Self {
+        self.options = self.options.correlation_id(correlation_id);
+        self
+    }
+
+    /// Aborts commands and scans that take longer than `timeout`, including
+    /// connecting to ClamAV, with [`TimedOut`](io::ErrorKind::TimedOut)
+    ///
+    /// Like other dropped scans, a scan that times out terminates its stream
+    /// to ClamAV.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the [`FailurePolicy`] of [`decide_file`](Self::decide_file) and
+    /// [`decide_buffer`](Self::decide_buffer), which propagates errors by
+    /// default
+    pub fn failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Applies `verdict_policy` to the outcome of each scan of this client
+    /// before it is returned, replacing any policy set before
+    ///
+    /// Hooks, detection sinks, the audit log and the cache see the outcome
+    /// after the policy, so clients that share a cache should use the same
+    /// policy. Outcomes found in the cache are returned as they are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clamav_client::ScanOutcome;
+    ///
+    /// # #[async_std::main]
+    /// # async fn main() {
+    /// let clamd_tcp = clamav_client::async_std::Tcp{ host_address: "localhost:3310" };
+    /// let client = clamav_client::async_std::Client::new(clamd_tcp).verdict_policy(|outcome| match outcome {
+    ///     // Tolerate heuristic detections of broken executables
+    ///     ScanOutcome::Infected { signature } if signature.starts_with("Heuristics.Broken.") => {
+    ///         ScanOutcome::Clean
+    ///     }
+    ///     outcome => outcome,
+    /// });
+    /// # assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+    /// # }
+    /// ```
+    ///
+    pub fn verdict_policy<V: VerdictPolicy + 'static>(mut self, verdict_policy: V) -> Self {
+        self.verdict_policy = Some(Arc::new(verdict_policy));
+        self
+    }
+
+    /// Looks up the SHA-256 digest of the content in `cache` before scanning
+    /// it, and stores the outcome of each scan in `cache`
+    ///
+    /// To share a cache between clients, pass an `Arc` of it to each client.
+    ///
+    /// Use the feature flag "cache" to enable this option.
+    #[cfg(feature = "cache")]
+    pub fn cache<C: ScanCache + 'static>(mut self, cache: C) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Records each scan in `audit_log`, including failed scans
+    ///
+    /// To share an audit log between clients, pass an `Arc` of it to each
+    /// client.
+    ///
+    /// Use the feature flag "audit" to enable this option.
+    #[cfg(feature = "audit")]
+    pub fn audit_log<A: Into<Arc<AuditLog>>>(mut self, audit_log: A) -> Self {
+        self.audit_log = Some(audit_log.into());
+        self
+    }
+
+    /// Returns the cumulative statistics of the scans of this client and its
+    /// clones
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+
+    /// Sends a ping request to ClamAV (see [`ping`])
+    pub async fn ping(&self) -> IoResult {
+        with_timeout(self.timeout, ping(&self.connection)).await
+    }
+
+    /// Gets the version number from ClamAV (see [`get_version`])
+    pub async fn get_version(&self) -> IoResult {
+        with_timeout(self.timeout, get_version(&self.connection)).await
+    }
+
+    /// Gets the age of the signature databases of ClamAV (see
+    /// [`database_age`])
+    pub async fn database_age(&self) -> Result<Duration, ClamdError> {
+        with_timeout(self.timeout, database_age(&self.connection)).await
+    }
+
+    /// Checks whether the signature databases of ClamAV are older than
+    /// `max_age` (see [`is_database_stale`])
+    pub async fn is_database_stale(&self, max_age: Duration) -> Result<bool, ClamdError> {
+        with_timeout(self.timeout, is_database_stale(&self.connection, max_age)).await
+    }
+
+    /// Scans a file for viruses (see [`scan_file`])
+    ///
+    /// With a cache, the file is read once to compute its digest before it is
+    /// scanned.
+    pub async fn scan_file<P: AsRef<Path>>(&self, file_path: P) -> Result<ScanOutcome, ClamdError> {
+        self.scan_file_response(file_path)
+            .await
+            .map(|response| response.outcome)
+    }
+
+    /// Scans a file for viruses like [`scan_file`](Self::scan_file) and returns
+    /// the raw response of ClamAV along with the outcome
+    ///
+    /// The outcome is the one after the verdict policy of this client, and the
+    /// raw response is empty if the outcome was found in the cache.
+    pub async fn scan_file_response<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<ScanResponse, ClamdError> {
+        let file_path = file_path.as_ref();
+
+        #[cfg(feature = "cache")]
+        let cached_scan = match self.cache.as_deref() {
+            Some(cache) => Some(CachedScan::new(cache, sha256_file(file_path).await?)),
+            None => None,
+        };
+        #[cfg(feature = "cache")]
+        if let Some(outcome) = cached_scan.as_ref().and_then(CachedScan::lookup) {
+            return Ok(ScanResponse {
+                outcome,
+                raw: Vec::new(),
+            });
+        }
+
+        #[cfg(feature = "audit")]
+        let audit_scan = AuditScan::start(self.audit_log.as_deref(), &self.options);
+        #[cfg(feature = "audit")]
+        let options = audit_scan.options();
+        #[cfg(not(feature = "audit"))]
+        let options = &self.options;
+
+        let mut raw = Vec::new();
+        let result = with_timeout(
+            self.timeout,
+            scan_file(file_path, &self.connection, options),
+        )
+        .await
+        .map_err(ClamdError::from)
+        .and_then(|response| {
+            let outcome = ScanOutcome::from_response(&response);
+            raw = response;
+            outcome
+        })
+        .map(|outcome| verdict::apply(self.verdict_policy.as_deref(), outcome));
+        #[cfg(feature = "cache")]
+        let sha256 = cached_scan.as_ref().map(CachedScan::digest);
+        #[cfg(not(feature = "cache"))]
+        let sha256 = None;
+        self.options.hooks.result(
+            &result,
+            Some(file_path.as_ref()),
+            sha256,
+            self.options.correlation_id.as_deref(),
+        );
+        #[cfg(feature = "audit")]
+        let result = audit_scan.finish(Some(file_path.as_ref()), result);
+
+        #[cfg(feature = "cache")]
+        if let Some(cached_scan) = cached_scan {
+            cached_scan.store(&result);
+        }
+        result.map(|outcome| ScanResponse { outcome, raw })
+    }
+
+    /// Scans a data buffer for viruses (see [`scan_buffer`])
+    pub async fn scan_buffer<B: AsRef<[u8]>>(&self, buffer: B) -> Result<ScanOutcome, ClamdError> {
+        self.scan_buffer_response(buffer)
+            .await
+            .map(|response| response.outcome)
+    }
+
+    /// Scans a data buffer for viruses like [`scan_buffer`](Self::scan_buffer)
+    /// and returns the raw response of ClamAV along with the outcome
+    ///
+    /// The outcome is the one after the verdict policy of this client, and the
+    /// raw response is empty if the outcome was found in the cache.
+    pub async fn scan_buffer_response<B: AsRef<[u8]>>(
+        &self,
+        buffer: B,
+    ) -> Result<ScanResponse, ClamdError> {
+        let buffer = buffer.as_ref();
+        #[cfg(feature = "cache")]
+        let cached_scan = self
+            .cache
+            .as_deref()
+            .map(|cache| CachedScan::new(cache, cache::sha256(buffer)));
+        #[cfg(feature = "cache")]
+        if let Some(outcome) = cached_scan.as_ref().and_then(CachedScan::lookup) {
+            return Ok(ScanResponse {
+                outcome,
+                raw: Vec::new(),
+            });
+        }
+
+        #[cfg(feature = "audit")]
+        let audit_scan = AuditScan::start(self.audit_log.as_deref(), &self.options);
+        #[cfg(feature = "audit")]
+        let options = audit_scan.options();
+        #[cfg(not(feature = "audit"))]
+        let options = &self.options;
+
+        let mut raw = Vec::new();
+        let result = with_timeout(self.timeout, scan_buffer(buffer, &self.connection, options))
+            .await
+            .map_err(ClamdError::from)
+            .and_then(|response| {
+                let outcome = ScanOutcome::from_response(&response);
+                raw = response;
+                outcome
+            })
+            .map(|outcome| verdict::apply(self.verdict_policy.as_deref(), outcome));
+        #[cfg(feature = "cache")]
+        let sha256 = cached_scan.as_ref().map(CachedScan::digest);
+        #[cfg(not(feature = "cache"))]
+        let sha256 = None;
+        self.options.hooks.result(
+            &result,
+            None,
+            sha256,
+            self.options.correlation_id.as_deref(),
+        );
+        #[cfg(feature = "audit")]
+        let result = audit_scan.finish(None, result);
+
+        #[cfg(feature = "cache")]
+        if let Some(cached_scan) = cached_scan {
+            cached_scan.store(&result);
+        }
+        result.map(|outcome| ScanResponse { outcome, raw })
+    }
+
+    /// Scans a file for viruses and decides whether to accept it, applying the
+    /// [`FailurePolicy`] of this client if the scan fails
+    pub async fn decide_file<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<ScanDecision, ClamdError> {
+        self.failure_policy.decide(self.scan_file(file_path).await)
+    }
+
+    /// Scans a data buffer for viruses and decides whether to accept it,
+    /// applying the [`FailurePolicy`] of this client if the scan fails
+    pub async fn decide_buffer<B: AsRef<[u8]>>(
+        &self,
+        buffer: B,
+    ) -> Result<ScanDecision, ClamdError> {
+        self.failure_policy.decide(self.scan_buffer(buffer).await)
+    }
+}
+
+/// Fails with [`TimedOut`](io::ErrorKind::TimedOut) if `future` does not
+/// finish within `timeout`
+async fn with_timeout<F, R, E>(timeout: Option<Duration>, future: F) -> Result<R, E>
+where
+    F: std::future::Future<Output = Result<R, E>>,
+    E: From<io::Error>,
+{
+    match timeout {
+        Some(timeout) => async_std::future::timeout(timeout, future)
+            .await
+            .unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::TimedOut).into())),
+        None => future.await,
+    }
+}
+
+#[cfg(feature = "cache")]
+async fn sha256_file(file_path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(file_path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = DataBuffer::default();
+    let buffer = buffer.get(DEFAULT_CHUNK_SIZE);
+    loop {
+        let len = file.read(buffer).await?;
+        if len == 0 {
+            return Ok(hasher.finalize().into());
+        }
+        hasher.update(&buffer[..len]);
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde_json::json;
+
+use super::{timestamp::format_time, ClamdError, ScanOptions, ScanOutcome, ScanReport};
+
+type RotateHook = Arc<dyn Fn(&Path) -> io::Result<()> + Send + Sync>;
+
+/// An entry of an [`AuditLog`], which records a single scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AuditRecord {
+    /// The time the scan started
+    pub timestamp: SystemTime,
+    /// The path of the scanned file, or `None` if a buffer was scanned
+    pub source: Option<String>,
+    /// The SHA-256 digest of the data sent to ClamAV, unless the scan failed
+    /// before the response was received
+    pub sha256: Option<[u8; 32]>,
+    /// The number of bytes sent to ClamAV, unless the scan failed before the
+    /// response was received
+    pub size: Option<u64>,
+    /// The outcome of the scan, or `None` if it failed
+    pub outcome: Option<ScanOutcome>,
+    /// The error of a failed scan
+    pub error: Option<String>,
+    /// The time from starting the scan until its result was known
+    pub duration: Duration,
+    /// The ID attached to the scan using `ScanOptions::correlation_id`, if any
+    pub correlation_id: Option<String>,
+}
+
+impl AuditRecord {
+    /// Formats the record as a JSON object on a single line
+    ///
+    /// The object contains the `timestamp` in RFC 3339 format, the `source`,
+    /// the hex-encoded `sha256` digest, the `size` in bytes, the `outcome`
+    /// (`clean`, `infected`, `warning`, `error` or `failed`), the `signature`
+    /// of an infected or warning outcome, the `error` of ClamAV or of a failed
+    /// scan, the `duration_ms` and the `correlation_id`. Unknown values are
+    /// `null`.
+    pub fn to_json(&self) -> String {
+        let (outcome, signature, error) = match &self.outcome {
+            Some(ScanOutcome::Clean) => ("clean", None, None),
+            Some(ScanOutcome::Infected { signature }) => ("infected", Some(signature), None),
+            Some(ScanOutcome::Warning { signature }) => ("warning", Some(signature), None),
+            Some(ScanOutcome::Error { message }) => ("error", None, Some(message)),
+            None => ("failed", None, self.error.as_ref()),
+        };
+        json!({
+            "timestamp": format_time(self.timestamp, "Z"),
+            "source": self.source,
+            "sha256": self.sha256.map(|digest| hex(&digest)),
+            "size": self.size,
+            "outcome": outcome,
+            "signature": signature,
+            "error": error,
+            "duration_ms": self.duration.as_millis() as u64,
+            "correlation_id": self.correlation_id,
+        })
+        .to_string()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// An append-only log of all scans of a client, with one JSON object per line
+///
+/// The log records the time, source, SHA-256 digest, size, outcome and
+/// duration of each scan (see [`AuditRecord::to_json`]), including failed
+/// scans, e.g. as proof that each stored file was scanned. Outcomes found in
+/// the cache of a client are not recorded, because the content was scanned
+/// before.
+///
+/// Each record is written before the scan returns. If it cannot be written,
+/// the scan fails with the error, so that unrecorded files are not accepted.
+///
+/// If the log is rotated by another program, e.g. `logrotate`, call
+/// [`reopen`](Self::reopen) afterwards. Alternatively, the log rotates itself
+/// once it exceeds [`max_size`](Self::max_size).
+///
+/// Use the feature flag "audit" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// let audit_log = clamav_client::AuditLog::open("target/audit.jsonl")
+///     .unwrap()
+///     .max_size(10_000_000);
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let client = clamav_client::Client::new(clamd_tcp).audit_log(audit_log);
+/// let outcome = client.scan_file("README.md").unwrap();
+/// # assert!(outcome.is_clean());
+/// ```
+///
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: Option<u64>,
+    on_rotate: Option<RotateHook>,
+    file: Mutex<AuditFile>,
+}
+
+struct AuditFile {
+    file: File,
+    size: u64,
+}
+
+impl AuditFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(AuditFile { file, size })
+    }
+}
+
+impl AuditLog {
+    /// Opens the log at `path` for appending, and creates it if it does not
+    /// exist
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = AuditFile::open(&path)?;
+        Ok(AuditLog {
+            path,
+            max_size: None,
+            on_rotate: None,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Rotates the log after a record made it exceed `max_size` bytes
+    ///
+    /// By default, the log is renamed by appending `.1` to its file name,
+    /// which replaces the previously rotated log. Use
+    /// [`on_rotate`](Self::on_rotate) to keep more logs.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Calls `on_rotate` with the path of the log to rotate it, instead of
+    /// renaming it
+    ///
+    /// The hook should move the log away, e.g. to a name containing the
+    /// current date, and may compress or upload it. Afterwards, a new log is
+    /// created at the same path.
+    pub fn on_rotate<F>(mut self, on_rotate: F) -> Self
+    where
+        F: Fn(&Path) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.on_rotate = Some(Arc::new(on_rotate));
+        self
+    }
+
+    /// Returns the path of the log
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `record` to the log
+    pub fn record(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = record.to_json();
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        // A single write, so that records of concurrent writers are not mixed
+        file.file.write_all(line.as_bytes())?;
+        file.size += line.len() as u64;
+        match self.max_size {
+            Some(max_size) if file.size > max_size => {
+                self.rotate()?;
+                *file = AuditFile::open(&self.path)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Reopens the log at its path, e.g. after it was moved by another
+    /// program
+    pub fn reopen(&self) -> io::Result<()> {
+        *self.file.lock().unwrap() = AuditFile::open(&self.path)?;
+        Ok(())
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        match &self.on_rotate {
+            Some(on_rotate) => on_rotate(&self.path),
+            None => {
+                let mut rotated_path = self.path.clone().into_os_string();
+                rotated_path.push(".1");
+                fs::rename(&self.path, rotated_path)
+            }
+        }
+    }
+}
+
+/// Records a scan of a client in its audit log, if any
+///
+/// The scan uses scan options that compute the digest of the data and capture
+/// the [`ScanReport`], in addition to calling the report callback of the
+/// client.
+pub(crate) struct AuditScan<'a> {
+    log: Option<&'a AuditLog>,
+    options: Cow<'a, ScanOptions>,
+    report: Arc<Mutex<Option<ScanReport>>>,
+    timestamp: SystemTime,
+    start: Instant,
+}
+
+impl<'a> AuditScan<'a> {
+    pub(crate) fn start(log: Option<&'a AuditLog>, options: &'a ScanOptions) -> Self {
+        let report = Arc::new(Mutex::new(None));
+        let options = match log {
+            Some(_) => {
+                let mut options = options.clone();
+                let callback = options.report.take();
+                let captured_report = Arc::clone(&report);
+                Cow::Owned(options.sha256(true).on_report(move |report| {
+                    if let Some(callback) = &callback {
+                        (callback.0)(report);
+                    }
+                    *captured_report.lock().unwrap() = Some(report.clone());
+                }))
+            }
+            None => Cow::Borrowed(options),
+        };
+        AuditScan {
+            log,
+            options,
+            report,
+            timestamp: SystemTime::now(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the options of the scan
+    pub(crate) fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Records the result of the scan of the file at `source`, or of a buffer,
+    /// and passes it through unless it could not be recorded
+    pub(crate) fn finish(
+        self,
+        source: Option<&Path>,
+        result: Result<ScanOutcome, ClamdError>,
+    ) -> Result<ScanOutcome, ClamdError> {
+        let log = match self.log {
+            Some(log) => log,
+            None => return result,
+        };
+        let report = self.report.lock().unwrap().take();
+        let (sha256, size) = match report {
+            Some(report) => (report.sha256, Some(report.bytes_sent)),
+            None => (None, None),
+        };
+        log.record(&AuditRecord {
+            timestamp: self.timestamp,
+            source: source.map(|path| path.to_string_lossy().into_owned()),
+            sha256,
+            size,
+            outcome: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(ClamdError::to_string),
+            duration: self.start.elapsed(),
+            correlation_id: self.options.correlation_id.as_deref().map(str::to_owned),
+        })?;
+        result
+    }
+}
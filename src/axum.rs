@@ -0,0 +1,268 @@
+//! Virus scanning for upload endpoints of [`axum`](https://docs.rs/axum)
+//!
+//! The extractors [`ScannedBytes`] and [`ScannedMultipart`] scan the request
+//! body of a handler while it is received and reject infected uploads with a
+//! [`ScanRejection`]. The [`ScanLayer`] scans the request bodies of all routes
+//! it wraps before they reach the handlers. The extractors get the [`Scanner`]
+//! from the state of the router, using [`FromRef`].
+//!
+//! Request bodies are kept in memory and limited by axum's
+//! [`DefaultBodyLimit`](axum::extract::DefaultBodyLimit), 2 MB by default.
+//!
+//! Use the feature flag "axum" to enable this module.
+//!
+//! # Example
+//!
+//! ```
+//! use axum::{routing::post, Router};
+//! use clamav_client::axum::{ScanLayer, ScannedBytes, Scanner};
+//!
+//! async fn upload(ScannedBytes(data): ScannedBytes) -> String {
+//!     format!("Received {} clean bytes", data.len())
+//! }
+//!
+//! let scanner = Scanner::tcp("localhost:3310");
+//! let app: Router = Router::new()
+//!     .route("/upload", post(upload))
+//!     .with_state(scanner.clone());
+//!
+//! // Or scan the request bodies of all routes
+//! let app: Router = Router::new()
+//!     .route("/files", post(|body: String| async move { body }))
+//!     .layer(ScanLayer::new(scanner));
+//! ```
+//!
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRef, FromRequest, Request},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    RequestExt,
+};
+use http_body::Body as _;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use super::tokio::MultipartField;
+
+pub use super::web::{ScanRejection, Scanner};
+
+impl ScanRejection {
+    /// Returns the status code of the response for this rejection
+    ///
+    /// Use the feature flag "axum" to enable this method.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status_code()).expect("the status code is valid")
+    }
+}
+
+impl IntoResponse for ScanRejection {
+    fn into_response(self) -> Response {
+        (self.status(), self.to_string()).into_response()
+    }
+}
+
+/// An extractor that scans the request body and returns it if it is clean
+///
+/// # Example
+///
+/// ```
+/// use axum::{routing::post, Router};
+/// use clamav_client::axum::{ScannedBytes, Scanner};
+///
+/// async fn upload(ScannedBytes(data): ScannedBytes) -> String {
+///     format!("Received {} clean bytes", data.len())
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/upload", post(upload))
+///     .with_state(Scanner::tcp("localhost:3310"));
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ScannedBytes(pub Bytes);
+
+impl<S> FromRequest<S> for ScannedBytes
+where
+    Scanner: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ScanRejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let scanner = Scanner::from_ref(state);
+        let body = request.into_limited_body();
+        if body.size_hint().exact() == Some(0) {
+            return Ok(ScannedBytes(Bytes::new()));
+        }
+        scanner
+            .scan_body(body.into_data_stream())
+            .await
+            .map(ScannedBytes)
+    }
+}
+
+/// An extractor that scans the file fields of a `multipart/form-data` request
+/// body and returns all fields if the file fields are clean
+///
+/// See [`scan_multipart`](crate::tokio::scan_multipart) for how the fields
+/// are scanned.
+///
+/// # Example
+///
+/// ```
+/// use axum::{routing::post, Router};
+/// use clamav_client::axum::{ScannedMultipart, Scanner};
+///
+/// async fn upload(ScannedMultipart(fields): ScannedMultipart) -> String {
+///     format!("Received {} clean fields", fields.len())
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/upload", post(upload))
+///     .with_state(Scanner::tcp("localhost:3310"));
+/// ```
+///
+#[derive(Debug)]
+pub struct ScannedMultipart(pub Vec<MultipartField>);
+
+impl<S> FromRequest<S> for ScannedMultipart
+where
+    Scanner: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ScanRejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let scanner = Scanner::from_ref(state);
+        let boundary = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|content_type| multer::parse_boundary(content_type).ok())
+            .ok_or_else(|| ScanRejection::InvalidBody {
+                message: "invalid multipart/form-data content type".to_owned(),
+            })?;
+        let body = request.into_limited_body();
+        let multipart = multer::Multipart::new(body.into_data_stream(), boundary);
+        scanner
+            .scan_multer_form(multipart)
+            .await
+            .map(ScannedMultipart)
+    }
+}
+
+type RejectionHandler = Arc<dyn Fn(ScanRejection) -> Response + Send + Sync>;
+
+/// A [`Layer`] that scans request bodies before they reach the wrapped
+/// service and rejects infected requests
+///
+/// Requests with an empty body are passed through without scanning.
+///
+/// # Example
+///
+/// ```
+/// use axum::{http::StatusCode, response::IntoResponse, routing::post, Router};
+/// use clamav_client::axum::{ScanLayer, ScanRejection, Scanner};
+///
+/// let layer = ScanLayer::new(Scanner::tcp("localhost:3310")).on_rejection(|rejection| {
+///     match rejection {
+///         ScanRejection::Infected { .. } => StatusCode::FORBIDDEN.into_response(),
+///         rejection => rejection.into_response(),
+///     }
+/// });
+/// let app: Router = Router::new()
+///     .route("/upload", post(|body: String| async move { body }))
+///     .layer(layer);
+/// ```
+///
+#[derive(Clone)]
+pub struct ScanLayer {
+    scanner: Scanner,
+    on_rejection: RejectionHandler,
+}
+
+impl ScanLayer {
+    /// Creates a layer that scans request bodies using `scanner`
+    pub fn new(scanner: Scanner) -> Self {
+        ScanLayer {
+            scanner,
+            on_rejection: Arc::new(IntoResponse::into_response),
+        }
+    }
+
+    /// Sets the function that creates the response for a rejected request,
+    /// instead of the response of the [`ScanRejection`]
+    pub fn on_rejection<F>(mut self, on_rejection: F) -> Self
+    where
+        F: Fn(ScanRejection) -> Response + Send + Sync + 'static,
+    {
+        self.on_rejection = Arc::new(on_rejection);
+        self
+    }
+}
+
+impl<S> Layer<S> for ScanLayer {
+    type Service = ScanService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ScanService {
+            inner,
+            scanner: self.scanner.clone(),
+            on_rejection: Arc::clone(&self.on_rejection),
+        }
+    }
+}
+
+/// The [`Service`] created by [`ScanLayer`]
+#[derive(Clone)]
+pub struct ScanService<S> {
+    inner: S,
+    scanner: Scanner,
+    on_rejection: RejectionHandler,
+}
+
+impl<S> Service<Request> for ScanService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // Use the service that is ready and leave a clone in its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let scanner = self.scanner.clone();
+        let on_rejection = Arc::clone(&self.on_rejection);
+        Box::pin(async move {
+            let (parts, body) = request.with_limited_body().into_parts();
+            if body.size_hint().exact() == Some(0) {
+                return inner.call(Request::from_parts(parts, body)).await;
+            }
+            match scanner.scan_body(body.into_data_stream()).await {
+                Ok(data) => {
+                    inner
+                        .call(Request::from_parts(parts, Body::from(data)))
+                        .await
+                }
+                Err(rejection) => Ok(on_rejection(rejection)),
+            }
+        })
+    }
+}
@@ -0,0 +1,232 @@
+//! An HTTP sidecar that exposes a ClamAV server as a small REST API
+//!
+//! Use the feature flag "rest-server" to build this binary.
+//!
+//! - `POST /scan` scans the request body and responds with its verdict, e.g.
+//!   `{"verdict":"infected","signature":"Eicar-Signature","error":null}`
+//! - `GET /healthz` responds with `200 OK` if ClamAV is available
+//! - `GET /version` responds with the version of ClamAV
+//!
+//! Errors are responded with a JSON object with an `error` field and `400 Bad
+//! Request` if the body could not be read, `413 Payload Too Large` if it
+//! exceeds the maximum size, `502 Bad Gateway` if the response of ClamAV is
+//! invalid and `503 Service Unavailable` if ClamAV is not available.
+
+use std::{future::Future, io, net::SocketAddr, path::PathBuf};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use clamav_client::{
+    tokio::{Tcp, TransportProtocol},
+    ClamdError, IoResult, ScanOptions, ScanOutcome,
+};
+use clap::Parser;
+use serde_json::json;
+use tokio_stream::StreamExt;
+
+#[cfg(unix)]
+use clamav_client::tokio::Socket;
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "An HTTP sidecar that exposes a ClamAV server as a REST API"
+)]
+struct Cli {
+    /// The address the HTTP server listens on
+    #[arg(long, env = "CLAMAV_REST_LISTEN", default_value = "0.0.0.0:8080")]
+    listen: SocketAddr,
+
+    /// The address (host and port) of the ClamAV server
+    #[arg(long, env = "CLAMAV_HOST", default_value = "localhost:3310")]
+    host: String,
+
+    /// The socket file path of the ClamAV server, used instead of TCP
+    #[cfg(unix)]
+    #[arg(long, env = "CLAMAV_SOCKET", conflicts_with = "host")]
+    socket: Option<PathBuf>,
+
+    /// The maximum size of a scanned request body in bytes
+    #[arg(long, env = "CLAMAV_REST_MAX_SIZE")]
+    max_size: Option<u64>,
+}
+
+/// The connection to the ClamAV server, with concrete types so that the
+/// futures of the handlers are `Send`
+#[derive(Clone)]
+enum Connection {
+    Tcp(Tcp<String>),
+    #[cfg(unix)]
+    Socket(Socket<PathBuf>),
+}
+
+#[derive(Clone)]
+struct AppState {
+    connection: Connection,
+    options: ScanOptions,
+}
+
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    let mut connection = Connection::Tcp(Tcp {
+        host_address: cli.host,
+    });
+    #[cfg(unix)]
+    if let Some(socket_path) = cli.socket {
+        connection = Connection::Socket(Socket { socket_path });
+    }
+    let mut options = ScanOptions::new();
+    if let Some(max_size) = cli.max_size {
+        options = options.max_stream_size(max_size);
+    }
+
+    // Handle the signals before announcing the address, so that a signal sent
+    // right after it does not kill the process
+    let shutdown = shutdown_signal()?;
+    let listener = tokio::net::TcpListener::bind(cli.listen).await?;
+    // Print the address, which is needed if the port is chosen by the system
+    println!("Listening on {}", listener.local_addr()?);
+    axum::serve(
+        listener,
+        app(AppState {
+            connection,
+            options,
+        }),
+    )
+    .with_graceful_shutdown(shutdown)
+    .await
+}
+
+fn app(state: AppState) -> Router {
+    Router::new()
+        .route("/scan", post(scan))
+        .route("/healthz", get(healthz))
+        .route("/version", get(version))
+        .with_state(state)
+}
+
+/// Handles SIGINT (Ctrl+C) and SIGTERM, which Kubernetes sends to stop a
+/// container, and returns a future that completes on either of them
+#[cfg(unix)]
+fn shutdown_signal() -> io::Result<impl Future<Output = ()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+    use tokio_stream::wrappers::SignalStream;
+
+    let interrupt = signal(SignalKind::interrupt())?;
+    let terminate = signal(SignalKind::terminate())?;
+    Ok(async move {
+        SignalStream::new(interrupt)
+            .merge(SignalStream::new(terminate))
+            .next()
+            .await;
+    })
+}
+
+/// Returns a future that completes on Ctrl+C
+#[cfg(not(unix))]
+fn shutdown_signal() -> io::Result<impl Future<Output = ()>> {
+    Ok(async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+}
+
+async fn scan(State(state): State<AppState>, body: Body) -> Response {
+    let mut body_error = None;
+    let stream = body.into_data_stream().map(|chunk| {
+        chunk.map_err(|err| {
+            body_error = Some(err.to_string());
+            io::Error::other("could not read the request body")
+        })
+    });
+    let result = match &state.connection {
+        Connection::Tcp(tcp) => {
+            clamav_client::tokio::scan_stream(stream, tcp, &state.options).await
+        }
+        #[cfg(unix)]
+        Connection::Socket(socket) => {
+            clamav_client::tokio::scan_stream(stream, socket, &state.options).await
+        }
+    };
+    if let Some(message) = body_error {
+        return error_response(StatusCode::BAD_REQUEST, message);
+    }
+
+    let outcome = match result
+        .map_err(ClamdError::from)
+        .and_then(|response| ScanOutcome::from_response(&response))
+    {
+        Ok(outcome) => outcome,
+        Err(err) => return clamd_error_response(err),
+    };
+    let (verdict, signature, error) = match outcome {
+        ScanOutcome::Clean => ("clean", None, None),
+        ScanOutcome::Infected { signature } => ("infected", Some(signature), None),
+        ScanOutcome::Error { message } => ("error", None, Some(message)),
+        outcome => ("error", None, Some(format!("{:?}", outcome))),
+    };
+    Json(json!({
+        "verdict": verdict,
+        "signature": signature,
+        "error": error,
+    }))
+    .into_response()
+}
+
+async fn healthz(State(state): State<AppState>) -> Response {
+    match send_command(&state.connection, Command::Ping).await {
+        Ok(_) => Json(json!({ "status": "ok" })).into_response(),
+        Err(err) => clamd_error_response(ClamdError::from(err)),
+    }
+}
+
+async fn version(State(state): State<AppState>) -> Response {
+    match send_command(&state.connection, Command::Version).await {
+        Ok(response) => {
+            let response = String::from_utf8_lossy(&response);
+            let version = response.trim_end_matches(['\0', '\n']);
+            Json(json!({ "version": version })).into_response()
+        }
+        Err(err) => clamd_error_response(ClamdError::from(err)),
+    }
+}
+
+enum Command {
+    Ping,
+    Version,
+}
+
+async fn send_command(connection: &Connection, command: Command) -> IoResult {
+    match connection {
+        Connection::Tcp(tcp) => send_command_with(tcp, command).await,
+        #[cfg(unix)]
+        Connection::Socket(socket) => send_command_with(socket, command).await,
+    }
+}
+
+async fn send_command_with<T: TransportProtocol>(connection: T, command: Command) -> IoResult {
+    match command {
+        Command::Ping => clamav_client::tokio::ping(connection).await,
+        Command::Version => clamav_client::tokio::get_version(connection).await,
+    }
+}
+
+fn clamd_error_response(err: ClamdError) -> Response {
+    let status = match err {
+        ClamdError::SizeLimitExceeded { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        ClamdError::InvalidResponse { .. } => StatusCode::BAD_GATEWAY,
+        ClamdError::EmptyInput => StatusCode::BAD_REQUEST,
+        _ => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    error_response(status, err.to_string())
+}
+
+fn error_response(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,97 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::TransportProtocol;
+
+/// Use a TCP connection from a local address to communicate with a ClamAV
+/// server
+///
+/// The connection is bound to `local_address` before connecting, e.g. to send
+/// it from a specific interface of a multi-homed host or from the subnet that
+/// the ACLs of ClamAV allow. A port of `0` lets the operating system choose
+/// the local port. Only addresses of the ClamAV server of the same family as
+/// the local address, IPv4 or IPv6, are tried.
+///
+/// Use the feature flag "bind" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::BoundTcp {
+///     host_address: "localhost:3310",
+///     local_address: "127.0.0.1:0".parse().unwrap(),
+/// };
+/// let response = clamav_client::ping(clamd_tcp).unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BoundTcp<A: ToSocketAddrs> {
+    /// The address (host and port) of the ClamAV server
+    pub host_address: A,
+    /// The local address to bind the connection to
+    pub local_address: SocketAddr,
+}
+
+impl<A: ToSocketAddrs> TransportProtocol for BoundTcp<A> {
+    type Stream = TcpStream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        connect_bound(
+            self.host_address.to_socket_addrs()?,
+            self.local_address,
+            None,
+        )
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        let stream = connect_bound(
+            self.host_address.to_socket_addrs()?,
+            self.local_address,
+            Some(timeout),
+        )?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        Ok(stream)
+    }
+}
+
+/// Connects from `local_address` to the first of `addresses` of the same
+/// family that accepts a connection, waiting at most `timeout` for each
+pub(crate) fn connect_bound<I: IntoIterator<Item = SocketAddr>>(
+    addresses: I,
+    local_address: SocketAddr,
+    timeout: Option<Duration>,
+) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    let addresses = addresses
+        .into_iter()
+        .filter(|address| address.is_ipv4() == local_address.is_ipv4());
+    for address in addresses {
+        let socket = Socket::new(
+            Domain::for_address(address),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        socket.bind(&local_address.into())?;
+        let connected = match timeout {
+            Some(timeout) => socket.connect_timeout(&address.into(), timeout),
+            None => socket.connect(&address.into()),
+        };
+        match connected {
+            Ok(()) => return Ok(socket.into()),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses of the family of the local address",
+        )
+    }))
+}
@@ -0,0 +1,87 @@
+use std::{
+    error::Error,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use futures_core::Stream;
+use http_body::Body;
+
+/// An adapter that turns an HTTP body, e.g. a `hyper` or `axum` request body,
+/// into a stream accepted by the asynchronous `scan_stream` functions
+///
+/// The data frames of the body are scanned and trailers are skipped. An error
+/// of the body fails the scan with its [`io::Error`], if the body error is
+/// one, and otherwise with an [`io::Error`] of kind
+/// [`Other`](io::ErrorKind::Other) wrapping it.
+///
+/// Use the feature flag "http-body" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// # let request_body = http_body_util::Full::new(&b"clean data"[..]);
+/// use clamav_client::BodyStream;
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let stream = BodyStream::new(request_body);
+/// let response = clamav_client::tokio::scan_stream(stream, clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// # assert_eq!(&response, b"stream: OK\0");
+/// # }
+/// ```
+///
+pub struct BodyStream<B> {
+    body: Pin<Box<B>>,
+}
+
+impl<B> BodyStream<B>
+where
+    B: Body,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    /// Creates a stream of the data frames of `body`
+    pub fn new(body: B) -> Self {
+        BodyStream {
+            body: Box::pin(body),
+        }
+    }
+}
+
+impl<B> Stream for BodyStream<B>
+where
+    B: Body,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let frame = match this.body.as_mut().poll_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => frame,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(into_io_error(err)))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            if let Ok(mut data) = frame.into_data() {
+                let len = data.remaining();
+                if len > 0 {
+                    return Poll::Ready(Some(Ok(data.copy_to_bytes(len))));
+                }
+            }
+        }
+    }
+}
+
+fn into_io_error<E: Into<Box<dyn Error + Send + Sync>>>(err: E) -> io::Error {
+    match err.into().downcast::<io::Error>() {
+        Ok(err) => *err,
+        Err(err) => io::Error::other(err),
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use super::TransportProtocol;
+
+/// A transport whose concrete type is erased, e.g. to keep a connection that
+/// is either TCP or a Unix socket, possibly wrapped in TLS, in the state of an
+/// application without making every type that holds it generic
+///
+/// The transport is cheap to clone, since clones share the wrapped transport.
+/// Its streams are [`BoxedStream`]s.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::BoxedTransport;
+///
+/// let transport = if let Ok(socket_path) = std::env::var("CLAMD_SOCKET") {
+///     BoxedTransport::new(clamav_client::Socket { socket_path })
+/// } else {
+///     BoxedTransport::new(clamav_client::Tcp { host_address: "localhost:3310" })
+/// };
+/// let client = clamav_client::Client::new(transport);
+/// # assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+/// ```
+///
+#[derive(Clone)]
+pub struct BoxedTransport {
+    transport: Arc<dyn DynTransport + Send + Sync>,
+}
+
+impl BoxedTransport {
+    /// Erases the type of `transport`
+    pub fn new<T>(transport: T) -> Self
+    where
+        T: TransportProtocol + Send + Sync + 'static,
+        T::Stream: Send + 'static,
+    {
+        BoxedTransport {
+            transport: Arc::new(transport),
+        }
+    }
+}
+
+impl fmt::Debug for BoxedTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedTransport").finish_non_exhaustive()
+    }
+}
+
+impl TransportProtocol for BoxedTransport {
+    type Stream = BoxedStream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        self.transport.connect()
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        self.transport.connect_timeout(timeout)
+    }
+
+    fn is_local(&self) -> bool {
+        self.transport.is_local()
+    }
+}
+
+/// The object-safe part of [`TransportProtocol`] behind a [`BoxedTransport`]
+trait DynTransport {
+    fn connect(&self) -> io::Result<BoxedStream>;
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<BoxedStream>;
+    fn is_local(&self) -> bool;
+}
+
+impl<T> DynTransport for T
+where
+    T: TransportProtocol,
+    T::Stream: Send + 'static,
+{
+    fn connect(&self) -> io::Result<BoxedStream> {
+        TransportProtocol::connect(self).map(BoxedStream::new)
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<BoxedStream> {
+        TransportProtocol::connect_timeout(self, timeout).map(BoxedStream::new)
+    }
+
+    fn is_local(&self) -> bool {
+        TransportProtocol::is_local(self)
+    }
+}
+
+/// The stream of a [`BoxedTransport`], whose concrete type is erased
+pub struct BoxedStream {
+    stream: Box<dyn ReadWrite + Send>,
+}
+
+impl BoxedStream {
+    /// Erases the type of `stream`
+    pub fn new<S: Read + Write + Send + 'static>(stream: S) -> Self {
+        BoxedStream {
+            stream: Box::new(stream),
+        }
+    }
+}
+
+impl fmt::Debug for BoxedStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedStream").finish_non_exhaustive()
+    }
+}
+
+trait ReadWrite: Read + Write {}
+
+impl<S: Read + Write> ReadWrite for S {}
+
+impl Read for BoxedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for BoxedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
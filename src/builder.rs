@@ -0,0 +1,212 @@
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[cfg(unix)]
+use super::Socket;
+use super::{Client, FailurePolicy, ScanOptions, Tcp};
+
+/// Creates a client with `new` from the connection of `builder` and applies
+/// its settings, which are the same for the client of each runtime
+macro_rules! build_client {
+    ($new:path, $builder:expr) => {{
+        let builder = $builder;
+        let client = $new(builder.connection)
+            .options(builder.options)
+            .failure_policy(builder.failure_policy);
+        match builder.timeout {
+            Some(timeout) => client.timeout(timeout),
+            None => client,
+        }
+    }};
+}
+
+/// A builder for the [`Client`] of each runtime, which collects the connection,
+/// the timeout and the [`ScanOptions`] in one place
+///
+/// The builder starts with the connection, [`tcp`](ClamdClientBuilder::tcp) or
+/// [`socket`](ClamdClientBuilder::socket), and creates the blocking client
+/// with [`build`](Self::build), the client of the [`tokio`](crate::tokio)
+/// module with `build_tokio` and the client of the
+/// [`async_std`](crate::async_std) module with `build_async_std`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let client = clamav_client::ClamdClientBuilder::tcp("localhost:3310")
+///     .timeout(Duration::from_secs(30))
+///     .chunk_size(8192)
+///     .build();
+///
+/// let outcome = client.scan_file("README.md").unwrap();
+/// assert!(outcome.is_clean());
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ClamdClientBuilder<C> {
+    connection: C,
+    options: ScanOptions,
+    timeout: Option<Duration>,
+    failure_policy: FailurePolicy,
+}
+
+impl ClamdClientBuilder<Tcp<String>> {
+    /// Starts a builder for clients connecting to `host_address` via TCP
+    pub fn tcp<A: Into<String>>(host_address: A) -> Self {
+        ClamdClientBuilder::new(Tcp {
+            host_address: host_address.into(),
+        })
+    }
+
+    /// Builds the client of the [`tokio`](crate::tokio) module
+    ///
+    /// Use the feature flag "tokio" to enable this method.
+    #[cfg(feature = "tokio")]
+    pub fn build_tokio(self) -> crate::tokio::Client<crate::tokio::Tcp<String>> {
+        build_client!(
+            crate::tokio::Client::new,
+            self.map_connection(crate::tokio::Tcp::from)
+        )
+    }
+
+    /// Builds the client of the [`async_std`](crate::async_std) module
+    ///
+    /// Use the feature flag "async-std" to enable this method.
+    #[cfg(feature = "async-std")]
+    pub fn build_async_std(self) -> crate::async_std::Client<crate::async_std::Tcp<String>> {
+        build_client!(
+            crate::async_std::Client::new,
+            self.map_connection(crate::async_std::Tcp::from)
+        )
+    }
+}
+
+#[cfg(unix)]
+impl ClamdClientBuilder<Socket<PathBuf>> {
+    /// Starts a builder for clients connecting to the Unix socket at
+    /// `socket_path`
+    pub fn socket<P: Into<PathBuf>>(socket_path: P) -> Self {
+        ClamdClientBuilder::new(Socket {
+            socket_path: socket_path.into(),
+        })
+    }
+
+    /// Builds the client of the [`tokio`](crate::tokio) module
+    ///
+    /// Use the feature flag "tokio" to enable this method.
+    #[cfg(feature = "tokio")]
+    pub fn build_tokio(self) -> crate::tokio::Client<crate::tokio::Socket<PathBuf>> {
+        build_client!(
+            crate::tokio::Client::new,
+            self.map_connection(crate::tokio::Socket::from)
+        )
+    }
+
+    /// Builds the client of the [`async_std`](crate::async_std) module
+    ///
+    /// Use the feature flag "async-std" to enable this method.
+    #[cfg(feature = "async-std")]
+    pub fn build_async_std(
+        self,
+    ) -> crate::async_std::Client<crate::async_std::Socket<async_std::path::PathBuf>> {
+        build_client!(
+            crate::async_std::Client::new,
+            self.map_connection(|socket| crate::async_std::Socket {
+                socket_path: socket.socket_path.into(),
+            })
+        )
+    }
+}
+
+impl<C> ClamdClientBuilder<C> {
+    pub(crate) fn new(connection: C) -> Self {
+        ClamdClientBuilder {
+            connection,
+            options: ScanOptions::default(),
+            timeout: None,
+            failure_policy: FailurePolicy::default(),
+        }
+    }
+
+    /// Converts the connection, e.g. into the connection of another runtime,
+    /// and keeps the settings
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    fn map_connection<D>(self, map: impl FnOnce(C) -> D) -> ClamdClientBuilder<D> {
+        ClamdClientBuilder {
+            connection: map(self.connection),
+            options: self.options,
+            timeout: self.timeout,
+            failure_policy: self.failure_policy,
+        }
+    }
+
+    /// Sets the timeout of the client (see [`Client::timeout`] and the
+    /// `timeout` methods of the asynchronous clients)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the [`FailurePolicy`] of the client (see
+    /// [`Client::failure_policy`])
+    pub fn failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Sets the [`ScanOptions`] of the client, replacing the options set
+    /// before
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        self.options = options.into();
+        self
+    }
+
+    /// Sets the chunk size of the client (see [`ScanOptions::chunk_size`])
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.options = self.options.chunk_size(chunk_size);
+        self
+    }
+
+    /// Sets the maximum stream size of the client (see
+    /// [`ScanOptions::max_stream_size`])
+    pub fn max_stream_size(mut self, max_stream_size: u64) -> Self {
+        self.options = self.options.max_stream_size(max_stream_size);
+        self
+    }
+
+    /// Limits the upload rate of the client (see
+    /// [`ScanOptions::max_bytes_per_second`])
+    pub fn max_bytes_per_second(mut self, max_bytes_per_second: u64) -> Self {
+        self.options = self.options.max_bytes_per_second(max_bytes_per_second);
+        self
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ClamdClientBuilder<super::ClamdConnection> {
+    /// Builds the client of the [`tokio`](crate::tokio) module
+    ///
+    /// Use the feature flag "tokio" to enable this method.
+    pub fn build_tokio(self) -> crate::tokio::Client<super::ClamdConnection> {
+        build_client!(crate::tokio::Client::new, self)
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl ClamdClientBuilder<super::ClamdConnection> {
+    /// Builds the client of the [`async_std`](crate::async_std) module
+    ///
+    /// Use the feature flag "async-std" to enable this method.
+    pub fn build_async_std(self) -> crate::async_std::Client<super::ClamdConnection> {
+        build_client!(crate::async_std::Client::new, self)
+    }
+}
+
+impl<C: super::TransportProtocol> ClamdClientBuilder<C> {
+    /// Builds the blocking client
+    pub fn build(self) -> Client<C> {
+        build_client!(Client::new, self)
+    }
+}
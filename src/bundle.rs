@@ -0,0 +1,500 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use super::{
+    report::ReportTimer, ClamdError, ScanOptions, ScanOutcome, ScanResults, TransportProtocol,
+};
+
+/// The size of a tar header and the unit tar members are padded to
+const BLOCK_SIZE: u64 = 512;
+
+/// The largest size that fits the octal size field of a tar header
+const MAX_MEMBER_SIZE: u64 = 0o77777777777;
+
+/// Options that control how files are bundled into archives for scanning
+///
+/// By default, files of up to 64 KiB are bundled into archives of up to 1,000
+/// files and 16 MiB each.
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::BundleOptions::new()
+///     .max_files(5_000)
+///     .max_bundle_size(64 * 1024 * 1024)
+///     .max_file_size(1024 * 1024);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct BundleOptions {
+    pub(crate) max_files: usize,
+    pub(crate) max_bundle_size: u64,
+    pub(crate) max_file_size: u64,
+}
+
+impl Default for BundleOptions {
+    fn default() -> Self {
+        BundleOptions {
+            max_files: 1_000,
+            max_bundle_size: 16 * 1024 * 1024,
+            max_file_size: 64 * 1024,
+        }
+    }
+}
+
+impl BundleOptions {
+    /// Creates the default bundle options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of files per archive
+    ///
+    /// Keep this below ClamAV's `MaxFiles` setting (10,000 by default), since
+    /// ClamAV stops unpacking an archive after that many files.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_files` is zero.
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        assert!(
+            max_files > 0,
+            "the number of files per bundle must not be zero"
+        );
+        self.max_files = max_files;
+        self
+    }
+
+    /// Sets the maximum size of an archive in bytes, including the tar headers
+    ///
+    /// Keep this below ClamAV's `StreamMaxLength` and `MaxScanSize` settings.
+    /// A single file larger than this is scanned on its own.
+    pub fn max_bundle_size(mut self, max_bundle_size: u64) -> Self {
+        self.max_bundle_size = max_bundle_size;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a file to be bundled
+    ///
+    /// Larger files are scanned on their own, since bundling only saves
+    /// round-trips for small files.
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+}
+
+/// Scans many small files for viruses by bundling them into tar archives
+///
+/// This function packs the files at `file_paths` into uncompressed tar
+/// archives and streams each archive to a ClamAV server as a single INSTREAM
+/// scan, which ClamAV unpacks on the server side. This saves a round-trip per
+/// file, which dominates the time to scan many tiny files one by one.
+///
+/// Since ClamAV does not report which member of an archive matched, the files
+/// of an archive in which a virus was found (or for which ClamAV reported an
+/// error) are split into halves that are scanned again, down to single files
+/// that are scanned on their own. So every result maps to the file it belongs
+/// to, at the cost of a few extra scans per infected file. If an archive
+/// cannot be sent to ClamAV, the error is reported for all of its files. Files larger than
+/// [`BundleOptions::max_file_size`] are scanned on their own right away.
+///
+/// ClamAV must be configured to scan archives (`ScanArchive`, enabled by
+/// default). Each file is read up to the size it had when the scan started.
+///
+/// # Arguments
+///
+/// * `file_paths`: The paths to the files to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `bundle_options`: The [`BundleOptions`] that control how files are bundled
+///
+/// # Returns
+///
+/// The [`ScanResults`] with the path of each file together with its
+/// [`ScanOutcome`], or the error that occurred while reading or scanning it,
+/// in the order of the input. Callbacks and the maximum stream size of the
+/// [`ScanOptions`] apply to each streamed archive.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let file_paths = ["README.md", "tests/data/eicar.txt", "Cargo.toml"];
+/// let bundle_options = clamav_client::BundleOptions::new();
+/// let results = clamav_client::scan_files_bundled(file_paths, clamd_tcp, None, bundle_options);
+/// for (path, result) in results {
+///     match result {
+///         Ok(outcome) if outcome.is_infected() => println!("{} is infected!", path.display()),
+///         Ok(_) => println!("No virus found in {}", path.display()),
+///         Err(err) => println!("Could not scan {}: {}", path.display(), err),
+///     }
+/// }
+/// ```
+///
+pub fn scan_files_bundled<I, P, T, O>(
+    file_paths: I,
+    connection: T,
+    options: O,
+    bundle_options: BundleOptions,
+) -> ScanResults
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let start = Instant::now();
+    let max_bundle_size = match options.max_stream_size {
+        Some(limit) => bundle_options.max_bundle_size.min(limit),
+        None => bundle_options.max_bundle_size,
+    };
+
+    let mut results: Vec<(PathBuf, Option<Result<ScanOutcome, ClamdError>>)> = Vec::new();
+    let mut bundles: Vec<Vec<Member>> = Vec::new();
+    let mut bundle: Vec<Member> = Vec::new();
+    let mut bundle_size = 2 * BLOCK_SIZE;
+    for file_path in file_paths {
+        let path = file_path.as_ref().to_path_buf();
+        let index = results.len();
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                results.push((path, Some(Err(err.into()))));
+                continue;
+            }
+        };
+        let member_size = BLOCK_SIZE + padded(metadata.len());
+        if !metadata.is_file()
+            || metadata.len() > bundle_options.max_file_size.min(MAX_MEMBER_SIZE)
+            || 2 * BLOCK_SIZE + member_size > max_bundle_size
+        {
+            let result = scan_single(&path, &connection, &options);
+            results.push((path, Some(result)));
+            continue;
+        }
+        // Empty files are checked here since a bundle would hide them
+        if let Err(err) = options.check_input_size(metadata.len()) {
+            results.push((path, Some(Err(err.into()))));
+            continue;
+        }
+
+        if bundle.len() == bundle_options.max_files || bundle_size + member_size > max_bundle_size {
+            bundles.push(std::mem::take(&mut bundle));
+            bundle_size = 2 * BLOCK_SIZE;
+        }
+        bundle_size += member_size;
+        bundle.push(Member {
+            index,
+            path: path.clone(),
+            len: metadata.len(),
+        });
+        results.push((path, None));
+    }
+    if !bundle.is_empty() {
+        bundles.push(bundle);
+    }
+
+    for bundle in &bundles {
+        scan_bundle(bundle, &connection, &options, &mut |index, result| {
+            results[index].1 = Some(result);
+        });
+    }
+
+    let results = results
+        .into_iter()
+        .map(|(path, result)| {
+            let result =
+                result.unwrap_or_else(|| Err(io::Error::other("the file was not scanned").into()));
+            (path, result)
+        })
+        .collect();
+    ScanResults::from_file_results(results, start.elapsed())
+}
+
+/// A file in a bundle, with its index in the results and its size when the
+/// scan started
+struct Member {
+    index: usize,
+    path: PathBuf,
+    len: u64,
+}
+
+/// Scans the members as one archive, splitting it up if a virus is found or
+/// the scan fails
+fn scan_bundle<T: TransportProtocol>(
+    members: &[Member],
+    connection: &T,
+    options: &ScanOptions,
+    set_result: &mut dyn FnMut(usize, Result<ScanOutcome, ClamdError>),
+) {
+    if let [member] = members {
+        let result = scan_single(&member.path, connection, options);
+        set_result(member.index, result);
+        return;
+    }
+
+    let mut archive = TarStream::new(members);
+    let archive_size = archive.size();
+    let mut report = ReportTimer::start(options);
+    let result = report
+        .connect(connection.connect())
+        .and_then(|stream| super::scan(&mut archive, Some(archive_size), options, stream, report))
+        .map_err(ClamdError::from)
+        .and_then(|response| ScanOutcome::from_response(&response));
+
+    match result {
+        Ok(ScanOutcome::Clean) => {
+            let mut errors = archive.errors.into_iter().peekable();
+            for (position, member) in members.iter().enumerate() {
+                match errors.next_if(|(error_position, _)| *error_position == position) {
+                    Some((_, err)) => set_result(member.index, Err(err.into())),
+                    None => set_result(member.index, Ok(ScanOutcome::Clean)),
+                }
+            }
+            return;
+        }
+        // The archive could not be sent or the response not be read, which
+        // splitting it up would not change
+        Err(err) => {
+            for member in members {
+                set_result(member.index, Err(duplicate(&err)));
+            }
+            return;
+        }
+        Ok(_) => {}
+    }
+
+    let (first, second) = members.split_at(members.len() / 2);
+    scan_bundle(first, connection, options, set_result);
+    scan_bundle(second, connection, options, set_result);
+}
+
+fn scan_single<T: TransportProtocol>(
+    path: &Path,
+    connection: &T,
+    options: &ScanOptions,
+) -> Result<ScanOutcome, ClamdError> {
+    super::_scan_file(path, connection, options)
+        .map_err(ClamdError::from)
+        .and_then(|response| ScanOutcome::from_response(&response))
+}
+
+/// Returns an error equal to `err` for each member of a failed archive scan
+fn duplicate(err: &ClamdError) -> ClamdError {
+    match err {
+        ClamdError::Io(err) => io::Error::new(err.kind(), err.to_string()).into(),
+        ClamdError::SizeLimitExceeded { limit } => ClamdError::SizeLimitExceeded { limit: *limit },
+        ClamdError::InvalidResponse { response } => ClamdError::InvalidResponse {
+            response: response.clone(),
+        },
+        ClamdError::Cancelled => ClamdError::Cancelled,
+        ClamdError::InvalidPath { path, reason } => ClamdError::InvalidPath {
+            path: path.clone(),
+            reason,
+        },
+        ClamdError::ScanFailed { message } => ClamdError::ScanFailed {
+            message: message.clone(),
+        },
+        ClamdError::InvalidOptions { reason } => ClamdError::InvalidOptions { reason },
+        ClamdError::ArchiveLimitExceeded { reason } => ClamdError::ArchiveLimitExceeded {
+            reason: reason.clone(),
+        },
+        ClamdError::EmptyInput => ClamdError::EmptyInput,
+    }
+}
+
+fn padded(len: u64) -> u64 {
+    len.div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+}
+
+/// A reader of an uncompressed tar archive of the members, opening each file
+/// only when it is reached
+///
+/// Every member has exactly the size it was bundled with: files that have
+/// grown are truncated, and files that have shrunk or cannot be read are
+/// padded with zeros, with the error recorded for the member.
+struct TarStream<'a> {
+    members: &'a [Member],
+    position: usize,
+    state: State,
+    errors: Vec<(usize, io::Error)>,
+}
+
+enum State {
+    Next,
+    Header {
+        header: Box<[u8; BLOCK_SIZE as usize]>,
+        written: usize,
+        file: Option<File>,
+    },
+    Content {
+        file: Option<File>,
+        remaining: u64,
+    },
+    Padding {
+        remaining: u64,
+    },
+    Trailer {
+        remaining: u64,
+    },
+}
+
+impl<'a> TarStream<'a> {
+    fn new(members: &'a [Member]) -> Self {
+        TarStream {
+            members,
+            position: 0,
+            state: State::Next,
+            errors: Vec::new(),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        let members: u64 = self
+            .members
+            .iter()
+            .map(|member| BLOCK_SIZE + padded(member.len))
+            .sum();
+        members + 2 * BLOCK_SIZE
+    }
+
+    fn record_error(&mut self, err: io::Error) {
+        if self.errors.last().map(|(position, _)| *position) != Some(self.position) {
+            self.errors.push((self.position, err));
+        }
+    }
+}
+
+impl Read for TarStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match &mut self.state {
+                State::Next => {
+                    let member = match self.members.get(self.position) {
+                        Some(member) => member,
+                        None => {
+                            self.state = State::Trailer {
+                                remaining: 2 * BLOCK_SIZE,
+                            };
+                            continue;
+                        }
+                    };
+                    let header = Box::new(header(self.position, member.len));
+                    let file = match File::open(&member.path) {
+                        Ok(file) => Some(file),
+                        Err(err) => {
+                            self.record_error(err);
+                            None
+                        }
+                    };
+                    self.state = State::Header {
+                        header,
+                        written: 0,
+                        file,
+                    };
+                }
+                State::Header {
+                    header,
+                    written,
+                    file,
+                } => {
+                    if *written == header.len() {
+                        self.state = State::Content {
+                            file: file.take(),
+                            remaining: self.members[self.position].len,
+                        };
+                        continue;
+                    }
+                    let len = buf.len().min(header.len() - *written);
+                    buf[..len].copy_from_slice(&header[*written..*written + len]);
+                    *written += len;
+                    return Ok(len);
+                }
+                State::Content { file, remaining } => {
+                    if *remaining == 0 {
+                        let len = self.members[self.position].len;
+                        self.state = State::Padding {
+                            remaining: padded(len) - len,
+                        };
+                        continue;
+                    }
+                    let max = buf.len().min(*remaining as usize);
+                    let read = match file {
+                        Some(file) => file.read(&mut buf[..max]),
+                        None => Ok(0),
+                    };
+                    let (len, err) = match read {
+                        Ok(0) => {
+                            // The file has shrunk or could not be opened
+                            let err = file
+                                .take()
+                                .map(|_| io::Error::from(io::ErrorKind::UnexpectedEof));
+                            buf[..max].fill(0);
+                            (max, err)
+                        }
+                        Ok(len) => (len, None),
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(err) => {
+                            file.take();
+                            buf[..max].fill(0);
+                            (max, Some(err))
+                        }
+                    };
+                    *remaining -= len as u64;
+                    if let Some(err) = err {
+                        self.record_error(err);
+                    }
+                    return Ok(len);
+                }
+                State::Padding { remaining } => {
+                    if *remaining == 0 {
+                        self.position += 1;
+                        self.state = State::Next;
+                        continue;
+                    }
+                    let len = buf.len().min(*remaining as usize);
+                    buf[..len].fill(0);
+                    *remaining -= len as u64;
+                    return Ok(len);
+                }
+                State::Trailer { remaining } => {
+                    let len = buf.len().min(*remaining as usize);
+                    buf[..len].fill(0);
+                    *remaining -= len as u64;
+                    return Ok(len);
+                }
+            }
+        }
+    }
+}
+
+/// Creates the ustar header of a regular file, named after its position since
+/// ClamAV only scans the contents
+fn header(position: usize, len: u64) -> [u8; BLOCK_SIZE as usize] {
+    let mut header = [0; BLOCK_SIZE as usize];
+    let name = format!("{:08}", position);
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", len).as_bytes());
+    header[136..148].copy_from_slice(b"00000000000\0");
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with the checksum field set to spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    header
+}
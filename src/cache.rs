@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{ClamdError, ScanOutcome};
+
+/// A store for scan outcomes keyed by the SHA-256 digest of the scanned content
+///
+/// A client with a cache looks up the digest of the content before scanning
+/// it and only sends the content to ClamAV if no outcome is cached. Clean,
+/// infected and warning outcomes are stored, never errors.
+///
+/// Warning outcomes depend on the client's
+/// [`SignatureAllowlist`](crate::SignatureAllowlist), so a cache shared
+/// between clients with different allowlists can return the verdict of
+/// another client's policy. Give each allowlist its own cache in that case.
+///
+/// The cache is called from the thread or task that performs the scan, so
+/// implementations should not block for long.
+///
+/// Use the feature flag "cache" to enable this trait.
+///
+pub trait ScanCache: Send + Sync {
+    /// Returns the cached outcome for content with the given digest, if any
+    fn lookup(&self, digest: &[u8; 32]) -> Option<ScanOutcome>;
+
+    /// Stores the outcome of scanning content with the given digest
+    fn store(&self, digest: [u8; 32], outcome: &ScanOutcome);
+}
+
+impl<C: ScanCache + ?Sized> ScanCache for Arc<C> {
+    fn lookup(&self, digest: &[u8; 32]) -> Option<ScanOutcome> {
+        (**self).lookup(digest)
+    }
+
+    fn store(&self, digest: [u8; 32], outcome: &ScanOutcome) {
+        (**self).store(digest, outcome)
+    }
+}
+
+/// An in-memory [`ScanCache`] that evicts the least recently used outcomes
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let cache = clamav_client::MemoryCache::new(10_000).ttl(Duration::from_secs(3600));
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let client = clamav_client::Client::new(clamd_tcp).cache(cache);
+///
+/// // The second scan of the same content is answered from the cache
+/// let outcome = client.scan_buffer(b"clean data").unwrap();
+/// let cached_outcome = client.scan_buffer(b"clean data").unwrap();
+/// assert_eq!(outcome, cached_outcome);
+/// ```
+///
+pub struct MemoryCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    lru: Mutex<Lru>,
+}
+
+struct Entry {
+    outcome: ScanOutcome,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+/// The entries by digest and the digests by the tick of their last use
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<[u8; 32], Entry>,
+    usage: BTreeMap<u64, [u8; 32]>,
+    tick: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, digest: [u8; 32]) -> u64 {
+        self.tick += 1;
+        self.usage.insert(self.tick, digest);
+        self.tick
+    }
+
+    fn remove(&mut self, digest: &[u8; 32]) {
+        if let Some(entry) = self.entries.remove(digest) {
+            self.usage.remove(&entry.last_used);
+        }
+    }
+}
+
+impl MemoryCache {
+    /// Creates a cache that holds up to `capacity` outcomes
+    pub fn new(capacity: usize) -> Self {
+        MemoryCache {
+            capacity,
+            ttl: None,
+            lru: Mutex::default(),
+        }
+    }
+
+    /// Sets how long an outcome stays valid after it was stored
+    ///
+    /// Set this to roughly the interval of signature updates, so that content
+    /// is rescanned with the updated signatures.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the number of cached outcomes, including expired ones that have
+    /// not been evicted yet
+    pub fn len(&self) -> usize {
+        self.lru.lock().unwrap().entries.len()
+    }
+
+    /// Returns `true` if no outcomes are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all cached outcomes
+    pub fn clear(&self) {
+        *self.lru.lock().unwrap() = Lru::default();
+    }
+}
+
+impl ScanCache for MemoryCache {
+    fn lookup(&self, digest: &[u8; 32]) -> Option<ScanOutcome> {
+        let mut lru = self.lru.lock().unwrap();
+        let entry = lru.entries.get(digest)?;
+        if self.ttl.is_some_and(|ttl| entry.stored_at.elapsed() > ttl) {
+            lru.remove(digest);
+            return None;
+        }
+
+        let last_used = entry.last_used;
+        lru.usage.remove(&last_used);
+        let tick = lru.touch(*digest);
+        let entry = lru.entries.get_mut(digest)?;
+        entry.last_used = tick;
+        Some(entry.outcome.clone())
+    }
+
+    fn store(&self, digest: [u8; 32], outcome: &ScanOutcome) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut lru = self.lru.lock().unwrap();
+        lru.remove(&digest);
+        while lru.entries.len() >= self.capacity {
+            let oldest = match lru.usage.values().next() {
+                Some(oldest) => *oldest,
+                None => break,
+            };
+            lru.remove(&oldest);
+        }
+        let last_used = lru.touch(digest);
+        lru.entries.insert(
+            digest,
+            Entry {
+                outcome: outcome.clone(),
+                stored_at: Instant::now(),
+                last_used,
+            },
+        );
+    }
+}
+
+/// A pending scan of content whose digest is looked up in a cache
+pub(crate) struct CachedScan<'a> {
+    cache: &'a dyn ScanCache,
+    digest: [u8; 32],
+}
+
+impl<'a> CachedScan<'a> {
+    pub(crate) fn new(cache: &'a dyn ScanCache, digest: [u8; 32]) -> Self {
+        CachedScan { cache, digest }
+    }
+
+    pub(crate) fn lookup(&self) -> Option<ScanOutcome> {
+        self.cache.lookup(&self.digest)
+    }
+
+    pub(crate) fn digest(&self) -> [u8; 32] {
+        self.digest
+    }
+
+    /// Stores the outcome of the scan unless it failed or ClamAV reported an
+    /// error
+    pub(crate) fn store(&self, result: &Result<ScanOutcome, ClamdError>) {
+        if let Ok(
+            outcome @ (ScanOutcome::Clean
+            | ScanOutcome::Infected { .. }
+            | ScanOutcome::Warning { .. }),
+        ) = result
+        {
+            self.cache.store(self.digest, outcome);
+        }
+    }
+}
+
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+pub(crate) use super::dedup::sha256_file;
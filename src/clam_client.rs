@@ -0,0 +1,116 @@
+use std::{future::Future, path::Path, pin::Pin};
+
+use super::{ClamdError, IoResult, ScanOutcome};
+
+/// A boxed future returned by the methods of [`ClamClient`]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An object-safe interface of the asynchronous clients, for injecting a
+/// scanner into application services
+///
+/// Since its methods return boxed futures, the trait can be used as
+/// `Arc<dyn ClamClient>` and implemented by test doubles, e.g. with
+/// `mockall`. It is implemented by the clients of the
+/// [`tokio`](crate::tokio) and [`async_std`](crate::async_std) modules for TCP
+/// and Unix socket connections.
+///
+/// Use the feature flag "tokio" or "async-std" to enable this trait.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "tokio")]
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use std::sync::Arc;
+///
+/// use clamav_client::ClamClient;
+///
+/// struct UploadService {
+///     scanner: Arc<dyn ClamClient>,
+/// }
+///
+/// impl UploadService {
+///     async fn accept(&self, upload: &[u8]) -> bool {
+///         matches!(self.scanner.scan_bytes(upload).await, Ok(outcome) if outcome.is_clean())
+///     }
+/// }
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let service = UploadService {
+///     scanner: Arc::new(clamav_client::tokio::Client::new(clamd_tcp)),
+/// };
+/// assert!(service.accept(b"clean data").await);
+/// # }
+/// # #[cfg(not(feature = "tokio"))]
+/// # fn main() {}
+/// ```
+///
+pub trait ClamClient: Send + Sync {
+    /// Scans a data buffer for viruses
+    fn scan_bytes<'a>(&'a self, bytes: &'a [u8]) -> BoxFuture<'a, Result<ScanOutcome, ClamdError>>;
+
+    /// Scans a file for viruses
+    fn scan_file<'a>(
+        &'a self,
+        file_path: &'a Path,
+    ) -> BoxFuture<'a, Result<ScanOutcome, ClamdError>>;
+
+    /// Sends a ping request to ClamAV
+    fn ping(&self) -> BoxFuture<'_, IoResult>;
+
+    /// Gets the version number from ClamAV
+    fn version(&self) -> BoxFuture<'_, IoResult>;
+}
+
+/// Implements [`ClamClient`] for the client of a runtime module with the
+/// given connection type
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+macro_rules! impl_clam_client {
+    ($client:ty, $param:ident: $($bounds:tt)*) => {
+        impl<$param> ClamClient for $client
+        where
+            $param: $($bounds)*
+        {
+            fn scan_bytes<'a>(
+                &'a self,
+                bytes: &'a [u8],
+            ) -> BoxFuture<'a, Result<ScanOutcome, ClamdError>> {
+                Box::pin(self.scan_buffer(bytes))
+            }
+
+            fn scan_file<'a>(
+                &'a self,
+                file_path: &'a Path,
+            ) -> BoxFuture<'a, Result<ScanOutcome, ClamdError>> {
+                Box::pin(<$client>::scan_file(self, file_path))
+            }
+
+            fn ping(&self) -> BoxFuture<'_, IoResult> {
+                Box::pin(<$client>::ping(self))
+            }
+
+            fn version(&self) -> BoxFuture<'_, IoResult> {
+                Box::pin(self.get_version())
+            }
+        }
+    };
+}
+
+#[cfg(feature = "tokio")]
+impl_clam_client!(crate::tokio::Client<crate::tokio::Tcp<A>>, A: tokio::net::ToSocketAddrs + Send + Sync);
+
+#[cfg(all(feature = "tokio", unix))]
+impl_clam_client!(crate::tokio::Client<crate::tokio::Socket<P>>, P: AsRef<Path> + Send + Sync);
+
+#[cfg(feature = "async-std")]
+impl_clam_client!(
+    crate::async_std::Client<crate::async_std::Tcp<A>>,
+    A: async_std::net::ToSocketAddrs<Iter: Send> + Send + Sync
+);
+
+#[cfg(all(feature = "async-std", unix))]
+impl_clam_client!(
+    crate::async_std::Client<crate::async_std::Socket<P>>,
+    P: AsRef<async_std::path::Path> + Send + Sync
+);
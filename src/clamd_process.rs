@@ -0,0 +1,253 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{is_available, Socket, Tcp};
+
+/// The number of harnesses started by this process, to name their directories
+static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+/// Options for a [`ClamdProcess`]
+///
+/// Use the feature flag "test-util" to enable this type.
+#[derive(Debug, Clone)]
+pub struct ClamdProcessOptions {
+    clamd_path: PathBuf,
+    database_directory: Option<PathBuf>,
+    stream_max_length: Option<u64>,
+    tcp_port: Option<u16>,
+    config_lines: Vec<String>,
+    startup_timeout: Duration,
+}
+
+impl Default for ClamdProcessOptions {
+    fn default() -> Self {
+        ClamdProcessOptions {
+            clamd_path: PathBuf::from("clamd"),
+            database_directory: None,
+            stream_max_length: None,
+            tcp_port: None,
+            config_lines: Vec::new(),
+            startup_timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl ClamdProcessOptions {
+    /// Creates the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path of the `clamd` executable, which is looked up in the
+    /// `PATH` by default
+    pub fn clamd_path<P: Into<PathBuf>>(mut self, clamd_path: P) -> Self {
+        self.clamd_path = clamd_path.into();
+        self
+    }
+
+    /// Sets the directory with the signature databases, instead of the
+    /// default directory of the installation of ClamAV
+    pub fn database_directory<P: Into<PathBuf>>(mut self, database_directory: P) -> Self {
+        self.database_directory = Some(database_directory.into());
+        self
+    }
+
+    /// Sets `StreamMaxLength`, the maximum size in bytes of the data scanned
+    /// with the INSTREAM command
+    pub fn stream_max_length(mut self, bytes: u64) -> Self {
+        self.stream_max_length = Some(bytes);
+        self
+    }
+
+    /// Also listens on `port` of `127.0.0.1`, in addition to the Unix socket
+    pub fn tcp_port(mut self, port: u16) -> Self {
+        self.tcp_port = Some(port);
+        self
+    }
+
+    /// Adds a line to the generated `clamd.conf`, e.g. `"MaxThreads 2"`
+    pub fn config_line<S: Into<String>>(mut self, line: S) -> Self {
+        self.config_lines.push(line.into());
+        self
+    }
+
+    /// Sets how long to wait for `clamd` to load its databases and answer a
+    /// PING, 2 minutes by default
+    pub fn startup_timeout(mut self, startup_timeout: Duration) -> Self {
+        self.startup_timeout = startup_timeout;
+        self
+    }
+}
+
+/// A `clamd` child process with a generated configuration, for integration
+/// tests and tools that need a real ClamAV server of their own
+///
+/// [`start`](Self::start) writes a `clamd.conf` into a new temporary directory,
+/// spawns `clamd` in the foreground and waits until it answers a PING on its
+/// Unix socket in the same directory. The process is killed and the directory
+/// is removed when the harness is dropped.
+///
+/// Since `clamd` has to load its signature databases, starting it takes
+/// several seconds and about 1 GB of memory, so a test suite should share a
+/// harness between its tests where possible.
+///
+/// Use the feature flag "test-util" to enable this type.
+///
+/// # Example
+///
+/// ```no_run
+/// use clamav_client::{ClamdProcess, ClamdProcessOptions};
+///
+/// let options = ClamdProcessOptions::new().stream_max_length(1_000_000);
+/// let clamd = ClamdProcess::start(options).unwrap();
+///
+/// let client = clamav_client::Client::new(clamd.socket());
+/// assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+/// ```
+///
+#[derive(Debug)]
+pub struct ClamdProcess {
+    child: Child,
+    directory: PathBuf,
+    socket_path: PathBuf,
+    tcp_port: Option<u16>,
+}
+
+impl ClamdProcess {
+    /// Starts `clamd` and waits until it is ready
+    ///
+    /// Fails with [`TimedOut`](io::ErrorKind::TimedOut) if `clamd` does not
+    /// answer within the startup timeout, or with the output of `clamd` if it
+    /// exits before it is ready.
+    pub fn start(options: ClamdProcessOptions) -> io::Result<Self> {
+        let directory = std::env::temp_dir().join(format!(
+            "clamav-client-clamd-{}-{}",
+            std::process::id(),
+            STARTED.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&directory)?;
+        let result = Self::spawn(&options, &directory);
+        let mut clamd = match result {
+            Ok(clamd) => clamd,
+            Err(err) => {
+                let _ = fs::remove_dir_all(&directory);
+                return Err(err);
+            }
+        };
+        clamd.wait_until_ready(options.startup_timeout)?;
+        Ok(clamd)
+    }
+
+    fn spawn(options: &ClamdProcessOptions, directory: &Path) -> io::Result<Self> {
+        let socket_path = directory.join("clamd.socket");
+        let config_path = directory.join("clamd.conf");
+        let mut config = String::new();
+        let _ = writeln!(config, "Foreground yes");
+        let _ = writeln!(config, "LocalSocket {}", socket_path.display());
+        let _ = writeln!(config, "TemporaryDirectory {}", directory.display());
+        if let Some(database_directory) = &options.database_directory {
+            let _ = writeln!(config, "DatabaseDirectory {}", database_directory.display());
+        }
+        if let Some(bytes) = options.stream_max_length {
+            let _ = writeln!(config, "StreamMaxLength {}", bytes);
+        }
+        if let Some(port) = options.tcp_port {
+            let _ = writeln!(config, "TCPSocket {}", port);
+            let _ = writeln!(config, "TCPAddr 127.0.0.1");
+        }
+        for line in &options.config_lines {
+            let _ = writeln!(config, "{}", line);
+        }
+        fs::write(&config_path, config)?;
+
+        let output = File::create(directory.join("clamd.log"))?;
+        let child = Command::new(&options.clamd_path)
+            .arg(format!("--config-file={}", config_path.display()))
+            .stdin(Stdio::null())
+            .stdout(output.try_clone()?)
+            .stderr(output)
+            .spawn()?;
+        Ok(ClamdProcess {
+            child,
+            directory: directory.to_path_buf(),
+            socket_path,
+            tcp_port: options.tcp_port,
+        })
+    }
+
+    fn wait_until_ready(&mut self, timeout: Duration) -> io::Result<()> {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                let output = fs::read_to_string(self.directory.join("clamd.log"))?;
+                return Err(io::Error::other(format!(
+                    "clamd exited with {} before it was ready: {}",
+                    status,
+                    output.trim()
+                )));
+            }
+            if is_available(self.socket()) {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "clamd was not ready within the startup timeout",
+                ));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Returns the path of the Unix socket of `clamd`
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Returns a connection to the Unix socket of `clamd`
+    pub fn socket(&self) -> Socket<&Path> {
+        Socket {
+            socket_path: &self.socket_path,
+        }
+    }
+
+    /// Returns a TCP connection to `clamd`, if it listens on a TCP port
+    pub fn tcp(&self) -> Option<Tcp<(&'static str, u16)>> {
+        self.tcp_port.map(|port| Tcp {
+            host_address: ("127.0.0.1", port),
+        })
+    }
+
+    /// Returns the directory with the generated `clamd.conf` and the output of
+    /// `clamd` in `clamd.log`
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Stops `clamd` and removes its directory
+    pub fn stop(mut self) -> io::Result<()> {
+        self.kill()
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        if self.child.try_wait()?.is_none() {
+            self.child.kill()?;
+            self.child.wait()?;
+        }
+        fs::remove_dir_all(&self.directory)
+    }
+}
+
+impl Drop for ClamdProcess {
+    fn drop(&mut self) {
+        let _ = self.kill();
+    }
+}
@@ -0,0 +1,324 @@
+//! The argument structs and commands of the `clamav-client` binary
+//!
+//! Other binaries can embed the `scan` and `ping` subcommands with the same
+//! flags and behavior instead of running `clamav-client`: [`ClientArgs`] holds
+//! the flags that select the ClamAV server and the output format and can be
+//! flattened into another [`clap`] parser, [`ScanArgs`] holds the flags of the
+//! `scan` subcommand, and [`ClientArgs::run`] runs a [`Command`] and returns
+//! the exit code of `clamav-client`.
+//!
+//! Like `clamscan`, the `scan` subcommand exits with `0` if no virus was found,
+//! `1` if a virus was found and `2` if an error occurred.
+//!
+//! Use the feature flag "cli" to enable this module.
+//!
+//! # Example
+//!
+//! ```
+//! use clamav_client::cli::{ClientArgs, Command, ScanArgs};
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser)]
+//! struct Deployer {
+//!     #[command(flatten)]
+//!     clamd: ClientArgs,
+//!     #[command(subcommand)]
+//!     command: DeployerCommand,
+//! }
+//!
+//! #[derive(Subcommand)]
+//! enum DeployerCommand {
+//!     /// Scans the artifacts before deploying them
+//!     Scan(ScanArgs),
+//!     /// Checks that ClamAV is available
+//!     Ping,
+//!     /// Deploys the artifacts
+//!     Deploy,
+//! }
+//!
+//! let deployer = Deployer::parse_from(["deployer", "--host", "localhost:3310", "scan", "README.md"]);
+//! match deployer.command {
+//!     DeployerCommand::Scan(scan_args) => {
+//!         deployer.clamd.run(Command::Scan(scan_args));
+//!     }
+//!     DeployerCommand::Ping => {
+//!         deployer.clamd.run(Command::Ping);
+//!     }
+//!     DeployerCommand::Deploy => {}
+//! }
+//! ```
+//!
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, Mutex},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::json;
+
+use super::{
+    ClamdError, Client, DirectoryScanOptions, IoResult, ScanOptions, ScanOutcome, ScanReport,
+    TransportProtocol,
+};
+
+/// The exit code if a virus was found
+const EXIT_VIRUS_FOUND: u8 = 1;
+
+/// The exit code if an error occurred, also used by clap for invalid arguments
+const EXIT_ERROR: u8 = 2;
+
+/// The arguments of the `clamav-client` binary
+#[derive(Debug, Clone, Parser)]
+#[command(
+    name = "clamav-client",
+    version,
+    about = "A command-line client for a ClamAV server",
+    long_about = None
+)]
+pub struct Cli {
+    /// The flags that select the ClamAV server and the output format
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    /// The subcommand to run
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// The flags that select the ClamAV server, the chunk size and the output
+/// format
+#[derive(Debug, Clone, Args)]
+pub struct ClientArgs {
+    /// The address (host and port) of the ClamAV server
+    #[arg(long, default_value = "localhost:3310")]
+    pub host: String,
+
+    /// The socket file path of the ClamAV server, used instead of TCP
+    #[cfg(unix)]
+    #[arg(long, conflicts_with = "host")]
+    pub socket: Option<PathBuf>,
+
+    /// The size of the chunks streamed to the server in bytes
+    #[arg(long)]
+    pub chunk_size: Option<usize>,
+
+    /// The format of the output
+    #[arg(long, value_enum, default_value = "text")]
+    pub output: Output,
+}
+
+/// The subcommands of the `clamav-client` binary
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Scans files and directories for viruses
+    Scan(ScanArgs),
+    /// Checks that the server is available
+    Ping,
+    /// Prints the version of the server and its virus databases
+    Version,
+    /// Reloads the virus databases of the server
+    Reload,
+    /// Prints statistics about the scan queue, threads and memory usage
+    Stats,
+}
+
+/// The flags of the `scan` subcommand
+#[derive(Debug, Clone, Args)]
+pub struct ScanArgs {
+    /// Scans directories recursively instead of only the files directly inside
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Skips files and directories matching the glob within scanned
+    /// directories, e.g. `*.log` (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// The files and directories to scan
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+}
+
+/// The format of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// Human-readable lines in the format of clamdscan
+    Text,
+    /// One JSON object per line, e.g. one per scanned file
+    Json,
+}
+
+/// Runs the subcommand of the parsed arguments of `clamav-client`, printing
+/// its output, and returns the exit code
+pub fn run(cli: Cli) -> ExitCode {
+    cli.client.run(cli.command)
+}
+
+impl ClientArgs {
+    /// Returns the scan options selected by the flags
+    pub fn options(&self) -> ScanOptions {
+        let options = ScanOptions::new();
+        match self.chunk_size {
+            Some(chunk_size) => options.chunk_size(chunk_size),
+            None => options,
+        }
+    }
+
+    /// Runs `command` with the ClamAV server selected by the flags, printing
+    /// its output, and returns the exit code
+    pub fn run(&self, command: Command) -> ExitCode {
+        #[cfg(unix)]
+        if let Some(socket_path) = &self.socket {
+            let connection = super::Socket { socket_path };
+            return execute(connection, self.options(), command, self.output);
+        }
+        let connection = super::Tcp {
+            host_address: self.host.as_str(),
+        };
+        execute(connection, self.options(), command, self.output)
+    }
+}
+
+/// Runs `command` with `connection`, printing its output in the given format,
+/// and returns the exit code
+pub fn execute<T: TransportProtocol>(
+    connection: T,
+    options: ScanOptions,
+    command: Command,
+    output: Output,
+) -> ExitCode {
+    let client = Client::new(&connection);
+    let response = match command {
+        Command::Scan(scan_args) => return scan(&connection, options, scan_args, output),
+        Command::Ping => client.ping(),
+        Command::Version => client.get_version(),
+        Command::Reload => client.reload(),
+        Command::Stats => client.get_stats(),
+    };
+    print_response(response, output)
+}
+
+/// Scans the paths of the `scan` subcommand with `connection`, printing the
+/// result of each file in the given format, and returns the exit code
+pub fn scan<T: TransportProtocol>(
+    connection: &T,
+    options: ScanOptions,
+    scan_args: ScanArgs,
+    output: Output,
+) -> ExitCode {
+    // The reports of the successful scans, for their duration and digest
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let options = options.sha256(output == Output::Json).on_report({
+        let reports = Arc::clone(&reports);
+        move |report| reports.lock().unwrap().push(report.clone())
+    });
+
+    let mut directory_options = DirectoryScanOptions::new();
+    if !scan_args.recursive {
+        directory_options = directory_options.max_depth(0);
+    }
+    for glob in scan_args.exclude {
+        directory_options = directory_options.exclude(glob);
+    }
+
+    let mut infected = false;
+    let mut errored = false;
+    for path in &scan_args.paths {
+        let results =
+            super::scan_directory(path, connection, options.clone(), directory_options.clone());
+        // Files are scanned one after another and each successful scan is
+        // reported, so the reports are in the order of the successful results
+        let mut path_reports = std::mem::take(&mut *reports.lock().unwrap()).into_iter();
+        for (path, result) in results {
+            let report = match result {
+                Ok(_) => path_reports.next(),
+                Err(_) => None,
+            };
+            match result {
+                Ok(ScanOutcome::Clean) => {}
+                Ok(ScanOutcome::Infected { .. }) => infected = true,
+                _ => errored = true,
+            }
+            match output {
+                Output::Text => print_result_text(&path, &result),
+                Output::Json => print_result_json(&path, &result, report),
+            }
+        }
+    }
+
+    if infected {
+        ExitCode::from(EXIT_VIRUS_FOUND)
+    } else if errored {
+        ExitCode::from(EXIT_ERROR)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn print_result_text(path: &Path, result: &Result<ScanOutcome, ClamdError>) {
+    match result {
+        Ok(ScanOutcome::Clean) => println!("{}: OK", path.display()),
+        Ok(ScanOutcome::Infected { signature }) => {
+            println!("{}: {} FOUND", path.display(), signature)
+        }
+        Ok(ScanOutcome::Error { message }) => println!("{}: {} ERROR", path.display(), message),
+        Ok(outcome) => println!("{}: {:?} ERROR", path.display(), outcome),
+        Err(err) => println!("{}: {} ERROR", path.display(), err),
+    }
+}
+
+fn print_result_json(
+    path: &Path,
+    result: &Result<ScanOutcome, ClamdError>,
+    report: Option<ScanReport>,
+) {
+    let (verdict, signature, error) = match result {
+        Ok(ScanOutcome::Clean) => ("clean", None, None),
+        Ok(ScanOutcome::Infected { signature }) => ("infected", Some(signature.clone()), None),
+        Ok(ScanOutcome::Error { message }) => ("error", None, Some(message.clone())),
+        Ok(outcome) => ("error", None, Some(format!("{:?}", outcome))),
+        Err(err) => ("error", None, Some(err.to_string())),
+    };
+    let duration_ms = report
+        .as_ref()
+        .map(|report| report.total_duration.as_micros() as f64 / 1000.0);
+    let sha256 = report
+        .and_then(|report| report.sha256)
+        .map(|digest| hex(&digest));
+    let line = json!({
+        "path": path.to_string_lossy(),
+        "verdict": verdict,
+        "signature": signature,
+        "error": error,
+        "duration_ms": duration_ms,
+        "sha256": sha256,
+    });
+    println!("{}", line);
+}
+
+fn print_response(response: IoResult, output: Output) -> ExitCode {
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            let err = ClamdError::from(err);
+            match output {
+                Output::Text => eprintln!("error: {}", err),
+                Output::Json => println!("{}", json!({ "error": err.to_string() })),
+            }
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+    let response = String::from_utf8_lossy(&response);
+    let response = response.trim_end_matches(['\0', '\n']);
+    match output {
+        Output::Text => println!("{}", response),
+        Output::Json => println!("{}", json!({ "response": response })),
+    }
+    ExitCode::SUCCESS
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,460 @@
+use std::{io, mem, path::Path, sync::Arc, time::Duration};
+
+#[cfg(feature = "cache")]
+use super::cache::{self, CachedScan, ScanCache};
+#[cfg(feature = "audit")]
+use super::{audit::AuditScan, AuditLog};
+use super::{
+    stats::StatsCounters, verdict, ClamdError, ClientStats, DetectionSink, FailurePolicy, IoResult,
+    ScanDecision, ScanOptions, ScanOutcome, ScanResponse, TransportProtocol, VerdictPolicy,
+};
+
+/// A reusable client for a ClamAV server
+///
+/// The client keeps the connection type and the [`ScanOptions`] of all scans,
+/// and returns a typed [`ScanOutcome`] instead of the raw server response.
+/// With the feature flag "cache", it can consult a [`ScanCache`] before
+/// sending content to the server.
+///
+/// Lifecycle hooks, e.g. [`on_connect`](Self::on_connect) or
+/// [`on_error`](Self::on_error), plug custom accounting, auditing or adaptive
+/// throttling into its scans.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::ScanOptions::new().chunk_size(8192);
+/// let client = clamav_client::Client::new(clamd_tcp).options(options);
+///
+/// let outcome = client.scan_file("README.md").unwrap();
+/// assert!(outcome.is_clean());
+/// ```
+///
+#[derive(Clone)]
+pub struct Client<T> {
+    connection: T,
+    options: ScanOptions,
+    stats: Arc<StatsCounters>,
+    timeout: Option<Duration>,
+    failure_policy: FailurePolicy,
+    verdict_policy: Option<Arc<dyn VerdictPolicy>>,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<dyn ScanCache>>,
+    #[cfg(feature = "audit")]
+    audit_log: Option<Arc<AuditLog>>,
+}
+
+impl<T: TransportProtocol> Client<T> {
+    /// Creates a client that uses `connection` and the default scan options
+    pub fn new(connection: T) -> Self {
+        let stats = Arc::new(StatsCounters::new());
+        let mut options = ScanOptions::default();
+        options.hooks.stats = Some(Arc::clone(&stats));
+        Client {
+            connection,
+            options,
+            stats,
+            timeout: None,
+            failure_policy: FailurePolicy::default(),
+            verdict_policy: None,
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "audit")]
+            audit_log: None,
+        }
+    }
+
+    /// Sets the [`ScanOptions`] used by all scans of this client
+    ///
+    /// The lifecycle hooks of the client are kept.
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        let hooks = mem::take(&mut self.options.hooks);
+        self.options = options.into();
+        self.options.hooks = hooks;
+        self
+    }
+
+    /// Calls `on_connect` with the time it took to connect to ClamAV, after
+    /// the connection of each scan is established
+    ///
+    /// Like all hooks, it is called from the thread that performs the scan, so
+    /// it should return quickly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+    /// let client = clamav_client::Client::new(clamd_tcp).on_connect(|connect_time| {
+    ///     if connect_time > std::time::Duration::from_secs(1) {
+    ///         eprintln!("Connecting to clamd is slow: {:?}", connect_time);
+    ///     }
+    /// });
+    /// # assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+    /// ```
+    ///
+    pub fn on_connect<F>(mut self, on_connect: F) -> Self
+    where
+        F: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.options.hooks.connect = Some(Arc::new(on_connect));
+        self
+    }
+
+    /// Calls `on_chunk_sent` with the size in bytes of each chunk sent to
+    /// ClamAV
+    pub fn on_chunk_sent<F>(mut self, on_chunk_sent: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.options.hooks.chunk_sent = Some(Arc::new(on_chunk_sent));
+        self
+    }
+
+    /// Calls `on_response` with the [`ScanOutcome`] of each response of
+    /// ClamAV
+    ///
+    /// It is not called for outcomes found in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let detections = Arc::new(AtomicU64::new(0));
+    /// let counter = Arc::clone(&detections);
+    ///
+    /// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+    /// let client = clamav_client::Client::new(clamd_tcp).on_response(move |outcome| {
+    ///     if outcome.is_infected() {
+    ///         counter.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// });
+    ///
+    /// client.scan_file("tests/data/eicar.txt").unwrap();
+    /// assert_eq!(detections.load(Ordering::Relaxed), 1);
+    /// ```
+    ///
+    pub fn on_response<F>(mut self, on_response: F) -> Self
+    where
+        F: Fn(&ScanOutcome) + Send + Sync + 'static,
+    {
+        self.options.hooks.response = Some(Arc::new(on_response));
+        self
+    }
+
+    /// Calls `on_error` with the [`ClamdError`] of each failed scan, e.g. if
+    /// ClamAV is not available
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: Fn(&ClamdError) + Send + Sync + 'static,
+    {
+        self.options.hooks.error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Passes each virus found by ClamAV to `sink`, in addition to the sinks
+    /// added before
+    ///
+    /// Detections of outcomes found in the cache are not passed.
+    pub fn detection_sink<S: DetectionSink + 'static>(mut self, sink: S) -> Self {
+        self.options.hooks.detection_sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Attaches `correlation_id` to all scans of this client, e.g. to a clone
+    /// of the client that scans the uploads of a single request (see
+    /// [`ScanOptions::correlation_id`])
+    ///
+    /// Options set afterwards replace it.
+    pub fn correlation_id<I: Into<String>>(mut self, correlation_id: I) -> Self {
+        self.options = self.options.correlation_id(correlation_id);
+        self
+    }
+
+    /// Waits at most `timeout` for connecting to ClamAV and for each read and
+    /// write of a command or scan (see
+    /// [`TransportProtocol::connect_timeout`])
+    ///
+    /// Operations that time out fail with
+    /// [`TimedOut`](io::ErrorKind::TimedOut) or
+    /// [`WouldBlock`](io::ErrorKind::WouldBlock), depending on the platform.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the [`FailurePolicy`] of [`decide_file`](Self::decide_file) and
+    /// [`decide_buffer`](Self::decide_buffer), which propagates errors by
+    /// default
+    pub fn failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Applies `verdict_policy` to the outcome of each scan of this client
+    /// before it is returned, replacing any policy set before
+    ///
+    /// Hooks, detection sinks, the audit log and the cache see the outcome
+    /// after the policy, so clients that share a cache should use the same
+    /// policy. Outcomes found in the cache are returned as they are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clamav_client::ScanOutcome;
+    ///
+    /// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+    /// let client = clamav_client::Client::new(clamd_tcp).verdict_policy(|outcome| match outcome {
+    ///     // Tolerate heuristic detections of broken executables
+    ///     ScanOutcome::Infected { signature } if signature.starts_with("Heuristics.Broken.") => {
+    ///         ScanOutcome::Clean
+    ///     }
+    ///     outcome => outcome,
+    /// });
+    /// # assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+    /// ```
+    ///
+    pub fn verdict_policy<V: VerdictPolicy + 'static>(mut self, verdict_policy: V) -> Self {
+        self.verdict_policy = Some(Arc::new(verdict_policy));
+        self
+    }
+
+    /// Looks up the SHA-256 digest of the content in `cache` before scanning
+    /// it, and stores the outcome of each scan in `cache`
+    ///
+    /// To share a cache between clients, pass an `Arc` of it to each client.
+    ///
+    /// Use the feature flag "cache" to enable this option.
+    #[cfg(feature = "cache")]
+    pub fn cache<C: ScanCache + 'static>(mut self, cache: C) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Records each scan in `audit_log`, including failed scans
+    ///
+    /// To share an audit log between clients, pass an `Arc` of it to each
+    /// client.
+    ///
+    /// Use the feature flag "audit" to enable this option.
+    #[cfg(feature = "audit")]
+    pub fn audit_log<A: Into<Arc<AuditLog>>>(mut self, audit_log: A) -> Self {
+        self.audit_log = Some(audit_log.into());
+        self
+    }
+
+    /// Returns the cumulative statistics of the scans of this client and its
+    /// clones
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+
+    /// Sends a ping request to ClamAV (see [`ping`](crate::ping))
+    pub fn ping(&self) -> IoResult {
+        super::ping(self.transport())
+    }
+
+    /// Gets the version number from ClamAV (see
+    /// [`get_version`](crate::get_version))
+    pub fn get_version(&self) -> IoResult {
+        super::get_version(self.transport())
+    }
+
+    /// Gets the age of the signature databases of ClamAV (see
+    /// [`database_age`](crate::database_age))
+    pub fn database_age(&self) -> Result<Duration, ClamdError> {
+        super::database_age(self.transport())
+    }
+
+    /// Checks whether the signature databases of ClamAV are older than
+    /// `max_age` (see [`is_database_stale`](crate::is_database_stale))
+    pub fn is_database_stale(&self, max_age: Duration) -> Result<bool, ClamdError> {
+        super::is_database_stale(self.transport(), max_age)
+    }
+
+    /// Reloads the virus databases of ClamAV (see [`reload`](crate::reload))
+    pub fn reload(&self) -> IoResult {
+        super::reload(self.transport())
+    }
+
+    /// Gets statistics from ClamAV (see [`get_stats`](crate::get_stats))
+    pub fn get_stats(&self) -> IoResult {
+        super::get_stats(self.transport())
+    }
+
+    /// Scans a file for viruses (see [`scan_file`](crate::scan_file))
+    ///
+    /// With a cache, the file is read once to compute its digest before it is
+    /// scanned.
+    pub fn scan_file<P: AsRef<Path>>(&self, file_path: P) -> Result<ScanOutcome, ClamdError> {
+        self.scan_file_response(file_path)
+            .map(|response| response.outcome)
+    }
+
+    /// Scans a file for viruses like [`scan_file`](Self::scan_file) and returns
+    /// the raw response of ClamAV along with the outcome
+    ///
+    /// The outcome is the one after the verdict policy of this client, and the
+    /// raw response is empty if the outcome was found in the cache.
+    pub fn scan_file_response<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<ScanResponse, ClamdError> {
+        let file_path = file_path.as_ref();
+
+        #[cfg(feature = "cache")]
+        let cached_scan = match self.cache.as_deref() {
+            Some(cache) => Some(CachedScan::new(cache, cache::sha256_file(file_path)?)),
+            None => None,
+        };
+        #[cfg(feature = "cache")]
+        if let Some(outcome) = cached_scan.as_ref().and_then(CachedScan::lookup) {
+            return Ok(ScanResponse {
+                outcome,
+                raw: Vec::new(),
+            });
+        }
+
+        #[cfg(feature = "audit")]
+        let audit_scan = AuditScan::start(self.audit_log.as_deref(), &self.options);
+        #[cfg(feature = "audit")]
+        let options = audit_scan.options();
+        #[cfg(not(feature = "audit"))]
+        let options = &self.options;
+
+        let mut raw = Vec::new();
+        let result = super::_scan_file(file_path, &self.transport(), options)
+            .map_err(ClamdError::from)
+            .and_then(|response| {
+                let outcome = ScanOutcome::from_response(&response);
+                raw = response;
+                outcome
+            })
+            .map(|outcome| verdict::apply(self.verdict_policy.as_deref(), outcome));
+        #[cfg(feature = "cache")]
+        let sha256 = cached_scan.as_ref().map(CachedScan::digest);
+        #[cfg(not(feature = "cache"))]
+        let sha256 = None;
+        self.options.hooks.result(
+            &result,
+            Some(file_path),
+            sha256,
+            self.options.correlation_id.as_deref(),
+        );
+        #[cfg(feature = "audit")]
+        let result = audit_scan.finish(Some(file_path), result);
+
+        #[cfg(feature = "cache")]
+        if let Some(cached_scan) = cached_scan {
+            cached_scan.store(&result);
+        }
+        result.map(|outcome| ScanResponse { outcome, raw })
+    }
+
+    /// Scans a data buffer for viruses (see [`scan_buffer`](crate::scan_buffer))
+    pub fn scan_buffer<B: AsRef<[u8]>>(&self, buffer: B) -> Result<ScanOutcome, ClamdError> {
+        self.scan_buffer_response(buffer)
+            .map(|response| response.outcome)
+    }
+
+    /// Scans a data buffer for viruses like [`scan_buffer`](Self::scan_buffer)
+    /// and returns the raw response of ClamAV along with the outcome
+    ///
+    /// The outcome is the one after the verdict policy of this client, and the
+    /// raw response is empty if the outcome was found in the cache.
+    pub fn scan_buffer_response<B: AsRef<[u8]>>(
+        &self,
+        buffer: B,
+    ) -> Result<ScanResponse, ClamdError> {
+        let buffer = buffer.as_ref();
+        #[cfg(feature = "cache")]
+        let cached_scan = self
+            .cache
+            .as_deref()
+            .map(|cache| CachedScan::new(cache, cache::sha256(buffer)));
+        #[cfg(feature = "cache")]
+        if let Some(outcome) = cached_scan.as_ref().and_then(CachedScan::lookup) {
+            return Ok(ScanResponse {
+                outcome,
+                raw: Vec::new(),
+            });
+        }
+
+        #[cfg(feature = "audit")]
+        let audit_scan = AuditScan::start(self.audit_log.as_deref(), &self.options);
+        #[cfg(feature = "audit")]
+        let options = audit_scan.options();
+        #[cfg(not(feature = "audit"))]
+        let options = &self.options;
+
+        let mut raw = Vec::new();
+        let result = super::scan_buffer(buffer, self.transport(), options)
+            .map_err(ClamdError::from)
+            .and_then(|response| {
+                let outcome = ScanOutcome::from_response(&response);
+                raw = response;
+                outcome
+            })
+            .map(|outcome| verdict::apply(self.verdict_policy.as_deref(), outcome));
+        #[cfg(feature = "cache")]
+        let sha256 = cached_scan.as_ref().map(CachedScan::digest);
+        #[cfg(not(feature = "cache"))]
+        let sha256 = None;
+        self.options.hooks.result(
+            &result,
+            None,
+            sha256,
+            self.options.correlation_id.as_deref(),
+        );
+        #[cfg(feature = "audit")]
+        let result = audit_scan.finish(None, result);
+
+        #[cfg(feature = "cache")]
+        if let Some(cached_scan) = cached_scan {
+            cached_scan.store(&result);
+        }
+        result.map(|outcome| ScanResponse { outcome, raw })
+    }
+
+    /// Scans a file for viruses and decides whether to accept it, applying the
+    /// [`FailurePolicy`] of this client if the scan fails
+    pub fn decide_file<P: AsRef<Path>>(&self, file_path: P) -> Result<ScanDecision, ClamdError> {
+        self.failure_policy.decide(self.scan_file(file_path))
+    }
+
+    /// Scans a data buffer for viruses and decides whether to accept it,
+    /// applying the [`FailurePolicy`] of this client if the scan fails
+    pub fn decide_buffer<B: AsRef<[u8]>>(&self, buffer: B) -> Result<ScanDecision, ClamdError> {
+        self.failure_policy.decide(self.scan_buffer(buffer))
+    }
+
+    fn transport(&self) -> Timeout<'_, T> {
+        Timeout {
+            connection: &self.connection,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// The connection of a client with its optional timeout
+struct Timeout<'a, T> {
+    connection: &'a T,
+    timeout: Option<Duration>,
+}
+
+impl<T: TransportProtocol> TransportProtocol for Timeout<'_, T> {
+    type Stream = T::Stream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        match self.timeout {
+            Some(timeout) => self.connection.connect_timeout(timeout),
+            None => self.connection.connect(),
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        self.connection.is_local()
+    }
+}
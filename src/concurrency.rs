@@ -0,0 +1,154 @@
+use std::{
+    future::Future,
+    iter::Enumerate,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+#[cfg(feature = "tokio")]
+use futures_core::Stream;
+
+use super::ClamdError;
+
+/// Future that resolves to the next item of a stream
+#[cfg(feature = "tokio")]
+pub(crate) struct Next<'a, S: ?Sized>(&'a mut S);
+
+#[cfg(feature = "tokio")]
+pub(crate) fn next<S: Stream + Unpin + ?Sized>(stream: &mut S) -> Next<'_, S> {
+    Next(stream)
+}
+
+#[cfg(feature = "tokio")]
+impl<S: Stream + Unpin + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.0).poll_next(cx)
+    }
+}
+
+/// Future that runs the given futures concurrently within the current task,
+/// with at most `limit` of them in flight at any time, and resolves to their
+/// outputs in the order of the input
+pub(crate) struct JoinBounded<I: Iterator<Item = F>, F: Future> {
+    futures: Enumerate<I>,
+    in_flight: Vec<(usize, Pin<Box<F>>)>,
+    outputs: Vec<Option<F::Output>>,
+    limit: usize,
+}
+
+pub(crate) fn join_bounded<I, F>(futures: I, limit: usize) -> JoinBounded<I::IntoIter, F>
+where
+    I: IntoIterator<Item = F>,
+    F: Future,
+{
+    JoinBounded {
+        futures: futures.into_iter().enumerate(),
+        in_flight: Vec::new(),
+        outputs: Vec::new(),
+        limit: limit.max(1),
+    }
+}
+
+// The in-flight futures are boxed, so no field is ever pinned in place
+impl<I: Iterator<Item = F>, F: Future> Unpin for JoinBounded<I, F> {}
+
+impl<I: Iterator<Item = F>, F: Future> Future for JoinBounded<I, F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            while this.in_flight.len() < this.limit {
+                match this.futures.next() {
+                    Some((index, future)) => {
+                        this.in_flight.push((index, Box::pin(future)));
+                        this.outputs.push(None);
+                    }
+                    None => break,
+                }
+            }
+            if this.in_flight.is_empty() {
+                let outputs = mem::take(&mut this.outputs);
+                return Poll::Ready(
+                    outputs
+                        .into_iter()
+                        .map(|output| output.expect("every future has completed"))
+                        .collect(),
+                );
+            }
+
+            let mut any_ready = false;
+            let mut i = 0;
+            while i < this.in_flight.len() {
+                if let Poll::Ready(output) = this.in_flight[i].1.as_mut().poll(cx) {
+                    let (index, _) = this.in_flight.swap_remove(i);
+                    this.outputs[index] = Some(output);
+                    any_ready = true;
+                } else {
+                    i += 1;
+                }
+            }
+            if !any_ready {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// Future that resolves to the output of `scan`, or to
+/// [`ClamdError::Cancelled`] as soon as `stop` completes, dropping `scan` and
+/// thereby closing its connection
+pub(crate) struct Cancellable<F, S> {
+    scan: Option<Pin<Box<F>>>,
+    stop: Pin<Box<S>>,
+}
+
+pub(crate) fn cancellable<F, S>(scan: F, stop: S) -> Cancellable<F, S> {
+    Cancellable {
+        scan: Some(Box::pin(scan)),
+        stop: Box::pin(stop),
+    }
+}
+
+impl<F, S, T, E> Future for Cancellable<F, S>
+where
+    F: Future<Output = Result<T, E>>,
+    S: Future,
+    E: From<ClamdError>,
+{
+    type Output = Result<T, E>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let scan = self
+            .scan
+            .as_mut()
+            .expect("Cancellable polled after completion");
+        if let Poll::Ready(output) = scan.as_mut().poll(cx) {
+            self.scan = None;
+            return Poll::Ready(output);
+        }
+        match self.stop.as_mut().poll(cx) {
+            Poll::Ready(_) => {
+                self.scan = None;
+                Poll::Ready(Err(ClamdError::Cancelled.into()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Returns a waker that does nothing, to poll an operation once where it
+/// cannot be awaited, e.g. in `Drop`
+pub(crate) fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(std::ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    // SAFETY: The functions of the vtable ignore the data pointer
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+}
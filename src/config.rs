@@ -0,0 +1,317 @@
+use std::{io, time::Duration};
+
+#[cfg(not(unix))]
+use std::net::TcpStream;
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+#[cfg(feature = "serde")]
+use super::{
+    validate, ClamdClientBuilder, ClamdError, FailurePolicy, ScanOptions, ValidationCheck,
+    ValidationProblem,
+};
+#[cfg(unix)]
+use super::{Socket, SocketOrTcpStream};
+use super::{Tcp, TransportProtocol};
+
+/// The port that ClamAV listens on by default
+#[cfg(feature = "serde")]
+const DEFAULT_PORT: u16 = 3310;
+
+/// The timeout of [`ClamdConfig::validate`] if no timeout is configured
+#[cfg(feature = "serde")]
+const DEFAULT_VALIDATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings of a ClamAV client that can be loaded from a configuration file,
+/// e.g. with `serde_json`, `toml` or `serde_yaml`
+///
+/// The client connects via TCP to `host` and `port`, which defaults to 3310,
+/// or to the Unix socket at `socket_path`. All other settings are optional:
+/// `timeout_ms` sets the timeout of the client in milliseconds, `chunk_size`,
+/// `max_stream_size` and `max_bytes_per_second` the corresponding
+/// [`ScanOptions`](crate::ScanOptions), and `failure_policy` is one of
+/// `"fail_open"`, `"fail_closed"` and `"propagate"`. Unknown settings are
+/// rejected.
+///
+/// [`builder`](Self::builder) turns the settings into a [`ClamdClientBuilder`],
+/// which builds the blocking client or the client of the
+/// [`tokio`](crate::tokio) or [`async_std`](crate::async_std) module.
+///
+/// Use the feature flag "serde" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// let config: clamav_client::ClamdConfig = serde_json::from_str(
+///     r#"{ "host": "localhost", "port": 3310, "timeout_ms": 30000, "chunk_size": 8192 }"#,
+/// )
+/// .unwrap();
+/// let client = config.builder().unwrap().build();
+///
+/// let outcome = client.scan_file("README.md").unwrap();
+/// assert!(outcome.is_clean());
+/// ```
+///
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ClamdConfig {
+    /// The host name or IP address of the ClamAV server, with or without
+    /// brackets around an IPv6 address
+    pub host: Option<String>,
+    /// The TCP port of the ClamAV server, 3310 by default
+    pub port: Option<u16>,
+    /// The socket file path of the ClamAV server
+    #[cfg(unix)]
+    pub socket_path: Option<PathBuf>,
+    /// The timeout of the client in milliseconds
+    pub timeout_ms: Option<u64>,
+    /// The chunk size (see [`ScanOptions::chunk_size`](crate::ScanOptions::chunk_size))
+    pub chunk_size: Option<usize>,
+    /// The maximum stream size (see
+    /// [`ScanOptions::max_stream_size`](crate::ScanOptions::max_stream_size))
+    pub max_stream_size: Option<u64>,
+    /// The upload rate limit (see
+    /// [`ScanOptions::max_bytes_per_second`](crate::ScanOptions::max_bytes_per_second))
+    pub max_bytes_per_second: Option<u64>,
+    /// The failure policy (see [`FailurePolicy`])
+    pub failure_policy: Option<FailurePolicy>,
+}
+
+#[cfg(feature = "serde")]
+impl ClamdConfig {
+    /// Returns the connection to the configured ClamAV server
+    ///
+    /// Fails with [`ClamdError::InvalidOptions`] unless either a host or a
+    /// socket path is configured.
+    pub fn connection(&self) -> Result<ClamdConnection, ClamdError> {
+        #[cfg(unix)]
+        if let Some(socket_path) = &self.socket_path {
+            if self.host.is_some() || self.port.is_some() {
+                return Err(ClamdError::InvalidOptions {
+                    reason: "a host or port and a socket path are configured",
+                });
+            }
+            return Ok(ClamdConnection::Socket(Socket {
+                socket_path: socket_path.clone(),
+            }));
+        }
+        let port = self.port.unwrap_or(DEFAULT_PORT);
+        match &self.host {
+            // IPv6 addresses must be bracketed to be followed by a port
+            Some(host) if host.contains(':') && !host.starts_with('[') => {
+                Ok(ClamdConnection::Tcp(Tcp {
+                    host_address: format!("[{}]:{}", host, port),
+                }))
+            }
+            Some(host) => Ok(ClamdConnection::Tcp(Tcp {
+                host_address: format!("{}:{}", host, port),
+            })),
+            None => Err(ClamdError::InvalidOptions {
+                reason: "neither a host nor a socket path is configured",
+            }),
+        }
+    }
+
+    /// Returns a builder for clients with the configured connection and
+    /// settings
+    ///
+    /// Fails like [`connection`](Self::connection), or with
+    /// [`ClamdError::InvalidOptions`] if a setting is invalid, e.g. an upload
+    /// rate limit of zero.
+    pub fn builder(&self) -> Result<ClamdClientBuilder<ClamdConnection>, ClamdError> {
+        let mut builder = ClamdClientBuilder::new(self.connection()?).options(self.options()?);
+        if let Some(timeout) = self.timeout() {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(failure_policy) = self.failure_policy {
+            builder = builder.failure_policy(failure_policy);
+        }
+        Ok(builder)
+    }
+
+    /// Checks the settings and that ClamAV can be reached without scanning any
+    /// data (see [`validate`](crate::validate))
+    ///
+    /// Invalid settings are reported as [`ValidationCheck::Configuration`]
+    /// problems, and ClamAV is only contacted if there are none, with the
+    /// configured timeout or a timeout of five seconds.
+    pub fn validate(&self) -> Vec<ValidationProblem> {
+        let mut problems = Vec::new();
+        let connection = match self.connection() {
+            Ok(connection) => Some(connection),
+            Err(err) => {
+                problems.push(configuration_problem(err));
+                None
+            }
+        };
+        if let Err(err) = self.options() {
+            problems.push(configuration_problem(err));
+        }
+        match connection {
+            Some(connection) if problems.is_empty() => validate(
+                &connection,
+                self.timeout().unwrap_or(DEFAULT_VALIDATION_TIMEOUT),
+            ),
+            _ => problems,
+        }
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout_ms.map(Duration::from_millis)
+    }
+
+    fn options(&self) -> Result<ScanOptions, ClamdError> {
+        let mut options = ScanOptions::new();
+        if let Some(chunk_size) = self.chunk_size {
+            options = options.chunk_size(chunk_size);
+        }
+        if let Some(max_stream_size) = self.max_stream_size {
+            options = options.max_stream_size(max_stream_size);
+        }
+        if let Some(max_bytes_per_second) = self.max_bytes_per_second {
+            options = options.max_bytes_per_second(max_bytes_per_second);
+        }
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+/// A connection to a ClamAV server either via TCP or a Unix socket, chosen at
+/// runtime, e.g. by a `ClamdConfig`
+///
+/// The connection implements the transport protocols of this crate and of the
+/// [`tokio`](crate::tokio) and [`async_std`](crate::async_std) modules.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClamdConnection {
+    /// A TCP connection
+    Tcp(Tcp<String>),
+    /// A Unix socket connection
+    #[cfg(unix)]
+    Socket(Socket<PathBuf>),
+}
+
+impl TransportProtocol for ClamdConnection {
+    #[cfg(unix)]
+    type Stream = SocketOrTcpStream;
+    #[cfg(not(unix))]
+    type Stream = TcpStream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        match self {
+            #[cfg(unix)]
+            ClamdConnection::Tcp(tcp) => tcp.connect().map(SocketOrTcpStream::Tcp),
+            #[cfg(not(unix))]
+            ClamdConnection::Tcp(tcp) => tcp.connect(),
+            #[cfg(unix)]
+            ClamdConnection::Socket(socket) => socket.connect().map(SocketOrTcpStream::Socket),
+        }
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        match self {
+            #[cfg(unix)]
+            ClamdConnection::Tcp(tcp) => tcp.connect_timeout(timeout).map(SocketOrTcpStream::Tcp),
+            #[cfg(not(unix))]
+            ClamdConnection::Tcp(tcp) => tcp.connect_timeout(timeout),
+            #[cfg(unix)]
+            ClamdConnection::Socket(socket) => socket
+                .connect_timeout(timeout)
+                .map(SocketOrTcpStream::Socket),
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        match self {
+            ClamdConnection::Tcp(tcp) => tcp.is_local(),
+            #[cfg(unix)]
+            ClamdConnection::Socket(socket) => socket.is_local(),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl crate::tokio::TransportProtocol for ClamdConnection {
+    #[cfg(unix)]
+    type Stream = crate::tokio::SocketOrTcpStream;
+    #[cfg(not(unix))]
+    type Stream = tokio::net::TcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        match self {
+            #[cfg(unix)]
+            ClamdConnection::Tcp(tcp) => tokio_tcp(tcp)
+                .connect()
+                .await
+                .map(crate::tokio::SocketOrTcpStream::Tcp),
+            #[cfg(not(unix))]
+            ClamdConnection::Tcp(tcp) => tokio_tcp(tcp).connect().await,
+            #[cfg(unix)]
+            ClamdConnection::Socket(socket) => crate::tokio::Socket {
+                socket_path: &socket.socket_path,
+            }
+            .connect()
+            .await
+            .map(crate::tokio::SocketOrTcpStream::Socket),
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        TransportProtocol::is_local(self)
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn tokio_tcp(tcp: &Tcp<String>) -> crate::tokio::Tcp<&str> {
+    crate::tokio::Tcp {
+        host_address: &tcp.host_address,
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl crate::async_std::TransportProtocol for ClamdConnection {
+    #[cfg(unix)]
+    type Stream = crate::async_std::SocketOrTcpStream;
+    #[cfg(not(unix))]
+    type Stream = async_std::net::TcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        match self {
+            #[cfg(unix)]
+            ClamdConnection::Tcp(tcp) => async_std_tcp(tcp)
+                .connect()
+                .await
+                .map(crate::async_std::SocketOrTcpStream::Tcp),
+            #[cfg(not(unix))]
+            ClamdConnection::Tcp(tcp) => async_std_tcp(tcp).connect().await,
+            #[cfg(unix)]
+            ClamdConnection::Socket(socket) => crate::async_std::Socket {
+                socket_path: async_std::path::Path::new(&socket.socket_path),
+            }
+            .connect()
+            .await
+            .map(crate::async_std::SocketOrTcpStream::Socket),
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        TransportProtocol::is_local(self)
+    }
+}
+
+#[cfg(feature = "async-std")]
+fn async_std_tcp(tcp: &Tcp<String>) -> crate::async_std::Tcp<&str> {
+    crate::async_std::Tcp {
+        host_address: &tcp.host_address,
+    }
+}
+
+#[cfg(feature = "serde")]
+fn configuration_problem(err: ClamdError) -> ValidationProblem {
+    ValidationProblem::new(ValidationCheck::Configuration, err.to_string())
+}
@@ -0,0 +1,68 @@
+use std::{io, path::PathBuf};
+
+use bytes::Bytes;
+use tokio_stream::Stream;
+
+use super::{
+    tokio::{scan_stream, Tcp},
+    IoResult, ScanOptions,
+};
+
+#[cfg(unix)]
+use super::tokio::Socket;
+
+/// A connection to a ClamAV server with concrete types, so that the futures
+/// scanning with it are `Send`
+#[derive(Clone)]
+pub(crate) enum Connection {
+    Tcp(Tcp<String>),
+    #[cfg(unix)]
+    Socket(Socket<PathBuf>),
+}
+
+impl Connection {
+    pub(crate) fn tcp<A: Into<String>>(host_address: A) -> Self {
+        Connection::Tcp(Tcp {
+            host_address: host_address.into(),
+        })
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn socket<P: Into<PathBuf>>(socket_path: P) -> Self {
+        Connection::Socket(Socket {
+            socket_path: socket_path.into(),
+        })
+    }
+
+    /// Scans a stream using this connection
+    pub(crate) async fn scan_stream<S>(&self, stream: S, options: &ScanOptions) -> IoResult
+    where
+        S: Stream<Item = Result<Bytes, io::Error>>,
+    {
+        match self {
+            Connection::Tcp(tcp) => scan_stream(stream, tcp, options).await,
+            #[cfg(unix)]
+            Connection::Socket(socket) => scan_stream(stream, socket, options).await,
+        }
+    }
+
+    /// Sends the PING command using this connection
+    #[cfg(feature = "tonic")]
+    pub(crate) async fn ping(&self) -> IoResult {
+        match self {
+            Connection::Tcp(tcp) => super::tokio::ping(tcp).await,
+            #[cfg(unix)]
+            Connection::Socket(socket) => super::tokio::ping(socket).await,
+        }
+    }
+
+    /// Sends the VERSION command using this connection
+    #[cfg(feature = "tonic")]
+    pub(crate) async fn get_version(&self) -> IoResult {
+        match self {
+            Connection::Tcp(tcp) => super::tokio::get_version(tcp).await,
+            #[cfg(unix)]
+            Connection::Socket(socket) => super::tokio::get_version(socket).await,
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::{error, fmt, io, net::SocketAddr};
+
+use super::ClamdError;
+
+/// The phase of a request in which an I/O error occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorPhase {
+    /// Reading the data to scan, e.g. opening a file
+    Input,
+    /// Resolving the host address of ClamAV
+    Resolve,
+    /// Connecting to ClamAV
+    Connect,
+    /// Starting a session with the IDSESSION command
+    Handshake,
+    /// Sending a command, e.g. PING
+    Command,
+    /// Streaming the data to scan to ClamAV
+    Stream,
+    /// Reading the response of ClamAV
+    Response,
+}
+
+impl ErrorPhase {
+    fn describe(self, f: &mut fmt::Formatter<'_>, resource: Option<&str>) -> fmt::Result {
+        match (self, resource) {
+            (ErrorPhase::Input, Some(resource)) => write!(f, "reading {}", resource),
+            (ErrorPhase::Input, None) => f.write_str("reading the data to scan"),
+            (ErrorPhase::Resolve, Some(resource)) => write!(f, "resolving {}", resource),
+            (ErrorPhase::Resolve, None) => f.write_str("resolving the address of ClamAV"),
+            (ErrorPhase::Connect, Some(resource)) => write!(f, "connecting to {}", resource),
+            (ErrorPhase::Connect, None) => f.write_str("connecting to ClamAV"),
+            (ErrorPhase::Handshake, _) => f.write_str("starting a session with ClamAV"),
+            (ErrorPhase::Command, _) => f.write_str("sending the command to ClamAV"),
+            (ErrorPhase::Stream, _) => f.write_str("streaming the data to ClamAV"),
+            (ErrorPhase::Response, _) => f.write_str("reading the response of ClamAV"),
+        }
+    }
+}
+
+/// An I/O error together with the phase of the request in which it occurred
+/// and the resource involved, e.g. the path of the scanned file or the address
+/// of ClamAV
+///
+/// The functions of this crate wrap I/O errors in an `ErrorContext` within an
+/// [`io::Error`] of the same [`kind`](io::Error::kind), so that e.g. a refused
+/// connection can be told apart from a file that cannot be opened.
+/// [`ClamdError::phase`] and [`ClamdError::resource`] return the context of an
+/// error.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{ClamdError, ErrorPhase};
+///
+/// let clamd_tcp = clamav_client::Tcp { host_address: "localhost:3310" };
+/// let err = clamav_client::scan_file("missing.txt", clamd_tcp, None).unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+///
+/// let err = ClamdError::from(err);
+/// assert_eq!(err.phase(), Some(ErrorPhase::Input));
+/// assert_eq!(err.resource(), Some("missing.txt"));
+/// ```
+///
+#[derive(Debug)]
+pub struct ErrorContext {
+    phase: ErrorPhase,
+    resource: Option<String>,
+    source: io::Error,
+}
+
+impl ErrorContext {
+    /// Returns the phase of the request in which the error occurred
+    pub fn phase(&self) -> ErrorPhase {
+        self.phase
+    }
+
+    /// Returns the resource involved, e.g. a file path, a socket path or the
+    /// addresses of ClamAV, if known
+    pub fn resource(&self) -> Option<&str> {
+        self.resource.as_deref()
+    }
+
+    /// Returns the underlying I/O error
+    pub fn io_error(&self) -> &io::Error {
+        &self.source
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.phase.describe(f, self.resource.as_deref())?;
+        write!(f, " failed: {}", self.source)
+    }
+}
+
+impl error::Error for ErrorContext {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl ClamdError {
+    /// Returns the phase of the request in which the I/O error occurred, if
+    /// known (see [`ErrorContext`])
+    pub fn phase(&self) -> Option<ErrorPhase> {
+        self.context().map(ErrorContext::phase)
+    }
+
+    /// Returns the resource involved in the I/O error, e.g. a file path, a
+    /// socket path or the addresses of ClamAV, if known (see [`ErrorContext`])
+    pub fn resource(&self) -> Option<&str> {
+        self.context().and_then(ErrorContext::resource)
+    }
+
+    fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            ClamdError::Io(err) => err.get_ref()?.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Adds an [`ErrorContext`] to the error of an I/O result
+///
+/// Errors that already have a context keep it, since the innermost context is
+/// the most precise, and errors detected by the client itself, which are
+/// wrapped [`ClamdError`]s, are kept as they are.
+pub(crate) trait ResultExt<T> {
+    /// Adds the phase in which the error occurred
+    fn phase(self, phase: ErrorPhase) -> io::Result<T>;
+
+    /// Adds the phase in which the error occurred and the resource involved
+    fn phase_of<R: fmt::Display>(self, phase: ErrorPhase, resource: R) -> io::Result<T>;
+}
+
+impl<T> ResultExt<T> for io::Result<T> {
+    fn phase(self, phase: ErrorPhase) -> io::Result<T> {
+        self.map_err(|err| wrap(err, phase, None))
+    }
+
+    fn phase_of<R: fmt::Display>(self, phase: ErrorPhase, resource: R) -> io::Result<T> {
+        self.map_err(|err| wrap(err, phase, Some(resource.to_string())))
+    }
+}
+
+fn wrap(err: io::Error, phase: ErrorPhase, resource: Option<String>) -> io::Error {
+    if let Some(inner) = err.get_ref() {
+        if inner.is::<ErrorContext>() || inner.is::<ClamdError>() {
+            return err;
+        }
+    }
+    io::Error::new(
+        err.kind(),
+        ErrorContext {
+            phase,
+            resource,
+            source: err,
+        },
+    )
+}
+
+/// Displays resolved addresses as the resource of a connection error, e.g.
+/// `[::1]:3310, 127.0.0.1:3310`
+pub(crate) struct Addresses<'a>(pub(crate) &'a [SocketAddr]);
+
+impl fmt::Display for Addresses<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, address) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", address)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the addresses that a host address resolved to, failing with the
+/// phase [`ErrorPhase::Resolve`] if it did not resolve to any address
+pub(crate) fn resolved<I: IntoIterator<Item = SocketAddr>>(
+    addresses: io::Result<I>,
+) -> io::Result<Vec<SocketAddr>> {
+    let addresses: Vec<_> = addresses.phase(ErrorPhase::Resolve)?.into_iter().collect();
+    if addresses.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        ))
+        .phase(ErrorPhase::Resolve);
+    }
+    Ok(addresses)
+}
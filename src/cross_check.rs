@@ -0,0 +1,168 @@
+use std::{path::Path, thread};
+
+use super::{scan_buffer, ClamdError, ScanOptions, ScanOutcome, TransportProtocol};
+
+/// The combined verdicts of scanning the same input on several ClamAV
+/// servers, e.g. with differently configured engines
+///
+/// An input only counts as infected or clean if all servers scanned it
+/// successfully and agree, so that a single misconfigured or outdated server
+/// cannot trigger an automatic action on its own.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CrossCheck {
+    /// The outcome of the scan on each server, or the error that occurred
+    /// while scanning, in the order of the endpoints
+    pub results: Vec<Result<ScanOutcome, ClamdError>>,
+}
+
+impl CrossCheck {
+    pub(crate) fn new(results: Vec<Result<ScanOutcome, ClamdError>>) -> Self {
+        CrossCheck { results }
+    }
+
+    /// Returns `true` if all servers found a virus
+    ///
+    /// This is `false` if there are no endpoints or any scan failed.
+    pub fn all_infected(&self) -> bool {
+        self.all(ScanOutcome::is_infected)
+    }
+
+    /// Returns `true` if all servers found no virus
+    ///
+    /// This is `false` if there are no endpoints or any scan failed.
+    pub fn all_clean(&self) -> bool {
+        self.all(ScanOutcome::is_clean)
+    }
+
+    /// Returns `true` if at least one server found a virus
+    pub fn any_infected(&self) -> bool {
+        self.results
+            .iter()
+            .any(|result| matches!(result, Ok(outcome) if outcome.is_infected()))
+    }
+
+    /// Returns `true` if the servers disagree, i.e. the input is neither
+    /// infected nor clean on all of them
+    pub fn is_disputed(&self) -> bool {
+        !self.all_infected() && !self.all_clean()
+    }
+
+    /// Returns the names of the signatures that matched on any server, without
+    /// duplicates, in the order of the endpoints
+    pub fn signatures(&self) -> Vec<&str> {
+        let mut signatures = Vec::new();
+        for result in &self.results {
+            if let Ok(ScanOutcome::Infected { signature }) = result {
+                if !signatures.contains(&signature.as_str()) {
+                    signatures.push(signature.as_str());
+                }
+            }
+        }
+        signatures
+    }
+
+    fn all(&self, f: impl Fn(&ScanOutcome) -> bool) -> bool {
+        !self.results.is_empty()
+            && self
+                .results
+                .iter()
+                .all(|result| matches!(result, Ok(outcome) if f(outcome)))
+    }
+}
+
+/// Scans a data buffer on several ClamAV servers at the same time and combines
+/// their verdicts
+///
+/// The buffer is streamed to all `endpoints` concurrently, each from its own
+/// thread over a new connection, e.g. to one server with the default settings
+/// and one with `AlertBrokenExecutables` and other heuristics enabled.
+/// Without endpoints, the [`CrossCheck`] has no results and is neither
+/// infected nor clean.
+///
+/// # Example
+///
+/// ```
+/// let endpoints = [
+///     clamav_client::Tcp{ host_address: "localhost:3310" },
+///     clamav_client::Tcp{ host_address: "127.0.0.1:3310" },
+/// ];
+/// let check = clamav_client::scan_buffer_cross_checked(b"clean data", endpoints, None);
+/// assert!(check.all_clean());
+/// ```
+///
+pub fn scan_buffer_cross_checked<B, E, T, O>(buffer: B, endpoints: E, options: O) -> CrossCheck
+where
+    B: AsRef<[u8]>,
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol + Send,
+    O: Into<ScanOptions>,
+{
+    let buffer = buffer.as_ref();
+    let options = options.into();
+    cross_check(endpoints, |connection| {
+        scan_buffer(buffer, connection, &options)
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response))
+    })
+}
+
+/// Scans a file on several ClamAV servers at the same time and combines their
+/// verdicts
+///
+/// The file is opened once per endpoint and streamed to all `endpoints`
+/// concurrently, like the buffer of [`scan_buffer_cross_checked`].
+///
+/// # Example
+///
+/// ```
+/// let endpoints = [
+///     clamav_client::Tcp{ host_address: "localhost:3310" },
+///     clamav_client::Tcp{ host_address: "127.0.0.1:3310" },
+/// ];
+/// let check = clamav_client::scan_file_cross_checked("tests/data/eicar.txt", endpoints, None);
+/// if check.all_infected() {
+///     // Both engines agree, so the file may be deleted automatically
+/// }
+/// # assert!(check.all_infected());
+/// ```
+///
+pub fn scan_file_cross_checked<P, E, T, O>(file_path: P, endpoints: E, options: O) -> CrossCheck
+where
+    P: AsRef<Path>,
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol + Send,
+    O: Into<ScanOptions>,
+{
+    let file_path = file_path.as_ref();
+    let options = options.into();
+    cross_check(endpoints, |connection| {
+        super::_scan_file(file_path, &connection, &options)
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response))
+    })
+}
+
+fn cross_check<E, T, S>(endpoints: E, scan: S) -> CrossCheck
+where
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol + Send,
+    S: Fn(T) -> Result<ScanOutcome, ClamdError> + Sync,
+{
+    let scan = &scan;
+    let results = thread::scope(|scope| {
+        let scans: Vec<_> = endpoints
+            .into_iter()
+            .map(|connection| scope.spawn(move || scan(connection)))
+            .collect();
+        scans
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    CrossCheck::new(results)
+}
@@ -0,0 +1,277 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{get_version, ClamdError, TransportProtocol};
+
+/// The version of ClamAV and of its signature databases, as reported in the
+/// response to the VERSION command
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let response = b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0";
+/// let version = clamav_client::VersionInfo::from_response(response).unwrap();
+/// assert_eq!(version.clamav_version, "1.4.1");
+/// assert_eq!(version.database_version, Some(27400));
+/// assert_eq!(
+///     version.database_date,
+///     Some(UNIX_EPOCH + Duration::from_secs(1_726_480_800))
+/// );
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    /// The version of ClamAV, e.g. `1.4.1`
+    pub clamav_version: String,
+    /// The version number of the signature databases (see
+    /// [`database_version_number`]), or `None` if ClamAV has not loaded any
+    /// databases
+    pub database_version: Option<u32>,
+    /// The date of the signature databases (see [`database_date`]), or `None`
+    /// if ClamAV has not loaded any databases
+    pub database_date: Option<SystemTime>,
+}
+
+impl VersionInfo {
+    /// Parses the response to the VERSION command, e.g.
+    /// `ClamAV 1.4.3/27431/Fri Oct 16 08:26:01 2026`
+    ///
+    /// Fails with [`ClamdError::InvalidResponse`] if the response does not
+    /// start with `ClamAV` or if the database version or date is malformed.
+    pub fn from_response(version_response: &[u8]) -> Result<Self, ClamdError> {
+        let response = String::from_utf8_lossy(version_response);
+        let response = response.trim_end_matches(['\0', '\n']);
+        let invalid = || ClamdError::InvalidResponse {
+            response: response.to_owned(),
+        };
+        let mut fields = response
+            .strip_prefix("ClamAV ")
+            .ok_or_else(invalid)?
+            .splitn(3, '/');
+        let clamav_version = match fields.next() {
+            Some(version) if !version.is_empty() => version.to_owned(),
+            _ => return Err(invalid()),
+        };
+        let database_version = match fields.next() {
+            Some(version) => Some(version.parse().map_err(|_| invalid())?),
+            None => None,
+        };
+        let database_date = match fields.next() {
+            Some(date) => Some(parse_date(date).ok_or_else(invalid)?),
+            None => None,
+        };
+        Ok(VersionInfo {
+            clamav_version,
+            database_version,
+            database_date,
+        })
+    }
+}
+
+/// Parses the date of the signature databases from the response to the
+/// VERSION command, e.g. `ClamAV 1.4.3/27431/Fri Oct 16 08:26:01 2026`
+///
+/// ClamAV formats the date in the local time of its host, without a time
+/// zone, so the date is read as UTC. Fails with
+/// [`ClamdError::InvalidResponse`] if the response has no date, e.g. when
+/// ClamAV has not loaded any databases.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let date = clamav_client::database_date(b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0").unwrap();
+/// assert_eq!(date, UNIX_EPOCH + Duration::from_secs(1_726_480_800));
+/// ```
+///
+pub fn database_date(version_response: &[u8]) -> Result<SystemTime, ClamdError> {
+    let response = String::from_utf8_lossy(version_response);
+    let response = response.trim_end_matches(['\0', '\n']);
+    response
+        .splitn(3, '/')
+        .nth(2)
+        .and_then(parse_date)
+        .ok_or_else(|| ClamdError::InvalidResponse {
+            response: response.to_owned(),
+        })
+}
+
+/// Parses the version number of the signature databases from the response to
+/// the VERSION command, e.g. `27431` from
+/// `ClamAV 1.4.3/27431/Fri Oct 16 08:26:01 2026`
+///
+/// The number is the version of the daily database, which increases with each
+/// update, so it can be compared with a minimum version or with the version
+/// published by the ClamAV project. Fails with
+/// [`ClamdError::InvalidResponse`] if the response has no version number, e.g.
+/// when ClamAV has not loaded any databases.
+///
+/// # Example
+///
+/// ```
+/// let response = b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0";
+/// let version = clamav_client::database_version_number(response).unwrap();
+/// assert!(version >= 27400);
+/// ```
+///
+pub fn database_version_number(version_response: &[u8]) -> Result<u32, ClamdError> {
+    let response = String::from_utf8_lossy(version_response);
+    let response = response.trim_end_matches(['\0', '\n']);
+    response
+        .split('/')
+        .nth(1)
+        .and_then(|version| version.parse().ok())
+        .ok_or_else(|| ClamdError::InvalidResponse {
+            response: response.to_owned(),
+        })
+}
+
+/// Gets the age of the signature databases of ClamAV
+///
+/// This function sends the VERSION command to ClamAV and returns the time
+/// elapsed since the date of the databases in the response (see
+/// [`database_date`]). A date in the future results in an age of zero.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// The age of the databases, or a [`ClamdError`] if the connection fails or the
+/// response has no date
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let age = clamav_client::database_age(clamd_tcp).unwrap();
+/// println!("The signatures are {} hours old", age.as_secs() / 3600);
+/// ```
+///
+pub fn database_age<T: TransportProtocol>(connection: T) -> Result<Duration, ClamdError> {
+    let response = get_version(connection)?;
+    age_of(&response)
+}
+
+/// Checks whether the signature databases of ClamAV are older than `max_age`
+///
+/// A service that must not report files as clean when the signatures are
+/// outdated can check this before scanning, e.g. with a `max_age` of 48 hours.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `max_age`: The maximum age of up-to-date databases
+///
+/// # Returns
+///
+/// `true` if the databases are older than `max_age`, or a [`ClamdError`] if the
+/// age cannot be determined (see [`database_age`])
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let max_age = Duration::from_secs(48 * 60 * 60);
+/// if clamav_client::is_database_stale(clamd_tcp, max_age).unwrap() {
+///     eprintln!("The signatures of ClamAV are outdated");
+/// }
+/// ```
+///
+pub fn is_database_stale<T: TransportProtocol>(
+    connection: T,
+    max_age: Duration,
+) -> Result<bool, ClamdError> {
+    database_age(connection).map(|age| age > max_age)
+}
+
+/// Parses the database version from a response like
+/// `ClamAV 1.4.3/27431/Fri Oct 16 08:26:01 2026`
+#[cfg(feature = "cache-redis")]
+pub(crate) fn database_version(version_response: &[u8]) -> Result<String, ClamdError> {
+    let response = String::from_utf8_lossy(version_response);
+    let response = response.trim_end_matches(['\0', '\n']);
+    match response.split('/').nth(1) {
+        Some(version) if !version.is_empty() => Ok(version.to_owned()),
+        _ => Err(ClamdError::InvalidResponse {
+            response: response.to_owned(),
+        }),
+    }
+}
+
+/// Returns the time elapsed since the database date in `version_response`
+pub(crate) fn age_of(version_response: &[u8]) -> Result<Duration, ClamdError> {
+    let date = database_date(version_response)?;
+    Ok(SystemTime::now()
+        .duration_since(date)
+        .unwrap_or(Duration::ZERO))
+}
+
+/// Parses a date in the format of `ctime`, e.g. `Fri Oct 16 08:26:01 2026`
+fn parse_date(date: &str) -> Option<SystemTime> {
+    let mut fields = date.split_whitespace();
+    let _weekday = fields.next()?;
+    let month = match fields.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let day: u64 = fields.next()?.parse().ok()?;
+    let mut time = fields.next()?.split(':');
+    let hours: u64 = time.next()?.parse().ok()?;
+    let minutes: u64 = time.next()?.parse().ok()?;
+    let seconds: u64 = time.next()?.parse().ok()?;
+    let year: u64 = fields.next()?.parse().ok()?;
+    if fields.next().is_some()
+        || time.next().is_some()
+        || !(1970..=9999).contains(&year)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 60
+    {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    let seconds = days
+        .checked_mul(86400)?
+        .checked_add(hours * 3600 + minutes * 60 + seconds)?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+/// Returns the number of days of a month of the Gregorian calendar
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Converts a date of the Gregorian calendar to days since the Unix epoch
+/// (see <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>)
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
@@ -0,0 +1,215 @@
+use std::{
+    ffi::OsString,
+    io,
+    path::PathBuf,
+    process::{Command, ExitStatus, Stdio},
+};
+
+use super::{is_reloading, reload, ClamdError, TransportProtocol};
+
+/// Options for running `freshclam` with [`run_freshclam`] or
+/// [`update_databases`]
+///
+/// Use the feature flag "db-update" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::FreshclamOptions::new()
+///     .freshclam_path("/usr/local/bin/freshclam")
+///     .arg("--config-file=/etc/clamav/freshclam.conf")
+///     .arg("--quiet");
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct FreshclamOptions {
+    freshclam_path: PathBuf,
+    args: Vec<OsString>,
+}
+
+impl Default for FreshclamOptions {
+    fn default() -> Self {
+        FreshclamOptions {
+            freshclam_path: PathBuf::from("freshclam"),
+            args: Vec::new(),
+        }
+    }
+}
+
+impl FreshclamOptions {
+    /// Creates the default options, which run `freshclam` from the `PATH`
+    /// without arguments
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path of the `freshclam` executable, which is looked up in the
+    /// `PATH` by default
+    pub fn freshclam_path<P: Into<PathBuf>>(mut self, freshclam_path: P) -> Self {
+        self.freshclam_path = freshclam_path.into();
+        self
+    }
+
+    /// Adds an argument to the command line of `freshclam`, e.g.
+    /// `"--datadir=/var/lib/clamav"`
+    pub fn arg<S: Into<OsString>>(mut self, arg: S) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+}
+
+/// The result of a run of `freshclam`, as told by its exit status
+///
+/// Use the feature flag "db-update" to enable this type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FreshclamStatus {
+    /// At least one database was updated (exit status 0)
+    Updated,
+    /// All databases were already up to date (exit status 1)
+    UpToDate,
+    /// The update failed, with the exit status of `freshclam` unless it was
+    /// terminated by a signal
+    Failed {
+        /// The exit status of `freshclam`
+        code: Option<i32>,
+        /// The meaning of the exit status, as documented by `freshclam`
+        reason: &'static str,
+    },
+}
+
+impl FreshclamStatus {
+    fn from_exit_status(status: ExitStatus) -> Self {
+        let code = status.code();
+        let reason = match code {
+            Some(0) => return FreshclamStatus::Updated,
+            Some(1) => return FreshclamStatus::UpToDate,
+            Some(40) => "unknown option",
+            Some(50) => "cannot change directory",
+            Some(51) => "cannot check the MD5 sum",
+            Some(52) => "connection problem",
+            Some(53) => "cannot unlink a file",
+            Some(54) => "MD5 or digital signature verification error",
+            Some(55) => "error reading a file",
+            Some(56) => "configuration file error",
+            Some(57) => "cannot create a new file",
+            Some(58) => "cannot read the database from the remote server",
+            Some(59) => "mirrors are not fully synchronized",
+            Some(60) => "cannot get information about the user",
+            Some(61) => "cannot drop privileges",
+            Some(62) => "cannot initialize the logger",
+            Some(_) => "unknown error",
+            None => "terminated by a signal",
+        };
+        FreshclamStatus::Failed { code, reason }
+    }
+}
+
+/// A completed run of `freshclam`
+///
+/// Use the feature flag "db-update" to enable this type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FreshclamReport {
+    /// The result of the update
+    pub status: FreshclamStatus,
+    /// The names of the updated databases, e.g. `daily.cld`, as reported in
+    /// the output
+    pub updated_databases: Vec<String>,
+    /// The standard output and standard error of `freshclam`
+    pub output: String,
+    /// Whether ClamAV was told to reload its databases afterwards
+    pub reloaded: bool,
+}
+
+impl FreshclamReport {
+    /// Returns `true` if at least one database was updated
+    pub fn is_updated(&self) -> bool {
+        self.status == FreshclamStatus::Updated
+    }
+}
+
+/// Runs `freshclam` to update the virus databases
+///
+/// This function runs `freshclam` with `options` until it exits and parses
+/// its exit status and output. It fails only if `freshclam` cannot be run;
+/// a failed update is reported as [`FreshclamStatus::Failed`]. Use
+/// [`update_databases`] to reload ClamAV after an update.
+///
+/// Use the feature flag "db-update" to enable this function.
+///
+/// # Example
+///
+/// ```no_run
+/// let report = clamav_client::run_freshclam(&clamav_client::FreshclamOptions::new()).unwrap();
+/// for database in &report.updated_databases {
+///     println!("updated {}", database);
+/// }
+/// ```
+///
+pub fn run_freshclam(options: &FreshclamOptions) -> io::Result<FreshclamReport> {
+    let output = Command::new(&options.freshclam_path)
+        .args(&options.args)
+        .stdin(Stdio::null())
+        .output()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(FreshclamReport {
+        status: FreshclamStatus::from_exit_status(output.status),
+        updated_databases: updated_databases(&text),
+        output: text,
+        reloaded: false,
+    })
+}
+
+/// Runs `freshclam` and tells ClamAV to reload its databases if any of them
+/// was updated
+///
+/// See [`run_freshclam`]. Fails if `freshclam` cannot be run or ClamAV does
+/// not accept the RELOAD command.
+///
+/// Use the feature flag "db-update" to enable this function.
+///
+/// # Arguments
+///
+/// * `options`: How to run `freshclam`
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Example
+///
+/// ```no_run
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::FreshclamOptions::new();
+/// let report = clamav_client::update_databases(&options, clamd_tcp).unwrap();
+/// assert_eq!(report.reloaded, report.is_updated());
+/// ```
+///
+pub fn update_databases<T: TransportProtocol>(
+    options: &FreshclamOptions,
+    connection: T,
+) -> Result<FreshclamReport, ClamdError> {
+    let mut report = run_freshclam(options)?;
+    if report.is_updated() {
+        let response = reload(connection)?;
+        if !is_reloading(&response) {
+            return Err(ClamdError::InvalidResponse {
+                response: String::from_utf8_lossy(&response).into_owned(),
+            });
+        }
+        report.reloaded = true;
+    }
+    Ok(report)
+}
+
+/// Returns the names of the databases reported as updated in the output of
+/// `freshclam`, from lines like `daily.cld updated (version: 27401, ...)`
+fn updated_databases(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (database, rest) = line.trim().split_once(' ')?;
+            rest.starts_with("updated (version:")
+                .then(|| database.to_owned())
+        })
+        .collect()
+}
@@ -0,0 +1,328 @@
+use std::io::{self, Read, Write};
+
+#[cfg(all(feature = "bytes", any(feature = "tokio", feature = "async-std")))]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use flate2::write::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+
+use super::wipe::DataBuffer;
+
+#[cfg(all(feature = "bytes", any(feature = "tokio", feature = "async-std")))]
+use futures_core::Stream;
+
+/// The size of the compressed chunks read by [`Decompress`]
+const INPUT_CHUNK_SIZE: usize = 8192;
+
+/// A compression format that input can be decompressed from before scanning
+///
+/// Use the feature flag "decompress" to enable this type.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// The gzip format (RFC 1952), including concatenated gzip members
+    Gzip,
+    /// The zlib format (RFC 1950) as well as raw deflate data (RFC 1951),
+    /// since both are sent as HTTP `Content-Encoding: deflate`
+    Deflate,
+    /// The Zstandard format (RFC 8878)
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// Returns the encoding of an HTTP `Content-Encoding` header value, e.g.
+    /// `gzip`
+    ///
+    /// Returns [`None`] for `identity` and unsupported or multiple encodings.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clamav_client::ContentEncoding;
+    ///
+    /// assert_eq!(ContentEncoding::from_header("gzip"), Some(ContentEncoding::Gzip));
+    /// assert_eq!(ContentEncoding::from_header("identity"), None);
+    /// ```
+    pub fn from_header(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Some(ContentEncoding::Gzip)
+        } else if value.eq_ignore_ascii_case("deflate") {
+            Some(ContentEncoding::Deflate)
+        } else if value.eq_ignore_ascii_case("zstd") {
+            Some(ContentEncoding::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Detects the encoding from the first bytes of the input
+    ///
+    /// Gzip, zlib and Zstandard data are detected by their headers. Raw
+    /// deflate data has no header and is not detected.
+    pub fn detect(prefix: &[u8]) -> Option<Self> {
+        match prefix {
+            [0x1f, 0x8b, ..] => Some(ContentEncoding::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(ContentEncoding::Zstd),
+            [cmf, flg, ..] if is_zlib_header(*cmf, *flg) => Some(ContentEncoding::Deflate),
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether the bytes are a zlib header with the deflate method
+fn is_zlib_header(cmf: u8, flg: u8) -> bool {
+    cmf & 0x0f == 8 && cmf >> 4 <= 7 && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0
+}
+
+enum Inner {
+    /// Deflate input before its first two bytes tell zlib and raw data apart
+    Deflate(Vec<u8>),
+    Gzip(MultiGzDecoder<Vec<u8>>),
+    Zlib(ZlibDecoder<Vec<u8>>),
+    RawDeflate(DeflateDecoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+/// A decoder that is fed compressed chunks and returns the decompressed data
+struct Decoder(Inner);
+
+impl Decoder {
+    fn new(encoding: ContentEncoding) -> io::Result<Self> {
+        let inner = match encoding {
+            ContentEncoding::Gzip => Inner::Gzip(MultiGzDecoder::new(Vec::new())),
+            ContentEncoding::Deflate => Inner::Deflate(Vec::new()),
+            ContentEncoding::Zstd => Inner::Zstd(zstd::stream::write::Decoder::new(Vec::new())?),
+        };
+        Ok(Decoder(inner))
+    }
+
+    /// Decompresses the next chunk of input, returning the data decompressed
+    /// so far
+    fn decode(&mut self, input: &[u8]) -> io::Result<Vec<u8>> {
+        if let Inner::Deflate(prefix) = &mut self.0 {
+            prefix.extend_from_slice(input);
+            if prefix.len() < 2 {
+                return Ok(Vec::new());
+            }
+            let prefix = std::mem::take(prefix);
+            self.0 = if is_zlib_header(prefix[0], prefix[1]) {
+                Inner::Zlib(ZlibDecoder::new(Vec::new()))
+            } else {
+                Inner::RawDeflate(DeflateDecoder::new(Vec::new()))
+            };
+            return self.decode(&prefix);
+        }
+
+        match &mut self.0 {
+            Inner::Deflate(_) => unreachable!("the deflate format has been determined"),
+            Inner::Gzip(decoder) => write(decoder, input),
+            Inner::Zlib(decoder) => write(decoder, input),
+            Inner::RawDeflate(decoder) => write(decoder, input),
+            Inner::Zstd(decoder) => write(decoder, input),
+        }
+    }
+
+    /// Decompresses the rest of the input, returning the remaining data
+    fn finish(mut self) -> io::Result<Vec<u8>> {
+        match &mut self.0 {
+            Inner::Deflate(prefix) if prefix.is_empty() => Ok(Vec::new()),
+            Inner::Deflate(prefix) => {
+                let prefix = std::mem::take(prefix);
+                self.0 = Inner::RawDeflate(DeflateDecoder::new(Vec::new()));
+                let mut output = self.decode(&prefix)?;
+                output.extend(self.finish()?);
+                Ok(output)
+            }
+            Inner::Gzip(decoder) => decoder.try_finish().map(|_| decoder.get_mut().split_off(0)),
+            Inner::Zlib(decoder) => decoder.try_finish().map(|_| decoder.get_mut().split_off(0)),
+            Inner::RawDeflate(decoder) => {
+                decoder.try_finish().map(|_| decoder.get_mut().split_off(0))
+            }
+            Inner::Zstd(decoder) => decoder.flush().map(|_| decoder.get_mut().split_off(0)),
+        }
+    }
+}
+
+/// The write decoders of flate2 and zstd, which write decompressed data into a
+/// vector
+trait WriteDecoder: Write {
+    fn output(&mut self) -> &mut Vec<u8>;
+}
+
+impl WriteDecoder for MultiGzDecoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+
+impl WriteDecoder for ZlibDecoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+
+impl WriteDecoder for DeflateDecoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+
+impl WriteDecoder for zstd::stream::write::Decoder<'static, Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+
+fn write<D: WriteDecoder>(decoder: &mut D, input: &[u8]) -> io::Result<Vec<u8>> {
+    decoder.write_all(input)?;
+    decoder.flush()?;
+    Ok(std::mem::take(decoder.output()))
+}
+
+/// A reader that decompresses gzip, deflate or Zstandard data read from
+/// another reader, so that ClamAV scans the decompressed content
+///
+/// ClamAV may reach a different verdict for compressed data than for the
+/// decompressed content, e.g. if it is configured not to unpack archives. The
+/// [`ScanOptions::max_stream_size`](crate::ScanOptions::max_stream_size)
+/// applies to the decompressed data, which protects against decompression
+/// bombs.
+///
+/// Use the feature flag "decompress" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// # let gzip_upload: &[u8] = &[
+/// #     0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x4b, 0xce, 0x49, 0x4d, 0xcc,
+/// #     0x53, 0x48, 0x49, 0x2c, 0x49, 0x04, 0x00, 0x6a, 0x21, 0x4f, 0xa9, 0x0a, 0x00, 0x00, 0x00,
+/// # ];
+/// use clamav_client::{ContentEncoding, Decompress};
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let encoding = ContentEncoding::from_header("gzip").unwrap();
+/// let reader = Decompress::new(gzip_upload, encoding).unwrap();
+/// let response = clamav_client::scan_reader(reader, clamd_tcp, None).unwrap();
+/// # assert_eq!(&response, b"stream: OK\0");
+/// ```
+///
+pub struct Decompress<R> {
+    input: R,
+    decoder: Option<Decoder>,
+    buffer: DataBuffer,
+    output: DataBuffer,
+    position: usize,
+}
+
+impl<R: Read> Decompress<R> {
+    /// Creates a reader that decompresses `input` in the given encoding
+    pub fn new(input: R, encoding: ContentEncoding) -> io::Result<Self> {
+        Ok(Decompress {
+            input,
+            decoder: Some(Decoder::new(encoding)?),
+            buffer: DataBuffer::default(),
+            output: DataBuffer::default(),
+            position: 0,
+        })
+    }
+
+    /// Returns the underlying reader of the compressed data
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+impl<R: Read> Read for Decompress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.position < self.output.len() {
+                let len = buf.len().min(self.output.len() - self.position);
+                buf[..len].copy_from_slice(&self.output[self.position..self.position + len]);
+                self.position += len;
+                return Ok(len);
+            }
+            let decoder = match &mut self.decoder {
+                Some(decoder) => decoder,
+                None => return Ok(0),
+            };
+            let buffer = self.buffer.get(INPUT_CHUNK_SIZE);
+            let len = match self.input.read(buffer) {
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            self.output.replace(if len == 0 {
+                self.decoder
+                    .take()
+                    .map_or(Ok(Vec::new()), Decoder::finish)?
+            } else {
+                decoder.decode(&buffer[..len])?
+            });
+            self.position = 0;
+        }
+    }
+}
+
+/// A stream that decompresses gzip, deflate or Zstandard data from another
+/// stream, to be scanned using the asynchronous `scan_stream` functions
+///
+/// See [`Decompress`] for details. Each chunk of the input stream is
+/// decompressed as it arrives.
+///
+/// Use the feature flag "decompress" together with "async-std", or with
+/// "tokio" and "bytes", to enable this type.
+///
+#[cfg(all(feature = "bytes", any(feature = "tokio", feature = "async-std")))]
+pub struct DecompressStream<S> {
+    input: Pin<Box<S>>,
+    decoder: Option<Decoder>,
+}
+
+#[cfg(all(feature = "bytes", any(feature = "tokio", feature = "async-std")))]
+impl<S> DecompressStream<S>
+where
+    S: Stream<Item = Result<bytes::Bytes, io::Error>>,
+{
+    /// Creates a stream that decompresses `input` in the given encoding
+    pub fn new(input: S, encoding: ContentEncoding) -> io::Result<Self> {
+        Ok(DecompressStream {
+            input: Box::pin(input),
+            decoder: Some(Decoder::new(encoding)?),
+        })
+    }
+}
+
+#[cfg(all(feature = "bytes", any(feature = "tokio", feature = "async-std")))]
+impl<S> Stream for DecompressStream<S>
+where
+    S: Stream<Item = Result<bytes::Bytes, io::Error>>,
+{
+    type Item = Result<bytes::Bytes, io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let decoder = match &mut this.decoder {
+                Some(decoder) => decoder,
+                None => return Poll::Ready(None),
+            };
+            let output = match this.input.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => decoder.decode(&chunk),
+                Poll::Ready(Some(Err(err))) => Err(err),
+                Poll::Ready(None) => this.decoder.take().map_or(Ok(Vec::new()), Decoder::finish),
+                Poll::Pending => return Poll::Pending,
+            };
+            match output {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => return Poll::Ready(Some(Ok(output.into()))),
+                Err(err) => {
+                    this.decoder = None;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+}
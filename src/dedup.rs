@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "sha256")]
+use std::{fs::File, io, path::Path};
+
+#[cfg(feature = "sha256")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "sha256")]
+use super::{context::ResultExt, ClamdError, ErrorPhase, ScanOutcome};
+
+/// The distinct inputs of a batch, which are scanned, and for each input the
+/// distinct input whose outcome it shares
+///
+/// See [`ScanOptions::deduplicate`](crate::ScanOptions::deduplicate).
+pub(crate) struct Duplicates {
+    distinct: Vec<usize>,
+    shares: Vec<usize>,
+}
+
+impl Duplicates {
+    /// Finds identical buffers, or treats all buffers as distinct if
+    /// deduplication is disabled
+    pub(crate) fn of_buffers<B: AsRef<[u8]>>(buffers: &[B], deduplicate: bool) -> Self {
+        if !deduplicate {
+            return Duplicates {
+                distinct: (0..buffers.len()).collect(),
+                shares: (0..buffers.len()).collect(),
+            };
+        }
+        let mut first_seen = HashMap::with_capacity(buffers.len());
+        let mut distinct = Vec::new();
+        let shares = buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| {
+                *first_seen.entry(buffer.as_ref()).or_insert_with(|| {
+                    distinct.push(index);
+                    distinct.len() - 1
+                })
+            })
+            .collect();
+        Duplicates { distinct, shares }
+    }
+
+    /// Returns the indices of the inputs to scan, in the order of the input
+    pub(crate) fn distinct(&self) -> &[usize] {
+        &self.distinct
+    }
+
+    /// Returns the outcome of each input, given the outcome of each distinct
+    /// input
+    pub(crate) fn fan_out<R: Clone>(&self, outcomes: Vec<R>) -> Vec<R> {
+        if self.distinct.len() == self.shares.len() {
+            return outcomes;
+        }
+        self.shares
+            .iter()
+            .map(|&share| outcomes[share].clone())
+            .collect()
+    }
+}
+
+/// The outcomes of the files scanned so far, keyed by the SHA-256 digest of
+/// their content
+#[cfg(feature = "sha256")]
+#[derive(Default)]
+pub(crate) struct SeenFiles {
+    outcomes: HashMap<[u8; 32], ScanOutcome>,
+}
+
+#[cfg(feature = "sha256")]
+impl SeenFiles {
+    /// Returns the outcome of a file with the same content as `file_path`, or
+    /// scans it with `scan`
+    ///
+    /// Errors are not remembered, so a duplicate of a file that could not be
+    /// scanned is scanned again.
+    pub(crate) fn scan<F>(&mut self, file_path: &Path, scan: F) -> Result<ScanOutcome, ClamdError>
+    where
+        F: FnOnce() -> Result<ScanOutcome, ClamdError>,
+    {
+        let digest = sha256_file(file_path)?;
+        if let Some(outcome) = self.outcomes.get(&digest) {
+            return Ok(outcome.clone());
+        }
+        let outcome = scan()?;
+        self.outcomes.insert(digest, outcome.clone());
+        Ok(outcome)
+    }
+}
+
+#[cfg(feature = "sha256")]
+pub(crate) fn sha256_file(file_path: &Path) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    let mut file = File::open(file_path).phase_of(ErrorPhase::Input, file_path.display())?;
+    io::copy(&mut file, &mut hasher).phase_of(ErrorPhase::Input, file_path.display())?;
+    Ok(hasher.finalize().into())
+}
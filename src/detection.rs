@@ -0,0 +1,200 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+/// A virus found by ClamAV, as passed to a [`DetectionSink`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Detection {
+    /// The name of the matching signature, e.g. `Eicar-Signature`
+    pub signature: String,
+    /// The path of the scanned file, or `None` if a buffer was scanned
+    pub path: Option<PathBuf>,
+    /// The time the response of ClamAV was received
+    pub detected_at: SystemTime,
+    /// The ID attached to the scan using `ScanOptions::correlation_id`, if any
+    pub correlation_id: Option<String>,
+    /// The SHA-256 digest of the scanned data, if the client computed it to
+    /// consult its cache
+    pub sha256: Option<[u8; 32]>,
+}
+
+impl Detection {
+    pub(crate) fn new(
+        signature: &str,
+        path: Option<&Path>,
+        sha256: Option<[u8; 32]>,
+        correlation_id: Option<&str>,
+    ) -> Self {
+        Detection {
+            signature: signature.to_owned(),
+            path: path.map(Path::to_path_buf),
+            detected_at: SystemTime::now(),
+            correlation_id: correlation_id.map(str::to_owned),
+            sha256,
+        }
+    }
+}
+
+/// A destination for detections, e.g. an alerting pipeline
+///
+/// A client calls each of its sinks whenever ClamAV reports a virus, but not
+/// for outcomes found in its cache. The sinks are called from the thread or
+/// task that performs the scan, so implementations that send detections over
+/// the network should do so in the background, like
+/// [`WebhookSink`](crate::WebhookSink).
+///
+/// Closures that take a `&Detection` are sinks, too.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let client = clamav_client::Client::new(clamd_tcp)
+///     .detection_sink(|detection: &clamav_client::Detection| {
+///         eprintln!("{} found in {:?}", detection.signature, detection.path);
+///     });
+///
+/// let outcome = client.scan_file("tests/data/eicar.txt").unwrap();
+/// assert!(outcome.is_infected());
+/// ```
+///
+pub trait DetectionSink: Send + Sync {
+    /// Handles a detection
+    fn detected(&self, detection: &Detection);
+}
+
+impl<S: DetectionSink + ?Sized> DetectionSink for Arc<S> {
+    fn detected(&self, detection: &Detection) {
+        (**self).detected(detection)
+    }
+}
+
+impl<F: Fn(&Detection) + Send + Sync> DetectionSink for F {
+    fn detected(&self, detection: &Detection) {
+        self(detection)
+    }
+}
+
+/// A detection published to the subscribers of [`DetectionEvents`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DetectionEvent {
+    /// The time the response of ClamAV was received
+    pub timestamp: SystemTime,
+    /// The ID attached to the scan using `ScanOptions::correlation_id`, if any
+    pub correlation_id: Option<String>,
+    /// The SHA-256 digest of the scanned data, if the client computed it to
+    /// consult its cache
+    pub sha256: Option<[u8; 32]>,
+    /// The name of the matching signature, e.g. `Eicar-Signature`
+    pub signature: String,
+    /// The path of the scanned file, or `None` if a buffer was scanned
+    pub path: Option<PathBuf>,
+    /// The label of the sink that published the event, e.g. `uploads`
+    pub source: Arc<str>,
+}
+
+/// A broadcast channel for detections, so that several consumers, e.g.
+/// metrics, alerting and quarantine, receive the detections of one or more
+/// clients
+///
+/// Each receiver returned by [`subscribe`](Self::subscribe) gets every event
+/// published after it subscribed. Clients publish to the channel through the
+/// sinks returned by [`sink`](Self::sink), which attach a source label to
+/// their events. Events are buffered until they are received, so subscribers
+/// should keep up with the detections or drop their receiver, which
+/// unsubscribes them.
+///
+/// # Example
+///
+/// ```
+/// let events = clamav_client::DetectionEvents::new();
+/// let alerts = events.subscribe();
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let client = clamav_client::Client::new(clamd_tcp).detection_sink(events.sink("uploads"));
+///
+/// let outcome = client.scan_file("tests/data/eicar.txt").unwrap();
+/// assert!(outcome.is_infected());
+///
+/// let event = alerts.recv().unwrap();
+/// assert_eq!(event.signature, "Eicar-Signature");
+/// assert_eq!(&*event.source, "uploads");
+/// ```
+///
+#[derive(Clone, Default)]
+pub struct DetectionEvents {
+    subscribers: Arc<Mutex<Vec<Sender<DetectionEvent>>>>,
+}
+
+impl DetectionEvents {
+    /// Creates a channel without subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a receiver for the events published from now on
+    pub fn subscribe(&self) -> Receiver<DetectionEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Returns the number of subscribers whose receivers have not been dropped
+    /// as of the last published event
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    /// Returns a sink that publishes the detections of a client to this
+    /// channel, labeled with `source`
+    pub fn sink<S: Into<Arc<str>>>(&self, source: S) -> DetectionEventSink {
+        DetectionEventSink {
+            events: self.clone(),
+            source: source.into(),
+        }
+    }
+
+    /// Sends `event` to all subscribers and removes those that unsubscribed
+    fn publish(&self, event: DetectionEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+impl fmt::Debug for DetectionEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DetectionEvents")
+            .field("subscribers", &self.subscriber_count())
+            .finish()
+    }
+}
+
+/// A [`DetectionSink`] that publishes detections to [`DetectionEvents`]
+#[derive(Debug, Clone)]
+pub struct DetectionEventSink {
+    events: DetectionEvents,
+    source: Arc<str>,
+}
+
+impl DetectionSink for DetectionEventSink {
+    fn detected(&self, detection: &Detection) {
+        self.events.publish(DetectionEvent {
+            timestamp: detection.detected_at,
+            correlation_id: detection.correlation_id.clone(),
+            sha256: detection.sha256,
+            signature: detection.signature.clone(),
+            path: detection.path.clone(),
+            source: Arc::clone(&self.source),
+        });
+    }
+}
@@ -0,0 +1,539 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    vec,
+};
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use futures_core::Stream;
+
+#[cfg(feature = "ignore")]
+use ignore::{gitignore::GitignoreBuilder, overrides::OverrideBuilder};
+
+use super::{journal::Journal, state::StateStore};
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use super::{ClamdError, ScanOutcome};
+
+/// Options that control which files a directory scan visits
+///
+/// By default, the whole directory tree is scanned, symbolic links are not
+/// followed, and files of any size are scanned.
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::DirectoryScanOptions::new()
+///     .max_depth(2)
+///     .follow_symlinks(true)
+///     .max_file_size(25 * 1024 * 1024);
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryScanOptions {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) follow_symlinks: bool,
+    pub(crate) max_file_size: Option<u64>,
+    pub(crate) journal: Option<PathBuf>,
+    pub(crate) state_store: Option<(PathBuf, u32)>,
+    pub(crate) max_concurrency: usize,
+    #[cfg(feature = "ignore")]
+    pub(crate) include: Vec<String>,
+    #[cfg(feature = "ignore")]
+    pub(crate) exclude: Vec<String>,
+    #[cfg(feature = "ignore")]
+    pub(crate) ignore_files: Vec<PathBuf>,
+}
+
+impl DirectoryScanOptions {
+    /// Creates the default directory scan options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many levels of subdirectories to descend into
+    ///
+    /// A depth of `0` only scans the files directly inside the directory.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Follows symbolic links to files and directories instead of skipping
+    /// them
+    ///
+    /// Directories that have already been visited are skipped, so symbolic
+    /// links pointing back up the tree do not cause an endless scan.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Skips files larger than the given number of bytes
+    ///
+    /// Skipped files are not part of the results.
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// Records the outcome of each scanned file in a journal file, so that an
+    /// interrupted scan can be resumed
+    ///
+    /// The journal is created if it does not exist. Files whose outcome is
+    /// already recorded are not scanned again; their recorded outcome is part
+    /// of the results instead. Files that could not be scanned are not
+    /// recorded and are scanned again when the scan is resumed, as are files
+    /// whose path is not valid UTF-8. Resume a scan with the same directory
+    /// path, and delete the journal to start a new scan from scratch.
+    ///
+    /// A journal that cannot be opened or read is reported as an error for the
+    /// journal path, and no files are scanned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let journal_path = std::env::temp_dir().join("clamav-client-doc.journal");
+    /// # let _ = std::fs::remove_file(&journal_path);
+    /// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+    /// let directory_options = clamav_client::DirectoryScanOptions::new()
+    ///     .max_file_size(1_000_000)
+    ///     .journal(&journal_path);
+    /// let results =
+    ///     clamav_client::scan_directory("tests/data", clamd_tcp, None, directory_options.clone());
+    ///
+    /// // Scanning again only reads the outcomes from the journal
+    /// let resumed_results =
+    ///     clamav_client::scan_directory("tests/data", clamd_tcp, None, directory_options);
+    /// assert_eq!(results.summary.infected, resumed_results.summary.infected);
+    /// # std::fs::remove_file(&journal_path).unwrap();
+    /// ```
+    pub fn journal<P: Into<PathBuf>>(mut self, journal_path: P) -> Self {
+        self.journal = Some(journal_path.into());
+        self
+    }
+
+    /// Records the size and modification time of each file found clean in a
+    /// state file, so that later scans skip files that have not changed since
+    ///
+    /// Unlike a [`journal`](Self::journal), which resumes a single scan, the
+    /// state store is meant to be kept between scans. The recorded states are
+    /// only valid for the given version of the virus databases, as returned by
+    /// [`version`](crate::version), so all files are scanned again after the
+    /// signatures have been updated. Skipped files are part of the results as
+    /// [`ScanOutcome::Clean`](crate::ScanOutcome::Clean); files that are not
+    /// clean or could not be scanned are always scanned again.
+    ///
+    /// The state file is created if it does not exist. A state file that
+    /// cannot be opened, read or rewritten is reported as an error for its
+    /// path, and no files are scanned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let state_path = std::env::temp_dir().join("clamav-client-doc.state");
+    /// # let _ = std::fs::remove_file(&state_path);
+    /// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+    /// let version = clamav_client::version(clamd_tcp).unwrap();
+    /// let directory_options = clamav_client::DirectoryScanOptions::new()
+    ///     .state_store(&state_path, version.database_version.unwrap());
+    /// let results =
+    ///     clamav_client::scan_directory("tests/data", clamd_tcp, None, directory_options.clone());
+    ///
+    /// // Scanning again only scans the files that were not clean
+    /// let rescan_results =
+    ///     clamav_client::scan_directory("tests/data", clamd_tcp, None, directory_options);
+    /// assert_eq!(results.summary.infected, rescan_results.summary.infected);
+    /// # std::fs::remove_file(&state_path).unwrap();
+    /// ```
+    pub fn state_store<P: Into<PathBuf>>(mut self, state_path: P, database_version: u32) -> Self {
+        self.state_store = Some((state_path.into(), database_version));
+        self
+    }
+
+    /// Sets the maximum number of files that the asynchronous
+    /// `scan_directory` functions scan at the same time, one by default
+    ///
+    /// The scans run within the returned stream, which still yields the
+    /// results in the order of the walk, so dropping the stream cancels all
+    /// scans in flight and closes their connections. The blocking
+    /// [`scan_directory`](crate::scan_directory) ignores this option.
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    /// Only scans files matching the given glob, e.g. `*.exe`
+    ///
+    /// If no include globs are set, all files are scanned. Globs are matched
+    /// relative to the scanned directory, like in a `.gitignore` file. An
+    /// invalid glob is reported as an error for the scanned directory.
+    ///
+    /// Use the feature flag "ignore" to enable this option.
+    #[cfg(feature = "ignore")]
+    pub fn include<S: Into<String>>(mut self, glob: S) -> Self {
+        self.include.push(glob.into());
+        self
+    }
+
+    /// Skips files and directories matching the given glob, e.g.
+    /// `node_modules`
+    ///
+    /// Exclude globs take precedence over include globs. Globs are matched
+    /// relative to the scanned directory, like in a `.gitignore` file. An
+    /// invalid glob is reported as an error for the scanned directory.
+    ///
+    /// Use the feature flag "ignore" to enable this option.
+    #[cfg(feature = "ignore")]
+    pub fn exclude<S: Into<String>>(mut self, glob: S) -> Self {
+        self.exclude.push(glob.into());
+        self
+    }
+
+    /// Skips files and directories matching the patterns of a
+    /// `.gitignore`-style file
+    ///
+    /// The patterns are matched relative to the scanned directory. A file that
+    /// cannot be read or parsed is reported as an error for the scanned
+    /// directory.
+    ///
+    /// Use the feature flag "ignore" to enable this option.
+    #[cfg(feature = "ignore")]
+    pub fn ignore_file<P: Into<PathBuf>>(mut self, ignore_file: P) -> Self {
+        self.ignore_files.push(ignore_file.into());
+        self
+    }
+}
+
+/// Matcher for the include and exclude globs and ignore files of a directory
+/// scan
+#[cfg(feature = "ignore")]
+struct Filter {
+    overrides: ignore::overrides::Override,
+    ignore_files: ignore::gitignore::Gitignore,
+}
+
+#[cfg(feature = "ignore")]
+impl Filter {
+    fn new(root: &Path, options: &DirectoryScanOptions) -> Result<Self, ignore::Error> {
+        let mut overrides = OverrideBuilder::new(root);
+        for glob in &options.include {
+            overrides.add(glob)?;
+        }
+        for glob in &options.exclude {
+            overrides.add(&format!("!{}", glob))?;
+        }
+
+        let mut ignore_files = GitignoreBuilder::new(root);
+        for ignore_file in &options.ignore_files {
+            if let Some(err) = ignore_files.add(ignore_file) {
+                return Err(err);
+            }
+        }
+
+        Ok(Filter {
+            overrides: overrides.build()?,
+            ignore_files: ignore_files.build()?,
+        })
+    }
+
+    fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        self.overrides.matched(path, is_dir).is_ignore()
+            || self.ignore_files.matched(path, is_dir).is_ignore()
+    }
+}
+
+/// Depth-first iterator over the files of a directory tree, in lexicographic
+/// order within each directory
+///
+/// Yields the path of every file to scan, or the path and error of every
+/// entry that could not be read.
+pub(crate) struct Walk {
+    options: DirectoryScanOptions,
+    stack: Vec<(vec::IntoIter<PathBuf>, usize)>,
+    visited: HashSet<PathBuf>,
+    journal: Option<Arc<Journal>>,
+    state: Option<Arc<StateStore>>,
+    #[cfg(feature = "ignore")]
+    filter: Option<Filter>,
+    error: Option<(PathBuf, io::Error)>,
+}
+
+impl Walk {
+    pub(crate) fn new(root: &Path, options: DirectoryScanOptions) -> Self {
+        #[cfg(feature = "ignore")]
+        let filter = match Filter::new(root, &options) {
+            Ok(filter) => Some(filter),
+            Err(err) => {
+                // Report the error for the root and do not walk the tree
+                let err = io::Error::new(io::ErrorKind::InvalidInput, err);
+                return Walk::failed(options, root.to_path_buf(), err);
+            }
+        };
+        let journal = match options.journal.as_deref().map(Journal::open) {
+            Some(Ok(journal)) => Some(Arc::new(journal)),
+            Some(Err(err)) => {
+                let journal_path = options.journal.clone().unwrap_or_default();
+                return Walk::failed(options, journal_path, err);
+            }
+            None => None,
+        };
+        let state = match &options.state_store {
+            Some((state_path, database_version)) => {
+                match StateStore::open(state_path, *database_version) {
+                    Ok(state) => Some(Arc::new(state)),
+                    Err(err) => {
+                        let state_path = state_path.clone();
+                        return Walk::failed(options, state_path, err);
+                    }
+                }
+            }
+            None => None,
+        };
+        Walk {
+            options,
+            stack: vec![(vec![root.to_path_buf()].into_iter(), 0)],
+            visited: HashSet::new(),
+            journal,
+            state,
+            #[cfg(feature = "ignore")]
+            filter,
+            error: None,
+        }
+    }
+
+    /// Creates a walk that only reports the error for `path`
+    fn failed(options: DirectoryScanOptions, path: PathBuf, err: io::Error) -> Self {
+        Walk {
+            options,
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            journal: None,
+            state: None,
+            #[cfg(feature = "ignore")]
+            filter: None,
+            error: Some((path, err)),
+        }
+    }
+
+    /// Returns the journal that records the outcomes of the walked files, if
+    /// any
+    pub(crate) fn journal(&self) -> Option<Arc<Journal>> {
+        self.journal.clone()
+    }
+
+    /// Returns the state store that records the unchanged clean files, if any
+    pub(crate) fn state(&self) -> Option<Arc<StateStore>> {
+        self.state.clone()
+    }
+
+    fn read_dir(path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+        Ok(entries)
+    }
+
+    /// Returns whether the directory has not been visited before
+    fn visit(&mut self, path: &Path) -> io::Result<bool> {
+        if !self.options.follow_symlinks {
+            return Ok(true);
+        }
+        Ok(self.visited.insert(fs::canonicalize(path)?))
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<PathBuf, (PathBuf, io::Error)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        loop {
+            let (entries, depth) = self.stack.last_mut()?;
+            let depth = *depth;
+            let path = match entries.next() {
+                Some(path) => path,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            // The root itself is always followed, even if it is a symbolic link
+            let is_root = self.stack.len() == 1;
+            let metadata = if self.options.follow_symlinks || is_root {
+                fs::metadata(&path)
+            } else {
+                fs::symlink_metadata(&path)
+            };
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(err) => return Some(Err((path, err))),
+            };
+
+            #[cfg(feature = "ignore")]
+            if let Some(filter) = &self.filter {
+                if !is_root && filter.is_excluded(&path, metadata.is_dir()) {
+                    continue;
+                }
+            }
+
+            if metadata.is_dir() {
+                let within_depth =
+                    is_root || self.options.max_depth.map_or(true, |max| depth < max);
+                if !within_depth {
+                    continue;
+                }
+                match self.visit(&path) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(err) => return Some(Err((path, err))),
+                }
+                match Self::read_dir(&path) {
+                    Ok(entries) => {
+                        let depth = if is_root { 0 } else { depth + 1 };
+                        self.stack.push((entries.into_iter(), depth));
+                    }
+                    Err(err) => return Some(Err((path, err))),
+                }
+            } else if metadata.is_file() {
+                if self
+                    .options
+                    .max_file_size
+                    .is_some_and(|max| metadata.len() > max)
+                {
+                    continue;
+                }
+                return Some(Ok(path));
+            }
+        }
+    }
+}
+
+/// Stream that scans the files of a [`Walk`], at most
+/// [`max_concurrency`](DirectoryScanOptions::max_concurrency) at a time, and
+/// yields their results in the order of the walk
+///
+/// The scan of each file is created by `scan_file`, so that the runtime
+/// modules can provide their own scan function and path type. Files whose
+/// outcome is recorded in the journal of the walk, or that are unchanged
+/// according to its state store, are not scanned again. The
+/// scans are polled by the stream itself rather than spawned, so dropping the
+/// stream drops them, which closes their connections.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub(crate) struct DirectoryScan<S, F: Future> {
+    walk: Option<Walk>,
+    journal: Option<Arc<Journal>>,
+    state: Option<Arc<StateStore>>,
+    scan_file: S,
+    max_concurrency: usize,
+    in_flight: VecDeque<InFlight<F>>,
+}
+
+/// A file of a [`DirectoryScan`] whose result has not been yielded yet
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+enum InFlight<F: Future> {
+    Scanning(PathBuf, Pin<Box<F>>),
+    Done(F::Output),
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl<S, F: Future> DirectoryScan<S, F> {
+    pub(crate) fn new(walk: Walk, scan_file: S) -> Self {
+        DirectoryScan {
+            journal: walk.journal(),
+            state: walk.state(),
+            max_concurrency: walk.options.max_concurrency.max(1),
+            walk: Some(walk),
+            scan_file,
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    /// Returns the outcome of a file that does not need to be scanned
+    fn recorded(&self, path: &Path) -> Option<ScanOutcome> {
+        if let Some(outcome) = self.journal.as_ref().and_then(|j| j.recorded(path)) {
+            return Some(outcome);
+        }
+        match &self.state {
+            Some(state) if state.is_unchanged(path) => Some(ScanOutcome::Clean),
+            _ => None,
+        }
+    }
+}
+
+// The in-flight scans are boxed, so no field is ever pinned in place
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl<S, F: Future> Unpin for DirectoryScan<S, F> {}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl<S, F, P> Stream for DirectoryScan<S, F>
+where
+    S: FnMut(PathBuf) -> F,
+    F: Future<Output = (P, Result<ScanOutcome, ClamdError>)>,
+    P: From<PathBuf>,
+{
+    type Item = (P, Result<ScanOutcome, ClamdError>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while this.in_flight.len() < this.max_concurrency {
+            let next = match this.walk.as_mut().map(Iterator::next) {
+                Some(Some(next)) => next,
+                _ => {
+                    this.walk = None;
+                    break;
+                }
+            };
+            let in_flight = match next {
+                Ok(path) => match this.recorded(&path) {
+                    Some(outcome) => InFlight::Done((path.into(), Ok(outcome))),
+                    None => {
+                        let scan = Box::pin((this.scan_file)(path.clone()));
+                        InFlight::Scanning(path, scan)
+                    }
+                },
+                Err((path, err)) => InFlight::Done((path.into(), Err(err.into()))),
+            };
+            this.in_flight.push_back(in_flight);
+        }
+
+        for in_flight in this.in_flight.iter_mut() {
+            if let InFlight::Scanning(path, scan) = in_flight {
+                if let Poll::Ready(item) = scan.as_mut().poll(cx) {
+                    if let Some(journal) = &this.journal {
+                        journal.record(path, &item.1);
+                    }
+                    if let Some(state) = &this.state {
+                        state.record(path, &item.1);
+                    }
+                    *in_flight = InFlight::Done(item);
+                }
+            }
+        }
+
+        // Yield the results in the order of the walk
+        match this.in_flight.pop_front() {
+            Some(InFlight::Done(item)) => Poll::Ready(Some(item)),
+            Some(scanning) => {
+                this.in_flight.push_front(scanning);
+                Poll::Pending
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
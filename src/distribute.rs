@@ -0,0 +1,434 @@
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex},
+    thread,
+    time::Instant,
+};
+
+use super::{
+    scan_buffer, ClamdError, ScanOptions, ScanOutcome, ScanResults, ScanSummary, TransportProtocol,
+};
+
+/// How [`scan_files_distributed`] and [`scan_buffers_distributed`] assign
+/// scans to the endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Distribution {
+    /// The scans are spread evenly across the endpoints, and endpoints that
+    /// run out of scans take over scans queued for slower endpoints
+    #[default]
+    WorkStealing,
+    /// Each scan is assigned to an endpoint by the hash of its path or
+    /// content, so that repeated scans of the same input hit the same
+    /// endpoint, e.g. to make use of a cache in front of it
+    ///
+    /// If an endpoint fails, only its scans are assigned to the remaining
+    /// endpoints.
+    Hashed,
+}
+
+/// Options that control how scans are distributed across multiple ClamAV
+/// servers
+///
+/// By default, each endpoint runs 4 scans at a time using
+/// [`Distribution::WorkStealing`], and an endpoint is no longer used after 3
+/// consecutive connection failures.
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::DistributionOptions::new()
+///     .distribution(clamav_client::Distribution::Hashed)
+///     .workers_per_endpoint(8)
+///     .max_endpoint_failures(5);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct DistributionOptions {
+    pub(crate) distribution: Distribution,
+    pub(crate) workers_per_endpoint: usize,
+    pub(crate) max_endpoint_failures: usize,
+}
+
+impl Default for DistributionOptions {
+    fn default() -> Self {
+        DistributionOptions {
+            distribution: Distribution::default(),
+            workers_per_endpoint: 4,
+            max_endpoint_failures: 3,
+        }
+    }
+}
+
+impl DistributionOptions {
+    /// Creates the default distribution options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how scans are assigned to the endpoints
+    pub fn distribution(mut self, distribution: Distribution) -> Self {
+        self.distribution = distribution;
+        self
+    }
+
+    /// Sets how many scans run at a time on each endpoint
+    ///
+    /// Keep this below the `MaxThreads` setting of ClamAV (10 by default).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers_per_endpoint` is zero.
+    pub fn workers_per_endpoint(mut self, workers_per_endpoint: usize) -> Self {
+        assert!(
+            workers_per_endpoint > 0,
+            "the number of workers per endpoint must not be zero"
+        );
+        self.workers_per_endpoint = workers_per_endpoint;
+        self
+    }
+
+    /// Sets after how many consecutive connection failures an endpoint is no
+    /// longer used
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_endpoint_failures` is zero.
+    pub fn max_endpoint_failures(mut self, max_endpoint_failures: usize) -> Self {
+        assert!(
+            max_endpoint_failures > 0,
+            "the maximum number of endpoint failures must not be zero"
+        );
+        self.max_endpoint_failures = max_endpoint_failures;
+        self
+    }
+}
+
+/// Scans multiple files for viruses, distributed across multiple ClamAV
+/// servers
+///
+/// This function scans the files in parallel on all `endpoints`, using
+/// [`DistributionOptions::workers_per_endpoint`] threads and a new connection
+/// per file on each endpoint. If a scan fails because the endpoint cannot be
+/// reached or drops the connection, the file is scanned again on another
+/// endpoint, and an endpoint that fails
+/// [`DistributionOptions::max_endpoint_failures`] times in a row is no longer
+/// used. Other errors, e.g. of files that cannot be read, are reported as
+/// they are.
+///
+/// # Arguments
+///
+/// * `file_paths`: The paths to the files to be scanned
+/// * `endpoints`: The connection types of the ClamAV servers to use
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `distribution_options`: The [`DistributionOptions`] that control how the files are distributed
+///
+/// # Returns
+///
+/// The [`ScanResults`] with the path of each file together with its
+/// [`ScanOutcome`], or the error that occurred while reading or scanning it,
+/// in the order of the input. If no endpoint is left, the remaining files
+/// fail with an error of the kind [`io::ErrorKind::NotConnected`].
+///
+/// # Example
+///
+/// ```
+/// let endpoints = [
+///     clamav_client::Tcp{ host_address: "localhost:3310" },
+///     clamav_client::Tcp{ host_address: "127.0.0.1:3310" },
+/// ];
+/// let file_paths = ["README.md", "tests/data/eicar.txt", "Cargo.toml"];
+/// let distribution_options = clamav_client::DistributionOptions::new();
+/// let results =
+///     clamav_client::scan_files_distributed(file_paths, endpoints, None, distribution_options);
+/// println!("{}", results.summary);
+/// # assert_eq!(results.summary.infected, 1);
+/// ```
+///
+pub fn scan_files_distributed<I, P, E, T, O>(
+    file_paths: I,
+    endpoints: E,
+    options: O,
+    distribution_options: DistributionOptions,
+) -> ScanResults
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol + Sync,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let start = Instant::now();
+    let paths: Vec<PathBuf> = file_paths
+        .into_iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect();
+    let endpoints: Vec<T> = endpoints.into_iter().collect();
+    let results = distribute(
+        &endpoints,
+        paths.iter().map(key_hash).collect(),
+        &distribution_options,
+        |connection, index| {
+            super::_scan_file(&paths[index], connection, &options)
+                .map_err(ClamdError::from)
+                .and_then(|response| ScanOutcome::from_response(&response))
+        },
+    );
+    ScanResults::from_file_results(paths.into_iter().zip(results).collect(), start.elapsed())
+}
+
+/// Scans multiple data buffers for viruses, distributed across multiple
+/// ClamAV servers
+///
+/// The buffers are distributed like the files of [`scan_files_distributed`],
+/// using a new connection per buffer.
+///
+/// # Arguments
+///
+/// * `buffers`: The data buffers to be scanned
+/// * `endpoints`: The connection types of the ClamAV servers to use
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `distribution_options`: The [`DistributionOptions`] that control how the buffers are distributed
+///
+/// # Returns
+///
+/// The [`ScanResults`] with the index of each buffer together with its
+/// [`ScanOutcome`], or the error that occurred while scanning it, in the
+/// order of the input
+///
+/// # Example
+///
+/// ```
+/// let endpoints = [
+///     clamav_client::Tcp{ host_address: "localhost:3310" },
+///     clamav_client::Tcp{ host_address: "127.0.0.1:3310" },
+/// ];
+/// let buffers = [&b"clean data"[..], &b"more clean data"[..]];
+/// let distribution_options = clamav_client::DistributionOptions::new();
+/// let results =
+///     clamav_client::scan_buffers_distributed(buffers, endpoints, None, distribution_options);
+/// # assert_eq!(results.summary.clean, 2);
+/// ```
+///
+pub fn scan_buffers_distributed<I, B, E, T, O>(
+    buffers: I,
+    endpoints: E,
+    options: O,
+    distribution_options: DistributionOptions,
+) -> ScanResults<usize>
+where
+    I: IntoIterator<Item = B>,
+    B: AsRef<[u8]> + Sync,
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol + Sync,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let start = Instant::now();
+    let buffers: Vec<B> = buffers.into_iter().collect();
+    let endpoints: Vec<T> = endpoints.into_iter().collect();
+    let results = distribute(
+        &endpoints,
+        buffers
+            .iter()
+            .map(|buffer| key_hash(buffer.as_ref()))
+            .collect(),
+        &distribution_options,
+        |connection, index| {
+            scan_buffer(&buffers[index], connection, &options)
+                .map_err(ClamdError::from)
+                .and_then(|response| ScanOutcome::from_response(&response))
+        },
+    );
+    let mut summary = ScanSummary::default();
+    for (buffer, result) in buffers.iter().zip(&results) {
+        summary.add(result, buffer.as_ref().len() as u64);
+    }
+    summary.elapsed = start.elapsed();
+    ScanResults {
+        results: results.into_iter().enumerate().collect(),
+        summary,
+    }
+}
+
+fn key_hash<K: Hash>(key: K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns `true` if a scan failed because the endpoint could not be reached
+/// or dropped the connection, so that it can be retried on another endpoint
+fn is_endpoint_failure(err: &ClamdError) -> bool {
+    match err {
+        ClamdError::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::AddrNotAvailable
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+/// The scans shared by the workers of all endpoints
+struct Scans {
+    /// The indices of the scans queued for each endpoint
+    queues: Vec<VecDeque<usize>>,
+    /// The consecutive connection failures of each endpoint
+    failures: Vec<usize>,
+    /// Whether each endpoint is still used
+    available: Vec<bool>,
+    /// The endpoints each scan failed on
+    attempts: Vec<Vec<usize>>,
+    results: Vec<Option<Result<ScanOutcome, ClamdError>>>,
+    /// The number of scans that are running
+    running: usize,
+}
+
+impl Scans {
+    /// Returns the next scan for a worker of `endpoint`, if any
+    fn next(&mut self, endpoint: usize, distribution: Distribution) -> Option<usize> {
+        if let Some(index) = self.queues[endpoint].pop_front() {
+            return Some(index);
+        }
+        if distribution == Distribution::Hashed {
+            return None;
+        }
+        let longest = (0..self.queues.len()).max_by_key(|&other| self.queues[other].len())?;
+        self.queues[longest].pop_back()
+    }
+
+    /// Queues a scan for the available endpoint it has not failed on with the
+    /// highest rendezvous hash, or stores the error if there is none
+    fn queue(&mut self, index: usize, hash: u64, err: Option<ClamdError>) {
+        let attempts = &self.attempts[index];
+        let endpoint = (0..self.queues.len())
+            .filter(|endpoint| self.available[*endpoint] && !attempts.contains(endpoint))
+            .max_by_key(|&endpoint| key_hash((hash, endpoint)));
+        match endpoint {
+            Some(endpoint) => self.queues[endpoint].push_back(index),
+            None => self.results[index] = Some(Err(err.unwrap_or_else(no_endpoint_error))),
+        }
+    }
+
+    /// Records a connection failure of `endpoint` and stops using it after
+    /// too many, queueing its scans for the other endpoints
+    fn fail(&mut self, endpoint: usize, max_failures: usize, hashes: &[u64]) {
+        self.failures[endpoint] += 1;
+        if self.failures[endpoint] < max_failures || !self.available[endpoint] {
+            return;
+        }
+        self.available[endpoint] = false;
+        for index in std::mem::take(&mut self.queues[endpoint]) {
+            self.queue(index, hashes[index], None);
+        }
+    }
+}
+
+/// Runs `scan` for each of the inputs with the given hashes on the workers of
+/// all endpoints and returns the results in the order of the inputs
+fn distribute<T, F>(
+    endpoints: &[T],
+    hashes: Vec<u64>,
+    distribution_options: &DistributionOptions,
+    scan: F,
+) -> Vec<Result<ScanOutcome, ClamdError>>
+where
+    T: TransportProtocol + Sync,
+    F: Fn(&T, usize) -> Result<ScanOutcome, ClamdError> + Sync,
+{
+    let distribution = distribution_options.distribution;
+    let mut scans = Scans {
+        queues: vec![VecDeque::new(); endpoints.len()],
+        failures: vec![0; endpoints.len()],
+        available: vec![true; endpoints.len()],
+        attempts: vec![Vec::new(); hashes.len()],
+        results: (0..hashes.len()).map(|_| None).collect(),
+        running: 0,
+    };
+    for (index, &hash) in hashes.iter().enumerate() {
+        match distribution {
+            Distribution::WorkStealing if !endpoints.is_empty() => {
+                scans.queues[index % endpoints.len()].push_back(index)
+            }
+            _ => scans.queue(index, hash, None),
+        }
+    }
+    let scans = Mutex::new(scans);
+    let changed = Condvar::new();
+
+    thread::scope(|scope| {
+        for (endpoint, connection) in endpoints.iter().enumerate() {
+            for _ in 0..distribution_options.workers_per_endpoint {
+                let (scans, changed, hashes, scan) = (&scans, &changed, &hashes, &scan);
+                scope.spawn(move || {
+                    let mut guard = scans.lock().unwrap();
+                    loop {
+                        if !guard.available[endpoint] {
+                            return;
+                        }
+                        let index = match guard.next(endpoint, distribution) {
+                            Some(index) => index,
+                            // Scans that fail on other endpoints may still be
+                            // queued for this one
+                            None if guard.running > 0
+                                || guard.queues.iter().any(|queue| !queue.is_empty()) =>
+                            {
+                                guard = changed.wait(guard).unwrap();
+                                continue;
+                            }
+                            None => {
+                                changed.notify_all();
+                                return;
+                            }
+                        };
+                        guard.running += 1;
+                        drop(guard);
+                        let result = scan(connection, index);
+                        guard = scans.lock().unwrap();
+                        guard.running -= 1;
+                        match result {
+                            Err(err) if is_endpoint_failure(&err) => {
+                                guard.attempts[index].push(endpoint);
+                                guard.fail(
+                                    endpoint,
+                                    distribution_options.max_endpoint_failures,
+                                    hashes,
+                                );
+                                guard.queue(index, hashes[index], Some(err));
+                            }
+                            result => {
+                                guard.failures[endpoint] = 0;
+                                guard.results[index] = Some(result);
+                            }
+                        }
+                        changed.notify_all();
+                    }
+                });
+            }
+        }
+    });
+
+    let scans = scans.into_inner().unwrap();
+    scans
+        .results
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(no_endpoint_error())))
+        .collect()
+}
+
+fn no_endpoint_error() -> ClamdError {
+    ClamdError::Io(io::Error::new(
+        io::ErrorKind::NotConnected,
+        "no ClamAV endpoint is available",
+    ))
+}
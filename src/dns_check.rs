@@ -0,0 +1,152 @@
+use std::io;
+
+use hickory_resolver::Resolver;
+
+use super::{database, get_version, ClamdError, TransportProtocol};
+
+/// The domain whose TXT record advertises the current versions of ClamAV and
+/// its signature databases
+const CURRENT_CVD_DOMAIN: &str = "current.cvd.clamav.net.";
+
+/// The version of the signature databases of ClamAV compared with the current
+/// version advertised by the ClamAV project
+///
+/// Use the feature flag "dns-check" to enable this type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DatabaseStatus {
+    /// The version of the daily database loaded by ClamAV
+    pub installed_version: u32,
+    /// The version of the daily database advertised in the TXT record of
+    /// `current.cvd.clamav.net`
+    pub current_version: u32,
+}
+
+impl DatabaseStatus {
+    /// Compares the database version in a response to the VERSION command, e.g.
+    /// `ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024`, with the TXT record of
+    /// `current.cvd.clamav.net`, e.g. `1.4.1:62:27400:1726480800:1:90:49192:335`
+    ///
+    /// This lets applications that resolve the TXT record themselves, e.g.
+    /// with an asynchronous resolver, check the databases without blocking.
+    /// Fails with [`ClamdError::InvalidResponse`] if either of them has no
+    /// version of the daily database.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clamav_client::DatabaseStatus;
+    ///
+    /// let status = DatabaseStatus::from_responses(
+    ///     b"ClamAV 1.4.1/27398/Sat Sep 14 10:00:00 2024\0",
+    ///     "1.4.1:62:27400:1726480800:1:90:49192:335",
+    /// )
+    /// .unwrap();
+    /// assert!(!status.is_current());
+    /// assert_eq!(status.versions_behind(), 2);
+    /// ```
+    ///
+    pub fn from_responses(version_response: &[u8], txt_record: &str) -> Result<Self, ClamdError> {
+        Ok(DatabaseStatus {
+            installed_version: database::database_version_number(version_response)?,
+            current_version: parse_txt_record(txt_record)?,
+        })
+    }
+
+    /// Returns `true` if ClamAV has loaded the current daily database or a
+    /// newer one
+    pub fn is_current(&self) -> bool {
+        self.installed_version >= self.current_version
+    }
+
+    /// Returns the number of daily database versions ClamAV is behind the
+    /// current version
+    pub fn versions_behind(&self) -> u32 {
+        self.current_version.saturating_sub(self.installed_version)
+    }
+}
+
+/// Looks up the version of the current daily database in the TXT record of
+/// `current.cvd.clamav.net`, like `freshclam` does
+///
+/// The lookup uses the system configuration of DNS and blocks the current
+/// thread, so it must not be called from an asynchronous task.
+///
+/// Use the feature flag "dns-check" to enable this function.
+///
+/// # Example
+///
+/// ```no_run
+/// let version = clamav_client::current_database_version().unwrap();
+/// println!("The current daily database is version {}", version);
+/// ```
+///
+pub fn current_database_version() -> io::Result<u32> {
+    let resolver = Resolver::from_system_conf()?;
+    let lookup = resolver
+        .txt_lookup(CURRENT_CVD_DOMAIN)
+        .map_err(io::Error::other)?;
+    let txt_record = lookup
+        .iter()
+        .map(|txt| {
+            txt.txt_data()
+                .iter()
+                .map(|data| String::from_utf8_lossy(data))
+                .collect::<String>()
+        })
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "no TXT record for current.cvd.clamav.net",
+            )
+        })?;
+    Ok(parse_txt_record(&txt_record)?)
+}
+
+/// Checks whether the signature databases of ClamAV are current
+///
+/// This function sends the VERSION command to ClamAV and compares the version
+/// of its daily database with the version advertised in the TXT record of
+/// `current.cvd.clamav.net` (see [`current_database_version`]).
+///
+/// Use the feature flag "dns-check" to enable this function.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// The [`DatabaseStatus`] of ClamAV, or a [`ClamdError`] if the connection or
+/// the DNS lookup fails or either response has no database version
+///
+/// # Example
+///
+/// ```no_run
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let status = clamav_client::check_database(clamd_tcp).unwrap();
+/// if !status.is_current() {
+///     eprintln!("ClamAV is {} versions behind", status.versions_behind());
+/// }
+/// ```
+///
+pub fn check_database<T: TransportProtocol>(connection: T) -> Result<DatabaseStatus, ClamdError> {
+    let installed_version = database::database_version_number(&get_version(connection)?)?;
+    Ok(DatabaseStatus {
+        installed_version,
+        current_version: current_database_version()?,
+    })
+}
+
+/// Parses the version of the daily database, the third field of the TXT
+/// record of `current.cvd.clamav.net`
+fn parse_txt_record(txt_record: &str) -> Result<u32, ClamdError> {
+    txt_record
+        .split(':')
+        .nth(2)
+        .and_then(|version| version.parse().ok())
+        .ok_or_else(|| ClamdError::InvalidResponse {
+            response: txt_record.to_owned(),
+        })
+}
@@ -0,0 +1,134 @@
+use std::{error, fmt, io, path::PathBuf};
+
+/// The error type of the typed client functions
+///
+/// Functions returning an [`IoResult`](crate::IoResult) wrap errors detected by
+/// the client itself in an [`io::Error`], which can be inspected using
+/// `err.get_ref().and_then(|e| e.downcast_ref::<ClamdError>())` or converted
+/// back using [`ClamdError::from`].
+///
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ClamdError {
+    /// An I/O error occurred while communicating with ClamAV
+    Io(io::Error),
+    /// The input is larger than the configured maximum stream size
+    SizeLimitExceeded {
+        /// The configured maximum stream size in bytes
+        limit: u64,
+    },
+    /// ClamAV sent a response that could not be interpreted
+    InvalidResponse {
+        /// The response as (lossily decoded) text
+        response: String,
+    },
+    /// The scan was cancelled before ClamAV responded
+    Cancelled,
+    /// The path cannot be sent to ClamAV in a path-based command
+    InvalidPath {
+        /// The path
+        path: PathBuf,
+        /// Why the path cannot be sent
+        reason: &'static str,
+    },
+    /// ClamAV reported an error instead of a scan result, as returned by a
+    /// [`FailurePolicy`](crate::FailurePolicy) for [`ScanOutcome::Error`](crate::ScanOutcome::Error)
+    ScanFailed {
+        /// The error message reported by ClamAV
+        message: String,
+    },
+    /// The [`ScanOptions`](crate::ScanOptions) are invalid, see
+    /// [`ScanOptions::validate`](crate::ScanOptions::validate)
+    InvalidOptions {
+        /// Why the options are invalid
+        reason: &'static str,
+    },
+    /// The input is an archive that exceeds the configured
+    /// [`ArchiveLimits`](crate::ArchiveLimits), e.g. a decompression bomb
+    ArchiveLimitExceeded {
+        /// Which limit the archive exceeds
+        reason: String,
+    },
+    /// The input is empty, which the
+    /// [`ScanOptions`](crate::ScanOptions::reject_empty) reject
+    EmptyInput,
+}
+
+impl fmt::Display for ClamdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClamdError::Io(err) => err.fmt(f),
+            ClamdError::SizeLimitExceeded { limit } => {
+                write!(f, "stream size limit of {} bytes exceeded", limit)
+            }
+            ClamdError::InvalidResponse { response } => {
+                write!(f, "invalid response from ClamAV: {:?}", response)
+            }
+            ClamdError::Cancelled => f.write_str("scan cancelled"),
+            ClamdError::InvalidPath { path, reason } => {
+                write!(f, "cannot send path {:?} to ClamAV: {}", path, reason)
+            }
+            ClamdError::ScanFailed { message } => write!(f, "ClamAV failed to scan: {}", message),
+            ClamdError::InvalidOptions { reason } => write!(f, "invalid scan options: {}", reason),
+            ClamdError::ArchiveLimitExceeded { reason } => {
+                write!(f, "archive rejected before scanning: {}", reason)
+            }
+            ClamdError::EmptyInput => f.write_str("empty input rejected"),
+        }
+    }
+}
+
+impl error::Error for ClamdError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ClamdError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ClamdError {
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<ClamdError>()) {
+            if let Some(Ok(inner)) = err.into_inner().map(|inner| inner.downcast::<ClamdError>()) {
+                return *inner;
+            }
+            unreachable!("the inner error is a ClamdError");
+        }
+        ClamdError::Io(err)
+    }
+}
+
+impl From<ClamdError> for io::Error {
+    fn from(err: ClamdError) -> Self {
+        let kind = match err {
+            ClamdError::Io(err) => return err,
+            ClamdError::SizeLimitExceeded { .. } => io::ErrorKind::InvalidInput,
+            ClamdError::InvalidResponse { .. } => io::ErrorKind::InvalidData,
+            ClamdError::Cancelled => io::ErrorKind::Other,
+            ClamdError::InvalidPath { .. } => io::ErrorKind::InvalidInput,
+            ClamdError::ScanFailed { .. } => io::ErrorKind::Other,
+            ClamdError::InvalidOptions { .. } => io::ErrorKind::InvalidInput,
+            ClamdError::ArchiveLimitExceeded { .. } => io::ErrorKind::InvalidInput,
+            ClamdError::EmptyInput => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// Converts the error of an item of a stream to scan into an [`io::Error`],
+/// keeping I/O errors and [`ClamdError`]s as they are
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub(crate) fn stream_error<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn error::Error + Send + Sync>>,
+{
+    let err = match err.into().downcast::<io::Error>() {
+        Ok(err) => return *err,
+        Err(err) => err,
+    };
+    match err.downcast::<ClamdError>() {
+        Ok(err) => (*err).into(),
+        Err(err) => io::Error::other(err),
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    time::Duration,
+};
+
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+#[cfg(feature = "parquet")]
+use parquet::{
+    basic::{ConvertedType, Repetition, Type as PhysicalType},
+    data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int64Type},
+    file::{
+        properties::WriterProperties,
+        writer::{SerializedFileWriter, SerializedRowGroupWriter},
+    },
+    schema::types::Type,
+};
+
+use super::ScanReport;
+
+/// The names of the columns written by [`write_reports_csv`] and
+/// [`write_reports_parquet`]
+const COLUMNS: [&str; 8] = [
+    "correlation_id",
+    "bytes_sent",
+    "connect_time_us",
+    "streaming_time_us",
+    "total_duration_us",
+    "throughput",
+    "sha256",
+    "md5",
+];
+
+/// Writes scan reports as CSV, with a header and one row per report
+///
+/// The columns are the `correlation_id`, the `bytes_sent`, the
+/// `connect_time_us`, `streaming_time_us` and `total_duration_us` in
+/// microseconds, the `throughput` in bytes per second and the hex-encoded
+/// `sha256` and `md5` digests. Unknown values are empty, and values are quoted
+/// as described in RFC 4180 if necessary.
+///
+/// Use the feature flag "export" to enable this function.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// let reports = Arc::new(Mutex::new(Vec::new()));
+/// let collected = reports.clone();
+/// let options = clamav_client::ScanOptions::new()
+///     .on_report(move |report| collected.lock().unwrap().push(report.clone()));
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// for path in ["README.md", "Cargo.toml"] {
+///     clamav_client::scan_file(path, clamd_tcp, Some(options.clone())).unwrap();
+/// }
+///
+/// let file = std::fs::File::create("target/reports.csv").unwrap();
+/// clamav_client::write_reports_csv(reports.lock().unwrap().iter(), file).unwrap();
+/// ```
+///
+pub fn write_reports_csv<'a, I, W>(reports: I, mut writer: W) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a ScanReport>,
+    W: Write,
+{
+    writeln!(writer, "{}", COLUMNS.join(","))?;
+    for report in reports {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            report
+                .correlation_id
+                .as_deref()
+                .map_or(String::new(), csv_field),
+            report.bytes_sent,
+            micros(report.connect_time),
+            micros(report.streaming_time),
+            micros(report.total_duration),
+            report.throughput(),
+            report.sha256.map_or(String::new(), |digest| hex(&digest)),
+            report.md5.map_or(String::new(), |digest| hex(&digest)),
+        )?;
+    }
+    writer.flush()
+}
+
+/// Writes scan reports as a Parquet file with a single row group
+///
+/// The file has the same columns as the CSV written by [`write_reports_csv`].
+/// The `correlation_id` and the digests are optional UTF-8 strings, the sizes
+/// and times 64-bit integers and the `throughput` a double.
+///
+/// Use the feature flag "parquet" to enable this function.
+#[cfg(feature = "parquet")]
+pub fn write_reports_parquet<'a, I, W>(reports: I, writer: W) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a ScanReport>,
+    W: Write + Send,
+{
+    let reports: Vec<&ScanReport> = reports.into_iter().collect();
+    let schema = parquet_schema()?;
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(writer, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+    let integers = |value: fn(&ScanReport) -> i64| reports.iter().map(|r| value(r)).collect();
+    write_strings(
+        &mut row_group,
+        reports.iter().map(|r| r.correlation_id.clone()),
+    )?;
+    write_column::<Int64Type, _>(
+        &mut row_group,
+        integers(|r| i64::try_from(r.bytes_sent).unwrap_or(i64::MAX)),
+        None,
+    )?;
+    write_column::<Int64Type, _>(&mut row_group, integers(|r| micros(r.connect_time)), None)?;
+    write_column::<Int64Type, _>(&mut row_group, integers(|r| micros(r.streaming_time)), None)?;
+    write_column::<Int64Type, _>(&mut row_group, integers(|r| micros(r.total_duration)), None)?;
+    write_column::<DoubleType, _>(
+        &mut row_group,
+        reports.iter().map(|r| r.throughput()).collect(),
+        None,
+    )?;
+    write_strings(
+        &mut row_group,
+        reports.iter().map(|r| r.sha256.map(|digest| hex(&digest))),
+    )?;
+    write_strings(
+        &mut row_group,
+        reports.iter().map(|r| r.md5.map(|digest| hex(&digest))),
+    )?;
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Writes the values of the next column of a row group
+///
+/// The definition levels mark which values of an optional column are present.
+#[cfg(feature = "parquet")]
+fn write_column<T: DataType, W: Write + Send>(
+    row_group: &mut SerializedRowGroupWriter<'_, W>,
+    values: Vec<T::T>,
+    levels: Option<&[i16]>,
+) -> io::Result<()> {
+    if let Some(mut column) = row_group.next_column()? {
+        column.typed::<T>().write_batch(&values, levels, None)?;
+        column.close()?;
+    }
+    Ok(())
+}
+
+/// Writes the values of the next column of a row group, which is an optional
+/// string
+#[cfg(feature = "parquet")]
+fn write_strings<W: Write + Send>(
+    row_group: &mut SerializedRowGroupWriter<'_, W>,
+    values: impl Iterator<Item = Option<String>>,
+) -> io::Result<()> {
+    let values: Vec<Option<String>> = values.collect();
+    let levels: Vec<i16> = values.iter().map(|v| i16::from(v.is_some())).collect();
+    let values = values
+        .into_iter()
+        .flatten()
+        .map(|v| ByteArray::from(v.into_bytes()))
+        .collect();
+    write_column::<ByteArrayType, _>(row_group, values, Some(&levels))
+}
+
+/// Returns the schema of the files written by [`write_reports_parquet`]
+#[cfg(feature = "parquet")]
+fn parquet_schema() -> io::Result<Arc<Type>> {
+    let fields = COLUMNS
+        .iter()
+        .map(|&name| {
+            let field = match name {
+                "correlation_id" | "sha256" | "md5" => {
+                    Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+                        .with_repetition(Repetition::OPTIONAL)
+                        .with_converted_type(ConvertedType::UTF8)
+                }
+                "throughput" => Type::primitive_type_builder(name, PhysicalType::DOUBLE)
+                    .with_repetition(Repetition::REQUIRED),
+                _ => Type::primitive_type_builder(name, PhysicalType::INT64)
+                    .with_repetition(Repetition::REQUIRED),
+            };
+            field.build().map(Arc::new)
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Arc::new(
+        Type::group_type_builder("scan_report")
+            .with_fields(fields)
+            .build()?,
+    ))
+}
+
+fn micros(duration: Duration) -> i64 {
+    i64::try_from(duration.as_micros()).unwrap_or(i64::MAX)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// Quotes a value if it contains a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
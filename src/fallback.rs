@@ -0,0 +1,127 @@
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    os::unix::net::UnixStream,
+    path::Path,
+    time::Duration,
+};
+
+use super::{Socket, Tcp, TransportProtocol};
+
+/// Use the Unix socket of a local ClamAV server if it accepts connections, or
+/// a TCP connection to a ClamAV server otherwise
+///
+/// The TCP connection is used if the socket file does not exist or refuses
+/// connections, e.g. because ClamAV does not run on the same host, so the same
+/// configuration works whether ClamAV is colocated or remote. Other errors of
+/// the socket, like missing permissions, are returned as they are. The socket
+/// is tried again for each connection.
+///
+/// Since the ClamAV server in use is only known once connected, this transport
+/// is not [local](TransportProtocol::is_local).
+///
+/// # Example
+///
+/// ```
+/// let clamd = clamav_client::SocketOrTcp {
+///     socket_path: "/tmp/clamd.sock",
+///     host_address: "localhost:3310",
+/// };
+/// let response = clamav_client::ping(clamd).unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SocketOrTcp<P: AsRef<Path>, A: ToSocketAddrs> {
+    /// The socket file path of the local ClamAV server
+    pub socket_path: P,
+    /// The address (host and port) of the ClamAV server to fall back to
+    pub host_address: A,
+}
+
+impl<P: AsRef<Path>, A: ToSocketAddrs> SocketOrTcp<P, A> {
+    fn socket(&self) -> Socket<&Path> {
+        Socket {
+            socket_path: self.socket_path.as_ref(),
+        }
+    }
+
+    fn tcp(&self) -> Tcp<&A> {
+        Tcp {
+            host_address: &self.host_address,
+        }
+    }
+}
+
+/// The stream of a [`SocketOrTcp`] connection
+#[derive(Debug)]
+pub enum SocketOrTcpStream {
+    /// A connection to the Unix socket
+    Socket(UnixStream),
+    /// A connection to the TCP address
+    Tcp(TcpStream),
+}
+
+/// Returns `true` if a failed connection to a Unix socket should be retried
+/// over TCP
+pub(crate) fn falls_back(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+    )
+}
+
+impl<P: AsRef<Path>, A: ToSocketAddrs> TransportProtocol for SocketOrTcp<P, A> {
+    type Stream = SocketOrTcpStream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        match self.socket().connect() {
+            Ok(stream) => Ok(SocketOrTcpStream::Socket(stream)),
+            Err(err) if falls_back(&err) => self.tcp().connect().map(SocketOrTcpStream::Tcp),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        match self.socket().connect_timeout(timeout) {
+            Ok(stream) => Ok(SocketOrTcpStream::Socket(stream)),
+            Err(err) if falls_back(&err) => self
+                .tcp()
+                .connect_timeout(timeout)
+                .map(SocketOrTcpStream::Tcp),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Read for SocketOrTcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SocketOrTcpStream::Socket(stream) => stream.read(buf),
+            SocketOrTcpStream::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for SocketOrTcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            SocketOrTcpStream::Socket(stream) => stream.write(buf),
+            SocketOrTcpStream::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        match self {
+            SocketOrTcpStream::Socket(stream) => stream.write_vectored(bufs),
+            SocketOrTcpStream::Tcp(stream) => stream.write_vectored(bufs),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            SocketOrTcpStream::Socket(stream) => stream.flush(),
+            SocketOrTcpStream::Tcp(stream) => stream.flush(),
+        }
+    }
+}
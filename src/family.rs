@@ -0,0 +1,117 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use super::{connect_timeout_any, TransportProtocol};
+
+/// The IP family, IPv4 or IPv6, of the addresses of a ClamAV server to
+/// connect to
+///
+/// By default, the addresses are tried in the order in which they are
+/// resolved, so whether IPv4 or IPv6 is tried first depends on the resolver of
+/// the operating system.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum IpFamily {
+    /// Try the addresses in the order in which they are resolved
+    #[default]
+    Any,
+    /// Try the IPv4 addresses before the IPv6 addresses
+    PreferIpv4,
+    /// Try the IPv6 addresses before the IPv4 addresses
+    PreferIpv6,
+    /// Only try the IPv4 addresses
+    Ipv4Only,
+    /// Only try the IPv6 addresses
+    Ipv6Only,
+}
+
+impl IpFamily {
+    /// Filters and orders resolved addresses according to this family
+    ///
+    /// The order of the addresses within each family is kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::net::SocketAddr;
+    /// use clamav_client::IpFamily;
+    ///
+    /// let v6: SocketAddr = "[::1]:3310".parse().unwrap();
+    /// let v4: SocketAddr = "127.0.0.1:3310".parse().unwrap();
+    /// assert_eq!(IpFamily::PreferIpv4.apply(vec![v6, v4]), vec![v4, v6]);
+    /// assert_eq!(IpFamily::Ipv4Only.apply(vec![v6, v4]), vec![v4]);
+    /// ```
+    ///
+    pub fn apply<I: IntoIterator<Item = SocketAddr>>(self, addresses: I) -> Vec<SocketAddr> {
+        let mut addresses: Vec<SocketAddr> = addresses.into_iter().collect();
+        match self {
+            IpFamily::Any => {}
+            IpFamily::PreferIpv4 => addresses.sort_by_key(|address| address.is_ipv6()),
+            IpFamily::PreferIpv6 => addresses.sort_by_key(|address| address.is_ipv4()),
+            IpFamily::Ipv4Only => addresses.retain(|address| address.is_ipv4()),
+            IpFamily::Ipv6Only => addresses.retain(|address| address.is_ipv6()),
+        }
+        addresses
+    }
+
+    /// Returns the error for resolved addresses of which none are left after
+    /// [`apply`](IpFamily::apply)
+    pub(crate) fn no_addresses() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses of the required IP family",
+        )
+    }
+}
+
+/// Use a TCP connection to communicate with a ClamAV server, preferring or
+/// requiring IPv4 or IPv6 addresses
+///
+/// Host names that resolve to both IPv4 and IPv6 addresses are otherwise
+/// connected to in the order chosen by the resolver, which breaks scans if the
+/// route for one of the families is blocked.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{FamilyTcp, IpFamily};
+///
+/// let clamd_tcp = FamilyTcp {
+///     host_address: "localhost:3310",
+///     ip_family: IpFamily::Ipv4Only,
+/// };
+/// let response = clamav_client::ping(clamd_tcp).unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FamilyTcp<A: ToSocketAddrs> {
+    /// The address (host and port) of the ClamAV server
+    pub host_address: A,
+    /// The IP family of the addresses to connect to
+    pub ip_family: IpFamily,
+}
+
+impl<A: ToSocketAddrs> FamilyTcp<A> {
+    fn addresses(&self) -> io::Result<Vec<SocketAddr>> {
+        let addresses = self.ip_family.apply(self.host_address.to_socket_addrs()?);
+        if addresses.is_empty() {
+            return Err(IpFamily::no_addresses());
+        }
+        Ok(addresses)
+    }
+}
+
+impl<A: ToSocketAddrs> TransportProtocol for FamilyTcp<A> {
+    type Stream = TcpStream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        TcpStream::connect(&self.addresses()?[..])
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        connect_timeout_any(self.addresses()?, timeout)
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use super::{database, get_version, is_pong, ping, scan_buffer, ScanOutcome, TransportProtocol};
+
+/// The EICAR test file, which every antivirus detects, for the self-scan of a
+/// [`health_check`]
+pub(crate) const EICAR: &[u8] =
+    br"X5O!P%@AP[4\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+/// Options for a [`health_check`]
+///
+/// By default, a health check pings ClamAV and gets its version, and a ping
+/// that takes longer than one second degrades the status. The age of the
+/// signature databases only degrades the status if a maximum age is set.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let options = clamav_client::HealthCheckOptions::new()
+///     .max_ping_latency(Duration::from_millis(200))
+///     .max_database_age(Duration::from_secs(48 * 60 * 60))
+///     .self_scan(true);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct HealthCheckOptions {
+    pub(crate) max_ping_latency: Duration,
+    pub(crate) max_database_age: Option<Duration>,
+    pub(crate) self_scan: bool,
+}
+
+impl HealthCheckOptions {
+    /// Creates the default health check options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the latency of a ping above which ClamAV is degraded
+    pub fn max_ping_latency(mut self, max_ping_latency: Duration) -> Self {
+        self.max_ping_latency = max_ping_latency;
+        self
+    }
+
+    /// Sets the age of the signature databases above which ClamAV is degraded
+    ///
+    /// See [`database_age`](crate::database_age).
+    pub fn max_database_age(mut self, max_database_age: Duration) -> Self {
+        self.max_database_age = Some(max_database_age);
+        self
+    }
+
+    /// Scans the EICAR test file as well, so that a ClamAV that answers but
+    /// does not detect anything, e.g. without any databases, is unhealthy
+    pub fn self_scan(mut self, self_scan: bool) -> Self {
+        self.self_scan = self_scan;
+        self
+    }
+}
+
+impl Default for HealthCheckOptions {
+    fn default() -> Self {
+        HealthCheckOptions {
+            max_ping_latency: Duration::from_secs(1),
+            max_database_age: None,
+            self_scan: false,
+        }
+    }
+}
+
+/// The overall status of a [`HealthReport`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HealthStatus {
+    /// ClamAV answers quickly with up-to-date databases
+    Healthy,
+    /// ClamAV scans, but slowly, with outdated databases or without a valid
+    /// version
+    Degraded,
+    /// ClamAV does not answer or does not detect the EICAR test file
+    Unhealthy,
+}
+
+/// The result of a [`health_check`]
+///
+/// The checks after a failed ping are skipped, so their fields are [`None`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HealthReport {
+    /// The overall status
+    pub status: HealthStatus,
+    /// The time until ClamAV answered the PING command with `PONG`
+    pub ping_latency: Option<Duration>,
+    /// The response of ClamAV to the VERSION command
+    pub version: Option<Vec<u8>>,
+    /// The age of the signature databases according to the version
+    pub database_age: Option<Duration>,
+    /// Whether ClamAV detected the EICAR test file, if it was scanned
+    pub eicar_detected: Option<bool>,
+    /// The reasons for a status other than [`HealthStatus::Healthy`]
+    pub problems: Vec<String>,
+}
+
+impl HealthReport {
+    /// Returns `true` if the status is [`HealthStatus::Healthy`]
+    pub fn is_healthy(&self) -> bool {
+        self.status == HealthStatus::Healthy
+    }
+
+    /// Returns `true` if ClamAV can scan, i.e. the status is not
+    /// [`HealthStatus::Unhealthy`]
+    ///
+    /// This is the answer to a readiness probe.
+    pub fn is_ready(&self) -> bool {
+        self.status != HealthStatus::Unhealthy
+    }
+
+    pub(crate) fn new() -> Self {
+        HealthReport {
+            status: HealthStatus::Healthy,
+            ping_latency: None,
+            version: None,
+            database_age: None,
+            eicar_detected: None,
+            problems: Vec::new(),
+        }
+    }
+
+    fn problem(&mut self, status: HealthStatus, problem: String) {
+        self.status = self.status.max(status);
+        self.problems.push(problem);
+    }
+
+    /// Records the response to the PING command and returns whether the other
+    /// checks should run
+    pub(crate) fn ping(
+        &mut self,
+        options: &HealthCheckOptions,
+        response: io::Result<Vec<u8>>,
+        latency: Duration,
+    ) -> bool {
+        match response {
+            Ok(response) if is_pong(&response) => {
+                self.ping_latency = Some(latency);
+                if latency > options.max_ping_latency {
+                    self.problem(
+                        HealthStatus::Degraded,
+                        format!("the ping took {} ms", latency.as_millis()),
+                    );
+                }
+                true
+            }
+            Ok(response) => {
+                self.problem(
+                    HealthStatus::Unhealthy,
+                    format!(
+                        "unexpected response to PING: {}",
+                        String::from_utf8_lossy(&response).trim_end_matches(['\0', '\n'])
+                    ),
+                );
+                false
+            }
+            Err(err) => {
+                self.problem(HealthStatus::Unhealthy, format!("ping failed: {}", err));
+                false
+            }
+        }
+    }
+
+    /// Records the response to the VERSION command
+    pub(crate) fn version(&mut self, options: &HealthCheckOptions, response: io::Result<Vec<u8>>) {
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                self.problem(
+                    HealthStatus::Degraded,
+                    format!("getting the version failed: {}", err),
+                );
+                return;
+            }
+        };
+        match database::age_of(&response) {
+            Ok(age) => {
+                self.database_age = Some(age);
+                if matches!(options.max_database_age, Some(max_age) if age > max_age) {
+                    self.problem(
+                        HealthStatus::Degraded,
+                        format!("the databases are {} hours old", age.as_secs() / 3600),
+                    );
+                }
+            }
+            Err(err) => self.problem(HealthStatus::Degraded, err.to_string()),
+        }
+        self.version = Some(response);
+    }
+
+    /// Records the response to the scan of the EICAR test file
+    pub(crate) fn self_scan(&mut self, response: io::Result<Vec<u8>>) {
+        let outcome = response
+            .map_err(|err| err.to_string())
+            .and_then(|response| {
+                ScanOutcome::from_response(&response).map_err(|err| err.to_string())
+            });
+        match outcome {
+            Ok(ScanOutcome::Infected { .. }) => self.eicar_detected = Some(true),
+            Ok(ScanOutcome::Error { message }) => self.problem(
+                HealthStatus::Unhealthy,
+                format!("the self-scan failed: {}", message),
+            ),
+            Ok(_) => {
+                self.eicar_detected = Some(false);
+                self.problem(
+                    HealthStatus::Unhealthy,
+                    "the EICAR test file was not detected".to_owned(),
+                );
+            }
+            Err(err) => self.problem(
+                HealthStatus::Unhealthy,
+                format!("the self-scan failed: {}", err),
+            ),
+        }
+    }
+}
+
+/// Checks whether ClamAV is healthy, e.g. for a readiness probe
+///
+/// This function pings ClamAV, gets its version to determine the age of the
+/// signature databases and, if enabled in the options, scans the EICAR test
+/// file, each over its own connection. It combines the results into a
+/// [`HealthReport`] with an overall [`HealthStatus`]: a failed ping or
+/// self-scan makes ClamAV unhealthy, and a slow ping, outdated databases or an
+/// invalid version response degrade it.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::HealthCheckOptions::new().self_scan(true);
+/// let report = clamav_client::health_check(clamd_tcp, &options);
+/// if !report.is_ready() {
+///     eprintln!("ClamAV is not ready: {:?}", report.problems);
+/// }
+/// # assert!(report.is_ready());
+/// ```
+///
+pub fn health_check<T: TransportProtocol>(
+    connection: T,
+    options: &HealthCheckOptions,
+) -> HealthReport {
+    let mut report = HealthReport::new();
+    let start = Instant::now();
+    let response = ping(&connection);
+    if !report.ping(options, response, start.elapsed()) {
+        return report;
+    }
+    report.version(options, get_version(&connection));
+    if options.self_scan {
+        report.self_scan(scan_buffer(EICAR, &connection, None));
+    }
+    report
+}
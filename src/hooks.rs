@@ -0,0 +1,55 @@
+use std::{fmt, path::Path, sync::Arc, time::Duration};
+
+use super::{stats::StatsCounters, ClamdError, Detection, DetectionSink, ScanOutcome};
+
+type Hook<T> = Option<Arc<dyn Fn(T) + Send + Sync>>;
+type RefHook<T> = Option<Arc<dyn Fn(&T) + Send + Sync>>;
+
+/// The lifecycle hooks of a [`Client`](crate::Client), which are stored in its
+/// [`ScanOptions`](crate::ScanOptions) so that the scan loop can call them
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    pub(crate) connect: Hook<Duration>,
+    pub(crate) chunk_sent: Hook<usize>,
+    pub(crate) response: RefHook<ScanOutcome>,
+    pub(crate) error: RefHook<ClamdError>,
+    pub(crate) detection_sinks: Vec<Arc<dyn DetectionSink>>,
+    pub(crate) stats: Option<Arc<StatsCounters>>,
+}
+
+impl Hooks {
+    /// Calls the response or error hook with the result of a scan of the file
+    /// at `path`, or of a buffer, counts it and passes detections to the sinks
+    /// along with the digest of the data, if it was computed
+    pub(crate) fn result(
+        &self,
+        result: &Result<ScanOutcome, ClamdError>,
+        path: Option<&Path>,
+        sha256: Option<[u8; 32]>,
+        correlation_id: Option<&str>,
+    ) {
+        if let Some(stats) = &self.stats {
+            stats.result(result);
+        }
+        match (result, &self.response, &self.error) {
+            (Ok(outcome), Some(on_response), _) => on_response(outcome),
+            (Err(err), _, Some(on_error)) => on_error(err),
+            _ => {}
+        }
+        match result {
+            Ok(ScanOutcome::Infected { signature }) if !self.detection_sinks.is_empty() => {
+                let detection = Detection::new(signature, path, sha256, correlation_id);
+                for sink in &self.detection_sinks {
+                    sink.detected(&detection);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hooks")
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use super::{ClamdError, ScanOutcome};
+
+/// An append-only file recording the outcome of each file scanned by a
+/// directory scan, so that an interrupted scan can be resumed
+///
+/// Each line holds the outcome in the format of a ClamAV response (`OK` or
+/// `<signature> FOUND`), a tab, and the path of the file, with backslashes,
+/// tabs and line breaks escaped. Errors are not recorded, so files that could
+/// not be scanned are scanned again when the scan is resumed. An incomplete
+/// last line, e.g. from a crash while it was written, is ignored.
+pub(crate) struct Journal {
+    file: Mutex<File>,
+    recorded: HashMap<PathBuf, ScanOutcome>,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, creating it if it does not exist
+    pub(crate) fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let mut recorded = HashMap::new();
+        let complete = match contents.rfind('\n') {
+            Some(end) => &contents[..end],
+            None => "",
+        };
+        for line in complete.lines() {
+            if let Some((path, outcome)) = parse_line(line) {
+                recorded.insert(path, outcome);
+            }
+        }
+        if complete.len() + 1 < contents.len() {
+            // Terminate the incomplete last line, so that it stays ignored
+            file.write_all(b"\n")?;
+        }
+
+        Ok(Journal {
+            file: Mutex::new(file),
+            recorded,
+        })
+    }
+
+    /// Returns the outcome recorded for the file at `path`, if any
+    pub(crate) fn recorded(&self, path: &Path) -> Option<ScanOutcome> {
+        self.recorded.get(path).cloned()
+    }
+
+    /// Records the result of scanning the file at `path` unless it failed or
+    /// ClamAV reported an error
+    ///
+    /// Write errors are ignored, the file is then scanned again when the scan
+    /// is resumed.
+    pub(crate) fn record(&self, path: &Path, result: &Result<ScanOutcome, ClamdError>) {
+        let response = match result {
+            Ok(ScanOutcome::Clean) => "OK".to_owned(),
+            Ok(ScanOutcome::Infected { signature }) => format!("{} FOUND", signature),
+            Ok(outcome @ ScanOutcome::Warning { .. }) => outcome.to_string(),
+            Ok(ScanOutcome::Error { .. }) | Err(_) => return,
+        };
+        let path = match path.to_str() {
+            Some(path) => escape(path),
+            None => return,
+        };
+        let line = format!("{}\t{}\n", response, path);
+        let _ = self.file.lock().unwrap().write_all(line.as_bytes());
+    }
+
+    /// Returns the recorded outcome of the file at `path`, or scans and
+    /// records it
+    pub(crate) fn scan<F>(
+        journal: Option<&Self>,
+        path: &Path,
+        scan: F,
+    ) -> Result<ScanOutcome, ClamdError>
+    where
+        F: FnOnce() -> Result<ScanOutcome, ClamdError>,
+    {
+        let journal = match journal {
+            Some(journal) => journal,
+            None => return scan(),
+        };
+        if let Some(outcome) = journal.recorded(path) {
+            return Ok(outcome);
+        }
+        let result = scan();
+        journal.record(path, &result);
+        result
+    }
+}
+
+fn parse_line(line: &str) -> Option<(PathBuf, ScanOutcome)> {
+    let (response, path) = line.split_once('\t')?;
+    let outcome: ScanOutcome = response.parse().ok()?;
+    match outcome {
+        ScanOutcome::Clean | ScanOutcome::Infected { .. } | ScanOutcome::Warning { .. } => {
+            Some((unescape(path)?.into(), outcome))
+        }
+        ScanOutcome::Error { .. } => None,
+    }
+}
+
+pub(crate) fn escape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(crate) fn unescape(escaped: &str) -> Option<String> {
+    let mut path = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            path.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => path.push('\\'),
+            't' => path.push('\t'),
+            'n' => path.push('\n'),
+            'r' => path.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(path)
+}
@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use super::{response, ClamdError, TransportProtocol};
+
+/// The round-trip times of pings to ClamAV, measured by [`measure_latency`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct LatencyStats {
+    /// The number of pings
+    pub samples: usize,
+    /// The mean time to establish a connection
+    pub connect: Duration,
+    /// The shortest time until ClamAV answered a PING command
+    pub min: Duration,
+    /// The mean time until ClamAV answered a PING command
+    pub avg: Duration,
+    /// The time within which ClamAV answered 95 percent of the PING commands
+    pub p95: Duration,
+    /// The longest time until ClamAV answered a PING command
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    /// Returns the mean time of a ping including connecting, i.e. the overhead
+    /// of each request to ClamAV
+    pub fn total(&self) -> Duration {
+        self.connect + self.avg
+    }
+}
+
+/// The times measured so far by [`measure_latency`]
+pub(crate) struct LatencySamples {
+    connects: Vec<Duration>,
+    round_trips: Vec<Duration>,
+}
+
+impl LatencySamples {
+    pub(crate) fn new(samples: usize) -> Self {
+        LatencySamples {
+            connects: Vec::with_capacity(samples),
+            round_trips: Vec::with_capacity(samples),
+        }
+    }
+
+    /// Records a ping that connected after `connect` and was answered with
+    /// `response` after a further `round_trip`
+    pub(crate) fn push(
+        &mut self,
+        connect: Duration,
+        round_trip: Duration,
+        response: &[u8],
+    ) -> Result<(), ClamdError> {
+        response::expect_pong(response)?;
+        self.connects.push(connect);
+        self.round_trips.push(round_trip);
+        Ok(())
+    }
+
+    pub(crate) fn stats(mut self) -> LatencyStats {
+        let samples = self.round_trips.len();
+        self.round_trips.sort_unstable();
+        // The nearest rank, i.e. the smallest time that is at least as long as
+        // 95 percent of the times
+        let p95 = (samples * 95).div_ceil(100).max(1) - 1;
+        LatencyStats {
+            samples,
+            connect: mean(&self.connects),
+            min: self.round_trips[0],
+            avg: mean(&self.round_trips),
+            p95: self.round_trips[p95],
+            max: self.round_trips[samples - 1],
+        }
+    }
+}
+
+fn mean(times: &[Duration]) -> Duration {
+    times.iter().sum::<Duration>() / times.len() as u32
+}
+
+/// Measures the latency of ClamAV by pinging it `samples` times, e.g. to pick
+/// the closest of several servers at startup
+///
+/// Each ping is sent over its own connection, one after the other, and the
+/// time to connect is measured separately from the round trip of the PING
+/// command. At least one ping is sent. The measurement fails with the first
+/// error, or with [`ClamdError::InvalidResponse`] if ClamAV does not answer
+/// with [`PONG`](crate::PONG).
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::measure_latency(clamd_tcp, 10).unwrap();
+/// println!("{:?} on average, {:?} at the 95th percentile", stats.avg, stats.p95);
+/// # assert_eq!(stats.samples, 10);
+/// ```
+///
+pub fn measure_latency<T: TransportProtocol>(
+    connection: T,
+    samples: usize,
+) -> Result<LatencyStats, ClamdError> {
+    let mut measured = LatencySamples::new(samples.max(1));
+    for _ in 0..samples.max(1) {
+        let start = Instant::now();
+        let stream = connection.connect()?;
+        let connected = Instant::now();
+        let response = super::_ping(stream)?;
+        measured.push(connected - start, connected.elapsed(), &response)?;
+    }
+    Ok(measured.stats())
+}
@@ -2,58 +2,470 @@
 #![deny(missing_docs)]
 
 #[cfg(feature = "tokio")]
-/// Use the feature flag "tokio" or "tokio-stream" to enable this module
+/// Use the feature flag "tokio" to enable this module
+///
+/// # Cancel safety
+///
+/// All functions are cancel safe: dropping a future, e.g. because it lost a
+/// race against a timeout, never affects other calls, because each call uses
+/// its own connection. If a scan is dropped while data is streamed to ClamAV,
+/// the write half of its connection is shut down, so that ClamAV stops waiting
+/// for the rest of the stream instead of running into its own timeout. See
+/// [`tokio::cancellable`] to cancel a scan when another future completes. The
+/// requests of a [`tokio::Session`] share one connection instead, see its
+/// documentation.
 pub mod tokio;
 
 #[cfg(feature = "async-std")]
 /// Use the feature flag "async-std" to enable this module
+///
+/// # Cancel safety
+///
+/// All functions are cancel safe: dropping a future, e.g. because it lost a
+/// race against a timeout, never affects other calls, because each call uses
+/// its own connection. If a scan is dropped while data is streamed to ClamAV,
+/// the stream is terminated with a zero-length chunk, so that ClamAV stops
+/// waiting for the rest of the stream instead of running into its own timeout.
 pub mod async_std;
 
+#[cfg(feature = "axum")]
+pub mod axum;
+
+#[cfg(feature = "actix")]
+pub mod actix;
+
+#[cfg(feature = "warp")]
+pub mod warp;
+
+#[cfg(feature = "poem")]
+pub mod poem;
+
+#[cfg(feature = "tower")]
+pub mod tower;
+
+#[cfg(feature = "reqwest-middleware")]
+pub mod reqwest_middleware;
+
+#[cfg(feature = "tonic")]
+pub mod tonic;
+
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
+#[cfg(feature = "cli")]
+pub mod cli;
+
+mod allowlist;
+mod archive;
+mod boxed;
+mod builder;
+mod bundle;
+mod client;
+mod config;
+mod context;
+mod cross_check;
+mod database;
+mod dedup;
+mod detection;
+mod directory;
+mod distribute;
+mod error;
+mod family;
+mod health;
+mod hooks;
+mod journal;
+mod latency;
+mod options;
+mod outcome;
+mod path_scan;
+mod pipeline;
+mod policy;
+mod progress;
+mod protocol;
+mod pua;
+mod queue;
+mod random;
+mod reload;
+mod report;
+mod resolver;
+mod response;
+mod server_stats;
+mod signature;
+mod state;
+mod stats;
+mod summary;
+mod throttle;
+mod validate;
+mod verdict;
+mod wipe;
+mod wire;
+
+#[cfg(unix)]
+mod fallback;
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod clam_client;
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod concurrency;
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod spool;
+
+#[cfg(feature = "cache")]
+mod cache;
+
+#[cfg(feature = "rayon")]
+mod parallel;
+
+#[cfg(feature = "cache-redis")]
+mod redis_cache;
+
+#[cfg(all(feature = "sendfile", target_os = "linux"))]
+mod sendfile;
+
+#[cfg(feature = "dns-check")]
+mod dns_check;
+
+#[cfg(feature = "hickory-dns")]
+mod srv;
+
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "decompress")]
+mod decompress;
+
+#[cfg(feature = "http-body")]
+mod body;
+
+#[cfg(feature = "mail")]
+mod mail;
+
+#[cfg(feature = "wasi")]
+mod wasi;
+
+#[cfg(feature = "bind")]
+mod bind;
+
+#[cfg(feature = "webhook")]
+mod webhook;
+
+#[cfg(feature = "siem")]
+mod siem;
+
+#[cfg(feature = "audit")]
+mod audit;
+
+#[cfg(feature = "export")]
+mod export;
+
+#[cfg(feature = "schedule")]
+mod schedule;
+
+#[cfg(feature = "db-update")]
+mod db_update;
+
+#[cfg(any(feature = "siem", feature = "audit", feature = "schedule"))]
+mod timestamp;
+
+#[cfg(feature = "test-util")]
+mod mock;
+
+#[cfg(feature = "test-util")]
+mod mock_server;
+
+#[cfg(all(feature = "test-util", unix))]
+mod clamd_process;
+
+#[cfg(feature = "test-util")]
+mod test_files;
+
+#[cfg(any(
+    feature = "axum",
+    feature = "actix",
+    feature = "warp",
+    feature = "poem"
+))]
+mod web;
+
+#[cfg(any(
+    feature = "axum",
+    feature = "actix",
+    feature = "warp",
+    feature = "poem",
+    feature = "tower",
+    feature = "tonic",
+    feature = "reqwest-middleware"
+))]
+mod connection;
+
+pub use allowlist::SignatureAllowlist;
+pub use archive::ArchiveLimits;
+pub use boxed::{BoxedStream, BoxedTransport};
+pub use builder::ClamdClientBuilder;
+pub use bundle::{scan_files_bundled, BundleOptions};
+pub use client::Client;
+pub use config::ClamdConnection;
+pub use context::{ErrorContext, ErrorPhase};
+pub use cross_check::{scan_buffer_cross_checked, scan_file_cross_checked, CrossCheck};
+pub use database::{
+    database_age, database_date, database_version_number, is_database_stale, VersionInfo,
+};
+pub use detection::{
+    Detection, DetectionEvent, DetectionEventSink, DetectionEvents, DetectionSink,
+};
+pub use directory::DirectoryScanOptions;
+pub use distribute::{
+    scan_buffers_distributed, scan_files_distributed, Distribution, DistributionOptions,
+};
+pub use error::ClamdError;
+pub use family::{FamilyTcp, IpFamily};
+pub use health::{health_check, HealthCheckOptions, HealthReport, HealthStatus};
+pub use latency::{measure_latency, LatencyStats};
+pub use options::{AdaptiveChunkSize, ResponseBuffer, ScanOptions};
+pub use outcome::{ScanOutcome, ScanResponse};
+pub use path_scan::{scan_path, PathCommand};
+pub use policy::{FailurePolicy, ScanDecision};
+pub use progress::ScanProgress;
+pub use pua::PuaPolicy;
+pub use queue::{ScanHandle, ScanJob, ScanQueue, ScanQueueOptions, SessionValidation};
+pub use reload::{schedule_reload, ReloadReport, ReloadSchedule, ReloadScheduler};
+pub use report::ScanReport;
+pub use resolver::{CachingResolver, Resolve, ResolvingTcp, SystemResolver, TimeoutResolver};
+pub use response::{is_error, is_ok, is_pong, is_reloading};
+pub use server_stats::{poll_stats, ServerStats, StatsPoller};
+pub use signature::SignatureName;
+pub use stats::ClientStats;
+pub use summary::{ScanResults, ScanSummary};
+pub use validate::{validate, ValidationCheck, ValidationProblem};
+pub use verdict::VerdictPolicy;
+
+#[cfg(unix)]
+pub use fallback::{SocketOrTcp, SocketOrTcpStream};
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use clam_client::{BoxFuture, ClamClient};
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use spool::CommittedScan;
+
+#[cfg(feature = "cache")]
+pub use cache::{MemoryCache, ScanCache};
+
+#[cfg(feature = "rayon")]
+pub use parallel::scan_directory_parallel;
+
+#[cfg(feature = "cache-redis")]
+pub use redis_cache::RedisCache;
+
+#[cfg(all(feature = "sendfile", target_os = "linux"))]
+pub use sendfile::scan_file_zero_copy;
+
+#[cfg(feature = "dns-check")]
+pub use dns_check::{check_database, current_database_version, DatabaseStatus};
+
+#[cfg(feature = "hickory-dns")]
+pub use resolver::HickoryResolver;
+
+#[cfg(feature = "hickory-dns")]
+pub use srv::{SrvEndpoint, SrvResolver};
+
+#[cfg(feature = "watch")]
+pub use watch::{watch_directory, DirectoryWatcher, WatchOptions};
+
+#[cfg(feature = "wasi")]
+pub use wasi::Connector;
+
+#[cfg(feature = "bind")]
+pub use bind::BoundTcp;
+
+#[cfg(feature = "decompress")]
+pub use decompress::{ContentEncoding, Decompress};
+
+#[cfg(all(
+    feature = "decompress",
+    feature = "bytes",
+    any(feature = "tokio", feature = "async-std")
+))]
+pub use decompress::DecompressStream;
+
+#[cfg(feature = "http-body")]
+pub use body::BodyStream;
+
+#[cfg(feature = "mail")]
+pub use mail::{scan_message, scan_parsed_message, MailPart};
+
+#[cfg(feature = "webhook")]
+pub use webhook::WebhookSink;
+
+#[cfg(feature = "siem")]
+pub use siem::EventFormatter;
+
+#[cfg(feature = "audit")]
+pub use audit::{AuditLog, AuditRecord};
+
+#[cfg(feature = "export")]
+pub use export::write_reports_csv;
+
+#[cfg(feature = "parquet")]
+pub use export::write_reports_parquet;
+
+#[cfg(feature = "schedule")]
+pub use schedule::{schedule_scans, ScanScheduler, ScheduledScan, ScheduledScanReport};
+
+#[cfg(feature = "serde")]
+pub use config::ClamdConfig;
+
+#[cfg(feature = "db-update")]
+pub use db_update::{
+    run_freshclam, update_databases, FreshclamOptions, FreshclamReport, FreshclamStatus,
+};
+
+#[cfg(feature = "test-util")]
+pub use mock::{MockStream, MockTransport};
+
+#[cfg(feature = "test-util")]
+pub use mock_server::{MockServer, MockServerOptions};
+
+#[cfg(all(feature = "test-util", unix))]
+pub use clamd_process::{ClamdProcess, ClamdProcessOptions};
+
+#[cfg(feature = "test-util")]
+pub use test_files::TestFiles;
+
 use std::{
     fs::File,
-    io::{self, Error, Read, Write},
-    net::{TcpStream, ToSocketAddrs},
+    io::{self, BufRead, BufReader, BufWriter, Error, Read, Seek, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
     path::Path,
+    process::{Command, Stdio},
     str::{self, Utf8Error},
+    thread,
+    time::{Duration, Instant},
 };
 
 #[cfg(unix)]
 use std::os::unix::net::UnixStream;
 
+use context::{Addresses, ResultExt};
+use dedup::Duplicates;
+use journal::Journal;
+use pipeline::Pipeline;
+use protocol::{Exchange, Frame, Instream, ResponseReader};
+use report::ReportTimer;
+use state::StateStore;
+use wipe::DataBuffer;
+
 /// Custom result type
 pub type IoResult = Result<Vec<u8>, Error>;
 
 /// Custom result type
 pub type Utf8Result = Result<bool, Utf8Error>;
 
-/// Default chunk size in bytes for reading data during scanning
+/// Default chunk size in bytes for reading data of small or unknown size
 const DEFAULT_CHUNK_SIZE: usize = 4096;
 
+/// Default size in bytes of the buffer that combines small chunks into one
+/// write
+const DEFAULT_WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The largest chunk whose length fits into the 4-byte header of a chunk
+const MAX_CHUNK_SIZE: usize = u32::MAX as usize;
+
 /// ClamAV commands
 const PING: &[u8; 6] = b"zPING\0";
 const VERSION: &[u8; 9] = b"zVERSION\0";
 const SHUTDOWN: &[u8; 10] = b"zSHUTDOWN\0";
+const RELOAD: &[u8; 8] = b"zRELOAD\0";
+const STATS: &[u8; 7] = b"zSTATS\0";
 const INSTREAM: &[u8; 10] = b"zINSTREAM\0";
+const IDSESSION: &[u8; 11] = b"zIDSESSION\0";
+const END: &[u8; 5] = b"zEND\0";
 const END_OF_STREAM: &[u8; 4] = &[0, 0, 0, 0];
 
 /// ClamAV's response to a PING request
 pub const PONG: &[u8; 5] = b"PONG\0";
 
+/// Returns the name of a command for tracing, e.g. `PING` for `zPING\0`
+#[cfg(feature = "tracing")]
+fn command_name(command: &[u8]) -> std::borrow::Cow<'_, str> {
+    let command = command.strip_prefix(b"z").unwrap_or(command);
+    let command = command.strip_suffix(b"\0").unwrap_or(command);
+    String::from_utf8_lossy(command)
+}
+
 fn send_command<RW: Read + Write>(
-    mut stream: RW,
+    stream: RW,
     command: &[u8],
     expected_response_length: Option<usize>,
 ) -> IoResult {
-    stream.write_all(command)?;
-    stream.flush()?;
+    let mut response = Vec::new();
+    send_command_into(
+        stream,
+        command,
+        &mut response,
+        &ResponseBuffer::expecting(expected_response_length),
+    )?;
+    Ok(response)
+}
 
-    let mut response = match expected_response_length {
-        Some(len) => Vec::with_capacity(len),
-        None => Vec::new(),
-    };
+/// Sends `command` and reads the response into `response`, which is cleared
+/// first
+fn send_command_into<RW: Read + Write>(
+    mut stream: RW,
+    command: &[u8],
+    response: &mut Vec<u8>,
+    buffer: &ResponseBuffer,
+) -> io::Result<()> {
+    let command = Exchange::new(command);
+    stream
+        .write_all(command.bytes())
+        .phase(ErrorPhase::Command)?;
+    stream.flush().phase(ErrorPhase::Command)?;
+    command.sent();
 
-    stream.read_to_end(&mut response)?;
-    Ok(response)
+    read_response(stream, response, buffer).phase(ErrorPhase::Response)?;
+    command.received(response);
+    Ok(())
+}
+
+/// Reads the response of ClamAV into `response`, which is cleared first
+fn read_response<R: Read>(
+    mut stream: R,
+    response: &mut Vec<u8>,
+    buffer: &ResponseBuffer,
+) -> io::Result<()> {
+    let mut reader = ResponseReader::new(response, buffer);
+    loop {
+        let read = stream.read(reader.unfilled());
+        if reader.advance(read)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Returns the 4-byte header of a chunk of `len` bytes
+///
+/// # Panics
+///
+/// Panics if `len` exceeds [`MAX_CHUNK_SIZE`], which callers prevent by
+/// splitting larger chunks.
+fn chunk_header(len: usize) -> [u8; 4] {
+    u32::try_from(len)
+        .expect("the chunk exceeds the maximum chunk size")
+        .to_be_bytes()
+}
+
+/// Writes a length-prefixed chunk, combining the 4-byte header and the payload
+/// into vectored writes
+fn write_chunk<W: Write>(stream: &mut W, chunk: &[u8]) -> io::Result<()> {
+    for mut frame in Frame::split(chunk) {
+        while !frame.is_written() {
+            frame.advance(stream.write_vectored(&frame.remaining()))?;
+        }
+    }
+    Ok(())
 }
 
 fn _ping<RW: Read + Write>(stream: RW) -> IoResult {
@@ -65,33 +477,168 @@ fn _get_version<RW: Read + Write>(stream: RW) -> IoResult {
 }
 
 fn scan<R: Read, RW: Read + Write>(
-    mut input: R,
-    chunk_size: Option<usize>,
+    input: R,
+    input_size: Option<u64>,
+    options: &ScanOptions,
     mut stream: RW,
+    mut report: ReportTimer<'_>,
 ) -> IoResult {
-    stream.write_all(INSTREAM)?;
+    let mut buffer = DataBuffer::default();
+    write_instream(
+        input,
+        input_size,
+        options,
+        &mut stream,
+        &mut buffer,
+        &mut report,
+    )?;
+
+    let mut response = Vec::new();
+    read_response(&mut stream, &mut response, &options.response_buffer)
+        .phase(ErrorPhase::Response)?;
+    report.finish(&response);
+    Ok(response)
+}
+
+/// Scans `input_size` bytes of `input` in parts of at most `split_size` bytes,
+/// each with its own connection, and returns the response to the first part
+/// that is not clean or to the last part
+fn scan_split<R: Read, T: TransportProtocol>(
+    mut input: R,
+    input_size: u64,
+    split_size: u64,
+    connection: &T,
+    options: &ScanOptions,
+) -> IoResult {
+    // A split size of zero would never make progress
+    options.validate()?;
+    let mut remaining = input_size;
+    loop {
+        let part_size = remaining.min(split_size);
+        remaining -= part_size;
+        let mut report = ReportTimer::start(options);
+        let stream = report.connect(connection.connect())?;
+        let part = (&mut input).take(part_size);
+        let response = scan(part, Some(part_size), options, stream, report)?;
+        if remaining == 0 || !outcome::is_clean_response(&response) {
+            return Ok(response);
+        }
+    }
+}
+
+/// Sends the INSTREAM command followed by the chunked `input`, using `buffer`
+/// as the chunk buffer so that it can be reused across scans
+///
+/// Small chunks are combined into one write using the write buffer of the
+/// options.
+fn write_instream<R: Read, W: Write>(
+    mut input: R,
+    input_size: Option<u64>,
+    options: &ScanOptions,
+    stream: &mut W,
+    buffer: &mut DataBuffer,
+    report: &mut ReportTimer<'_>,
+) -> io::Result<()> {
+    let mut instream = Instream::start(options, input_size)?;
+    let mut stream = BufWriter::with_capacity(options.write_buffer_size_or_default(), stream);
+    stream.write_all(INSTREAM).phase(ErrorPhase::Stream)?;
+    instream.started();
 
-    let chunk_size = chunk_size
-        .unwrap_or(DEFAULT_CHUNK_SIZE)
-        .min(u32::MAX as usize);
-    let mut buffer = vec![0; chunk_size];
+    let chunk_size = instream.chunk_size();
+    let buffer = buffer.get(chunk_size);
     loop {
-        let len = input.read(&mut buffer[..])?;
+        let len = input.read(buffer).phase(ErrorPhase::Input)?;
         if len != 0 {
-            stream.write_all(&(len as u32).to_be_bytes())?;
-            stream.write_all(&buffer[..len])?;
+            instream.input(len)?;
+            if let Some(delay) = instream.delay(len) {
+                stream.flush().phase(ErrorPhase::Stream)?;
+                thread::sleep(delay);
+            }
+            write_chunk(&mut stream, &buffer[..len]).phase(ErrorPhase::Stream)?;
+            instream.chunk_sent(&buffer[..len], report);
         } else {
-            stream.write_all(END_OF_STREAM)?;
-            stream.flush()?;
-            break;
+            instream.end()?;
+            stream.write_all(END_OF_STREAM).phase(ErrorPhase::Stream)?;
+            stream.flush().phase(ErrorPhase::Stream)?;
+            instream.finished(report);
+            return Ok(());
         }
     }
+}
+
+/// Streams one input to ClamAV within a session started with IDSESSION and
+/// reads the response
+fn scan_in_session<R: Read, S: Read + Write>(
+    input: R,
+    input_size: Option<u64>,
+    options: &ScanOptions,
+    stream: &mut BufReader<S>,
+    buffer: &mut DataBuffer,
+) -> Result<ScanOutcome, ClamdError> {
+    let mut report = ReportTimer::start(options);
+    report.connected();
+    write_instream(
+        input,
+        input_size,
+        options,
+        stream.get_mut(),
+        buffer,
+        &mut report,
+    )?;
 
     let mut response = Vec::new();
-    stream.read_to_end(&mut response)?;
+    read_session_response(stream, &mut response)?;
+    report.finish(&response);
+    ScanOutcome::from_response(outcome::strip_request_id(&response))
+}
+
+fn scan_slice<RW: Read + Write>(
+    data: &[u8],
+    options: &ScanOptions,
+    stream: RW,
+    report: ReportTimer<'_>,
+) -> IoResult {
+    let mut response = Vec::new();
+    scan_slice_into(data, options, stream, report, &mut response)?;
     Ok(response)
 }
 
+/// Streams `data` to ClamAV without copying it into a chunk buffer and reads
+/// the response into `response`, which is cleared first
+fn scan_slice_into<RW: Read + Write>(
+    data: &[u8],
+    options: &ScanOptions,
+    mut stream: RW,
+    mut report: ReportTimer<'_>,
+    response: &mut Vec<u8>,
+) -> io::Result<()> {
+    let mut instream = Instream::start(options, Some(data.len() as u64))?;
+    let mut buffered =
+        BufWriter::with_capacity(options.write_buffer_size_or_default(), &mut stream);
+    buffered.write_all(INSTREAM).phase(ErrorPhase::Stream)?;
+    instream.started();
+
+    for chunk in data.chunks(instream.chunk_size()) {
+        instream.input(chunk.len())?;
+        if let Some(delay) = instream.delay(chunk.len()) {
+            buffered.flush().phase(ErrorPhase::Stream)?;
+            thread::sleep(delay);
+        }
+        write_chunk(&mut buffered, chunk).phase(ErrorPhase::Stream)?;
+        instream.chunk_sent(chunk, &mut report);
+    }
+    buffered
+        .write_all(END_OF_STREAM)
+        .phase(ErrorPhase::Stream)?;
+    buffered.flush().phase(ErrorPhase::Stream)?;
+    drop(buffered);
+    instream.finished(&mut report);
+
+    read_response(&mut stream, response, &options.response_buffer).phase(ErrorPhase::Response)?;
+    report.finish(response);
+    Ok(())
+}
+
 /// Checks whether the ClamAV response indicates that the scanned content is
 /// clean or contains a virus
 ///
@@ -114,14 +661,14 @@ pub fn clean(response: &[u8]) -> Utf8Result {
 }
 
 /// Use a TCP connection to communicate with a ClamAV server
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Tcp<A: ToSocketAddrs> {
     /// The address (host and port) of the ClamAV server
     pub host_address: A,
 }
 
 /// Use a Unix socket connection to communicate with a ClamAV server
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg(unix)]
 pub struct Socket<P: AsRef<Path>> {
     /// The socket file path of the ClamAV server
@@ -135,14 +682,63 @@ pub trait TransportProtocol {
 
     /// Converts the protocol instance into the corresponding stream
     fn connect(&self) -> io::Result<Self::Stream>;
+
+    /// Converts the protocol instance into the corresponding stream, waiting
+    /// at most `timeout` for connecting and for each read and write
+    ///
+    /// The default implementation ignores the timeout.
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        let _ = timeout;
+        self.connect()
+    }
+
+    /// Returns `true` if ClamAV runs on the same host and can read its files,
+    /// which is assumed for Unix sockets
+    ///
+    /// The default implementation returns `false`.
+    fn is_local(&self) -> bool {
+        false
+    }
 }
 
 impl<A: ToSocketAddrs> TransportProtocol for Tcp<A> {
     type Stream = TcpStream;
 
     fn connect(&self) -> io::Result<Self::Stream> {
-        TcpStream::connect(&self.host_address)
+        let addresses = context::resolved(self.host_address.to_socket_addrs())?;
+        TcpStream::connect(&addresses[..]).phase_of(ErrorPhase::Connect, Addresses(&addresses))
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        let addresses = context::resolved(self.host_address.to_socket_addrs())?;
+        connect_timeout_any(addresses.iter().copied(), timeout)
+            .phase_of(ErrorPhase::Connect, Addresses(&addresses))
+    }
+}
+
+/// Connects to the first of `addresses` that accepts a connection within
+/// `timeout` and applies the timeout to reads and writes
+fn connect_timeout_any<I: IntoIterator<Item = SocketAddr>>(
+    addresses: I,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(err) => last_err = Some(err),
+        }
     }
+    Err(last_err.unwrap_or_else(|| {
+        Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        )
+    }))
 }
 
 #[cfg(unix)]
@@ -151,6 +747,35 @@ impl<P: AsRef<Path>> TransportProtocol for Socket<P> {
 
     fn connect(&self) -> io::Result<Self::Stream> {
         UnixStream::connect(&self.socket_path)
+            .phase_of(ErrorPhase::Connect, self.socket_path.as_ref().display())
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        // Connecting to a Unix socket does not block
+        let stream = self.connect()?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        Ok(stream)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+impl<T: TransportProtocol + ?Sized> TransportProtocol for &T {
+    type Stream = T::Stream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        (**self).connect()
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        (**self).connect_timeout(timeout)
+    }
+
+    fn is_local(&self) -> bool {
+        (**self).is_local()
     }
 }
 
@@ -172,7 +797,7 @@ impl<P: AsRef<Path>> TransportProtocol for Socket<P> {
 /// ```
 /// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
 /// let clamd_available = match clamav_client::ping(clamd_tcp) {
-///     Ok(ping_response) => ping_response == clamav_client::PONG,
+///     Ok(ping_response) => clamav_client::is_pong(&ping_response),
 ///     Err(_) => false,
 /// };
 /// # assert!(clamd_available);
@@ -183,6 +808,90 @@ pub fn ping<T: TransportProtocol>(connection: T) -> IoResult {
     _ping(stream)
 }
 
+/// Sends a ping request to ClamAV and writes the response into `response`
+///
+/// This function works like [`ping`], but clears `response` and reads the
+/// server's response into it instead of allocating a new vector, so that its
+/// capacity can be reused across calls.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let mut response = Vec::with_capacity(clamav_client::PONG.len());
+/// clamav_client::ping_into(clamd_tcp, &mut response).unwrap();
+/// assert_eq!(&response, clamav_client::PONG);
+/// ```
+///
+pub fn ping_into<T: TransportProtocol>(connection: T, response: &mut Vec<u8>) -> io::Result<()> {
+    let stream = connection.connect()?;
+    send_command_into(
+        stream,
+        PING,
+        response,
+        &ResponseBuffer::expecting(Some(PONG.len())),
+    )
+}
+
+/// Checks that ClamAV responds to a ping request with [`PONG`]
+///
+/// This function reads the response into a fixed-size buffer on the stack and
+/// does not allocate if ClamAV responds with [`PONG`]. A response that is
+/// longer than [`PONG`] or that is not a PONG according to [`is_pong`] fails
+/// with [`ClamdError::InvalidResponse`].
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// clamav_client::ping_ok(clamd_tcp).unwrap();
+/// ```
+///
+pub fn ping_ok<T: TransportProtocol>(connection: T) -> Result<(), ClamdError> {
+    let mut stream = connection.connect()?;
+    stream.write_all(PING).phase(ErrorPhase::Command)?;
+    stream.flush().phase(ErrorPhase::Command)?;
+    wire::command(PING);
+
+    // One byte more than `PONG` to detect longer responses
+    let mut response = [0; PONG.len() + 1];
+    let mut len = 0;
+    while len < response.len() {
+        match stream
+            .read(&mut response[len..])
+            .phase(ErrorPhase::Response)
+        {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    wire::response(&response[..len]);
+    response::expect_pong(&response[..len])
+}
+
+/// Checks whether ClamAV is available, i.e. whether it responds to a ping
+/// request with [`PONG`]
+///
+/// This function works like [`ping_ok`], but returns `false` instead of an
+/// error, e.g. if the connection fails, for readiness checks that only need a
+/// yes or no.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// if !clamav_client::is_available(clamd_tcp) {
+///     eprintln!("Cannot ping clamd at {}", clamd_tcp.host_address);
+/// }
+/// # assert!(clamav_client::is_available(clamd_tcp));
+/// ```
+///
+pub fn is_available<T: TransportProtocol>(connection: T) -> bool {
+    ping_ok(connection).is_ok()
+}
+
 /// Gets the version number from ClamAV
 ///
 /// This function establishes a connection to a ClamAV server and sends the
@@ -210,6 +919,94 @@ pub fn get_version<T: TransportProtocol>(connection: T) -> IoResult {
     _get_version(stream)
 }
 
+/// Gets the version of ClamAV and of its signature databases
+///
+/// This function works like [`get_version`], but parses the response into a
+/// [`VersionInfo`].
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let version = clamav_client::version(clamd_tcp).unwrap();
+/// println!("ClamAV {}", version.clamav_version);
+/// ```
+///
+pub fn version<T: TransportProtocol>(connection: T) -> Result<VersionInfo, ClamdError> {
+    VersionInfo::from_response(&get_version(connection)?)
+}
+
+/// Reloads the virus databases of ClamAV
+///
+/// This function establishes a connection to a ClamAV server and sends the
+/// RELOAD command to it. The server responds with `RELOADING` and reloads its
+/// virus databases in the background.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::reload(clamd_tcp).unwrap();
+/// # assert_eq!(&response, b"RELOADING\0");
+/// ```
+///
+pub fn reload<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect()?;
+    send_command(stream, RELOAD, None)
+}
+
+/// Gets statistics about the scan queue, threads and memory usage of ClamAV
+///
+/// This function establishes a connection to a ClamAV server and sends the
+/// STATS command to it. The server responds with a multi-line report that
+/// ends with `END`.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::get_stats(clamd_tcp).unwrap();
+/// # assert!(stats.starts_with(b"POOLS:"));
+/// ```
+///
+pub fn get_stats<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect()?;
+    send_command(stream, STATS, None)
+}
+
+/// Gets the queue length, threads and memory usage of ClamAV
+///
+/// This function works like [`get_stats`], but parses the response into
+/// [`ServerStats`].
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::server_stats(clamd_tcp).unwrap();
+/// println!("{} of {} threads busy", stats.busy_threads(), stats.max_threads);
+/// ```
+///
+pub fn server_stats<T: TransportProtocol>(connection: T) -> Result<ServerStats, ClamdError> {
+    ServerStats::from_response(&get_stats(connection)?)
+}
+
 /// Scans a file for viruses
 ///
 /// This function reads data from a file located at the specified `file_path`
@@ -219,44 +1016,336 @@ pub fn get_version<T: TransportProtocol>(connection: T) -> IoResult {
 ///
 /// * `file_path`: The path to the file to be scanned
 /// * `connection`: The connection type to use - either TCP or a Unix socket connection
-/// * `chunk_size`: An optional chunk size for reading data. If [`None`], a default chunk size is used
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
 ///
 /// # Returns
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the file exceeds the configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`] without connecting to the server, unless
+/// it is scanned by its path (see [`ScanOptions::path_fallback`]).
 ///
-pub fn scan_file<P: AsRef<Path>, T: TransportProtocol>(
+pub fn scan_file<P: AsRef<Path>, T: TransportProtocol, O: Into<ScanOptions>>(
     file_path: P,
     connection: T,
-    chunk_size: Option<usize>,
+    options: O,
 ) -> IoResult {
-    let file = File::open(file_path)?;
-    let stream = connection.connect()?;
-    scan(file, chunk_size, stream)
+    _scan_file(file_path.as_ref(), &connection, &options.into())
+}
+
+fn _scan_file<T: TransportProtocol>(
+    file_path: &Path,
+    connection: &T,
+    options: &ScanOptions,
+) -> IoResult {
+    let file = File::open(file_path).phase_of(ErrorPhase::Input, file_path.display())?;
+    let file_size = file
+        .metadata()
+        .phase_of(ErrorPhase::Input, file_path.display())?
+        .len();
+    if let Err(err) = options.check_input_size(file_size) {
+        // An empty file is rejected rather than scanned by its path
+        if file_size > 0 && options.path_fallback && connection.is_local() {
+            let path = std::fs::canonicalize(file_path)
+                .phase_of(ErrorPhase::Input, file_path.display())?;
+            return scan_path(path, PathCommand::Scan, connection);
+        }
+        return Err(err);
+    }
+    options.check_archive(&file, file_size)?;
+    if let Some(split_size) = options.split_size.filter(|&size| file_size > size) {
+        return scan_split(file, file_size, split_size, connection, options);
+    }
+
+    options.validate()?;
+    #[cfg(feature = "mmap")]
+    if options.memory_map && file_size > 0 {
+        // SAFETY: The mapping is read-only and dropped before returning. As
+        // documented on `ScanOptions::memory_map`, the file must not be
+        // truncated while it is being scanned.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        let mut report = ReportTimer::start(options);
+        let stream = report.connect(connection.connect())?;
+        return scan_slice(&mmap, options, stream, report);
+    }
+
+    let mut report = ReportTimer::start(options);
+    let stream = report.connect(connection.connect())?;
+    scan(file, Some(file_size), options, stream, report)
+}
+
+/// Scans a region of a file for viruses
+///
+/// This function streams the `length` bytes of the file at `file_path` that
+/// start at `offset` to a ClamAV server, as if they were a file of their own,
+/// e.g. to scan an object embedded in a container without extracting it
+/// first.
+///
+/// # Arguments
+///
+/// * `file_path`: The path to the file that contains the region
+/// * `offset`: The offset of the first byte of the region
+/// * `length`: The number of bytes of the region
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the region extends beyond the end of the file, the scan fails with an
+/// [`io::Error`] of kind [`InvalidInput`](io::ErrorKind::InvalidInput), and if
+/// it exceeds the configured maximum stream size, with
+/// [`ClamdError::SizeLimitExceeded`], both without connecting to the server.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::scan_file_range("README.md", 0, 64, clamd_tcp, None).unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// ```
+///
+pub fn scan_file_range<P: AsRef<Path>, T: TransportProtocol, O: Into<ScanOptions>>(
+    file_path: P,
+    offset: u64,
+    length: u64,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    let file_path = file_path.as_ref();
+    let mut file = File::open(file_path).phase_of(ErrorPhase::Input, file_path.display())?;
+    let file_size = file
+        .metadata()
+        .phase_of(ErrorPhase::Input, file_path.display())?
+        .len();
+    check_file_range(file_size, offset, length)?;
+    options.check_input_size(length)?;
+    file.seek(io::SeekFrom::Start(offset))
+        .phase_of(ErrorPhase::Input, file_path.display())?;
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect())?;
+    scan(file.take(length), Some(length), &options, stream, report)
+}
+
+/// Checks that the region of `length` bytes at `offset` lies within a file of
+/// `file_size` bytes
+pub(crate) fn check_file_range(file_size: u64, offset: u64, length: u64) -> io::Result<()> {
+    match offset.checked_add(length) {
+        Some(end) if end <= file_size => Ok(()),
+        _ => Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            "byte range exceeds the end of the file",
+        )),
+    }
 }
 
 /// Scans a data buffer for viruses
 ///
 /// This function streams the provided `buffer` data to a ClamAV server for
-/// scanning.
+/// scanning. The chunks are written directly from `buffer`, without copying
+/// them into a chunk buffer.
 ///
 /// # Arguments
 ///
-/// * `buffer`: The data to be scanned
+/// * `buffer`: The data to be scanned, e.g. a `&[u8]`, `Vec<u8>`, `String` or `Bytes`
 /// * `connection`: The connection type to use - either TCP or a Unix socket connection
-/// * `chunk_size`: An optional chunk size for reading data. If [`None`], a default chunk size is used
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
 ///
 /// # Returns
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the buffer exceeds the configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`] without connecting to the server.
 ///
-pub fn scan_buffer<T: TransportProtocol>(
-    buffer: &[u8],
+pub fn scan_buffer<B: AsRef<[u8]>, T: TransportProtocol, O: Into<ScanOptions>>(
+    buffer: B,
     connection: T,
-    chunk_size: Option<usize>,
+    options: O,
 ) -> IoResult {
-    let stream = connection.connect()?;
-    scan(buffer, chunk_size, stream)
+    let buffer = buffer.as_ref();
+    let options = options.into();
+    let buffer_size = buffer.len() as u64;
+    options.check_input_size(buffer_size)?;
+    options.check_archive(io::Cursor::new(buffer), buffer_size)?;
+    if let Some(split_size) = options.split_size.filter(|&size| buffer_size > size) {
+        return scan_split(buffer, buffer_size, split_size, &connection, &options);
+    }
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect())?;
+    scan_slice(buffer, &options, stream, report)
+}
+
+/// Scans shared bytes for viruses
+///
+/// This function works like [`scan_buffer`], but takes the [`Bytes`](bytes::Bytes)
+/// of e.g. a request body by value, so that the scan owns a reference to the
+/// shared buffer instead of borrowing it. The chunks are written as slices of
+/// the shared buffer, so the data is never copied.
+///
+/// Use the feature flag "bytes" to enable this function.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let bytes = bytes::Bytes::from_static(b"clean data");
+/// let response = clamav_client::scan_bytes(bytes.clone(), clamd_tcp, None).unwrap();
+/// # assert_eq!(clamav_client::clean(&response), Ok(true));
+/// ```
+///
+#[cfg(feature = "bytes")]
+pub fn scan_bytes<T: TransportProtocol, O: Into<ScanOptions>>(
+    bytes: bytes::Bytes,
+    connection: T,
+    options: O,
+) -> IoResult {
+    scan_buffer(bytes, connection, options)
+}
+
+/// Scans a data buffer for viruses and writes the response into `response`
+///
+/// This function works like [`scan_buffer`], but clears `response` and reads
+/// the server's response into it instead of allocating a new vector. The
+/// chunks are written directly from `buffer`, so that a scan does not need a
+/// chunk buffer either. Only scans that are split with
+/// [`ScanOptions::split_size`] still allocate their responses internally.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::ScanOptions::default();
+/// let mut response = Vec::new();
+/// for buffer in [&b"hello"[..], &b"world"[..]] {
+///     clamav_client::scan_buffer_into(buffer, &clamd_tcp, &options, &mut response).unwrap();
+///     assert_eq!(clamav_client::clean(&response), Ok(true));
+/// }
+/// ```
+///
+pub fn scan_buffer_into<B: AsRef<[u8]>, T: TransportProtocol>(
+    buffer: B,
+    connection: T,
+    options: &ScanOptions,
+    response: &mut Vec<u8>,
+) -> io::Result<()> {
+    let buffer = buffer.as_ref();
+    let buffer_size = buffer.len() as u64;
+    options.check_input_size(buffer_size)?;
+    options.check_archive(io::Cursor::new(buffer), buffer_size)?;
+    if let Some(split_size) = options.split_size.filter(|&size| buffer_size > size) {
+        let split_response = scan_split(buffer, buffer_size, split_size, &connection, options)?;
+        response.clear();
+        response.extend_from_slice(&split_response);
+        return Ok(());
+    }
+    options.validate()?;
+    let mut report = ReportTimer::start(options);
+    let stream = report.connect(connection.connect())?;
+    scan_slice_into(buffer, options, stream, report, response)
+}
+
+/// Scans the data of a reader for viruses
+///
+/// This function streams the data read from `reader` to a ClamAV server for
+/// scanning until the reader reaches its end.
+///
+/// # Arguments
+///
+/// * `reader`: The reader of the data to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the data exceeds the configured maximum stream size, the scan is aborted
+/// with [`ClamdError::SizeLimitExceeded`].
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let file = std::fs::File::open("README.md").unwrap();
+/// let response = clamav_client::scan_reader(file, clamd_tcp, None).unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// ```
+///
+pub fn scan_reader<R: Read, T: TransportProtocol, O: Into<ScanOptions>>(
+    reader: R,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect())?;
+    scan(reader, None, &options, stream, report)
+}
+
+/// Runs a command and scans its standard output for viruses
+///
+/// This function spawns `command` with its standard output piped and streams
+/// the output to a ClamAV server while the command runs, without storing it,
+/// e.g. to scan an artifact decrypted by an external tool. If the scan fails
+/// before the output has been read to its end, the command is killed.
+///
+/// # Arguments
+///
+/// * `command`: The command to run
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the output exceeds the configured maximum stream size, the scan is aborted
+/// with [`ClamdError::SizeLimitExceeded`]. If the command exits unsuccessfully,
+/// its output may be incomplete and the scan fails with an [`io::Error`] of
+/// kind [`Other`](io::ErrorKind::Other).
+///
+/// # Example
+///
+/// ```
+/// use std::process::Command;
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let mut command = Command::new("cat");
+/// command.arg("README.md");
+/// let response = clamav_client::scan_command_output(&mut command, clamd_tcp, None).unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// ```
+///
+pub fn scan_command_output<T: TransportProtocol, O: Into<ScanOptions>>(
+    command: &mut Command,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect())?;
+
+    let mut child = command.stdout(Stdio::piped()).spawn()?;
+    let output = child.stdout.take().expect("the standard output is piped");
+    let response = match scan(output, None, &options, stream, report) {
+        Ok(response) => response,
+        Err(err) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(err);
+        }
+    };
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("command exited with {}", status)));
+    }
+    Ok(response)
 }
 
 /// Shuts down a ClamAV server
@@ -277,3 +1366,193 @@ pub fn shutdown<T: TransportProtocol>(connection: T) -> IoResult {
     let stream = connection.connect()?;
     send_command(stream, SHUTDOWN, None)
 }
+
+/// Scans multiple data buffers for viruses using a single connection
+///
+/// This function establishes one connection to a ClamAV server, starts a
+/// session (IDSESSION) and streams each buffer to the server in turn. This
+/// avoids a new connection per buffer when scanning many small buffers. With
+/// a [pipeline depth](ScanOptions::pipeline_depth) greater than one, the next
+/// buffers are streamed while responses are still outstanding.
+///
+/// # Arguments
+///
+/// * `buffers`: The data buffers to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// The [`ScanOutcome`] of each buffer, in the order of the input. If any buffer
+/// exceeds the configured maximum stream size, the batch fails with
+/// [`ClamdError::SizeLimitExceeded`] without connecting to the server.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let buffers = [&b"clean data"[..], &b"more clean data"[..]];
+/// let outcomes = clamav_client::scan_buffers(buffers, clamd_tcp, None).unwrap();
+/// # assert!(outcomes.iter().all(|outcome| outcome.is_clean()));
+/// ```
+///
+pub fn scan_buffers<I, B, T, O>(
+    buffers: I,
+    connection: T,
+    options: O,
+) -> Result<Vec<ScanOutcome>, ClamdError>
+where
+    I: IntoIterator<Item = B>,
+    B: AsRef<[u8]>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    // A pipeline depth of zero would wait for a response before the first
+    // buffer is sent
+    options.validate()?;
+    let buffers: Vec<B> = buffers.into_iter().collect();
+    for buffer in &buffers {
+        let buffer_size = buffer.as_ref().len() as u64;
+        options.check_input_size(buffer_size)?;
+        options.check_archive(io::Cursor::new(buffer.as_ref()), buffer_size)?;
+    }
+    if buffers.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut stream = BufReader::new(connection.connect()?);
+    stream
+        .get_mut()
+        .write_all(IDSESSION)
+        .phase(ErrorPhase::Handshake)?;
+    wire::command(IDSESSION);
+
+    let mut chunk_buffer = DataBuffer::default();
+    let mut response = Vec::new();
+    let duplicates = Duplicates::of_buffers(&buffers, options.deduplicate);
+    let mut pipeline = Pipeline::new(&options, duplicates.distinct().len());
+    for &index in duplicates.distinct() {
+        if pipeline.is_full() {
+            receive_pipelined(&mut stream, &mut response, &mut pipeline)?;
+        }
+        let buffer = buffers[index].as_ref();
+        let buffer_size = Some(buffer.len() as u64);
+        let mut report = ReportTimer::start(&options);
+        report.connected();
+        write_instream(
+            buffer,
+            buffer_size,
+            &options,
+            stream.get_mut(),
+            &mut chunk_buffer,
+            &mut report,
+        )?;
+        pipeline.sent(report);
+    }
+    while !pipeline.is_empty() {
+        receive_pipelined(&mut stream, &mut response, &mut pipeline)?;
+    }
+
+    stream.get_mut().write_all(END).phase(ErrorPhase::Command)?;
+    wire::command(END);
+    stream.get_mut().flush().phase(ErrorPhase::Command)?;
+    Ok(duplicates.fan_out(pipeline.into_outcomes()))
+}
+
+/// Reads the next response within a session and passes it to `pipeline`
+fn receive_pipelined<S: Read>(
+    stream: &mut BufReader<S>,
+    response: &mut Vec<u8>,
+    pipeline: &mut Pipeline<'_>,
+) -> Result<(), ClamdError> {
+    response.clear();
+    read_session_response(stream, response)?;
+    pipeline.receive(response)
+}
+
+/// Reads the next NUL-terminated response within a session into `response`
+fn read_session_response<S: Read>(
+    stream: &mut BufReader<S>,
+    response: &mut Vec<u8>,
+) -> io::Result<()> {
+    match stream.read_until(0, response) {
+        Ok(0) => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+        result => result.map(drop),
+    }
+    .phase(ErrorPhase::Response)
+}
+
+/// Scans all files in a directory tree for viruses
+///
+/// This function walks the directory tree at `dir_path` on the client side and
+/// streams each file to a ClamAV server for scanning, using a new connection
+/// per file. Files are visited depth-first, in lexicographic order within each
+/// directory. If `dir_path` is a file, only that file is scanned.
+///
+/// # Arguments
+///
+/// * `dir_path`: The path to the directory to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `directory_options`: The [`DirectoryScanOptions`] that control which files are scanned
+///
+/// # Returns
+///
+/// The [`ScanResults`] with the path of each scanned file together with its
+/// [`ScanOutcome`], or the error that occurred while reading or scanning it,
+/// and a [`ScanSummary`] of all scans. Directories that cannot be read are
+/// reported with their error as well.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+/// let results = clamav_client::scan_directory("tests/data", clamd_tcp, None, directory_options);
+/// for (path, result) in results {
+///     match result {
+///         Ok(outcome) if outcome.is_infected() => println!("{} is infected!", path.display()),
+///         Ok(_) => println!("No virus found in {}", path.display()),
+///         Err(err) => println!("Could not scan {}: {}", path.display(), err),
+///     }
+/// }
+/// ```
+///
+pub fn scan_directory<P: AsRef<Path>, T: TransportProtocol, O: Into<ScanOptions>>(
+    dir_path: P,
+    connection: T,
+    options: O,
+    directory_options: DirectoryScanOptions,
+) -> ScanResults {
+    let options = options.into();
+    let start = Instant::now();
+    let walk = directory::Walk::new(dir_path.as_ref(), directory_options);
+    let journal = walk.journal();
+    let state = walk.state();
+    #[cfg(feature = "sha256")]
+    let mut seen_files = options.deduplicate.then(dedup::SeenFiles::default);
+    let results = walk
+        .map(|entry| match entry {
+            Ok(path) => {
+                let result = Journal::scan(journal.as_deref(), &path, || {
+                    StateStore::scan(state.as_deref(), &path, || {
+                        let scan = || {
+                            _scan_file(&path, &connection, &options)
+                                .map_err(ClamdError::from)
+                                .and_then(|response| ScanOutcome::from_response(&response))
+                        };
+                        #[cfg(feature = "sha256")]
+                        if let Some(seen_files) = seen_files.as_mut() {
+                            return seen_files.scan(&path, scan);
+                        }
+                        scan()
+                    })
+                });
+                (path, result)
+            }
+            Err((path, err)) => (path, Err(err.into())),
+        })
+        .collect();
+    ScanResults::from_file_results(results, start.elapsed())
+}
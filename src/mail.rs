@@ -0,0 +1,155 @@
+use std::io::{self, BufReader, Write};
+
+use mail_parser::{Message, MessageParser, MimeHeaders, PartType};
+
+use super::{
+    scan_in_session, wipe::DataBuffer, wire, ClamdError, ScanOptions, ScanOutcome,
+    TransportProtocol, END, IDSESSION,
+};
+
+/// A part of an email message scanned by [`scan_message`] or
+/// [`scan_parsed_message`]
+///
+/// Use the feature flag "mail" to enable this type.
+///
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct MailPart {
+    /// The index of the part in the parts of the parsed message
+    pub part_id: usize,
+    /// Whether the part is an attachment rather than a body of the message
+    pub is_attachment: bool,
+    /// The file name of the part, usually set for attachments
+    pub file_name: Option<String>,
+    /// The content type of the part, e.g. `application/pdf`
+    pub content_type: Option<String>,
+    /// The size of the decoded contents of the part in bytes
+    pub size: usize,
+    /// The outcome of scanning the decoded contents of the part, or the error
+    /// that occurred
+    pub result: Result<ScanOutcome, ClamdError>,
+}
+
+/// Scans each body and attachment of an email message for viruses
+///
+/// This function parses a raw RFC 5322 message, decodes each of its MIME parts
+/// (e.g. from base64) and scans them one by one using a single connection and
+/// session (IDSESSION). Multipart containers are not scanned themselves, their
+/// parts are. Nested messages, e.g. forwarded emails, are scanned as a whole.
+///
+/// Use the feature flag "mail" to enable this function.
+///
+/// # Arguments
+///
+/// * `raw_message`: The raw message, including its headers
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// A [`MailPart`] with the result of each scanned part, in the order of the
+/// message. Parts that exceed the configured maximum stream size fail with
+/// [`ClamdError::SizeLimitExceeded`] without being sent. If the message cannot
+/// be parsed, the scan fails with an [`io::Error`] of kind
+/// [`InvalidData`](io::ErrorKind::InvalidData).
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let raw_message = b"From: alice@example.com\r\nSubject: Hello\r\n\r\nHello Bob\r\n";
+/// let parts = clamav_client::scan_message(raw_message, clamd_tcp, None).unwrap();
+/// for part in parts {
+///     match part.result {
+///         Ok(outcome) if outcome.is_infected() => println!("Part {} is infected!", part.part_id),
+///         Ok(_) => println!("No virus found in part {}", part.part_id),
+///         Err(err) => println!("Could not scan part {}: {}", part.part_id, err),
+///     }
+/// }
+/// ```
+///
+pub fn scan_message<T: TransportProtocol, O: Into<ScanOptions>>(
+    raw_message: &[u8],
+    connection: T,
+    options: O,
+) -> Result<Vec<MailPart>, ClamdError> {
+    let message = MessageParser::default()
+        .parse(raw_message)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid email message"))?;
+    scan_parsed_message(&message, connection, options)
+}
+
+/// Scans each body and attachment of an email message parsed by `mail-parser`
+/// for viruses
+///
+/// See [`scan_message`] for details.
+///
+/// Use the feature flag "mail" to enable this function.
+///
+/// # Arguments
+///
+/// * `message`: The parsed message
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// A [`MailPart`] with the result of each scanned part, in the order of the
+/// message
+///
+pub fn scan_parsed_message<T: TransportProtocol, O: Into<ScanOptions>>(
+    message: &Message<'_>,
+    connection: T,
+    options: O,
+) -> Result<Vec<MailPart>, ClamdError> {
+    let options = options.into();
+    let mut stream = None;
+    let mut chunk_buffer = DataBuffer::default();
+    let mut parts = Vec::new();
+    for (part_id, part) in message.parts.iter().enumerate() {
+        if let PartType::Multipart(_) = part.body {
+            continue;
+        }
+        let contents = part.contents();
+        let contents_size = contents.len() as u64;
+        let result = match options.check_input_size(contents_size) {
+            Ok(()) => {
+                if stream.is_none() {
+                    let mut session = BufReader::new(connection.connect()?);
+                    session.get_mut().write_all(IDSESSION)?;
+                    wire::command(IDSESSION);
+                    stream = Some(session);
+                }
+                let stream = stream.as_mut().unwrap();
+                Ok(scan_in_session(
+                    contents,
+                    Some(contents_size),
+                    &options,
+                    stream,
+                    &mut chunk_buffer,
+                )?)
+            }
+            Err(err) => Err(err.into()),
+        };
+        parts.push(MailPart {
+            part_id,
+            is_attachment: message.attachments.contains(&(part_id as u32)),
+            file_name: part.attachment_name().map(str::to_owned),
+            content_type: part
+                .content_type()
+                .map(|content_type| match content_type.subtype() {
+                    Some(subtype) => format!("{}/{}", content_type.ctype(), subtype),
+                    None => content_type.ctype().to_owned(),
+                }),
+            size: contents.len(),
+            result,
+        });
+    }
+
+    if let Some(mut stream) = stream {
+        stream.get_mut().write_all(END)?;
+        stream.get_mut().flush()?;
+        wire::command(END);
+    }
+    Ok(parts)
+}
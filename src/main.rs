@@ -0,0 +1,16 @@
+//! A command-line client for a ClamAV server
+//!
+//! Use the feature flag "cli" to build this binary. Its arguments and commands
+//! are available to other binaries in the [`cli`](clamav_client::cli) module.
+//!
+//! Like `clamscan`, the `scan` subcommand exits with `0` if no virus was found,
+//! `1` if a virus was found and `2` if an error occurred.
+
+use std::process::ExitCode;
+
+use clamav_client::cli::{self, Cli};
+use clap::Parser;
+
+fn main() -> ExitCode {
+    cli::run(Cli::parse())
+}
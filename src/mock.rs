@@ -0,0 +1,255 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Cursor, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::TransportProtocol;
+
+/// A transport that answers with scripted responses instead of connecting to
+/// ClamAV, for testing code that uses this crate without a running server
+///
+/// Each connection receives the next scripted response, regardless of the
+/// command sent, and records the data written to it, which can be inspected
+/// using [`requests`](Self::requests). Connecting fails with
+/// [`ConnectionRefused`](io::ErrorKind::ConnectionRefused) once all responses
+/// are used up, or if the connection was scripted to be refused. A connection
+/// can also be scripted to be reset after receiving some data. Clones of the
+/// transport share their responses and requests.
+///
+/// The transport implements the transport protocols of all modules, i.e. of
+/// this crate, of [`tokio`](crate::tokio) and of [`async_std`](crate::async_std).
+///
+/// Use the feature flag "test-util" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// let transport = clamav_client::MockTransport::new()
+///     .clean()
+///     .infected("Eicar-Signature");
+/// let client = clamav_client::Client::new(transport.clone());
+///
+/// assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+/// assert!(client.scan_buffer(b"infected data").unwrap().is_infected());
+/// assert!(client.scan_buffer(b"more data").is_err());
+/// assert!(transport.requests()[0].starts_with(b"zINSTREAM\0"));
+/// ```
+///
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    responses: VecDeque<MockResponse>,
+    requests: Vec<Vec<u8>>,
+}
+
+enum MockResponse {
+    Respond(Vec<u8>),
+    Reset(usize),
+    Refuse,
+}
+
+impl MockTransport {
+    /// Creates a transport without any scripted responses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers the next connection with `response`, e.g.
+    /// `b"stream: OK\0".to_vec()`
+    pub fn respond<R: Into<Vec<u8>>>(self, response: R) -> Self {
+        self.push(MockResponse::Respond(response.into()));
+        self
+    }
+
+    /// Answers the next connection with the response of ClamAV for clean
+    /// content
+    pub fn clean(self) -> Self {
+        self.respond(b"stream: OK\0".to_vec())
+    }
+
+    /// Answers the next connection with the response of ClamAV for content
+    /// matching `signature`
+    pub fn infected(self, signature: &str) -> Self {
+        self.respond(format!("stream: {} FOUND\0", signature))
+    }
+
+    /// Refuses the next connection, like an unavailable server
+    pub fn refuse(self) -> Self {
+        self.push(MockResponse::Refuse);
+        self
+    }
+
+    /// Resets the next connection after it received `bytes` bytes, like a
+    /// server that crashes or a proxy that resets the connection in the middle
+    /// of a stream
+    pub fn reset_after(self, bytes: usize) -> Self {
+        self.push(MockResponse::Reset(bytes));
+        self
+    }
+
+    /// Returns the data written to each connection so far, in the order of
+    /// the connections
+    pub fn requests(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    fn push(&self, response: MockResponse) {
+        self.state.lock().unwrap().responses.push_back(response);
+    }
+
+    fn open(&self) -> io::Result<MockStream> {
+        let mut state = self.state.lock().unwrap();
+        let (response, reset_after) = match state.responses.pop_front() {
+            Some(MockResponse::Respond(response)) => (response, None),
+            Some(MockResponse::Reset(bytes)) => (Vec::new(), Some(bytes)),
+            Some(MockResponse::Refuse) | None => {
+                return Err(io::ErrorKind::ConnectionRefused.into())
+            }
+        };
+        state.requests.push(Vec::new());
+        Ok(MockStream {
+            response: Cursor::new(response),
+            reset_after,
+            state: Arc::clone(&self.state),
+            index: state.requests.len() - 1,
+        })
+    }
+}
+
+/// A connection of a [`MockTransport`]
+///
+/// Use the feature flag "test-util" to enable this type.
+pub struct MockStream {
+    response: Cursor<Vec<u8>>,
+    reset_after: Option<usize>,
+    state: Arc<Mutex<MockState>>,
+    index: usize,
+}
+
+impl Read for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.reset_after.is_some() {
+            return Err(io::ErrorKind::ConnectionReset.into());
+        }
+        self.response.read(buf)
+    }
+}
+
+impl Write for MockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = match &mut self.reset_after {
+            Some(0) => return Err(io::ErrorKind::ConnectionReset.into()),
+            Some(remaining) => {
+                let len = buf.len().min(*remaining);
+                *remaining -= len;
+                len
+            }
+            None => buf.len(),
+        };
+        self.state.lock().unwrap().requests[self.index].extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl TransportProtocol for MockTransport {
+    type Stream = MockStream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        self.open()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for MockStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = self.get_mut().read(buf.initialize_unfilled())?;
+        buf.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for MockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl crate::tokio::TransportProtocol for MockTransport {
+    type Stream = MockStream;
+
+    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
+        std::future::ready(self.open())
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl async_std::io::Read for MockStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().read(buf))
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl async_std::io::Write for MockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl crate::async_std::TransportProtocol for MockTransport {
+    type Stream = MockStream;
+
+    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
+        std::future::ready(self.open())
+    }
+}
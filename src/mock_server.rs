@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+};
+
+use sha2::{Digest, Sha256};
+
+use super::health::EICAR;
+
+/// The signature the official ClamAV databases report for the EICAR test file
+const EICAR_SIGNATURE: &str = "Win.Test.EICAR_HDB-1";
+
+/// Options for a [`MockServer`]
+///
+/// By default, the server reports the EICAR test file as infected with
+/// `Win.Test.EICAR_HDB-1`, like ClamAV with its official databases, and all
+/// other content as clean.
+///
+/// To test how code handles an unreliable or overloaded server, e.g. its
+/// retries, the options can inject faults: a latency before each response,
+/// connections reset in the middle of a stream, truncated responses and the
+/// size limit error of ClamAV. The faults apply to all connections, or only to
+/// the first ones with [`faulty_connections`](Self::faulty_connections).
+///
+/// Use the feature flag "test-util" to enable this type.
+#[derive(Clone)]
+pub struct MockServerOptions {
+    version: String,
+    infected_digests: HashMap<[u8; 32], String>,
+    infected_sizes: HashMap<u64, String>,
+    latency: Duration,
+    reset_after: Option<u64>,
+    truncate_response: Option<usize>,
+    stream_max_length: Option<u64>,
+    faulty_connections: Option<usize>,
+}
+
+impl Default for MockServerOptions {
+    fn default() -> Self {
+        MockServerOptions {
+            version: "ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024".to_owned(),
+            infected_digests: HashMap::new(),
+            infected_sizes: HashMap::new(),
+            latency: Duration::ZERO,
+            reset_after: None,
+            truncate_response: None,
+            stream_max_length: None,
+            faulty_connections: None,
+        }
+    }
+}
+
+impl MockServerOptions {
+    /// Creates the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the response to the VERSION command
+    pub fn version<V: Into<String>>(mut self, version: V) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Reports content with the SHA-256 digest `sha256` as infected with
+    /// `signature`
+    pub fn infected_sha256<S: Into<String>>(mut self, sha256: [u8; 32], signature: S) -> Self {
+        self.infected_digests.insert(sha256, signature.into());
+        self
+    }
+
+    /// Reports content of `size` bytes as infected with `signature`, unless
+    /// its digest has a verdict
+    pub fn infected_size<S: Into<String>>(mut self, size: u64, signature: S) -> Self {
+        self.infected_sizes.insert(size, signature.into());
+        self
+    }
+
+    /// Waits for `latency` before sending each response
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Closes connections without a response after receiving `bytes` bytes
+    /// of the data to scan, like a server that crashes or a proxy that resets
+    /// the connection in the middle of a stream
+    pub fn reset_after(mut self, bytes: u64) -> Self {
+        self.reset_after = Some(bytes);
+        self
+    }
+
+    /// Sends only the first `len` bytes of each response before closing the
+    /// connection
+    pub fn truncate_response(mut self, len: usize) -> Self {
+        self.truncate_response = Some(len);
+        self
+    }
+
+    /// Answers with `INSTREAM size limit exceeded. ERROR` once more than
+    /// `bytes` bytes of data to scan were received, like ClamAV with the
+    /// setting `StreamMaxLength`
+    pub fn stream_max_length(mut self, bytes: u64) -> Self {
+        self.stream_max_length = Some(bytes);
+        self
+    }
+
+    /// Injects the configured faults only into the first `count` connections,
+    /// so that a retry can succeed
+    ///
+    /// The size limit of [`stream_max_length`](Self::stream_max_length)
+    /// applies to all connections.
+    pub fn faulty_connections(mut self, count: usize) -> Self {
+        self.faulty_connections = Some(count);
+        self
+    }
+
+    fn signature(&self, data: &[u8]) -> Option<&str> {
+        let digest: [u8; 32] = Sha256::digest(data).into();
+        self.infected_digests
+            .get(&digest)
+            .or_else(|| self.infected_sizes.get(&(data.len() as u64)))
+            .map(String::as_str)
+            .or_else(|| is_eicar(data).then_some(EICAR_SIGNATURE))
+    }
+}
+
+/// Returns whether `data` is the EICAR test file, which may be followed by
+/// whitespace up to a total of 128 bytes
+fn is_eicar(data: &[u8]) -> bool {
+    data.len() <= 128
+        && data.starts_with(EICAR)
+        && data[EICAR.len()..].iter().all(u8::is_ascii_whitespace)
+}
+
+/// A local server that speaks enough of the protocol of ClamAV to test code
+/// using this crate without a running ClamAV, e.g. in CI
+///
+/// The server answers PING, VERSION, RELOAD, STATS, INSTREAM and SCAN, with
+/// the verdicts configured in its [`MockServerOptions`], also within an
+/// IDSESSION. SCAN reads a single file of the local file system, like ClamAV
+/// on the same host. It listens on a TCP port
+/// of `127.0.0.1` or on a Unix socket, serves each connection in its own
+/// thread and stops when it is dropped, so it can be used in `#[test]` and
+/// `#[tokio::test]` functions alike.
+///
+/// Use the feature flag "test-util" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{MockServer, MockServerOptions};
+///
+/// let options = MockServerOptions::new().infected_size(10, "Test-Signature");
+/// let server = MockServer::start(options).unwrap();
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: server.address() };
+/// let client = clamav_client::Client::new(clamd_tcp);
+/// let outcome = client.scan_buffer(b"clean data").unwrap();
+/// assert_eq!(outcome.signature(), Some("Test-Signature"));
+/// ```
+///
+pub struct MockServer {
+    address: String,
+    #[cfg(unix)]
+    unix: bool,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Starts a server listening on a free TCP port of `127.0.0.1`
+    pub fn start(options: MockServerOptions) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?.to_string();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = spawn_accept_loop(Arc::clone(&stopped), options, move || {
+            listener.accept().map(|(stream, _)| stream)
+        });
+        Ok(MockServer {
+            address,
+            #[cfg(unix)]
+            unix: false,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// Starts a server listening on a Unix socket at `socket_path`, which
+    /// is removed when the server stops
+    #[cfg(unix)]
+    pub fn start_unix<P: AsRef<Path>>(
+        socket_path: P,
+        options: MockServerOptions,
+    ) -> io::Result<Self> {
+        let listener = UnixListener::bind(socket_path.as_ref())?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = spawn_accept_loop(Arc::clone(&stopped), options, move || {
+            listener.accept().map(|(stream, _)| stream)
+        });
+        Ok(MockServer {
+            address: socket_path.as_ref().to_string_lossy().into_owned(),
+            unix: true,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the address of the server, i.e. `127.0.0.1` and the port for
+    /// TCP or the path of the Unix socket
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wakes up the accept loop, which then sees that it was stopped
+        #[cfg(unix)]
+        let woken = if self.unix {
+            UnixStream::connect(&self.address).is_ok()
+        } else {
+            TcpStream::connect(&self.address).is_ok()
+        };
+        #[cfg(not(unix))]
+        let woken = TcpStream::connect(&self.address).is_ok();
+        if let (true, Some(thread)) = (woken, self.thread.take()) {
+            let _ = thread.join();
+        }
+        #[cfg(unix)]
+        if self.unix {
+            let _ = std::fs::remove_file(&self.address);
+        }
+    }
+}
+
+fn spawn_accept_loop<S, A>(
+    stopped: Arc<AtomicBool>,
+    options: MockServerOptions,
+    mut accept: A,
+) -> JoinHandle<()>
+where
+    S: Read + Write + Send + 'static,
+    A: FnMut() -> io::Result<S> + Send + 'static,
+{
+    let options = Arc::new(options);
+    let connections = AtomicUsize::new(0);
+    thread::spawn(move || loop {
+        let stream = accept();
+        if stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Ok(stream) = stream {
+            let options = Arc::clone(&options);
+            let faulty = options.faulty_connections.map_or(true, |count| {
+                connections.fetch_add(1, Ordering::SeqCst) < count
+            });
+            thread::spawn(move || {
+                // Errors only affect the connection, e.g. if a client hangs up
+                let _ = serve(stream, &options, faulty);
+            });
+        }
+    })
+}
+
+/// Answers a single command, or the commands of an IDSESSION until END, like
+/// ClamAV, and injects the configured faults if the connection is `faulty`
+fn serve<S: Read + Write>(stream: S, options: &MockServerOptions, faulty: bool) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut session = None;
+    let mut command = Vec::new();
+    loop {
+        let delimiter = match stream.fill_buf()?.first() {
+            Some(b'z') => 0,
+            Some(b'n') => b'\n',
+            _ => return Ok(()),
+        };
+        command.clear();
+        stream.read_until(delimiter, &mut command)?;
+        let name = command[1..]
+            .strip_suffix(&[delimiter])
+            .unwrap_or(&command[1..]);
+
+        let response = match (name, &mut session) {
+            (b"IDSESSION", None) => {
+                session = Some(0);
+                continue;
+            }
+            (b"END", Some(_)) => return Ok(()),
+            (_, None) => answer(name, &mut stream, options, faulty)?,
+            (_, Some(id)) => {
+                *id += 1;
+                format!("{}: {}", id, answer(name, &mut stream, options, faulty)?)
+            }
+        };
+
+        let mut response = response.into_bytes();
+        response.push(delimiter);
+        if faulty {
+            thread::sleep(options.latency);
+            if let Some(len) = options.truncate_response {
+                response.truncate(len);
+            }
+        }
+        let writer = stream.get_mut();
+        writer.write_all(&response)?;
+        writer.flush()?;
+        if session.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Answers the command `name`, reading its data from `stream`
+fn answer<R: Read>(
+    name: &[u8],
+    stream: &mut R,
+    options: &MockServerOptions,
+    faulty: bool,
+) -> io::Result<String> {
+    let response = match name {
+        b"PING" => "PONG".to_owned(),
+        b"VERSION" => options.version.clone(),
+        b"RELOAD" => "RELOADING".to_owned(),
+        b"STATS" => "POOLS: 1\n\nSTATE: VALID PRIMARY\nTHREADS: live 1  idle 0 max 10 \
+                     idle-timeout 30\nQUEUE: 0 items\nMEMSTATS: heap N/A mmap N/A used N/A \
+                     free N/A releasable N/A pools 1 pools_used N/A pools_total N/A\nEND"
+            .to_owned(),
+        b"INSTREAM" => {
+            let reset_after = options.reset_after.filter(|_| faulty);
+            match read_instream(stream, options.stream_max_length, reset_after)? {
+                Some(data) => match options.signature(&data) {
+                    Some(signature) => format!("stream: {} FOUND", signature),
+                    None => "stream: OK".to_owned(),
+                },
+                None => "INSTREAM size limit exceeded. ERROR".to_owned(),
+            }
+        }
+        _ => match name.strip_prefix(b"SCAN ") {
+            Some(path) => scan_file(&String::from_utf8_lossy(path), options),
+            None => "UNKNOWN COMMAND".to_owned(),
+        },
+    };
+    Ok(response)
+}
+
+/// Answers the SCAN command for the file at `path`
+fn scan_file(path: &str, options: &MockServerOptions) -> String {
+    match std::fs::read(path) {
+        Ok(data) => match options.signature(&data) {
+            Some(signature) => format!("{}: {} FOUND", path, signature),
+            None => format!("{}: OK", path),
+        },
+        Err(err) => format!("{}: lstat() failed: {}. ERROR", path, err),
+    }
+}
+
+/// Reads the chunks of an INSTREAM command until the zero-length chunk
+///
+/// Returns `None` if the data exceeds `max_length`, after discarding the rest
+/// of the stream, and fails with
+/// [`ConnectionReset`](io::ErrorKind::ConnectionReset) after `reset_after`
+/// bytes, which closes the connection.
+fn read_instream<R: Read>(
+    stream: &mut R,
+    max_length: Option<u64>,
+    reset_after: Option<u64>,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    let mut received = 0;
+    loop {
+        let mut header = [0; 4];
+        stream.read_exact(&mut header)?;
+        let len = u32::from_be_bytes(header) as u64;
+        if len == 0 {
+            let exceeded = max_length.is_some_and(|max_length| received > max_length);
+            return Ok((!exceeded).then_some(data));
+        }
+        let readable = reset_after.map_or(len, |bytes| len.min(bytes.saturating_sub(received)));
+        let mut chunk = stream.take(readable);
+        if max_length.is_some_and(|max_length| received + len > max_length) {
+            data.clear();
+            io::copy(&mut chunk, &mut io::sink())?;
+        } else {
+            chunk.read_to_end(&mut data)?;
+        }
+        if readable < len {
+            return Err(io::ErrorKind::ConnectionReset.into());
+        }
+        received += len;
+    }
+}
@@ -0,0 +1,536 @@
+use std::{
+    io::{self, Read, Seek},
+    sync::Arc,
+};
+
+use super::{
+    archive::{self, ArchiveLimits},
+    hooks::Hooks,
+    progress::ProgressCallback,
+    report::ReportCallback,
+    ClamdError, ScanProgress, ScanReport, DEFAULT_CHUNK_SIZE, DEFAULT_WRITE_BUFFER_SIZE,
+    MAX_CHUNK_SIZE,
+};
+
+/// How the buffer for the response of ClamAV is allocated
+///
+/// The response is read in steps of at most `read_size` bytes directly into
+/// the response buffer, which reserves `initial_capacity` bytes up front and
+/// grows only by the bytes that are read. Functions that read into a buffer
+/// of the caller, like [`scan_buffer_into`](crate::scan_buffer_into), keep its
+/// capacity, so that reusing the buffer does not reallocate it.
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::ScanOptions::new().response_buffer(
+///     clamav_client::ResponseBuffer {
+///         initial_capacity: 128,
+///         read_size: 1024,
+///     },
+/// );
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseBuffer {
+    /// The number of bytes to reserve before reading the response
+    pub initial_capacity: usize,
+    /// The largest number of bytes to read at once
+    pub read_size: usize,
+}
+
+impl ResponseBuffer {
+    /// Returns the default buffer, reserving `expected_response_length` bytes
+    /// instead of the default initial capacity if it is known
+    pub(crate) fn expecting(expected_response_length: Option<usize>) -> Self {
+        let buffer = ResponseBuffer::default();
+        ResponseBuffer {
+            initial_capacity: expected_response_length.unwrap_or(buffer.initial_capacity),
+            ..buffer
+        }
+    }
+}
+
+impl Default for ResponseBuffer {
+    fn default() -> Self {
+        ResponseBuffer {
+            initial_capacity: 64,
+            read_size: 256,
+        }
+    }
+}
+
+/// Heuristic for choosing the chunk size based on the size of the input
+///
+/// The chunk size is a sixteenth of the input size, rounded up to the next
+/// power of two and limited to the range `min..=max`. If the input size is
+/// unknown, e.g. when scanning a stream, `min` is used.
+///
+/// # Example
+///
+/// ```
+/// let heuristic = clamav_client::AdaptiveChunkSize::default();
+/// assert_eq!(heuristic.chunk_size(Some(100)), 4096);
+/// assert_eq!(heuristic.chunk_size(Some(1_000_000)), 65536);
+/// assert_eq!(heuristic.chunk_size(Some(500_000_000)), 262144);
+/// assert_eq!(heuristic.chunk_size(None), 4096);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveChunkSize {
+    /// The smallest chunk size in bytes
+    pub min: usize,
+    /// The largest chunk size in bytes
+    pub max: usize,
+}
+
+impl AdaptiveChunkSize {
+    /// Returns the chunk size for an input of the given size in bytes
+    pub fn chunk_size(&self, input_size: Option<u64>) -> usize {
+        let chunk_size = match input_size {
+            Some(size) => usize::try_from(size / 16)
+                .ok()
+                .and_then(usize::checked_next_power_of_two)
+                .unwrap_or(usize::MAX),
+            None => self.min,
+        };
+        chunk_size.min(self.max).max(self.min)
+    }
+}
+
+impl Default for AdaptiveChunkSize {
+    fn default() -> Self {
+        AdaptiveChunkSize {
+            min: DEFAULT_CHUNK_SIZE,
+            max: 256 * 1024,
+        }
+    }
+}
+
+/// Options that control how data is streamed to ClamAV
+///
+/// The scan functions accept anything that converts into [`ScanOptions`],
+/// including an optional chunk size (`None` or `Some(chunk_size)`) for
+/// backward compatibility.
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::ScanOptions::new()
+///     .chunk_size(8192)
+///     .max_stream_size(1_000_000);
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::scan_buffer(b"clean data", clamd_tcp, options).unwrap();
+/// # assert_eq!(clamav_client::clean(&response), Ok(true));
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub(crate) chunk_size: Option<usize>,
+    pub(crate) adaptive_chunk_size: AdaptiveChunkSize,
+    pub(crate) max_stream_size: Option<u64>,
+    pub(crate) split_size: Option<u64>,
+    pub(crate) path_fallback: bool,
+    pub(crate) pipeline_depth: Option<usize>,
+    pub(crate) write_buffer_size: Option<usize>,
+    pub(crate) response_buffer: ResponseBuffer,
+    pub(crate) deduplicate: bool,
+    pub(crate) reject_empty: bool,
+    pub(crate) archive_limits: Option<ArchiveLimits>,
+    #[cfg(feature = "mmap")]
+    pub(crate) memory_map: bool,
+    pub(crate) progress: Option<ProgressCallback>,
+    pub(crate) report: Option<ReportCallback>,
+    pub(crate) max_bytes_per_second: Option<u64>,
+    pub(crate) correlation_id: Option<Arc<str>>,
+    #[cfg(feature = "sha256")]
+    pub(crate) sha256: bool,
+    #[cfg(feature = "md5")]
+    pub(crate) md5: bool,
+    pub(crate) hooks: Hooks,
+}
+
+impl ScanOptions {
+    /// Creates the default scan options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a fixed chunk size in bytes for reading data during scanning
+    ///
+    /// If no fixed chunk size is set, the chunk size is chosen based on the
+    /// size of the input (see [`AdaptiveChunkSize`]).
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Sets the heuristic for choosing the chunk size if no fixed chunk size
+    /// is set
+    pub fn adaptive_chunk_size(mut self, adaptive_chunk_size: AdaptiveChunkSize) -> Self {
+        self.adaptive_chunk_size = adaptive_chunk_size;
+        self
+    }
+
+    /// Sets the maximum number of bytes to stream to ClamAV
+    ///
+    /// Scans of larger inputs fail locally with
+    /// [`ClamdError::SizeLimitExceeded`] instead of sending the data to the
+    /// server. Set this to the server's `StreamMaxLength` to fail fast.
+    pub fn max_stream_size(mut self, max_stream_size: u64) -> Self {
+        self.max_stream_size = Some(max_stream_size);
+        self
+    }
+
+    /// Splits files and buffers larger than `split_size` bytes into parts that
+    /// are scanned one after another, each with its own INSTREAM command
+    ///
+    /// Set this to the server's `StreamMaxLength` to scan larger inputs
+    /// instead of getting a size limit error. The outcome is the one of the
+    /// first part that is not clean, so the input is reported as infected if
+    /// any part is. Signatures that span two parts, e.g. in archives, are not
+    /// detected, so this is meant for large flat files.
+    ///
+    /// The option applies to the `scan_file` and `scan_buffer` functions of
+    /// all modules and to the clients. The [maximum stream
+    /// size](Self::max_stream_size) still limits the size of the whole input,
+    /// and progress and reports are passed for each part separately.
+    ///
+    /// Scans fail with [`ClamdError::InvalidOptions`] if `split_size` is zero
+    /// (see [`validate`](Self::validate)).
+    pub fn split_size(mut self, split_size: u64) -> Self {
+        self.split_size = Some(split_size);
+        self
+    }
+
+    /// Scans files that exceed the [maximum stream
+    /// size](Self::max_stream_size) by their path instead of failing, if
+    /// ClamAV runs on the same host
+    ///
+    /// ClamAV is assumed to run on the same host if it is connected through a
+    /// Unix socket (see
+    /// [`TransportProtocol::is_local`](crate::TransportProtocol::is_local)).
+    /// The file is then scanned with the SCAN command and its canonical path
+    /// (see [`scan_path`](crate::scan_path)), so ClamAV needs permission to
+    /// read it, and the response contains the path instead of `stream`. Over
+    /// TCP, oversized files still fail with
+    /// [`ClamdError::SizeLimitExceeded`].
+    ///
+    /// The option applies to the `scan_file` functions of all modules and to
+    /// the clients.
+    pub fn path_fallback(mut self, path_fallback: bool) -> Self {
+        self.path_fallback = path_fallback;
+        self
+    }
+
+    /// Sets how many buffers of a batch are streamed to ClamAV before the
+    /// response to the first of them is read
+    ///
+    /// By default, the `scan_buffers` functions of all modules wait for the
+    /// response to each buffer before streaming the next one, so every buffer
+    /// costs a round trip to ClamAV. With a depth greater than one, the next
+    /// buffers are streamed while responses are outstanding (pipelining), and
+    /// the responses are matched to the buffers by their request ID. The
+    /// depth should stay below the `MaxQueue` setting of ClamAV.
+    ///
+    /// Scans fail with [`ClamdError::InvalidOptions`] if `pipeline_depth` is
+    /// zero (see [`validate`](Self::validate)).
+    pub fn pipeline_depth(mut self, pipeline_depth: usize) -> Self {
+        self.pipeline_depth = Some(pipeline_depth);
+        self
+    }
+
+    /// Sets the size in bytes of the buffer that collects chunks before they
+    /// are written to the connection
+    ///
+    /// Chunks smaller than the buffer are combined into one write, which saves
+    /// system calls when many small chunks are streamed, e.g. with the default
+    /// chunk size of 4 KiB for inputs of unknown size. Larger chunks are
+    /// written directly. The default is 64 KiB, and `0` writes every chunk
+    /// directly.
+    ///
+    /// Progress callbacks are called when a chunk is added to the buffer, so
+    /// they can report up to one buffer of data that has not been sent yet.
+    /// Throttled scans (see [`max_bytes_per_second`](Self::max_bytes_per_second))
+    /// send the buffered data before they wait.
+    ///
+    /// The write buffer is not used if the feature "zeroize" is enabled,
+    /// because its memory cannot be wiped after the scan.
+    pub fn write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = Some(write_buffer_size);
+        self
+    }
+
+    /// Sets how the buffer for the response of a scan is allocated
+    ///
+    /// The default reserves 64 bytes, enough for most responses, and reads at
+    /// most 256 bytes at once.
+    pub fn response_buffer(mut self, response_buffer: ResponseBuffer) -> Self {
+        self.response_buffer = response_buffer;
+        self
+    }
+
+    /// Scans identical inputs of a batch only once and passes the outcome to
+    /// all of them
+    ///
+    /// This applies to the `scan_buffers` functions of all modules, which
+    /// compare the buffers themselves, and with the feature flag "sha256" to
+    /// the blocking [`scan_directory`](crate::scan_directory), which compares
+    /// the SHA-256 digests of the files and so reads each file twice. Progress
+    /// and reports are only passed for the inputs that are scanned.
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// Rejects empty inputs with [`ClamdError::EmptyInput`] instead of
+    /// scanning them
+    ///
+    /// By default, an empty input is streamed to ClamAV like any other and
+    /// reported as clean. If an empty input rather indicates a bug, e.g. an
+    /// upload whose body was lost, the scan fails instead. Empty files and
+    /// buffers are rejected without connecting to ClamAV, streams when they
+    /// end without any data.
+    pub fn reject_empty(mut self, reject_empty: bool) -> Self {
+        self.reject_empty = reject_empty;
+        self
+    }
+
+    /// Rejects ZIP archives and gzip files that exceed the given limits before
+    /// they are sent to ClamAV
+    ///
+    /// Decompression bombs, i.e. small archives that expand to huge amounts
+    /// of data or to huge numbers of files, tie up the workers of ClamAV. With
+    /// limits, such archives fail locally with
+    /// [`ClamdError::ArchiveLimitExceeded`] without connecting to the server.
+    /// Only the headers and the central directory of the archive are read,
+    /// nothing is decompressed. By default, archives are not checked.
+    ///
+    /// The option applies to the `scan_file`, `scan_buffer` and
+    /// `scan_buffers` functions of all modules and to the clients.
+    pub fn archive_limits(mut self, archive_limits: ArchiveLimits) -> Self {
+        self.archive_limits = Some(archive_limits);
+        self
+    }
+
+    /// Memory-maps files instead of reading them into an intermediate buffer
+    ///
+    /// Only applies to the blocking [`scan_file`](crate::scan_file). The file
+    /// must not be truncated while it is being scanned, otherwise the process
+    /// may be terminated with `SIGBUS`.
+    ///
+    /// Use the feature flag "mmap" to enable this option.
+    #[cfg(feature = "mmap")]
+    pub fn memory_map(mut self, memory_map: bool) -> Self {
+        self.memory_map = memory_map;
+        self
+    }
+
+    /// Calls `on_progress` with the [`ScanProgress`] after each chunk sent to
+    /// ClamAV
+    ///
+    /// The callback is called from the task or thread that performs the scan,
+    /// so it should return quickly.
+    pub fn on_progress<F>(mut self, on_progress: F) -> Self
+    where
+        F: Fn(&ScanProgress) + Send + Sync + 'static,
+    {
+        self.progress = Some(ProgressCallback(Arc::new(on_progress)));
+        self
+    }
+
+    /// Calls `on_report` with the [`ScanReport`] after each successful scan
+    ///
+    /// The callback is called from the task or thread that performs the scan,
+    /// so it should return quickly.
+    pub fn on_report<F>(mut self, on_report: F) -> Self
+    where
+        F: Fn(&ScanReport) + Send + Sync + 'static,
+    {
+        self.report = Some(ReportCallback(Arc::new(on_report)));
+        self
+    }
+
+    /// Computes the SHA-256 digest of the data while it is streamed to ClamAV
+    ///
+    /// The digest is part of the [`ScanReport`] passed to
+    /// [`on_report`](Self::on_report). It is not available for
+    /// `scan_file_zero_copy`, which never reads the data itself.
+    ///
+    /// Use the feature flag "sha256" or "hash" to enable this option.
+    #[cfg(feature = "sha256")]
+    pub fn sha256(mut self, sha256: bool) -> Self {
+        self.sha256 = sha256;
+        self
+    }
+
+    /// Computes the MD5 digest of the data while it is streamed to ClamAV
+    ///
+    /// The digest is part of the [`ScanReport`] passed to
+    /// [`on_report`](Self::on_report). It is not available for
+    /// `scan_file_zero_copy`, which never reads the data itself.
+    ///
+    /// Use the feature flag "md5" or "hash" to enable this option.
+    #[cfg(feature = "md5")]
+    pub fn md5(mut self, md5: bool) -> Self {
+        self.md5 = md5;
+        self
+    }
+
+    /// Limits the upload rate of each scan to the given number of bytes per
+    /// second
+    ///
+    /// The limit is enforced per scan (and connection) using a token bucket
+    /// that allows bursts of up to one second worth of data. The asynchronous
+    /// scan functions wait using the runtime's timer, so Tokio scans require a
+    /// runtime with the time driver enabled.
+    ///
+    /// Scans fail with [`ClamdError::InvalidOptions`] if
+    /// `max_bytes_per_second` is zero (see [`validate`](Self::validate)).
+    pub fn max_bytes_per_second(mut self, max_bytes_per_second: u64) -> Self {
+        self.max_bytes_per_second = Some(max_bytes_per_second);
+        self
+    }
+
+    /// Attaches a caller-supplied ID, e.g. of the request that uploaded the
+    /// scanned data, to each scan
+    ///
+    /// The ID is included in the [`ScanReport`], in the detections passed to
+    /// the detection sinks and in the audit log of a client, in the
+    /// `clamav_client::scan` span with the feature "tracing" and in the
+    /// messages logged with the feature "log".
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let options = clamav_client::ScanOptions::new()
+    ///     .correlation_id("upload-42")
+    ///     .on_report(|report| {
+    ///         println!("{:?}: {} bytes", report.correlation_id, report.bytes_sent);
+    ///     });
+    ///
+    /// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+    /// let response = clamav_client::scan_buffer(b"clean data", clamd_tcp, options).unwrap();
+    /// # assert_eq!(clamav_client::clean(&response), Ok(true));
+    /// ```
+    ///
+    pub fn correlation_id<I: Into<String>>(mut self, correlation_id: I) -> Self {
+        self.correlation_id = Some(Arc::from(correlation_id.into()));
+        self
+    }
+
+    /// Checks that the options can be used for a scan
+    ///
+    /// The scan functions check the options before streaming data and fail
+    /// with [`ClamdError::InvalidOptions`] if they are invalid, e.g. if the
+    /// chunk size is zero, which would otherwise terminate the stream before
+    /// any data is sent, or if the split size, the pipeline depth or the upload
+    /// rate is zero. Call this to reject a configuration early, e.g. when
+    /// an application starts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clamav_client::{ClamdError, ScanOptions};
+    ///
+    /// assert!(ScanOptions::new().chunk_size(8192).validate().is_ok());
+    /// assert!(matches!(
+    ///     ScanOptions::from(Some(0)).validate(),
+    ///     Err(ClamdError::InvalidOptions { .. })
+    /// ));
+    /// ```
+    ///
+    pub fn validate(&self) -> Result<(), ClamdError> {
+        let reason = if self.chunk_size == Some(0) {
+            "the chunk size must not be zero"
+        } else if self.adaptive_chunk_size.min == 0 {
+            "the minimum adaptive chunk size must not be zero"
+        } else if self.adaptive_chunk_size.min > self.adaptive_chunk_size.max {
+            "the minimum adaptive chunk size must not exceed the maximum"
+        } else if self.response_buffer.read_size == 0 {
+            "the response read size must not be zero"
+        } else if self.split_size == Some(0) {
+            "the split size must not be zero"
+        } else if self.pipeline_depth == Some(0) {
+            "the pipeline depth must not be zero"
+        } else if self.max_bytes_per_second == Some(0) {
+            "the upload rate must not be zero"
+        } else {
+            return Ok(());
+        };
+        Err(ClamdError::InvalidOptions { reason })
+    }
+
+    /// Returns the chunk size for an input of the given size, limited to the
+    /// largest chunk that can be framed, or an error if the options are
+    /// invalid
+    ///
+    /// The chunk size is also limited to the size of the input if it is known,
+    /// so that a huge configured chunk size does not allocate a huge buffer.
+    pub(crate) fn chunk_size_for(&self, input_size: Option<u64>) -> io::Result<usize> {
+        self.validate()?;
+        let chunk_size = self
+            .chunk_size
+            .unwrap_or_else(|| self.adaptive_chunk_size.chunk_size(input_size))
+            .min(MAX_CHUNK_SIZE);
+        Ok(
+            match input_size.and_then(|size| usize::try_from(size).ok()) {
+                Some(size) => chunk_size.min(size.max(1)),
+                None => chunk_size,
+            },
+        )
+    }
+
+    /// Returns the size of the write buffer of a scan
+    pub(crate) fn write_buffer_size_or_default(&self) -> usize {
+        if cfg!(feature = "zeroize") {
+            return 0;
+        }
+        self.write_buffer_size.unwrap_or(DEFAULT_WRITE_BUFFER_SIZE)
+    }
+
+    /// Checks the size of an input before connecting to ClamAV, failing if it
+    /// is empty and empty inputs are rejected or if it exceeds the maximum
+    /// stream size
+    pub(crate) fn check_input_size(&self, input_size: u64) -> io::Result<()> {
+        if input_size == 0 && self.reject_empty {
+            return Err(ClamdError::EmptyInput.into());
+        }
+        self.check_stream_size(input_size)
+    }
+
+    pub(crate) fn check_stream_size(&self, stream_size: u64) -> io::Result<()> {
+        match self.max_stream_size {
+            Some(limit) if stream_size > limit => {
+                Err(ClamdError::SizeLimitExceeded { limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks an input of `input_size` bytes against the archive limits and
+    /// rewinds it
+    pub(crate) fn check_archive<R: Read + Seek>(
+        &self,
+        input: R,
+        input_size: u64,
+    ) -> io::Result<()> {
+        archive::check(self.archive_limits, input, input_size)
+    }
+}
+
+impl From<Option<usize>> for ScanOptions {
+    fn from(chunk_size: Option<usize>) -> Self {
+        ScanOptions {
+            chunk_size,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&ScanOptions> for ScanOptions {
+    fn from(options: &ScanOptions) -> Self {
+        options.clone()
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fmt, str::FromStr};
+
+use super::{pua::PUA_PREFIX, ClamdError, SignatureName};
+
+/// The typed result of a scan
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::ScanOutcome;
+///
+/// let outcome = ScanOutcome::from_response(b"stream: Eicar-Signature FOUND\0").unwrap();
+/// assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+///
+/// let outcome = ScanOutcome::from_response(b"stream: OK\0").unwrap();
+/// assert!(outcome.is_clean());
+/// ```
+///
+/// Outcomes are displayed as `Clean`, `Infected: <signature>`,
+/// `Warning: <signature>` or `Error: <message>`, and can be parsed from this
+/// text as well as from responses of ClamAV:
+///
+/// ```
+/// use clamav_client::ScanOutcome;
+///
+/// let outcome = ScanOutcome::try_from(&b"stream: Eicar-Signature FOUND\0"[..]).unwrap();
+/// assert_eq!(outcome.to_string(), "Infected: Eicar-Signature");
+/// assert_eq!("Infected: Eicar-Signature".parse::<ScanOutcome>().unwrap(), outcome);
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ScanOutcome {
+    /// No virus was found
+    Clean,
+    /// A virus was found
+    Infected {
+        /// The name of the signature that matched
+        signature: String,
+    },
+    /// A signature matched that is tolerated, e.g. by a
+    /// [`SignatureAllowlist`](crate::SignatureAllowlist), so the data is
+    /// accepted with a warning
+    Warning {
+        /// The name of the signature that matched
+        signature: String,
+    },
+    /// ClamAV could not scan the data, e.g. because the size limit was exceeded
+    Error {
+        /// The error message reported by ClamAV
+        message: String,
+    },
+}
+
+impl ScanOutcome {
+    /// Parses a scan response from ClamAV
+    ///
+    /// Trailing NUL and newline terminators are ignored.
+    pub fn from_response(response: &[u8]) -> Result<Self, ClamdError> {
+        let response = String::from_utf8_lossy(response);
+        let response = response.trim_end_matches(['\0', '\n']);
+
+        if let Some(rest) = response.strip_suffix(" FOUND") {
+            let signature = rest
+                .rsplit_once(": ")
+                .map_or(rest, |(_, signature)| signature);
+            return Ok(ScanOutcome::Infected {
+                signature: signature.to_owned(),
+            });
+        }
+        if let Some(message) = response.strip_suffix(" ERROR") {
+            return Ok(ScanOutcome::Error {
+                message: message.to_owned(),
+            });
+        }
+        if response == "OK" || response.ends_with(": OK") {
+            return Ok(ScanOutcome::Clean);
+        }
+        Err(ClamdError::InvalidResponse {
+            response: response.to_owned(),
+        })
+    }
+
+    /// Returns `true` if no virus was found
+    pub fn is_clean(&self) -> bool {
+        matches!(self, ScanOutcome::Clean)
+    }
+
+    /// Returns `true` if a virus was found
+    pub fn is_infected(&self) -> bool {
+        matches!(self, ScanOutcome::Infected { .. })
+    }
+
+    /// Returns `true` if a tolerated signature matched
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ScanOutcome::Warning { .. })
+    }
+
+    /// Returns `true` if the matched signature is that of a potentially
+    /// unwanted application, i.e. starts with `PUA.` (see
+    /// [`PuaPolicy`](crate::PuaPolicy))
+    pub fn is_pua(&self) -> bool {
+        matches!(self.signature(), Some(signature) if signature.starts_with(PUA_PREFIX))
+    }
+
+    /// Returns the name of the matched signature if a virus was found or a
+    /// tolerated signature matched
+    pub fn signature(&self) -> Option<&str> {
+        match self {
+            ScanOutcome::Infected { signature } | ScanOutcome::Warning { signature } => {
+                Some(signature)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the components of the name of the matched signature, e.g. its
+    /// platform and family, see [`SignatureName`]
+    pub fn signature_name(&self) -> Option<SignatureName<'_>> {
+        self.signature().map(SignatureName::parse)
+    }
+}
+
+impl fmt::Display for ScanOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanOutcome::Clean => f.write_str("Clean"),
+            ScanOutcome::Infected { signature } => write!(f, "Infected: {}", signature),
+            ScanOutcome::Warning { signature } => write!(f, "Warning: {}", signature),
+            ScanOutcome::Error { message } => write!(f, "Error: {}", message),
+        }
+    }
+}
+
+impl FromStr for ScanOutcome {
+    type Err = ClamdError;
+
+    /// Parses the displayed text of an outcome or a scan response from ClamAV
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "Clean" {
+            return Ok(ScanOutcome::Clean);
+        }
+        if let Some(signature) = s.strip_prefix("Infected: ") {
+            return Ok(ScanOutcome::Infected {
+                signature: signature.to_owned(),
+            });
+        }
+        if let Some(signature) = s.strip_prefix("Warning: ") {
+            return Ok(ScanOutcome::Warning {
+                signature: signature.to_owned(),
+            });
+        }
+        if let Some(message) = s.strip_prefix("Error: ") {
+            return Ok(ScanOutcome::Error {
+                message: message.to_owned(),
+            });
+        }
+        ScanOutcome::from_response(s.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for ScanOutcome {
+    type Error = ClamdError;
+
+    /// Parses a scan response from ClamAV (see [`ScanOutcome::from_response`])
+    fn try_from(response: &[u8]) -> Result<Self, ClamdError> {
+        ScanOutcome::from_response(response)
+    }
+}
+
+/// The [`ScanOutcome`] of a scan together with the raw response of ClamAV, e.g.
+/// to log or store the exact response for forensics
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{ScanOutcome, ScanResponse};
+///
+/// let response = ScanResponse::from_response(b"stream: Eicar-Signature FOUND\0".to_vec()).unwrap();
+/// assert_eq!(response.outcome.signature(), Some("Eicar-Signature"));
+/// assert_eq!(response.raw, b"stream: Eicar-Signature FOUND\0");
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ScanResponse {
+    /// The outcome of the scan
+    pub outcome: ScanOutcome,
+    /// The response of ClamAV as received, including its terminator
+    pub raw: Vec<u8>,
+}
+
+impl ScanResponse {
+    /// Parses a scan response from ClamAV (see [`ScanOutcome::from_response`])
+    /// and keeps it along with the outcome
+    pub fn from_response(response: Vec<u8>) -> Result<Self, ClamdError> {
+        Ok(ScanResponse {
+            outcome: ScanOutcome::from_response(&response)?,
+            raw: response,
+        })
+    }
+}
+
+/// Strips the request ID that prefixes responses within an IDSESSION
+pub(crate) fn strip_request_id(response: &[u8]) -> &[u8] {
+    split_request_id(response).map_or(response, |(_, response)| response)
+}
+
+/// Splits a response within an IDSESSION, e.g. `1: stream: OK`, into the
+/// request ID and the response to the request
+pub(crate) fn split_request_id(response: &[u8]) -> Option<(u64, &[u8])> {
+    let digits = response.iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits > 0 && response[digits..].starts_with(b": ") {
+        let id = std::str::from_utf8(&response[..digits])
+            .ok()?
+            .parse()
+            .ok()?;
+        Some((id, &response[digits + 2..]))
+    } else {
+        None
+    }
+}
+
+/// Returns `true` if `response` reports that the scanned data is clean
+pub(crate) fn is_clean_response(response: &[u8]) -> bool {
+    matches!(ScanOutcome::from_response(response), Ok(ScanOutcome::Clean))
+}
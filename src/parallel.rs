@@ -0,0 +1,81 @@
+use std::{path::Path, time::Instant};
+
+use rayon::prelude::*;
+
+use super::{
+    directory::Walk, journal::Journal, state::StateStore, ClamdError, DirectoryScanOptions,
+    ScanOptions, ScanOutcome, ScanResults, TransportProtocol,
+};
+
+/// Scans all files in a directory tree for viruses using multiple threads
+///
+/// This function walks the directory tree at `dir_path` on the client side and
+/// then scans the files in parallel on the Rayon thread pool, using a new
+/// connection per file. The files and results are the same as for
+/// [`scan_directory`](crate::scan_directory).
+///
+/// Use the feature flag "rayon" to enable this function.
+///
+/// # Arguments
+///
+/// * `dir_path`: The path to the directory to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `directory_options`: The [`DirectoryScanOptions`] that control which files are scanned
+///
+/// # Returns
+///
+/// The [`ScanResults`] with the path of each scanned file together with its
+/// [`ScanOutcome`], or the error that occurred while reading or scanning it,
+/// in the order of the directory walk
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+/// let results =
+///     clamav_client::scan_directory_parallel("tests/data", clamd_tcp, None, directory_options);
+/// let infected = results
+///     .into_iter()
+///     .filter(|(_, result)| matches!(result, Ok(outcome) if outcome.is_infected()))
+///     .count();
+/// println!("{} infected files found", infected);
+/// # assert_eq!(infected, 1);
+/// ```
+///
+pub fn scan_directory_parallel<P, T, O>(
+    dir_path: P,
+    connection: T,
+    options: O,
+    directory_options: DirectoryScanOptions,
+) -> ScanResults
+where
+    P: AsRef<Path>,
+    T: TransportProtocol + Sync,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let start = Instant::now();
+    let walk = Walk::new(dir_path.as_ref(), directory_options);
+    let journal = walk.journal();
+    let state = walk.state();
+    let entries: Vec<_> = walk.collect();
+    let results = entries
+        .into_par_iter()
+        .map(|entry| match entry {
+            Ok(path) => {
+                let result = Journal::scan(journal.as_deref(), &path, || {
+                    StateStore::scan(state.as_deref(), &path, || {
+                        super::_scan_file(&path, &connection, &options)
+                            .map_err(ClamdError::from)
+                            .and_then(|response| ScanOutcome::from_response(&response))
+                    })
+                });
+                (path, result)
+            }
+            Err((path, err)) => (path, Err(err.into())),
+        })
+        .collect();
+    ScanResults::from_file_results(results, start.elapsed())
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use super::{send_command, ClamdError, IoResult, TransportProtocol};
+
+/// A command of ClamAV that scans a file or directory on the host of ClamAV
+/// by its path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathCommand {
+    /// `SCAN`: scans a file or directory recursively and stops at the first
+    /// virus found
+    Scan,
+    /// `CONTSCAN`: scans a file or directory recursively and continues after
+    /// a virus was found
+    ContScan,
+    /// `MULTISCAN`: scans a file or directory recursively using multiple
+    /// threads of ClamAV
+    MultiScan,
+    /// `ALLMATCHSCAN`: scans a file or directory recursively and reports all
+    /// signatures matching a file, not only the first one
+    AllMatchScan,
+}
+
+impl PathCommand {
+    fn name(self) -> &'static [u8] {
+        match self {
+            PathCommand::Scan => b"SCAN",
+            PathCommand::ContScan => b"CONTSCAN",
+            PathCommand::MultiScan => b"MULTISCAN",
+            PathCommand::AllMatchScan => b"ALLMATCHSCAN",
+        }
+    }
+}
+
+/// Scans a file or directory on the host of ClamAV by its path
+///
+/// Unlike [`scan_file`](crate::scan_file), this function does not stream the
+/// data to ClamAV, but sends the path, so ClamAV must run on the same host, or
+/// share the file system, and have permission to read the files.
+///
+/// The command is terminated with a NUL byte, so that the path may contain
+/// spaces and newlines, and ClamAV separates the results of the scanned files
+/// with NUL bytes as well. Non-UTF-8 paths are sent as they are on Unix.
+///
+/// # Arguments
+///
+/// * `path`: The absolute path of the file or directory to scan
+/// * `command`: The [`PathCommand`] to use
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes, with
+/// one NUL-terminated result per file with a virus or error, or a single `OK`
+/// result. If the path is relative, contains a NUL byte or, on platforms other
+/// than Unix, is not valid Unicode, the scan fails with
+/// [`ClamdError::InvalidPath`] without connecting to the server.
+///
+/// # Example
+///
+/// ```no_run
+/// use clamav_client::PathCommand;
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let path = std::env::current_dir().unwrap().join("tests/data/eicar.txt");
+/// let response = clamav_client::scan_path(&path, PathCommand::ContScan, clamd_tcp).unwrap();
+/// let outcome = clamav_client::ScanOutcome::from_response(&response).unwrap();
+/// assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+/// ```
+///
+pub fn scan_path<P: AsRef<Path>, T: TransportProtocol>(
+    path: P,
+    command: PathCommand,
+    connection: T,
+) -> IoResult {
+    let command = path_command(command, path.as_ref())?;
+    let stream = connection.connect()?;
+    send_command(stream, &command, None)
+}
+
+/// Encodes `command` for `path`, e.g. `zSCAN /path\0`
+pub(crate) fn path_command(command: PathCommand, path: &Path) -> Result<Vec<u8>, ClamdError> {
+    let invalid_path = |reason| ClamdError::InvalidPath {
+        path: path.to_path_buf(),
+        reason,
+    };
+    // ClamAV would resolve a relative path against its own working directory
+    if !path.is_absolute() {
+        return Err(invalid_path("the path is not absolute"));
+    }
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes()
+    };
+    #[cfg(not(unix))]
+    let bytes = path
+        .to_str()
+        .ok_or_else(|| invalid_path("the path is not valid Unicode"))?
+        .as_bytes();
+    if bytes.contains(&0) {
+        return Err(invalid_path("the path contains a NUL byte"));
+    }
+
+    let mut encoded = Vec::with_capacity(command.name().len() + bytes.len() + 3);
+    encoded.push(b'z');
+    encoded.extend_from_slice(command.name());
+    encoded.push(b' ');
+    encoded.extend_from_slice(bytes);
+    encoded.push(0);
+    Ok(encoded)
+}
@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+use super::{outcome, report::ReportTimer, ClamdError, ScanOptions, ScanOutcome};
+
+/// The INSTREAM commands of a batch within an IDSESSION whose responses are
+/// still outstanding
+///
+/// Responses are matched to their command by the request ID, so that it does
+/// not matter whether ClamAV completes the commands in order.
+pub(crate) struct Pipeline<'a> {
+    depth: usize,
+    sent: u64,
+    pending: VecDeque<(u64, ReportTimer<'a>)>,
+    outcomes: Vec<Option<ScanOutcome>>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub(crate) fn new(options: &ScanOptions, len: usize) -> Self {
+        let depth = options.pipeline_depth.unwrap_or(1);
+        Pipeline {
+            depth,
+            sent: 0,
+            pending: VecDeque::with_capacity(depth),
+            outcomes: vec![None; len],
+        }
+    }
+
+    /// Returns `true` if a response must be read before the next command is
+    /// sent
+    pub(crate) fn is_full(&self) -> bool {
+        self.pending.len() >= self.depth
+    }
+
+    /// Returns `true` if no response is outstanding
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Records that the next command was sent
+    pub(crate) fn sent(&mut self, report: ReportTimer<'a>) {
+        self.sent += 1;
+        self.pending.push_back((self.sent, report));
+    }
+
+    /// Passes `response` to the command with its request ID, or to the oldest
+    /// outstanding command if the response has no request ID
+    pub(crate) fn receive(&mut self, response: &[u8]) -> Result<(), ClamdError> {
+        let (index, request_response) = match outcome::split_request_id(response) {
+            Some((id, request_response)) => (
+                self.pending.iter().position(|(sent, _)| *sent == id),
+                request_response,
+            ),
+            None => ((!self.pending.is_empty()).then_some(0), response),
+        };
+        let (id, report) = match index.and_then(|index| self.pending.remove(index)) {
+            Some(pending) => pending,
+            None => {
+                let response = String::from_utf8_lossy(response);
+                return Err(ClamdError::InvalidResponse {
+                    response: response.trim_end_matches('\0').to_owned(),
+                });
+            }
+        };
+        report.finish(response);
+        self.outcomes[id as usize - 1] = Some(ScanOutcome::from_response(request_response)?);
+        Ok(())
+    }
+
+    /// Returns the outcome of each command, in the order in which they were
+    /// sent
+    pub(crate) fn into_outcomes(self) -> Vec<ScanOutcome> {
+        self.outcomes.into_iter().flatten().collect()
+    }
+}
@@ -0,0 +1,237 @@
+//! Virus scanning for upload endpoints of [`poem`](https://docs.rs/poem)
+//!
+//! The extractors [`ScannedBytes`] and [`ScannedMultipart`] scan the request
+//! body of a handler while it is received and reject infected uploads with a
+//! [`ScanRejection`]. The [`ScanMiddleware`] scans the request bodies of all
+//! endpoints it wraps before they reach the handlers. The extractors get the
+//! [`Scanner`] from the endpoint data, where it is added using
+//! [`EndpointExt::data`](poem::EndpointExt::data).
+//!
+//! Request bodies are kept in memory, so limit their size, e.g. using
+//! [`SizeLimit`](poem::middleware::SizeLimit) or
+//! [`ScanOptions::max_stream_size`](crate::ScanOptions::max_stream_size).
+//!
+//! Use the feature flag "poem" to enable this module.
+//!
+//! # Example
+//!
+//! ```
+//! use clamav_client::poem::{ScanMiddleware, ScannedBytes, Scanner};
+//! use poem::{handler, post, EndpointExt, Route};
+//!
+//! #[handler]
+//! fn upload(ScannedBytes(data): ScannedBytes) -> String {
+//!     format!("Received {} clean bytes", data.len())
+//! }
+//!
+//! #[handler]
+//! fn echo(body: String) -> String {
+//!     body
+//! }
+//!
+//! let scanner = Scanner::tcp("localhost:3310");
+//! let app = Route::new()
+//!     .at("/upload", post(upload))
+//!     .data(scanner.clone());
+//!
+//! // Or scan the request bodies of all endpoints
+//! let app = Route::new()
+//!     .at("/files", post(echo))
+//!     .with(ScanMiddleware::new(scanner));
+//! ```
+//!
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use poem::{
+    error::ResponseError, http::StatusCode, Endpoint, Error, FromRequest, IntoResponse, Middleware,
+    Request, RequestBody, Response, Result,
+};
+
+use super::tokio::MultipartField;
+
+pub use super::web::{ScanRejection, Scanner};
+
+impl ResponseError for ScanRejection {
+    fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status_code()).expect("the status code is valid")
+    }
+}
+
+/// Returns the scanner added to the endpoint data
+fn endpoint_scanner(request: &Request) -> Result<&Scanner> {
+    request.data::<Scanner>().ok_or_else(|| {
+        Error::from_string(
+            "Scanner is not added to the endpoint data",
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })
+}
+
+/// An extractor that scans the request body and returns it if it is clean
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::poem::{ScannedBytes, Scanner};
+/// use poem::{handler, post, EndpointExt, Route};
+///
+/// #[handler]
+/// fn upload(ScannedBytes(data): ScannedBytes) -> String {
+///     format!("Received {} clean bytes", data.len())
+/// }
+///
+/// let app = Route::new()
+///     .at("/upload", post(upload))
+///     .data(Scanner::tcp("localhost:3310"));
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ScannedBytes(pub Bytes);
+
+impl<'a> FromRequest<'a> for ScannedBytes {
+    async fn from_request(request: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let scanner = endpoint_scanner(request)?;
+        let body = body.take()?;
+        if body.is_empty() {
+            return Ok(ScannedBytes(Bytes::new()));
+        }
+        let data = scanner.scan_body(body.into_bytes_stream()).await?;
+        Ok(ScannedBytes(data))
+    }
+}
+
+/// An extractor that scans the file fields of a `multipart/form-data` request
+/// body and returns all fields if the file fields are clean
+///
+/// See [`scan_multipart`](crate::tokio::scan_multipart) for how the fields
+/// are scanned.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::poem::{ScannedMultipart, Scanner};
+/// use poem::{handler, post, EndpointExt, Route};
+///
+/// #[handler]
+/// fn upload(ScannedMultipart(fields): ScannedMultipart) -> String {
+///     format!("Received {} clean fields", fields.len())
+/// }
+///
+/// let app = Route::new()
+///     .at("/upload", post(upload))
+///     .data(Scanner::tcp("localhost:3310"));
+/// ```
+///
+#[derive(Debug)]
+pub struct ScannedMultipart(pub Vec<MultipartField>);
+
+impl<'a> FromRequest<'a> for ScannedMultipart {
+    async fn from_request(request: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let scanner = endpoint_scanner(request)?;
+        let boundary = request
+            .content_type()
+            .and_then(|content_type| multer::parse_boundary(content_type).ok())
+            .ok_or_else(|| ScanRejection::InvalidBody {
+                message: "invalid multipart/form-data content type".to_owned(),
+            })?;
+        let multipart = multer::Multipart::new(body.take()?.into_bytes_stream(), boundary);
+        let fields = scanner.scan_multer_form(multipart).await?;
+        Ok(ScannedMultipart(fields))
+    }
+}
+
+type RejectionHandler = Arc<dyn Fn(ScanRejection) -> Response + Send + Sync>;
+
+/// A [`Middleware`] that scans request bodies before they reach the wrapped
+/// endpoint and rejects infected requests
+///
+/// Requests with an empty body are passed through without scanning.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::poem::{ScanMiddleware, ScanRejection, Scanner};
+/// use poem::{
+///     error::ResponseError, handler, http::StatusCode, post, EndpointExt, IntoResponse, Route,
+/// };
+///
+/// #[handler]
+/// fn echo(body: String) -> String {
+///     body
+/// }
+///
+/// let middleware = ScanMiddleware::new(Scanner::tcp("localhost:3310")).on_rejection(|rejection| {
+///     match rejection {
+///         ScanRejection::Infected { .. } => StatusCode::FORBIDDEN.into_response(),
+///         rejection => rejection.as_response(),
+///     }
+/// });
+/// let app = Route::new().at("/upload", post(echo)).with(middleware);
+/// ```
+///
+#[derive(Clone)]
+pub struct ScanMiddleware {
+    scanner: Scanner,
+    on_rejection: RejectionHandler,
+}
+
+impl ScanMiddleware {
+    /// Creates a middleware that scans request bodies using `scanner`
+    pub fn new(scanner: Scanner) -> Self {
+        ScanMiddleware {
+            scanner,
+            on_rejection: Arc::new(|rejection: ScanRejection| rejection.as_response()),
+        }
+    }
+
+    /// Sets the function that creates the response for a rejected request,
+    /// instead of the response of the [`ScanRejection`]
+    pub fn on_rejection<F>(mut self, on_rejection: F) -> Self
+    where
+        F: Fn(ScanRejection) -> Response + Send + Sync + 'static,
+    {
+        self.on_rejection = Arc::new(on_rejection);
+        self
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ScanMiddleware {
+    type Output = ScanEndpoint<E>;
+
+    fn transform(&self, inner: E) -> Self::Output {
+        ScanEndpoint {
+            inner,
+            scanner: self.scanner.clone(),
+            on_rejection: Arc::clone(&self.on_rejection),
+        }
+    }
+}
+
+/// The [`Endpoint`] created by [`ScanMiddleware`]
+pub struct ScanEndpoint<E> {
+    inner: E,
+    scanner: Scanner,
+    on_rejection: RejectionHandler,
+}
+
+impl<E: Endpoint> Endpoint for ScanEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut request: Request) -> Result<Self::Output> {
+        let body = request.take_body();
+        if body.is_empty() {
+            request.set_body(body);
+        } else {
+            match self.scanner.scan_body(body.into_bytes_stream()).await {
+                Ok(data) => request.set_body(data),
+                Err(rejection) => return Ok((self.on_rejection)(rejection)),
+            }
+        }
+        self.inner
+            .call(request)
+            .await
+            .map(IntoResponse::into_response)
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use super::{ClamdError, ScanOutcome};
+
+/// What to do with data that could not be scanned, because ClamAV is
+/// unreachable, times out or reports an error
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{ClamdError, FailurePolicy, ScanDecision};
+///
+/// let unreachable = || Err(ClamdError::Io(std::io::ErrorKind::ConnectionRefused.into()));
+///
+/// let decision = FailurePolicy::FailOpen.decide(unreachable()).unwrap();
+/// assert!(decision.is_allowed());
+/// assert!(!decision.is_scanned());
+///
+/// let decision = FailurePolicy::FailClosed.decide(unreachable()).unwrap();
+/// assert!(!decision.is_allowed());
+///
+/// assert!(FailurePolicy::Propagate.decide(unreachable()).is_err());
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FailurePolicy {
+    /// Allows the data, e.g. for services where availability matters more than
+    /// the scan
+    FailOpen,
+    /// Rejects the data, as if a virus had been found
+    FailClosed,
+    /// Returns the error to the caller
+    #[default]
+    Propagate,
+}
+
+impl FailurePolicy {
+    /// Turns the result of a scan into a [`ScanDecision`]
+    ///
+    /// Clean data is allowed, data with a tolerated signature is allowed with
+    /// a warning and infected data is rejected regardless of the policy.
+    /// Errors, including [`ScanOutcome::Error`], are allowed by
+    /// [`FailOpen`](Self::FailOpen), rejected by
+    /// [`FailClosed`](Self::FailClosed) and returned by
+    /// [`Propagate`](Self::Propagate), with [`ScanOutcome::Error`] as
+    /// [`ClamdError::ScanFailed`].
+    pub fn decide(
+        self,
+        result: Result<ScanOutcome, ClamdError>,
+    ) -> Result<ScanDecision, ClamdError> {
+        let err = match result {
+            Ok(ScanOutcome::Clean) => return Ok(ScanDecision::Allow),
+            Ok(ScanOutcome::Infected { signature }) => {
+                return Ok(ScanDecision::Reject { signature })
+            }
+            Ok(ScanOutcome::Warning { signature }) => {
+                return Ok(ScanDecision::AllowWithWarning { signature })
+            }
+            Ok(ScanOutcome::Error { message }) => ClamdError::ScanFailed { message },
+            Err(err) => err,
+        };
+        match self {
+            FailurePolicy::FailOpen => Ok(ScanDecision::AllowUnscanned {
+                error: err.to_string(),
+            }),
+            FailurePolicy::FailClosed => Ok(ScanDecision::RejectUnscanned {
+                error: err.to_string(),
+            }),
+            FailurePolicy::Propagate => Err(err),
+        }
+    }
+}
+
+/// Whether to accept scanned data, as decided by a [`FailurePolicy`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ScanDecision {
+    /// No virus was found
+    Allow,
+    /// A tolerated signature matched (see [`ScanOutcome::Warning`])
+    AllowWithWarning {
+        /// The name of the signature that matched
+        signature: String,
+    },
+    /// The data could not be scanned and is allowed by
+    /// [`FailurePolicy::FailOpen`]
+    AllowUnscanned {
+        /// The reason why the data could not be scanned
+        error: String,
+    },
+    /// A virus was found
+    Reject {
+        /// The name of the signature that matched
+        signature: String,
+    },
+    /// The data could not be scanned and is rejected by
+    /// [`FailurePolicy::FailClosed`]
+    RejectUnscanned {
+        /// The reason why the data could not be scanned
+        error: String,
+    },
+}
+
+impl ScanDecision {
+    /// Returns `true` if the data is allowed
+    pub fn is_allowed(&self) -> bool {
+        matches!(
+            self,
+            ScanDecision::Allow
+                | ScanDecision::AllowWithWarning { .. }
+                | ScanDecision::AllowUnscanned { .. }
+        )
+    }
+
+    /// Returns `true` if ClamAV scanned the data, and `false` if the decision
+    /// was made by the [`FailurePolicy`]
+    pub fn is_scanned(&self) -> bool {
+        matches!(
+            self,
+            ScanDecision::Allow
+                | ScanDecision::AllowWithWarning { .. }
+                | ScanDecision::Reject { .. }
+        )
+    }
+
+    /// Returns the name of the signature that matched, if a virus was found or
+    /// a tolerated signature matched
+    pub fn signature(&self) -> Option<&str> {
+        match self {
+            ScanDecision::Reject { signature } | ScanDecision::AllowWithWarning { signature } => {
+                Some(signature)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the reason why the data could not be scanned, if the decision
+    /// was made by the [`FailurePolicy`]
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            ScanDecision::AllowUnscanned { error } | ScanDecision::RejectUnscanned { error } => {
+                Some(error)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ScanDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanDecision::Allow => f.write_str("Allowed"),
+            ScanDecision::AllowWithWarning { signature } => {
+                write!(f, "Allowed with warning: {}", signature)
+            }
+            ScanDecision::AllowUnscanned { error } => write!(f, "Allowed unscanned: {}", error),
+            ScanDecision::Reject { signature } => write!(f, "Rejected: {}", signature),
+            ScanDecision::RejectUnscanned { error } => write!(f, "Rejected unscanned: {}", error),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::ScanOptions;
+
+/// The progress of a scan, reported after each chunk sent to ClamAV
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::ScanOptions::new().on_progress(|progress| {
+///     if let Some(total_bytes) = progress.total_bytes {
+///         println!("{} of {} bytes sent", progress.bytes_sent, total_bytes);
+///     }
+/// });
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::scan_file("README.md", clamd_tcp, options).unwrap();
+/// # assert_eq!(clamav_client::clean(&response), Ok(true));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScanProgress {
+    /// The number of bytes of the input sent so far
+    pub bytes_sent: u64,
+    /// The size of the input in bytes, if known (e.g. not for streams)
+    pub total_bytes: Option<u64>,
+    /// The number of chunks sent so far
+    pub chunks_sent: u64,
+    /// The time since the scan started sending data
+    pub elapsed: Duration,
+}
+
+/// A shareable progress callback that can be stored in [`ScanOptions`]
+#[derive(Clone)]
+pub(crate) struct ProgressCallback(pub(crate) Arc<dyn Fn(&ScanProgress) + Send + Sync>);
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Keeps track of the progress of a single scan and reports it to the
+/// callback of the scan options, if any
+pub(crate) struct ProgressTracker<'a> {
+    callback: Option<&'a ProgressCallback>,
+    start: Instant,
+    progress: ScanProgress,
+}
+
+impl<'a> ProgressTracker<'a> {
+    pub(crate) fn new(options: &'a ScanOptions, total_bytes: Option<u64>) -> Self {
+        ProgressTracker {
+            callback: options.progress.as_ref(),
+            start: Instant::now(),
+            progress: ScanProgress {
+                bytes_sent: 0,
+                total_bytes,
+                chunks_sent: 0,
+                elapsed: Duration::ZERO,
+            },
+        }
+    }
+
+    pub(crate) fn chunk_sent(&mut self, len: usize) {
+        if let Some(callback) = self.callback {
+            self.progress.bytes_sent += len as u64;
+            self.progress.chunks_sent += 1;
+            self.progress.elapsed = self.start.elapsed();
+            (callback.0)(&self.progress);
+        }
+    }
+}
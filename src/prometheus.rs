@@ -0,0 +1,139 @@
+//! Scan statistics for [`prometheus`](https://docs.rs/prometheus) registries
+//!
+//! For services that do not use the `metrics` facade, this module records
+//! the statistics of all scans in `prometheus` metrics: the counter
+//! `clamav_client_scans_total` and the histogram
+//! `clamav_client_scan_duration_seconds`, both labeled with the `outcome`
+//! (`clean`, `infected`, `error` or `invalid`), and the counters
+//! `clamav_client_scanned_bytes_total` and
+//! `clamav_client_connect_failures_total`.
+//!
+//! Add them to a [`Registry`] using [`register`], or render them in the text
+//! format of Prometheus using [`text_snapshot`], e.g. for a `/metrics`
+//! endpoint.
+//!
+//! Use the feature flag "prometheus" to enable this module.
+//!
+//! # Example
+//!
+//! ```
+//! let registry = prometheus::Registry::new();
+//! clamav_client::prometheus::register(&registry).unwrap();
+//!
+//! let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+//! clamav_client::scan_buffer(b"clean data", clamd_tcp, None).unwrap();
+//!
+//! let snapshot = clamav_client::prometheus::text_snapshot();
+//! assert!(snapshot.contains("clamav_client_scans_total{outcome=\"clean\"}"));
+//! ```
+//!
+
+use std::{sync::Mutex, time::Duration};
+
+use prometheus::{
+    core::Collector, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry,
+    TextEncoder,
+};
+
+#[derive(Clone)]
+struct ScanMetrics {
+    scans: IntCounterVec,
+    duration: HistogramVec,
+    scanned_bytes: IntCounter,
+    connect_failures: IntCounter,
+}
+
+impl ScanMetrics {
+    fn new() -> Self {
+        ScanMetrics {
+            scans: IntCounterVec::new(
+                Opts::new("clamav_client_scans_total", "Number of scans by outcome"),
+                &["outcome"],
+            )
+            .expect("the metric is valid"),
+            duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "clamav_client_scan_duration_seconds",
+                    "Duration of scans by outcome, from connecting until the response",
+                ),
+                &["outcome"],
+            )
+            .expect("the metric is valid"),
+            scanned_bytes: IntCounter::new(
+                "clamav_client_scanned_bytes_total",
+                "Number of bytes sent to ClamAV",
+            )
+            .expect("the metric is valid"),
+            connect_failures: IntCounter::new(
+                "clamav_client_connect_failures_total",
+                "Number of failed connections to ClamAV",
+            )
+            .expect("the metric is valid"),
+        }
+    }
+
+    fn collectors(&self) -> [Box<dyn Collector>; 4] {
+        [
+            Box::new(self.scans.clone()),
+            Box::new(self.duration.clone()),
+            Box::new(self.scanned_bytes.clone()),
+            Box::new(self.connect_failures.clone()),
+        ]
+    }
+}
+
+/// The metrics of all scans, created on first use
+static METRICS: Mutex<Option<ScanMetrics>> = Mutex::new(None);
+
+fn metrics() -> ScanMetrics {
+    METRICS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(ScanMetrics::new)
+        .clone()
+}
+
+/// Registers the metrics of all scans with `registry`
+///
+/// The metrics can be registered with several registries, but only once with
+/// each of them.
+///
+/// # Errors
+///
+/// Fails if metrics with the same names are already registered.
+pub fn register(registry: &Registry) -> prometheus::Result<()> {
+    for collector in metrics().collectors() {
+        registry.register(collector)?;
+    }
+    Ok(())
+}
+
+/// Returns the metrics of all scans in the text format of Prometheus
+pub fn text_snapshot() -> String {
+    let metric_families: Vec<_> = metrics()
+        .collectors()
+        .iter()
+        .flat_map(|collector| collector.collect())
+        // Labeled metrics without any values yet cannot be encoded
+        .filter(|metric_family| !metric_family.get_metric().is_empty())
+        .collect();
+    let mut snapshot = String::new();
+    TextEncoder::new()
+        .encode_utf8(&metric_families, &mut snapshot)
+        .expect("the metrics can be encoded");
+    snapshot
+}
+
+pub(crate) fn record_scan(outcome: &str, duration: Duration, bytes_sent: u64) {
+    let metrics = metrics();
+    metrics.scans.with_label_values(&[outcome]).inc();
+    metrics
+        .duration
+        .with_label_values(&[outcome])
+        .observe(duration.as_secs_f64());
+    metrics.scanned_bytes.inc_by(bytes_sent);
+}
+
+pub(crate) fn record_connect_failure() {
+    metrics().connect_failures.inc();
+}
@@ -0,0 +1,265 @@
+use std::{
+    io::{self, IoSlice},
+    time::Duration,
+};
+
+use super::{
+    progress::ProgressTracker, report::ReportTimer, throttle::Throttle, wire, ClamdError,
+    ResponseBuffer, ScanOptions, INSTREAM, MAX_CHUNK_SIZE,
+};
+
+/// The exchange of a command sent to ClamAV, which records the command and its
+/// response
+///
+/// The command functions of this crate, of [`tokio`](crate::tokio) and of
+/// [`async_std`](crate::async_std) only write [`bytes`](Self::bytes) and read
+/// the response, and report both to the exchange.
+pub(crate) struct Exchange<'a> {
+    bytes: &'a [u8],
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<'a> Exchange<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Exchange {
+            bytes,
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "clamav_client::command",
+                command = %super::command_name(bytes)
+            ),
+        }
+    }
+
+    /// Returns the bytes to write to ClamAV
+    pub(crate) fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Records that the command was written and flushed
+    pub(crate) fn sent(&self) {
+        wire::command(self.bytes);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, "sent command");
+    }
+
+    /// Records the response of ClamAV
+    pub(crate) fn received(self, response: &[u8]) {
+        wire::response(response);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, len = response.len(), "received response");
+    }
+}
+
+/// The state of an INSTREAM command
+///
+/// It chooses the chunk size, enforces the maximum stream size, paces the
+/// chunks and reports them, so that the scan loops of all runtimes only read
+/// the input, sleep for the returned delays and write the [`Frame`]s of the
+/// chunks to their streams.
+pub(crate) struct Instream<'a> {
+    options: &'a ScanOptions,
+    chunk_size: usize,
+    stream_size: u64,
+    progress: ProgressTracker<'a>,
+    throttle: Option<Throttle>,
+}
+
+impl<'a> Instream<'a> {
+    /// Starts a stream of `input_size` bytes, or of unknown size
+    ///
+    /// Fails if the chunk size of the options is invalid or if the input is
+    /// empty and empty inputs are rejected.
+    pub(crate) fn start(options: &'a ScanOptions, input_size: Option<u64>) -> io::Result<Self> {
+        if input_size == Some(0) && options.reject_empty {
+            return Err(ClamdError::EmptyInput.into());
+        }
+        Ok(Instream {
+            options,
+            chunk_size: options.chunk_size_for(input_size)?,
+            stream_size: 0,
+            progress: ProgressTracker::new(options, input_size),
+            throttle: Throttle::new(options),
+        })
+    }
+
+    /// Records that the INSTREAM command was written
+    pub(crate) fn started(&self) {
+        wire::command(INSTREAM);
+    }
+
+    /// Returns the maximum number of bytes of a chunk
+    pub(crate) fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Accounts for `len` bytes of input, failing if the stream exceeds the
+    /// maximum stream size
+    pub(crate) fn input(&mut self, len: usize) -> io::Result<()> {
+        self.stream_size += len as u64;
+        self.options.check_stream_size(self.stream_size)
+    }
+
+    /// Fails before the end of the stream is written if the stream is empty
+    /// and empty inputs are rejected
+    pub(crate) fn end(&self) -> io::Result<()> {
+        if self.stream_size == 0 && self.options.reject_empty {
+            return Err(ClamdError::EmptyInput.into());
+        }
+        Ok(())
+    }
+
+    /// Returns how long to wait before sending a chunk of `len` bytes
+    pub(crate) fn delay(&mut self, len: usize) -> Option<Duration> {
+        self.throttle
+            .as_mut()
+            .and_then(|throttle| throttle.delay(len))
+    }
+
+    /// Reports a chunk that was written to ClamAV
+    pub(crate) fn chunk_sent(&mut self, chunk: &[u8], report: &mut ReportTimer<'_>) {
+        self.progress.chunk_sent(chunk.len());
+        report.chunk_sent(chunk);
+    }
+
+    /// Reports a chunk that was written to ClamAV without passing through
+    /// userspace
+    #[cfg(all(feature = "sendfile", target_os = "linux"))]
+    pub(crate) fn chunk_sent_unhashed(&mut self, len: u64, report: &mut ReportTimer<'_>) {
+        self.progress.chunk_sent(len as usize);
+        report.chunk_sent_unhashed(len);
+    }
+
+    /// Reports that the end of the stream was written and flushed
+    pub(crate) fn finished(self, report: &mut ReportTimer<'_>) {
+        report.streamed();
+    }
+}
+
+/// A chunk and its 4-byte length header, which may take several writes
+///
+/// The header and the payload are written together using vectored writes.
+pub(crate) struct Frame<'a> {
+    header: [u8; 4],
+    chunk: &'a [u8],
+    written: usize,
+}
+
+impl<'a> Frame<'a> {
+    /// Returns the frames of `chunk`, which is split into several chunks if it
+    /// is larger than [`MAX_CHUNK_SIZE`], so that its length is never
+    /// truncated in the header
+    pub(crate) fn split(chunk: &'a [u8]) -> impl Iterator<Item = Frame<'a>> {
+        chunk
+            .chunks(MAX_CHUNK_SIZE)
+            .map(|chunk| Frame::resume(chunk, 0))
+    }
+
+    /// Returns the frame of `chunk` of which `written` bytes were written
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk` is larger than [`MAX_CHUNK_SIZE`].
+    pub(crate) fn resume(chunk: &'a [u8], written: usize) -> Self {
+        Frame {
+            header: super::chunk_header(chunk.len()),
+            chunk,
+            written,
+        }
+    }
+
+    /// Returns the number of bytes of the frame that were written
+    #[cfg(feature = "sink")]
+    pub(crate) fn written(&self) -> usize {
+        self.written
+    }
+
+    pub(crate) fn is_written(&self) -> bool {
+        self.written == self.header.len() + self.chunk.len()
+    }
+
+    /// Returns the parts of the frame that remain to be written
+    pub(crate) fn remaining(&self) -> [IoSlice<'_>; 2] {
+        let header_written = self.written.min(self.header.len());
+        [
+            IoSlice::new(&self.header[header_written..]),
+            IoSlice::new(&self.chunk[self.written - header_written..]),
+        ]
+    }
+
+    /// Advances the frame by the result of a write, ignoring interruptions
+    ///
+    /// A write of zero bytes fails with [`WriteZero`](io::ErrorKind::WriteZero).
+    pub(crate) fn advance(&mut self, result: io::Result<usize>) -> io::Result<()> {
+        match result {
+            Ok(0) => Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                self.written += n;
+                Ok(())
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// A response that is read in steps of at most the read size of a
+/// [`ResponseBuffer`] directly into the buffer of the response
+///
+/// The buffer only contains the bytes that were read, also if a read fails or
+/// the read is dropped.
+pub(crate) struct ResponseReader<'a> {
+    response: &'a mut Vec<u8>,
+    read_size: usize,
+    filled: usize,
+}
+
+impl<'a> ResponseReader<'a> {
+    /// Clears `response` and reserves its initial capacity
+    pub(crate) fn new(response: &'a mut Vec<u8>, buffer: &ResponseBuffer) -> Self {
+        response.clear();
+        response.reserve(buffer.initial_capacity);
+        ResponseReader {
+            response,
+            read_size: buffer.read_size.max(1),
+            filled: 0,
+        }
+    }
+
+    /// Returns the part of the buffer to read into next, which is the spare
+    /// capacity of the buffer, or the read size once the buffer is full
+    pub(crate) fn unfilled(&mut self) -> &mut [u8] {
+        let spare = self.response.capacity() - self.filled;
+        let len = if spare == 0 {
+            self.read_size
+        } else {
+            spare.min(self.read_size)
+        };
+        self.response.resize(self.filled + len, 0);
+        &mut self.response[self.filled..]
+    }
+
+    /// Advances the response by the result of a read into
+    /// [`unfilled`](Self::unfilled), ignoring interruptions
+    ///
+    /// Returns whether the end of the response was reached.
+    pub(crate) fn advance(&mut self, result: io::Result<usize>) -> io::Result<bool> {
+        let result = match result {
+            Ok(n) => {
+                self.filled += n;
+                Ok(n == 0)
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => Ok(false),
+            Err(err) => Err(err),
+        };
+        self.response.truncate(self.filled);
+        result
+    }
+}
+
+impl Drop for ResponseReader<'_> {
+    fn drop(&mut self) {
+        self.response.truncate(self.filled);
+    }
+}
@@ -0,0 +1,53 @@
+use super::{ScanOutcome, VerdictPolicy};
+
+/// The prefix of the signatures of potentially unwanted applications
+pub(crate) const PUA_PREFIX: &str = "PUA.";
+
+/// How detections of potentially unwanted applications (PUA), e.g.
+/// `PUA.Win.Packer.Upx-1`, are classified
+///
+/// ClamAV only reports PUA if `DetectPUA` is enabled in its configuration. As
+/// a [`VerdictPolicy`], this policy only changes outcomes whose signature
+/// starts with `PUA.`, see [`ScanOutcome::is_pua`].
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{PuaPolicy, ScanOutcome, VerdictPolicy};
+///
+/// let outcome = ScanOutcome::Infected {
+///     signature: "PUA.Win.Packer.Upx-1".to_owned(),
+/// };
+/// assert!(PuaPolicy::Infected.apply(outcome.clone()).is_infected());
+/// assert!(PuaPolicy::Warning.apply(outcome.clone()).is_warning());
+/// assert!(PuaPolicy::Clean.apply(outcome).is_clean());
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PuaPolicy {
+    /// Reports PUA as infected, like any other detection
+    #[default]
+    Infected,
+    /// Reports PUA as [`ScanOutcome::Warning`]
+    Warning,
+    /// Reports PUA as clean
+    Clean,
+}
+
+impl VerdictPolicy for PuaPolicy {
+    fn apply(&self, outcome: ScanOutcome) -> ScanOutcome {
+        if !outcome.is_pua() {
+            return outcome;
+        }
+        match (self, outcome) {
+            (PuaPolicy::Infected, ScanOutcome::Warning { signature }) => {
+                ScanOutcome::Infected { signature }
+            }
+            (PuaPolicy::Warning, ScanOutcome::Infected { signature }) => {
+                ScanOutcome::Warning { signature }
+            }
+            (PuaPolicy::Clean, _) => ScanOutcome::Clean,
+            (_, outcome) => outcome,
+        }
+    }
+}
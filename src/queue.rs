@@ -0,0 +1,604 @@
+use std::{
+    fmt,
+    fs::File,
+    future::Future,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use super::{
+    context::ResultExt, outcome, response, wipe::DataBuffer, wire, ClamdError, ErrorPhase,
+    ScanOptions, ScanOutcome, TransportProtocol, END, IDSESSION, PING,
+};
+
+/// Sessions that have been idle for longer are replaced by a new connection
+/// before the next scan, since ClamAV closes idle sessions after its
+/// `IdleTimeout` (30 seconds by default)
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The input of a job submitted to a [`ScanQueue`]
+pub enum ScanJob {
+    /// A file to be read and scanned by the worker
+    File(PathBuf),
+    /// A data buffer to be scanned
+    Buffer(Vec<u8>),
+    /// A reader whose data is scanned until it reaches its end
+    Stream(Box<dyn Read + Send>),
+}
+
+impl ScanJob {
+    /// Creates a job that scans the file at `file_path`
+    pub fn file<P: Into<PathBuf>>(file_path: P) -> Self {
+        ScanJob::File(file_path.into())
+    }
+
+    /// Creates a job that scans `buffer`
+    pub fn buffer<B: Into<Vec<u8>>>(buffer: B) -> Self {
+        ScanJob::Buffer(buffer.into())
+    }
+
+    /// Creates a job that scans the data read from `reader`
+    pub fn stream<R: Read + Send + 'static>(reader: R) -> Self {
+        ScanJob::Stream(Box::new(reader))
+    }
+}
+
+impl fmt::Debug for ScanJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanJob::File(file_path) => f.debug_tuple("File").field(file_path).finish(),
+            ScanJob::Buffer(buffer) => f.debug_tuple("Buffer").field(&buffer.len()).finish(),
+            ScanJob::Stream(_) => f.write_str("Stream(..)"),
+        }
+    }
+}
+
+/// How a worker of a [`ScanQueue`] checks its reused session before scanning
+/// the next job
+///
+/// Pinging finds sessions that ClamAV closed, e.g. after a restart, before a
+/// job is sent on them, at the cost of a round trip. Without it, file and
+/// buffer jobs that fail on a reused session are still retried once on a new
+/// connection, but stream jobs fail, since their data cannot be read again.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use clamav_client::{ScanQueueOptions, SessionValidation};
+///
+/// let options = ScanQueueOptions::new()
+///     .session_validation(SessionValidation::PingIfIdle(Duration::from_secs(2)));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum SessionValidation {
+    /// Reuses the session without checking it
+    #[default]
+    None,
+    /// Pings ClamAV on the session before each job
+    Ping,
+    /// Pings ClamAV on the session before a job if the session has been idle
+    /// for longer than the given duration
+    PingIfIdle(Duration),
+}
+
+impl SessionValidation {
+    fn should_ping(self, idle: Duration) -> bool {
+        match self {
+            SessionValidation::None => false,
+            SessionValidation::Ping => true,
+            SessionValidation::PingIfIdle(max_idle) => idle > max_idle,
+        }
+    }
+}
+
+/// Options that control the workers and the queue of a [`ScanQueue`]
+///
+/// By default, four workers scan jobs from a queue holding up to 64 jobs and
+/// connect to ClamAV when they receive their first job.
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::ScanQueueOptions::new()
+///     .workers(8)
+///     .capacity(256)
+///     .warmup(8)
+///     .warmup_ping(true);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ScanQueueOptions {
+    pub(crate) workers: usize,
+    pub(crate) capacity: usize,
+    pub(crate) warmup: usize,
+    pub(crate) warmup_ping: bool,
+    pub(crate) session_validation: SessionValidation,
+}
+
+impl Default for ScanQueueOptions {
+    fn default() -> Self {
+        ScanQueueOptions {
+            workers: 4,
+            capacity: 64,
+            warmup: 0,
+            warmup_ping: false,
+            session_validation: SessionValidation::None,
+        }
+    }
+}
+
+impl ScanQueueOptions {
+    /// Creates the default queue options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of worker threads, each holding its own connection
+    ///
+    /// [`ScanQueue::new`] fails with [`ClamdError::InvalidOptions`] if
+    /// `workers` is zero (see [`validate`](Self::validate)).
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Sets how many submitted jobs may wait for a worker before
+    /// [`ScanQueue::submit`] blocks
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets how many workers establish their session when the queue is
+    /// started, so that the first jobs do not wait for a connection, none by
+    /// default
+    ///
+    /// At most one session per worker is established. A worker whose warmup
+    /// fails connects again for its first job. Since idle sessions are
+    /// replaced after a few seconds, warming up helps jobs that are submitted
+    /// shortly after the queue is started, e.g. right after a deployment.
+    pub fn warmup(mut self, sessions: usize) -> Self {
+        self.warmup = sessions;
+        self
+    }
+
+    /// Sets whether warmed up sessions are primed with a PING, which also
+    /// checks that ClamAV answers on them, `false` by default
+    pub fn warmup_ping(mut self, warmup_ping: bool) -> Self {
+        self.warmup_ping = warmup_ping;
+        self
+    }
+
+    /// Sets how a worker checks its reused session before scanning the next
+    /// job, [`SessionValidation::None`] by default
+    ///
+    /// A session that fails the check is replaced by a new connection.
+    pub fn session_validation(mut self, session_validation: SessionValidation) -> Self {
+        self.session_validation = session_validation;
+        self
+    }
+
+    /// Checks that the options are valid
+    ///
+    /// [`ScanQueue::new`] checks the options before starting any worker and
+    /// fails with [`ClamdError::InvalidOptions`] if the number of workers is
+    /// zero.
+    pub fn validate(&self) -> Result<(), ClamdError> {
+        if self.workers == 0 {
+            return Err(ClamdError::InvalidOptions {
+                reason: "the number of workers must not be zero",
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A pool of worker threads that scan submitted jobs in the background
+///
+/// Each worker holds its own session with ClamAV (IDSESSION) and scans one
+/// job after another on it, so the connection is reused across jobs. Broken
+/// or idle sessions are replaced by a new connection; file and buffer jobs are
+/// retried once on a new connection if a reused session fails. Workers can also
+/// ping their session before a job, see [`SessionValidation`].
+///
+/// Dropping the queue waits for all submitted jobs to be scanned.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{ScanJob, ScanQueue, ScanQueueOptions, Tcp};
+///
+/// let clamd_tcp = Tcp{ host_address: "localhost:3310" };
+/// let queue = ScanQueue::new(clamd_tcp, None, ScanQueueOptions::new().workers(2)).unwrap();
+///
+/// let file_scan = queue.submit(ScanJob::file("tests/data/eicar.txt"));
+/// let buffer_scan = queue.submit(ScanJob::buffer(&b"clean data"[..]));
+/// assert!(file_scan.wait().unwrap().is_infected());
+/// assert!(buffer_scan.wait().unwrap().is_clean());
+/// ```
+///
+pub struct ScanQueue {
+    jobs: Option<SyncSender<QueuedJob>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ScanQueue {
+    /// Starts the workers of a new queue
+    ///
+    /// # Arguments
+    ///
+    /// * `connection`: The connection type to use - either TCP or a Unix socket connection
+    /// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+    /// * `queue_options`: The [`ScanQueueOptions`] that control the workers and the queue
+    ///
+    /// # Returns
+    ///
+    /// The [`ScanQueue`], or an [`io::Error`] if the queue options are invalid
+    /// or a worker thread cannot be spawned. Connections are established lazily by the workers, unless
+    /// they are warmed up (see [`ScanQueueOptions::warmup`]), which happens in
+    /// the background as well.
+    pub fn new<T, O>(connection: T, options: O, queue_options: ScanQueueOptions) -> io::Result<Self>
+    where
+        T: TransportProtocol + Clone + Send + 'static,
+        O: Into<ScanOptions>,
+    {
+        queue_options.validate()?;
+        let options = options.into();
+        let (sender, receiver) = mpsc::sync_channel(queue_options.capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut queue = ScanQueue {
+            jobs: Some(sender),
+            workers: Vec::with_capacity(queue_options.workers),
+        };
+        for index in 0..queue_options.workers {
+            let connection = connection.clone();
+            let options = options.clone();
+            let receiver = Arc::clone(&receiver);
+            let warmup = index < queue_options.warmup;
+            let warmup_ping = queue_options.warmup_ping;
+            let handle = thread::Builder::new()
+                .name(format!("clamav-client-worker-{}", index))
+                .spawn(move || {
+                    let mut worker = Worker {
+                        connection,
+                        options,
+                        session_validation: queue_options.session_validation,
+                        session: None,
+                        buffer: DataBuffer::default(),
+                    };
+                    if warmup {
+                        worker.warm_up(warmup_ping);
+                    }
+                    worker.run(&receiver)
+                })?;
+            queue.workers.push(handle);
+        }
+        Ok(queue)
+    }
+
+    /// Submits a job, waiting for space in the queue if it is full
+    ///
+    /// # Returns
+    ///
+    /// A [`ScanHandle`] for the result of the job
+    pub fn submit(&self, job: ScanJob) -> ScanHandle {
+        let (completer, handle) = ScanHandle::pair();
+        if let Some(jobs) = &self.jobs {
+            // If all workers have stopped, the dropped completer fails the job
+            let _ = jobs.send(QueuedJob { job, completer });
+        }
+        handle
+    }
+
+    /// Submits a job unless the queue is full
+    ///
+    /// # Returns
+    ///
+    /// A [`ScanHandle`] for the result of the job, or the job itself if the
+    /// queue is full
+    pub fn try_submit(&self, job: ScanJob) -> Result<ScanHandle, ScanJob> {
+        let (completer, handle) = ScanHandle::pair();
+        if let Some(jobs) = &self.jobs {
+            if let Err(TrySendError::Full(queued)) = jobs.try_send(QueuedJob { job, completer }) {
+                return Err(queued.job);
+            }
+        }
+        Ok(handle)
+    }
+
+    /// Waits for all submitted jobs to be scanned and stops the workers
+    pub fn shutdown(self) {}
+}
+
+impl Drop for ScanQueue {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A handle for the result of a job submitted to a [`ScanQueue`]
+///
+/// The result can be waited for using [`wait`](Self::wait), or awaited in any
+/// async runtime since the handle is a [`Future`].
+///
+pub struct ScanHandle {
+    slot: Arc<Slot>,
+}
+
+impl ScanHandle {
+    fn pair() -> (Completer, ScanHandle) {
+        let slot = Arc::new(Slot::default());
+        (
+            Completer {
+                slot: Arc::clone(&slot),
+            },
+            ScanHandle { slot },
+        )
+    }
+
+    /// Blocks until the job has been scanned
+    ///
+    /// # Returns
+    ///
+    /// The [`ScanOutcome`] of the job, or the error that occurred while reading
+    /// or scanning it
+    pub fn wait(self) -> Result<ScanOutcome, ClamdError> {
+        let mut state = self.slot.state.lock().unwrap();
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            state = self.slot.done.wait(state).unwrap();
+        }
+    }
+
+    /// Returns `true` if the job has been scanned
+    pub fn is_finished(&self) -> bool {
+        self.slot.state.lock().unwrap().result.is_some()
+    }
+}
+
+impl Future for ScanHandle {
+    type Output = Result<ScanOutcome, ClamdError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.slot.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    state: Mutex<SlotState>,
+    done: Condvar,
+}
+
+#[derive(Default)]
+struct SlotState {
+    result: Option<Result<ScanOutcome, ClamdError>>,
+    waker: Option<Waker>,
+}
+
+/// The worker's side of a [`ScanHandle`]
+///
+/// Dropping it without a result, e.g. because the worker panicked, fails the
+/// job instead of leaving the handle waiting forever.
+struct Completer {
+    slot: Arc<Slot>,
+}
+
+impl Completer {
+    fn complete(self, result: Result<ScanOutcome, ClamdError>) {
+        self.set(result);
+    }
+
+    fn is_set(&self) -> bool {
+        self.slot.state.lock().unwrap().result.is_some()
+    }
+
+    fn set(&self, result: Result<ScanOutcome, ClamdError>) {
+        let mut state = self.slot.state.lock().unwrap();
+        state.result = Some(result);
+        let waker = state.waker.take();
+        drop(state);
+        self.slot.done.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Completer {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.slot) > 1 && !self.is_set() {
+            let err = io::Error::other("the scan job was dropped");
+            self.set(Err(err.into()));
+        }
+    }
+}
+
+struct QueuedJob {
+    job: ScanJob,
+    completer: Completer,
+}
+
+struct Session<S> {
+    stream: BufReader<S>,
+    last_used: Instant,
+}
+
+impl<S: Read + Write> Session<S> {
+    /// Returns `true` if ClamAV answers a ping on the session
+    fn ping(&mut self) -> bool {
+        let stream = self.stream.get_mut();
+        if stream.write_all(PING).and_then(|_| stream.flush()).is_err() {
+            return false;
+        }
+        wire::command(PING);
+        let mut response = Vec::new();
+        super::read_session_response(&mut self.stream, &mut response).is_ok()
+            && response::is_pong(outcome::strip_request_id(&response))
+    }
+}
+
+struct Worker<T: TransportProtocol> {
+    connection: T,
+    options: ScanOptions,
+    session_validation: SessionValidation,
+    session: Option<Session<T::Stream>>,
+    buffer: DataBuffer,
+}
+
+impl<T: TransportProtocol> Worker<T> {
+    /// Establishes the session before the first job, optionally checking it
+    /// with a ping
+    fn warm_up(&mut self, ping: bool) {
+        // A session that cannot be established is not an error yet, since the
+        // first job connects again
+        if let Ok(mut session) = self.connect_session() {
+            if !ping || session.ping() {
+                session.last_used = Instant::now();
+                self.session = Some(session);
+            }
+        }
+    }
+
+    /// Scans jobs until the queue is dropped, then ends the session
+    fn run(mut self, jobs: &Mutex<Receiver<QueuedJob>>) {
+        loop {
+            let queued = jobs.lock().unwrap().recv();
+            let QueuedJob { job, completer } = match queued {
+                Ok(queued) => queued,
+                Err(_) => break,
+            };
+            let result = self.scan(job);
+            completer.complete(result);
+        }
+        if let Some(mut session) = self.session.take() {
+            let stream = session.stream.get_mut();
+            if stream.write_all(END).and_then(|_| stream.flush()).is_ok() {
+                wire::command(END);
+            }
+        }
+    }
+
+    fn scan(&mut self, job: ScanJob) -> Result<ScanOutcome, ClamdError> {
+        match job {
+            ScanJob::File(file_path) => {
+                // The file is opened once, since a new connection cannot fix
+                // errors opening it
+                let file =
+                    File::open(&file_path).phase_of(ErrorPhase::Input, file_path.display())?;
+                let file_size = file
+                    .metadata()
+                    .phase_of(ErrorPhase::Input, file_path.display())?
+                    .len();
+                self.options.check_input_size(file_size)?;
+                self.options.check_archive(&file, file_size)?;
+                self.scan_retrying(|worker| {
+                    (&file)
+                        .seek(SeekFrom::Start(0))
+                        .phase_of(ErrorPhase::Input, file_path.display())?;
+                    worker.scan_in_session(&file, Some(file_size))
+                })
+            }
+            ScanJob::Buffer(buffer) => self.scan_retrying(|worker| {
+                let buffer_size = buffer.len() as u64;
+                worker.options.check_input_size(buffer_size)?;
+                worker
+                    .options
+                    .check_archive(io::Cursor::new(&buffer[..]), buffer_size)?;
+                worker.scan_in_session(&buffer[..], Some(buffer_size))
+            }),
+            ScanJob::Stream(reader) => self.scan_in_session(reader, None),
+        }
+    }
+
+    /// Scans once more on a new connection if a reused session failed with an
+    /// I/O error
+    fn scan_retrying<F>(&mut self, mut scan: F) -> Result<ScanOutcome, ClamdError>
+    where
+        F: FnMut(&mut Self) -> Result<ScanOutcome, ClamdError>,
+    {
+        let reused = self.session.is_some();
+        match scan(self) {
+            #[cfg(feature = "log")]
+            Err(ClamdError::Io(err)) if reused => {
+                log::info!(
+                    target: "clamav_client",
+                    "Retrying on a new connection after the session failed: {}",
+                    err
+                );
+                scan(self)
+            }
+            #[cfg(not(feature = "log"))]
+            Err(ClamdError::Io(_)) if reused => scan(self),
+            result => result,
+        }
+    }
+
+    fn scan_in_session<R: Read>(
+        &mut self,
+        input: R,
+        input_size: Option<u64>,
+    ) -> Result<ScanOutcome, ClamdError> {
+        let session_validation = self.session_validation;
+        let reusable = self.session.take().and_then(|mut session| {
+            let idle = session.last_used.elapsed();
+            let valid = idle < SESSION_IDLE_TIMEOUT
+                && (!session_validation.should_ping(idle) || session.ping());
+            if valid {
+                Some(session)
+            } else {
+                None
+            }
+        });
+        let mut session = match reusable {
+            Some(session) => session,
+            None => self.connect_session()?,
+        };
+        let result = super::scan_in_session(
+            input,
+            input_size,
+            &self.options,
+            &mut session.stream,
+            &mut self.buffer,
+        );
+        // After an error, the session may be in the middle of a stream
+        if result.is_ok() {
+            session.last_used = Instant::now();
+            self.session = Some(session);
+        }
+        result
+    }
+
+    fn connect_session(&self) -> io::Result<Session<T::Stream>> {
+        let mut stream = self.connection.connect()?;
+        stream.write_all(IDSESSION).phase(ErrorPhase::Handshake)?;
+        wire::command(IDSESSION);
+        Ok(Session {
+            stream: BufReader::new(stream),
+            last_used: Instant::now(),
+        })
+    }
+}
@@ -0,0 +1,18 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// Returns a random number from `0` to `max`, both inclusive
+pub(crate) fn random_up_to(max: u64) -> u64 {
+    if max == 0 {
+        return 0;
+    }
+    // Each `RandomState` is seeded differently, which is random enough to
+    // spread connections across servers and reloads across clients
+    let random = RandomState::new().build_hasher().finish();
+    match max.checked_add(1) {
+        Some(range) => random % range,
+        None => random,
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    fmt::Write,
+    sync::{Mutex, RwLock},
+    time::Duration,
+};
+
+use redis::{Commands, Connection};
+
+use super::{database, ClamdError, ScanCache, ScanOutcome, TransportProtocol};
+
+/// A [`ScanCache`] that stores outcomes in Redis, so that multiple clients,
+/// possibly on different hosts, share the outcomes for identical content
+///
+/// Outcomes are stored under keys that contain the version of the ClamAV
+/// signature database. After a signature update, set the new version using
+/// [`update_database_version`](Self::update_database_version) or
+/// [`set_database_version`](Self::set_database_version); outcomes of the
+/// previous version are no longer found and expire after their TTL. Until the
+/// database version is set, the cache is bypassed.
+///
+/// Redis errors are not reported: a failed lookup is treated as a cache miss
+/// and a failed store is ignored, so an unavailable Redis server only disables
+/// caching. The connection is re-established by the next operation after an
+/// error.
+///
+/// Use the feature flag "cache-redis" to enable this type.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let redis_client = redis::Client::open("redis://localhost:6379").unwrap();
+/// let cache = clamav_client::RedisCache::new(redis_client).ttl(Duration::from_secs(86400));
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// cache.update_database_version(clamd_tcp).unwrap();
+/// let client = clamav_client::Client::new(clamd_tcp).cache(cache);
+/// let outcome = client.scan_buffer(b"clean data").unwrap();
+/// ```
+///
+pub struct RedisCache {
+    client: redis::Client,
+    connection: Mutex<Option<Connection>>,
+    key_prefix: String,
+    ttl: Option<Duration>,
+    database_version: RwLock<Option<String>>,
+}
+
+impl RedisCache {
+    /// Creates a cache that stores outcomes using `client`
+    pub fn new(client: redis::Client) -> Self {
+        RedisCache {
+            client,
+            connection: Mutex::new(None),
+            key_prefix: "clamav-client:".to_owned(),
+            ttl: None,
+            database_version: RwLock::new(None),
+        }
+    }
+
+    /// Sets the prefix of all keys, `clamav-client:` by default
+    pub fn key_prefix<S: Into<String>>(mut self, key_prefix: S) -> Self {
+        self.key_prefix = key_prefix.into();
+        self
+    }
+
+    /// Sets how long an outcome stays valid after it was stored
+    ///
+    /// Without a TTL, outcomes of previous database versions stay in Redis
+    /// until they are evicted by its `maxmemory-policy`.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the version of the signature database that outcomes are looked
+    /// up and stored for, if it has been set
+    pub fn database_version(&self) -> Option<String> {
+        self.database_version.read().unwrap().clone()
+    }
+
+    /// Sets the version of the signature database from a response to the
+    /// VERSION command, e.g. `ClamAV 1.4.3/27431/Fri Oct 16 08:26:01 2026`
+    ///
+    /// # Returns
+    ///
+    /// [`ClamdError::InvalidResponse`] if the response does not contain a
+    /// database version
+    pub fn set_database_version(&self, version_response: &[u8]) -> Result<(), ClamdError> {
+        let version = database::database_version(version_response)?;
+        *self.database_version.write().unwrap() = Some(version);
+        Ok(())
+    }
+
+    /// Gets the version from ClamAV and sets the version of the signature
+    /// database from it
+    ///
+    /// Call this periodically, e.g. after each signature update, so that no
+    /// outcomes of an outdated database are served.
+    pub fn update_database_version<T: TransportProtocol>(
+        &self,
+        connection: T,
+    ) -> Result<(), ClamdError> {
+        let response = super::get_version(connection)?;
+        self.set_database_version(&response)
+    }
+
+    fn key(&self, digest: &[u8; 32]) -> Option<String> {
+        let database_version = self.database_version.read().unwrap();
+        let mut key = format!("{}{}:", self.key_prefix, database_version.as_ref()?);
+        for byte in digest {
+            let _ = write!(key, "{:02x}", byte);
+        }
+        Some(key)
+    }
+
+    fn with_connection<R>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> redis::RedisResult<R>,
+    ) -> Option<R> {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.is_none() {
+            *connection = self.client.get_connection().ok();
+        }
+        let result = f(connection.as_mut()?);
+        if result.is_err() {
+            *connection = None;
+        }
+        result.ok()
+    }
+}
+
+impl ScanCache for RedisCache {
+    fn lookup(&self, digest: &[u8; 32]) -> Option<ScanOutcome> {
+        let key = self.key(digest)?;
+        let value =
+            self.with_connection(|connection| connection.get::<_, Option<String>>(key))??;
+        value.parse().ok()
+    }
+
+    fn store(&self, digest: [u8; 32], outcome: &ScanOutcome) {
+        let key = match self.key(&digest) {
+            Some(key) => key,
+            None => return,
+        };
+        let value = match outcome {
+            ScanOutcome::Clean => "OK".to_owned(),
+            ScanOutcome::Infected { signature } => format!("{} FOUND", signature),
+            ScanOutcome::Warning { .. } => outcome.to_string(),
+            ScanOutcome::Error { .. } => return,
+        };
+        self.with_connection(|connection| -> redis::RedisResult<()> {
+            match self.ttl {
+                Some(ttl) => connection.pset_ex(key, value, ttl.as_millis().max(1) as u64),
+                None => connection.set(key, value),
+            }
+        });
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    io,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    get_version, is_available, is_reloading, random::random_up_to, reload, ClamdError,
+    TransportProtocol,
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// When to reload the virus databases of ClamAV with [`schedule_reload`]
+///
+/// A schedule either reloads at a fixed interval or daily at fixed times of
+/// day. A random jitter spreads the reloads of many clients, and after each
+/// reload the scheduler waits until ClamAV answers again.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// // Every hour, 0 to 5 minutes after the hour
+/// let hourly = clamav_client::ReloadSchedule::every(Duration::from_secs(60 * 60))
+///     .jitter(Duration::from_secs(5 * 60));
+///
+/// // Shortly after the freshclam runs at 02:00 and 14:00 UTC
+/// let twice_a_day = clamav_client::ReloadSchedule::daily_at_utc([(2, 10), (14, 10)]);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ReloadSchedule {
+    pub(crate) times: ReloadTimes,
+    pub(crate) jitter: Duration,
+    pub(crate) ready_timeout: Duration,
+    pub(crate) poll_interval: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum ReloadTimes {
+    Every(Duration),
+    /// Seconds since midnight UTC
+    DailyAt(Vec<u64>),
+}
+
+impl ReloadSchedule {
+    /// Reloads every `interval`, starting `interval` after the scheduler
+    /// starts
+    pub fn every(interval: Duration) -> Self {
+        Self::new(ReloadTimes::Every(interval))
+    }
+
+    /// Reloads every day at the given times of day in UTC, as hours and
+    /// minutes
+    ///
+    /// Hours and minutes beyond the end of the day wrap around, e.g. `(24, 30)`
+    /// is half past midnight. Without any times, no reload is ever scheduled.
+    pub fn daily_at_utc<I: IntoIterator<Item = (u8, u8)>>(times: I) -> Self {
+        let times = times
+            .into_iter()
+            .map(|(hour, minute)| {
+                (u64::from(hour) * 3600 + u64::from(minute) * 60) % SECONDS_PER_DAY
+            })
+            .collect();
+        Self::new(ReloadTimes::DailyAt(times))
+    }
+
+    fn new(times: ReloadTimes) -> Self {
+        ReloadSchedule {
+            times,
+            jitter: Duration::ZERO,
+            ready_timeout: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Delays each reload by a random duration of up to `jitter`, so that
+    /// many clients do not reload the same server at the same time
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets how long to wait for ClamAV to answer a ping after a reload
+    /// before the reload is reported as failed, 60 seconds by default
+    pub fn ready_timeout(mut self, ready_timeout: Duration) -> Self {
+        self.ready_timeout = ready_timeout;
+        self
+    }
+
+    /// Sets how often ClamAV is pinged while waiting for it to be ready after
+    /// a reload, every second by default
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Returns how long to wait at `now` until the next reload, without the
+    /// jitter
+    fn next_delay(&self, now: SystemTime) -> Option<Duration> {
+        match &self.times {
+            ReloadTimes::Every(interval) => Some(*interval),
+            ReloadTimes::DailyAt(times) => {
+                let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+                let since_midnight = since_epoch.as_secs() % SECONDS_PER_DAY;
+                times
+                    .iter()
+                    .map(|&time| {
+                        match (time + SECONDS_PER_DAY - since_midnight) % SECONDS_PER_DAY {
+                            // A reload at this very second already happened
+                            0 => SECONDS_PER_DAY,
+                            delay => delay,
+                        }
+                    })
+                    .min()
+                    .map(|delay| {
+                        Duration::from_secs(delay).saturating_sub(Duration::from_nanos(u64::from(
+                            since_epoch.subsec_nanos(),
+                        )))
+                    })
+            }
+        }
+    }
+}
+
+/// A completed reload of the virus databases of ClamAV
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReloadReport {
+    /// The time at which the RELOAD command was sent
+    pub started_at: SystemTime,
+    /// The time from sending the RELOAD command until ClamAV answered a ping
+    pub ready_after: Duration,
+    /// The response of ClamAV to the VERSION command after the reload, which
+    /// contains the version of the loaded databases
+    pub version: Vec<u8>,
+}
+
+/// Reloads the virus databases of ClamAV on a [`ReloadSchedule`] in the
+/// background
+///
+/// The result of each reload can be received using [`recv`](Self::recv),
+/// [`recv_timeout`](Self::recv_timeout) or by iterating over the scheduler.
+/// Dropping the scheduler stops it.
+///
+pub struct ReloadScheduler {
+    _stop: Sender<()>,
+    results: Receiver<Result<ReloadReport, ClamdError>>,
+}
+
+impl ReloadScheduler {
+    /// Blocks until the next reload has completed
+    ///
+    /// Returns [`None`] if the background thread has stopped.
+    pub fn recv(&self) -> Option<Result<ReloadReport, ClamdError>> {
+        self.results.recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next reload to complete
+    ///
+    /// Returns [`None`] if no reload has completed within `timeout` or the
+    /// background thread has stopped.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<ReloadReport, ClamdError>> {
+        self.results.recv_timeout(timeout).ok()
+    }
+
+    /// Returns the result of a reload that has already completed, if any,
+    /// without blocking
+    pub fn try_recv(&self) -> Option<Result<ReloadReport, ClamdError>> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl Iterator for ReloadScheduler {
+    type Item = Result<ReloadReport, ClamdError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+/// Reloads the virus databases of ClamAV on a schedule
+///
+/// This function starts a background thread that sends the RELOAD command to
+/// ClamAV at the times of `schedule`, e.g. after `freshclam` updated the
+/// databases in a separate process. After each reload, it pings ClamAV until
+/// it answers, gets the version of the loaded databases and reports the
+/// outcome through the returned [`ReloadScheduler`]. A failed reload does not
+/// stop the scheduler.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `schedule`: When to reload the databases
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let schedule = clamav_client::ReloadSchedule::every(Duration::from_millis(10));
+/// let scheduler = clamav_client::schedule_reload(clamd_tcp, schedule);
+///
+/// let report = scheduler.recv().unwrap().unwrap();
+/// println!("loaded {}", String::from_utf8_lossy(&report.version));
+/// ```
+///
+pub fn schedule_reload<T>(connection: T, schedule: ReloadSchedule) -> ReloadScheduler
+where
+    T: TransportProtocol + Send + 'static,
+{
+    let (stop_sender, stop) = mpsc::channel();
+    let (results_sender, results) = mpsc::channel();
+    thread::spawn(move || run_schedule(connection, schedule, stop, results_sender));
+    ReloadScheduler {
+        _stop: stop_sender,
+        results,
+    }
+}
+
+fn run_schedule<T: TransportProtocol>(
+    connection: T,
+    schedule: ReloadSchedule,
+    stop: Receiver<()>,
+    results: Sender<Result<ReloadReport, ClamdError>>,
+) {
+    while let Some(delay) = schedule.next_delay(SystemTime::now()) {
+        let jitter = u64::try_from(schedule.jitter.as_nanos()).unwrap_or(u64::MAX);
+        let delay = delay + Duration::from_nanos(random_up_to(jitter));
+        // The scheduler was dropped if the channel is disconnected
+        if stop.recv_timeout(delay) != Err(RecvTimeoutError::Timeout) {
+            return;
+        }
+        let result = reload_until_ready(&connection, &schedule, &stop);
+        if results.send(result).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reloads the databases and waits until ClamAV answers a ping
+fn reload_until_ready<T: TransportProtocol>(
+    connection: &T,
+    schedule: &ReloadSchedule,
+    stop: &Receiver<()>,
+) -> Result<ReloadReport, ClamdError> {
+    let started_at = SystemTime::now();
+    let start = Instant::now();
+    let response = reload(connection)?;
+    if !is_reloading(&response) {
+        return Err(ClamdError::InvalidResponse {
+            response: String::from_utf8_lossy(&response).into_owned(),
+        });
+    }
+    loop {
+        if is_available(connection) {
+            break;
+        }
+        if start.elapsed() >= schedule.ready_timeout {
+            return Err(ClamdError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "clamd was not ready within the timeout after reloading",
+            )));
+        }
+        if stop.recv_timeout(schedule.poll_interval) != Err(RecvTimeoutError::Timeout) {
+            return Err(ClamdError::Cancelled);
+        }
+    }
+    let ready_after = start.elapsed();
+    let version = get_version(connection)?;
+    Ok(ReloadReport {
+        started_at,
+        ready_after,
+        version,
+    })
+}
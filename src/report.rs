@@ -0,0 +1,406 @@
+use std::{
+    fmt, io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[cfg(all(feature = "md5", not(feature = "sha256")))]
+use md5::Digest;
+#[cfg(feature = "md5")]
+use md5::Md5;
+#[cfg(feature = "sha256")]
+use sha2::{Digest, Sha256};
+
+use super::{hooks::Hooks, wire, ScanOptions};
+
+#[cfg(any(
+    feature = "tracing",
+    feature = "metrics",
+    feature = "log",
+    feature = "prometheus"
+))]
+use super::{outcome, ClamdError, ScanOutcome};
+
+/// Timing and throughput metadata of a completed scan
+///
+/// # Example
+///
+/// ```
+/// let options = clamav_client::ScanOptions::new().on_report(|report| {
+///     if report.connect_time > std::time::Duration::from_secs(1) {
+///         eprintln!("Connecting to clamd is slow: {:?}", report.connect_time);
+///     }
+///     println!("{:.0} bytes/s", report.throughput());
+/// });
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::scan_file("README.md", clamd_tcp, options).unwrap();
+/// # assert_eq!(clamav_client::clean(&response), Ok(true));
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScanReport {
+    /// The time it took to connect to ClamAV
+    pub connect_time: Duration,
+    /// The time it took to send the data to ClamAV
+    pub streaming_time: Duration,
+    /// The time from connecting until the response was received
+    pub total_duration: Duration,
+    /// The number of bytes of the input sent to ClamAV
+    pub bytes_sent: u64,
+    /// The SHA-256 digest of the data sent to ClamAV, if requested using
+    /// `ScanOptions::sha256`
+    pub sha256: Option<[u8; 32]>,
+    /// The MD5 digest of the data sent to ClamAV, if requested using
+    /// `ScanOptions::md5`
+    pub md5: Option<[u8; 16]>,
+    /// The ID attached to the scan using `ScanOptions::correlation_id`, if any
+    pub correlation_id: Option<String>,
+}
+
+impl ScanReport {
+    /// Returns the effective upload rate in bytes per second
+    ///
+    /// Returns `0.0` if the streaming time is too short to be measured.
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.streaming_time.as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes_sent as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A shareable report callback that can be stored in [`ScanOptions`]
+#[derive(Clone)]
+pub(crate) struct ReportCallback(pub(crate) Arc<dyn Fn(&ScanReport) + Send + Sync>);
+
+impl fmt::Debug for ReportCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ReportCallback")
+    }
+}
+
+/// Measures the phases of a single scan and reports them to the callback of
+/// the scan options, if any
+///
+/// With the feature "tracing", the phases are also recorded as events of a
+/// `clamav_client::scan` span. The events contain the number of bytes sent and
+/// the verdict, but never the scanned data. With the feature "metrics", the
+/// number of scans by outcome, their duration, the bytes scanned and the
+/// failed connections are recorded using the `metrics` facade, and with the
+/// feature "prometheus" in the same metrics of `prometheus`. With the feature
+/// "log", connections, detections and errors are logged using the
+/// `log` facade.
+pub(crate) struct ReportTimer<'a> {
+    callback: Option<&'a ReportCallback>,
+    hooks: &'a Hooks,
+    correlation_id: Option<&'a str>,
+    start: Instant,
+    connected: Option<Instant>,
+    streamed: Option<Instant>,
+    bytes_sent: u64,
+    #[cfg(feature = "sha256")]
+    sha256: Option<Sha256>,
+    #[cfg(feature = "md5")]
+    md5: Option<Md5>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<'a> ReportTimer<'a> {
+    /// Starts the timer right before connecting to ClamAV
+    pub(crate) fn start(options: &'a ScanOptions) -> Self {
+        let callback = options.report.as_ref();
+        ReportTimer {
+            callback,
+            hooks: &options.hooks,
+            correlation_id: options.correlation_id.as_deref(),
+            start: Instant::now(),
+            connected: None,
+            streamed: None,
+            bytes_sent: 0,
+            #[cfg(feature = "sha256")]
+            sha256: (callback.is_some() && options.sha256).then(Sha256::new),
+            #[cfg(feature = "md5")]
+            md5: (callback.is_some() && options.md5).then(Md5::new),
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "clamav_client::scan",
+                command = "INSTREAM",
+                correlation_id = options.correlation_id.as_deref()
+            ),
+        }
+    }
+
+    /// Records the result of connecting to ClamAV and passes it through
+    pub(crate) fn connect<S>(&mut self, result: io::Result<S>) -> io::Result<S> {
+        match &result {
+            Ok(_) => self.connected(),
+            Err(err) => self.connect_failed(err),
+        }
+        result
+    }
+
+    #[cfg_attr(
+        not(any(feature = "tracing", feature = "log")),
+        allow(unused_variables)
+    )]
+    fn connect_failed(&self, err: &io::Error) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            parent: &self.span,
+            error = %err,
+            "could not connect to ClamAV"
+        );
+        #[cfg(feature = "metrics")]
+        metrics::counter!("clamav_client_connect_failures_total").increment(1);
+        #[cfg(feature = "prometheus")]
+        super::prometheus::record_connect_failure();
+        #[cfg(feature = "log")]
+        log::warn!(
+            target: "clamav_client",
+            "{}Could not connect to ClamAV: {}",
+            self.log_prefix(),
+            err
+        );
+    }
+
+    /// Records that the connection to ClamAV is established, e.g. when it is
+    /// reused within a session
+    pub(crate) fn connected(&mut self) {
+        let now = Instant::now();
+        self.connected = Some(now);
+        if let Some(on_connect) = &self.hooks.connect {
+            on_connect(now.duration_since(self.start));
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            parent: &self.span,
+            connect_time = ?now.duration_since(self.start),
+            "connected to ClamAV"
+        );
+        #[cfg(feature = "log")]
+        log::debug!(
+            target: "clamav_client",
+            "{}Connected to ClamAV in {:?}",
+            self.log_prefix(),
+            now.duration_since(self.start)
+        );
+    }
+
+    pub(crate) fn chunk_sent(&mut self, chunk: &[u8]) {
+        self.bytes_sent += chunk.len() as u64;
+        wire::chunk(chunk.len() as u64);
+        if let Some(on_chunk_sent) = &self.hooks.chunk_sent {
+            on_chunk_sent(chunk.len());
+        }
+        if let Some(stats) = &self.hooks.stats {
+            stats.chunk_sent(chunk.len() as u64);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(parent: &self.span, len = chunk.len(), "sent chunk");
+        #[cfg(feature = "sha256")]
+        if let Some(sha256) = &mut self.sha256 {
+            sha256.update(chunk);
+        }
+        #[cfg(feature = "md5")]
+        if let Some(md5) = &mut self.md5 {
+            md5.update(chunk);
+        }
+    }
+
+    /// Records a chunk that was sent without passing through userspace, which
+    /// makes the digests of the data unavailable
+    #[cfg(all(feature = "sendfile", target_os = "linux"))]
+    pub(crate) fn chunk_sent_unhashed(&mut self, len: u64) {
+        self.bytes_sent += len;
+        wire::chunk(len);
+        if let Some(on_chunk_sent) = &self.hooks.chunk_sent {
+            on_chunk_sent(len as usize);
+        }
+        if let Some(stats) = &self.hooks.stats {
+            stats.chunk_sent(len);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(parent: &self.span, len, "sent chunk");
+        #[cfg(feature = "sha256")]
+        {
+            self.sha256 = None;
+        }
+        #[cfg(feature = "md5")]
+        {
+            self.md5 = None;
+        }
+    }
+
+    pub(crate) fn streamed(&mut self) {
+        self.streamed = Some(Instant::now());
+        wire::end_of_stream();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            parent: &self.span,
+            bytes_sent = self.bytes_sent,
+            "finished streaming"
+        );
+    }
+
+    /// Finishes the timer after the response of ClamAV was received
+    pub(crate) fn finish(self, response: &[u8]) {
+        wire::response(response);
+        #[cfg(any(
+            feature = "tracing",
+            feature = "metrics",
+            feature = "log",
+            feature = "prometheus"
+        ))]
+        {
+            let result = ScanOutcome::from_response(outcome::strip_request_id(response));
+            #[cfg(feature = "tracing")]
+            self.trace_response(&result);
+            #[cfg(any(feature = "metrics", feature = "prometheus"))]
+            self.record_metrics(&result);
+            #[cfg(feature = "log")]
+            self.log_response(&result);
+        }
+        let callback = match self.callback {
+            Some(callback) => callback,
+            None => return,
+        };
+        let now = Instant::now();
+        let connected = self.connected.unwrap_or(self.start);
+        let streamed = self.streamed.unwrap_or(now);
+        (callback.0)(&ScanReport {
+            connect_time: connected.duration_since(self.start),
+            streaming_time: streamed.duration_since(connected),
+            total_duration: now.duration_since(self.start),
+            bytes_sent: self.bytes_sent,
+            #[cfg(feature = "sha256")]
+            sha256: self.sha256.map(|sha256| sha256.finalize().into()),
+            #[cfg(not(feature = "sha256"))]
+            sha256: None,
+            #[cfg(feature = "md5")]
+            md5: self.md5.map(|md5| md5.finalize().into()),
+            #[cfg(not(feature = "md5"))]
+            md5: None,
+            correlation_id: self.correlation_id.map(str::to_owned),
+        });
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace_response(&self, result: &Result<ScanOutcome, ClamdError>) {
+        match result {
+            Ok(ScanOutcome::Clean) => tracing::debug!(
+                parent: &self.span,
+                verdict = "clean",
+                bytes_sent = self.bytes_sent,
+                "received response"
+            ),
+            Ok(ScanOutcome::Infected { signature }) => tracing::debug!(
+                parent: &self.span,
+                verdict = "infected",
+                signature = %signature,
+                bytes_sent = self.bytes_sent,
+                "received response"
+            ),
+            Ok(ScanOutcome::Warning { signature }) => tracing::debug!(
+                parent: &self.span,
+                verdict = "warning",
+                signature = %signature,
+                bytes_sent = self.bytes_sent,
+                "received response"
+            ),
+            Ok(ScanOutcome::Error { message }) => tracing::debug!(
+                parent: &self.span,
+                verdict = "error",
+                message = %message,
+                bytes_sent = self.bytes_sent,
+                "received response"
+            ),
+            Err(err) => tracing::warn!(
+                parent: &self.span,
+                error = %err,
+                bytes_sent = self.bytes_sent,
+                "received invalid response"
+            ),
+        }
+    }
+
+    #[cfg(any(feature = "metrics", feature = "prometheus"))]
+    fn record_metrics(&self, result: &Result<ScanOutcome, ClamdError>) {
+        let outcome = match result {
+            Ok(ScanOutcome::Clean) => "clean",
+            Ok(ScanOutcome::Infected { .. }) => "infected",
+            Ok(ScanOutcome::Warning { .. }) => "warning",
+            Ok(ScanOutcome::Error { .. }) => "error",
+            Err(_) => "invalid",
+        };
+        let duration = self.start.elapsed();
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("clamav_client_scans_total", "outcome" => outcome).increment(1);
+            metrics::histogram!("clamav_client_scan_duration_seconds", "outcome" => outcome)
+                .record(duration);
+            metrics::counter!("clamav_client_scanned_bytes_total").increment(self.bytes_sent);
+        }
+        #[cfg(feature = "prometheus")]
+        super::prometheus::record_scan(outcome, duration, self.bytes_sent);
+    }
+
+    /// Returns the correlation ID in brackets followed by a space, to prefix
+    /// log messages with it
+    #[cfg(feature = "log")]
+    fn log_prefix(&self) -> String {
+        match self.correlation_id {
+            Some(correlation_id) => format!("[{}] ", correlation_id),
+            None => String::new(),
+        }
+    }
+
+    #[cfg(feature = "log")]
+    fn log_response(&self, result: &Result<ScanOutcome, ClamdError>) {
+        match result {
+            Ok(ScanOutcome::Clean) => log::debug!(
+                target: "clamav_client",
+                "{}Scanned {} bytes in {:?}: clean",
+                self.log_prefix(),
+                self.bytes_sent,
+                self.start.elapsed()
+            ),
+            Ok(ScanOutcome::Infected { signature }) => log::info!(
+                target: "clamav_client",
+                "{}Scanned {} bytes in {:?}: {} found",
+                self.log_prefix(),
+                self.bytes_sent,
+                self.start.elapsed(),
+                signature
+            ),
+            Ok(ScanOutcome::Warning { signature }) => log::info!(
+                target: "clamav_client",
+                "{}Scanned {} bytes in {:?}: {} found and tolerated",
+                self.log_prefix(),
+                self.bytes_sent,
+                self.start.elapsed(),
+                signature
+            ),
+            Ok(ScanOutcome::Error { message }) => log::warn!(
+                target: "clamav_client",
+                "{}Scanned {} bytes in {:?}: ClamAV reported an error: {}",
+                self.log_prefix(),
+                self.bytes_sent,
+                self.start.elapsed(),
+                message
+            ),
+            Err(err) => log::warn!(
+                target: "clamav_client",
+                "{}Scanned {} bytes in {:?}: {}",
+                self.log_prefix(),
+                self.bytes_sent,
+                self.start.elapsed(),
+                err
+            ),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! A [`reqwest-middleware`](https://docs.rs/reqwest-middleware) middleware
+//! that scans HTTP bodies
+//!
+//! [`ScanMiddleware`] scans the body of every response before it is handed to
+//! the application and, optionally, the body of every request before it is
+//! sent. Infected bodies are rejected with a [`ScanError`], wrapped in a
+//! [`reqwest_middleware::Error::Middleware`].
+//!
+//! Response bodies are streamed to ClamAV as they are received and kept in
+//! memory, limited by the maximum stream size of the [`ScanOptions`].
+//!
+//! Use the feature flag "reqwest-middleware" to enable this module.
+//!
+//! # Example
+//!
+//! ```
+//! use clamav_client::reqwest_middleware::ScanMiddleware;
+//!
+//! let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+//!     .with(ScanMiddleware::tcp("localhost:3310"))
+//!     .build();
+//! ```
+//!
+
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::Extensions;
+use reqwest::{Request, Response, ResponseBuilderExt};
+use reqwest_middleware::{Middleware, Next};
+use tokio_stream::{Stream, StreamExt};
+
+use super::{connection::Connection, ClamdError, ScanOptions, ScanOutcome};
+
+/// A [`Middleware`] that scans response bodies and, optionally, request
+/// bodies
+///
+/// Each body is scanned over a new connection to ClamAV. A response whose body
+/// is clean is passed on with the same status, headers and URL. Bodies with a
+/// [`ScanOutcome::Warning`] are accepted.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{reqwest_middleware::ScanMiddleware, ScanOptions};
+///
+/// let middleware = ScanMiddleware::tcp("localhost:3310")
+///     .options(ScanOptions::new().max_stream_size(25_000_000))
+///     .scan_requests(true);
+/// ```
+///
+#[derive(Clone)]
+pub struct ScanMiddleware {
+    connection: Connection,
+    options: ScanOptions,
+    scan_requests: bool,
+}
+
+impl ScanMiddleware {
+    /// Creates a middleware that connects to a ClamAV server via TCP
+    pub fn tcp<A: Into<String>>(host_address: A) -> Self {
+        ScanMiddleware {
+            connection: Connection::tcp(host_address),
+            options: ScanOptions::default(),
+            scan_requests: false,
+        }
+    }
+
+    /// Creates a middleware that connects to a ClamAV server via a Unix socket
+    #[cfg(unix)]
+    pub fn socket<P: Into<PathBuf>>(socket_path: P) -> Self {
+        ScanMiddleware {
+            connection: Connection::socket(socket_path),
+            options: ScanOptions::default(),
+            scan_requests: false,
+        }
+    }
+
+    /// Sets the [`ScanOptions`] used to scan bodies
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        self.options = options.into();
+        self
+    }
+
+    /// Sets whether request bodies are scanned before they are sent, `false`
+    /// by default
+    ///
+    /// Only request bodies in memory can be scanned. Requests with a streamed
+    /// body are rejected with [`ScanError::StreamedRequest`].
+    pub fn scan_requests(mut self, scan_requests: bool) -> Self {
+        self.scan_requests = scan_requests;
+        self
+    }
+
+    async fn scan<S>(&self, stream: S) -> Result<ScanOutcome, ClamdError>
+    where
+        S: Stream<Item = Result<Bytes, io::Error>>,
+    {
+        let response = self.connection.scan_stream(stream, &self.options).await?;
+        ScanOutcome::from_response(&response)
+    }
+
+    async fn scan_request(&self, request: &Request) -> Result<(), ScanError> {
+        let body = match request.body() {
+            Some(body) => body.as_bytes().ok_or(ScanError::StreamedRequest)?,
+            None => return Ok(()),
+        };
+        let body = Bytes::copy_from_slice(body);
+        let outcome = self.scan(tokio_stream::once(Ok(body))).await?;
+        check_outcome(outcome, |signature| ScanError::InfectedRequest {
+            signature,
+        })
+    }
+
+    async fn scan_response(&self, response: Response) -> reqwest_middleware::Result<Response> {
+        let url = response.url().clone();
+        let status = response.status();
+        let version = response.version();
+        let headers = response.headers().clone();
+        let extensions = response.extensions().clone();
+
+        let mut chunks = Vec::new();
+        let mut body_error = None;
+        let stream = response.bytes_stream().map(|chunk| match chunk {
+            Ok(chunk) => {
+                chunks.push(chunk.clone());
+                Ok(chunk)
+            }
+            Err(err) => {
+                let io_err = io::Error::other(err.to_string());
+                body_error = Some(err);
+                Err(io_err)
+            }
+        });
+        let result = self.scan(stream).await;
+        if let Some(err) = body_error {
+            return Err(err.into());
+        }
+        result
+            .map_err(ScanError::from)
+            .and_then(|outcome| {
+                check_outcome(outcome, |signature| ScanError::InfectedResponse {
+                    signature,
+                })
+            })
+            .map_err(reqwest_middleware::Error::middleware)?;
+
+        let mut scanned = http::Response::builder()
+            .status(status)
+            .version(version)
+            .url(url)
+            .body(Bytes::from(chunks.concat()))
+            .expect("the parts of the response are valid");
+        *scanned.headers_mut() = headers;
+        scanned.extensions_mut().extend(extensions);
+        Ok(Response::from(scanned))
+    }
+}
+
+impl fmt::Debug for ScanMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScanMiddleware")
+            .field("scan_requests", &self.scan_requests)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Middleware for ScanMiddleware {
+    async fn handle(
+        &self,
+        request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if self.scan_requests {
+            self.scan_request(&request)
+                .await
+                .map_err(reqwest_middleware::Error::middleware)?;
+        }
+        let response = next.run(request, extensions).await?;
+        self.scan_response(response).await
+    }
+}
+
+/// Returns the error for an outcome other than clean or a warning
+fn check_outcome(outcome: ScanOutcome, infected: fn(String) -> ScanError) -> Result<(), ScanError> {
+    match outcome {
+        ScanOutcome::Clean | ScanOutcome::Warning { .. } => Ok(()),
+        ScanOutcome::Infected { signature } => Err(infected(signature)),
+        ScanOutcome::Error { message } => {
+            Err(ScanError::Failed(ClamdError::ScanFailed { message }))
+        }
+    }
+}
+
+/// The reason a [`ScanMiddleware`] rejected a request or a response
+///
+/// The error is wrapped in a [`reqwest_middleware::Error::Middleware`], from
+/// which it can be extracted using [`ScanError::from_error`].
+///
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ScanError {
+    /// A virus was found in the body of the response
+    InfectedResponse {
+        /// The name of the signature that matched
+        signature: String,
+    },
+    /// A virus was found in the body of the request, which was not sent
+    InfectedRequest {
+        /// The name of the signature that matched
+        signature: String,
+    },
+    /// The body of the request is a stream, which cannot be scanned before it
+    /// is sent
+    StreamedRequest,
+    /// The body could not be scanned, e.g. because ClamAV is not available or
+    /// the body exceeds the maximum stream size
+    Failed(ClamdError),
+}
+
+impl ScanError {
+    /// Returns the [`ScanError`] wrapped in an error of a client with
+    /// middleware, or `None` if the request failed for another reason
+    pub fn from_error(err: &reqwest_middleware::Error) -> Option<&Self> {
+        match err {
+            reqwest_middleware::Error::Middleware(err) => err.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if a virus was found in the request or the response
+    pub fn is_infected(&self) -> bool {
+        matches!(
+            self,
+            ScanError::InfectedResponse { .. } | ScanError::InfectedRequest { .. }
+        )
+    }
+}
+
+impl From<ClamdError> for ScanError {
+    fn from(err: ClamdError) -> Self {
+        ScanError::Failed(err)
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::InfectedResponse { signature } => {
+                write!(f, "virus found in response body: {}", signature)
+            }
+            ScanError::InfectedRequest { signature } => {
+                write!(f, "virus found in request body: {}", signature)
+            }
+            ScanError::StreamedRequest => f.write_str("streamed request body cannot be scanned"),
+            ScanError::Failed(err) => write!(f, "body could not be scanned: {}", err),
+        }
+    }
+}
+
+impl Error for ScanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScanError::Failed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::{connect_timeout_any, TransportProtocol};
+
+/// Resolves host names into socket addresses for [`ResolvingTcp`]
+///
+/// Implement this trait to control how the host name of a ClamAV server is
+/// resolved, e.g. to use a specific name server, a cache or a fixed address in
+/// tests.
+///
+/// # Example
+///
+/// ```
+/// use std::{io, net::SocketAddr};
+///
+/// struct Localhost;
+///
+/// impl clamav_client::Resolve for Localhost {
+///     fn resolve(&self, _host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+///         Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+///     }
+/// }
+///
+/// let clamd_tcp = clamav_client::ResolvingTcp::new("clamd.internal", 3310, Localhost);
+/// let response = clamav_client::ping(clamd_tcp).unwrap();
+/// assert_eq!(&response, clamav_client::PONG);
+/// ```
+///
+pub trait Resolve {
+    /// Returns the socket addresses of `host` with `port`, in the order in
+    /// which they are tried
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+impl<R: Resolve + ?Sized> Resolve for &R {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        (**self).resolve(host, port)
+    }
+}
+
+/// Resolves host names with the resolver of the operating system, like
+/// [`Tcp`](crate::Tcp) does
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs()?.collect())
+    }
+}
+
+/// Caches the addresses resolved by another resolver for a fixed time to live
+///
+/// A [`ResolvingTcp`] resolves the host name for each connection, so scanning
+/// many small inputs would otherwise resolve the same name over and over. The
+/// cached addresses are resolved again once they expire, so that changes to
+/// the DNS records, e.g. for a failover, take effect after at most `ttl`.
+/// Failed lookups are not cached.
+///
+/// `CachingResolver` implements [`Resolve`] and, if the wrapped resolver does,
+/// the `Resolve` traits of the asynchronous runtimes.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let resolver = clamav_client::CachingResolver::new(
+///     clamav_client::SystemResolver,
+///     Duration::from_secs(30),
+/// );
+/// let clamd_tcp = clamav_client::ResolvingTcp::new("localhost", 3310, &resolver);
+/// for _ in 0..3 {
+///     // Only the first ping resolves `localhost`
+///     clamav_client::ping(&clamd_tcp).unwrap();
+/// }
+/// ```
+///
+#[derive(Debug)]
+pub struct CachingResolver<R> {
+    pub(crate) resolver: R,
+    ttl: Duration,
+    cache: Mutex<HashMap<(String, u16), CachedAddresses>>,
+}
+
+#[derive(Debug)]
+struct CachedAddresses {
+    addresses: Vec<SocketAddr>,
+    expires_at: Instant,
+}
+
+impl<R> CachingResolver<R> {
+    /// Creates a resolver that caches the addresses resolved by `resolver` for
+    /// `ttl`
+    pub fn new(resolver: R, ttl: Duration) -> Self {
+        CachingResolver {
+            resolver,
+            ttl,
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Removes all cached addresses, so that the next lookup of each host name
+    /// uses the wrapped resolver
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Returns the cached addresses of `host` with `port` unless they expired
+    pub(crate) fn cached(&self, host: &str, port: u16) -> Option<Vec<SocketAddr>> {
+        let mut cache = self.cache.lock().unwrap();
+        let key = (host.to_owned(), port);
+        match cache.get(&key) {
+            Some(cached) if cached.expires_at > Instant::now() => Some(cached.addresses.clone()),
+            Some(_) => {
+                cache.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Caches the addresses of `host` with `port`, unless there are none
+    pub(crate) fn store(&self, host: &str, port: u16, addresses: &[SocketAddr]) {
+        if addresses.is_empty() {
+            return;
+        }
+        let cached = CachedAddresses {
+            addresses: addresses.to_vec(),
+            expires_at: Instant::now() + self.ttl,
+        };
+        self.cache
+            .lock()
+            .unwrap()
+            .insert((host.to_owned(), port), cached);
+    }
+}
+
+impl<R: Resolve> Resolve for CachingResolver<R> {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if let Some(addresses) = self.cached(host, port) {
+            return Ok(addresses);
+        }
+        let addresses = self.resolver.resolve(host, port)?;
+        self.store(host, port, &addresses);
+        Ok(addresses)
+    }
+}
+
+/// Bounds the time that another resolver may take to resolve a host name
+///
+/// The resolver of the operating system has no timeout of its own, so an
+/// unresponsive name server delays each connection by the timeouts and retries
+/// configured in `/etc/resolv.conf`. A lookup that does not finish within
+/// `timeout` fails with [`TimedOut`](io::ErrorKind::TimedOut) and the phase
+/// [`ErrorPhase::Resolve`](crate::ErrorPhase::Resolve) instead. The
+/// `SystemResolver`s of the runtimes resolve on a blocking thread pool, so an
+/// abandoned lookup keeps one of its threads busy until it finishes on its
+/// own.
+///
+/// `TimeoutResolver` implements the `Resolve` traits of the asynchronous
+/// runtimes if the wrapped resolver does. Blocking lookups cannot be abandoned,
+/// so it does not implement [`Resolve`]. See
+/// [`tokio::ResolvingTcp`](crate::tokio::ResolvingTcp) for an example.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TimeoutResolver<R> {
+    pub(crate) resolver: R,
+    pub(crate) timeout: Duration,
+}
+
+impl<R> TimeoutResolver<R> {
+    /// Creates a resolver that fails lookups of `resolver` that take longer
+    /// than `timeout`
+    pub fn new(resolver: R, timeout: Duration) -> Self {
+        TimeoutResolver { resolver, timeout }
+    }
+
+    /// Returns the error of a lookup of `host` that timed out
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub(crate) fn timed_out<T>(&self, host: &str) -> io::Result<T> {
+        use crate::{context::ResultExt, ErrorPhase};
+
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("the lookup did not finish within {:?}", self.timeout),
+        ))
+        .phase_of(ErrorPhase::Resolve, host)
+    }
+}
+
+/// Resolves host names with [Hickory DNS](https://github.com/hickory-dns/hickory-dns)
+/// instead of the resolver of the operating system
+///
+/// The lookups block the current thread. Use
+/// [`tokio::HickoryResolver`](crate::tokio::HickoryResolver) with Tokio.
+///
+/// Use the feature flag "hickory-dns" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// let resolver = clamav_client::HickoryResolver::from_system_conf().unwrap();
+/// let clamd_tcp = clamav_client::ResolvingTcp::new("localhost", 3310, resolver);
+/// let response = clamav_client::ping(clamd_tcp).unwrap();
+/// assert_eq!(&response, clamav_client::PONG);
+/// ```
+///
+#[cfg(feature = "hickory-dns")]
+pub struct HickoryResolver {
+    pub(crate) resolver: hickory_resolver::Resolver,
+}
+
+#[cfg(feature = "hickory-dns")]
+impl HickoryResolver {
+    /// Creates a resolver with the system configuration of DNS, e.g. from
+    /// `/etc/resolv.conf`
+    pub fn from_system_conf() -> io::Result<Self> {
+        hickory_resolver::Resolver::from_system_conf().map(Self::from)
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl From<hickory_resolver::Resolver> for HickoryResolver {
+    fn from(resolver: hickory_resolver::Resolver) -> Self {
+        HickoryResolver { resolver }
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl std::fmt::Debug for HickoryResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HickoryResolver").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl Resolve for HickoryResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let lookup = self.resolver.lookup_ip(host).map_err(io::Error::other)?;
+        Ok(lookup.iter().map(|ip| SocketAddr::new(ip, port)).collect())
+    }
+}
+
+/// Use a TCP connection to communicate with a ClamAV server whose host name is
+/// resolved with a [`Resolve`] implementation
+///
+/// The host name is resolved again for each connection, and the addresses are
+/// tried in order until a connection succeeds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvingTcp<H: AsRef<str>, R: Resolve> {
+    /// The host name or IP address of the ClamAV server
+    pub host: H,
+    /// The port of the ClamAV server
+    pub port: u16,
+    /// The resolver of the host name
+    pub resolver: R,
+}
+
+impl<H: AsRef<str>, R: Resolve> ResolvingTcp<H, R> {
+    /// Creates a TCP connection to `host` and `port` that resolves `host` with
+    /// `resolver`
+    pub fn new(host: H, port: u16, resolver: R) -> Self {
+        ResolvingTcp {
+            host,
+            port,
+            resolver,
+        }
+    }
+}
+
+impl<H: AsRef<str>, R: Resolve> TransportProtocol for ResolvingTcp<H, R> {
+    type Stream = TcpStream;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        let addresses = self.resolver.resolve(self.host.as_ref(), self.port)?;
+        TcpStream::connect(&addresses[..])
+    }
+
+    fn connect_timeout(&self, timeout: Duration) -> io::Result<Self::Stream> {
+        let addresses = self.resolver.resolve(self.host.as_ref(), self.port)?;
+        connect_timeout_any(addresses, timeout)
+    }
+}
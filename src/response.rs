@@ -0,0 +1,82 @@
+use super::ClamdError;
+
+/// Returns `response` without its trailing NUL and newline terminators
+///
+/// ClamAV terminates its responses with NUL for the `z` commands this crate
+/// sends and with a newline for the `n` commands.
+pub(crate) fn trim(response: &[u8]) -> &[u8] {
+    let end = response
+        .iter()
+        .rposition(|&byte| byte != b'\0' && byte != b'\n')
+        .map_or(0, |last| last + 1);
+    &response[..end]
+}
+
+/// Checks whether a response is ClamAV's answer to a PING request
+///
+/// # Example
+///
+/// ```
+/// assert!(clamav_client::is_pong(b"PONG\0"));
+/// assert!(clamav_client::is_pong(b"PONG\n"));
+/// assert!(!clamav_client::is_pong(b"PONGPONG\0"));
+/// ```
+///
+pub fn is_pong(response: &[u8]) -> bool {
+    trim(response) == b"PONG"
+}
+
+/// Returns an error unless a response is ClamAV's answer to a PING request
+pub(crate) fn expect_pong(response: &[u8]) -> Result<(), ClamdError> {
+    if is_pong(response) {
+        return Ok(());
+    }
+    Err(ClamdError::InvalidResponse {
+        response: String::from_utf8_lossy(trim(response)).into_owned(),
+    })
+}
+
+/// Checks whether a response is ClamAV's answer to a RELOAD request
+///
+/// # Example
+///
+/// ```
+/// assert!(clamav_client::is_reloading(b"RELOADING\0"));
+/// assert!(!clamav_client::is_reloading(b"UNKNOWN COMMAND\0"));
+/// ```
+///
+pub fn is_reloading(response: &[u8]) -> bool {
+    trim(response) == b"RELOADING"
+}
+
+/// Checks whether a scan response reports that no virus was found, e.g.
+/// `stream: OK`
+///
+/// Unlike [`clean`](crate::clean), this does not fail on invalid UTF-8 and
+/// only accepts `OK` at the end of the response.
+///
+/// # Example
+///
+/// ```
+/// assert!(clamav_client::is_ok(b"stream: OK\0"));
+/// assert!(!clamav_client::is_ok(b"stream: Win.Test.EICAR_HDB-1 FOUND\0"));
+/// ```
+///
+pub fn is_ok(response: &[u8]) -> bool {
+    let response = trim(response);
+    response == b"OK" || response.ends_with(b": OK")
+}
+
+/// Checks whether a response reports an error, e.g.
+/// `INSTREAM size limit exceeded. ERROR`
+///
+/// # Example
+///
+/// ```
+/// assert!(clamav_client::is_error(b"INSTREAM size limit exceeded. ERROR\0"));
+/// assert!(!clamav_client::is_error(b"stream: OK\0"));
+/// ```
+///
+pub fn is_error(response: &[u8]) -> bool {
+    trim(response).ends_with(b" ERROR")
+}
@@ -0,0 +1,453 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    scan_directory, timestamp::civil_from_days, ClamdError, DirectoryScanOptions, ScanOptions,
+    ScanResults, TransportProtocol,
+};
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+/// How many days ahead the next time of a cron expression is searched for,
+/// enough to reach the next 29 February even across a century
+const CRON_SEARCH_DAYS: u64 = 8 * 366;
+
+type CompleteCallback = Arc<dyn Fn(&ScheduledScanReport) + Send + Sync>;
+
+/// A scan of one or more directories that [`schedule_scans`] runs
+/// repeatedly, either at a fixed interval or at the times of a cron
+/// expression
+///
+/// Use the feature flag "schedule" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// // Every night at 02:30 UTC
+/// let nightly = clamav_client::ScheduledScan::cron("uploads", "30 2 * * *")
+///     .unwrap()
+///     .path("/mnt/uploads")
+///     .directory_options(clamav_client::DirectoryScanOptions::new().max_file_size(100_000_000));
+///
+/// // Every 15 minutes
+/// let frequent = clamav_client::ScheduledScan::every("inbox", Duration::from_secs(15 * 60))
+///     .path("/var/spool/inbox")
+///     .on_complete(|report| println!("{}: {}", report.name, report.results.summary));
+/// ```
+///
+#[derive(Clone)]
+pub struct ScheduledScan {
+    name: String,
+    recurrence: Recurrence,
+    paths: Vec<PathBuf>,
+    options: ScanOptions,
+    directory_options: DirectoryScanOptions,
+    on_complete: Option<CompleteCallback>,
+}
+
+#[derive(Debug, Clone)]
+enum Recurrence {
+    Every(Duration),
+    Cron(CronExpression),
+}
+
+impl ScheduledScan {
+    /// Creates a scan that runs every `interval`, starting `interval` after
+    /// the scheduler starts
+    pub fn every<S: Into<String>>(name: S, interval: Duration) -> Self {
+        Self::new(name.into(), Recurrence::Every(interval))
+    }
+
+    /// Creates a scan that runs at the times of a cron expression in UTC
+    ///
+    /// The expression has the five fields minute (0-59), hour (0-23), day of
+    /// the month (1-31), month (1-12) and day of the week (0-7, where 0 and 7
+    /// are Sunday). Each field is `*`, a number, a range like `1-5` or a list
+    /// of them like `0,30`, optionally followed by a step like `*/15`. Like
+    /// in cron, a time matches either day field if both are restricted. The
+    /// shortcuts `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are
+    /// supported as well.
+    ///
+    /// Returns [`ClamdError::InvalidOptions`] if the expression is invalid.
+    pub fn cron<S: Into<String>>(name: S, expression: &str) -> Result<Self, ClamdError> {
+        let expression = CronExpression::parse(expression)?;
+        Ok(Self::new(name.into(), Recurrence::Cron(expression)))
+    }
+
+    fn new(name: String, recurrence: Recurrence) -> Self {
+        ScheduledScan {
+            name,
+            recurrence,
+            paths: Vec::new(),
+            options: ScanOptions::default(),
+            directory_options: DirectoryScanOptions::default(),
+            on_complete: None,
+        }
+    }
+
+    /// Adds a directory or file to scan
+    pub fn path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Sets the [`ScanOptions`] of the scans
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        self.options = options.into();
+        self
+    }
+
+    /// Sets the [`DirectoryScanOptions`] that control which files are scanned
+    pub fn directory_options(mut self, directory_options: DirectoryScanOptions) -> Self {
+        self.directory_options = directory_options;
+        self
+    }
+
+    /// Calls `on_complete` with the report of each run instead of sending it
+    /// to the [`ScanScheduler`]
+    ///
+    /// The callback is called on the thread of the scheduler, so the next
+    /// scans wait until it returns.
+    pub fn on_complete<F>(mut self, on_complete: F) -> Self
+    where
+        F: Fn(&ScheduledScanReport) + Send + Sync + 'static,
+    {
+        self.on_complete = Some(Arc::new(on_complete));
+        self
+    }
+
+    /// Returns the time of the next run after `time`, or `None` if the cron
+    /// expression never matches, e.g. for 31 February
+    pub fn next_run(&self, time: SystemTime) -> Option<SystemTime> {
+        match &self.recurrence {
+            Recurrence::Every(interval) => time.checked_add(*interval),
+            Recurrence::Cron(expression) => expression.next_after(time),
+        }
+    }
+}
+
+impl fmt::Debug for ScheduledScan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScheduledScan")
+            .field("name", &self.name)
+            .field("recurrence", &self.recurrence)
+            .field("paths", &self.paths)
+            .field("options", &self.options)
+            .field("directory_options", &self.directory_options)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A parsed cron expression, with a bit for each value that matches a field
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether both day fields are restricted, in which case a day matches
+    /// if either of them matches
+    either_day: bool,
+}
+
+impl CronExpression {
+    fn parse(expression: &str) -> Result<Self, ClamdError> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(ClamdError::InvalidOptions {
+                reason: "a cron expression must have five fields",
+            });
+        }
+        let field = |index: usize, min: u64, max: u64, reason: &'static str| {
+            parse_field(fields[index], min, max).ok_or(ClamdError::InvalidOptions { reason })
+        };
+        let mut days_of_week = field(
+            4,
+            0,
+            7,
+            "the day of the week of the cron expression is invalid",
+        )?;
+        // Both 0 and 7 are Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+        Ok(CronExpression {
+            minutes: field(0, 0, 59, "the minute of the cron expression is invalid")?,
+            hours: field(1, 0, 23, "the hour of the cron expression is invalid")?,
+            days_of_month: field(
+                2,
+                1,
+                31,
+                "the day of the month of the cron expression is invalid",
+            )?,
+            months: field(3, 1, 12, "the month of the cron expression is invalid")?,
+            days_of_week,
+            either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+        })
+    }
+
+    /// Returns the first matching minute after `time`
+    fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let minutes = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_secs()
+            / 60
+            + 1;
+        let first_day = minutes / MINUTES_PER_DAY;
+        (first_day..first_day + CRON_SEARCH_DAYS)
+            .filter(|&day| self.matches_day(day))
+            .find_map(|day| {
+                let start = if day == first_day {
+                    minutes % MINUTES_PER_DAY
+                } else {
+                    0
+                };
+                (start..MINUTES_PER_DAY)
+                    .find(|minute| bit(self.hours, minute / 60) && bit(self.minutes, minute % 60))
+                    .map(|minute| day * MINUTES_PER_DAY + minute)
+            })
+            .map(|minutes| UNIX_EPOCH + Duration::from_secs(minutes * 60))
+    }
+
+    /// Returns `true` if the day fields match the given day since the epoch
+    fn matches_day(&self, day: u64) -> bool {
+        let (_, month, day_of_month) = civil_from_days(day);
+        if !bit(self.months, month) {
+            return false;
+        }
+        // 1 January 1970 was a Thursday
+        let day_of_month = bit(self.days_of_month, day_of_month);
+        let day_of_week = bit(self.days_of_week, (day + 4) % 7);
+        if self.either_day {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+}
+
+/// Parses a field of a cron expression into a bit for each matching value
+fn parse_field(field: &str, min: u64, max: u64) -> Option<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().ok().filter(|&step| step > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+                // A single value with a step runs from the value to the end
+                None if part.contains('/') => (range.parse().ok()?, max),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+fn bit(bits: u64, value: u64) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// The results of a run of a [`ScheduledScan`]
+///
+/// Use the feature flag "schedule" to enable this type.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ScheduledScanReport {
+    /// The name of the scheduled scan
+    pub name: String,
+    /// The time at which the run started
+    pub started_at: SystemTime,
+    /// The results of all files scanned in the run and their summary
+    pub results: ScanResults,
+}
+
+/// Runs [`ScheduledScan`]s in the background
+///
+/// The reports of scans without an
+/// [`on_complete`](ScheduledScan::on_complete) callback can be received using
+/// [`recv`](Self::recv), [`recv_timeout`](Self::recv_timeout) or by iterating
+/// over the scheduler. Dropping the scheduler stops it once the current run,
+/// if any, has completed.
+///
+/// Use the feature flag "schedule" to enable this type.
+pub struct ScanScheduler {
+    _stop: Sender<()>,
+    reports: Receiver<ScheduledScanReport>,
+}
+
+impl ScanScheduler {
+    /// Blocks until the next run has completed
+    ///
+    /// Returns [`None`] if the background thread has stopped.
+    pub fn recv(&self) -> Option<ScheduledScanReport> {
+        self.reports.recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next run to complete
+    ///
+    /// Returns [`None`] if no run has completed within `timeout` or the
+    /// background thread has stopped.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<ScheduledScanReport> {
+        self.reports.recv_timeout(timeout).ok()
+    }
+
+    /// Returns the report of a run that has already completed, if any, without
+    /// blocking
+    pub fn try_recv(&self) -> Option<ScheduledScanReport> {
+        self.reports.try_recv().ok()
+    }
+}
+
+impl Iterator for ScanScheduler {
+    type Item = ScheduledScanReport;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+/// Runs scans of directories on a schedule
+///
+/// This function starts a background thread that scans the paths of each
+/// [`ScheduledScan`] with [`scan_directory`] whenever it is due, and delivers
+/// the results of each run together with their summary to the callback of
+/// the scan or through the returned [`ScanScheduler`]. Scans run one after
+/// another, and runs that are missed while another scan is running are
+/// skipped, so that the next run of an interval starts `interval` after the
+/// previous one completed.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `scans`: The scans to run
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let scan = clamav_client::ScheduledScan::every("test data", Duration::from_millis(10))
+///     .path("tests/data");
+/// let scheduler = clamav_client::schedule_scans(clamd_tcp, [scan]);
+///
+/// let report = scheduler.recv().unwrap();
+/// println!("{}: {}", report.name, report.results.summary);
+/// # assert_eq!(report.results.summary.infected, 1);
+/// ```
+///
+pub fn schedule_scans<T, I>(connection: T, scans: I) -> ScanScheduler
+where
+    T: TransportProtocol + Send + 'static,
+    I: IntoIterator<Item = ScheduledScan>,
+{
+    let scans = scans.into_iter().collect();
+    let (stop_sender, stop) = mpsc::channel();
+    let (reports_sender, reports) = mpsc::channel();
+    thread::spawn(move || run_schedule(connection, scans, stop, reports_sender));
+    ScanScheduler {
+        _stop: stop_sender,
+        reports,
+    }
+}
+
+fn run_schedule<T: TransportProtocol>(
+    connection: T,
+    scans: Vec<ScheduledScan>,
+    stop: Receiver<()>,
+    reports: Sender<ScheduledScanReport>,
+) {
+    let now = SystemTime::now();
+    let mut next_runs: Vec<Option<SystemTime>> =
+        scans.iter().map(|scan| scan.next_run(now)).collect();
+    while let Some(next_run) = next_runs.iter().flatten().min().copied() {
+        let delay = next_run
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO);
+        // The scheduler was dropped if the channel is disconnected
+        if stop.recv_timeout(delay) != Err(RecvTimeoutError::Timeout) {
+            return;
+        }
+        for (scan, next_run) in scans.iter().zip(&mut next_runs) {
+            if !matches!(*next_run, Some(time) if time <= SystemTime::now()) {
+                continue;
+            }
+            let report = run_scan(&connection, scan);
+            *next_run = scan.next_run(SystemTime::now());
+            match &scan.on_complete {
+                Some(on_complete) => on_complete(&report),
+                None => {
+                    if reports.send(report).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Scans all paths of a scheduled scan and merges their results
+fn run_scan<T: TransportProtocol>(connection: &T, scan: &ScheduledScan) -> ScheduledScanReport {
+    let started_at = SystemTime::now();
+    let mut results = ScanResults {
+        results: Vec::new(),
+        summary: Default::default(),
+    };
+    for path in &scan.paths {
+        let path_results = scan_directory(
+            path,
+            connection,
+            scan.options.clone(),
+            scan.directory_options.clone(),
+        );
+        let (summary, path_summary) = (&mut results.summary, path_results.summary);
+        summary.files_scanned += path_summary.files_scanned;
+        summary.clean += path_summary.clean;
+        summary.infected += path_summary.infected;
+        summary.warnings += path_summary.warnings;
+        summary.errored += path_summary.errored;
+        summary.bytes_scanned += path_summary.bytes_scanned;
+        summary.elapsed += path_summary.elapsed;
+        for (signature, count) in path_summary.signatures {
+            *summary.signatures.entry(signature).or_insert(0) += count;
+        }
+        results.results.extend(path_results.results);
+    }
+    ScheduledScanReport {
+        name: scan.name.clone(),
+        started_at,
+        results,
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    os::unix::io::AsRawFd,
+    path::Path,
+    thread,
+};
+
+use super::{
+    chunk_header, context::ResultExt, protocol::Instream, read_response, report::ReportTimer,
+    ErrorPhase, IoResult, ScanOptions, TransportProtocol, END_OF_STREAM, INSTREAM,
+};
+
+fn send_file_chunk<S: AsRawFd>(file: &File, stream: &S, offset: u64, len: u64) -> io::Result<()> {
+    let mut offset = libc::off_t::try_from(offset)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file offset out of range"))?;
+    let mut remaining = len as usize;
+    while remaining > 0 {
+        // SAFETY: Both file descriptors are valid for the duration of the call
+        // and `offset` points to a valid `off_t`
+        let sent =
+            unsafe { libc::sendfile(stream.as_raw_fd(), file.as_raw_fd(), &mut offset, remaining) };
+        if sent < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if sent == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file was truncated while scanning",
+            ));
+        }
+        remaining -= sent as usize;
+    }
+    Ok(())
+}
+
+/// Scans a file for viruses without copying its contents through userspace
+///
+/// This function works like [`scan_file`](crate::scan_file), but moves the file
+/// chunks directly from the file into the socket using `sendfile(2)`. Only the
+/// 4-byte chunk headers are written by the client itself. The file is scanned
+/// up to the size it had when it was opened.
+///
+/// Use the feature flag "sendfile" to enable this function (Linux only).
+///
+/// # Arguments
+///
+/// * `file_path`: The path to the file to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the file exceeds the configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded)
+/// without connecting to the server.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::scan_file_zero_copy("README.md", clamd_tcp, None).unwrap();
+/// # assert_eq!(clamav_client::clean(&response), Ok(true));
+/// ```
+///
+pub fn scan_file_zero_copy<P: AsRef<Path>, T: TransportProtocol, O: Into<ScanOptions>>(
+    file_path: P,
+    connection: T,
+    options: O,
+) -> IoResult
+where
+    T::Stream: AsRawFd,
+{
+    let options = options.into();
+    let file_path = file_path.as_ref();
+    let file = File::open(file_path).phase_of(ErrorPhase::Input, file_path.display())?;
+    let file_size = file
+        .metadata()
+        .phase_of(ErrorPhase::Input, file_path.display())?
+        .len();
+    options.check_input_size(file_size)?;
+    options.check_archive(&file, file_size)?;
+    let mut instream = Instream::start(&options, Some(file_size))?;
+    let chunk_size = instream.chunk_size() as u64;
+    let mut report = ReportTimer::start(&options);
+    let mut stream = report.connect(connection.connect())?;
+
+    stream.write_all(INSTREAM).phase(ErrorPhase::Stream)?;
+    instream.started();
+
+    let mut offset = 0;
+    while offset < file_size {
+        let len = chunk_size.min(file_size - offset);
+        if let Some(delay) = instream.delay(len as usize) {
+            thread::sleep(delay);
+        }
+        stream
+            .write_all(&chunk_header(len as usize))
+            .phase(ErrorPhase::Stream)?;
+        stream.flush().phase(ErrorPhase::Stream)?;
+        send_file_chunk(&file, &stream, offset, len).phase(ErrorPhase::Stream)?;
+        instream.chunk_sent_unhashed(len, &mut report);
+        offset += len;
+    }
+    stream.write_all(END_OF_STREAM).phase(ErrorPhase::Stream)?;
+    stream.flush().phase(ErrorPhase::Stream)?;
+    instream.finished(&mut report);
+
+    let mut response = Vec::new();
+    read_response(&mut stream, &mut response, &options.response_buffer)
+        .phase(ErrorPhase::Response)?;
+    report.finish(&response);
+    Ok(response)
+}
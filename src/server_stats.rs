@@ -0,0 +1,277 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{get_stats, ClamdError, TransportProtocol};
+
+/// The load of ClamAV, parsed from its response to the STATS command
+///
+/// The memory statistics are `None` if ClamAV does not report them, e.g. if
+/// it was not built with glibc.
+///
+/// # Example
+///
+/// ```
+/// let response = b"POOLS: 1\n\nSTATE: VALID PRIMARY\n\
+///     THREADS: live 3  idle 1 max 10 idle-timeout 30\nQUEUE: 2 items\n\
+///     \tSTATS 0.000048\n\tINSTREAM 1.254327\n\
+///     MEMSTATS: heap 9.082M mmap 0.000M used 6.902M free 2.184M releasable 0.129M \
+///     pools 1 pools_used 565.979M pools_total 565.999M\nEND\0";
+/// let stats = clamav_client::ServerStats::from_response(response).unwrap();
+/// assert_eq!(stats.queue_length, 2);
+/// assert_eq!(stats.busy_threads(), 2);
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ServerStats {
+    /// The number of thread pools
+    pub pools: u32,
+    /// The number of scan requests waiting for a thread
+    pub queue_length: u32,
+    /// The number of threads, including idle threads
+    pub live_threads: u32,
+    /// The number of threads waiting for a scan request
+    pub idle_threads: u32,
+    /// The maximum number of threads, i.e. `MaxThreads`
+    pub max_threads: u32,
+    /// The size of the heap in bytes
+    pub heap_bytes: Option<u64>,
+    /// The memory allocated with `mmap` in bytes
+    pub mmap_bytes: Option<u64>,
+    /// The memory in use in bytes
+    pub used_bytes: Option<u64>,
+    /// The memory used by the signature databases in bytes
+    pub pools_used_bytes: Option<u64>,
+}
+
+impl ServerStats {
+    /// Parses the response of ClamAV to the STATS command
+    ///
+    /// The threads of several pools are added up. Fails with
+    /// [`ClamdError::InvalidResponse`] if the response does not contain the
+    /// threads or the queue.
+    pub fn from_response(stats_response: &[u8]) -> Result<Self, ClamdError> {
+        let response = String::from_utf8_lossy(stats_response);
+        let response = response.trim_end_matches(['\0', '\n']);
+        let invalid = || ClamdError::InvalidResponse {
+            response: response.to_owned(),
+        };
+        let mut stats = ServerStats {
+            pools: 0,
+            queue_length: 0,
+            live_threads: 0,
+            idle_threads: 0,
+            max_threads: 0,
+            heap_bytes: None,
+            mmap_bytes: None,
+            used_bytes: None,
+            pools_used_bytes: None,
+        };
+        let (mut threads, mut queue) = (false, false);
+        for line in response.lines() {
+            let (key, value) = match line.split_once(": ") {
+                Some(field) => field,
+                None => continue,
+            };
+            match key {
+                "POOLS" => stats.pools = value.trim().parse().map_err(|_| invalid())?,
+                "THREADS" => {
+                    for (name, count) in pairs(value) {
+                        let count: u32 = count.parse().map_err(|_| invalid())?;
+                        match name {
+                            "live" => stats.live_threads += count,
+                            "idle" => stats.idle_threads += count,
+                            "max" => stats.max_threads += count,
+                            _ => {}
+                        }
+                    }
+                    threads = true;
+                }
+                "QUEUE" => {
+                    let items = value.split_whitespace().next().ok_or_else(invalid)?;
+                    stats.queue_length += items.parse::<u32>().map_err(|_| invalid())?;
+                    queue = true;
+                }
+                "MEMSTATS" => {
+                    for (name, size) in pairs(value) {
+                        let size = parse_megabytes(size);
+                        match name {
+                            "heap" => stats.heap_bytes = size,
+                            "mmap" => stats.mmap_bytes = size,
+                            "used" => stats.used_bytes = size,
+                            "pools_used" => stats.pools_used_bytes = size,
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !threads || !queue {
+            return Err(invalid());
+        }
+        Ok(stats)
+    }
+
+    /// Returns the number of threads that are scanning
+    pub fn busy_threads(&self) -> u32 {
+        self.live_threads.saturating_sub(self.idle_threads)
+    }
+}
+
+/// Splits `name value name value ...` into pairs
+fn pairs(value: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut words = value.split_whitespace();
+    std::iter::from_fn(move || Some((words.next()?, words.next()?)))
+}
+
+/// Parses a size such as `9.082M`, or `N/A` as `None`
+fn parse_megabytes(size: &str) -> Option<u64> {
+    let megabytes: f64 = size.strip_suffix('M')?.parse().ok()?;
+    Some((megabytes * 1024.0 * 1024.0).round() as u64)
+}
+
+/// Polls the STATS of ClamAV in the background, started by [`poll_stats`]
+///
+/// The handle keeps the stats of the last successful poll. Dropping it stops
+/// the background thread.
+///
+pub struct StatsPoller {
+    _stop: Sender<()>,
+    polled: Arc<Mutex<PolledStats>>,
+}
+
+#[derive(Default)]
+struct PolledStats {
+    stats: Option<(ServerStats, Instant)>,
+    last_error: Option<String>,
+}
+
+impl StatsPoller {
+    /// Returns the stats of the last successful poll, or `None` if no poll has
+    /// succeeded yet
+    pub fn latest(&self) -> Option<ServerStats> {
+        self.polled().stats.as_ref().map(|(stats, _)| stats.clone())
+    }
+
+    /// Returns the time since the last successful poll, e.g. to ignore stale
+    /// stats while ClamAV does not answer
+    pub fn age(&self) -> Option<Duration> {
+        self.polled().stats.as_ref().map(|(_, at)| at.elapsed())
+    }
+
+    /// Returns the message of the error of the last poll, or `None` if it
+    /// succeeded
+    pub fn last_error(&self) -> Option<String> {
+        self.polled().last_error.clone()
+    }
+
+    fn polled(&self) -> std::sync::MutexGuard<'_, PolledStats> {
+        self.polled.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Polls the STATS of ClamAV at a fixed interval
+///
+/// This function starts a background thread that sends the STATS command to
+/// ClamAV right away and then every `interval`, e.g. to scale the number of
+/// scanning workers with the length of the queue of ClamAV. The parsed
+/// [`ServerStats`] of the last successful poll are available through the
+/// returned [`StatsPoller`]. A failed poll does not stop the poller.
+///
+/// With the `metrics` feature, each successful poll also sets the gauges
+/// `clamav_client_server_queue_length`, `clamav_client_server_threads` with
+/// the label `state` (`live`, `idle` or `max`) and, if reported,
+/// `clamav_client_server_memory_bytes` with the label `kind` (`heap`, `mmap`,
+/// `used` or `pools_used`).
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `interval`: The time between two polls
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let poller = clamav_client::poll_stats(clamd_tcp, Duration::from_secs(10));
+///
+/// # std::thread::sleep(Duration::from_millis(500));
+/// if let Some(stats) = poller.latest() {
+///     println!("{} requests queued", stats.queue_length);
+/// }
+/// # assert!(poller.latest().is_some());
+/// ```
+///
+pub fn poll_stats<T>(connection: T, interval: Duration) -> StatsPoller
+where
+    T: TransportProtocol + Send + 'static,
+{
+    let (stop_sender, stop) = mpsc::channel();
+    let polled = Arc::new(Mutex::new(PolledStats::default()));
+    let shared = Arc::clone(&polled);
+    thread::spawn(move || run_poller(connection, interval, stop, shared));
+    StatsPoller {
+        _stop: stop_sender,
+        polled,
+    }
+}
+
+fn run_poller<T: TransportProtocol>(
+    connection: T,
+    interval: Duration,
+    stop: Receiver<()>,
+    polled: Arc<Mutex<PolledStats>>,
+) {
+    loop {
+        let result = get_stats(&connection)
+            .map_err(ClamdError::from)
+            .and_then(|response| ServerStats::from_response(&response));
+        {
+            let mut polled = polled.lock().unwrap_or_else(|err| err.into_inner());
+            match result {
+                Ok(stats) => {
+                    #[cfg(feature = "metrics")]
+                    record_metrics(&stats);
+                    polled.stats = Some((stats, Instant::now()));
+                    polled.last_error = None;
+                }
+                Err(err) => polled.last_error = Some(err.to_string()),
+            }
+        }
+        // The poller was dropped if the channel is disconnected
+        if stop.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+            return;
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn record_metrics(stats: &ServerStats) {
+    metrics::gauge!("clamav_client_server_queue_length").set(f64::from(stats.queue_length));
+    for (state, threads) in [
+        ("live", stats.live_threads),
+        ("idle", stats.idle_threads),
+        ("max", stats.max_threads),
+    ] {
+        metrics::gauge!("clamav_client_server_threads", "state" => state).set(f64::from(threads));
+    }
+    for (kind, bytes) in [
+        ("heap", stats.heap_bytes),
+        ("mmap", stats.mmap_bytes),
+        ("used", stats.used_bytes),
+        ("pools_used", stats.pools_used_bytes),
+    ] {
+        if let Some(bytes) = bytes {
+            metrics::gauge!("clamav_client_server_memory_bytes", "kind" => kind).set(bytes as f64);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt::Write;
+
+use serde_json::json;
+
+use super::{
+    timestamp::{format_time, unix_millis},
+    Detection,
+};
+
+const VENDOR: &str = "ClamAV";
+const PRODUCT: &str = "clamav-client";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The severity of detections, on the scale from 0 to 10 of CEF and LEEF
+const SEVERITY: u8 = 10;
+
+/// Formats [`Detection`]s as events for SIEM systems, e.g. Splunk or QRadar
+///
+/// Each event contains the signature, the path and file name of the scanned
+/// file, the time of the detection and, if set, the address of the ClamAV
+/// server and the correlation ID of the scan. Events are formatted as
+///
+/// - CEF, the Common Event Format of ArcSight, using [`cef`](Self::cef)
+/// - LEEF 2.0, the Log Event Extended Format of QRadar, using
+///   [`leef`](Self::leef)
+/// - a JSON object, using [`json`](Self::json)
+///
+/// Use the feature flag "siem" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// let formatter = clamav_client::EventFormatter::new().clamd_host("localhost:3310");
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let client = clamav_client::Client::new(clamd_tcp)
+///     .detection_sink(move |detection: &clamav_client::Detection| {
+///         // Send the event to the SIEM, e.g. via syslog
+///         let event = formatter.cef(detection);
+///         # assert!(event.starts_with("CEF:0|ClamAV|clamav-client|"));
+///     });
+///
+/// let outcome = client.scan_file("tests/data/eicar.txt").unwrap();
+/// assert!(outcome.is_infected());
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct EventFormatter {
+    clamd_host: Option<String>,
+}
+
+impl EventFormatter {
+    /// Creates a formatter for events without the address of ClamAV
+    pub fn new() -> Self {
+        EventFormatter::default()
+    }
+
+    /// Sets the address of the ClamAV server that is included in all events,
+    /// e.g. `clamav.example.com:3310`
+    pub fn clamd_host<H: Into<String>>(mut self, clamd_host: H) -> Self {
+        self.clamd_host = Some(clamd_host.into());
+        self
+    }
+
+    /// Formats `detection` as a CEF event
+    ///
+    /// The signature is the signature ID of the event and the `cs1` custom
+    /// string. The extension contains the time of the detection in
+    /// milliseconds since the Unix epoch as `rt`, the file name as `fname`,
+    /// the path as `filePath`, the address of ClamAV as `dhost` and the
+    /// correlation ID as `externalId`.
+    ///
+    /// # Example
+    ///
+    /// ```text
+    /// CEF:0|ClamAV|clamav-client|2.0.0|Eicar-Signature|Virus found|10|rt=1700000000000 cs1Label=signature cs1=Eicar-Signature fname=eicar.txt filePath=/uploads/eicar.txt dhost=localhost:3310
+    /// ```
+    ///
+    pub fn cef(&self, detection: &Detection) -> String {
+        let mut event = format!(
+            "CEF:0|{}|{}|{}|{}|Virus found|{}|",
+            VENDOR,
+            PRODUCT,
+            VERSION,
+            escape_cef_header(&detection.signature),
+            SEVERITY
+        );
+        let fields = self.fields(detection);
+        let extension = [
+            ("rt", Some(unix_millis(detection.detected_at).to_string())),
+            ("cs1Label", Some("signature".to_owned())),
+            ("cs1", Some(detection.signature.clone())),
+            ("fname", fields.file_name),
+            ("filePath", fields.path),
+            ("dhost", self.clamd_host.clone()),
+            ("externalId", detection.correlation_id.clone()),
+        ];
+        let mut separator = "";
+        for (key, value) in extension {
+            if let Some(value) = value {
+                let _ = write!(event, "{}{}={}", separator, key, escape_cef_value(&value));
+                separator = " ";
+            }
+        }
+        event
+    }
+
+    /// Formats `detection` as a LEEF 2.0 event with tab-separated attributes
+    ///
+    /// The event ID is `VirusFound`. The attributes contain the time of the
+    /// detection as `devTime` in the format given by `devTimeFormat`, the
+    /// signature as `signature`, the file name as `resource`, the path as
+    /// `filePath`, the address of ClamAV as `clamdHost` and the correlation ID
+    /// as `correlationId`.
+    ///
+    /// # Example
+    ///
+    /// With `\t` for the tabs:
+    ///
+    /// ```text
+    /// LEEF:2.0|ClamAV|clamav-client|2.0.0|VirusFound|x09|cat=Malware\tsev=10\tdevTime=2023-11-14T22:13:20.000+0000\tdevTimeFormat=yyyy-MM-dd'T'HH:mm:ss.SSSZ\tsignature=Eicar-Signature\tresource=eicar.txt\tfilePath=/uploads/eicar.txt\tclamdHost=localhost:3310
+    /// ```
+    ///
+    pub fn leef(&self, detection: &Detection) -> String {
+        let mut event = format!(
+            "LEEF:2.0|{}|{}|{}|VirusFound|x09|",
+            VENDOR, PRODUCT, VERSION
+        );
+        let fields = self.fields(detection);
+        let attributes = [
+            ("cat", Some("Malware".to_owned())),
+            ("sev", Some(SEVERITY.to_string())),
+            ("devTime", Some(format_time(detection.detected_at, "+0000"))),
+            (
+                "devTimeFormat",
+                Some("yyyy-MM-dd'T'HH:mm:ss.SSSZ".to_owned()),
+            ),
+            ("signature", Some(detection.signature.clone())),
+            ("resource", fields.file_name),
+            ("filePath", fields.path),
+            ("clamdHost", self.clamd_host.clone()),
+            ("correlationId", detection.correlation_id.clone()),
+        ];
+        let mut separator = "";
+        for (key, value) in attributes {
+            if let Some(value) = value {
+                let _ = write!(event, "{}{}={}", separator, key, escape_leef_value(&value));
+                separator = "\t";
+            }
+        }
+        event
+    }
+
+    /// Formats `detection` as a JSON object
+    ///
+    /// The object contains the `event` type `virus_found`, the `timestamp` in
+    /// RFC 3339 format, the `signature`, the `path` and `file_name` of the
+    /// scanned file, the address of ClamAV as `clamd_host` and the
+    /// `correlation_id`. Unknown values are `null`.
+    ///
+    /// # Example
+    ///
+    /// ```text
+    /// {"clamd_host":"localhost:3310","correlation_id":"upload-42","event":"virus_found","file_name":"eicar.txt","path":"/uploads/eicar.txt","signature":"Eicar-Signature","timestamp":"2023-11-14T22:13:20.000Z"}
+    /// ```
+    ///
+    pub fn json(&self, detection: &Detection) -> String {
+        let fields = self.fields(detection);
+        json!({
+            "event": "virus_found",
+            "timestamp": format_time(detection.detected_at, "Z"),
+            "signature": detection.signature,
+            "path": fields.path,
+            "file_name": fields.file_name,
+            "clamd_host": self.clamd_host,
+            "correlation_id": detection.correlation_id,
+        })
+        .to_string()
+    }
+
+    fn fields(&self, detection: &Detection) -> FileFields {
+        let path = detection.path.as_deref();
+        FileFields {
+            path: path.map(|path| path.to_string_lossy().into_owned()),
+            file_name: path
+                .and_then(|path| path.file_name())
+                .map(|file_name| file_name.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+/// The identity of the scanned file, if a file was scanned
+struct FileFields {
+    path: Option<String>,
+    file_name: Option<String>,
+}
+
+/// Escapes the pipes and backslashes of a CEF header field
+fn escape_cef_header(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+/// Escapes the equal signs, backslashes and line breaks of a CEF extension
+/// value
+fn escape_cef_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+/// Replaces the tabs and line breaks of a LEEF attribute value, which would
+/// end the attribute or the event
+fn escape_leef_value(value: &str) -> String {
+    value.replace(['\t', '\r', '\n'], " ")
+}
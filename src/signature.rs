@@ -0,0 +1,108 @@
+use super::pua::PUA_PREFIX;
+
+/// The suffix of the signatures of third-party databases
+const UNOFFICIAL_SUFFIX: &str = ".UNOFFICIAL";
+
+/// The components of a signature name, e.g. `Win.Trojan.Emotet-123456-1`
+///
+/// ClamAV names its signatures `Platform.Category.Family-Variant`, where the
+/// variant is the ID and the revision of the signature. Signatures of
+/// potentially unwanted applications are prefixed with `PUA.` and signatures
+/// of third-party databases end with `.UNOFFICIAL`, which are both stripped
+/// before the components are read.
+///
+/// The components are positional: names with three or more components start
+/// with the platform and the category and the remaining components form the
+/// family, names with two components consist of the category and the family,
+/// and names without a dot, e.g. `Eicar-Signature`, or with empty components
+/// only of the family. The variant is the suffix of numbers separated by `-`
+/// at the end of the family, if any.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{ScanOutcome, SignatureName};
+///
+/// let name = SignatureName::parse("Win.Trojan.Emotet-123456-1");
+/// assert_eq!(name.platform, Some("Win"));
+/// assert_eq!(name.category, Some("Trojan"));
+/// assert_eq!(name.family, "Emotet");
+/// assert_eq!(name.variant, Some("123456-1"));
+///
+/// let outcome = ScanOutcome::from_response(b"stream: PUA.Pdf.Trojan.X FOUND\0").unwrap();
+/// let name = outcome.signature_name().unwrap();
+/// assert!(name.pua);
+/// assert_eq!(name.platform, Some("Pdf"));
+/// assert_eq!(name.family, "X");
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct SignatureName<'a> {
+    /// Whether the signature is that of a potentially unwanted application
+    pub pua: bool,
+    /// The platform, e.g. `Win`, `Pdf` or `Email`
+    pub platform: Option<&'a str>,
+    /// The category, e.g. `Trojan`, `Exploit` or `Phishing`
+    pub category: Option<&'a str>,
+    /// The name of the family, e.g. `Emotet`
+    pub family: &'a str,
+    /// The ID and the revision of the signature, e.g. `123456-1`
+    pub variant: Option<&'a str>,
+    /// Whether the signature is from a third-party database
+    pub unofficial: bool,
+}
+
+impl<'a> SignatureName<'a> {
+    /// Splits a signature name into its components
+    pub fn parse(signature: &'a str) -> Self {
+        let (name, unofficial) = match signature.strip_suffix(UNOFFICIAL_SUFFIX) {
+            Some(name) if !name.is_empty() => (name, true),
+            _ => (signature, false),
+        };
+        let (name, pua) = match name.strip_prefix(PUA_PREFIX) {
+            Some(name) if !name.is_empty() => (name, true),
+            _ => (name, false),
+        };
+
+        let mut components = name.splitn(3, '.');
+        let (platform, category, family) = if name.split('.').any(str::is_empty) {
+            (None, None, name)
+        } else {
+            match (components.next(), components.next(), components.next()) {
+                (Some(platform), Some(category), Some(family)) => {
+                    (Some(platform), Some(category), family)
+                }
+                (Some(category), Some(family), None) => (None, Some(category), family),
+                _ => (None, None, name),
+            }
+        };
+        let (family, variant) = split_variant(family);
+
+        SignatureName {
+            pua,
+            platform,
+            category,
+            family,
+            variant,
+            unofficial,
+        }
+    }
+}
+
+/// Splits the variant, the numbers separated by `-` at the end, from a family
+fn split_variant(family: &str) -> (&str, Option<&str>) {
+    let mut split = None;
+    let mut rest = family;
+    while let Some((head, number)) = rest.rsplit_once('-') {
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            break;
+        }
+        split = Some(head.len());
+        rest = head;
+    }
+    match split {
+        Some(len) if len > 0 => (&family[..len], Some(&family[len + 1..])),
+        _ => (family, None),
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::ScanOutcome;
+
+/// The result of scanning data before persisting it, returned by
+/// `scan_and_commit` of the [`tokio`](crate::tokio) and
+/// [`async_std`](crate::async_std) modules
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct CommittedScan {
+    /// The outcome of the scan
+    pub outcome: ScanOutcome,
+    /// The path of the committed file, or `None` if the data was not committed
+    /// because it is infected or could not be scanned
+    pub path: Option<PathBuf>,
+}
+
+impl CommittedScan {
+    pub(crate) fn new(outcome: ScanOutcome, path: Option<PathBuf>) -> Self {
+        CommittedScan { outcome, path }
+    }
+
+    /// Returns `true` if the data was committed to its destination
+    pub fn is_committed(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+/// Returns `true` if data with this outcome may be committed, i.e. it is clean
+/// or only matched a tolerated signature
+pub(crate) fn is_committable(outcome: &ScanOutcome) -> bool {
+    outcome.is_clean() || outcome.is_warning()
+}
+
+/// The path of a spool file next to its destination, which is removed when
+/// dropped unless it has been committed
+pub(crate) struct SpoolPath {
+    path: PathBuf,
+    committed: bool,
+}
+
+impl SpoolPath {
+    /// Returns a unique hidden path in the directory of `destination`, so that
+    /// it can be renamed to the destination atomically
+    pub(crate) fn next_to(destination: &Path) -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let file_name = destination.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the destination has no file name",
+            )
+        })?;
+        let mut spool_name = std::ffi::OsString::from(".");
+        spool_name.push(file_name);
+        spool_name.push(format!(
+            ".{}-{}.spool",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        Ok(SpoolPath {
+            path: destination.with_file_name(spool_name),
+            committed: false,
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps the file, after it has been renamed to its destination
+    pub(crate) fn committed(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for SpoolPath {
+    fn drop(&mut self) {
+        // The removal is synchronous so that the file is also removed if the
+        // scan is dropped
+        if !self.committed {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
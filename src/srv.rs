@@ -0,0 +1,182 @@
+use std::{io, net::SocketAddr};
+
+use hickory_resolver::lookup::SrvLookup;
+
+use super::{random::random_up_to, HickoryResolver, Resolve};
+
+/// A ClamAV server published in a DNS SRV record
+///
+/// Use the feature flag "hickory-dns" to enable this type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct SrvEndpoint {
+    /// The host name of the server, as a fully qualified name with a trailing
+    /// dot
+    pub target: String,
+    /// The port of the server
+    pub port: u16,
+    /// The priority of the server, where servers with a lower value are tried
+    /// first
+    pub priority: u16,
+    /// The relative weight of the server among the servers with the same
+    /// priority
+    pub weight: u16,
+}
+
+impl SrvEndpoint {
+    /// Creates an endpoint from the fields of an SRV record
+    pub fn new(target: impl Into<String>, port: u16, priority: u16, weight: u16) -> Self {
+        SrvEndpoint {
+            target: target.into(),
+            port,
+            priority,
+            weight,
+        }
+    }
+
+    /// Orders `endpoints` in the order in which clients should try them, as
+    /// described in RFC 2782
+    ///
+    /// Endpoints with a lower priority come first. Endpoints with the same
+    /// priority are shuffled, and each of them comes first with a probability
+    /// proportional to its weight, which spreads the connections of many
+    /// clients across the servers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clamav_client::SrvEndpoint;
+    ///
+    /// let endpoints = SrvEndpoint::ordered(vec![
+    ///     SrvEndpoint::new("backup.example.com.", 3310, 20, 0),
+    ///     SrvEndpoint::new("clamd1.example.com.", 3310, 10, 50),
+    ///     SrvEndpoint::new("clamd2.example.com.", 3310, 10, 50),
+    /// ]);
+    /// assert_eq!(endpoints[2].target, "backup.example.com.");
+    /// ```
+    ///
+    pub fn ordered(mut endpoints: Vec<SrvEndpoint>) -> Vec<SrvEndpoint> {
+        // Endpoints without weight go first within their priority, so that
+        // they are only selected if the random number is zero
+        endpoints.sort_by_key(|endpoint| (endpoint.priority, endpoint.weight != 0));
+        let mut ordered = Vec::with_capacity(endpoints.len());
+        while let Some(priority) = endpoints.first().map(|endpoint| endpoint.priority) {
+            let len = endpoints
+                .iter()
+                .take_while(|endpoint| endpoint.priority == priority)
+                .count();
+            let mut group: Vec<SrvEndpoint> = endpoints.drain(..len).collect();
+            while !group.is_empty() {
+                let total: u32 = group
+                    .iter()
+                    .map(|endpoint| u32::from(endpoint.weight))
+                    .sum();
+                let selected = random_up_to(u64::from(total)) as u32;
+                let mut running = 0;
+                let index = group
+                    .iter()
+                    .position(|endpoint| {
+                        running += u32::from(endpoint.weight);
+                        running >= selected
+                    })
+                    .unwrap_or(0);
+                ordered.push(group.remove(index));
+            }
+        }
+        ordered
+    }
+}
+
+/// Converts the records of an SRV lookup into endpoints in the order in which
+/// they should be tried
+///
+/// A single record with the target `.` means that the service is not
+/// available, so it results in no endpoints.
+pub(crate) fn endpoints_from_lookup(lookup: &SrvLookup) -> Vec<SrvEndpoint> {
+    let endpoints = lookup
+        .iter()
+        .filter(|srv| !srv.target().is_root())
+        .map(|srv| {
+            SrvEndpoint::new(
+                srv.target().to_utf8(),
+                srv.port(),
+                srv.priority(),
+                srv.weight(),
+            )
+        })
+        .collect();
+    SrvEndpoint::ordered(endpoints)
+}
+
+/// Discovers ClamAV servers from the DNS SRV records of a service name, e.g.
+/// `_clamav._tcp.example.com`
+///
+/// As a [`Resolve`] implementation, `SrvResolver` treats the host name of a
+/// [`ResolvingTcp`](crate::ResolvingTcp) as the service name and ignores its
+/// port. It returns the addresses of all servers in the order of
+/// [`SrvEndpoint::ordered`], each with the port of its SRV record, so that a
+/// connection fails over to the next server if one is unavailable. Wrap it in
+/// a [`CachingResolver`](crate::CachingResolver) to avoid a lookup for each
+/// connection. Use [`tokio::SrvResolver`](crate::tokio::SrvResolver) with
+/// Tokio.
+///
+/// Use the feature flag "hickory-dns" to enable this type.
+///
+/// # Example
+///
+/// ```no_run
+/// let resolver = clamav_client::SrvResolver::from_system_conf().unwrap();
+/// for endpoint in resolver.endpoints("_clamav._tcp.example.com").unwrap() {
+///     println!("{}:{}", endpoint.target, endpoint.port);
+/// }
+///
+/// let clamd_tcp = clamav_client::ResolvingTcp::new("_clamav._tcp.example.com", 0, resolver);
+/// let response = clamav_client::ping(clamd_tcp).unwrap();
+/// ```
+///
+#[derive(Debug)]
+pub struct SrvResolver {
+    resolver: HickoryResolver,
+}
+
+impl SrvResolver {
+    /// Creates a resolver with the system configuration of DNS, e.g. from
+    /// `/etc/resolv.conf`
+    pub fn from_system_conf() -> io::Result<Self> {
+        HickoryResolver::from_system_conf().map(Self::from)
+    }
+
+    /// Looks up the SRV records of `service_name` and returns the endpoints in
+    /// the order in which they should be tried
+    pub fn endpoints(&self, service_name: &str) -> io::Result<Vec<SrvEndpoint>> {
+        let lookup = self
+            .resolver
+            .resolver
+            .srv_lookup(service_name)
+            .map_err(io::Error::other)?;
+        Ok(endpoints_from_lookup(&lookup))
+    }
+}
+
+impl From<HickoryResolver> for SrvResolver {
+    fn from(resolver: HickoryResolver) -> Self {
+        SrvResolver { resolver }
+    }
+}
+
+impl Resolve for SrvResolver {
+    fn resolve(&self, host: &str, _port: u16) -> io::Result<Vec<SocketAddr>> {
+        let mut addresses = Vec::new();
+        let mut last_err = None;
+        for endpoint in self.endpoints(host)? {
+            match self.resolver.resolve(&endpoint.target, endpoint.port) {
+                Ok(endpoint_addresses) => addresses.extend(endpoint_addresses),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        match last_err {
+            Some(err) if addresses.is_empty() => Err(err),
+            _ => Ok(addresses),
+        }
+    }
+}
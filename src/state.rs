@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use super::{
+    journal::{escape, unescape},
+    ClamdError, ScanOutcome,
+};
+
+/// The size and modification time of a file when it was found clean
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    size: u64,
+    /// Nanoseconds since the Unix epoch
+    modified: u128,
+}
+
+impl FileState {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(FileState {
+            size: metadata.len(),
+            modified: modified.as_nanos(),
+        })
+    }
+}
+
+/// An append-only file recording the size and modification time of each file
+/// found clean by a directory scan, together with the version of the virus
+/// databases, so that later scans can skip unchanged files
+///
+/// Each line holds the database version, the size, the modification time in
+/// nanoseconds since the Unix epoch and the escaped path of a file, separated
+/// by tabs. When the store is opened, it is rewritten with the latest entries
+/// for the current database version, so it does not grow beyond one line per
+/// clean file between two scans, and entries of older databases are dropped.
+pub(crate) struct StateStore {
+    file: Mutex<File>,
+    database_version: u32,
+    recorded: HashMap<PathBuf, FileState>,
+}
+
+impl StateStore {
+    /// Opens the store at `path` for scans with the given database version,
+    /// creating it if it does not exist
+    pub(crate) fn open(path: &Path, database_version: u32) -> io::Result<Self> {
+        let mut contents = String::new();
+        match File::open(path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut contents)?;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        let mut recorded = HashMap::new();
+        let complete = match contents.rfind('\n') {
+            Some(end) => &contents[..end],
+            None => "",
+        };
+        for line in complete.lines() {
+            if let Some((version, path, state)) = parse_line(line) {
+                if version == database_version {
+                    recorded.insert(path, state);
+                }
+            }
+        }
+
+        // Replace the store atomically, so that a crash while it is rewritten
+        // does not lose it
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let mut temp_file = File::create(&temp_path)?;
+        let mut lines = String::new();
+        for (path, state) in &recorded {
+            if let Some(line) = format_line(database_version, path, *state) {
+                lines.push_str(&line);
+            }
+        }
+        temp_file.write_all(lines.as_bytes())?;
+        temp_file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        let file = OpenOptions::new().append(true).open(path)?;
+
+        Ok(StateStore {
+            file: Mutex::new(file),
+            database_version,
+            recorded,
+        })
+    }
+
+    /// Returns `true` if the file at `path` was found clean with the current
+    /// databases and its size and modification time have not changed since
+    pub(crate) fn is_unchanged(&self, path: &Path) -> bool {
+        match self.recorded.get(path) {
+            Some(recorded) => FileState::of(path).as_ref() == Some(recorded),
+            None => false,
+        }
+    }
+
+    /// Records the state of the file at `path` if it was found clean
+    ///
+    /// Write errors are ignored, the file is then scanned again by the next
+    /// scan.
+    pub(crate) fn record(&self, path: &Path, result: &Result<ScanOutcome, ClamdError>) {
+        if !matches!(result, Ok(ScanOutcome::Clean)) {
+            return;
+        }
+        let line = match FileState::of(path)
+            .and_then(|state| format_line(self.database_version, path, state))
+        {
+            Some(line) => line,
+            None => return,
+        };
+        let _ = self.file.lock().unwrap().write_all(line.as_bytes());
+    }
+
+    /// Returns [`ScanOutcome::Clean`] for an unchanged file at `path`, or
+    /// scans and records it
+    pub(crate) fn scan<F>(
+        state: Option<&Self>,
+        path: &Path,
+        scan: F,
+    ) -> Result<ScanOutcome, ClamdError>
+    where
+        F: FnOnce() -> Result<ScanOutcome, ClamdError>,
+    {
+        let state = match state {
+            Some(state) => state,
+            None => return scan(),
+        };
+        if state.is_unchanged(path) {
+            return Ok(ScanOutcome::Clean);
+        }
+        let result = scan();
+        state.record(path, &result);
+        result
+    }
+}
+
+fn format_line(database_version: u32, path: &Path, state: FileState) -> Option<String> {
+    Some(format!(
+        "{}\t{}\t{}\t{}\n",
+        database_version,
+        state.size,
+        state.modified,
+        escape(path.to_str()?)
+    ))
+}
+
+fn parse_line(line: &str) -> Option<(u32, PathBuf, FileState)> {
+    let mut fields = line.splitn(4, '\t');
+    let version = fields.next()?.parse().ok()?;
+    let size = fields.next()?.parse().ok()?;
+    let modified = fields.next()?.parse().ok()?;
+    let path = unescape(fields.next()?)?;
+    Some((version, path.into(), FileState { size, modified }))
+}
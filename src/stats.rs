@@ -0,0 +1,102 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{ClamdError, ScanOutcome};
+
+/// Cumulative statistics of the scans of a client, returned by its `stats`
+/// method
+///
+/// Only scans answered by ClamAV are counted, not outcomes found in the cache
+/// of the client.
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let client = clamav_client::Client::new(clamd_tcp);
+/// client.scan_file("README.md").unwrap();
+/// client.scan_file("tests/data/eicar.txt").unwrap();
+///
+/// let stats = client.stats();
+/// println!(
+///     "{} scans, {} infections, {} errors, {} bytes in {:?}",
+///     stats.scans, stats.infections, stats.errors, stats.bytes_sent, stats.uptime
+/// );
+/// # assert_eq!((stats.scans, stats.infections, stats.errors), (2, 1, 0));
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClientStats {
+    /// The number of scans, including failed scans
+    pub scans: u64,
+    /// The number of scans that found a virus
+    pub infections: u64,
+    /// The number of scans that failed or for which ClamAV reported an error
+    pub errors: u64,
+    /// The number of bytes sent to ClamAV
+    pub bytes_sent: u64,
+    /// The message of the last error, if any
+    pub last_error: Option<String>,
+    /// The time since the client was created
+    pub uptime: Duration,
+}
+
+/// The counters behind [`ClientStats`], shared by a client and its clones
+#[derive(Debug)]
+pub(crate) struct StatsCounters {
+    created: Instant,
+    scans: AtomicU64,
+    infections: AtomicU64,
+    errors: AtomicU64,
+    bytes_sent: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl StatsCounters {
+    pub(crate) fn new() -> Self {
+        StatsCounters {
+            created: Instant::now(),
+            scans: AtomicU64::new(0),
+            infections: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn chunk_sent(&self, len: u64) {
+        self.bytes_sent.fetch_add(len, Ordering::Relaxed);
+    }
+
+    pub(crate) fn result(&self, result: &Result<ScanOutcome, ClamdError>) {
+        self.scans.fetch_add(1, Ordering::Relaxed);
+        let error = match result {
+            Ok(ScanOutcome::Clean | ScanOutcome::Warning { .. }) => return,
+            Ok(ScanOutcome::Infected { .. }) => {
+                self.infections.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            Ok(ScanOutcome::Error { message }) => message.clone(),
+            Err(err) => err.to_string(),
+        };
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        *self.last_error.lock().unwrap() = Some(error);
+    }
+
+    pub(crate) fn snapshot(&self) -> ClientStats {
+        ClientStats {
+            scans: self.scans.load(Ordering::Relaxed),
+            infections: self.infections.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            last_error: self.last_error.lock().unwrap().clone(),
+            uptime: self.created.elapsed(),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    slice,
+    time::Duration,
+    vec,
+};
+
+use super::{ClamdError, ScanOutcome};
+
+/// Aggregated statistics of multiple scans
+///
+/// # Example
+///
+/// ```
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+/// let results = clamav_client::scan_directory("tests/data", clamd_tcp, None, directory_options);
+/// println!("{}", results.summary);
+/// # assert_eq!(results.summary.infected, 1);
+/// # assert_eq!(results.summary.signatures.get("Eicar-Signature"), Some(&1));
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScanSummary {
+    /// The number of files (or other inputs) that were scanned or attempted
+    pub files_scanned: usize,
+    /// The number of scans in which no virus was found
+    pub clean: usize,
+    /// The number of scans in which a virus was found
+    pub infected: usize,
+    /// The number of scans in which a tolerated signature matched
+    pub warnings: usize,
+    /// The number of scans that failed or were reported as an error by ClamAV
+    pub errored: usize,
+    /// The number of infected scans per matched signature
+    pub signatures: BTreeMap<String, usize>,
+    /// The number of bytes sent to ClamAV by successful scans
+    pub bytes_scanned: u64,
+    /// The wall-clock time the scans took
+    pub elapsed: Duration,
+}
+
+impl ScanSummary {
+    /// Adds the result of a single scan of `bytes_scanned` bytes
+    ///
+    /// This can be used to aggregate results that are consumed one by one, e.g.
+    /// from the stream returned by the asynchronous `scan_directory`.
+    pub fn add(&mut self, result: &Result<ScanOutcome, ClamdError>, bytes_scanned: u64) {
+        self.files_scanned += 1;
+        match result {
+            Ok(ScanOutcome::Clean) => self.clean += 1,
+            Ok(ScanOutcome::Infected { signature }) => {
+                self.infected += 1;
+                *self.signatures.entry(signature.clone()).or_insert(0) += 1;
+            }
+            Ok(ScanOutcome::Warning { .. }) => self.warnings += 1,
+            Ok(ScanOutcome::Error { .. }) | Err(_) => self.errored += 1,
+        }
+        if result.is_ok() {
+            self.bytes_scanned += bytes_scanned;
+        }
+    }
+}
+
+impl fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Scanned {} files ({} bytes) in {:.2?}: {} clean, {} infected, {} errored",
+            self.files_scanned,
+            self.bytes_scanned,
+            self.elapsed,
+            self.clean,
+            self.infected,
+            self.errored
+        )?;
+        if self.warnings > 0 {
+            write!(f, ", {} warnings", self.warnings)?;
+        }
+        for (signature, count) in &self.signatures {
+            write!(f, "\n  {}: {}", signature, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The per-file results of a batch or directory scan together with their
+/// [`ScanSummary`]
+///
+/// Iterating over the results yields the path of each file together with its
+/// [`ScanOutcome`], or the error that occurred while reading or scanning it.
+///
+#[derive(Debug)]
+pub struct ScanResults<P = PathBuf> {
+    /// The path and the result of each scan
+    pub results: Vec<(P, Result<ScanOutcome, ClamdError>)>,
+    /// The aggregated statistics of all scans
+    pub summary: ScanSummary,
+}
+
+impl<P: AsRef<Path>> ScanResults<P> {
+    /// Collects the results of file scans, using the sizes of the successfully
+    /// scanned files as the number of bytes scanned
+    pub(crate) fn from_file_results(
+        results: Vec<(P, Result<ScanOutcome, ClamdError>)>,
+        elapsed: Duration,
+    ) -> Self {
+        let mut summary = ScanSummary {
+            elapsed,
+            ..Default::default()
+        };
+        for (path, result) in &results {
+            let bytes_scanned = match result {
+                Ok(_) => fs::metadata(path).map_or(0, |metadata| metadata.len()),
+                Err(_) => 0,
+            };
+            summary.add(result, bytes_scanned);
+        }
+        ScanResults { results, summary }
+    }
+}
+
+impl<P> IntoIterator for ScanResults<P> {
+    type Item = (P, Result<ScanOutcome, ClamdError>);
+    type IntoIter = vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.into_iter()
+    }
+}
+
+impl<'a, P> IntoIterator for &'a ScanResults<P> {
+    type Item = &'a (P, Result<ScanOutcome, ClamdError>);
+    type IntoIter = slice::Iter<'a, (P, Result<ScanOutcome, ClamdError>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.iter()
+    }
+}
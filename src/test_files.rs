@@ -0,0 +1,99 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::health;
+
+/// The number of directories created by this process, to name them
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory with generated test files, so that test suites do not
+/// depend on binary fixtures checked into their repositories
+///
+/// [`eicar`](Self::eicar) writes the EICAR test file, which every antivirus
+/// detects, e.g. as an infected file without an antivirus on the development
+/// machine quarantining it. [`sparse`](Self::sparse) creates a file of an
+/// arbitrary size that consists of zeros without using disk space on most file
+/// systems, e.g. to exceed the `StreamMaxLength` of ClamAV. The directory and
+/// its files are removed when the value is dropped.
+///
+/// Use the feature flag "test-util" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{MockServer, MockServerOptions, TestFiles};
+///
+/// let server = MockServer::start(MockServerOptions::new()).unwrap();
+/// let client = clamav_client::Client::new(clamav_client::Tcp {
+///     host_address: server.address(),
+/// });
+///
+/// let files = TestFiles::new().unwrap();
+/// let eicar = files.eicar("eicar.txt").unwrap();
+/// let outcome = client.scan_file(&eicar).unwrap();
+/// assert!(outcome.is_infected());
+///
+/// let oversized = files.sparse("oversized.bin", 30 * 1024 * 1024).unwrap();
+/// assert_eq!(oversized.metadata().unwrap().len(), 30 * 1024 * 1024);
+/// ```
+///
+#[derive(Debug)]
+pub struct TestFiles {
+    directory: PathBuf,
+}
+
+impl TestFiles {
+    /// The content of the EICAR test file
+    pub const EICAR: &'static [u8] = health::EICAR;
+
+    /// Creates a new, empty temporary directory
+    pub fn new() -> io::Result<Self> {
+        let directory = std::env::temp_dir().join(format!(
+            "clamav-client-test-files-{}-{}",
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&directory)?;
+        Ok(TestFiles { directory })
+    }
+
+    /// Returns the path of the temporary directory
+    pub fn path(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Writes `contents` to the file `name` in the directory and returns its
+    /// path
+    pub fn file<P: AsRef<Path>>(&self, name: P, contents: &[u8]) -> io::Result<PathBuf> {
+        let path = self.directory.join(name);
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+
+    /// Writes the EICAR test file to the file `name` in the directory and
+    /// returns its path
+    pub fn eicar<P: AsRef<Path>>(&self, name: P) -> io::Result<PathBuf> {
+        self.file(name, Self::EICAR)
+    }
+
+    /// Creates the file `name` in the directory with `size` zeros and returns
+    /// its path
+    ///
+    /// The file is sparse on file systems that support it, so that even files
+    /// of several gigabytes are created instantly.
+    pub fn sparse<P: AsRef<Path>>(&self, name: P, size: u64) -> io::Result<PathBuf> {
+        let path = self.directory.join(name);
+        File::create(&path)?.set_len(size)?;
+        Ok(path)
+    }
+}
+
+impl Drop for TestFiles {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
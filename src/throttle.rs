@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use super::ScanOptions;
+
+/// Token bucket that limits the upload rate of a single scan
+///
+/// The bucket holds up to one second worth of bytes and starts full, so short
+/// bursts are sent without delay. Chunks larger than the bucket put it into
+/// debt, which is paid off by waiting before the next chunk.
+pub(crate) struct Throttle {
+    bytes_per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Throttle {
+    pub(crate) fn new(options: &ScanOptions) -> Option<Self> {
+        let bytes_per_second = options.max_bytes_per_second? as f64;
+        Some(Throttle {
+            bytes_per_second,
+            tokens: bytes_per_second,
+            last_refill: Instant::now(),
+        })
+    }
+
+    /// Takes `len` bytes from the bucket and returns how long to wait before
+    /// sending them
+    pub(crate) fn delay(&mut self, len: usize) -> Option<Duration> {
+        let now = Instant::now();
+        let refill = now.duration_since(self.last_refill).as_secs_f64() * self.bytes_per_second;
+        self.tokens = (self.tokens + refill).min(self.bytes_per_second);
+        self.last_refill = now;
+
+        self.tokens -= len as f64;
+        if self.tokens >= 0.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                -self.tokens / self.bytes_per_second,
+            ))
+        }
+    }
+}
@@ -0,0 +1,49 @@
+#[cfg(any(feature = "siem", feature = "audit"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the milliseconds since the Unix epoch, or 0 for earlier times
+#[cfg(any(feature = "siem", feature = "audit"))]
+pub(crate) fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis())
+}
+
+/// Formats `time` in UTC as `yyyy-MM-ddTHH:mm:ss.SSS` followed by `offset`
+#[cfg(any(feature = "siem", feature = "audit"))]
+pub(crate) fn format_time(time: SystemTime, offset: &str) -> String {
+    let millis = unix_millis(time);
+    let seconds = (millis / 1000) as u64;
+    let (year, month, day) = civil_from_days(seconds / 86400);
+    let seconds_of_day = seconds % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}{}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        millis % 1000,
+        offset
+    )
+}
+
+/// Converts days since the Unix epoch to a date of the Gregorian calendar
+/// (see <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>)
+pub(crate) fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
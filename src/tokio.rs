@@ -1,115 +1,525 @@
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    mem,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
     fs::File,
-    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
-    net::{TcpStream, ToSocketAddrs},
+    io::{
+        self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt,
+        BufReader, BufWriter,
+    },
+    net::{TcpSocket, TcpStream, ToSocketAddrs},
+    sync::oneshot,
 };
 
+#[cfg(feature = "process")]
+use std::process::Stdio;
+
+#[cfg(feature = "process")]
+use tokio::process::Command;
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
 #[cfg(unix)]
 use tokio::net::UnixStream;
 
-#[cfg(feature = "tokio-stream")]
-use tokio_stream::{Stream, StreamExt};
+use futures_core::Stream;
+#[cfg(any(feature = "reqwest", feature = "multipart"))]
+use tokio_stream::StreamExt;
+
+#[cfg(feature = "cache")]
+use sha2::{Digest, Sha256};
+
+use super::{
+    archive::ArchiveCheck,
+    concurrency,
+    context::{self, Addresses, ResultExt},
+    database,
+    dedup::Duplicates,
+    health::EICAR,
+    latency::LatencySamples,
+    outcome,
+    path_scan::path_command,
+    pipeline::Pipeline,
+    protocol::{Exchange, Frame, Instream, ResponseReader},
+    report::ReportTimer,
+    response,
+    spool::{self, SpoolPath},
+    stats::StatsCounters,
+    verdict,
+    wipe::DataBuffer,
+    wire, ClamdError, ClientStats, CommittedScan, CrossCheck, DetectionSink, ErrorPhase,
+    FailurePolicy, HealthCheckOptions, HealthReport, IoResult, IpFamily, LatencyStats, PathCommand,
+    ResponseBuffer, ScanDecision, ScanOptions, ScanOutcome, ScanResponse, ScanResults, ServerStats,
+    VerdictPolicy, VersionInfo, END, END_OF_STREAM, IDSESSION, INSTREAM, PING, PONG, RELOAD,
+    SHUTDOWN, STATS, VERSION,
+};
+
+#[cfg(feature = "audit")]
+use super::{audit::AuditScan, AuditLog};
+#[cfg(feature = "cache")]
+use super::{
+    cache::{self, CachedScan, ScanCache},
+    DEFAULT_CHUNK_SIZE,
+};
+
+use super::{directory, error, DirectoryScanOptions};
 
-use super::{IoResult, DEFAULT_CHUNK_SIZE, END_OF_STREAM, INSTREAM, PING, PONG, SHUTDOWN, VERSION};
+#[cfg(feature = "hickory-dns")]
+use super::srv;
+
+#[cfg(feature = "sink")]
+use super::ScanProgress;
+#[cfg(feature = "sink")]
+use std::{future::Future, task::ready};
 
 async fn send_command<RW: AsyncRead + AsyncWrite + Unpin>(
-    mut stream: RW,
+    stream: RW,
     command: &[u8],
     expected_response_length: Option<usize>,
 ) -> IoResult {
-    stream.write_all(command).await?;
-    stream.flush().await?;
+    let mut response = Vec::new();
+    send_command_into(
+        stream,
+        command,
+        &mut response,
+        &ResponseBuffer::expecting(expected_response_length),
+    )
+    .await?;
+    Ok(response)
+}
 
-    let mut response = match expected_response_length {
-        Some(len) => Vec::with_capacity(len),
-        None => Vec::new(),
-    };
+/// Sends `command` and reads the response into `response`, which is cleared
+/// first
+async fn send_command_into<RW: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: RW,
+    command: &[u8],
+    response: &mut Vec<u8>,
+    buffer: &ResponseBuffer,
+) -> io::Result<()> {
+    let command = Exchange::new(command);
+    stream
+        .write_all(command.bytes())
+        .await
+        .phase(ErrorPhase::Command)?;
+    stream.flush().await.phase(ErrorPhase::Command)?;
+    command.sent();
 
-    stream.read_to_end(&mut response).await?;
-    Ok(response)
+    read_response(stream, response, buffer)
+        .await
+        .phase(ErrorPhase::Response)?;
+    command.received(response);
+    Ok(())
+}
+
+/// Reads the response of ClamAV into `response`, which is cleared first
+async fn read_response<R: AsyncRead + Unpin>(
+    mut stream: R,
+    response: &mut Vec<u8>,
+    buffer: &ResponseBuffer,
+) -> io::Result<()> {
+    let mut reader = ResponseReader::new(response, buffer);
+    loop {
+        let read = stream.read(reader.unfilled()).await;
+        if reader.advance(read)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Writes a length-prefixed chunk, combining the 4-byte header and the payload
+/// into vectored writes
+async fn write_chunk<W: AsyncWrite + Unpin>(stream: &mut W, chunk: &[u8]) -> io::Result<()> {
+    for mut frame in Frame::split(chunk) {
+        while !frame.is_written() {
+            frame.advance(stream.write_vectored(&frame.remaining()).await)?;
+        }
+    }
+    Ok(())
+}
+
+/// Guards a stream to ClamAV while data is streamed to it through the write
+/// buffer of the scan options
+///
+/// If the scan is dropped, e.g. because it lost a race against a timeout, or
+/// fails before the stream was terminated, the write half of the connection is
+/// shut down, so that ClamAV stops waiting for the rest of the stream. Data
+/// left in the write buffer is discarded.
+struct StreamGuard<'a, W: AsyncWrite + Unpin> {
+    stream: BufWriter<&'a mut W>,
+    streamed: bool,
+}
+
+impl<'a, W: AsyncWrite + Unpin> StreamGuard<'a, W> {
+    fn new(stream: &'a mut W, options: &ScanOptions) -> Self {
+        StreamGuard {
+            stream: BufWriter::with_capacity(options.write_buffer_size_or_default(), stream),
+            streamed: false,
+        }
+    }
+
+    /// Writes the end of the stream, after which the connection is left open
+    /// for the response
+    async fn finish(mut self) -> io::Result<()> {
+        self.stream
+            .write_all(END_OF_STREAM)
+            .await
+            .phase(ErrorPhase::Stream)?;
+        self.stream.flush().await.phase(ErrorPhase::Stream)?;
+        self.streamed = true;
+        Ok(())
+    }
+}
+
+impl<'a, W: AsyncWrite + Unpin> std::ops::Deref for StreamGuard<'a, W> {
+    type Target = BufWriter<&'a mut W>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl<W: AsyncWrite + Unpin> std::ops::DerefMut for StreamGuard<'_, W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.stream
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Drop for StreamGuard<'_, W> {
+    fn drop(&mut self) {
+        if !self.streamed {
+            // The shutdown cannot be awaited here, so it is attempted once,
+            // which completes immediately for TCP and Unix sockets
+            let waker = concurrency::noop_waker();
+            let mut cx = std::task::Context::from_waker(&waker);
+            let _ = std::pin::Pin::new(&mut **self.stream.get_mut()).poll_shutdown(&mut cx);
+        }
+    }
 }
 
-async fn scan<R: AsyncRead + Unpin, RW: AsyncRead + AsyncWrite + Unpin>(
-    mut input: R,
-    chunk_size: Option<usize>,
+async fn scan<R: AsyncRead, RW: AsyncRead + AsyncWrite + Unpin>(
+    input: R,
+    input_size: Option<u64>,
+    options: &ScanOptions,
     mut stream: RW,
+    mut report: ReportTimer<'_>,
 ) -> IoResult {
-    stream.write_all(INSTREAM).await?;
+    let mut buffer = DataBuffer::default();
+    write_instream(
+        input,
+        input_size,
+        options,
+        &mut stream,
+        &mut buffer,
+        &mut report,
+    )
+    .await?;
+
+    let mut response = Vec::new();
+    read_response(&mut stream, &mut response, &options.response_buffer)
+        .await
+        .phase(ErrorPhase::Response)?;
+    report.finish(&response);
+    Ok(response)
+}
+
+async fn scan_slice<RW: AsyncRead + AsyncWrite + Unpin>(
+    data: &[u8],
+    options: &ScanOptions,
+    stream: RW,
+    report: ReportTimer<'_>,
+) -> IoResult {
+    let mut response = Vec::new();
+    scan_slice_into(data, options, stream, report, &mut response).await?;
+    Ok(response)
+}
+
+/// Streams `data` to ClamAV without copying it into a chunk buffer and reads
+/// the response into `response`, which is cleared first
+async fn scan_slice_into<RW: AsyncRead + AsyncWrite + Unpin>(
+    data: &[u8],
+    options: &ScanOptions,
+    mut stream: RW,
+    mut report: ReportTimer<'_>,
+    response: &mut Vec<u8>,
+) -> io::Result<()> {
+    let mut instream = Instream::start(options, Some(data.len() as u64))?;
+    let mut guard = StreamGuard::new(&mut stream, options);
+    guard.write_all(INSTREAM).await.phase(ErrorPhase::Stream)?;
+    instream.started();
+
+    for chunk in data.chunks(instream.chunk_size()) {
+        instream.input(chunk.len())?;
+        if let Some(delay) = instream.delay(chunk.len()) {
+            guard.flush().await.phase(ErrorPhase::Stream)?;
+            tokio::time::sleep(delay).await;
+        }
+        write_chunk(&mut *guard, chunk)
+            .await
+            .phase(ErrorPhase::Stream)?;
+        instream.chunk_sent(chunk, &mut report);
+    }
+    guard.finish().await?;
+    instream.finished(&mut report);
+
+    read_response(&mut stream, response, &options.response_buffer)
+        .await
+        .phase(ErrorPhase::Response)?;
+    report.finish(response);
+    Ok(())
+}
 
-    let chunk_size = chunk_size
-        .unwrap_or(DEFAULT_CHUNK_SIZE)
-        .min(u32::MAX as usize);
+/// Scans `input_size` bytes of `input` in parts of at most `split_size` bytes,
+/// each with its own connection, and returns the response to the first part
+/// that is not clean or to the last part
+async fn scan_split<R: AsyncRead, T: TransportProtocol>(
+    input: R,
+    input_size: u64,
+    split_size: u64,
+    connection: &T,
+    options: &ScanOptions,
+) -> IoResult {
+    let mut input = std::pin::pin!(input);
+    // A split size of zero would never make progress
+    options.validate()?;
+    let mut remaining = input_size;
+    loop {
+        let part_size = remaining.min(split_size);
+        remaining -= part_size;
+        let mut report = ReportTimer::start(options);
+        let stream = report.connect(connection.connect().await)?;
+        let part = input.as_mut().take(part_size);
+        let response = scan(part, Some(part_size), options, stream, report).await?;
+        if remaining == 0 || !outcome::is_clean_response(&response) {
+            return Ok(response);
+        }
+    }
+}
 
-    let mut buffer = vec![0; chunk_size];
+async fn write_instream<R: AsyncRead, W: AsyncWrite + Unpin>(
+    input: R,
+    input_size: Option<u64>,
+    options: &ScanOptions,
+    stream: &mut W,
+    buffer: &mut DataBuffer,
+    report: &mut ReportTimer<'_>,
+) -> io::Result<()> {
+    // Pinning the input here lets callers pass readers that are not `Unpin`
+    let mut input = std::pin::pin!(input);
+    let mut instream = Instream::start(options, input_size)?;
+    let mut stream = StreamGuard::new(stream, options);
+    stream.write_all(INSTREAM).await.phase(ErrorPhase::Stream)?;
+    instream.started();
 
+    let chunk_size = instream.chunk_size();
+    let buffer = buffer.get(chunk_size);
     loop {
-        let len = input.read(&mut buffer[..]).await?;
+        let len = input.read(buffer).await.phase(ErrorPhase::Input)?;
         if len != 0 {
-            stream.write_all(&(len as u32).to_be_bytes()).await?;
-            stream.write_all(&buffer[..len]).await?;
+            instream.input(len)?;
+            if let Some(delay) = instream.delay(len) {
+                stream.flush().await.phase(ErrorPhase::Stream)?;
+                tokio::time::sleep(delay).await;
+            }
+            write_chunk(&mut *stream, &buffer[..len])
+                .await
+                .phase(ErrorPhase::Stream)?;
+            instream.chunk_sent(&buffer[..len], report);
         } else {
-            stream.write_all(END_OF_STREAM).await?;
-            stream.flush().await?;
-            break;
+            instream.end()?;
+            stream.finish().await?;
+            instream.finished(report);
+            return Ok(());
         }
     }
-
-    let mut response = Vec::new();
-    stream.read_to_end(&mut response).await?;
-    Ok(response)
 }
 
-#[cfg(feature = "tokio-stream")]
-async fn _scan_stream<
-    S: Stream<Item = Result<bytes::Bytes, std::io::Error>>,
-    RW: AsyncRead + AsyncWrite + Unpin,
->(
+async fn _scan_stream<S, B, E, RW>(
     input_stream: S,
-    chunk_size: Option<usize>,
+    options: &ScanOptions,
     mut output_stream: RW,
-) -> IoResult {
-    output_stream.write_all(INSTREAM).await?;
-
-    let chunk_size = chunk_size
-        .unwrap_or(DEFAULT_CHUNK_SIZE)
-        .min(u32::MAX as usize);
+    mut report: ReportTimer<'_>,
+) -> IoResult
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    RW: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut instream = Instream::start(options, None)?;
+    let mut stream = StreamGuard::new(&mut output_stream, options);
+    stream.write_all(INSTREAM).await.phase(ErrorPhase::Stream)?;
+    instream.started();
 
     let mut input_stream = std::pin::pin!(input_stream);
-
-    while let Some(bytes) = input_stream.next().await {
-        let bytes = bytes?;
+    while let Some(bytes) = concurrency::next(&mut input_stream).await {
+        let bytes = bytes
+            .map_err(error::stream_error)
+            .phase(ErrorPhase::Input)?;
         let bytes = bytes.as_ref();
-        for chunk in bytes.chunks(chunk_size) {
-            let len = chunk.len();
-            output_stream.write_all(&(len as u32).to_be_bytes()).await?;
-            output_stream.write_all(chunk).await?;
+        instream.input(bytes.len())?;
+        for chunk in bytes.chunks(instream.chunk_size()) {
+            if let Some(delay) = instream.delay(chunk.len()) {
+                stream.flush().await.phase(ErrorPhase::Stream)?;
+                tokio::time::sleep(delay).await;
+            }
+            write_chunk(&mut *stream, chunk)
+                .await
+                .phase(ErrorPhase::Stream)?;
+            instream.chunk_sent(chunk, &mut report);
         }
     }
 
-    output_stream.write_all(END_OF_STREAM).await?;
-    output_stream.flush().await?;
+    instream.end()?;
+    stream.finish().await?;
+    instream.finished(&mut report);
 
     let mut response = Vec::new();
-    output_stream.read_to_end(&mut response).await?;
+    read_response(&mut output_stream, &mut response, &options.response_buffer)
+        .await
+        .phase(ErrorPhase::Response)?;
+    report.finish(&response);
     Ok(response)
 }
 
 /// Use a TCP connection to communicate with a ClamAV server
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Tcp<A: ToSocketAddrs> {
     /// The address (host and port) of the ClamAV server
     pub host_address: A,
 }
 
+/// Use a TCP connection from a local address to communicate with a ClamAV
+/// server
+///
+/// The connection is bound to `local_address` before connecting, e.g. to send
+/// it from a specific interface of a multi-homed host or from the subnet that
+/// the ACLs of ClamAV allow. A port of `0` lets the operating system choose
+/// the local port. Only addresses of the ClamAV server of the same family as
+/// the local address, IPv4 or IPv6, are tried.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::BoundTcp {
+///     host_address: "localhost:3310",
+///     local_address: "127.0.0.1:0".parse().unwrap(),
+/// };
+/// let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// # }
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BoundTcp<A: ToSocketAddrs> {
+    /// The address (host and port) of the ClamAV server
+    pub host_address: A,
+    /// The local address to bind the connection to
+    pub local_address: SocketAddr,
+}
+
+/// Use a TCP connection to communicate with a ClamAV server, preferring or
+/// requiring IPv4 or IPv6 addresses
+///
+/// See [`IpFamily`](crate::IpFamily).
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::FamilyTcp {
+///     host_address: "localhost:3310",
+///     ip_family: clamav_client::IpFamily::Ipv4Only,
+/// };
+/// let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// # }
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FamilyTcp<A: ToSocketAddrs> {
+    /// The address (host and port) of the ClamAV server
+    pub host_address: A,
+    /// The IP family of the addresses to connect to
+    pub ip_family: IpFamily,
+}
+
+/// Use the Unix socket of a local ClamAV server if it accepts connections, or
+/// a TCP connection to a ClamAV server otherwise
+///
+/// See [`SocketOrTcp`](crate::SocketOrTcp).
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd = clamav_client::tokio::SocketOrTcp {
+///     socket_path: "/tmp/clamd.sock",
+///     host_address: "localhost:3310",
+/// };
+/// let response = clamav_client::tokio::ping(clamd).await.unwrap();
+/// # assert_eq!(&response, clamav_client::PONG);
+/// # }
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg(unix)]
+pub struct SocketOrTcp<P: AsRef<Path>, A: ToSocketAddrs> {
+    /// The socket file path of the local ClamAV server
+    pub socket_path: P,
+    /// The address (host and port) of the ClamAV server to fall back to
+    pub host_address: A,
+}
+
+/// The stream of a [`SocketOrTcp`] connection
+#[derive(Debug)]
+#[cfg(unix)]
+pub enum SocketOrTcpStream {
+    /// A connection to the Unix socket
+    Socket(UnixStream),
+    /// A connection to the TCP address
+    Tcp(TcpStream),
+}
+
 /// Use a Unix socket connection to communicate with a ClamAV server
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg(unix)]
 pub struct Socket<P: AsRef<Path>> {
     /// The socket file path of the ClamAV server
     pub socket_path: P,
 }
 
+/// Converts the blocking [`Tcp`](crate::Tcp), e.g. to construct transports in
+/// configuration code independently of the runtime
+impl<A: ToSocketAddrs + std::net::ToSocketAddrs> From<crate::Tcp<A>> for Tcp<A> {
+    fn from(transport: crate::Tcp<A>) -> Self {
+        Tcp {
+            host_address: transport.host_address,
+        }
+    }
+}
+
+/// Converts the blocking [`Socket`](crate::Socket), e.g. to construct
+/// transports in configuration code independently of the runtime
+#[cfg(unix)]
+impl<P: AsRef<Path>> From<crate::Socket<P>> for Socket<P> {
+    fn from(transport: crate::Socket<P>) -> Self {
+        Socket {
+            socket_path: transport.socket_path,
+        }
+    }
+}
+
 /// The communication protocol to use
 pub trait TransportProtocol {
     /// Bidirectional stream
@@ -117,13 +527,67 @@ pub trait TransportProtocol {
 
     /// Converts the protocol instance into the corresponding stream
     fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>>;
+
+    /// Returns `true` if ClamAV runs on the same host and can read its files,
+    /// which is assumed for Unix sockets
+    ///
+    /// The default implementation returns `false`.
+    fn is_local(&self) -> bool {
+        false
+    }
 }
 
 impl<A: ToSocketAddrs> TransportProtocol for Tcp<A> {
     type Stream = TcpStream;
 
-    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
-        TcpStream::connect(&self.host_address)
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        let addresses = context::resolved(tokio::net::lookup_host(&self.host_address).await)?;
+        TcpStream::connect(&addresses[..])
+            .await
+            .phase_of(ErrorPhase::Connect, Addresses(&addresses))
+    }
+}
+
+impl<A: ToSocketAddrs> TransportProtocol for BoundTcp<A> {
+    type Stream = TcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        let mut last_err = None;
+        for address in tokio::net::lookup_host(&self.host_address).await? {
+            if address.is_ipv4() != self.local_address.is_ipv4() {
+                continue;
+            }
+            let socket = if address.is_ipv4() {
+                TcpSocket::new_v4()?
+            } else {
+                TcpSocket::new_v6()?
+            };
+            socket.bind(self.local_address)?;
+            match socket.connect(address).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses of the family of the local address",
+            )
+        }))
+    }
+}
+
+impl<A: ToSocketAddrs> TransportProtocol for FamilyTcp<A> {
+    type Stream = TcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        let addresses = self
+            .ip_family
+            .apply(tokio::net::lookup_host(&self.host_address).await?);
+        if addresses.is_empty() {
+            return Err(IpFamily::no_addresses());
+        }
+        TcpStream::connect(&addresses[..]).await
     }
 }
 
@@ -131,153 +595,708 @@ impl<A: ToSocketAddrs> TransportProtocol for Tcp<A> {
 impl<P: AsRef<Path>> TransportProtocol for Socket<P> {
     type Stream = UnixStream;
 
-    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
+    async fn connect(&self) -> io::Result<Self::Stream> {
         UnixStream::connect(&self.socket_path)
+            .await
+            .phase_of(ErrorPhase::Connect, self.socket_path.as_ref().display())
+    }
+
+    fn is_local(&self) -> bool {
+        true
     }
 }
 
-/// Sends a ping request to ClamAV
-///
-/// This function establishes a connection to a ClamAV server and sends the PING
-/// command to it. If the server is available, it responds with [`PONG`].
+#[cfg(unix)]
+impl<P: AsRef<Path>, A: ToSocketAddrs> TransportProtocol for SocketOrTcp<P, A> {
+    type Stream = SocketOrTcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        match UnixStream::connect(&self.socket_path).await {
+            Ok(stream) => Ok(SocketOrTcpStream::Socket(stream)),
+            Err(err) if super::fallback::falls_back(&err) => TcpStream::connect(&self.host_address)
+                .await
+                .map(SocketOrTcpStream::Tcp),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsyncRead for SocketOrTcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SocketOrTcpStream::Socket(stream) => Pin::new(stream).poll_read(cx, buf),
+            SocketOrTcpStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsyncWrite for SocketOrTcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            SocketOrTcpStream::Socket(stream) => Pin::new(stream).poll_write(cx, buf),
+            SocketOrTcpStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SocketOrTcpStream::Socket(stream) => Pin::new(stream).poll_flush(cx),
+            SocketOrTcpStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SocketOrTcpStream::Socket(stream) => Pin::new(stream).poll_shutdown(cx),
+            SocketOrTcpStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+impl<T: TransportProtocol + ?Sized> TransportProtocol for &T {
+    type Stream = T::Stream;
+
+    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
+        (**self).connect()
+    }
+
+    fn is_local(&self) -> bool {
+        (**self).is_local()
+    }
+}
+
+/// Resolves host names into socket addresses for [`ResolvingTcp`]
 ///
-/// # Arguments
+/// Implement this trait to control how the host name of a ClamAV server is
+/// resolved, e.g. to use a specific name server, a cache or a fixed address in
+/// tests.
 ///
-/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// # Example
 ///
-/// # Returns
+/// ```
+/// use std::{io, net::SocketAddr};
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// struct Localhost;
 ///
-/// # Example
+/// impl clamav_client::tokio::Resolve for Localhost {
+///     async fn resolve(&self, _host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+///         Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+///     }
+/// }
 ///
-/// ```
 /// # #[tokio::main(flavor = "current_thread")]
 /// # async fn main() {
-/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
-/// let clamd_available = match clamav_client::tokio::ping(clamd_tcp).await {
-///     Ok(ping_response) => ping_response == clamav_client::PONG,
-///     Err(_) => false,
-/// };
-/// # assert!(clamd_available);
+/// let clamd_tcp = clamav_client::tokio::ResolvingTcp::new("clamd.internal", 3310, Localhost);
+/// let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+/// assert_eq!(&response, clamav_client::PONG);
 /// # }
 /// ```
 ///
-pub async fn ping<T: TransportProtocol>(connection: T) -> IoResult {
-    let stream = connection.connect().await?;
-    send_command(stream, PING, Some(PONG.len())).await
+pub trait Resolve {
+    /// Returns the socket addresses of `host` with `port`, in the order in
+    /// which they are tried
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl std::future::Future<Output = io::Result<Vec<SocketAddr>>>;
 }
 
-/// Gets the version number from ClamAV
-///
-/// This function establishes a connection to a ClamAV server and sends the
-/// VERSION command to it. If the server is available, it responds with its
-/// version number.
-///
-/// # Arguments
-///
-/// * `connection`: The connection type to use - either TCP or a Unix socket connection
-///
-/// # Returns
+impl<R: Resolve + ?Sized> Resolve for &R {
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl std::future::Future<Output = io::Result<Vec<SocketAddr>>> {
+        (**self).resolve(host, port)
+    }
+}
+
+/// Resolves host names with the resolver of the operating system, like
+/// [`Tcp`] does, on the blocking thread pool of Tokio
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok(tokio::net::lookup_host((host, port)).await?.collect())
+    }
+}
+
+/// Resolves host names with [Hickory DNS](https://github.com/hickory-dns/hickory-dns)
+/// instead of the resolver of the operating system, without blocking
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// Use the feature flag "hickory-dns" to enable this type.
 ///
 /// # Example
 ///
 /// ```
 /// # #[tokio::main(flavor = "current_thread")]
 /// # async fn main() {
-/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
-/// let version = clamav_client::tokio::get_version(clamd_tcp).await.unwrap();
-/// # assert!(version.starts_with(b"ClamAV"));
+/// let resolver = clamav_client::tokio::HickoryResolver::from_system_conf().unwrap();
+/// let clamd_tcp = clamav_client::tokio::ResolvingTcp::new("localhost", 3310, resolver);
+/// let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+/// assert_eq!(&response, clamav_client::PONG);
 /// # }
 /// ```
 ///
-pub async fn get_version<T: TransportProtocol>(connection: T) -> IoResult {
-    let stream = connection.connect().await?;
-    send_command(stream, VERSION, None).await
+#[cfg(feature = "hickory-dns")]
+#[derive(Clone)]
+pub struct HickoryResolver {
+    resolver: hickory_resolver::TokioAsyncResolver,
 }
 
-/// Scans a file for viruses
-///
-/// This function reads data from a file located at the specified `file_path`
-/// and streams it to a ClamAV server for scanning.
+#[cfg(feature = "hickory-dns")]
+impl HickoryResolver {
+    /// Creates a resolver with the system configuration of DNS, e.g. from
+    /// `/etc/resolv.conf`
+    pub fn from_system_conf() -> io::Result<Self> {
+        hickory_resolver::TokioAsyncResolver::tokio_from_system_conf()
+            .map(Self::from)
+            .map_err(io::Error::other)
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl From<hickory_resolver::TokioAsyncResolver> for HickoryResolver {
+    fn from(resolver: hickory_resolver::TokioAsyncResolver) -> Self {
+        HickoryResolver { resolver }
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl std::fmt::Debug for HickoryResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HickoryResolver").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl Resolve for HickoryResolver {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let lookup = self
+            .resolver
+            .lookup_ip(host)
+            .await
+            .map_err(io::Error::other)?;
+        Ok(lookup.iter().map(|ip| SocketAddr::new(ip, port)).collect())
+    }
+}
+
+/// Discovers ClamAV servers from the DNS SRV records of a service name, e.g.
+/// `_clamav._tcp.example.com`, without blocking
 ///
-/// # Arguments
+/// This is the asynchronous version of
+/// [`SrvResolver`](crate::SrvResolver): as a [`Resolve`] implementation, it
+/// treats the host name of a [`ResolvingTcp`] as the service name, ignores its
+/// port and returns the addresses of all servers in the order of
+/// [`SrvEndpoint::ordered`](crate::SrvEndpoint::ordered).
 ///
-/// * `file_path`: The path to the file to be scanned
-/// * `connection`: The connection type to use - either TCP or a Unix socket connection
-/// * `chunk_size`: An optional chunk size for reading data. If [`None`], a default chunk size is used
+/// Use the feature flag "hickory-dns" to enable this type.
 ///
-/// # Returns
+/// # Example
 ///
-/// An [`IoResult`] containing the server's response as a vector of bytes
+/// ```no_run
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let resolver = clamav_client::tokio::SrvResolver::from_system_conf().unwrap();
+/// let clamd_tcp =
+///     clamav_client::tokio::ResolvingTcp::new("_clamav._tcp.example.com", 0, resolver);
+/// let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+/// # }
+/// ```
 ///
-pub async fn scan_file<P: AsRef<Path>, T: TransportProtocol>(
-    file_path: P,
-    connection: T,
-    chunk_size: Option<usize>,
-) -> IoResult {
-    let file = File::open(file_path).await?;
-    let stream = connection.connect().await?;
-    scan(file, chunk_size, stream).await
+#[cfg(feature = "hickory-dns")]
+#[derive(Debug, Clone)]
+pub struct SrvResolver {
+    resolver: HickoryResolver,
 }
 
-/// Scans a data buffer for viruses
+#[cfg(feature = "hickory-dns")]
+impl SrvResolver {
+    /// Creates a resolver with the system configuration of DNS, e.g. from
+    /// `/etc/resolv.conf`
+    pub fn from_system_conf() -> io::Result<Self> {
+        HickoryResolver::from_system_conf().map(Self::from)
+    }
+
+    /// Looks up the SRV records of `service_name` and returns the endpoints in
+    /// the order in which they should be tried
+    pub async fn endpoints(&self, service_name: &str) -> io::Result<Vec<crate::SrvEndpoint>> {
+        let lookup = self
+            .resolver
+            .resolver
+            .srv_lookup(service_name)
+            .await
+            .map_err(io::Error::other)?;
+        Ok(srv::endpoints_from_lookup(&lookup))
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl From<HickoryResolver> for SrvResolver {
+    fn from(resolver: HickoryResolver) -> Self {
+        SrvResolver { resolver }
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl Resolve for SrvResolver {
+    async fn resolve(&self, host: &str, _port: u16) -> io::Result<Vec<SocketAddr>> {
+        let mut addresses = Vec::new();
+        let mut last_err = None;
+        for endpoint in self.endpoints(host).await? {
+            match self.resolver.resolve(&endpoint.target, endpoint.port).await {
+                Ok(endpoint_addresses) => addresses.extend(endpoint_addresses),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        match last_err {
+            Some(err) if addresses.is_empty() => Err(err),
+            _ => Ok(addresses),
+        }
+    }
+}
+
+impl<R: Resolve> Resolve for crate::CachingResolver<R> {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if let Some(addresses) = self.cached(host, port) {
+            return Ok(addresses);
+        }
+        let addresses = self.resolver.resolve(host, port).await?;
+        self.store(host, port, &addresses);
+        Ok(addresses)
+    }
+}
+
+impl<R: Resolve> Resolve for crate::TimeoutResolver<R> {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        tokio::time::timeout(self.timeout, self.resolver.resolve(host, port))
+            .await
+            .unwrap_or_else(|_| self.timed_out(host))
+    }
+}
+
+/// Use a TCP connection to communicate with a ClamAV server whose host name is
+/// resolved with a [`Resolve`] implementation
+///
+/// The host name is resolved again for each connection, and the addresses are
+/// tried in order until a connection succeeds.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use std::time::Duration;
+///
+/// // Fail instead of waiting for an unresponsive name server
+/// let resolver = clamav_client::TimeoutResolver::new(
+///     clamav_client::tokio::SystemResolver,
+///     Duration::from_secs(2),
+/// );
+/// let clamd_tcp = clamav_client::tokio::ResolvingTcp::new("localhost", 3310, resolver);
+/// let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+/// assert_eq!(&response, clamav_client::PONG);
+/// # }
+/// ```
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvingTcp<H: AsRef<str>, R: Resolve> {
+    /// The host name or IP address of the ClamAV server
+    pub host: H,
+    /// The port of the ClamAV server
+    pub port: u16,
+    /// The resolver of the host name
+    pub resolver: R,
+}
+
+impl<H: AsRef<str>, R: Resolve> ResolvingTcp<H, R> {
+    /// Creates a TCP connection to `host` and `port` that resolves `host` with
+    /// `resolver`
+    pub fn new(host: H, port: u16, resolver: R) -> Self {
+        ResolvingTcp {
+            host,
+            port,
+            resolver,
+        }
+    }
+}
+
+impl<H: AsRef<str>, R: Resolve> TransportProtocol for ResolvingTcp<H, R> {
+    type Stream = TcpStream;
+
+    async fn connect(&self) -> io::Result<Self::Stream> {
+        let addresses = self.resolver.resolve(self.host.as_ref(), self.port).await?;
+        TcpStream::connect(&addresses[..]).await
+    }
+}
+
+/// A transport whose concrete type is erased (see
+/// [`BoxedTransport`](crate::BoxedTransport))
+///
+/// The TCP and Unix socket transports of this crate convert into a
+/// `BoxedTransport` with [`From`]. Other transports, e.g. a TLS wrapper, are
+/// erased with [`from_fn`](Self::from_fn), since the futures of a generic
+/// transport cannot be shown to be [`Send`], which is needed to scan from
+/// spawned tasks. The transport is cheap to clone, since clones share the
+/// wrapped transport.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use clamav_client::tokio::BoxedTransport;
+///
+/// let transport = if let Ok(socket_path) = std::env::var("CLAMD_SOCKET") {
+///     BoxedTransport::from(clamav_client::tokio::Socket { socket_path })
+/// } else {
+///     BoxedTransport::from_fn(|| async {
+///         // E.g. wrap the stream in TLS here
+///         tokio::net::TcpStream::connect("localhost:3310").await
+///     })
+/// };
+/// let client = clamav_client::tokio::Client::new(transport);
+/// # assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+/// # }
+/// ```
+///
+#[derive(Clone)]
+pub struct BoxedTransport {
+    connect: Arc<dyn Fn() -> BoxedConnect + Send + Sync>,
+    is_local: bool,
+}
+
+type BoxedConnect = Pin<Box<dyn std::future::Future<Output = io::Result<BoxedStream>> + Send>>;
+
+impl BoxedTransport {
+    /// Creates a transport that connects with the future returned by
+    /// `connect`
+    pub fn from_fn<F, C, S>(connect: F) -> Self
+    where
+        F: Fn() -> C + Send + Sync + 'static,
+        C: std::future::Future<Output = io::Result<S>> + Send + 'static,
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        BoxedTransport {
+            connect: Arc::new(move || {
+                let connecting = connect();
+                Box::pin(async move { connecting.await.map(BoxedStream::new) })
+            }),
+            is_local: false,
+        }
+    }
+
+    /// Sets whether ClamAV runs on the same host and can read its files (see
+    /// [`TransportProtocol::is_local`])
+    pub fn local(mut self, is_local: bool) -> Self {
+        self.is_local = is_local;
+        self
+    }
+}
+
+impl std::fmt::Debug for BoxedTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxedTransport")
+            .field("is_local", &self.is_local)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TransportProtocol for BoxedTransport {
+    type Stream = BoxedStream;
+
+    fn connect(&self) -> impl std::future::Future<Output = io::Result<Self::Stream>> {
+        (self.connect)()
+    }
+
+    fn is_local(&self) -> bool {
+        self.is_local
+    }
+}
+
+impl<A: ToSocketAddrs + Send + Sync + 'static> From<Tcp<A>> for BoxedTransport {
+    fn from(transport: Tcp<A>) -> Self {
+        let transport = Arc::new(transport);
+        BoxedTransport::from_fn(move || {
+            let transport = Arc::clone(&transport);
+            async move { transport.connect().await }
+        })
+    }
+}
+
+#[cfg(unix)]
+impl<P: AsRef<Path> + Send + Sync + 'static> From<Socket<P>> for BoxedTransport {
+    fn from(transport: Socket<P>) -> Self {
+        let transport = Arc::new(transport);
+        BoxedTransport::from_fn(move || {
+            let transport = Arc::clone(&transport);
+            async move { transport.connect().await }
+        })
+        .local(true)
+    }
+}
+
+impl From<crate::ClamdConnection> for BoxedTransport {
+    fn from(transport: crate::ClamdConnection) -> Self {
+        let is_local = TransportProtocol::is_local(&transport);
+        let transport = Arc::new(transport);
+        BoxedTransport::from_fn(move || {
+            let transport = Arc::clone(&transport);
+            async move { TransportProtocol::connect(&*transport).await }
+        })
+        .local(is_local)
+    }
+}
+
+/// The stream of a [`BoxedTransport`], whose concrete type is erased
+pub struct BoxedStream {
+    stream: Pin<Box<dyn AsyncReadWrite + Send>>,
+}
+
+impl BoxedStream {
+    /// Erases the type of `stream`
+    pub fn new<S: AsyncRead + AsyncWrite + Send + 'static>(stream: S) -> Self {
+        BoxedStream {
+            stream: Box::pin(stream),
+        }
+    }
+}
+
+impl std::fmt::Debug for BoxedStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxedStream").finish_non_exhaustive()
+    }
+}
+
+trait AsyncReadWrite: AsyncRead + AsyncWrite {}
+
+impl<S: AsyncRead + AsyncWrite> AsyncReadWrite for S {}
+
+impl AsyncRead for BoxedStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.get_mut().stream.as_mut().poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for BoxedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().stream.as_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().stream.as_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().stream.as_mut().poll_shutdown(cx)
+    }
+}
+
+/// Sends a ping request to ClamAV
 ///
-/// This function streams the provided `buffer` data to a ClamAV server
+/// This function establishes a connection to a ClamAV server and sends the PING
+/// command to it. If the server is available, it responds with [`PONG`].
 ///
 /// # Arguments
 ///
-/// * `buffer`: The data to be scanned
 /// * `connection`: The connection type to use - either TCP or a Unix socket connection
-/// * `chunk_size`: An optional chunk size for reading data. If [`None`], a default chunk size is used
 ///
 /// # Returns
 ///
 /// An [`IoResult`] containing the server's response as a vector of bytes
 ///
-pub async fn scan_buffer<T: TransportProtocol>(
-    buffer: &[u8],
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let clamd_available = match clamav_client::tokio::ping(clamd_tcp).await {
+///     Ok(ping_response) => clamav_client::is_pong(&ping_response),
+///     Err(_) => false,
+/// };
+/// # assert!(clamd_available);
+/// # }
+/// ```
+///
+pub async fn ping<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect().await?;
+    send_command(stream, PING, Some(PONG.len())).await
+}
+
+/// Sends a ping request to ClamAV and writes the response into `response`
+///
+/// This function works like [`ping`], but clears `response` and reads the
+/// server's response into it instead of allocating a new vector, so that its
+/// capacity can be reused across calls.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let mut response = Vec::with_capacity(clamav_client::PONG.len());
+/// clamav_client::tokio::ping_into(clamd_tcp, &mut response).await.unwrap();
+/// assert_eq!(&response, clamav_client::PONG);
+/// # }
+/// ```
+///
+pub async fn ping_into<T: TransportProtocol>(
     connection: T,
-    chunk_size: Option<usize>,
-) -> IoResult {
+    response: &mut Vec<u8>,
+) -> io::Result<()> {
     let stream = connection.connect().await?;
-    scan(buffer, chunk_size, stream).await
+    send_command_into(
+        stream,
+        PING,
+        response,
+        &ResponseBuffer::expecting(Some(PONG.len())),
+    )
+    .await
 }
 
-/// Scans a stream for viruses
+/// Checks that ClamAV responds to a ping request with [`PONG`]
 ///
-/// This function sends the provided stream to a ClamAV server for scanning.
+/// This function reads the response into a fixed-size buffer on the stack and
+/// does not allocate if ClamAV responds with [`PONG`]. A response that is
+/// longer than [`PONG`] or differs from it fails with
+/// [`ClamdError::InvalidResponse`].
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// clamav_client::tokio::ping_ok(clamd_tcp).await.unwrap();
+/// # }
+/// ```
+///
+pub async fn ping_ok<T: TransportProtocol>(connection: T) -> Result<(), ClamdError> {
+    let mut stream = connection.connect().await?;
+    stream.write_all(PING).await.phase(ErrorPhase::Command)?;
+    stream.flush().await.phase(ErrorPhase::Command)?;
+    wire::command(PING);
+
+    // One byte more than `PONG` to detect longer responses
+    let mut response = [0; PONG.len() + 1];
+    let mut len = 0;
+    while len < response.len() {
+        match stream
+            .read(&mut response[len..])
+            .await
+            .phase(ErrorPhase::Response)
+        {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    wire::response(&response[..len]);
+    response::expect_pong(&response[..len])
+}
+
+/// Checks whether ClamAV is available, i.e. whether it responds to a ping
+/// request with [`PONG`]
+///
+/// This function works like [`ping_ok`], but returns `false` instead of an
+/// error, e.g. if the connection fails, for readiness checks that only need a
+/// yes or no.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// if !clamav_client::tokio::is_available(clamd_tcp).await {
+///     eprintln!("Cannot ping clamd at {}", clamd_tcp.host_address);
+/// }
+/// # assert!(clamav_client::tokio::is_available(clamd_tcp).await);
+/// # }
+/// ```
+///
+pub async fn is_available<T: TransportProtocol>(connection: T) -> bool {
+    ping_ok(connection).await.is_ok()
+}
+
+/// Gets the version number from ClamAV
+///
+/// This function establishes a connection to a ClamAV server and sends the
+/// VERSION command to it. If the server is available, it responds with its
+/// version number.
 ///
 /// # Arguments
 ///
-/// * `input_stream`: The stream to be scanned
 /// * `connection`: The connection type to use - either TCP or a Unix socket connection
-/// * `chunk_size`: An optional chunk size for reading data. If [`None`], a default chunk size is used
 ///
 /// # Returns
 ///
 /// An [`IoResult`] containing the server's response as a vector of bytes
 ///
-#[cfg(feature = "tokio-stream")]
-pub async fn scan_stream<
-    S: Stream<Item = Result<bytes::Bytes, io::Error>>,
-    T: TransportProtocol,
->(
-    input_stream: S,
-    connection: T,
-    chunk_size: Option<usize>,
-) -> IoResult {
-    let output_stream = connection.connect().await?;
-    _scan_stream(input_stream, chunk_size, output_stream).await
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let version = clamav_client::tokio::get_version(clamd_tcp).await.unwrap();
+/// # assert!(version.starts_with(b"ClamAV"));
+/// # }
+/// ```
+///
+pub async fn get_version<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect().await?;
+    send_command(stream, VERSION, None).await
 }
 
-/// Shuts down a ClamAV server
+/// Gets the version of ClamAV and of its signature databases
+///
+/// This function works like [`get_version`], but parses the response into a
+/// [`VersionInfo`].
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let version = clamav_client::tokio::version(clamd_tcp).await.unwrap();
+/// println!("ClamAV {}", version.clamav_version);
+/// # }
+/// ```
+///
+pub async fn version<T: TransportProtocol>(connection: T) -> Result<VersionInfo, ClamdError> {
+    VersionInfo::from_response(&get_version(connection).await?)
+}
+
+/// Reloads the virus databases of ClamAV
 ///
 /// This function establishes a connection to a ClamAV server and sends the
-/// SHUTDOWN command to it. If the server is available, it will perform a clean
-/// exit and shut itself down. The response will be empty.
+/// RELOAD command to it. The server responds with `RELOADING` and reloads its
+/// virus databases in the background.
 ///
 /// # Arguments
 ///
@@ -285,9 +1304,2854 @@ pub async fn scan_stream<
 ///
 /// # Returns
 ///
-/// An [`IoResult`] containing the server's response
+/// An [`IoResult`] containing the server's response as a vector of bytes
 ///
-pub async fn shutdown<T: TransportProtocol>(connection: T) -> IoResult {
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::tokio::reload(clamd_tcp).await.unwrap();
+/// # assert_eq!(&response, b"RELOADING\0");
+/// # }
+/// ```
+///
+pub async fn reload<T: TransportProtocol>(connection: T) -> IoResult {
     let stream = connection.connect().await?;
-    send_command(stream, SHUTDOWN, None).await
+    send_command(stream, RELOAD, None).await
+}
+
+/// Gets statistics about the scan queue, threads and memory usage of ClamAV
+///
+/// This function establishes a connection to a ClamAV server and sends the
+/// STATS command to it. The server responds with a multi-line report that
+/// ends with `END`.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::tokio::get_stats(clamd_tcp).await.unwrap();
+/// # assert!(stats.starts_with(b"POOLS:"));
+/// # }
+/// ```
+///
+pub async fn get_stats<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect().await?;
+    send_command(stream, STATS, None).await
+}
+
+/// Gets the queue length, threads and memory usage of ClamAV
+///
+/// This function works like [`get_stats`], but parses the response into
+/// [`ServerStats`].
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::tokio::server_stats(clamd_tcp).await.unwrap();
+/// println!("{} of {} threads busy", stats.busy_threads(), stats.max_threads);
+/// # }
+/// ```
+///
+pub async fn server_stats<T: TransportProtocol>(connection: T) -> Result<ServerStats, ClamdError> {
+    ServerStats::from_response(&get_stats(connection).await?)
+}
+
+/// Gets the age of the signature databases of ClamAV
+///
+/// This function sends the VERSION command to ClamAV and returns the time
+/// elapsed since the date of the databases in the response (see
+/// [`database_date`](crate::database_date)). A date in the future results in
+/// an age of zero.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// The age of the databases, or a [`ClamdError`] if the connection fails or the
+/// response has no date
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let age = clamav_client::tokio::database_age(clamd_tcp).await.unwrap();
+/// println!("The signatures are {} hours old", age.as_secs() / 3600);
+/// # }
+/// ```
+///
+pub async fn database_age<T: TransportProtocol>(connection: T) -> Result<Duration, ClamdError> {
+    let response = get_version(connection).await?;
+    database::age_of(&response)
+}
+
+/// Checks whether the signature databases of ClamAV are older than `max_age`
+/// (see [`is_database_stale`](crate::is_database_stale))
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `max_age`: The maximum age of up-to-date databases
+///
+/// # Returns
+///
+/// `true` if the databases are older than `max_age`, or a [`ClamdError`] if the
+/// age cannot be determined (see [`database_age`])
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use std::time::Duration;
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let max_age = Duration::from_secs(48 * 60 * 60);
+/// if clamav_client::tokio::is_database_stale(clamd_tcp, max_age).await.unwrap() {
+///     eprintln!("The signatures of ClamAV are outdated");
+/// }
+/// # }
+/// ```
+///
+pub async fn is_database_stale<T: TransportProtocol>(
+    connection: T,
+    max_age: Duration,
+) -> Result<bool, ClamdError> {
+    database_age(connection).await.map(|age| age > max_age)
+}
+
+/// Checks whether ClamAV is healthy, e.g. for a readiness probe
+///
+/// This function pings ClamAV, gets its version to determine the age of the
+/// signature databases and, if enabled in the options, scans the EICAR test
+/// file, each over its own connection (see
+/// [`health_check`](crate::health_check)).
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::HealthCheckOptions::new().self_scan(true);
+/// let report = clamav_client::tokio::health_check(clamd_tcp, &options).await;
+/// if !report.is_ready() {
+///     eprintln!("ClamAV is not ready: {:?}", report.problems);
+/// }
+/// # assert!(report.is_ready());
+/// # }
+/// ```
+///
+pub async fn health_check<T: TransportProtocol>(
+    connection: T,
+    options: &HealthCheckOptions,
+) -> HealthReport {
+    let mut report = HealthReport::new();
+    let start = Instant::now();
+    let response = ping(&connection).await;
+    if !report.ping(options, response, start.elapsed()) {
+        return report;
+    }
+    report.version(options, get_version(&connection).await);
+    if options.self_scan {
+        report.self_scan(scan_buffer(EICAR, &connection, None).await);
+    }
+    report
+}
+
+/// Measures the latency of ClamAV by pinging it `samples` times, e.g. to pick
+/// the closest of several servers at startup (see
+/// [`measure_latency`](crate::measure_latency))
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let stats = clamav_client::tokio::measure_latency(clamd_tcp, 10).await.unwrap();
+/// println!("{:?} on average, {:?} at the 95th percentile", stats.avg, stats.p95);
+/// # assert_eq!(stats.samples, 10);
+/// # }
+/// ```
+///
+pub async fn measure_latency<T: TransportProtocol>(
+    connection: T,
+    samples: usize,
+) -> Result<LatencyStats, ClamdError> {
+    let mut measured = LatencySamples::new(samples.max(1));
+    for _ in 0..samples.max(1) {
+        let start = Instant::now();
+        let stream = connection.connect().await?;
+        let connected = Instant::now();
+        let response = send_command(stream, PING, Some(PONG.len())).await?;
+        measured.push(connected - start, connected.elapsed(), &response)?;
+    }
+    Ok(measured.stats())
+}
+
+/// Scans a data buffer on several ClamAV servers at the same time and combines
+/// their verdicts (see
+/// [`scan_buffer_cross_checked`](crate::scan_buffer_cross_checked))
+///
+/// The scans run concurrently within the current task.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let endpoints = [
+///     clamav_client::tokio::Tcp{ host_address: "localhost:3310" },
+///     clamav_client::tokio::Tcp{ host_address: "127.0.0.1:3310" },
+/// ];
+/// let check = clamav_client::tokio::scan_buffer_cross_checked(b"clean data", endpoints, None).await;
+/// assert!(check.all_clean());
+/// # }
+/// ```
+///
+pub async fn scan_buffer_cross_checked<B, E, T, O>(
+    buffer: B,
+    endpoints: E,
+    options: O,
+) -> CrossCheck
+where
+    B: AsRef<[u8]>,
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let buffer = buffer.as_ref();
+    let options = options.into();
+    let endpoints: Vec<T> = endpoints.into_iter().collect();
+    let options = &options;
+    let scans = endpoints.iter().map(|connection| async move {
+        scan_buffer(buffer, connection, options)
+            .await
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response))
+    });
+    CrossCheck::new(concurrency::join_bounded(scans, endpoints.len()).await)
+}
+
+/// Scans a file on several ClamAV servers at the same time and combines their
+/// verdicts (see [`scan_file_cross_checked`](crate::scan_file_cross_checked))
+///
+/// The file is opened once per endpoint, and the scans run concurrently within
+/// the current task.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let endpoints = [
+///     clamav_client::tokio::Tcp{ host_address: "localhost:3310" },
+///     clamav_client::tokio::Tcp{ host_address: "127.0.0.1:3310" },
+/// ];
+/// let check =
+///     clamav_client::tokio::scan_file_cross_checked("tests/data/eicar.txt", endpoints, None).await;
+/// assert!(check.all_infected());
+/// # }
+/// ```
+///
+pub async fn scan_file_cross_checked<P, E, T, O>(
+    file_path: P,
+    endpoints: E,
+    options: O,
+) -> CrossCheck
+where
+    P: AsRef<Path>,
+    E: IntoIterator<Item = T>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let file_path = file_path.as_ref();
+    let options = options.into();
+    let endpoints: Vec<T> = endpoints.into_iter().collect();
+    let options = &options;
+    let scans = endpoints.iter().map(|connection| async move {
+        scan_file(file_path, connection, options)
+            .await
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response))
+    });
+    CrossCheck::new(concurrency::join_bounded(scans, endpoints.len()).await)
+}
+
+/// Scans a file for viruses
+///
+/// This function reads data from a file located at the specified `file_path`
+/// and streams it to a ClamAV server for scanning.
+///
+/// # Arguments
+///
+/// * `file_path`: The path to the file to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the file exceeds the configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded)
+/// without connecting to the server, unless it is scanned by its path (see
+/// [`ScanOptions::path_fallback`]).
+///
+pub async fn scan_file<P: AsRef<Path>, T: TransportProtocol, O: Into<ScanOptions>>(
+    file_path: P,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    let file_path = file_path.as_ref();
+    let mut file = File::open(file_path)
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?;
+    let file_size = file
+        .metadata()
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?
+        .len();
+    if let Err(err) = options.check_input_size(file_size) {
+        // An empty file is rejected rather than scanned by its path
+        if file_size > 0 && options.path_fallback && connection.is_local() {
+            let path: std::path::PathBuf = tokio::fs::canonicalize(file_path)
+                .await
+                .phase_of(ErrorPhase::Input, file_path.display())?;
+            let command = path_command(PathCommand::Scan, &path)?;
+            let stream = connection.connect().await?;
+            return send_command(stream, &command, None).await;
+        }
+        return Err(err);
+    }
+    check_archive(&mut file, file_size, &options).await?;
+    if let Some(split_size) = options.split_size.filter(|&size| file_size > size) {
+        return scan_split(file, file_size, split_size, &connection, &options).await;
+    }
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+    scan(file, Some(file_size), &options, stream, report).await
+}
+
+/// Scans a region of a file for viruses
+///
+/// This function streams the `length` bytes of the file at `file_path` that
+/// start at `offset` to a ClamAV server, as if they were a file of their own,
+/// e.g. to scan an object embedded in a container without extracting it
+/// first.
+///
+/// # Arguments
+///
+/// * `file_path`: The path to the file that contains the region
+/// * `offset`: The offset of the first byte of the region
+/// * `length`: The number of bytes of the region
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the region extends beyond the end of the file, the scan fails with an
+/// [`io::Error`] of kind [`InvalidInput`](io::ErrorKind::InvalidInput), and if
+/// it exceeds the configured maximum stream size, with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded),
+/// both without connecting to the server.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let response = clamav_client::tokio::scan_file_range("README.md", 0, 64, clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// # }
+/// ```
+///
+pub async fn scan_file_range<P: AsRef<Path>, T: TransportProtocol, O: Into<ScanOptions>>(
+    file_path: P,
+    offset: u64,
+    length: u64,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    let file_path = file_path.as_ref();
+    let mut file = File::open(file_path)
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?;
+    let file_size = file
+        .metadata()
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?
+        .len();
+    super::check_file_range(file_size, offset, length)?;
+    options.check_input_size(length)?;
+    file.seek(io::SeekFrom::Start(offset))
+        .await
+        .phase_of(ErrorPhase::Input, file_path.display())?;
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+    scan(file.take(length), Some(length), &options, stream, report).await
+}
+
+/// Checks a file of `file_size` bytes against the archive limits of the
+/// options and rewinds it
+async fn check_archive(file: &mut File, file_size: u64, options: &ScanOptions) -> io::Result<()> {
+    let mut check = match ArchiveCheck::start(options.archive_limits, file_size) {
+        Some(check) => check,
+        None => return Ok(()),
+    };
+    let mut buffer = DataBuffer::default();
+    while let Some((offset, len)) = check.next_read() {
+        file.seek(io::SeekFrom::Start(offset)).await?;
+        let bytes = buffer.get(len);
+        file.read_exact(bytes).await?;
+        check.read(bytes)?;
+    }
+    file.seek(io::SeekFrom::Start(0)).await?;
+    Ok(())
+}
+
+/// Scans a data buffer for viruses
+///
+/// This function streams the provided `buffer` data to a ClamAV server. The
+/// chunks are written directly from `buffer`, without copying them into a
+/// chunk buffer.
+///
+/// # Arguments
+///
+/// * `buffer`: The data to be scanned, e.g. a `&[u8]`, `Vec<u8>`, `String` or `Bytes`
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the buffer exceeds the configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded)
+/// without connecting to the server.
+///
+pub async fn scan_buffer<B: AsRef<[u8]>, T: TransportProtocol, O: Into<ScanOptions>>(
+    buffer: B,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let buffer = buffer.as_ref();
+    let options = options.into();
+    let buffer_size = buffer.len() as u64;
+    options.check_input_size(buffer_size)?;
+    options.check_archive(std::io::Cursor::new(buffer), buffer_size)?;
+    if let Some(split_size) = options.split_size.filter(|&size| buffer_size > size) {
+        return scan_split(buffer, buffer_size, split_size, &connection, &options).await;
+    }
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+    scan_slice(buffer, &options, stream, report).await
+}
+
+/// Scans shared bytes for viruses
+///
+/// This function works like [`scan_buffer`], but takes the [`Bytes`](bytes::Bytes)
+/// of e.g. a request body by value, so that the scan owns a reference to the
+/// shared buffer instead of borrowing it. The chunks are written as slices of
+/// the shared buffer, so the data is never copied.
+///
+/// Use the feature flag "bytes" to enable this function.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let bytes = bytes::Bytes::from_static(b"clean data");
+/// let response = clamav_client::tokio::scan_bytes(bytes.clone(), clamd_tcp, None).await.unwrap();
+/// # assert_eq!(clamav_client::clean(&response), Ok(true));
+/// # }
+/// ```
+///
+#[cfg(feature = "bytes")]
+pub async fn scan_bytes<T: TransportProtocol, O: Into<ScanOptions>>(
+    bytes: bytes::Bytes,
+    connection: T,
+    options: O,
+) -> IoResult {
+    scan_buffer(bytes, connection, options).await
+}
+
+/// Scans a data buffer for viruses and writes the response into `response`
+///
+/// This function works like [`scan_buffer`], but clears `response` and reads
+/// the server's response into it instead of allocating a new vector. The
+/// chunks are written directly from `buffer`, so that a scan does not need a
+/// chunk buffer either. Only scans that are split with
+/// [`ScanOptions::split_size`] still allocate their responses internally.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::ScanOptions::default();
+/// let mut response = Vec::new();
+/// for buffer in [&b"hello"[..], &b"world"[..]] {
+///     clamav_client::tokio::scan_buffer_into(buffer, &clamd_tcp, &options, &mut response)
+///         .await
+///         .unwrap();
+///     assert_eq!(clamav_client::clean(&response), Ok(true));
+/// }
+/// # }
+/// ```
+///
+pub async fn scan_buffer_into<B: AsRef<[u8]>, T: TransportProtocol>(
+    buffer: B,
+    connection: T,
+    options: &ScanOptions,
+    response: &mut Vec<u8>,
+) -> io::Result<()> {
+    let buffer = buffer.as_ref();
+    let buffer_size = buffer.len() as u64;
+    options.check_input_size(buffer_size)?;
+    options.check_archive(std::io::Cursor::new(buffer), buffer_size)?;
+    if let Some(split_size) = options.split_size.filter(|&size| buffer_size > size) {
+        let split_response =
+            scan_split(buffer, buffer_size, split_size, &connection, options).await?;
+        response.clear();
+        response.extend_from_slice(&split_response);
+        return Ok(());
+    }
+    options.validate()?;
+    let mut report = ReportTimer::start(options);
+    let stream = report.connect(connection.connect().await)?;
+    scan_slice_into(buffer, options, stream, report, response).await
+}
+
+/// Scans the data of a reader for viruses
+///
+/// This function streams the data read from `reader` to a ClamAV server for
+/// scanning until the reader reaches its end. The reader is pinned internally,
+/// so it does not need to be [`Unpin`], e.g. a decoder built with an `async`
+/// block.
+///
+/// # Arguments
+///
+/// * `reader`: The reader of the data to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the data exceeds the configured maximum stream size, the scan is aborted
+/// with [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded).
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let file = tokio::fs::File::open("README.md").await.unwrap();
+/// let response = clamav_client::tokio::scan_reader(file, clamd_tcp, None).await.unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// # }
+/// ```
+///
+pub async fn scan_reader<R: AsyncRead, T: TransportProtocol, O: Into<ScanOptions>>(
+    reader: R,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+    scan(reader, None, &options, stream, report).await
+}
+
+/// Runs a command and scans its standard output for viruses
+///
+/// This function spawns `command` with its standard output piped and streams
+/// the output to a ClamAV server while the command runs, without storing it,
+/// e.g. to scan an artifact decrypted by an external tool. If the scan fails
+/// before the output has been read to its end, the command is killed.
+///
+/// # Arguments
+///
+/// * `command`: The command to run
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the output exceeds the configured maximum stream size, the scan is aborted
+/// with [`ClamdError::SizeLimitExceeded`]. If the command exits unsuccessfully,
+/// its output may be incomplete and the scan fails with an [`io::Error`] of
+/// kind [`Other`](io::ErrorKind::Other).
+///
+/// Use the feature flag "process" to enable this function.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use tokio::process::Command;
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let mut command = Command::new("cat");
+/// command.arg("README.md");
+/// let response = clamav_client::tokio::scan_command_output(&mut command, clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// # }
+/// ```
+///
+#[cfg(feature = "process")]
+pub async fn scan_command_output<T: TransportProtocol, O: Into<ScanOptions>>(
+    command: &mut Command,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let stream = report.connect(connection.connect().await)?;
+
+    let mut child = command.stdout(Stdio::piped()).spawn()?;
+    let output = child.stdout.take().expect("the standard output is piped");
+    let response = match scan(output, None, &options, stream, report).await {
+        Ok(response) => response,
+        Err(err) => {
+            let _ = child.kill().await;
+            return Err(err);
+        }
+    };
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(io::Error::other(format!("command exited with {}", status)));
+    }
+    Ok(response)
+}
+
+/// Scans a stream for viruses
+///
+/// This function sends the provided stream to a ClamAV server for scanning.
+///
+/// # Arguments
+///
+/// * `input_stream`: The stream to be scanned, e.g. a request body of `axum`, `hyper` or `reqwest`, with items of any buffer type, e.g. `Bytes`, `Vec<u8>` or `&[u8]`, and any error type that converts into `Box<dyn Error + Send + Sync>`
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the stream exceeds the configured maximum stream size, the scan is aborted
+/// with [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded).
+/// If the stream fails, the scan is aborted with its error, which is wrapped
+/// in an [`io::Error`] of kind [`Other`](io::ErrorKind::Other) unless it is an
+/// [`io::Error`] or a [`ClamdError`](crate::ClamdError).
+///
+pub async fn scan_stream<S, B, E, T, O>(input_stream: S, connection: T, options: O) -> IoResult
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let output_stream = report.connect(connection.connect().await)?;
+    _scan_stream(input_stream, &options, output_stream, report).await
+}
+
+/// Scans a stream for viruses while writing it to a spool file, and commits
+/// the file to `destination` only if the stream is clean
+///
+/// This function writes each item of the stream to a hidden spool file in the
+/// directory of `destination` while it is streamed to ClamAV, e.g. an upload
+/// that must not be persisted before it has been scanned. If ClamAV finds no
+/// virus, or only a tolerated signature, the spool file is synced and renamed
+/// to `destination`, which replaces an existing file atomically. Otherwise, or
+/// if the scan fails or is dropped, the spool file is removed.
+///
+/// # Arguments
+///
+/// * `input_stream`: The stream to be scanned and persisted, with the item types accepted by [`scan_stream`]
+/// * `destination`: The path of the file to commit the data to
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// The [`CommittedScan`] with the outcome of the scan and the path of the
+/// committed file, if any, or a [`ClamdError`] if the stream could not be
+/// scanned or written
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let upload = futures_util::stream::iter([Ok::<_, std::io::Error>(&b"clean data"[..])]);
+/// let destination = std::env::temp_dir().join("upload.txt");
+/// let scan = clamav_client::tokio::scan_and_commit(upload, &destination, clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// assert_eq!(scan.path, Some(destination));
+/// # }
+/// ```
+///
+pub async fn scan_and_commit<S, B, E, P, T, O>(
+    input_stream: S,
+    destination: P,
+    connection: T,
+    options: O,
+) -> Result<CommittedScan, ClamdError>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]> + Unpin,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    P: AsRef<Path>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let destination = destination.as_ref();
+    let spool_path = SpoolPath::next_to(destination)?;
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(spool_path.path())
+        .await?;
+    let mut spool_error = None;
+    let spooled = SpoolStream {
+        input: std::pin::pin!(input_stream),
+        file: &mut file,
+        pending: None,
+        error: &mut spool_error,
+    };
+    let result = scan_stream(spooled, connection, options).await;
+    if let Some(err) = spool_error {
+        return Err(err.into());
+    }
+    let outcome = ScanOutcome::from_response(&result?)?;
+    if !spool::is_committable(&outcome) {
+        return Ok(CommittedScan::new(outcome, None));
+    }
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(spool_path.path(), destination).await?;
+    spool_path.committed();
+    Ok(CommittedScan::new(outcome, Some(destination.to_owned())))
+}
+
+/// A stream that writes each item of its input to a spool file before passing
+/// it on
+struct SpoolStream<'a, S, B> {
+    input: Pin<&'a mut S>,
+    file: &'a mut File,
+    /// An item and how much of it has been written
+    pending: Option<(B, usize)>,
+    error: &'a mut Option<io::Error>,
+}
+
+impl<S, B, E> Stream for SpoolStream<'_, S, B>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]> + Unpin,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Item = Result<B, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((bytes, written)) = &mut this.pending {
+                while *written < bytes.as_ref().len() {
+                    let result = match Pin::new(&mut *this.file)
+                        .poll_write(cx, &bytes.as_ref()[*written..])
+                    {
+                        Poll::Ready(Ok(0)) => Err(io::Error::from(io::ErrorKind::WriteZero)),
+                        Poll::Ready(result) => result,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    match result {
+                        Ok(len) => *written += len,
+                        Err(err) => {
+                            // The error is returned instead of the error of the scan
+                            *this.error = Some(err);
+                            this.pending = None;
+                            return Poll::Ready(Some(Err(
+                                "the spool file could not be written".into()
+                            )));
+                        }
+                    }
+                }
+                return Poll::Ready(this.pending.take().map(|(bytes, _)| Ok(bytes)));
+            }
+            match this.input.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(bytes))) => this.pending = Some((bytes, 0)),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err.into()))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Extends streams of buffers with [`virus_scan`](ScanStreamExt::virus_scan),
+/// to scan them at the end of a chain of stream adapters
+///
+/// The trait is implemented for all streams. Its method only applies to
+/// streams whose items are results of buffers, like those of [`scan_stream`].
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use clamav_client::tokio::ScanStreamExt;
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let chunks = vec![Ok::<_, std::io::Error>(&b"clean "[..]), Ok(&b"data"[..])];
+/// let response = futures_util::stream::iter(chunks)
+///     .virus_scan(clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// assert_eq!(clamav_client::clean(&response), Ok(true));
+/// # }
+/// ```
+///
+pub trait ScanStreamExt: Stream {
+    /// Scans the stream for viruses, like [`scan_stream`] does
+    fn virus_scan<B, E, T, O>(
+        self,
+        connection: T,
+        options: O,
+    ) -> impl std::future::Future<Output = IoResult>
+    where
+        Self: Stream<Item = Result<B, E>> + Sized,
+        B: AsRef<[u8]>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+        T: TransportProtocol,
+        O: Into<ScanOptions>,
+    {
+        scan_stream(self, connection, options)
+    }
+}
+
+impl<S: Stream + ?Sized> ScanStreamExt for S {}
+
+/// Starts a scan whose data is pushed to ClamAV through a [`ScanSink`]
+///
+/// This function connects to a ClamAV server and starts an INSTREAM command.
+/// The data is then sent with the [`Sink`](futures_sink::Sink) interface of
+/// the returned [`ScanSink`], e.g. chunk by chunk as it arrives from a
+/// websocket, and the response is read when the sink is closed.
+///
+/// Use the feature flag "sink" to enable this function.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// The [`ScanSink`], or an [`io::Error`] if the connection fails
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use futures_util::SinkExt;
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let mut sink = clamav_client::tokio::scan_sink(clamd_tcp, None).await.unwrap();
+/// sink.send(bytes::Bytes::from_static(b"clean ")).await.unwrap();
+/// sink.send(bytes::Bytes::from_static(b"data")).await.unwrap();
+/// let response = sink.finish().await.unwrap();
+/// assert_eq!(&response, b"stream: OK\0");
+/// # }
+/// ```
+///
+#[cfg(feature = "sink")]
+pub async fn scan_sink<T: TransportProtocol, O: Into<ScanOptions>>(
+    connection: T,
+    options: O,
+) -> io::Result<ScanSink<T::Stream>> {
+    let options = options.into();
+    let chunk_size = options.chunk_size_for(None)?;
+    let mut stream = connection.connect().await?;
+    stream.write_all(INSTREAM).await.phase(ErrorPhase::Stream)?;
+    wire::command(INSTREAM);
+    Ok(ScanSink {
+        stream,
+        chunk_size,
+        options,
+        state: SinkState::Streaming,
+        pending: bytes::Bytes::new(),
+        written: 0,
+        stream_size: 0,
+        chunks_sent: 0,
+        start: Instant::now(),
+        response: Vec::new(),
+    })
+}
+
+/// A scan that receives its data through the [`Sink`](futures_sink::Sink)
+/// interface, created by [`scan_sink`]
+///
+/// Each [`Bytes`](bytes::Bytes) sent to the sink is streamed to ClamAV in
+/// chunks of at most the chunk size of the [`ScanOptions`]. Closing the sink
+/// terminates the stream and reads the response of ClamAV, which is then
+/// available from [`response`](Self::response). If more data than the
+/// configured maximum stream size is sent, sending fails with
+/// [`ClamdError::SizeLimitExceeded`]. Progress callbacks are called for each
+/// chunk, but the data is not throttled and no [`ScanReport`](crate::ScanReport)
+/// is created.
+///
+/// If the sink is dropped before it is closed, the write half of its
+/// connection is shut down, so that ClamAV stops waiting for the rest of the
+/// stream. After an error, the sink cannot be used anymore.
+///
+/// Use the feature flag "sink" to enable this type.
+#[cfg(feature = "sink")]
+pub struct ScanSink<S: AsyncWrite + Unpin> {
+    stream: S,
+    options: ScanOptions,
+    chunk_size: usize,
+    state: SinkState,
+    pending: bytes::Bytes,
+    written: usize,
+    stream_size: u64,
+    chunks_sent: u64,
+    start: Instant,
+    response: Vec<u8>,
+}
+
+/// The state of a [`ScanSink`]
+#[cfg(feature = "sink")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SinkState {
+    Streaming,
+    Terminating,
+    Reading,
+    Closed,
+    Failed,
+}
+
+#[cfg(feature = "sink")]
+impl<S: AsyncRead + AsyncWrite + Unpin> ScanSink<S> {
+    /// Returns the response of ClamAV once the sink is closed
+    pub fn response(&self) -> Option<&[u8]> {
+        (self.state == SinkState::Closed).then_some(&self.response[..])
+    }
+
+    /// Closes the sink and returns the response of ClamAV
+    pub async fn finish(mut self) -> IoResult {
+        SinkClose(&mut self).await?;
+        Ok(mem::take(&mut self.response))
+    }
+
+    /// Writes the pending data in chunks, each with its length as header
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let len = self.pending.len().min(self.chunk_size);
+            let mut frame = Frame::resume(&self.pending[..len], self.written);
+            while !frame.is_written() {
+                let result = Pin::new(&mut self.stream).poll_write_vectored(cx, &frame.remaining());
+                let result = frame.advance(ready!(result));
+                self.written = frame.written();
+                result?;
+            }
+            self.written = 0;
+            let _ = self.pending.split_to(len);
+            self.chunk_sent(len);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn chunk_sent(&mut self, len: usize) {
+        wire::chunk(len as u64);
+        self.chunks_sent += 1;
+        if let Some(callback) = &self.options.progress {
+            (callback.0)(&ScanProgress {
+                bytes_sent: self.stream_size - self.pending.len() as u64,
+                total_bytes: None,
+                chunks_sent: self.chunks_sent,
+                elapsed: self.start.elapsed(),
+            });
+        }
+    }
+
+    /// Fails if the sink cannot be used anymore
+    fn check_state(&self, state: SinkState) -> io::Result<()> {
+        match self.state {
+            SinkState::Failed => Err(io::Error::other("the scan sink failed before")),
+            current if current != state => Err(io::Error::other("the scan sink is closed")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Marks the sink as failed if `result` is an error
+    fn track<T>(&mut self, result: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if let Poll::Ready(Err(_)) = result {
+            self.state = SinkState::Failed;
+        }
+        result
+    }
+}
+
+#[cfg(feature = "sink")]
+impl<S: AsyncRead + AsyncWrite + Unpin> futures_sink::Sink<bytes::Bytes> for ScanSink<S> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.check_state(SinkState::Streaming)?;
+        let result = this.poll_write_pending(cx);
+        this.track(result)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: bytes::Bytes) -> io::Result<()> {
+        let this = self.get_mut();
+        this.check_state(SinkState::Streaming)?;
+        debug_assert!(this.pending.is_empty(), "poll_ready must be called first");
+        this.stream_size += item.len() as u64;
+        if let Err(err) = this.options.check_stream_size(this.stream_size) {
+            this.state = SinkState::Failed;
+            return Err(err);
+        }
+        this.pending = item;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.state != SinkState::Streaming {
+            return Poll::Ready(Ok(()));
+        }
+        let result = match this.poll_write_pending(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.stream).poll_flush(cx),
+            result => result,
+        };
+        this.track(result)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            match this.state {
+                SinkState::Streaming => {
+                    let result = this.poll_write_pending(cx);
+                    ready!(this.track(result))?;
+                    if this.stream_size == 0 && this.options.reject_empty {
+                        this.state = SinkState::Failed;
+                        return Poll::Ready(Err(ClamdError::EmptyInput.into()));
+                    }
+                    this.state = SinkState::Terminating;
+                }
+                SinkState::Terminating => {
+                    while this.written < END_OF_STREAM.len() {
+                        let result = Pin::new(&mut this.stream)
+                            .poll_write(cx, &END_OF_STREAM[this.written..]);
+                        match ready!(this.track(result))? {
+                            0 => {
+                                this.state = SinkState::Failed;
+                                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                            }
+                            n => this.written += n,
+                        }
+                    }
+                    let result = Pin::new(&mut this.stream).poll_flush(cx);
+                    ready!(this.track(result))?;
+                    wire::end_of_stream();
+                    this.state = SinkState::Reading;
+                }
+                SinkState::Reading => {
+                    let mut buffer = [0; 1024];
+                    let mut buffer = io::ReadBuf::new(&mut buffer);
+                    let result = Pin::new(&mut this.stream).poll_read(cx, &mut buffer);
+                    ready!(this.track(result))?;
+                    if buffer.filled().is_empty() {
+                        wire::response(&this.response);
+                        this.state = SinkState::Closed;
+                    } else {
+                        this.response.extend_from_slice(buffer.filled());
+                    }
+                }
+                SinkState::Closed => return Poll::Ready(Ok(())),
+                SinkState::Failed => return Poll::Ready(this.check_state(SinkState::Closed)),
+            }
+        }
+    }
+}
+
+/// A future that closes a [`ScanSink`]
+#[cfg(feature = "sink")]
+struct SinkClose<'a, S: AsyncWrite + Unpin>(&'a mut ScanSink<S>);
+
+#[cfg(feature = "sink")]
+impl<S: AsyncRead + AsyncWrite + Unpin> Future for SinkClose<'_, S> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let sink = Pin::new(&mut *self.get_mut().0);
+        futures_sink::Sink::<bytes::Bytes>::poll_close(sink, cx)
+    }
+}
+
+#[cfg(feature = "sink")]
+impl<S: AsyncWrite + Unpin> Drop for ScanSink<S> {
+    fn drop(&mut self) {
+        if self.state == SinkState::Streaming {
+            // The shutdown cannot be awaited here, so it is attempted once,
+            // like the shutdown of a dropped scan
+            let waker = concurrency::noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let _ = Pin::new(&mut self.stream).poll_shutdown(&mut cx);
+        }
+    }
+}
+
+/// Downloads a URL and scans the response body for viruses
+///
+/// This function sends a GET request to `url` and streams the response body
+/// straight to a ClamAV server for scanning as it is received, without
+/// buffering it in memory or on disk. Use [`scan_response`] to send the request
+/// with your own [`reqwest::Client`], e.g. with custom headers or timeouts.
+///
+/// HTTPS URLs require one of reqwest's TLS features, e.g. `rustls-tls`, to be
+/// enabled in your own `Cargo.toml`.
+///
+/// Use the feature flag "reqwest" to enable this function.
+///
+/// # Arguments
+///
+/// * `url`: The URL of the content to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. See
+/// [`scan_response`] for the errors.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let url = "http://example.com/upload.pdf";
+/// let response = clamav_client::tokio::scan_url(url, clamd_tcp, None).await.unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # }
+/// ```
+///
+#[cfg(feature = "reqwest")]
+pub async fn scan_url<U: reqwest::IntoUrl, T: TransportProtocol, O: Into<ScanOptions>>(
+    url: U,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let response = reqwest::get(url).await.map_err(into_io_error)?;
+    scan_response(response, connection, options).await
+}
+
+/// Scans the body of an HTTP response for viruses
+///
+/// This function streams the body of `response` to a ClamAV server for
+/// scanning as it is received.
+///
+/// Use the feature flag "reqwest" to enable this function.
+///
+/// # Arguments
+///
+/// * `response`: The response whose body is to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes.
+/// Responses with an error status (4xx or 5xx) are not scanned but fail with
+/// an [`io::Error`] of kind [`Other`](io::ErrorKind::Other) that wraps the
+/// [`reqwest::Error`], as do failures while receiving the body. If the
+/// `Content-Length` of the response or the received body exceeds the
+/// configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded).
+///
+#[cfg(feature = "reqwest")]
+pub async fn scan_response<T: TransportProtocol, O: Into<ScanOptions>>(
+    response: reqwest::Response,
+    connection: T,
+    options: O,
+) -> IoResult {
+    let options = options.into();
+    let response = response.error_for_status().map_err(into_io_error)?;
+    if let Some(content_length) = response.content_length() {
+        options.check_input_size(content_length)?;
+    }
+    let body = response
+        .bytes_stream()
+        .map(|chunk| chunk.map_err(into_io_error));
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let output_stream = report.connect(connection.connect().await)?;
+    _scan_stream(body, &options, output_stream, report).await
+}
+
+#[cfg(feature = "reqwest")]
+fn into_io_error(err: reqwest::Error) -> io::Error {
+    io::Error::other(err)
+}
+
+/// An adapter that turns the body of an S3 object into a stream accepted by
+/// [`scan_stream`]
+///
+/// Use the feature flag "s3" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use aws_sdk_s3::primitives::ByteStream;
+/// use clamav_client::tokio::S3ByteStream;
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let body = ByteStream::from_static(b"clean data");
+/// let response = clamav_client::tokio::scan_stream(S3ByteStream::from(body), clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// # assert_eq!(&response, b"stream: OK\0");
+/// # }
+/// ```
+///
+#[cfg(feature = "s3")]
+pub struct S3ByteStream(aws_sdk_s3::primitives::ByteStream);
+
+#[cfg(feature = "s3")]
+impl From<aws_sdk_s3::primitives::ByteStream> for S3ByteStream {
+    fn from(byte_stream: aws_sdk_s3::primitives::ByteStream) -> Self {
+        S3ByteStream(byte_stream)
+    }
+}
+
+#[cfg(feature = "s3")]
+impl Stream for S3ByteStream {
+    type Item = Result<bytes::Bytes, io::Error>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::pin::Pin::new(&mut self.get_mut().0)
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map_err(io::Error::from)))
+    }
+}
+
+/// Downloads an S3 object and scans it for viruses
+///
+/// This function gets the object `key` from `bucket` and streams its body to a
+/// ClamAV server for scanning as it is received.
+///
+/// Use the feature flag "s3" to enable this function.
+///
+/// # Arguments
+///
+/// * `client`: The S3 client to get the object with
+/// * `bucket`: The bucket of the object
+/// * `key`: The key of the object
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response as a vector of bytes. If
+/// the object does not exist, the scan fails with an [`io::Error`] of kind
+/// [`NotFound`](io::ErrorKind::NotFound); other S3 errors fail it with an
+/// [`io::Error`] of kind [`Other`](io::ErrorKind::Other). If the object
+/// exceeds the configured maximum stream size, the scan fails with
+/// [`ClamdError::SizeLimitExceeded`](crate::ClamdError::SizeLimitExceeded)
+/// without connecting to the server.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn example(config: aws_sdk_s3::Config) {
+/// let s3_client = aws_sdk_s3::Client::from_conf(config);
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let response =
+///     clamav_client::tokio::scan_s3_object(&s3_client, "uploads", "report.pdf", clamd_tcp, None)
+///         .await
+///         .unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # }
+/// ```
+///
+#[cfg(feature = "s3")]
+pub async fn scan_s3_object<T: TransportProtocol, O: Into<ScanOptions>>(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    key: &str,
+    connection: T,
+    options: O,
+) -> IoResult {
+    use aws_sdk_s3::operation::get_object::GetObjectError;
+
+    let options = options.into();
+    let object = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(|err| match err.as_service_error() {
+            Some(GetObjectError::NoSuchKey(_)) => io::Error::new(io::ErrorKind::NotFound, err),
+            _ => io::Error::other(err),
+        })?;
+    if let Some(content_length) = object.content_length() {
+        options.check_input_size(content_length.max(0) as u64)?;
+    }
+    options.validate()?;
+    let mut report = ReportTimer::start(&options);
+    let output_stream = report.connect(connection.connect().await)?;
+    _scan_stream(
+        S3ByteStream::from(object.body),
+        &options,
+        output_stream,
+        report,
+    )
+    .await
+}
+
+/// A field of a multipart form scanned by [`scan_multipart`]
+///
+/// Use the feature flag "multipart" to enable this type.
+///
+#[cfg(feature = "multipart")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct MultipartField {
+    /// The name of the field
+    pub name: Option<String>,
+    /// The file name of the field, which makes it a file field
+    pub file_name: Option<String>,
+    /// The content type of the field
+    pub content_type: Option<String>,
+    /// The result of scanning a file field, or [`None`] for other fields,
+    /// which are not scanned
+    pub result: Option<Result<ScanOutcome, ClamdError>>,
+    /// The contents of the field if it is a clean file field or not a file
+    /// field, or [`None`] if it is infected or could not be scanned
+    pub data: Option<bytes::Bytes>,
+}
+
+#[cfg(feature = "multipart")]
+impl MultipartField {
+    /// Returns whether the contents of the field can be used, i.e. the field
+    /// is a clean file field or not a file field
+    pub fn is_clean(&self) -> bool {
+        self.data.is_some()
+    }
+}
+
+/// Scans the file fields of a multipart form for viruses as they stream in
+///
+/// This function reads the fields of `multipart` in order and streams each
+/// file field (a field with a file name) to a ClamAV server over its own
+/// connection while it is received. The contents of clean file fields and of
+/// the other fields are kept and returned with the results, so they should be
+/// limited using the [`Constraints`](multer::Constraints) of `multipart`.
+/// File fields are also limited by the configured maximum stream size.
+///
+/// Use the feature flag "multipart" to enable this function.
+///
+/// # Arguments
+///
+/// * `multipart`: The multipart form to read, e.g. the body of an upload request
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// A [`MultipartField`] with the result and, if clean, the contents of each
+/// field in the order of the form. If the form is malformed, the scan fails
+/// with an [`io::Error`] of kind [`InvalidData`](io::ErrorKind::InvalidData).
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// # let body = "--X\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\r\nclean data\r\n--X--\r\n";
+/// # let request_body = tokio_stream::iter([Ok::<_, std::io::Error>(body)]);
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let multipart = multer::Multipart::new(request_body, "X");
+/// let fields = clamav_client::tokio::scan_multipart(multipart, clamd_tcp, None)
+///     .await
+///     .unwrap();
+/// for field in fields {
+///     if !field.is_clean() {
+///         println!("Rejected field {:?}: {:?}", field.name, field.result);
+///     }
+/// }
+/// # }
+/// ```
+///
+#[cfg(feature = "multipart")]
+pub async fn scan_multipart<T, O>(
+    mut multipart: multer::Multipart<'_>,
+    connection: T,
+    options: O,
+) -> Result<Vec<MultipartField>, ClamdError>
+where
+    T: TransportProtocol + Clone,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let mut fields = Vec::new();
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        let name = field.name().map(str::to_owned);
+        let file_name = field.file_name().map(str::to_owned);
+        let content_type = field.content_type().map(ToString::to_string);
+
+        let mut chunks = Vec::new();
+        let result = if file_name.is_some() {
+            let mut parse_error = None;
+            let stream = (&mut field).map(|chunk| match chunk {
+                Ok(chunk) => {
+                    chunks.push(chunk.clone());
+                    Ok(chunk)
+                }
+                Err(err) => {
+                    let io_err = io::Error::new(io::ErrorKind::InvalidData, err.to_string());
+                    parse_error = Some(err);
+                    Err(io_err)
+                }
+            });
+            let result = scan_field(stream, connection.clone(), &options).await;
+            if let Some(err) = parse_error {
+                return Err(multipart_error(err));
+            }
+            Some(result)
+        } else {
+            None
+        };
+
+        // Read the rest of a file field that could not be scanned, or all of
+        // a field that is not scanned
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(multipart_error)?;
+            if result.is_none() {
+                chunks.push(chunk);
+            }
+        }
+        let data = match &result {
+            None | Some(Ok(ScanOutcome::Clean)) => Some(chunks.concat().into()),
+            Some(_) => None,
+        };
+        fields.push(MultipartField {
+            name,
+            file_name,
+            content_type,
+            result,
+            data,
+        });
+    }
+    Ok(fields)
+}
+
+#[cfg(feature = "multipart")]
+pub(crate) async fn scan_field<S, T>(
+    stream: S,
+    connection: T,
+    options: &ScanOptions,
+) -> Result<ScanOutcome, ClamdError>
+where
+    S: Stream<Item = Result<bytes::Bytes, io::Error>>,
+    T: TransportProtocol,
+{
+    options.validate()?;
+    let mut report = ReportTimer::start(options);
+    let output_stream = report.connect(connection.connect().await)?;
+    let response = _scan_stream(stream, options, output_stream, report).await?;
+    ScanOutcome::from_response(&response)
+}
+
+#[cfg(feature = "multipart")]
+fn multipart_error(err: multer::Error) -> ClamdError {
+    io::Error::new(io::ErrorKind::InvalidData, err).into()
+}
+
+/// Shuts down a ClamAV server
+///
+/// This function establishes a connection to a ClamAV server and sends the
+/// SHUTDOWN command to it. If the server is available, it will perform a clean
+/// exit and shut itself down. The response will be empty.
+///
+/// # Arguments
+///
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+///
+/// # Returns
+///
+/// An [`IoResult`] containing the server's response
+///
+pub async fn shutdown<T: TransportProtocol>(connection: T) -> IoResult {
+    let stream = connection.connect().await?;
+    send_command(stream, SHUTDOWN, None).await
+}
+
+/// Scans multiple data buffers for viruses using a single connection
+///
+/// This function establishes one connection to a ClamAV server, starts a
+/// session (IDSESSION) and streams each buffer to the server in turn. This
+/// avoids a new connection per buffer when scanning many small buffers. With
+/// a [pipeline depth](ScanOptions::pipeline_depth) greater than one, the next
+/// buffers are streamed while responses are still outstanding.
+///
+/// # Arguments
+///
+/// * `buffers`: The data buffers to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+///
+/// # Returns
+///
+/// The [`ScanOutcome`] of each buffer, in the order of the input. If any buffer
+/// exceeds the configured maximum stream size, the batch fails with
+/// [`ClamdError::SizeLimitExceeded`] without connecting to the server.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let buffers = [&b"clean data"[..], &b"more clean data"[..]];
+/// let outcomes = clamav_client::tokio::scan_buffers(buffers, clamd_tcp, None).await.unwrap();
+/// # assert!(outcomes.iter().all(|outcome| outcome.is_clean()));
+/// # }
+/// ```
+///
+pub async fn scan_buffers<I, B, T, O>(
+    buffers: I,
+    connection: T,
+    options: O,
+) -> Result<Vec<ScanOutcome>, ClamdError>
+where
+    I: IntoIterator<Item = B>,
+    B: AsRef<[u8]>,
+    T: TransportProtocol,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    // A pipeline depth of zero would wait for a response before the first
+    // buffer is sent
+    options.validate()?;
+    let buffers: Vec<B> = buffers.into_iter().collect();
+    for buffer in &buffers {
+        let buffer_size = buffer.as_ref().len() as u64;
+        options.check_input_size(buffer_size)?;
+        options.check_archive(std::io::Cursor::new(buffer.as_ref()), buffer_size)?;
+    }
+    if buffers.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut stream = BufReader::new(connection.connect().await?);
+    stream
+        .get_mut()
+        .write_all(IDSESSION)
+        .await
+        .phase(ErrorPhase::Handshake)?;
+    wire::command(IDSESSION);
+
+    let mut chunk_buffer = DataBuffer::default();
+    let mut response = Vec::new();
+    let duplicates = Duplicates::of_buffers(&buffers, options.deduplicate);
+    let mut pipeline = Pipeline::new(&options, duplicates.distinct().len());
+    for &index in duplicates.distinct() {
+        if pipeline.is_full() {
+            receive_pipelined(&mut stream, &mut response, &mut pipeline).await?;
+        }
+        let buffer = buffers[index].as_ref();
+        let buffer_size = Some(buffer.len() as u64);
+        let mut report = ReportTimer::start(&options);
+        report.connected();
+        write_instream(
+            buffer,
+            buffer_size,
+            &options,
+            stream.get_mut(),
+            &mut chunk_buffer,
+            &mut report,
+        )
+        .await?;
+        pipeline.sent(report);
+    }
+    while !pipeline.is_empty() {
+        receive_pipelined(&mut stream, &mut response, &mut pipeline).await?;
+    }
+
+    stream
+        .get_mut()
+        .write_all(END)
+        .await
+        .phase(ErrorPhase::Command)?;
+    wire::command(END);
+    stream.get_mut().flush().await.phase(ErrorPhase::Command)?;
+    Ok(duplicates.fan_out(pipeline.into_outcomes()))
+}
+
+/// Reads the next response within a session and passes it to `pipeline`
+async fn receive_pipelined<R: AsyncRead + Unpin>(
+    stream: &mut BufReader<R>,
+    response: &mut Vec<u8>,
+    pipeline: &mut Pipeline<'_>,
+) -> Result<(), ClamdError> {
+    response.clear();
+    match stream.read_until(0, response).await {
+        Ok(0) => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+        result => result.map(drop),
+    }
+    .phase(ErrorPhase::Response)?;
+    pipeline.receive(response)
+}
+
+/// A session (IDSESSION) with ClamAV that is shared by concurrent requests
+///
+/// Within a session, ClamAV tags each response with the ID of its request, so
+/// that requests from many tasks can be in flight on one connection at the
+/// same time. Each request is written to the connection in turn, while a
+/// background task reads the responses and resolves the future of the
+/// request they belong to, in the order in which ClamAV completes them. This
+/// gives a high throughput with few connections, e.g. one session per
+/// ClamAV instance shared through an [`Arc`].
+///
+/// # Cancel safety
+///
+/// Dropping a future while it waits for its response does not affect other
+/// requests. Dropping a scan while its data is streamed to ClamAV leaves the
+/// connection in an undefined state, so all further requests fail with
+/// [`BrokenPipe`](io::ErrorKind::BrokenPipe), as they do after an error while
+/// writing a request, unless the session is set to
+/// [`reconnect`](Session::reconnect).
+///
+/// # Closing
+///
+/// [`close`](Session::close) ends the session with the END command and shuts
+/// the connection down gracefully. A dropped session tries the same without
+/// waiting, which succeeds unless the connection is busy, so ClamAV may log a
+/// broken pipe for it.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let session = clamav_client::tokio::Session::start(clamd_tcp, None).await.unwrap();
+///
+/// let (pong, outcome) = tokio::join!(session.ping(), session.scan_buffer(b"clean data"));
+/// assert_eq!(&pong.unwrap(), clamav_client::PONG);
+/// assert!(outcome.unwrap().is_clean());
+/// session.close().await.unwrap();
+/// # }
+/// ```
+///
+pub struct Session<T> {
+    connection: T,
+    writer: tokio::sync::Mutex<SessionWriter>,
+    options: ScanOptions,
+    reconnect: bool,
+    on_reconnect: Option<ReconnectHook>,
+}
+
+type ReconnectHook = Arc<dyn Fn(&Reconnected) + Send + Sync>;
+
+/// The connection of a [`Session`], with the write half and the task that
+/// reads the responses
+struct SessionWriter {
+    stream: Box<dyn AsyncWrite + Send + Unpin>,
+    chunk_buffer: DataBuffer,
+    last_id: u64,
+    broken: bool,
+    closed: bool,
+    pending: Arc<std::sync::Mutex<PendingRequests>>,
+    reader: tokio::task::JoinHandle<()>,
+    reconnects: u64,
+}
+
+impl SessionWriter {
+    /// Ends the session unless it was interrupted while a request was
+    /// written, shuts the connection down and waits until ClamAV closed it
+    async fn close(&mut self) -> io::Result<()> {
+        self.closed = true;
+        if !self.broken {
+            self.stream
+                .write_all(END)
+                .await
+                .phase(ErrorPhase::Command)?;
+            wire::command(END);
+        }
+        self.stream.shutdown().await.phase(ErrorPhase::Command)?;
+        // The reader ends when ClamAV closes the connection
+        let _ = (&mut self.reader).await;
+        Ok(())
+    }
+}
+
+impl Drop for SessionWriter {
+    fn drop(&mut self) {
+        if !self.closed {
+            // END cannot be awaited here, so it is written and the connection
+            // is shut down with one attempt each, which completes immediately
+            // unless the send buffer of the connection is full
+            let waker = concurrency::noop_waker();
+            let mut cx = std::task::Context::from_waker(&waker);
+            let mut stream = std::pin::Pin::new(&mut self.stream);
+            if !self.broken {
+                let written = stream.as_mut().poll_write(&mut cx, END);
+                if matches!(written, std::task::Poll::Ready(Ok(len)) if len == END.len()) {
+                    wire::command(END);
+                }
+            }
+            let _ = stream.poll_shutdown(&mut cx);
+        }
+        self.reader.abort();
+    }
+}
+
+/// The requests of a [`Session`] waiting for their response, by request ID
+#[derive(Default)]
+struct PendingRequests {
+    senders: HashMap<u64, oneshot::Sender<IoResult>>,
+    closed: Option<io::ErrorKind>,
+}
+
+/// A request sent within a [`Session`]
+enum SessionRequest<'a, 'o, R> {
+    Command(&'static [u8]),
+    Instream {
+        input: R,
+        input_size: u64,
+        report: &'a mut ReportTimer<'o>,
+    },
+}
+
+/// A note that a [`Session`] lost its connection and reconnected to ClamAV
+///
+/// Sessions with [`reconnect`](Session::reconnect) enabled pass it to the
+/// [`on_reconnect`](Session::on_reconnect) hook instead of failing the
+/// requests that were interrupted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Reconnected {
+    /// The kind of error with which the previous connection failed
+    pub cause: io::ErrorKind,
+    /// The number of times the session reconnected so far, including this
+    /// time
+    pub reconnects: u64,
+}
+
+impl<T> Session<T>
+where
+    T: TransportProtocol,
+    T::Stream: Send + 'static,
+{
+    /// Connects to ClamAV and starts a session that uses `options` for all
+    /// scans
+    ///
+    /// The responses are read by a task spawned on the current Tokio runtime,
+    /// which ends when the session is dropped. The session keeps `connection`
+    /// to be able to [`reconnect`](Self::reconnect).
+    ///
+    /// # Arguments
+    ///
+    /// * `connection`: The connection type to use - either TCP or a Unix socket connection
+    /// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+    pub async fn start<O: Into<ScanOptions>>(connection: T, options: O) -> io::Result<Self> {
+        let writer = Self::open(&connection).await?;
+        Ok(Session {
+            connection,
+            writer: tokio::sync::Mutex::new(writer),
+            options: options.into(),
+            reconnect: false,
+            on_reconnect: None,
+        })
+    }
+
+    /// Reconnects and starts a new session if the connection is lost, e.g.
+    /// because ClamAV was restarted, and retries the interrupted requests once
+    ///
+    /// Pings, version requests and scans can be retried safely, because they
+    /// do not change the state of ClamAV. The data of a scan is read again
+    /// from the start, i.e. files are opened again. Requests fail with the
+    /// error of the connection if reconnecting fails or the retried request is
+    /// interrupted as well. A session interrupted while a request was written
+    /// (see cancel safety above) is recovered the same way. Disabled by
+    /// default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+    /// let session = clamav_client::tokio::Session::start(clamd_tcp, None)
+    ///     .await
+    ///     .unwrap()
+    ///     .reconnect(true)
+    ///     .on_reconnect(|reconnected| eprintln!("clamd reconnected: {:?}", reconnected.cause));
+    ///
+    /// let pong = session.ping().await.unwrap();
+    /// assert_eq!(&pong, clamav_client::PONG);
+    /// # }
+    /// ```
+    ///
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Calls `on_reconnect` with a [`Reconnected`] note each time the session
+    /// reconnects
+    pub fn on_reconnect<F>(mut self, on_reconnect: F) -> Self
+    where
+        F: Fn(&Reconnected) + Send + Sync + 'static,
+    {
+        self.on_reconnect = Some(Arc::new(on_reconnect));
+        self
+    }
+
+    /// Ends the session with the END command, shuts the connection down and
+    /// waits until ClamAV closed it
+    ///
+    /// Requests whose futures were dropped before their response arrived are
+    /// still completed by ClamAV before it ends the session. A session that was
+    /// interrupted while a request was written is only shut down.
+    pub async fn close(self) -> io::Result<()> {
+        self.writer.into_inner().close().await
+    }
+
+    /// Sends a ping request to ClamAV (see [`ping`])
+    pub async fn ping(&self) -> IoResult {
+        let response = self.command(PING).await?;
+        wire::response(&response);
+        Ok(response)
+    }
+
+    /// Gets the version number from ClamAV (see [`get_version`])
+    pub async fn get_version(&self) -> IoResult {
+        let response = self.command(VERSION).await?;
+        wire::response(&response);
+        Ok(response)
+    }
+
+    /// Scans a file for viruses (see [`scan_file`])
+    pub async fn scan_file<P: AsRef<Path>>(&self, file_path: P) -> Result<ScanOutcome, ClamdError> {
+        let file_path = file_path.as_ref();
+        self.scan(|| async move {
+            let file = File::open(file_path)
+                .await
+                .phase_of(ErrorPhase::Input, file_path.display())?;
+            let file_size = file
+                .metadata()
+                .await
+                .phase_of(ErrorPhase::Input, file_path.display())?
+                .len();
+            Ok((file, file_size))
+        })
+        .await
+    }
+
+    /// Scans a data buffer for viruses (see [`scan_buffer`])
+    pub async fn scan_buffer<B: AsRef<[u8]>>(&self, buffer: B) -> Result<ScanOutcome, ClamdError> {
+        let buffer = buffer.as_ref();
+        self.scan(|| async move { Ok((buffer, buffer.len() as u64)) })
+            .await
+    }
+
+    /// Connects to ClamAV, starts a session and spawns the task that reads
+    /// its responses
+    async fn open(connection: &T) -> io::Result<SessionWriter> {
+        let mut stream = connection.connect().await?;
+        stream
+            .write_all(IDSESSION)
+            .await
+            .phase(ErrorPhase::Handshake)?;
+        stream.flush().await.phase(ErrorPhase::Handshake)?;
+        wire::command(IDSESSION);
+
+        let (reader, writer) = io::split(stream);
+        let pending = Arc::new(std::sync::Mutex::new(PendingRequests::default()));
+        let reader = tokio::spawn(read_session_responses(reader, Arc::clone(&pending)));
+        Ok(SessionWriter {
+            stream: Box::new(writer),
+            chunk_buffer: DataBuffer::default(),
+            last_id: 0,
+            broken: false,
+            closed: false,
+            pending,
+            reader,
+            reconnects: 0,
+        })
+    }
+
+    async fn command(&self, command: &'static [u8]) -> IoResult {
+        let mut retried = false;
+        loop {
+            let (reconnects, response) = self.send(SessionRequest::<&[u8]>::Command(command)).await;
+            match response {
+                Err(err) if !retried && self.recover(reconnects, &err).await? => retried = true,
+                response => return response,
+            }
+        }
+    }
+
+    /// Scans the input returned by `open`, which is called again if the scan
+    /// is retried after reconnecting
+    async fn scan<R, F, Fut>(&self, open: F) -> Result<ScanOutcome, ClamdError>
+    where
+        R: AsyncRead + Unpin,
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = io::Result<(R, u64)>>,
+    {
+        let mut retried = false;
+        loop {
+            let (input, input_size) = open().await?;
+            let mut report = ReportTimer::start(&self.options);
+            let request = SessionRequest::Instream {
+                input,
+                input_size,
+                report: &mut report,
+            };
+            let (reconnects, response) = self.send(request).await;
+            match response {
+                Ok(response) => {
+                    report.finish(&response);
+                    return ScanOutcome::from_response(&response);
+                }
+                Err(err) if !retried && self.recover(reconnects, &err).await? => retried = true,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Reconnects if `err` means that the connection was lost after
+    /// `reconnects` reconnects and reconnecting is enabled, and returns
+    /// whether the request should be retried
+    async fn recover(&self, reconnects: u64, err: &io::Error) -> io::Result<bool> {
+        let lost = matches!(
+            err.kind(),
+            io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::UnexpectedEof
+        );
+        if !self.reconnect || !lost {
+            return Ok(false);
+        }
+        let mut writer = self.writer.lock().await;
+        if writer.reconnects != reconnects {
+            // Another request already reconnected in the meantime
+            return Ok(true);
+        }
+        let mut reconnected = Self::open(&self.connection).await?;
+        reconnected.chunk_buffer = mem::take(&mut writer.chunk_buffer);
+        reconnected.reconnects = reconnects + 1;
+        // Dropping the previous connection stops its reader, which fails the
+        // requests still waiting for it, so that they are retried as well
+        *writer = reconnected;
+        drop(writer);
+        if let Some(on_reconnect) = &self.on_reconnect {
+            on_reconnect(&Reconnected {
+                cause: err.kind(),
+                reconnects: reconnects + 1,
+            });
+        }
+        Ok(true)
+    }
+
+    /// Writes `request` with the next request ID and waits for its response,
+    /// without the request ID
+    ///
+    /// Also returns the number of reconnects of the connection the request
+    /// was sent on.
+    async fn send<R: AsyncRead + Unpin>(
+        &self,
+        request: SessionRequest<'_, '_, R>,
+    ) -> (u64, IoResult) {
+        let (reconnects, response) = {
+            let mut writer = self.writer.lock().await;
+            let writer = &mut *writer;
+            let reconnects = writer.reconnects;
+            if writer.broken {
+                let err = io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "the session was interrupted while a request was written",
+                );
+                return (reconnects, Err(err));
+            }
+            // Invalid options and inputs are rejected before anything is
+            // written, so that they do not interrupt the session
+            if let SessionRequest::Instream { input_size, .. } = &request {
+                let checked = self
+                    .options
+                    .validate()
+                    .map_err(io::Error::from)
+                    .and_then(|()| self.options.check_input_size(*input_size));
+                if let Err(err) = checked {
+                    return (reconnects, Err(err));
+                }
+            }
+            let (sender, response) = oneshot::channel();
+            let id = writer.last_id + 1;
+            {
+                let mut pending = writer.pending.lock().unwrap();
+                if let Some(kind) = pending.closed {
+                    return (reconnects, Err(kind.into()));
+                }
+                pending.senders.insert(id, sender);
+            }
+            writer.last_id = id;
+
+            // Until the request is written completely, the connection is in an
+            // undefined state if the future is dropped or writing fails
+            writer.broken = true;
+            let written = match request {
+                SessionRequest::Command(command) => {
+                    wire::command(command);
+                    writer.stream.write_all(command).await
+                }
+                SessionRequest::Instream {
+                    input,
+                    input_size,
+                    report,
+                } => {
+                    report.connected();
+                    write_instream(
+                        input,
+                        Some(input_size),
+                        &self.options,
+                        &mut writer.stream,
+                        &mut writer.chunk_buffer,
+                        report,
+                    )
+                    .await
+                }
+            };
+            let written = match written {
+                Ok(()) => writer.stream.flush().await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = written {
+                writer.pending.lock().unwrap().senders.remove(&id);
+                return (reconnects, Err(err));
+            }
+            writer.broken = false;
+            (reconnects, response)
+        };
+        let response = response
+            .await
+            .unwrap_or_else(|_| Err(io::ErrorKind::UnexpectedEof.into()));
+        (reconnects, response)
+    }
+}
+
+/// Reads the responses within a [`Session`] and passes each one to the
+/// request with its request ID, until the connection is closed
+async fn read_session_responses<R: AsyncRead + Unpin>(
+    stream: R,
+    pending: Arc<std::sync::Mutex<PendingRequests>>,
+) {
+    let mut stream = BufReader::new(stream);
+    let mut response = Vec::new();
+    let err = loop {
+        response.clear();
+        match stream.read_until(0, &mut response).await {
+            Ok(0) => break io::Error::from(io::ErrorKind::UnexpectedEof),
+            Ok(_) => {}
+            Err(err) => break err,
+        }
+        let (id, request_response) = match outcome::split_request_id(&response) {
+            Some(split) => split,
+            None => {
+                // ClamAV answers errors of the session itself without an ID
+                // and closes the connection
+                let response = String::from_utf8_lossy(&response);
+                break io::Error::new(io::ErrorKind::InvalidData, response.trim_end_matches('\0'));
+            }
+        };
+        let sender = pending.lock().unwrap().senders.remove(&id);
+        if let Some(sender) = sender {
+            // The request may have been dropped in the meantime
+            let _ = sender.send(Ok(request_response.to_vec()));
+        }
+    };
+
+    let mut pending = pending.lock().unwrap();
+    pending.closed = Some(err.kind());
+    for (_, sender) in pending.senders.drain() {
+        let _ = sender.send(Err(io::Error::new(err.kind(), err.to_string())));
+    }
+}
+
+/// Scans multiple files for viruses concurrently
+///
+/// This function scans each file over its own connection, with at most
+/// `max_concurrency` scans in flight at any time.
+///
+/// # Arguments
+///
+/// * `file_paths`: The paths to the files to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `max_concurrency`: The maximum number of concurrent scans (and connections)
+///
+/// # Returns
+///
+/// The [`ScanResults`] with the path and the result of each scan, in the order
+/// of the input, and a [`ScanSummary`](crate::ScanSummary) of all scans
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let file_paths = ["README.md", "tests/data/eicar.txt"];
+/// let results = clamav_client::tokio::scan_files(file_paths, clamd_tcp, None, 4).await;
+/// for (path, result) in results {
+///     match result {
+///         Ok(outcome) if outcome.is_infected() => println!("{} is infected!", path.display()),
+///         Ok(_) => println!("No virus found in {}", path.display()),
+///         Err(err) => println!("Could not scan {}: {}", path.display(), err),
+///     }
+/// }
+/// # }
+/// ```
+///
+pub async fn scan_files<I, P, T, O>(
+    file_paths: I,
+    connection: T,
+    options: O,
+    max_concurrency: usize,
+) -> ScanResults<PathBuf>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    T: TransportProtocol + Clone,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let start = Instant::now();
+    let scans = file_paths.into_iter().map(|file_path| {
+        let file_path = PathBuf::from(file_path.as_ref());
+        let connection = connection.clone();
+        let options = &options;
+        async move {
+            let result = scan_file(&file_path, connection, options)
+                .await
+                .map_err(ClamdError::from)
+                .and_then(|response| ScanOutcome::from_response(&response));
+            (file_path, result)
+        }
+    });
+    let results = concurrency::join_bounded(scans, max_concurrency).await;
+    ScanResults::from_file_results(results, start.elapsed())
+}
+
+/// Scans all files in a directory tree for viruses, yielding the results as a
+/// stream
+///
+/// This function walks the directory tree at `dir_path` on the client side and
+/// streams each file to a ClamAV server for scanning, using a new connection
+/// per file. The results are yielded in the order of the blocking
+/// [`scan_directory`](crate::scan_directory). By default, files are scanned one
+/// after another, and each file is only scanned once the result of the
+/// previous one has been consumed;
+/// [`max_concurrency`](DirectoryScanOptions::max_concurrency) scans several
+/// files at the same time. The scans run within the stream, so dropping it
+/// cancels the scans in flight and closes their connections. Directory entries
+/// are listed using blocking file system calls.
+///
+/// # Arguments
+///
+/// * `dir_path`: The path to the directory to be scanned
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `directory_options`: The [`DirectoryScanOptions`] that control which files are scanned
+///
+/// # Returns
+///
+/// A stream of the path of each scanned file together with its
+/// [`ScanOutcome`], or the error that occurred while reading or scanning it
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use futures_util::StreamExt;
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+/// let mut results =
+///     clamav_client::tokio::scan_directory("tests/data", clamd_tcp, None, directory_options);
+/// while let Some((path, result)) = results.next().await {
+///     match result {
+///         Ok(outcome) if outcome.is_infected() => println!("{} is infected!", path.display()),
+///         Ok(_) => println!("No virus found in {}", path.display()),
+///         Err(err) => println!("Could not scan {}: {}", path.display(), err),
+///     }
+/// }
+/// # }
+/// ```
+///
+pub fn scan_directory<P, T, O>(
+    dir_path: P,
+    connection: T,
+    options: O,
+    directory_options: DirectoryScanOptions,
+) -> impl Stream<Item = (PathBuf, Result<ScanOutcome, ClamdError>)> + Unpin
+where
+    P: AsRef<Path>,
+    T: TransportProtocol + Clone,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let walk = directory::Walk::new(dir_path.as_ref(), directory_options);
+    directory::DirectoryScan::new(walk, move |file_path: PathBuf| {
+        let connection = connection.clone();
+        let options = options.clone();
+        async move {
+            let result = scan_file(&file_path, connection, options)
+                .await
+                .map_err(ClamdError::from)
+                .and_then(|response| ScanOutcome::from_response(&response));
+            (file_path, result)
+        }
+    })
+}
+
+/// Runs a scan until it finishes or `stop` completes
+///
+/// This function cancels `scan`, e.g. a call of [`scan_file`] or
+/// [`Client::scan_buffer`], as soon as `stop` completes. The `stop` future can
+/// be anything that completes on cancellation, e.g.
+/// [`CancellationToken::cancelled`](https://docs.rs/tokio-util/latest/tokio_util/sync/struct.CancellationToken.html#method.cancelled)
+/// of `tokio-util` when the client of a request disconnects, or
+/// [`tokio::time::sleep`] for a request deadline. The cancelled scan is dropped
+/// immediately, which closes its connection to ClamAV.
+///
+/// # Arguments
+///
+/// * `scan`: The scan to run
+/// * `stop`: The future that cancels the scan when it completes
+///
+/// # Returns
+///
+/// The result of `scan`, or [`ClamdError::Cancelled`] if `stop` completed
+/// first. For functions returning an [`IoResult`], the error is wrapped in an
+/// [`io::Error`].
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use tokio_util::sync::CancellationToken;
+///
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let token = CancellationToken::new();
+/// let scan = clamav_client::tokio::scan_file("README.md", clamd_tcp, None);
+/// let response = clamav_client::tokio::cancellable(scan, token.cancelled())
+///     .await
+///     .unwrap();
+/// let data_clean = clamav_client::clean(&response).unwrap();
+/// # assert_eq!(data_clean, true);
+/// # }
+/// ```
+///
+pub async fn cancellable<F, S, R, E>(scan: F, stop: S) -> Result<R, E>
+where
+    F: std::future::Future<Output = Result<R, E>>,
+    S: std::future::Future,
+    E: From<ClamdError>,
+{
+    concurrency::cancellable(scan, stop).await
+}
+
+/// Caps the number of concurrent requests to ClamAV across all clients that
+/// share it
+///
+/// ClamAV handles at most `MaxThreads` requests at a time and queues or
+/// rejects the others, so many clients scanning at once can overload it. A
+/// [`Client`] with a [`limiter`](Client::limiter) waits for a permit before
+/// each request, and clones of a `ScanLimiter` share their permits, so that
+/// requests beyond the limit wait in the client instead. Requests answered
+/// from the cache do not need a permit.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use std::time::Duration;
+///
+/// let limiter = clamav_client::tokio::ScanLimiter::new(8).queue_timeout(Duration::from_secs(5));
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let uploads = clamav_client::tokio::Client::new(clamd_tcp).limiter(limiter.clone());
+/// let imports = clamav_client::tokio::Client::new(clamd_tcp).limiter(limiter);
+///
+/// let (upload, import) = tokio::join!(uploads.scan_buffer(b"clean data"), imports.scan_file("README.md"));
+/// assert!(upload.unwrap().is_clean());
+/// assert!(import.unwrap().is_clean());
+/// # }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ScanLimiter {
+    semaphore: Arc<tokio::sync::Semaphore>,
+    max_scans: usize,
+    queue_timeout: Option<Duration>,
+}
+
+/// A permit of a [`ScanLimiter`], which is returned when it is dropped
+#[derive(Debug)]
+pub struct ScanPermit {
+    _permit: tokio::sync::OwnedSemaphorePermit,
+}
+
+impl ScanLimiter {
+    /// Creates a limiter that allows at most `max_scans` concurrent requests,
+    /// but at least one
+    pub fn new(max_scans: usize) -> Self {
+        let max_scans = max_scans.max(1);
+        ScanLimiter {
+            semaphore: Arc::new(tokio::sync::Semaphore::new(max_scans)),
+            max_scans,
+            queue_timeout: None,
+        }
+    }
+
+    /// Fails requests that wait longer than `queue_timeout` for a permit with
+    /// [`TimedOut`](io::ErrorKind::TimedOut), instead of waiting indefinitely
+    pub fn queue_timeout(mut self, queue_timeout: Duration) -> Self {
+        self.queue_timeout = Some(queue_timeout);
+        self
+    }
+
+    /// Returns the maximum number of concurrent requests
+    pub fn max_scans(&self) -> usize {
+        self.max_scans
+    }
+
+    /// Returns the number of requests that can start without waiting
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    /// Waits for a permit, in the order in which the requests arrive
+    ///
+    /// Fails with [`TimedOut`](io::ErrorKind::TimedOut) if the queue timeout
+    /// elapses first.
+    pub async fn acquire(&self) -> io::Result<ScanPermit> {
+        let semaphore = Arc::clone(&self.semaphore);
+        let permit = with_timeout(self.queue_timeout, async {
+            semaphore.acquire_owned().await.map_err(io::Error::other)
+        })
+        .await?;
+        Ok(ScanPermit { _permit: permit })
+    }
+}
+
+/// A reusable client for a ClamAV server
+///
+/// The client keeps the connection type and the [`ScanOptions`] of all scans,
+/// and returns a typed [`ScanOutcome`] instead of the raw server response.
+/// With the feature flag "cache", it can consult a
+/// [`ScanCache`](crate::ScanCache) before sending content to the server.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+/// let options = clamav_client::ScanOptions::new().chunk_size(8192);
+/// let client = clamav_client::tokio::Client::new(clamd_tcp).options(options);
+///
+/// let outcome = client.scan_file("README.md").await.unwrap();
+/// assert!(outcome.is_clean());
+/// # }
+/// ```
+///
+#[derive(Clone)]
+pub struct Client<T> {
+    connection: T,
+    options: ScanOptions,
+    stats: Arc<StatsCounters>,
+    timeout: Option<Duration>,
+    limiter: Option<ScanLimiter>,
+    failure_policy: FailurePolicy,
+    verdict_policy: Option<Arc<dyn VerdictPolicy>>,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<dyn ScanCache>>,
+    #[cfg(feature = "audit")]
+    audit_log: Option<Arc<AuditLog>>,
+}
+
+impl<T: TransportProtocol> Client<T> {
+    /// Creates a client that uses `connection` and the default scan options
+    pub fn new(connection: T) -> Self {
+        let stats = Arc::new(StatsCounters::new());
+        let mut options = ScanOptions::default();
+        options.hooks.stats = Some(Arc::clone(&stats));
+        Client {
+            connection,
+            options,
+            stats,
+            timeout: None,
+            limiter: None,
+            failure_policy: FailurePolicy::default(),
+            verdict_policy: None,
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "audit")]
+            audit_log: None,
+        }
+    }
+
+    /// Sets the [`ScanOptions`] used by all scans of this client
+    ///
+    /// The lifecycle hooks of the client are kept.
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        let hooks = mem::take(&mut self.options.hooks);
+        self.options = options.into();
+        self.options.hooks = hooks;
+        self
+    }
+
+    /// Calls `on_connect` with the time it took to connect to ClamAV, after
+    /// the connection of each scan is established
+    ///
+    /// Like all hooks, it is called from the task that performs the scan, so
+    /// it should return quickly.
+    pub fn on_connect<F>(mut self, on_connect: F) -> Self
+    where
+        F: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.options.hooks.connect = Some(Arc::new(on_connect));
+        self
+    }
+
+    /// Calls `on_chunk_sent` with the size in bytes of each chunk sent to
+    /// ClamAV
+    pub fn on_chunk_sent<F>(mut self, on_chunk_sent: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.options.hooks.chunk_sent = Some(Arc::new(on_chunk_sent));
+        self
+    }
+
+    /// Calls `on_response` with the [`ScanOutcome`] of each response of
+    /// ClamAV
+    ///
+    /// It is not called for outcomes found in the cache.
+    pub fn on_response<F>(mut self, on_response: F) -> Self
+    where
+        F: Fn(&ScanOutcome) + Send + Sync + 'static,
+    {
+        self.options.hooks.response = Some(Arc::new(on_response));
+        self
+    }
+
+    /// Calls `on_error` with the [`ClamdError`] of each failed scan, e.g. if
+    /// ClamAV is not available
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: Fn(&ClamdError) + Send + Sync + 'static,
+    {
+        self.options.hooks.error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Passes each virus found by ClamAV to `sink`, in addition to the sinks
+    /// added before
+    ///
+    /// Detections of outcomes found in the cache are not passed.
+    pub fn detection_sink<S: DetectionSink + 'static>(mut self, sink: S) -> Self {
+        self.options.hooks.detection_sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Attaches `correlation_id` to all scans of this client, e.g. to a clone
+    /// of the client that scans the uploads of a single request (see
+    /// [`ScanOptions::correlation_id`])
+    ///
+    /// Options set afterwards replace it.
+    pub fn correlation_id<I: Into<String>>(mut self, correlation_id: I) -> Self {
+        self.options = self.options.correlation_id(correlation_id);
+        self
+    }
+
+    /// Aborts commands and scans that take longer than `timeout`, including
+    /// connecting to ClamAV, with [`TimedOut`](io::ErrorKind::TimedOut)
+    ///
+    /// Like other dropped scans, a scan that times out terminates its stream
+    /// to ClamAV.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Waits for a permit of `limiter` before each command and scan, e.g. to
+    /// share a cap on the concurrent requests to ClamAV between clients
+    ///
+    /// The [`timeout`](Self::timeout) starts once the permit is acquired.
+    pub fn limiter(mut self, limiter: ScanLimiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Sets the [`FailurePolicy`] of [`decide_file`](Self::decide_file) and
+    /// [`decide_buffer`](Self::decide_buffer), which propagates errors by
+    /// default
+    pub fn failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Applies `verdict_policy` to the outcome of each scan of this client
+    /// before it is returned, replacing any policy set before
+    ///
+    /// Hooks, detection sinks, the audit log and the cache see the outcome
+    /// after the policy, so clients that share a cache should use the same
+    /// policy. Outcomes found in the cache are returned as they are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clamav_client::ScanOutcome;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let clamd_tcp = clamav_client::tokio::Tcp{ host_address: "localhost:3310" };
+    /// let client = clamav_client::tokio::Client::new(clamd_tcp).verdict_policy(|outcome| match outcome {
+    ///     // Tolerate heuristic detections of broken executables
+    ///     ScanOutcome::Infected { signature } if signature.starts_with("Heuristics.Broken.") => {
+    ///         ScanOutcome::Clean
+    ///     }
+    ///     outcome => outcome,
+    /// });
+    /// # assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+    /// # }
+    /// ```
+    ///
+    pub fn verdict_policy<V: VerdictPolicy + 'static>(mut self, verdict_policy: V) -> Self {
+        self.verdict_policy = Some(Arc::new(verdict_policy));
+        self
+    }
+
+    /// Looks up the SHA-256 digest of the content in `cache` before scanning
+    /// it, and stores the outcome of each scan in `cache`
+    ///
+    /// To share a cache between clients, pass an `Arc` of it to each client.
+    ///
+    /// Use the feature flag "cache" to enable this option.
+    #[cfg(feature = "cache")]
+    pub fn cache<C: ScanCache + 'static>(mut self, cache: C) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Records each scan in `audit_log`, including failed scans
+    ///
+    /// To share an audit log between clients, pass an `Arc` of it to each
+    /// client.
+    ///
+    /// Use the feature flag "audit" to enable this option.
+    #[cfg(feature = "audit")]
+    pub fn audit_log<A: Into<Arc<AuditLog>>>(mut self, audit_log: A) -> Self {
+        self.audit_log = Some(audit_log.into());
+        self
+    }
+
+    /// Returns the cumulative statistics of the scans of this client and its
+    /// clones
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+
+    /// Sends a ping request to ClamAV (see [`ping`])
+    pub async fn ping(&self) -> IoResult {
+        self.limited(ping(&self.connection)).await
+    }
+
+    /// Gets the version number from ClamAV (see [`get_version`])
+    pub async fn get_version(&self) -> IoResult {
+        self.limited(get_version(&self.connection)).await
+    }
+
+    /// Gets the age of the signature databases of ClamAV (see
+    /// [`database_age`])
+    pub async fn database_age(&self) -> Result<Duration, ClamdError> {
+        self.limited(database_age(&self.connection)).await
+    }
+
+    /// Checks whether the signature databases of ClamAV are older than
+    /// `max_age` (see [`is_database_stale`])
+    pub async fn is_database_stale(&self, max_age: Duration) -> Result<bool, ClamdError> {
+        self.limited(is_database_stale(&self.connection, max_age))
+            .await
+    }
+
+    /// Scans a file for viruses (see [`scan_file`])
+    ///
+    /// With a cache, the file is read once to compute its digest before it is
+    /// scanned.
+    pub async fn scan_file<P: AsRef<Path>>(&self, file_path: P) -> Result<ScanOutcome, ClamdError> {
+        self.scan_file_response(file_path)
+            .await
+            .map(|response| response.outcome)
+    }
+
+    /// Scans a file for viruses like [`scan_file`](Self::scan_file) and returns
+    /// the raw response of ClamAV along with the outcome
+    ///
+    /// The outcome is the one after the verdict policy of this client, and the
+    /// raw response is empty if the outcome was found in the cache.
+    pub async fn scan_file_response<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<ScanResponse, ClamdError> {
+        let file_path = file_path.as_ref();
+
+        #[cfg(feature = "cache")]
+        let cached_scan = match self.cache.as_deref() {
+            Some(cache) => Some(CachedScan::new(cache, sha256_file(file_path).await?)),
+            None => None,
+        };
+        #[cfg(feature = "cache")]
+        if let Some(outcome) = cached_scan.as_ref().and_then(CachedScan::lookup) {
+            return Ok(ScanResponse {
+                outcome,
+                raw: Vec::new(),
+            });
+        }
+
+        #[cfg(feature = "audit")]
+        let audit_scan = AuditScan::start(self.audit_log.as_deref(), &self.options);
+        #[cfg(feature = "audit")]
+        let options = audit_scan.options();
+        #[cfg(not(feature = "audit"))]
+        let options = &self.options;
+
+        let mut raw = Vec::new();
+        let result = self
+            .limited(scan_file(file_path, &self.connection, options))
+            .await
+            .map_err(ClamdError::from)
+            .and_then(|response| {
+                let outcome = ScanOutcome::from_response(&response);
+                raw = response;
+                outcome
+            })
+            .map(|outcome| verdict::apply(self.verdict_policy.as_deref(), outcome));
+        #[cfg(feature = "cache")]
+        let sha256 = cached_scan.as_ref().map(CachedScan::digest);
+        #[cfg(not(feature = "cache"))]
+        let sha256 = None;
+        self.options.hooks.result(
+            &result,
+            Some(file_path),
+            sha256,
+            self.options.correlation_id.as_deref(),
+        );
+        #[cfg(feature = "audit")]
+        let result = audit_scan.finish(Some(file_path), result);
+
+        #[cfg(feature = "cache")]
+        if let Some(cached_scan) = cached_scan {
+            cached_scan.store(&result);
+        }
+        result.map(|outcome| ScanResponse { outcome, raw })
+    }
+
+    /// Scans a data buffer for viruses (see [`scan_buffer`])
+    pub async fn scan_buffer<B: AsRef<[u8]>>(&self, buffer: B) -> Result<ScanOutcome, ClamdError> {
+        self.scan_buffer_response(buffer)
+            .await
+            .map(|response| response.outcome)
+    }
+
+    /// Scans a data buffer for viruses like [`scan_buffer`](Self::scan_buffer)
+    /// and returns the raw response of ClamAV along with the outcome
+    ///
+    /// The outcome is the one after the verdict policy of this client, and the
+    /// raw response is empty if the outcome was found in the cache.
+    pub async fn scan_buffer_response<B: AsRef<[u8]>>(
+        &self,
+        buffer: B,
+    ) -> Result<ScanResponse, ClamdError> {
+        let buffer = buffer.as_ref();
+        #[cfg(feature = "cache")]
+        let cached_scan = self
+            .cache
+            .as_deref()
+            .map(|cache| CachedScan::new(cache, cache::sha256(buffer)));
+        #[cfg(feature = "cache")]
+        if let Some(outcome) = cached_scan.as_ref().and_then(CachedScan::lookup) {
+            return Ok(ScanResponse {
+                outcome,
+                raw: Vec::new(),
+            });
+        }
+
+        #[cfg(feature = "audit")]
+        let audit_scan = AuditScan::start(self.audit_log.as_deref(), &self.options);
+        #[cfg(feature = "audit")]
+        let options = audit_scan.options();
+        #[cfg(not(feature = "audit"))]
+        let options = &self.options;
+
+        let mut raw = Vec::new();
+        let result = self
+            .limited(scan_buffer(buffer, &self.connection, options))
+            .await
+            .map_err(ClamdError::from)
+            .and_then(|response| {
+                let outcome = ScanOutcome::from_response(&response);
+                raw = response;
+                outcome
+            })
+            .map(|outcome| verdict::apply(self.verdict_policy.as_deref(), outcome));
+        #[cfg(feature = "cache")]
+        let sha256 = cached_scan.as_ref().map(CachedScan::digest);
+        #[cfg(not(feature = "cache"))]
+        let sha256 = None;
+        self.options.hooks.result(
+            &result,
+            None,
+            sha256,
+            self.options.correlation_id.as_deref(),
+        );
+        #[cfg(feature = "audit")]
+        let result = audit_scan.finish(None, result);
+
+        #[cfg(feature = "cache")]
+        if let Some(cached_scan) = cached_scan {
+            cached_scan.store(&result);
+        }
+        result.map(|outcome| ScanResponse { outcome, raw })
+    }
+
+    /// Scans a file for viruses and decides whether to accept it, applying the
+    /// [`FailurePolicy`] of this client if the scan fails
+    pub async fn decide_file<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<ScanDecision, ClamdError> {
+        self.failure_policy.decide(self.scan_file(file_path).await)
+    }
+
+    /// Scans a data buffer for viruses and decides whether to accept it,
+    /// applying the [`FailurePolicy`] of this client if the scan fails
+    pub async fn decide_buffer<B: AsRef<[u8]>>(
+        &self,
+        buffer: B,
+    ) -> Result<ScanDecision, ClamdError> {
+        self.failure_policy.decide(self.scan_buffer(buffer).await)
+    }
+
+    /// Runs `request` with the timeout of this client, after acquiring a
+    /// permit of its limiter
+    async fn limited<F, R, E>(&self, request: F) -> Result<R, E>
+    where
+        F: std::future::Future<Output = Result<R, E>>,
+        E: From<io::Error>,
+    {
+        let _permit = match &self.limiter {
+            Some(limiter) => Some(limiter.acquire().await?),
+            None => None,
+        };
+        with_timeout(self.timeout, request).await
+    }
+}
+
+/// Fails with [`TimedOut`](io::ErrorKind::TimedOut) if `future` does not
+/// finish within `timeout`
+async fn with_timeout<F, R, E>(timeout: Option<Duration>, future: F) -> Result<R, E>
+where
+    F: std::future::Future<Output = Result<R, E>>,
+    E: From<io::Error>,
+{
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::TimedOut).into())),
+        None => future.await,
+    }
+}
+
+#[cfg(feature = "cache")]
+async fn sha256_file(file_path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(file_path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = DataBuffer::default();
+    let buffer = buffer.get(DEFAULT_CHUNK_SIZE);
+    loop {
+        let len = file.read(buffer).await?;
+        if len == 0 {
+            return Ok(hasher.finalize().into());
+        }
+        hasher.update(&buffer[..len]);
+    }
 }
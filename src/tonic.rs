@@ -0,0 +1,211 @@
+//! A [`tonic`](https://docs.rs/tonic) gRPC API that fronts a ClamAV server
+//!
+//! The API is defined in `proto/clamav.proto` of this crate, so clients can be
+//! generated for any language. It has three methods:
+//!
+//! - `Scan(stream ScanChunk) -> Verdict` scans the data streamed in chunks
+//! - `Ping(PingRequest) -> PingResponse` checks that ClamAV is available
+//! - `Version(VersionRequest) -> VersionResponse` returns the version of ClamAV
+//!
+//! [`ClamdProxy`] implements the API by forwarding the requests to ClamAV.
+//! Add it to a `tonic` server using [`ClamdProxy::into_server`]. Errors are
+//! returned as a [`Status`]: `UNAVAILABLE` if ClamAV cannot be reached,
+//! `RESOURCE_EXHAUSTED` if the data exceeds the maximum stream size and
+//! `INTERNAL` if the response of ClamAV is invalid.
+//!
+//! The generated messages, server and client are in the [`proto`] module.
+//!
+//! Use the feature flag "tonic" to enable this module.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use clamav_client::tonic::ClamdProxy;
+//! use tonic::transport::Server;
+//!
+//! Server::builder()
+//!     .add_service(ClamdProxy::tcp("localhost:3310").into_server())
+//!     .serve("[::1]:50051".parse()?)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+
+use std::{io, path::PathBuf};
+
+use bytes::Bytes;
+use tokio_stream::StreamExt;
+use tonic::{Request, Response, Status, Streaming};
+
+use super::{connection::Connection, ClamdError, ScanOptions, ScanOutcome};
+
+use proto::{
+    clamd_server::{Clamd, ClamdServer},
+    verdict, PingRequest, PingResponse, ScanChunk, Verdict, VersionRequest, VersionResponse,
+};
+
+/// The messages, server and client generated from `proto/clamav.proto`
+#[allow(missing_docs)]
+pub mod proto {
+    tonic::include_proto!("clamav");
+}
+
+/// An implementation of the gRPC API that forwards the requests to a ClamAV
+/// server
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{tonic::ClamdProxy, ScanOptions};
+///
+/// let options = ScanOptions::new().max_stream_size(10_000_000);
+/// let server = ClamdProxy::tcp("localhost:3310").options(options).into_server();
+/// ```
+///
+#[derive(Clone)]
+pub struct ClamdProxy {
+    connection: Connection,
+    options: ScanOptions,
+}
+
+impl ClamdProxy {
+    /// Creates a proxy that connects to a ClamAV server via TCP
+    pub fn tcp<A: Into<String>>(host_address: A) -> Self {
+        ClamdProxy {
+            connection: Connection::tcp(host_address),
+            options: ScanOptions::default(),
+        }
+    }
+
+    /// Creates a proxy that connects to a ClamAV server via a Unix socket
+    #[cfg(unix)]
+    pub fn socket<P: Into<PathBuf>>(socket_path: P) -> Self {
+        ClamdProxy {
+            connection: Connection::socket(socket_path),
+            options: ScanOptions::default(),
+        }
+    }
+
+    /// Sets the [`ScanOptions`] used to scan the streamed data
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        self.options = options.into();
+        self
+    }
+
+    /// Returns the gRPC server of this proxy, which can be added to a `tonic`
+    /// server
+    pub fn into_server(self) -> ClamdServer<Self> {
+        ClamdServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl Clamd for ClamdProxy {
+    async fn scan(
+        &self,
+        request: Request<Streaming<ScanChunk>>,
+    ) -> Result<Response<Verdict>, Status> {
+        let mut client_error = None;
+        let stream = request.into_inner().map(|chunk| match chunk {
+            Ok(chunk) => Ok(Bytes::from(chunk.data)),
+            Err(status) => {
+                let err = io::Error::other(status.message().to_owned());
+                client_error = Some(status);
+                Err(err)
+            }
+        });
+        let result = self.connection.scan_stream(stream, &self.options).await;
+        if let Some(status) = client_error {
+            return Err(status);
+        }
+
+        let outcome = result
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response))
+            .map_err(error_status)?;
+        Ok(Response::new(Verdict::from(outcome)))
+    }
+
+    async fn ping(&self, _request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        self.connection
+            .ping()
+            .await
+            .map_err(|err| error_status(ClamdError::from(err)))?;
+        Ok(Response::new(PingResponse {}))
+    }
+
+    async fn version(
+        &self,
+        _request: Request<VersionRequest>,
+    ) -> Result<Response<VersionResponse>, Status> {
+        let response = self
+            .connection
+            .get_version()
+            .await
+            .map_err(|err| error_status(ClamdError::from(err)))?;
+        let version = String::from_utf8_lossy(&response)
+            .trim_end_matches(['\0', '\n'])
+            .to_owned();
+        Ok(Response::new(VersionResponse { version }))
+    }
+}
+
+/// Returns the status for an error communicating with ClamAV
+fn error_status(err: ClamdError) -> Status {
+    match err {
+        ClamdError::Io(err) => Status::unavailable(err.to_string()),
+        err @ ClamdError::SizeLimitExceeded { .. } => Status::resource_exhausted(err.to_string()),
+        err @ ClamdError::InvalidResponse { .. } => Status::internal(err.to_string()),
+        err @ ClamdError::Cancelled => Status::cancelled(err.to_string()),
+        err @ ClamdError::InvalidPath { .. } => Status::invalid_argument(err.to_string()),
+        err @ ClamdError::ScanFailed { .. } => Status::internal(err.to_string()),
+        err @ ClamdError::InvalidOptions { .. } => Status::failed_precondition(err.to_string()),
+        err @ ClamdError::ArchiveLimitExceeded { .. } => Status::invalid_argument(err.to_string()),
+        err @ ClamdError::EmptyInput => Status::invalid_argument(err.to_string()),
+    }
+}
+
+impl From<ScanOutcome> for Verdict {
+    fn from(outcome: ScanOutcome) -> Self {
+        let (result, signature, message) = match outcome {
+            ScanOutcome::Clean => (verdict::Result::Clean, String::new(), String::new()),
+            ScanOutcome::Infected { signature } => {
+                (verdict::Result::Infected, signature, String::new())
+            }
+            ScanOutcome::Warning { signature } => {
+                (verdict::Result::Warning, signature, String::new())
+            }
+            ScanOutcome::Error { message } => (verdict::Result::Error, String::new(), message),
+        };
+        Verdict {
+            result: result.into(),
+            signature,
+            message,
+        }
+    }
+}
+
+impl From<Verdict> for ScanOutcome {
+    /// Converts a verdict received from the gRPC API, e.g. using the generated
+    /// [`ClamdClient`](proto::clamd_client::ClamdClient)
+    fn from(verdict: Verdict) -> Self {
+        match verdict::Result::try_from(verdict.result) {
+            Ok(verdict::Result::Clean) => ScanOutcome::Clean,
+            Ok(verdict::Result::Infected) => ScanOutcome::Infected {
+                signature: verdict.signature,
+            },
+            Ok(verdict::Result::Warning) => ScanOutcome::Warning {
+                signature: verdict.signature,
+            },
+            Ok(verdict::Result::Error) => ScanOutcome::Error {
+                message: verdict.message,
+            },
+            Err(_) => ScanOutcome::Error {
+                message: format!("unknown verdict result {}", verdict.result),
+            },
+        }
+    }
+}
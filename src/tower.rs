@@ -0,0 +1,190 @@
+//! A [`tower`](https://docs.rs/tower) service that scans request bodies
+//!
+//! [`ClamdService`] implements [`Service<ScanRequest>`](Service), so the
+//! middleware of `tower` and other crates, e.g. timeouts, rate limits or load
+//! shedding, can be added around scanning, and the service can be used in any
+//! `tower`-based pipeline. It responds with the [`ScanOutcome`] of the scanned
+//! data and fails with a [`ClamdError`].
+//!
+//! Use the feature flag "tower" to enable this module.
+//!
+//! # Example
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! use clamav_client::{
+//!     tower::{ClamdService, ScanRequest},
+//!     ScanOutcome,
+//! };
+//! use tower::{Service, ServiceExt};
+//!
+//! let mut service = ClamdService::tcp("localhost:3310");
+//! let request = ScanRequest::from(b"clean data".to_vec());
+//! let outcome = service.ready().await.unwrap().call(request).await.unwrap();
+//! assert_eq!(outcome, ScanOutcome::Clean);
+//! # }
+//! ```
+//!
+
+use std::{
+    fmt,
+    future::Future,
+    io,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use tokio_stream::Stream;
+use tower_service::Service;
+
+use super::{connection::Connection, ClamdError, ScanOptions, ScanOutcome};
+
+type BoxStream = Pin<Box<dyn Stream<Item = Result<Bytes, io::Error>> + Send>>;
+
+enum Body {
+    Bytes(Bytes),
+    Stream(BoxStream),
+}
+
+/// The data scanned by a [`ClamdService`]
+///
+/// A request is created from a stream of [`Bytes`] using [`ScanRequest::new`]
+/// or from data in memory using one of its `From` implementations.
+///
+pub struct ScanRequest {
+    body: Body,
+}
+
+impl ScanRequest {
+    /// Creates a request that scans the chunks of a stream
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, io::Error>> + Send + 'static,
+    {
+        ScanRequest {
+            body: Body::Stream(Box::pin(stream)),
+        }
+    }
+
+    /// Returns a copy of this request if its data is in memory, or `None` if
+    /// it is a stream
+    ///
+    /// Use this method to implement retry policies, which need to send a
+    /// request again.
+    pub fn try_clone(&self) -> Option<Self> {
+        match &self.body {
+            Body::Bytes(bytes) => Some(ScanRequest::from(bytes.clone())),
+            Body::Stream(_) => None,
+        }
+    }
+}
+
+impl From<Bytes> for ScanRequest {
+    fn from(bytes: Bytes) -> Self {
+        ScanRequest {
+            body: Body::Bytes(bytes),
+        }
+    }
+}
+
+impl From<Vec<u8>> for ScanRequest {
+    fn from(bytes: Vec<u8>) -> Self {
+        ScanRequest::from(Bytes::from(bytes))
+    }
+}
+
+impl From<&'static [u8]> for ScanRequest {
+    fn from(bytes: &'static [u8]) -> Self {
+        ScanRequest::from(Bytes::from_static(bytes))
+    }
+}
+
+impl fmt::Debug for ScanRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.body {
+            Body::Bytes(bytes) => f.debug_tuple("ScanRequest").field(bytes).finish(),
+            Body::Stream(_) => f.debug_tuple("ScanRequest").field(&"<stream>").finish(),
+        }
+    }
+}
+
+/// A [`Service`] that scans a [`ScanRequest`] and responds with its
+/// [`ScanOutcome`]
+///
+/// Every call connects to the ClamAV server, so the service is always ready
+/// and can be cloned freely.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use clamav_client::{tower::ClamdService, ScanOptions};
+/// use tower::ServiceBuilder;
+///
+/// let options = ScanOptions::new().max_stream_size(10_000_000);
+/// let service = ServiceBuilder::new()
+///     .timeout(Duration::from_secs(30))
+///     .service(ClamdService::tcp("localhost:3310").options(options));
+/// ```
+///
+#[derive(Clone)]
+pub struct ClamdService {
+    connection: Connection,
+    options: ScanOptions,
+}
+
+impl ClamdService {
+    /// Creates a service that connects to a ClamAV server via TCP
+    pub fn tcp<A: Into<String>>(host_address: A) -> Self {
+        ClamdService {
+            connection: Connection::tcp(host_address),
+            options: ScanOptions::default(),
+        }
+    }
+
+    /// Creates a service that connects to a ClamAV server via a Unix socket
+    #[cfg(unix)]
+    pub fn socket<P: Into<PathBuf>>(socket_path: P) -> Self {
+        ClamdService {
+            connection: Connection::socket(socket_path),
+            options: ScanOptions::default(),
+        }
+    }
+
+    /// Sets the [`ScanOptions`] used to scan requests
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        self.options = options.into();
+        self
+    }
+}
+
+impl Service<ScanRequest> for ClamdService {
+    type Response = ScanOutcome;
+    type Error = ClamdError;
+    type Future = Pin<Box<dyn Future<Output = Result<ScanOutcome, ClamdError>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: ScanRequest) -> Self::Future {
+        let ClamdService {
+            connection,
+            options,
+        } = self.clone();
+        Box::pin(async move {
+            let response = match request.body {
+                Body::Bytes(bytes) => {
+                    let stream = tokio_stream::once(Ok(bytes));
+                    connection.scan_stream(stream, &options).await?
+                }
+                Body::Stream(stream) => connection.scan_stream(stream, &options).await?,
+            };
+            ScanOutcome::from_response(&response)
+        })
+    }
+}
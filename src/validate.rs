@@ -0,0 +1,137 @@
+use std::{fmt, io, net::ToSocketAddrs, time::Duration};
+
+#[cfg(unix)]
+use std::{os::unix::fs::FileTypeExt, path::Path};
+
+use super::{is_pong, ClamdConnection, TransportProtocol};
+
+/// The part of the setup that a [`ValidationProblem`] concerns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ValidationCheck {
+    /// The settings are contradictory or invalid, e.g. a chunk size of zero
+    Configuration,
+    /// The host address cannot be resolved to any IP address
+    Resolution,
+    /// The socket file does not exist, is not a socket or may not be accessed
+    Socket,
+    /// No connection to ClamAV can be established
+    Connection,
+    /// ClamAV does not answer the PING command with `PONG`
+    Ping,
+}
+
+/// A problem found by [`validate`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ValidationProblem {
+    /// The part of the setup that the problem concerns
+    pub check: ValidationCheck,
+    /// A description of the problem
+    pub message: String,
+}
+
+impl ValidationProblem {
+    pub(crate) fn new(check: ValidationCheck, message: String) -> Self {
+        ValidationProblem { check, message }
+    }
+}
+
+impl fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Checks that ClamAV can be reached with `connection` without scanning any
+/// data, e.g. at the startup of a service before it accepts traffic
+///
+/// The host address of a TCP connection is resolved, and the socket file of a
+/// Unix socket connection is checked to exist and to be a socket. Then a
+/// connection is established and ClamAV is pinged, waiting at most `timeout`
+/// for connecting and for each read and write. The checks stop at the first
+/// problem, since the later checks depend on the earlier ones, and no problems
+/// are returned if ClamAV answered the ping.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let connection = clamav_client::ClamdConnection::Tcp(clamav_client::Tcp {
+///     host_address: "localhost:3310".to_owned(),
+/// });
+/// let problems = clamav_client::validate(&connection, Duration::from_secs(5));
+/// for problem in &problems {
+///     eprintln!("{:?}: {}", problem.check, problem);
+/// }
+/// # assert!(problems.is_empty());
+/// ```
+///
+pub fn validate(connection: &ClamdConnection, timeout: Duration) -> Vec<ValidationProblem> {
+    let mut problems = Vec::new();
+    let problem = match connection {
+        ClamdConnection::Tcp(tcp) => check_resolution(&tcp.host_address),
+        #[cfg(unix)]
+        ClamdConnection::Socket(socket) => check_socket(&socket.socket_path),
+    };
+    if let Some(problem) = problem {
+        problems.push(problem);
+        return problems;
+    }
+
+    let stream = match connection.connect_timeout(timeout) {
+        Ok(stream) => stream,
+        Err(err) => {
+            let check = match err.kind() {
+                io::ErrorKind::PermissionDenied => ValidationCheck::Socket,
+                _ => ValidationCheck::Connection,
+            };
+            problems.push(ValidationProblem::new(
+                check,
+                format!("connecting failed: {}", err),
+            ));
+            return problems;
+        }
+    };
+    match super::_ping(stream) {
+        Ok(response) if is_pong(&response) => {}
+        Ok(response) => problems.push(ValidationProblem::new(
+            ValidationCheck::Ping,
+            format!(
+                "unexpected response to PING: {}",
+                String::from_utf8_lossy(&response).trim_end_matches(['\0', '\n'])
+            ),
+        )),
+        Err(err) => problems.push(ValidationProblem::new(
+            ValidationCheck::Ping,
+            format!("ping failed: {}", err),
+        )),
+    }
+    problems
+}
+
+fn check_resolution(host_address: &str) -> Option<ValidationProblem> {
+    let message = match host_address
+        .to_socket_addrs()
+        .map(|mut addresses| addresses.next())
+    {
+        Ok(Some(_)) => return None,
+        Ok(None) => format!("{} does not resolve to any address", host_address),
+        Err(err) => format!("resolving {} failed: {}", host_address, err),
+    };
+    Some(ValidationProblem::new(ValidationCheck::Resolution, message))
+}
+
+#[cfg(unix)]
+fn check_socket(socket_path: &Path) -> Option<ValidationProblem> {
+    let message = match socket_path.metadata() {
+        Ok(metadata) if metadata.file_type().is_socket() => return None,
+        Ok(_) => format!("{} is not a socket", socket_path.display()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            format!("the socket {} does not exist", socket_path.display())
+        }
+        Err(err) => format!("accessing {} failed: {}", socket_path.display(), err),
+    };
+    Some(ValidationProblem::new(ValidationCheck::Socket, message))
+}
@@ -0,0 +1,60 @@
+use super::ScanOutcome;
+
+/// Post-processes the outcome of each scan of a client before it is returned
+///
+/// A verdict policy keeps organizational rules in one place, e.g. treating
+/// certain errors of ClamAV as clean or as infected, or downgrading the
+/// detections of a signature category. It is set with
+/// [`Client::verdict_policy`](crate::Client::verdict_policy) or the method of
+/// the same name of the asynchronous clients.
+///
+/// Closures that take and return a [`ScanOutcome`] implement this trait.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{ScanOutcome, VerdictPolicy};
+///
+/// /// Treats exceeded size limits as infected, so that they are rejected
+/// struct RejectOversized;
+///
+/// impl VerdictPolicy for RejectOversized {
+///     fn apply(&self, outcome: ScanOutcome) -> ScanOutcome {
+///         match outcome {
+///             ScanOutcome::Error { message } if message.contains("size limit exceeded") => {
+///                 ScanOutcome::Infected {
+///                     signature: "Oversized".to_owned(),
+///                 }
+///             }
+///             outcome => outcome,
+///         }
+///     }
+/// }
+///
+/// let outcome = ScanOutcome::Error {
+///     message: "INSTREAM size limit exceeded.".to_owned(),
+/// };
+/// assert_eq!(RejectOversized.apply(outcome).signature(), Some("Oversized"));
+/// ```
+///
+pub trait VerdictPolicy: Send + Sync {
+    /// Returns the outcome to report instead of `outcome`
+    fn apply(&self, outcome: ScanOutcome) -> ScanOutcome;
+}
+
+impl<F> VerdictPolicy for F
+where
+    F: Fn(ScanOutcome) -> ScanOutcome + Send + Sync,
+{
+    fn apply(&self, outcome: ScanOutcome) -> ScanOutcome {
+        self(outcome)
+    }
+}
+
+/// Applies `policy`, if any, to `outcome`
+pub(crate) fn apply(policy: Option<&dyn VerdictPolicy>, outcome: ScanOutcome) -> ScanOutcome {
+    match policy {
+        Some(policy) => policy.apply(outcome),
+        None => outcome,
+    }
+}
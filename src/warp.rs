@@ -0,0 +1,141 @@
+//! Virus scanning for upload endpoints of [`warp`](https://docs.rs/warp)
+//!
+//! The filters [`scanned_bytes`] and [`scanned_multipart`] scan the request
+//! body while it is received and extract its contents if it is clean. An
+//! infected body is rejected with a [`ScanRejection`], which carries the
+//! signature that matched and can be turned into a reply using [`recover`].
+//!
+//! Request bodies are kept in memory, so limit their size, e.g. using
+//! [`warp::body::content_length_limit`] or
+//! [`ScanOptions::max_stream_size`](crate::ScanOptions::max_stream_size).
+//!
+//! Use the feature flag "warp" to enable this module.
+//!
+//! # Example
+//!
+//! ```
+//! use clamav_client::warp::{recover, scanned_bytes, Scanner};
+//! use warp::Filter;
+//!
+//! let scanner = Scanner::tcp("localhost:3310");
+//! let upload = warp::post()
+//!     .and(warp::path("upload"))
+//!     .and(warp::body::content_length_limit(10_000_000))
+//!     .and(scanned_bytes(scanner))
+//!     .map(|data: bytes::Bytes| format!("Received {} clean bytes", data.len()))
+//!     .recover(recover);
+//! ```
+//!
+
+use bytes::{Buf, Bytes};
+use tokio_stream::{Stream, StreamExt};
+use warp::{
+    http::StatusCode,
+    reject::{Reject, Rejection},
+    reply::{self, Reply, Response},
+    Filter,
+};
+
+use super::tokio::MultipartField;
+
+pub use super::web::{ScanRejection, Scanner};
+
+impl Reject for ScanRejection {}
+
+impl Reply for ScanRejection {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status_code()).expect("the status code is valid");
+        reply::with_status(self.to_string(), status).into_response()
+    }
+}
+
+/// Turns a [`ScanRejection`] into its reply and passes other rejections on
+///
+/// Use this function with [`Filter::recover`].
+///
+pub async fn recover(rejection: Rejection) -> Result<Response, Rejection> {
+    match rejection.find::<ScanRejection>() {
+        Some(scan_rejection) => Ok(scan_rejection.clone().into_response()),
+        None => Err(rejection),
+    }
+}
+
+/// Converts the chunks of a warp body stream to [`Bytes`]
+fn body_stream<S, B>(body: S) -> impl Stream<Item = Result<Bytes, warp::Error>>
+where
+    S: Stream<Item = Result<B, warp::Error>>,
+    B: Buf,
+{
+    body.map(|chunk| chunk.map(|mut chunk| chunk.copy_to_bytes(chunk.remaining())))
+}
+
+/// A filter that scans the request body and extracts it if it is clean
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::warp::{scanned_bytes, Scanner};
+/// use warp::Filter;
+///
+/// let upload = warp::post()
+///     .and(scanned_bytes(Scanner::tcp("localhost:3310")))
+///     .map(|data: bytes::Bytes| format!("Received {} clean bytes", data.len()));
+/// ```
+///
+pub fn scanned_bytes(
+    scanner: Scanner,
+) -> impl Filter<Extract = (Bytes,), Error = Rejection> + Clone {
+    warp::body::stream().and_then(move |body| {
+        let scanner = scanner.clone();
+        async move {
+            scanner
+                .scan_body(body_stream(body))
+                .await
+                .map_err(warp::reject::custom)
+        }
+    })
+}
+
+/// A filter that scans the file fields of a `multipart/form-data` request
+/// body and extracts all fields if the file fields are clean
+///
+/// See [`scan_multipart`](crate::tokio::scan_multipart) for how the fields
+/// are scanned.
+///
+/// # Example
+///
+/// ```
+/// use clamav_client::{
+///     tokio::MultipartField,
+///     warp::{scanned_multipart, Scanner},
+/// };
+/// use warp::Filter;
+///
+/// let upload = warp::post()
+///     .and(scanned_multipart(Scanner::tcp("localhost:3310")))
+///     .map(|fields: Vec<MultipartField>| format!("Received {} clean fields", fields.len()));
+/// ```
+///
+pub fn scanned_multipart(
+    scanner: Scanner,
+) -> impl Filter<Extract = (Vec<MultipartField>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("content-type")
+        .and(warp::body::stream())
+        .and_then(move |content_type: Option<String>, body| {
+            let scanner = scanner.clone();
+            async move {
+                let boundary = content_type
+                    .and_then(|content_type| multer::parse_boundary(content_type).ok())
+                    .ok_or_else(|| {
+                        warp::reject::custom(ScanRejection::InvalidBody {
+                            message: "invalid multipart/form-data content type".to_owned(),
+                        })
+                    })?;
+                let multipart = multer::Multipart::new(body_stream(body), boundary);
+                scanner
+                    .scan_multer_form(multipart)
+                    .await
+                    .map_err(warp::reject::custom)
+            }
+        })
+}
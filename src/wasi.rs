@@ -0,0 +1,66 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+use super::TransportProtocol;
+
+/// A transport that connects using a function that opens a stream, e.g. a
+/// socket provided by the host of a WASI component
+///
+/// On `wasm32-wasip2`, [`Tcp`](crate::Tcp) connects through WASI sockets.
+/// Sandboxes that do not grant the component access to the network can hand
+/// it streams to ClamAV instead, which `Connector` passes to the scan
+/// functions and clients of this crate. The function is called once for each
+/// connection.
+///
+/// Timeouts are not applied to the streams, so the function should configure
+/// them if necessary.
+///
+/// Use the feature flag "wasi" to enable this type.
+///
+/// # Example
+///
+/// ```
+/// use std::net::TcpStream;
+///
+/// let connector = clamav_client::Connector::new(|| TcpStream::connect("localhost:3310"));
+/// let client = clamav_client::Client::new(connector);
+///
+/// let outcome = client.scan_buffer(b"clean data").unwrap();
+/// assert!(outcome.is_clean());
+/// ```
+///
+#[derive(Clone)]
+pub struct Connector<F> {
+    connect: F,
+}
+
+impl<F, S> Connector<F>
+where
+    F: Fn() -> io::Result<S>,
+    S: Read + Write,
+{
+    /// Creates a transport that calls `connect` to open each connection
+    pub fn new(connect: F) -> Self {
+        Connector { connect }
+    }
+}
+
+impl<F, S> TransportProtocol for Connector<F>
+where
+    F: Fn() -> io::Result<S>,
+    S: Read + Write,
+{
+    type Stream = S;
+
+    fn connect(&self) -> io::Result<Self::Stream> {
+        (self.connect)()
+    }
+}
+
+impl<F> fmt::Debug for Connector<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Connector")
+    }
+}
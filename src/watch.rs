@@ -0,0 +1,319 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use super::{ClamdError, ScanOptions, ScanOutcome, TransportProtocol};
+
+/// Options that control how a directory is watched
+///
+/// By default, the whole directory tree is watched, files are scanned once
+/// they have not changed for 500 milliseconds, and files of any size are
+/// scanned.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let options = clamav_client::WatchOptions::new()
+///     .recursive(false)
+///     .debounce(Duration::from_secs(2))
+///     .max_file_size(25 * 1024 * 1024);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub(crate) recursive: bool,
+    pub(crate) debounce: Duration,
+    pub(crate) max_file_size: Option<u64>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            recursive: true,
+            debounce: Duration::from_millis(500),
+            max_file_size: None,
+        }
+    }
+}
+
+impl WatchOptions {
+    /// Creates the default watch options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watches subdirectories as well, including ones created later
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Sets how long a file must remain unchanged before it is scanned
+    ///
+    /// A file is scanned once no change has been reported for this duration
+    /// and its size and modification time are the same as when the last change
+    /// was reported, so that files are not scanned while they are still being
+    /// written.
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Skips files larger than the given number of bytes
+    ///
+    /// Skipped files are not part of the results.
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+}
+
+/// A watched directory whose new and modified files are scanned in the
+/// background
+///
+/// The results can be received using [`recv`](Self::recv),
+/// [`recv_timeout`](Self::recv_timeout) or by iterating over the watcher.
+/// Dropping the watcher stops watching the directory.
+///
+pub struct DirectoryWatcher {
+    _watcher: notify::RecommendedWatcher,
+    results: Receiver<(PathBuf, Result<ScanOutcome, ClamdError>)>,
+}
+
+impl DirectoryWatcher {
+    /// Blocks until the next file has been scanned
+    ///
+    /// Returns the path of the file together with its [`ScanOutcome`], or the
+    /// error that occurred while watching, reading or scanning it. Returns
+    /// [`None`] if the background thread has stopped.
+    pub fn recv(&self) -> Option<(PathBuf, Result<ScanOutcome, ClamdError>)> {
+        self.results.recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next file to be scanned
+    ///
+    /// Returns [`None`] if no file has been scanned within `timeout` or the
+    /// background thread has stopped.
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Option<(PathBuf, Result<ScanOutcome, ClamdError>)> {
+        self.results.recv_timeout(timeout).ok()
+    }
+
+    /// Returns the result of a file that has already been scanned, if any,
+    /// without blocking
+    pub fn try_recv(&self) -> Option<(PathBuf, Result<ScanOutcome, ClamdError>)> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl Iterator for DirectoryWatcher {
+    type Item = (PathBuf, Result<ScanOutcome, ClamdError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+/// Watches a directory and scans new and modified files for viruses
+///
+/// This function watches the directory at `dir_path` for file system changes
+/// and streams each file that has been created or modified to a ClamAV server
+/// for scanning once it is no longer being written to (see
+/// [`WatchOptions::debounce`]), using a new connection per file. Files that
+/// already exist are not scanned until they change; use
+/// [`scan_directory`](crate::scan_directory) to scan them first.
+///
+/// The scans run one after another on a background thread.
+///
+/// # Arguments
+///
+/// * `dir_path`: The path to the directory to be watched
+/// * `connection`: The connection type to use - either TCP or a Unix socket connection
+/// * `options`: The [`ScanOptions`] to use, or an optional chunk size for reading data. If [`None`], the default options are used
+/// * `watch_options`: The [`WatchOptions`] that control which changes are scanned
+///
+/// # Returns
+///
+/// A [`DirectoryWatcher`] that yields the path of each scanned file together
+/// with its [`ScanOutcome`], or an [`io::Error`] if the directory cannot be
+/// watched
+///
+/// Use the feature flag "watch" to enable this function.
+///
+/// # Example
+///
+/// ```no_run
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let watch_options = clamav_client::WatchOptions::new();
+/// let watcher =
+///     clamav_client::watch_directory("/srv/uploads", clamd_tcp, None, watch_options).unwrap();
+/// for (path, result) in watcher {
+///     match result {
+///         Ok(outcome) if outcome.is_infected() => println!("{} is infected!", path.display()),
+///         Ok(_) => println!("No virus found in {}", path.display()),
+///         Err(err) => println!("Could not scan {}: {}", path.display(), err),
+///     }
+/// }
+/// ```
+///
+pub fn watch_directory<P, T, O>(
+    dir_path: P,
+    connection: T,
+    options: O,
+    watch_options: WatchOptions,
+) -> io::Result<DirectoryWatcher>
+where
+    P: AsRef<Path>,
+    T: TransportProtocol + Send + 'static,
+    O: Into<ScanOptions>,
+{
+    let options = options.into();
+    let (events_sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(events_sender).map_err(into_io_error)?;
+    let recursive_mode = if watch_options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(dir_path.as_ref(), recursive_mode)
+        .map_err(into_io_error)?;
+
+    let (results_sender, results) = mpsc::channel();
+    thread::Builder::new()
+        .name("clamav-client-watch".to_owned())
+        .spawn(move || {
+            let scanner = Scanner {
+                connection,
+                options,
+                watch_options,
+                results: results_sender,
+            };
+            scanner.run(events)
+        })?;
+
+    Ok(DirectoryWatcher {
+        _watcher: watcher,
+        results,
+    })
+}
+
+/// The size and modification time of a regular file
+#[derive(PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileState {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        Some(FileState {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// A changed file that is waiting to be scanned
+struct Pending {
+    changed_at: Instant,
+    state: Option<FileState>,
+}
+
+struct Scanner<T> {
+    connection: T,
+    options: ScanOptions,
+    watch_options: WatchOptions,
+    results: Sender<(PathBuf, Result<ScanOutcome, ClamdError>)>,
+}
+
+impl<T: TransportProtocol> Scanner<T> {
+    /// Scans changed files until the watcher or the receiver of the results
+    /// is dropped
+    fn run(self, events: Receiver<notify::Result<Event>>) {
+        let debounce = self.watch_options.debounce;
+        let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+        loop {
+            let event = if pending.is_empty() {
+                events.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                events.recv_timeout(debounce / 2)
+            };
+            match event {
+                Ok(Ok(event)) => {
+                    if let EventKind::Create(_) | EventKind::Modify(_) = event.kind {
+                        for path in event.paths {
+                            let state = FileState::of(&path);
+                            let changed_at = Instant::now();
+                            pending.insert(path, Pending { changed_at, state });
+                        }
+                    }
+                }
+                Ok(Err(err)) => {
+                    let path = err.paths.first().cloned().unwrap_or_default();
+                    let result = Err(into_io_error(err).into());
+                    if self.results.send((path, result)).is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let settled: Vec<PathBuf> = pending
+                .iter()
+                .filter(|(_, file)| file.changed_at.elapsed() >= debounce)
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in settled {
+                if let Some(file) = pending.remove(&path) {
+                    let state = FileState::of(&path);
+                    if state != file.state {
+                        let changed_at = Instant::now();
+                        pending.insert(path, Pending { changed_at, state });
+                    } else if let Some(state) = state {
+                        if !self.scan(path, state) {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Scans a file and sends its result, returning `false` if the receiver of
+    /// the results has been dropped
+    fn scan(&self, path: PathBuf, state: FileState) -> bool {
+        if let Some(max_file_size) = self.watch_options.max_file_size {
+            if state.len > max_file_size {
+                return true;
+            }
+        }
+        let result = super::_scan_file(&path, &self.connection, &self.options)
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response));
+        self.results.send((path, result)).is_ok()
+    }
+}
+
+fn into_io_error(err: notify::Error) -> io::Error {
+    match err.kind {
+        notify::ErrorKind::Io(err) => err,
+        _ => io::Error::other(err),
+    }
+}
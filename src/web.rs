@@ -0,0 +1,273 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use bytes::Bytes;
+use tokio_stream::{Stream, StreamExt};
+
+use super::{
+    connection::Connection, tokio::MultipartField, ClamdError, IoResult, ScanOptions, ScanOutcome,
+};
+
+/// The ClamAV server and the [`ScanOptions`] used to scan request bodies
+///
+/// To use the extractors, add the scanner to the state of an `axum` router,
+/// or to a state implementing `FromRef` for it, or wrap it in `web::Data` and
+/// add it to the app data of an `actix-web` app, or add it to the endpoint data
+/// of a `poem` endpoint. The `warp` filters take the scanner as an argument.
+///
+#[derive(Clone)]
+pub struct Scanner {
+    connection: Connection,
+    options: ScanOptions,
+}
+
+impl Scanner {
+    /// Creates a scanner that connects to a ClamAV server via TCP
+    pub fn tcp<A: Into<String>>(host_address: A) -> Self {
+        Scanner {
+            connection: Connection::tcp(host_address),
+            options: ScanOptions::default(),
+        }
+    }
+
+    /// Creates a scanner that connects to a ClamAV server via a Unix socket
+    #[cfg(unix)]
+    pub fn socket<P: Into<PathBuf>>(socket_path: P) -> Self {
+        Scanner {
+            connection: Connection::socket(socket_path),
+            options: ScanOptions::default(),
+        }
+    }
+
+    /// Sets the [`ScanOptions`] used to scan request bodies
+    pub fn options<O: Into<ScanOptions>>(mut self, options: O) -> Self {
+        self.options = options.into();
+        self
+    }
+
+    /// Scans a stream using the connection of this scanner
+    pub(crate) async fn scan_stream<S>(&self, stream: S) -> IoResult
+    where
+        S: Stream<Item = Result<Bytes, io::Error>>,
+    {
+        self.connection.scan_stream(stream, &self.options).await
+    }
+
+    /// Scans a request body while it is received and returns its contents if
+    /// it is clean
+    pub(crate) async fn scan_body<S, E>(&self, body: S) -> Result<Bytes, ScanRejection>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        E: Error + 'static,
+    {
+        let mut chunks = Vec::new();
+        let mut body_error = None;
+        let stream = body.map(|chunk| match chunk {
+            Ok(chunk) => {
+                chunks.push(chunk.clone());
+                Ok(chunk)
+            }
+            Err(err) => {
+                let io_err = io::Error::other(err.to_string());
+                body_error = Some(err);
+                Err(io_err)
+            }
+        });
+        let result = self.scan_stream(stream).await;
+        if let Some(err) = body_error {
+            return Err(ScanRejection::from_body_error(&err));
+        }
+
+        match result
+            .map_err(ClamdError::from)
+            .and_then(|response| ScanOutcome::from_response(&response))
+        {
+            Ok(outcome) => ScanRejection::check_outcome(&outcome)?,
+            Err(err) => return Err(ScanRejection::from_scan_error(&err)),
+        }
+        Ok(chunks.concat().into())
+    }
+
+    /// Scans the file fields of a multipart form and returns all fields if
+    /// the file fields are clean
+    #[cfg(any(feature = "axum", feature = "warp", feature = "poem"))]
+    pub(crate) async fn scan_multer_form(
+        &self,
+        multipart: multer::Multipart<'_>,
+    ) -> Result<Vec<MultipartField>, ScanRejection> {
+        let result = match &self.connection {
+            Connection::Tcp(tcp) => {
+                super::tokio::scan_multipart(multipart, tcp, &self.options).await
+            }
+            #[cfg(unix)]
+            Connection::Socket(socket) => {
+                super::tokio::scan_multipart(multipart, socket, &self.options).await
+            }
+        };
+        // Errors of individual fields are stored in the fields, so an error
+        // here means that the body could not be read or parsed
+        let fields = result.map_err(|err| ScanRejection::from_body_error(&err))?;
+        ScanRejection::check_fields(&fields)?;
+        Ok(fields)
+    }
+
+    /// Scans the file fields of an `actix-multipart` form and returns all
+    /// fields if the file fields are clean
+    #[cfg(feature = "actix")]
+    pub(crate) async fn scan_actix_form(
+        &self,
+        multipart: actix_multipart::Multipart,
+    ) -> Result<Vec<MultipartField>, ScanRejection> {
+        let result = match &self.connection {
+            Connection::Tcp(tcp) => {
+                super::actix::scan_multipart(multipart, tcp, &self.options).await
+            }
+            #[cfg(unix)]
+            Connection::Socket(socket) => {
+                super::actix::scan_multipart(multipart, socket, &self.options).await
+            }
+        };
+        let fields = result.map_err(|err| ScanRejection::from_body_error(&err))?;
+        ScanRejection::check_fields(&fields)?;
+        Ok(fields)
+    }
+}
+
+/// The reason a request body was rejected by the `axum`, `actix-web`, `warp`
+/// or `poem` integration
+///
+/// As a response, an infected body is rejected with `422 Unprocessable
+/// Entity`, a body that is too large with `413 Payload Too Large`, a body that
+/// cannot be read with `400 Bad Request` and a body that cannot be scanned
+/// with `503 Service Unavailable`.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScanRejection {
+    /// A virus was found in the body or one of its file fields
+    Infected {
+        /// The name of the signature that matched
+        signature: String,
+    },
+    /// The body exceeds the body limit of the framework or the maximum stream
+    /// size
+    TooLarge {
+        /// The maximum stream size in bytes, if it was exceeded
+        limit: Option<u64>,
+    },
+    /// The body could not be read, is not a valid multipart form or is empty
+    /// while empty inputs are rejected
+    InvalidBody {
+        /// A description of the error
+        message: String,
+    },
+    /// The body could not be scanned, e.g. because ClamAV is not available
+    ScanFailed {
+        /// A description of the error
+        message: String,
+    },
+}
+
+impl ScanRejection {
+    /// Returns the rejection for an outcome other than clean
+    pub(crate) fn check_outcome(outcome: &ScanOutcome) -> Result<(), Self> {
+        match outcome {
+            ScanOutcome::Clean | ScanOutcome::Warning { .. } => Ok(()),
+            ScanOutcome::Infected { signature } => Err(ScanRejection::Infected {
+                signature: signature.clone(),
+            }),
+            ScanOutcome::Error { message } => Err(ScanRejection::ScanFailed {
+                message: message.clone(),
+            }),
+        }
+    }
+
+    /// Returns the rejection for the first file field that is not clean
+    pub(crate) fn check_fields(fields: &[MultipartField]) -> Result<(), Self> {
+        for field in fields {
+            match &field.result {
+                Some(Ok(outcome)) => ScanRejection::check_outcome(outcome)?,
+                Some(Err(err)) => return Err(ScanRejection::from_scan_error(err)),
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the rejection for an error scanning the body
+    pub(crate) fn from_scan_error(err: &ClamdError) -> Self {
+        match err {
+            ClamdError::SizeLimitExceeded { limit } => ScanRejection::TooLarge {
+                limit: Some(*limit),
+            },
+            err @ ClamdError::EmptyInput => ScanRejection::InvalidBody {
+                message: err.to_string(),
+            },
+            err => ScanRejection::ScanFailed {
+                message: err.to_string(),
+            },
+        }
+    }
+
+    /// Returns the rejection for an error reading the body
+    pub(crate) fn from_body_error(err: &(dyn Error + 'static)) -> Self {
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if is_body_limit_error(err) {
+                return ScanRejection::TooLarge { limit: None };
+            }
+            // The source of an io::Error skips the error it wraps
+            source = match err.downcast_ref::<io::Error>() {
+                Some(err) => err.get_ref().map(|err| err as &(dyn Error + 'static)),
+                None => err.source(),
+            };
+        }
+        ScanRejection::InvalidBody {
+            message: err.to_string(),
+        }
+    }
+
+    /// Returns the status code of the response for this rejection
+    pub(crate) fn status_code(&self) -> u16 {
+        match self {
+            ScanRejection::Infected { .. } => 422,
+            ScanRejection::TooLarge { .. } => 413,
+            ScanRejection::InvalidBody { .. } => 400,
+            ScanRejection::ScanFailed { .. } => 503,
+        }
+    }
+}
+
+/// Returns whether the error is the error of a framework for a body that
+/// exceeds its body limit
+#[cfg_attr(not(any(feature = "axum", feature = "actix")), allow(unused_variables))]
+fn is_body_limit_error(err: &(dyn Error + 'static)) -> bool {
+    #[cfg(feature = "axum")]
+    if err.is::<http_body_util::LengthLimitError>() {
+        return true;
+    }
+    #[cfg(feature = "actix")]
+    if let Some(actix_web::error::PayloadError::Overflow) = err.downcast_ref() {
+        return true;
+    }
+    false
+}
+
+impl fmt::Display for ScanRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanRejection::Infected { signature } => write!(f, "virus found: {}", signature),
+            ScanRejection::TooLarge { limit: Some(limit) } => {
+                write!(f, "request body exceeds the limit of {} bytes", limit)
+            }
+            ScanRejection::TooLarge { limit: None } => f.write_str("request body is too large"),
+            ScanRejection::InvalidBody { message } => {
+                write!(f, "invalid request body: {}", message)
+            }
+            ScanRejection::ScanFailed { message } => {
+                write!(f, "request body could not be scanned: {}", message)
+            }
+        }
+    }
+}
+
+impl Error for ScanRejection {}
@@ -0,0 +1,161 @@
+use std::{
+    io,
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use reqwest::blocking::Client;
+use serde_json::json;
+
+use super::{Detection, DetectionSink};
+
+type FailureCallback = Arc<dyn Fn(&Detection, &io::Error) + Send + Sync>;
+
+/// A [`DetectionSink`] that posts each detection as a JSON object to a
+/// webhook, e.g. of an alerting pipeline
+///
+/// The JSON object contains the `signature`, the `path` of the scanned file
+/// (`null` for buffers), the Unix timestamp `detected_at` in seconds and the
+/// `correlation_id` of the scan, if any, e.g.
+/// `{"signature":"Eicar-Signature","path":"eicar.txt","detected_at":1700000000,"correlation_id":null}`.
+///
+/// The detections are posted one after another by a background thread, so
+/// that scans are not delayed by the webhook. Dropping the sink waits until
+/// the pending detections are posted. Failed requests are not retried, but
+/// reported to the callback of [`on_failure`](Self::on_failure), if any.
+///
+/// Only `http` URLs are supported, unless a TLS feature of `reqwest`, e.g.
+/// `rustls-tls`, is enabled.
+///
+/// Use the feature flag "webhook" to enable this type.
+///
+/// # Example
+///
+/// ```no_run
+/// let sink = clamav_client::WebhookSink::new("http://alerts.example.com/clamav")
+///     .header("Authorization", "Bearer secret");
+///
+/// let clamd_tcp = clamav_client::Tcp{ host_address: "localhost:3310" };
+/// let client = clamav_client::Client::new(clamd_tcp).detection_sink(sink);
+/// let outcome = client.scan_file("tests/data/eicar.txt").unwrap();
+/// ```
+///
+pub struct WebhookSink {
+    url: String,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    on_failure: Option<FailureCallback>,
+    worker: Mutex<Option<Worker>>,
+}
+
+struct Worker {
+    sender: mpsc::Sender<Detection>,
+    thread: JoinHandle<()>,
+}
+
+impl WebhookSink {
+    /// Creates a sink that posts detections to `url`, with a timeout of 10
+    /// seconds per request
+    pub fn new<U: Into<String>>(url: U) -> Self {
+        WebhookSink {
+            url: url.into(),
+            headers: Vec::new(),
+            timeout: Duration::from_secs(10),
+            on_failure: None,
+            worker: Mutex::new(None),
+        }
+    }
+
+    /// Adds a header to each request, e.g. for authentication
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the timeout of each request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Calls `on_failure` with each detection that could not be posted and the
+    /// error, e.g. to log it
+    pub fn on_failure<F>(mut self, on_failure: F) -> Self
+    where
+        F: Fn(&Detection, &io::Error) + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Arc::new(on_failure));
+        self
+    }
+
+    fn spawn_worker(&self) -> Worker {
+        let (sender, receiver) = mpsc::channel::<Detection>();
+        let url = self.url.clone();
+        let headers = self.headers.clone();
+        let timeout = self.timeout;
+        let on_failure = self.on_failure.clone();
+        let thread = thread::spawn(move || {
+            let client = Client::builder().timeout(timeout).build();
+            for detection in receiver {
+                let result = match &client {
+                    Ok(client) => post(client, &url, &headers, &detection),
+                    Err(err) => Err(io::Error::other(err.to_string())),
+                };
+                if let (Err(err), Some(on_failure)) = (result, &on_failure) {
+                    on_failure(&detection, &err);
+                }
+            }
+        });
+        Worker { sender, thread }
+    }
+}
+
+fn post(
+    client: &Client,
+    url: &str,
+    headers: &[(String, String)],
+    detection: &Detection,
+) -> io::Result<()> {
+    let detected_at = detection
+        .detected_at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let body = json!({
+        "signature": detection.signature,
+        "path": detection.path.as_ref().map(|path| path.to_string_lossy()),
+        "detected_at": detected_at,
+        "correlation_id": detection.correlation_id,
+    });
+    let mut request = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(body.to_string());
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    request
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map(|_| ())
+        .map_err(io::Error::other)
+}
+
+impl DetectionSink for WebhookSink {
+    fn detected(&self, detection: &Detection) {
+        let mut worker = self.worker.lock().unwrap();
+        let worker = worker.get_or_insert_with(|| self.spawn_worker());
+        // The worker only stops when the sender is dropped
+        let _ = worker.sender.send(detection.clone());
+    }
+}
+
+impl Drop for WebhookSink {
+    fn drop(&mut self) {
+        let worker = self.worker.get_mut().unwrap_or_else(|err| err.into_inner());
+        if let Some(Worker { sender, thread }) = worker.take() {
+            drop(sender);
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::{mem, ops::Deref};
+
+/// A buffer for copies of the scanned data, e.g. a chunk buffer, which is
+/// wiped before its memory is released if the feature "zeroize" is enabled
+#[derive(Default)]
+pub(crate) struct DataBuffer(Vec<u8>);
+
+impl DataBuffer {
+    /// Returns the first `len` bytes of the buffer, growing it if necessary
+    pub(crate) fn get(&mut self, len: usize) -> &mut [u8] {
+        if self.0.len() < len {
+            // Growing the vector in place could release the old allocation
+            // without wiping it
+            wipe(&mut mem::replace(&mut self.0, vec![0; len]));
+        }
+        &mut self.0[..len]
+    }
+
+    /// Replaces the contents of the buffer with `data`
+    #[cfg(feature = "decompress")]
+    pub(crate) fn replace(&mut self, data: Vec<u8>) {
+        wipe(&mut mem::replace(&mut self.0, data));
+    }
+}
+
+impl Deref for DataBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for DataBuffer {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+/// Overwrites `data`, including its spare capacity, with zeros if the feature
+/// "zeroize" is enabled
+#[cfg_attr(not(feature = "zeroize"), allow(unused_variables, clippy::ptr_arg))]
+fn wipe(data: &mut Vec<u8>) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(data);
+}
@@ -0,0 +1,71 @@
+#[cfg(any(feature = "tracing", feature = "log"))]
+use std::fmt;
+
+/// The maximum number of bytes of a response that are recorded
+#[cfg(any(feature = "tracing", feature = "log"))]
+const MAX_TRACED_BYTES: usize = 512;
+
+/// Records the protocol at trace level with its own target, so that it can be
+/// enabled separately, as a `tracing` event if the feature "tracing" is enabled
+/// and as a `log` record otherwise
+#[cfg(any(feature = "tracing", feature = "log"))]
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(target: "clamav_client::wire", $($arg)+);
+        #[cfg(all(feature = "log", not(feature = "tracing")))]
+        log::trace!(target: "clamav_client::wire", $($arg)+);
+    };
+}
+
+/// Displays bytes hex-escaped and truncated to `MAX_TRACED_BYTES`
+#[cfg(any(feature = "tracing", feature = "log"))]
+struct Escaped<'a>(&'a [u8]);
+
+#[cfg(any(feature = "tracing", feature = "log"))]
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.0.len().min(MAX_TRACED_BYTES);
+        write!(f, "\"{}\"", self.0[..len].escape_ascii())?;
+        if self.0.len() > len {
+            write!(f, " ({} of {} bytes)", len, self.0.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Records a command sent to ClamAV
+#[cfg_attr(
+    not(any(feature = "tracing", feature = "log")),
+    allow(unused_variables)
+)]
+pub(crate) fn command(command: &[u8]) {
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    trace!("> {}", Escaped(command));
+}
+
+/// Records the size of a chunk sent to ClamAV
+#[cfg_attr(
+    not(any(feature = "tracing", feature = "log")),
+    allow(unused_variables)
+)]
+pub(crate) fn chunk(len: u64) {
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    trace!("> chunk of {} bytes", len);
+}
+
+/// Records the zero-length chunk that ends a stream
+pub(crate) fn end_of_stream() {
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    trace!("> end of stream");
+}
+
+/// Records a response received from ClamAV
+#[cfg_attr(
+    not(any(feature = "tracing", feature = "log")),
+    allow(unused_variables)
+)]
+pub(crate) fn response(response: &[u8]) {
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    trace!("< {}", Escaped(response));
+}
@@ -14,8 +14,356 @@ const OK_RESPONSE: &[u8] = b"stream: OK\0";
 const OVERSIZED_TEST_FILE_PATH: &str = "tests/data/stream-max-length-test-file.bin";
 const SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE: &[u8] = b"INSTREAM size limit exceeded. ERROR\0";
 
+#[cfg(any(feature = "tokio", feature = "async-std"))]
 fn assert_implements_send_sync<T: Send + Sync>(_t: T) {}
 
+fn assert_size_limit_exceeded<T: std::fmt::Debug>(result: std::io::Result<T>, limit: u64) {
+    let err = result.expect_err("Expected the scan to exceed the maximum stream size");
+    let clamd_err = clamav_client::ClamdError::from(err);
+    assert!(
+        matches!(clamd_err, clamav_client::ClamdError::SizeLimitExceeded { limit: l } if l == limit)
+    );
+}
+
+fn assert_invalid_options<T: std::fmt::Debug>(result: std::io::Result<T>) {
+    let err = result.expect_err("Expected the scan to reject the options");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let clamd_err = clamav_client::ClamdError::from(err);
+    assert!(matches!(
+        clamd_err,
+        clamav_client::ClamdError::InvalidOptions { .. }
+    ));
+}
+
+fn assert_batch_outcomes(outcomes: &[clamav_client::ScanOutcome]) {
+    assert_eq!(outcomes.len(), 3);
+    assert_eq!(outcomes[0].signature(), Some("Eicar-Signature"));
+    assert!(outcomes[1].is_clean());
+    assert_eq!(outcomes[2].signature(), Some("Eicar-Signature"));
+}
+
+const BATCH_FILE_PATHS: [&str; 4] = [
+    EICAR_TEST_FILE_PATH,
+    CLEAN_TEST_FILE_PATH,
+    "tests/data/missing.txt",
+    EICAR_TEST_FILE_PATH,
+];
+
+fn assert_batch_file_results<P: AsRef<std::path::Path>>(results: &clamav_client::ScanResults<P>) {
+    let summary = &results.summary;
+    assert_eq!(summary.files_scanned, 4);
+    assert_eq!(
+        (summary.clean, summary.infected, summary.errored),
+        (1, 2, 1)
+    );
+    assert_eq!(summary.signatures.get("Eicar-Signature"), Some(&2));
+
+    let results = &results.results;
+    let paths: Vec<_> = results.iter().map(|(path, _)| path.as_ref()).collect();
+    let expected_paths: Vec<_> = BATCH_FILE_PATHS.iter().map(std::path::Path::new).collect();
+    assert_eq!(paths, expected_paths);
+    assert!(results[0].1.as_ref().unwrap().is_infected());
+    assert!(results[1].1.as_ref().unwrap().is_clean());
+    assert!(matches!(
+        &results[2].1,
+        Err(clamav_client::ClamdError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound
+    ));
+    assert!(results[3].1.as_ref().unwrap().is_infected());
+}
+
+const TEST_DATA_DIR_PATH: &str = "tests/data";
+
+fn assert_directory_results<P: AsRef<std::path::Path>>(results: &clamav_client::ScanResults<P>) {
+    // The oversized test file is skipped by `max_file_size`
+    let summary = &results.summary;
+    assert_eq!(summary.files_scanned, 1);
+    assert_eq!(summary.infected, 1);
+    assert_eq!(summary.bytes_scanned, 69);
+
+    let results = &results.results;
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0].0.as_ref(),
+        std::path::Path::new(EICAR_TEST_FILE_PATH)
+    );
+    assert!(results[0].1.as_ref().unwrap().is_infected());
+}
+
+/// Returns the EICAR test file compressed in each supported encoding, with
+/// both zlib and raw data for deflate
+#[cfg(feature = "decompress")]
+fn compressed_eicar() -> Vec<(clamav_client::ContentEncoding, Vec<u8>)> {
+    use clamav_client::ContentEncoding;
+    use flate2::{write, Compression};
+    use std::io::Write;
+
+    let eicar = include_bytes!("data/eicar.txt");
+    let mut gzip = write::GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(eicar).unwrap();
+    let mut zlib = write::ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(eicar).unwrap();
+    let mut deflate = write::DeflateEncoder::new(Vec::new(), Compression::default());
+    deflate.write_all(eicar).unwrap();
+    vec![
+        (ContentEncoding::Gzip, gzip.finish().unwrap()),
+        (ContentEncoding::Deflate, zlib.finish().unwrap()),
+        (ContentEncoding::Deflate, deflate.finish().unwrap()),
+        (
+            ContentEncoding::Zstd,
+            zstd::encode_all(&eicar[..], 0).unwrap(),
+        ),
+    ]
+}
+
+/// Serves a single HTTP response on a local port and returns its URL
+#[cfg(feature = "reqwest")]
+fn serve_http_once(status: &'static str, body: &'static [u8]) -> String {
+    use std::io::{BufRead, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/file", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = std::io::BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        let mut stream = reader.into_inner();
+        let header = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        );
+        let _ = stream.write_all(header.as_bytes());
+        let _ = stream.write_all(body);
+    });
+    url
+}
+
+/// An HTTP body of the given frames, with a non-io error type like the bodies
+/// of web frameworks
+#[cfg(feature = "http-body")]
+struct TestBody(std::collections::VecDeque<Result<&'static [u8], String>>);
+
+#[cfg(feature = "http-body")]
+impl http_body::Body for TestBody {
+    type Data = &'static [u8];
+    type Error = String;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let frame = self.get_mut().0.pop_front();
+        std::task::Poll::Ready(frame.map(|frame| frame.map(http_body::Frame::data)))
+    }
+}
+
+/// Returns the body of a multipart form with the boundary `BOUNDARY` of the
+/// given fields, with a file name for file fields
+#[cfg(feature = "multipart")]
+fn multipart_body(fields: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, file_name, data) in fields {
+        body.extend_from_slice(b"--BOUNDARY\r\nContent-Disposition: form-data; name=\"");
+        body.extend_from_slice(name.as_bytes());
+        if let Some(file_name) = file_name {
+            body.extend_from_slice(b"\"; filename=\"");
+            body.extend_from_slice(file_name.as_bytes());
+        }
+        body.extend_from_slice(b"\"\r\n\r\n");
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"--BOUNDARY--\r\n");
+    body
+}
+
+/// Returns a multipart form of the given fields, with a file name for file
+/// fields, split into chunks of `chunk_size` bytes
+#[cfg(feature = "multipart")]
+fn multipart_form(
+    fields: &[(&str, Option<&str>, &[u8])],
+    chunk_size: usize,
+) -> multer::Multipart<'static> {
+    let chunks: Vec<_> = multipart_body(fields)
+        .chunks(chunk_size)
+        .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+        .collect();
+    multer::Multipart::new(tokio_stream::iter(chunks), "BOUNDARY")
+}
+
+/// An email message with a text body, a clean attachment and an attachment
+/// with the EICAR test file, both encoded in base64
+#[cfg(feature = "mail")]
+const EICAR_EMAIL: &[u8] = b"From: alice@example.com\r\n\
+To: bob@example.com\r\n\
+Subject: Files\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"BOUNDARY\"\r\n\
+\r\n\
+--BOUNDARY\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Hello Bob\r\n\
+--BOUNDARY\r\n\
+Content-Type: text/plain\r\n\
+Content-Disposition: attachment; filename=\"clean.txt\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+Y2xlYW4gZGF0YQ==\r\n\
+--BOUNDARY\r\n\
+Content-Type: application/octet-stream\r\n\
+Content-Disposition: attachment; filename=\"eicar.com\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+WDVPIVAlQEFQWzRcUFpYNTQoUF4pN0NDKTd9JEVJQ0FSLVNUQU5EQVJELUFOVElWSVJVUy1URVNU\r\n\
+LUZJTEUhJEgrSCoK\r\n\
+--BOUNDARY--\r\n";
+
+fn journal_test_path(name: &str) -> std::path::PathBuf {
+    let journal_path = std::env::temp_dir().join(format!(
+        "clamav-client-{}-{}.journal",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&journal_path);
+    journal_path
+}
+
+// Records the EICAR test file as clean, so that a resumed scan is detectable,
+// followed by an incomplete line
+const INTERRUPTED_JOURNAL: &str = "OK\ttests/data/eicar.txt\nEicar-Signature FO";
+
+type ProgressEvents = std::sync::Arc<std::sync::Mutex<Vec<clamav_client::ScanProgress>>>;
+
+fn options_with_progress() -> (clamav_client::ScanOptions, ProgressEvents) {
+    let events = ProgressEvents::default();
+    let recorded_events = events.clone();
+    let options = clamav_client::ScanOptions::new()
+        .chunk_size(1024)
+        .on_progress(move |progress| recorded_events.lock().unwrap().push(*progress));
+    (options, events)
+}
+
+fn assert_progress(events: &ProgressEvents, total_bytes: u64, known_total: bool) {
+    let events = events.lock().unwrap();
+    let last = events.last().expect("Expected progress to be reported");
+    assert_eq!(events.len() as u64, last.chunks_sent);
+    assert_eq!(last.bytes_sent, total_bytes);
+    assert_eq!(last.total_bytes, known_total.then_some(total_bytes));
+    assert!(events
+        .windows(2)
+        .all(|pair| pair[0].bytes_sent < pair[1].bytes_sent && pair[0].elapsed <= pair[1].elapsed));
+}
+
+type ScanReports = std::sync::Arc<std::sync::Mutex<Vec<clamav_client::ScanReport>>>;
+
+fn options_with_report() -> (clamav_client::ScanOptions, ScanReports) {
+    let reports = ScanReports::default();
+    let recorded_reports = reports.clone();
+    let options = clamav_client::ScanOptions::new()
+        .on_report(move |report| recorded_reports.lock().unwrap().push(report.clone()));
+    (options, reports)
+}
+
+fn assert_report(reports: &ScanReports, bytes_sent: u64) {
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(report.bytes_sent, bytes_sent);
+    assert!(report.connect_time + report.streaming_time <= report.total_duration);
+}
+
+type HookEvents = std::sync::Arc<std::sync::Mutex<Vec<String>>>;
+
+/// Adds lifecycle hooks to a client of any module that record their calls in
+/// `events`
+macro_rules! record_hooks {
+    ($client:expr, $events:expr) => {{
+        let (connect, chunk, response, error) = (
+            $events.clone(),
+            $events.clone(),
+            $events.clone(),
+            $events.clone(),
+        );
+        $client
+            .on_connect(move |_| connect.lock().unwrap().push("connect".to_owned()))
+            .on_chunk_sent(move |len| chunk.lock().unwrap().push(format!("chunk {}", len)))
+            .on_response(move |outcome| {
+                response
+                    .lock()
+                    .unwrap()
+                    .push(format!("response {:?}", outcome))
+            })
+            .on_error(move |err| error.lock().unwrap().push(format!("error {}", err)))
+    }};
+}
+
+#[cfg(feature = "sha256")]
+const EICAR_TEST_FILE_SHA256: &str =
+    "131f95c51cc819465fa1797f6ccacf9d494aaaff46fa3eac73ae63ffbdfd8267";
+#[cfg(feature = "md5")]
+const EICAR_TEST_FILE_MD5: &str = "69630e4574ec6798239b091cda43dca0";
+
+#[cfg(feature = "sha256")]
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Twice as large as the upload rate, so that sending the second half has to
+// wait for about half a second after the initial burst
+#[cfg(feature = "cache-redis")]
+const TEST_REDIS_URL: &str = "redis://localhost:6379";
+
+/// Returns a Redis cache whose keys are unique to the calling test
+#[cfg(feature = "cache-redis")]
+fn redis_cache() -> clamav_client::RedisCache {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let client = redis::Client::open(TEST_REDIS_URL).unwrap();
+    clamav_client::RedisCache::new(client)
+        .key_prefix(format!("clamav-client-test:{}:", nanos))
+        .ttl(std::time::Duration::from_secs(60))
+}
+
+#[cfg(feature = "watch")]
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(200);
+#[cfg(feature = "watch")]
+const WATCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Creates an empty directory to be watched by the calling test
+#[cfg(feature = "watch")]
+fn watch_test_dir(name: &str) -> std::path::PathBuf {
+    let dir_path = std::env::temp_dir().join(format!(
+        "clamav-client-watch-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir_path);
+    std::fs::create_dir_all(&dir_path).unwrap();
+    dir_path
+}
+
+const THROTTLED_BUFFER: &[u8] = &[b'a'; 16 * 1024];
+const THROTTLED_BYTES_PER_SECOND: u64 = 8 * 1024;
+const THROTTLED_MIN_DURATION: std::time::Duration = std::time::Duration::from_millis(400);
+
+fn throttled_options() -> clamav_client::ScanOptions {
+    clamav_client::ScanOptions::new()
+        .chunk_size(1024)
+        .max_bytes_per_second(THROTTLED_BYTES_PER_SECOND)
+}
+
+const BATCH_BUFFERS: [&[u8]; 3] = [
+    include_bytes!("data/eicar.txt"),
+    b"clean data",
+    include_bytes!("data/eicar.txt"),
+];
+
 mod lib_tests {
     use super::*;
 
@@ -112,6 +460,167 @@ mod lib_tests {
         assert_eq!(&response, clamav_client::PONG);
     }
 
+    #[test]
+    fn ping_into_tcp() {
+        let mut response = b"stale".to_vec();
+        clamav_client::ping_into(CLAMD_HOST_TCP, &mut response).expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[test]
+    fn ping_ok_tcp() {
+        clamav_client::ping_ok(CLAMD_HOST_TCP).expect("Could not ping clamd");
+    }
+
+    #[test]
+    fn ping_resolving_tcp() {
+        let clamd_tcp =
+            clamav_client::ResolvingTcp::new("localhost", 3310, clamav_client::SystemResolver);
+        let response = clamav_client::ping(clamd_tcp).expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[test]
+    fn ping_resolving_tcp_with_custom_resolver() {
+        use std::{io, net::SocketAddr, sync::Mutex};
+
+        struct Recording(Mutex<Vec<String>>);
+
+        impl clamav_client::Resolve for Recording {
+            fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+                self.0.lock().unwrap().push(host.to_owned());
+                Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+            }
+        }
+
+        let resolver = Recording(Mutex::new(Vec::new()));
+        let clamd_tcp = clamav_client::ResolvingTcp::new("clamd.invalid", 3310, &resolver);
+        let response = clamav_client::ping(clamd_tcp).expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+        let client =
+            clamav_client::Client::new(clamd_tcp).timeout(std::time::Duration::from_secs(5));
+        client.ping().expect("Could not ping clamd with a timeout");
+        assert_eq!(
+            *resolver.0.lock().unwrap(),
+            ["clamd.invalid", "clamd.invalid"]
+        );
+    }
+
+    #[test]
+    fn resolving_tcp_without_addresses() {
+        use std::{io, net::SocketAddr};
+
+        struct Unresolvable;
+
+        impl clamav_client::Resolve for Unresolvable {
+            fn resolve(&self, _host: &str, _port: u16) -> io::Result<Vec<SocketAddr>> {
+                Ok(Vec::new())
+            }
+        }
+
+        let clamd_tcp = clamav_client::ResolvingTcp::new("clamd.invalid", 3310, Unresolvable);
+        let err = clamav_client::ping(clamd_tcp).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn caching_resolver_reuses_addresses_until_expiry() {
+        use clamav_client::Resolve;
+        use std::{
+            io,
+            net::SocketAddr,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            time::Duration,
+        };
+
+        struct Counting(Arc<AtomicUsize>);
+
+        impl clamav_client::Resolve for Counting {
+            fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                if host == "unresolvable.invalid" {
+                    return Err(io::ErrorKind::NotFound.into());
+                }
+                Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+            }
+        }
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let resolver = clamav_client::CachingResolver::new(
+            Counting(lookups.clone()),
+            Duration::from_millis(200),
+        );
+        let clamd_tcp = clamav_client::ResolvingTcp::new("clamd.invalid", 3310, &resolver);
+        for _ in 0..3 {
+            clamav_client::ping(clamd_tcp).expect("Could not ping clamd");
+        }
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        std::thread::sleep(Duration::from_millis(250));
+        clamav_client::ping(clamd_tcp).expect("Could not ping clamd");
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        resolver.clear();
+        clamav_client::ping(clamd_tcp).expect("Could not ping clamd");
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+
+        assert!(resolver.resolve("unresolvable.invalid", 3310).is_err());
+        assert!(resolver.resolve("unresolvable.invalid", 3310).is_err());
+        assert_eq!(lookups.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "hickory-dns")]
+    fn srv_endpoints_ordered_by_priority_and_weight() {
+        use clamav_client::SrvEndpoint;
+
+        let endpoints = vec![
+            SrvEndpoint::new("backup.example.com.", 3310, 20, 10),
+            SrvEndpoint::new("idle.example.com.", 3310, 10, 0),
+            SrvEndpoint::new("clamd.example.com.", 3310, 10, 100),
+        ];
+        let mut idle_first = 0;
+        for _ in 0..100 {
+            let ordered = SrvEndpoint::ordered(endpoints.clone());
+            assert_eq!(ordered.len(), 3);
+            assert_eq!(ordered[2].target, "backup.example.com.");
+            if ordered[0].target == "idle.example.com." {
+                idle_first += 1;
+            }
+        }
+        // The endpoint without weight only comes first if the random number
+        // is zero, i.e. with a probability of 1 in 101
+        assert!(idle_first < 20, "{} of 100", idle_first);
+
+        assert!(SrvEndpoint::ordered(Vec::new()).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "hickory-dns")]
+    fn ping_resolving_tcp_with_hickory_resolver() {
+        let resolver = clamav_client::HickoryResolver::from_system_conf().unwrap();
+        let clamd_tcp = clamav_client::ResolvingTcp::new("localhost", 3310, resolver);
+        let response = clamav_client::ping(clamd_tcp).expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[test]
+    fn scan_tcp_buffer_into() {
+        let options = clamav_client::ScanOptions::new().chunk_size(16);
+        let mut response = Vec::new();
+        let buffer = include_bytes!("data/eicar.txt");
+        clamav_client::scan_buffer_into(buffer, CLAMD_HOST_TCP, &options, &mut response)
+            .expect("Could not scan EICAR test string");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+
+        clamav_client::scan_buffer_into(b"hello", CLAMD_HOST_TCP, &options, &mut response)
+            .expect("Could not scan clean buffer");
+        assert_eq!(&response, OK_RESPONSE);
+    }
+
     #[test]
     fn get_version_tcp() {
         let err_msg = format!(
@@ -122,6 +631,53 @@ mod lib_tests {
         assert!(&response.starts_with(b"ClamAV"));
     }
 
+    #[test]
+    fn reload_tcp() {
+        let response = clamav_client::reload(CLAMD_HOST_TCP).expect("Could not reload ClamAV");
+        assert_eq!(&response, b"RELOADING\0");
+    }
+
+    #[test]
+    fn schedule_reload_tcp() {
+        use std::time::Duration;
+
+        let schedule = clamav_client::ReloadSchedule::every(Duration::from_millis(20))
+            .jitter(Duration::from_millis(10))
+            .poll_interval(Duration::from_millis(10));
+        let scheduler = clamav_client::schedule_reload(CLAMD_HOST_TCP, schedule);
+        let reports: Vec<clamav_client::ReloadReport> = scheduler
+            .take(2)
+            .map(|result| result.expect("Could not reload ClamAV"))
+            .collect();
+        assert!(reports[0].version.starts_with(b"ClamAV"));
+        assert!(reports[1].started_at > reports[0].started_at);
+    }
+
+    #[test]
+    fn schedule_reload_continues_after_failure() {
+        use std::time::Duration;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host_address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let clamd_tcp = clamav_client::Tcp { host_address };
+        let schedule = clamav_client::ReloadSchedule::every(Duration::from_millis(10));
+        let scheduler = clamav_client::schedule_reload(clamd_tcp, schedule);
+        for _ in 0..2 {
+            let result = scheduler.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert!(matches!(result, Err(clamav_client::ClamdError::Io(_))));
+        }
+    }
+
+    #[test]
+    fn get_stats_tcp() {
+        let response =
+            clamav_client::get_stats(CLAMD_HOST_TCP).expect("Could not get ClamAV stats");
+        assert!(response.starts_with(b"POOLS:"));
+        assert!(response.ends_with(b"END\0"));
+    }
+
     #[test]
     fn scan_tcp_infected_file() {
         let err_msg = format!(
@@ -169,632 +725,9107 @@ mod lib_tests {
         assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
         assert_eq!(clamav_client::clean(&response), Ok(false));
     }
-}
-
-#[cfg(feature = "tokio")]
-mod tokio_tests {
-    use super::*;
-
-    const CLAMD_HOST_TCP: clamav_client::tokio::Tcp<&str> = clamav_client::tokio::Tcp {
-        host_address: TEST_HOST_ADDRESS,
-    };
-
-    #[cfg(unix)]
-    const CLAMD_HOST_SOCKET: clamav_client::tokio::Socket<&str> = clamav_client::tokio::Socket {
-        socket_path: TEST_SOCKET_PATH,
-    };
-
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_ping_socket() {
-        let err_msg = format!(
-            "Could not ping clamd via Unix socket at {}",
-            CLAMD_HOST_SOCKET.socket_path
-        );
-        let response = clamav_client::tokio::ping(CLAMD_HOST_SOCKET)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, clamav_client::PONG);
-    }
 
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_get_version_socket() {
+    #[test]
+    fn scan_tcp_infected_file_with_adaptive_chunk_size() {
         let err_msg = format!(
-            "Could not get ClamAV version via Unix socket at {}",
-            CLAMD_HOST_SOCKET.socket_path
+            "Could not scan test file {} via TCP at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
         );
-        let response = clamav_client::tokio::get_version(CLAMD_HOST_SOCKET)
-            .await
+        let heuristic = clamav_client::AdaptiveChunkSize { min: 1, max: 8 };
+        let options = clamav_client::ScanOptions::new().adaptive_chunk_size(heuristic);
+        let response = clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
             .expect(&err_msg);
-        assert!(&response.starts_with(b"ClamAV"));
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
     }
 
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_scan_socket_infected_file() {
+    #[test]
+    #[cfg(all(feature = "sendfile", target_os = "linux"))]
+    fn scan_socket_infected_file_zero_copy() {
         let err_msg = format!(
             "Could not scan test file {} via socket at {}",
             EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
         );
         let response =
-            clamav_client::tokio::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
-                .await
+            clamav_client::scan_file_zero_copy(EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
                 .expect(&err_msg);
         assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
         assert_eq!(clamav_client::clean(&response), Ok(false));
     }
 
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_scan_socket_infected_buffer() {
+    #[test]
+    #[cfg(all(feature = "sendfile", target_os = "linux"))]
+    fn scan_tcp_infected_file_zero_copy() {
         let err_msg = format!(
-            "Could not scan EICAR test string via socket at {}",
-            CLAMD_HOST_SOCKET.socket_path
+            "Could not scan test file {} via TCP at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
         );
-        let buffer = include_bytes!("data/eicar.txt");
-        let response = clamav_client::tokio::scan_buffer(buffer, CLAMD_HOST_SOCKET, None)
-            .await
-            .expect(&err_msg);
+        let options = clamav_client::ScanOptions::new().chunk_size(16);
+        let response =
+            clamav_client::scan_file_zero_copy(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+                .expect(&err_msg);
         assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
         assert_eq!(clamav_client::clean(&response), Ok(false));
     }
 
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_scan_socket_clean_file() {
+    #[test]
+    #[cfg(all(feature = "sendfile", target_os = "linux"))]
+    fn scan_tcp_oversized_file_zero_copy() {
         let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+            "Could not scan test file {} via TCP at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
         );
         let response =
-            clamav_client::tokio::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
-                .await
+            clamav_client::scan_file_zero_copy(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
                 .expect(&err_msg);
-        assert_eq!(&response, OK_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(true));
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
     }
 
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_scan_socket_oversized_file() {
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn scan_tcp_infected_file_memory_mapped() {
         let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+            "Could not scan test file {} via TCP at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
         );
-        let response =
-            clamav_client::tokio::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
-                .await
-                .expect(&err_msg);
-        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        let options = clamav_client::ScanOptions::new().memory_map(true);
+        let response = clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
         assert_eq!(clamav_client::clean(&response), Ok(false));
     }
 
-    #[tokio::test]
-    async fn async_tokio_ping_tcp() {
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn scan_tcp_oversized_file_memory_mapped() {
         let err_msg = format!(
-            "Could not ping clamd via TCP at {}",
-            CLAMD_HOST_TCP.host_address
+            "Could not scan test file {} via TCP at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
         );
-        let response = clamav_client::tokio::ping(CLAMD_HOST_TCP)
-            .await
+        let options = clamav_client::ScanOptions::new().memory_map(true);
+        let response = clamav_client::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
             .expect(&err_msg);
-        assert_eq!(&response, clamav_client::PONG);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
     }
 
-    #[tokio::test]
-    async fn async_tokio_get_version_tcp() {
+    #[test]
+    #[cfg(unix)]
+    fn scan_socket_buffers() {
         let err_msg = format!(
-            "Could not get ClamAV version via TCP at {}",
-            CLAMD_HOST_TCP.host_address
+            "Could not scan buffers via socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
         );
-        let response = clamav_client::tokio::get_version(CLAMD_HOST_TCP)
-            .await
-            .expect(&err_msg);
-        assert!(&response.starts_with(b"ClamAV"));
+        let outcomes =
+            clamav_client::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_SOCKET, None).expect(&err_msg);
+        assert_batch_outcomes(&outcomes);
     }
 
-    #[tokio::test]
-    async fn async_tokio_scan_tcp_infected_file() {
+    #[test]
+    fn scan_tcp_buffers() {
         let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+            "Could not scan buffers via TCP at {}",
+            CLAMD_HOST_TCP.host_address
         );
-        let response = clamav_client::tokio::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        let outcomes =
+            clamav_client::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_TCP, None).expect(&err_msg);
+        assert_batch_outcomes(&outcomes);
     }
 
-    #[tokio::test]
-    async fn async_tokio_scan_tcp_infected_buffer() {
-        let err_msg = format!(
-            "Could not scan EICAR test string via TCP at {}",
-            CLAMD_HOST_TCP.host_address
+    #[test]
+    fn scan_tcp_buffers_pipelined() {
+        let options = clamav_client::ScanOptions::new().pipeline_depth(2);
+        let outcomes = clamav_client::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_TCP, options)
+            .expect("Could not scan buffers via TCP");
+        assert_batch_outcomes(&outcomes);
+    }
+
+    #[test]
+    fn scan_buffers_pipelined_out_of_order_responses() {
+        use std::io::{BufRead, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host_address = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(&stream);
+            let mut command = Vec::new();
+            reader.read_until(0, &mut command).unwrap();
+            assert_eq!(command, b"zIDSESSION\0");
+            for _ in 0..2 {
+                command.clear();
+                reader.read_until(0, &mut command).unwrap();
+                assert_eq!(command, b"zINSTREAM\0");
+                loop {
+                    let mut header = [0; 4];
+                    reader.read_exact(&mut header).unwrap();
+                    let len = u32::from_be_bytes(header) as u64;
+                    if len == 0 {
+                        break;
+                    }
+                    std::io::copy(&mut (&mut reader).take(len), &mut std::io::sink()).unwrap();
+                }
+            }
+            // Answers the second buffer before the first one
+            (&stream).write_all(b"2: stream: OK\0").unwrap();
+            (&stream)
+                .write_all(b"1: stream: Eicar-Signature FOUND\0")
+                .unwrap();
+            command.clear();
+            reader.read_until(0, &mut command).unwrap();
+            assert_eq!(command, b"zEND\0");
+        });
+
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: host_address.as_str(),
+        };
+        let options = clamav_client::ScanOptions::new().pipeline_depth(2);
+        let outcomes =
+            clamav_client::scan_buffers(&BATCH_BUFFERS[..2], clamd_tcp, options).unwrap();
+        server.join().unwrap();
+        assert_eq!(outcomes[0].signature(), Some("Eicar-Signature"));
+        assert!(outcomes[1].is_clean());
+    }
+
+    #[test]
+    fn scan_tcp_files_bundled() {
+        let results = clamav_client::scan_files_bundled(
+            BATCH_FILE_PATHS,
+            CLAMD_HOST_TCP,
+            None,
+            clamav_client::BundleOptions::new(),
         );
-        let buffer = include_bytes!("data/eicar.txt");
-        let response = clamav_client::tokio::scan_buffer(buffer, CLAMD_HOST_TCP, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        assert_batch_file_results(&results);
     }
 
-    #[tokio::test]
-    async fn async_tokio_scan_tcp_clean_file() {
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+    #[test]
+    fn scan_tcp_files_bundled_maps_infected_member() {
+        let dir_path =
+            std::env::temp_dir().join(format!("clamav-client-bundle-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let file_paths: Vec<_> = (0..50)
+            .map(|index| {
+                let file_path = dir_path.join(format!("{}.txt", index));
+                let contents: &[u8] = if index == 37 {
+                    include_bytes!("data/eicar.txt")
+                } else {
+                    b"clean data"
+                };
+                std::fs::write(&file_path, contents).unwrap();
+                file_path
+            })
+            .collect();
+
+        // A chunk size below the tar block size splits the headers
+        let (options, reports) = options_with_report();
+        let results = clamav_client::scan_files_bundled(
+            &file_paths,
+            CLAMD_HOST_TCP,
+            options.chunk_size(100),
+            clamav_client::BundleOptions::new().max_files(40),
         );
-        let response = clamav_client::tokio::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, OK_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(true));
+        assert_eq!(results.summary.clean, 49);
+        assert_eq!(results.summary.infected, 1);
+        assert!(results.results[37].1.as_ref().unwrap().is_infected());
+        assert!(reports.lock().unwrap().len() < 20);
+        std::fs::remove_dir_all(&dir_path).unwrap();
     }
 
-    #[tokio::test]
-    async fn async_tokio_scan_tcp_oversized_file() {
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+    #[test]
+    fn scan_tcp_files_bundled_without_server() {
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: "localhost:1",
+        };
+        let file_paths = [CLEAN_TEST_FILE_PATH, EICAR_TEST_FILE_PATH];
+        let results = clamav_client::scan_files_bundled(
+            file_paths,
+            clamd_tcp,
+            None,
+            clamav_client::BundleOptions::new(),
         );
-        let response =
-            clamav_client::tokio::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
-                .await
-                .expect(&err_msg);
-        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        assert_eq!(results.summary.errored, 2);
+        assert!(results.results.iter().all(|(_, result)| matches!(
+            result,
+            Err(clamav_client::ClamdError::Io(err)) if err.kind() == std::io::ErrorKind::ConnectionRefused
+        )));
     }
 
-    #[tokio::test]
-    async fn async_tokio_implements_send_sync_trait() {
-        trait _AssertSendSync: Send + Sync {}
+    #[test]
+    fn scan_tcp_reader() {
+        let file = std::fs::File::open(EICAR_TEST_FILE_PATH).unwrap();
+        let response = clamav_client::scan_reader(file, CLAMD_HOST_TCP, None)
+            .expect("Could not scan reader via TCP");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
 
-        impl _AssertSendSync for clamav_client::tokio::Tcp<&str> {}
-        assert_implements_send_sync(clamav_client::tokio::scan_buffer(&[], CLAMD_HOST_TCP, None));
+    #[test]
+    #[cfg(unix)]
+    fn scan_tcp_command_output() {
+        let mut command = std::process::Command::new("cat");
+        command.arg(EICAR_TEST_FILE_PATH);
+        let response = clamav_client::scan_command_output(&mut command, CLAMD_HOST_TCP, None)
+            .expect("Could not scan command output via TCP");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
 
-        #[cfg(unix)]
-        {
-            impl _AssertSendSync for clamav_client::tokio::Socket<&str> {}
-            assert_implements_send_sync(clamav_client::tokio::scan_buffer(
-                &[],
-                CLAMD_HOST_SOCKET,
-                None,
-            ));
-        }
+    #[test]
+    #[cfg(unix)]
+    fn scan_tcp_command_output_with_failing_command() {
+        let mut command = std::process::Command::new("sh");
+        command.args(["-c", "cat tests/data/eicar.txt; exit 3"]);
+        let err =
+            clamav_client::scan_command_output(&mut command, CLAMD_HOST_TCP, None).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
     }
-}
 
-#[cfg(feature = "tokio-stream")]
-mod tokio_stream_tests {
-    use super::*;
-    use std::path::Path;
-    use tokio::fs::File;
-    use tokio_util::io::ReaderStream;
+    #[test]
+    #[cfg(unix)]
+    fn scan_tcp_command_output_size_limit() {
+        // The command would not exit by itself, so it must be killed
+        let mut command = std::process::Command::new("cat");
+        command.arg("/dev/zero");
+        let options = clamav_client::ScanOptions::new().max_stream_size(1000);
+        let result = clamav_client::scan_command_output(&mut command, CLAMD_HOST_TCP, options);
+        assert_size_limit_exceeded(result, 1000);
+    }
 
-    const CLAMD_HOST_TCP: clamav_client::tokio::Tcp<&str> = clamav_client::tokio::Tcp {
-        host_address: TEST_HOST_ADDRESS,
-    };
+    #[test]
+    #[cfg(feature = "decompress")]
+    fn scan_tcp_decompressed_reader() {
+        use clamav_client::{ContentEncoding, Decompress};
 
-    #[cfg(unix)]
-    const CLAMD_HOST_SOCKET: clamav_client::tokio::Socket<&str> = clamav_client::tokio::Socket {
-        socket_path: TEST_SOCKET_PATH,
-    };
+        for (encoding, compressed) in compressed_eicar() {
+            let reader = Decompress::new(&compressed[..], encoding).unwrap();
+            let response = clamav_client::scan_reader(reader, CLAMD_HOST_TCP, None)
+                .expect("Could not scan decompressed reader via TCP");
+            assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+            if encoding != ContentEncoding::Deflate {
+                assert_eq!(ContentEncoding::detect(&compressed), Some(encoding));
+            }
+        }
+    }
 
-    async fn stream_from_file<P: AsRef<Path>>(path: P) -> ReaderStream<File> {
-        let path_str = path.as_ref().to_str().expect("Invalid path");
-        let err_msg = format!("Could not read test file {}", path_str);
-        let file = File::open(path).await.expect(&err_msg);
-        ReaderStream::with_capacity(file, 16)
+    #[test]
+    #[cfg(feature = "decompress")]
+    fn scan_tcp_decompressed_reader_with_invalid_data() {
+        use clamav_client::{ContentEncoding, Decompress};
+
+        let reader = Decompress::new(&b"not gzip data"[..], ContentEncoding::Gzip).unwrap();
+        let result = clamav_client::scan_reader(reader, CLAMD_HOST_TCP, None);
+        assert!(result.is_err());
     }
 
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_scan_socket_infected_stream() {
-        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
+    #[test]
+    #[cfg(feature = "mail")]
+    fn scan_tcp_message() {
+        let parts = clamav_client::scan_message(EICAR_EMAIL, CLAMD_HOST_TCP, None)
+            .expect("Could not scan email message via TCP");
+        assert_eq!(parts.len(), 3);
 
-        let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        assert!(!parts[0].is_attachment);
+        assert_eq!(parts[0].content_type.as_deref(), Some("text/plain"));
+        assert!(matches!(
+            parts[0].result,
+            Ok(clamav_client::ScanOutcome::Clean)
+        ));
+
+        assert!(parts[1].is_attachment);
+        assert_eq!(parts[1].file_name.as_deref(), Some("clean.txt"));
+        assert_eq!(parts[1].size, 10);
+        assert!(matches!(
+            parts[1].result,
+            Ok(clamav_client::ScanOutcome::Clean)
+        ));
+
+        assert!(parts[2].is_attachment);
+        assert_eq!(parts[2].file_name.as_deref(), Some("eicar.com"));
+        assert_eq!(
+            parts[2].content_type.as_deref(),
+            Some("application/octet-stream")
         );
-        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_SOCKET, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        assert!(matches!(
+            &parts[2].result,
+            Ok(clamav_client::ScanOutcome::Infected { signature }) if signature == "Eicar-Signature"
+        ));
     }
 
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_scan_socket_clean_stream() {
-        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
+    #[test]
+    #[cfg(feature = "mail")]
+    fn scan_tcp_message_size_limit() {
+        let options = clamav_client::ScanOptions::new().max_stream_size(16);
+        let parts = clamav_client::scan_message(EICAR_EMAIL, CLAMD_HOST_TCP, options)
+            .expect("Could not scan email message via TCP");
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(
+            parts[0].result,
+            Ok(clamav_client::ScanOutcome::Clean)
+        ));
+        assert!(matches!(
+            parts[1].result,
+            Ok(clamav_client::ScanOutcome::Clean)
+        ));
+        assert!(matches!(
+            parts[2].result,
+            Err(clamav_client::ClamdError::SizeLimitExceeded { limit: 16 })
+        ));
+    }
 
-        let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
-        );
-        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_SOCKET, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, OK_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(true));
+    #[test]
+    #[cfg(feature = "mail")]
+    fn scan_invalid_message() {
+        let err = clamav_client::scan_message(b"", CLAMD_HOST_TCP, None).unwrap_err();
+        match err {
+            clamav_client::ClamdError::Io(err) => {
+                assert_eq!(err.kind(), std::io::ErrorKind::InvalidData)
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
     }
 
-    #[tokio::test]
-    #[cfg(unix)]
-    async fn async_tokio_scan_socket_oversized_stream() {
-        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
+    #[test]
+    #[cfg(feature = "decompress")]
+    fn scan_tcp_decompressed_reader_size_limit() {
+        use clamav_client::{ContentEncoding, Decompress};
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
 
-        let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
-        );
-        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_SOCKET, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        // The limit applies to the decompressed data, not the compressed data
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&[0; 100_000]).unwrap();
+        let compressed = gzip.finish().unwrap();
+        assert!(compressed.len() < 1000);
+        let options = clamav_client::ScanOptions::new().max_stream_size(1000);
+        let reader = Decompress::new(&compressed[..], ContentEncoding::Gzip).unwrap();
+        let result = clamav_client::scan_reader(reader, CLAMD_HOST_TCP, options);
+        assert_size_limit_exceeded(result, 1000);
     }
 
-    #[tokio::test]
-    async fn async_tokio_scan_tcp_infected_stream() {
-        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
+    #[test]
+    fn scan_queue_tcp_jobs() {
+        use clamav_client::{ScanJob, ScanQueue, ScanQueueOptions};
 
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
-        );
-        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        let queue_options = ScanQueueOptions::new().workers(2);
+        let queue = ScanQueue::new(CLAMD_HOST_TCP, None, queue_options).unwrap();
+        let file_scan = queue.submit(ScanJob::file(EICAR_TEST_FILE_PATH));
+        let buffer_scan = queue.submit(ScanJob::buffer(CLEAN_TEST_FILE_PATH.as_bytes()));
+        let stream_scan = queue.submit(ScanJob::stream(
+            std::fs::File::open(EICAR_TEST_FILE_PATH).unwrap(),
+        ));
+        assert!(file_scan.wait().unwrap().is_infected());
+        assert!(buffer_scan.wait().unwrap().is_clean());
+        assert!(stream_scan.wait().unwrap().is_infected());
     }
 
-    #[tokio::test]
-    async fn async_tokio_scan_tcp_clean_stream() {
-        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
+    #[test]
+    fn scan_queue_reuses_sessions() {
+        use clamav_client::{ScanJob, ScanQueue, ScanQueueOptions};
 
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
-        );
-        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, OK_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(true));
+        let queue_options = ScanQueueOptions::new().workers(1);
+        let queue = ScanQueue::new(CLAMD_HOST_TCP, None, queue_options).unwrap();
+        for _ in 0..3 {
+            for (index, buffer) in BATCH_BUFFERS.iter().enumerate() {
+                let outcome = queue.submit(ScanJob::buffer(*buffer)).wait().unwrap();
+                assert_eq!(outcome.is_clean(), index == 1);
+            }
+        }
+        queue.shutdown();
     }
 
-    #[tokio::test]
-    async fn async_tokio_scan_tcp_oversized_stream() {
-        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
+    #[test]
+    fn scan_queue_size_limit() {
+        use clamav_client::{ClamdError, ScanJob, ScanQueue, ScanQueueOptions};
 
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
-        );
-        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        let options = clamav_client::ScanOptions::new().max_stream_size(16);
+        let queue = ScanQueue::new(CLAMD_HOST_TCP, options, ScanQueueOptions::new()).unwrap();
+        let result = queue.submit(ScanJob::file(EICAR_TEST_FILE_PATH)).wait();
+        assert!(matches!(
+            result,
+            Err(ClamdError::SizeLimitExceeded { limit: 16 })
+        ));
     }
-}
 
-#[cfg(feature = "async-std")]
-mod async_std_tests {
-    use super::*;
+    #[test]
+    fn scan_queue_try_submit_when_full() {
+        use clamav_client::{ScanJob, ScanQueue, ScanQueueOptions};
 
-    const CLAMD_HOST_TCP: clamav_client::async_std::Tcp<&str> = clamav_client::async_std::Tcp {
-        host_address: TEST_HOST_ADDRESS,
-    };
+        // Blocks the only worker until the sender is dropped
+        struct BlockingReader(std::sync::mpsc::Receiver<()>);
 
-    #[cfg(unix)]
-    const CLAMD_HOST_SOCKET: clamav_client::async_std::Socket<&str> =
-        clamav_client::async_std::Socket {
-            socket_path: TEST_SOCKET_PATH,
-        };
+        impl std::io::Read for BlockingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                let _ = self.0.recv();
+                Ok(0)
+            }
+        }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_ping_socket() {
-        let err_msg = format!(
-            "Could not ping clamd via Unix socket at {}",
-            CLAMD_HOST_SOCKET.socket_path
-        );
-        let response = clamav_client::async_std::ping(CLAMD_HOST_SOCKET)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, clamav_client::PONG);
+        let (unblock, blocked) = std::sync::mpsc::channel();
+        let queue_options = ScanQueueOptions::new().workers(1).capacity(1);
+        let queue = ScanQueue::new(CLAMD_HOST_TCP, None, queue_options).unwrap();
+        let blocking_scan = queue.submit(ScanJob::stream(BlockingReader(blocked)));
+        let queued_scan = queue.submit(ScanJob::file(EICAR_TEST_FILE_PATH));
+        let rejected = queue.try_submit(ScanJob::file(EICAR_TEST_FILE_PATH));
+        assert!(matches!(rejected, Err(ScanJob::File(_))));
+        assert!(!queued_scan.is_finished());
+
+        drop(unblock);
+        assert!(blocking_scan.wait().unwrap().is_clean());
+        assert!(queued_scan.wait().unwrap().is_infected());
     }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_get_version_socket() {
-        let err_msg = format!(
-            "Could not get ClamAV version via Unix socket at {}",
-            CLAMD_HOST_SOCKET.socket_path
+    #[test]
+    fn scan_tcp_directory() {
+        let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+        let results = clamav_client::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
         );
-        let response = clamav_client::async_std::get_version(CLAMD_HOST_SOCKET)
-            .await
-            .expect(&err_msg);
-        assert!(&response.starts_with(b"ClamAV"));
+        assert_directory_results(&results);
     }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_scan_socket_infected_file() {
-        let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+    #[test]
+    fn scan_tcp_directory_without_max_file_size() {
+        let results = clamav_client::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            clamav_client::DirectoryScanOptions::new(),
+        )
+        .results;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].1.as_ref().unwrap().is_infected());
+        assert_eq!(
+            results[1].0.as_path(),
+            std::path::Path::new(OVERSIZED_TEST_FILE_PATH)
         );
-        let response =
-            clamav_client::async_std::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
-                .await
-                .expect(&err_msg);
-        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        assert!(matches!(
+            results[1].1,
+            Ok(clamav_client::ScanOutcome::Error { .. })
+        ));
     }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_scan_socket_infected_buffer() {
-        let err_msg = format!(
-            "Could not scan EICAR test string via socket at {}",
-            CLAMD_HOST_SOCKET.socket_path
-        );
-        let buffer = include_bytes!("data/eicar.txt");
-        let response = clamav_client::async_std::scan_buffer(buffer, CLAMD_HOST_SOCKET, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+    #[test]
+    fn scan_tcp_directory_with_max_depth() {
+        let directory_options = clamav_client::DirectoryScanOptions::new().max_depth(0);
+        let results =
+            clamav_client::scan_directory("tests", CLAMD_HOST_TCP, None, directory_options).results;
+        let paths: Vec<_> = results.iter().map(|(path, _)| path.as_path()).collect();
+        assert_eq!(paths, [std::path::Path::new("tests/clamav_client.rs")]);
     }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_scan_socket_clean_file() {
-        let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn scan_tcp_directory_parallel() {
+        let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+        let results = clamav_client::scan_directory_parallel(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
         );
-        let response =
-            clamav_client::async_std::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
-                .await
-                .expect(&err_msg);
-        assert_eq!(&response, OK_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(true));
+        assert_directory_results(&results);
     }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_scan_socket_oversized_file() {
-        let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+    #[test]
+    #[cfg(feature = "ignore")]
+    fn scan_tcp_directory_with_include_glob() {
+        let directory_options = clamav_client::DirectoryScanOptions::new().include("*.txt");
+        let results = clamav_client::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
         );
-        let response =
-            clamav_client::async_std::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
-                .await
-                .expect(&err_msg);
-        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        assert_directory_results(&results);
     }
 
-    #[async_std::test]
-    async fn async_std_ping_tcp() {
-        let err_msg = format!(
-            "Could not ping clamd via TCP at {}",
-            CLAMD_HOST_TCP.host_address
+    #[test]
+    #[cfg(feature = "ignore")]
+    fn scan_tcp_directory_with_exclude_globs() {
+        let directory_options = clamav_client::DirectoryScanOptions::new()
+            .include("*.rs")
+            .exclude("async_std_util");
+        let results =
+            clamav_client::scan_directory("tests", CLAMD_HOST_TCP, None, directory_options).results;
+        let paths: Vec<_> = results.iter().map(|(path, _)| path.as_path()).collect();
+        assert_eq!(paths, [std::path::Path::new("tests/clamav_client.rs")]);
+    }
+
+    #[test]
+    #[cfg(feature = "ignore")]
+    fn scan_tcp_directory_with_ignore_file() {
+        let ignore_file = std::env::temp_dir().join("clamav-client-test.scanignore");
+        std::fs::write(&ignore_file, "# Skip oversized files\n*.bin\n").unwrap();
+        let directory_options =
+            clamav_client::DirectoryScanOptions::new().ignore_file(&ignore_file);
+        let results = clamav_client::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
         );
-        let response = clamav_client::async_std::ping(CLAMD_HOST_TCP)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, clamav_client::PONG);
+        std::fs::remove_file(&ignore_file).unwrap();
+        assert_directory_results(&results);
     }
 
-    #[async_std::test]
-    async fn async_std_get_version_tcp() {
-        let err_msg = format!(
-            "Could not get ClamAV version via TCP at {}",
-            CLAMD_HOST_TCP.host_address
+    #[test]
+    fn scan_tcp_directory_with_journal() {
+        let journal_path = journal_test_path("scan");
+        let directory_options = clamav_client::DirectoryScanOptions::new()
+            .max_file_size(1_000_000)
+            .journal(&journal_path);
+        for _ in 0..2 {
+            let results = clamav_client::scan_directory(
+                TEST_DATA_DIR_PATH,
+                CLAMD_HOST_TCP,
+                None,
+                directory_options.clone(),
+            );
+            assert_directory_results(&results);
+        }
+        let journal = std::fs::read_to_string(&journal_path).unwrap();
+        assert_eq!(journal, "Eicar-Signature FOUND\ttests/data/eicar.txt\n");
+        std::fs::remove_file(&journal_path).unwrap();
+    }
+
+    #[test]
+    fn scan_tcp_directory_resumes_from_journal() {
+        let journal_path = journal_test_path("resume");
+        std::fs::write(&journal_path, INTERRUPTED_JOURNAL).unwrap();
+        let directory_options = clamav_client::DirectoryScanOptions::new()
+            .max_file_size(1_000_000)
+            .journal(&journal_path);
+        let results = clamav_client::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
         );
-        let response = clamav_client::async_std::get_version(CLAMD_HOST_TCP)
-            .await
-            .expect(&err_msg);
-        assert!(&response.starts_with(b"ClamAV"));
+        assert_eq!(results.summary.clean, 1);
+        assert_eq!(results.summary.infected, 0);
+
+        // The incomplete line is terminated and stays ignored
+        let journal = std::fs::read_to_string(&journal_path).unwrap();
+        assert_eq!(journal, format!("{}\n", INTERRUPTED_JOURNAL));
+        std::fs::remove_file(&journal_path).unwrap();
     }
 
-    #[async_std::test]
-    async fn async_std_scan_tcp_infected_file() {
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn scan_tcp_directory_parallel_resumes_from_journal() {
+        let journal_path = journal_test_path("resume-parallel");
+        std::fs::write(&journal_path, INTERRUPTED_JOURNAL).unwrap();
+        let directory_options = clamav_client::DirectoryScanOptions::new()
+            .max_file_size(1_000_000)
+            .journal(&journal_path);
+        let results = clamav_client::scan_directory_parallel(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
         );
-        let response =
-            clamav_client::async_std::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
-                .await
-                .expect(&err_msg);
+        assert_eq!(results.summary.clean, 1);
+        std::fs::remove_file(&journal_path).unwrap();
+    }
+
+    #[test]
+    fn scan_tcp_directory_with_invalid_journal() {
+        // A directory cannot be opened as a journal
+        let directory_options =
+            clamav_client::DirectoryScanOptions::new().journal(TEST_DATA_DIR_PATH);
+        let results = clamav_client::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
+        )
+        .results;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, std::path::Path::new(TEST_DATA_DIR_PATH));
+        assert!(results[0].1.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "ignore")]
+    fn scan_tcp_directory_with_invalid_glob() {
+        let directory_options = clamav_client::DirectoryScanOptions::new().exclude("[");
+        let results = clamav_client::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
+        )
+        .results;
+        assert_eq!(results.len(), 1);
+        assert!(matches!(
+            &results[0].1,
+            Err(clamav_client::ClamdError::Io(err)) if err.kind() == std::io::ErrorKind::InvalidInput
+        ));
+    }
+
+    #[test]
+    fn scan_tcp_missing_directory() {
+        let results = clamav_client::scan_directory(
+            "tests/missing",
+            CLAMD_HOST_TCP,
+            None,
+            clamav_client::DirectoryScanOptions::new(),
+        )
+        .results;
+        assert_eq!(results.len(), 1);
+        assert!(matches!(
+            &results[0].1,
+            Err(clamav_client::ClamdError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn scan_tcp_file_with_progress() {
+        let (options, events) = options_with_progress();
+        let response = clamav_client::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+            .expect("Could not scan file with progress");
+        assert_eq!(&response, OK_RESPONSE);
+        let file_size = std::fs::metadata(CLEAN_TEST_FILE_PATH).unwrap().len();
+        assert_progress(&events, file_size, true);
+    }
+
+    #[test]
+    fn scan_tcp_file_with_report() {
+        let (options, reports) = options_with_report();
+        let response = clamav_client::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+            .expect("Could not scan file with report");
+        assert_eq!(&response, OK_RESPONSE);
+        let file_size = std::fs::metadata(CLEAN_TEST_FILE_PATH).unwrap().len();
+        assert_report(&reports, file_size);
+    }
+
+    #[test]
+    #[cfg(feature = "hash")]
+    fn scan_tcp_file_with_digests() {
+        let (options, reports) = options_with_report();
+        let options = options.chunk_size(16).sha256(true).md5(true);
+        let response = clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+            .expect("Could not scan file with digests");
         assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        let report = reports.lock().unwrap()[0].clone();
+        assert_eq!(to_hex(&report.sha256.unwrap()), EICAR_TEST_FILE_SHA256);
+        assert_eq!(to_hex(&report.md5.unwrap()), EICAR_TEST_FILE_MD5);
     }
 
-    #[async_std::test]
-    async fn async_std_scan_tcp_infected_buffer() {
-        let err_msg = format!(
-            "Could not scan EICAR test string via TCP at {}",
-            CLAMD_HOST_TCP.host_address
-        );
-        let buffer = include_bytes!("data/eicar.txt");
-        let response = clamav_client::async_std::scan_buffer(buffer, CLAMD_HOST_TCP, None)
-            .await
-            .expect(&err_msg);
+    #[test]
+    fn scan_tcp_file_without_digests() {
+        let (options, reports) = options_with_report();
+        let response = clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+            .expect("Could not scan file without digests");
         assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        let report = reports.lock().unwrap()[0].clone();
+        assert_eq!((report.sha256, report.md5), (None, None));
     }
 
-    #[async_std::test]
-    async fn async_std_scan_tcp_clean_file() {
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
-        );
+    #[test]
+    fn scan_tcp_buffer_with_max_bytes_per_second() {
+        let start = std::time::Instant::now();
         let response =
-            clamav_client::async_std::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
-                .await
-                .expect(&err_msg);
+            clamav_client::scan_buffer(THROTTLED_BUFFER, CLAMD_HOST_TCP, throttled_options())
+                .expect("Could not scan throttled buffer");
         assert_eq!(&response, OK_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(true));
+        assert!(start.elapsed() >= THROTTLED_MIN_DURATION);
     }
 
-    #[async_std::test]
-    async fn async_std_scan_tcp_oversized_file() {
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
-        );
-        let response =
-            clamav_client::async_std::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
-                .await
-                .expect(&err_msg);
-        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+    #[test]
+    fn scan_tcp_file_exceeding_max_stream_size() {
+        let options = clamav_client::ScanOptions::new().max_stream_size(1_000_000);
+        let result = clamav_client::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, options);
+        assert_size_limit_exceeded(result, 1_000_000);
     }
 
-    #[async_std::test]
-    async fn async_std_implements_send_sync_trait() {
-        trait _AssertSendSync: Send + Sync {}
+    #[test]
+    fn scan_tcp_buffer_exceeding_max_stream_size() {
+        let buffer = include_bytes!("data/eicar.txt");
+        let options = clamav_client::ScanOptions::new().max_stream_size(16);
+        let result = clamav_client::scan_buffer(buffer, CLAMD_HOST_TCP, options);
+        assert_size_limit_exceeded(result, 16);
+    }
 
-        impl _AssertSendSync for clamav_client::async_std::Tcp<&str> {}
-        assert_implements_send_sync(clamav_client::async_std::scan_buffer(
-            &[],
+    #[test]
+    fn scan_options_validate() {
+        use clamav_client::{AdaptiveChunkSize, ClamdError, ScanOptions};
+
+        assert!(ScanOptions::new().validate().is_ok());
+        assert!(ScanOptions::from(None).validate().is_ok());
+        assert!(matches!(
+            ScanOptions::new().chunk_size(0).validate(),
+            Err(ClamdError::InvalidOptions {
+                reason: "the chunk size must not be zero"
+            })
+        ));
+        let adaptive_chunk_size = AdaptiveChunkSize { min: 0, max: 1024 };
+        assert!(ScanOptions::new()
+            .adaptive_chunk_size(adaptive_chunk_size)
+            .validate()
+            .is_err());
+        let adaptive_chunk_size = AdaptiveChunkSize {
+            min: 8192,
+            max: 1024,
+        };
+        assert!(ScanOptions::new()
+            .adaptive_chunk_size(adaptive_chunk_size)
+            .validate()
+            .is_err());
+        // A fixed chunk size takes precedence over the heuristic, but the
+        // heuristic is still checked
+        assert!(ScanOptions::new()
+            .chunk_size(1024)
+            .adaptive_chunk_size(adaptive_chunk_size)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn scan_tcp_with_zero_chunk_size() {
+        let buffer = include_bytes!("data/eicar.txt");
+        assert_invalid_options(clamav_client::scan_buffer(buffer, CLAMD_HOST_TCP, Some(0)));
+        assert_invalid_options(clamav_client::scan_file(
+            EICAR_TEST_FILE_PATH,
             CLAMD_HOST_TCP,
-            None,
+            Some(0),
+        ));
+        assert_invalid_options(clamav_client::scan_reader(
+            &buffer[..],
+            CLAMD_HOST_TCP,
+            Some(0),
         ));
+    }
 
-        #[cfg(unix)]
-        {
-            impl _AssertSendSync for clamav_client::async_std::Socket<&str> {}
-            assert_implements_send_sync(clamav_client::async_std::scan_buffer(
-                &[],
-                CLAMD_HOST_SOCKET,
-                None,
-            ));
-        }
+    #[test]
+    fn scan_tcp_with_huge_chunk_size() {
+        let options = clamav_client::ScanOptions::new().chunk_size(usize::MAX);
+        let buffer = include_bytes!("data/eicar.txt");
+        let response = clamav_client::scan_buffer(buffer, CLAMD_HOST_TCP, &options)
+            .expect("Could not scan buffer");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        let response = clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, &options)
+            .expect("Could not scan file");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
     }
-}
 
-#[cfg(feature = "async-std")]
-mod async_std_stream_tests {
-    use super::*;
-    use async_std::{fs::File, path::Path};
-    use async_std_util::io::ReaderStream;
+    #[test]
+    #[ignore = "streams more than 4 GiB"]
+    fn scan_buffer_larger_than_max_chunk_size() {
+        use std::io::{BufRead, Read, Write};
 
-    const CLAMD_HOST_TCP: clamav_client::async_std::Tcp<&str> = clamav_client::async_std::Tcp {
-        host_address: TEST_HOST_ADDRESS,
-    };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host_address = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::with_capacity(1 << 20, &stream);
+            let mut command = Vec::new();
+            reader.read_until(0, &mut command).unwrap();
+            assert_eq!(command, b"zINSTREAM\0");
+            let mut chunk_sizes = Vec::new();
+            loop {
+                let mut header = [0; 4];
+                reader.read_exact(&mut header).unwrap();
+                let len = u32::from_be_bytes(header);
+                if len == 0 {
+                    break;
+                }
+                let copied = std::io::copy(
+                    &mut (&mut reader).take(u64::from(len)),
+                    &mut std::io::sink(),
+                )
+                .unwrap();
+                assert_eq!(copied, u64::from(len));
+                chunk_sizes.push(len);
+            }
+            stream.write_all(OK_RESPONSE).unwrap();
+            chunk_sizes
+        });
 
-    #[cfg(unix)]
-    const CLAMD_HOST_SOCKET: clamav_client::async_std::Socket<&str> =
-        clamav_client::async_std::Socket {
-            socket_path: TEST_SOCKET_PATH,
+        // The pages of the zeroed buffer are only allocated when written to
+        let buffer = vec![0; u32::MAX as usize + 18];
+        let options = clamav_client::ScanOptions::new().chunk_size(usize::MAX);
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: host_address.as_str(),
         };
-
-    async fn stream_from_file<P: AsRef<Path>>(path: P) -> ReaderStream<File> {
-        let path_str = path.as_ref().to_str().expect("Invalid path");
-        let err_msg = format!("Could not read test file {}", path_str);
-        let file = File::open(path).await.expect(&err_msg);
-        ReaderStream::with_capacity(file, 16)
+        let mut response = Vec::new();
+        clamav_client::scan_buffer_into(&buffer, clamd_tcp, &options, &mut response)
+            .expect("Could not scan buffer");
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(server.join().unwrap(), [u32::MAX, 18]);
     }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_scan_socket_infected_stream() {
-        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
+    #[test]
+    fn scan_tcp_buffer_within_max_stream_size() {
         let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+            "Could not scan EICAR test string via TCP at {}",
+            CLAMD_HOST_TCP.host_address
         );
-        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_SOCKET, None)
-            .await
-            .expect(&err_msg);
+        let buffer = include_bytes!("data/eicar.txt");
+        let options = clamav_client::ScanOptions::new().max_stream_size(buffer.len() as u64);
+        let response = clamav_client::scan_buffer(buffer, CLAMD_HOST_TCP, options).expect(&err_msg);
         assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
     }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_scan_socket_clean_stream() {
-        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
-        let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+    #[test]
+    fn database_date_parsing() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let date = clamav_client::database_date(b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0");
+        assert_eq!(
+            date.unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1_726_480_800)
         );
-        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_SOCKET, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, OK_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(true));
+        let date = clamav_client::database_date(b"ClamAV 0.103.8/26800/Thu Feb  2 07:59:03 2023\n");
+        assert_eq!(
+            date.unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1_675_324_743)
+        );
+        let date = clamav_client::database_date(b"ClamAV 1.0.0/1/Thu Jan  1 00:00:00 1970");
+        assert_eq!(date.unwrap(), UNIX_EPOCH);
+        let date = clamav_client::database_date(b"ClamAV 1.3.0/27200/Thu Feb 29 12:00:00 2024");
+        assert_eq!(
+            date.unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1_709_208_000)
+        );
+
+        for response in [
+            &b"ClamAV 1.4.1\0"[..],
+            b"ClamAV 1.4.1/27400\0",
+            b"ClamAV 1.4.1/27400/Mon Foo 16 10:00:00 2024\0",
+            b"ClamAV 1.4.1/27400/Mon Sep 16 25:00:00 2024\0",
+            b"ClamAV 1.4.1/27400/Mon Sep 16 10:00 2024\0",
+            b"ClamAV 1.4.1/27400/Mon Feb 31 10:00:00 2024\0",
+            b"ClamAV 1.4.1/27400/Mon Feb 29 10:00:00 2023\0",
+            b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 18446744073709551615\0",
+            b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 99999999999\0",
+        ] {
+            let err = clamav_client::database_date(response).unwrap_err();
+            assert!(matches!(
+                err,
+                clamav_client::ClamdError::InvalidResponse { .. }
+            ));
+        }
     }
 
-    #[async_std::test]
-    #[cfg(unix)]
-    async fn async_std_scan_socket_oversized_stream() {
-        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
-        let err_msg = format!(
-            "Could not scan test file {} via socket at {}",
-            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+    #[test]
+    fn database_version_number_parsing() {
+        let version = clamav_client::database_version_number(
+            b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0",
         );
-        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_SOCKET, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        assert_eq!(version.unwrap(), 27400);
+        let version = clamav_client::database_version_number(b"ClamAV 1.4.1/27400\n");
+        assert_eq!(version.unwrap(), 27400);
+
+        for response in [
+            &b"ClamAV 1.4.1\0"[..],
+            b"ClamAV 1.4.1//Mon Sep 16 10:00:00 2024\0",
+            b"ClamAV 1.4.1/daily/Mon Sep 16 10:00:00 2024\0",
+        ] {
+            let err = clamav_client::database_version_number(response).unwrap_err();
+            assert!(matches!(
+                err,
+                clamav_client::ClamdError::InvalidResponse { .. }
+            ));
+        }
+
+        let response = clamav_client::get_version(CLAMD_HOST_TCP).unwrap();
+        assert!(clamav_client::database_version_number(&response).is_ok());
+    }
+
+    #[test]
+    fn version_info_parsing() {
+        use clamav_client::VersionInfo;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let version =
+            VersionInfo::from_response(b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0").unwrap();
+        assert_eq!(version.clamav_version, "1.4.1");
+        assert_eq!(version.database_version, Some(27400));
+        assert_eq!(
+            version.database_date,
+            Some(UNIX_EPOCH + Duration::from_secs(1_726_480_800))
+        );
+        let version = VersionInfo::from_response(b"ClamAV 1.4.1\n").unwrap();
+        assert_eq!(version.clamav_version, "1.4.1");
+        assert_eq!(version.database_version, None);
+        assert_eq!(version.database_date, None);
+
+        for response in [
+            &b"UNKNOWN COMMAND\0"[..],
+            b"ClamAV \0",
+            b"ClamAV 1.4.1/daily/Mon Sep 16 10:00:00 2024\0",
+            b"ClamAV 1.4.1/27400/yesterday\0",
+        ] {
+            let err = VersionInfo::from_response(response).unwrap_err();
+            assert!(matches!(
+                err,
+                clamav_client::ClamdError::InvalidResponse { .. }
+            ));
+        }
+
+        let version = clamav_client::version(CLAMD_HOST_TCP).unwrap();
+        assert!(version.database_version.is_some());
+    }
+
+    #[test]
+    fn signature_name_parsing() {
+        use clamav_client::{ScanOutcome, SignatureName};
+
+        let name = SignatureName::parse("Win.Trojan.Emotet-123456-1");
+        assert!(!name.pua && !name.unofficial);
+        assert_eq!(name.platform, Some("Win"));
+        assert_eq!(name.category, Some("Trojan"));
+        assert_eq!(name.family, "Emotet");
+        assert_eq!(name.variant, Some("123456-1"));
+
+        let name = SignatureName::parse("PUA.Pdf.Trojan.X");
+        assert!(name.pua);
+        assert_eq!(name.platform, Some("Pdf"));
+        assert_eq!(name.category, Some("Trojan"));
+        assert_eq!(name.family, "X");
+        assert_eq!(name.variant, None);
+
+        let name = SignatureName::parse("Heuristics.Phishing.Email.SpoofedDomain");
+        assert_eq!(name.platform, Some("Heuristics"));
+        assert_eq!(name.category, Some("Phishing"));
+        assert_eq!(name.family, "Email.SpoofedDomain");
+
+        let name = SignatureName::parse("Sanesecurity.Jurlbl.Auto.8f2a.UNOFFICIAL");
+        assert!(name.unofficial);
+        assert_eq!(name.platform, Some("Sanesecurity"));
+        assert_eq!(name.family, "Auto.8f2a");
+
+        let name = SignatureName::parse("Trojan.Agent-42");
+        assert_eq!(name.platform, None);
+        assert_eq!(name.category, Some("Trojan"));
+        assert_eq!(name.family, "Agent");
+        assert_eq!(name.variant, Some("42"));
+
+        for signature in ["Eicar-Signature", "Eicar-Test-Signature", "PUA.", "-1"] {
+            let name = SignatureName::parse(signature);
+            assert!(!name.pua);
+            assert_eq!((name.platform, name.category), (None, None));
+            assert_eq!(name.family, signature);
+            assert_eq!(name.variant, None);
+        }
+
+        let outcome = ScanOutcome::from_response(b"stream: Win.Test.EICAR_HDB-1 FOUND\0").unwrap();
+        let name = outcome.signature_name().unwrap();
+        assert_eq!(name.family, "EICAR_HDB");
+        assert_eq!(name.variant, Some("1"));
+        assert_eq!(ScanOutcome::Clean.signature_name(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "schedule")]
+    fn scheduled_scan_next_run() {
+        use clamav_client::{ClamdError, ScheduledScan};
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let at = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+        let next_run = |expression, seconds| {
+            ScheduledScan::cron("test", expression)
+                .unwrap()
+                .next_run(at(seconds))
+        };
+        // Monday, 1 January 2024, 00:00 UTC
+        let monday = 1_704_067_200;
+        assert_eq!(next_run("30 2 * * *", monday), Some(at(monday + 9000)));
+        assert_eq!(
+            next_run("30 2 * * *", monday + 9000),
+            Some(at(monday + 9000 + 86400))
+        );
+        assert_eq!(
+            next_run("*/15 * * * *", monday + 450),
+            Some(at(monday + 900))
+        );
+        // Saturday to Monday, 9:00
+        assert_eq!(
+            next_run("0 9 * * 1-5", 1_704_499_200),
+            Some(at(1_704_704_400))
+        );
+        // The 13th or a Friday
+        assert_eq!(next_run("0 0 13 * 5", monday), Some(at(1_704_412_800)));
+        // Both 0 and 7 are Sunday
+        assert_eq!(next_run("@weekly", monday), Some(at(1_704_585_600)));
+        assert_eq!(next_run("0 0 * * 7", monday), Some(at(1_704_585_600)));
+        // From 1 March 2024 to 29 February 2028
+        assert_eq!(
+            next_run("0 0 29 2 *", 1_709_251_200),
+            Some(at(1_835_395_200))
+        );
+        assert_eq!(next_run("0 0 31 2 *", monday), None);
+
+        let scan = ScheduledScan::every("test", Duration::from_secs(60));
+        assert_eq!(scan.next_run(at(monday)), Some(at(monday + 60)));
+
+        for expression in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "* * 0 * *",
+            "a * * * *",
+        ] {
+            assert!(matches!(
+                ScheduledScan::cron("test", expression),
+                Err(ClamdError::InvalidOptions { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn signature_allowlist() {
+        use clamav_client::{
+            FailurePolicy, ScanDecision, ScanOutcome, SignatureAllowlist, VerdictPolicy,
+        };
+
+        let infected = |signature: &str| ScanOutcome::Infected {
+            signature: signature.to_owned(),
+        };
+        let allowlist = SignatureAllowlist::new()
+            .allow("Eicar-Signature")
+            .allow("*.EICAR_HDB-?")
+            .warn("Heuristics.*")
+            .warn("Eicar-*");
+
+        assert!(allowlist.is_allowed("Eicar-Signature"));
+        assert!(allowlist.is_allowed("Win.Test.EICAR_HDB-1"));
+        assert!(!allowlist.is_allowed("Win.Test.EICAR_HDB-10"));
+        assert!(!allowlist.is_allowed("eicar-signature"));
+        assert!(!allowlist.is_warned("Eicar-Signature"));
+        assert!(allowlist.is_warned("Eicar-Other"));
+        assert!(allowlist.is_warned("Heuristics.Broken.Executable"));
+        assert!(!allowlist.is_warned("Win.Heuristics.Fake"));
+
+        assert_eq!(
+            allowlist.apply(infected("Eicar-Signature")),
+            ScanOutcome::Clean
+        );
+        let outcome = allowlist.apply(infected("Heuristics.Encrypted.PDF"));
+        assert!(outcome.is_warning());
+        assert_eq!(outcome.signature(), Some("Heuristics.Encrypted.PDF"));
+        assert!(allowlist
+            .apply(infected("Win.Trojan.Agent-1"))
+            .is_infected());
+        let error = ScanOutcome::Error {
+            message: "Heuristics.Limits ERROR".to_owned(),
+        };
+        assert_eq!(allowlist.apply(error.clone()), error);
+
+        assert_eq!(outcome.to_string(), "Warning: Heuristics.Encrypted.PDF");
+        assert_eq!(outcome.to_string().parse::<ScanOutcome>().unwrap(), outcome);
+        let decision = FailurePolicy::FailClosed.decide(Ok(outcome)).unwrap();
+        assert_eq!(
+            decision,
+            ScanDecision::AllowWithWarning {
+                signature: "Heuristics.Encrypted.PDF".to_owned()
+            }
+        );
+        assert!(decision.is_allowed());
+        assert!(decision.is_scanned());
+    }
+
+    #[test]
+    fn pua_policy() {
+        use clamav_client::{PuaPolicy, ScanOutcome, VerdictPolicy};
+
+        let pua = ScanOutcome::Infected {
+            signature: "PUA.Win.Packer.Upx-1".to_owned(),
+        };
+        let virus = ScanOutcome::Infected {
+            signature: "Win.Trojan.PUA-1".to_owned(),
+        };
+        assert!(pua.is_pua());
+        assert!(!virus.is_pua());
+        assert!(!ScanOutcome::Clean.is_pua());
+
+        assert_eq!(PuaPolicy::default(), PuaPolicy::Infected);
+        assert_eq!(PuaPolicy::Infected.apply(pua.clone()), pua);
+        let warning = PuaPolicy::Warning.apply(pua.clone());
+        assert!(warning.is_warning() && warning.is_pua());
+        assert_eq!(PuaPolicy::Infected.apply(warning.clone()), pua);
+        assert_eq!(PuaPolicy::Clean.apply(warning), ScanOutcome::Clean);
+        for policy in [PuaPolicy::Infected, PuaPolicy::Warning, PuaPolicy::Clean] {
+            assert_eq!(policy.apply(virus.clone()), virus);
+        }
+    }
+
+    #[test]
+    fn response_predicates() {
+        use clamav_client::{is_error, is_ok, is_pong, is_reloading};
+
+        assert!(is_pong(clamav_client::PONG));
+        assert!(is_pong(b"PONG\n"));
+        assert!(is_pong(b"PONG"));
+        assert!(!is_pong(b"PONG PONG\0"));
+        assert!(!is_pong(b""));
+
+        assert!(is_reloading(b"RELOADING\0"));
+        assert!(is_reloading(b"RELOADING\n"));
+        assert!(!is_reloading(b"PONG\0"));
+
+        assert!(is_ok(b"stream: OK\0"));
+        assert!(is_ok(b"/tmp/file: OK\n"));
+        assert!(!is_ok(b"stream: OK.Signature FOUND\0"));
+        assert!(!is_ok(b"INSTREAM size limit exceeded. ERROR\0"));
+
+        assert!(is_error(b"INSTREAM size limit exceeded. ERROR\0"));
+        assert!(is_error(
+            b"/tmp/file: lstat() failed: No such file or directory. ERROR\n"
+        ));
+        assert!(!is_error(b"stream: OK\0"));
+
+        let response = clamav_client::ping(CLAMD_HOST_TCP).unwrap();
+        assert!(is_pong(&response));
+    }
+
+    #[test]
+    fn failure_policy_decisions() {
+        use clamav_client::{ClamdError, FailurePolicy, ScanDecision, ScanOutcome};
+
+        let infected = || {
+            Ok(ScanOutcome::Infected {
+                signature: "Eicar-Signature".to_owned(),
+            })
+        };
+        let scan_error = || {
+            Ok(ScanOutcome::Error {
+                message: "INSTREAM size limit exceeded.".to_owned(),
+            })
+        };
+        let io_error = || Err(ClamdError::Io(std::io::ErrorKind::ConnectionRefused.into()));
+
+        for policy in [
+            FailurePolicy::FailOpen,
+            FailurePolicy::FailClosed,
+            FailurePolicy::Propagate,
+        ] {
+            let decision = policy.decide(Ok(ScanOutcome::Clean)).unwrap();
+            assert_eq!(decision, ScanDecision::Allow);
+            assert!(decision.is_allowed() && decision.is_scanned());
+            let decision = policy.decide(infected()).unwrap();
+            assert_eq!(decision.signature(), Some("Eicar-Signature"));
+            assert!(!decision.is_allowed() && decision.is_scanned());
+        }
+
+        let decision = FailurePolicy::FailOpen.decide(scan_error()).unwrap();
+        assert!(decision.is_allowed() && !decision.is_scanned());
+        assert_eq!(
+            decision.error(),
+            Some("ClamAV failed to scan: INSTREAM size limit exceeded.")
+        );
+        let decision = FailurePolicy::FailClosed.decide(io_error()).unwrap();
+        assert!(!decision.is_allowed() && !decision.is_scanned());
+        assert_eq!(decision.signature(), None);
+        assert!(decision.to_string().starts_with("Rejected unscanned: "));
+
+        assert!(matches!(
+            FailurePolicy::Propagate.decide(scan_error()),
+            Err(ClamdError::ScanFailed { message }) if message == "INSTREAM size limit exceeded."
+        ));
+        assert!(matches!(
+            FailurePolicy::default().decide(io_error()),
+            Err(ClamdError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn scan_outcome_text_round_trip() {
+        use clamav_client::ScanOutcome;
+
+        let outcomes = [
+            ScanOutcome::Clean,
+            ScanOutcome::Infected {
+                signature: "Eicar-Signature".to_owned(),
+            },
+            ScanOutcome::Error {
+                message: "INSTREAM size limit exceeded.".to_owned(),
+            },
+        ];
+        let texts = [
+            "Clean",
+            "Infected: Eicar-Signature",
+            "Error: INSTREAM size limit exceeded.",
+        ];
+        for (outcome, text) in outcomes.iter().zip(texts) {
+            assert_eq!(outcome.to_string(), text);
+            assert_eq!(&text.parse::<ScanOutcome>().unwrap(), outcome);
+        }
+
+        let responses: [&[u8]; 3] = [
+            OK_RESPONSE,
+            EICAR_FILE_SIGNATURE_FOUND_RESPONSE,
+            SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE,
+        ];
+        for (outcome, response) in outcomes.iter().zip(responses) {
+            assert_eq!(&ScanOutcome::try_from(response).unwrap(), outcome);
+        }
+        assert_eq!(
+            "stream: OK".parse::<ScanOutcome>().unwrap(),
+            ScanOutcome::Clean
+        );
+        assert!(matches!(
+            "PONG".parse::<ScanOutcome>(),
+            Err(clamav_client::ClamdError::InvalidResponse { .. })
+        ));
+    }
+
+    #[test]
+    fn scan_owned_buffers() {
+        let response =
+            clamav_client::scan_buffer(String::from("clean data"), CLAMD_HOST_TCP, None).unwrap();
+        assert_eq!(&response, OK_RESPONSE);
+
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP);
+        let buffer = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let outcome = client.scan_buffer(buffer).unwrap();
+        assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+    }
+
+    #[test]
+    fn scan_path_invalid_paths() {
+        use clamav_client::{ClamdError, PathCommand};
+
+        let err =
+            clamav_client::scan_path("tests/data/eicar.txt", PathCommand::Scan, CLAMD_HOST_TCP)
+                .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(matches!(
+            ClamdError::from(err),
+            ClamdError::InvalidPath {
+                reason: "the path is not absolute",
+                ..
+            }
+        ));
+
+        let path = std::env::current_dir().unwrap().join("eicar\0.txt");
+        let err = clamav_client::scan_path(&path, PathCommand::Scan, CLAMD_HOST_TCP).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "cannot send path {:?} to ClamAV: the path contains a NUL byte",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn client_builder() {
+        use std::time::Duration;
+
+        let client = clamav_client::ClamdClientBuilder::tcp(TEST_HOST_ADDRESS)
+            .timeout(Duration::from_secs(10))
+            .chunk_size(16)
+            .build();
+        let outcome = client.scan_file(EICAR_TEST_FILE_PATH).unwrap();
+        assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+
+        #[cfg(unix)]
+        {
+            let client = clamav_client::ClamdClientBuilder::socket(TEST_SOCKET_PATH).build();
+            assert_eq!(&client.ping().unwrap(), clamav_client::PONG);
+        }
+    }
+
+    #[test]
+    fn client_timeout() {
+        use std::{
+            io::ErrorKind,
+            net::TcpListener,
+            time::{Duration, Instant},
+        };
+
+        // Accepts connections in its backlog, but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client =
+            clamav_client::ClamdClientBuilder::tcp(listener.local_addr().unwrap().to_string())
+                .timeout(Duration::from_millis(100))
+                .build();
+        let start = Instant::now();
+        let err = client.scan_buffer(b"data").unwrap_err();
+        assert!(matches!(
+            err,
+            clamav_client::ClamdError::Io(ref err)
+                if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock)
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn client_scan_tcp_file() {
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP).options(Some(16));
+        let outcome = client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .expect("Could not scan file with client");
+        assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+    }
+
+    #[test]
+    #[cfg(feature = "cache")]
+    fn client_scan_tcp_file_with_cache() {
+        let (options, reports) = options_with_report();
+        let cache = std::sync::Arc::new(clamav_client::MemoryCache::new(16));
+        let events = clamav_client::DetectionEvents::new();
+        let detections = events.subscribe();
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .cache(cache.clone())
+            .detection_sink(events.sink("cache"));
+        let outcome = client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .expect("Could not scan file with cache");
+        let cached_outcome = client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .expect("Could not scan cached file");
+        assert_eq!(outcome, cached_outcome);
+        assert!(outcome.is_infected());
+        assert_eq!(reports.lock().unwrap().len(), 1);
+        assert_eq!(cache.len(), 1);
+
+        let events: Vec<_> = detections.try_iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].sha256.map(|digest| to_hex(&digest)).as_deref(),
+            Some(EICAR_TEST_FILE_SHA256)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cache")]
+    fn client_scan_tcp_buffer_with_cache() {
+        let (options, reports) = options_with_report();
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .cache(clamav_client::MemoryCache::new(16));
+        for _ in 0..3 {
+            let outcome = client
+                .scan_buffer(b"clean data")
+                .expect("Could not scan buffer with cache");
+            assert!(outcome.is_clean());
+        }
+        assert_eq!(reports.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "cache")]
+    fn client_does_not_cache_errors() {
+        let cache = std::sync::Arc::new(clamav_client::MemoryCache::new(16));
+        let options = clamav_client::ScanOptions::new().max_stream_size(16);
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .cache(cache.clone());
+        let result = client.scan_file(EICAR_TEST_FILE_PATH);
+        assert!(matches!(
+            result,
+            Err(clamav_client::ClamdError::SizeLimitExceeded { limit: 16 })
+        ));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn client_lifecycle_hooks() {
+        let events = HookEvents::default();
+        let client = record_hooks!(clamav_client::Client::new(CLAMD_HOST_TCP), events)
+            .options(clamav_client::ScanOptions::new().chunk_size(32));
+        let outcome = client.scan_file(EICAR_TEST_FILE_PATH).unwrap();
+        assert!(outcome.is_infected());
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "connect",
+                "chunk 32",
+                "chunk 32",
+                "chunk 5",
+                r#"response Infected { signature: "Eicar-Signature" }"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn client_error_hook() {
+        let events = HookEvents::default();
+        let clamd_unavailable = clamav_client::Tcp {
+            host_address: "localhost:1",
+        };
+        let client = record_hooks!(clamav_client::Client::new(clamd_unavailable), events);
+        assert!(client.scan_buffer(b"clean data").is_err());
+
+        let client = record_hooks!(clamav_client::Client::new(CLAMD_HOST_TCP), events)
+            .options(clamav_client::ScanOptions::new().max_stream_size(4));
+        assert!(client.scan_buffer(b"clean data").is_err());
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].starts_with("error "));
+        assert_eq!(events[1], "error stream size limit of 4 bytes exceeded");
+    }
+
+    #[test]
+    fn client_stats() {
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP);
+        let stats = client.stats();
+        assert_eq!(
+            (stats.scans, stats.bytes_sent, stats.last_error),
+            (0, 0, None)
+        );
+
+        assert!(client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        // Clones share the statistics, even with other options
+        let clone = client
+            .clone()
+            .options(clamav_client::ScanOptions::new().max_stream_size(4));
+        assert!(clone.scan_buffer(b"clean data").is_err());
+        assert!(clone.scan_file(OVERSIZED_TEST_FILE_PATH).is_err());
+
+        let stats = client.stats();
+        assert_eq!(stats.scans, 4);
+        assert_eq!(stats.infections, 1);
+        assert_eq!(stats.errors, 2);
+        assert_eq!(stats.bytes_sent, 69 + 10);
+        assert_eq!(
+            stats.last_error.as_deref(),
+            Some("stream size limit of 4 bytes exceeded")
+        );
+        assert!(stats.uptime > std::time::Duration::ZERO);
+        assert_eq!(clone.stats().scans, 4);
+    }
+
+    #[test]
+    fn client_detection_sinks() {
+        use std::{
+            path::{Path, PathBuf},
+            sync::{Arc, Mutex},
+        };
+
+        use clamav_client::Detection;
+
+        let detections = Arc::new(Mutex::new(Vec::new()));
+        let recorded_detections = detections.clone();
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let recorded_paths = paths.clone();
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP)
+            .detection_sink(move |detection: &Detection| {
+                recorded_detections.lock().unwrap().push(detection.clone())
+            })
+            .detection_sink(move |detection: &Detection| {
+                recorded_paths.lock().unwrap().push(detection.path.clone())
+            });
+
+        assert!(client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        assert!(client.scan_buffer(&eicar).unwrap().is_infected());
+
+        let detections = detections.lock().unwrap();
+        assert_eq!(detections.len(), 2);
+        assert_eq!(detections[0].signature, "Eicar-Signature");
+        assert_eq!(
+            detections[0].path.as_deref(),
+            Some(Path::new(EICAR_TEST_FILE_PATH))
+        );
+        assert_eq!(detections[1].path, None);
+        assert!(detections[0].detected_at <= detections[1].detected_at);
+        assert_eq!(
+            *paths.lock().unwrap(),
+            [Some(PathBuf::from(EICAR_TEST_FILE_PATH)), None]
+        );
+    }
+
+    #[test]
+    fn client_detection_events() {
+        use std::path::Path;
+
+        use clamav_client::{DetectionEvents, ScanOptions};
+
+        let events = DetectionEvents::new();
+        let alerts = events.subscribe();
+        let quarantine = events.subscribe();
+        assert_eq!(events.subscriber_count(), 2);
+
+        let uploads = clamav_client::Client::new(CLAMD_HOST_TCP)
+            .options(ScanOptions::new().correlation_id("request-1"))
+            .detection_sink(events.sink("uploads"));
+        let mail = clamav_client::Client::new(CLAMD_HOST_TCP).detection_sink(events.sink("mail"));
+
+        assert!(uploads
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+        assert!(mail.scan_buffer(b"clean data").unwrap().is_clean());
+        drop(quarantine);
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        assert!(mail.scan_buffer(&eicar).unwrap().is_infected());
+        assert_eq!(events.subscriber_count(), 1);
+
+        let events: Vec<_> = alerts.try_iter().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].signature, "Eicar-Signature");
+        assert_eq!(&*events[0].source, "uploads");
+        assert_eq!(events[0].correlation_id.as_deref(), Some("request-1"));
+        assert_eq!(
+            events[0].path.as_deref(),
+            Some(Path::new(EICAR_TEST_FILE_PATH))
+        );
+        assert_eq!(&*events[1].source, "mail");
+        assert_eq!(events[1].correlation_id, None);
+        assert_eq!(events[1].path, None);
+        assert!(events[0].timestamp <= events[1].timestamp);
+    }
+
+    #[test]
+    #[cfg(feature = "wasi")]
+    fn client_scan_with_connector() {
+        use std::{
+            net::TcpStream,
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+
+        let connections = AtomicUsize::new(0);
+        let connector = clamav_client::Connector::new(|| {
+            connections.fetch_add(1, Ordering::Relaxed);
+            TcpStream::connect(TEST_HOST_ADDRESS)
+        });
+        let client = clamav_client::Client::new(&connector);
+
+        assert_eq!(&client.ping().unwrap(), clamav_client::PONG);
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        assert!(client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+        assert_eq!(connections.load(Ordering::Relaxed), 3);
+
+        let refused = clamav_client::Connector::new(|| -> std::io::Result<TcpStream> {
+            Err(std::io::ErrorKind::ConnectionRefused.into())
+        });
+        assert!(clamav_client::ping(refused).is_err());
+    }
+
+    #[test]
+    fn client_correlation_id() {
+        use std::sync::{Arc, Mutex};
+
+        use clamav_client::Detection;
+
+        let detections = Arc::new(Mutex::new(Vec::new()));
+        let recorded_detections = detections.clone();
+        let (options, reports) = options_with_report();
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .correlation_id("upload-42")
+            .detection_sink(move |detection: &Detection| {
+                recorded_detections.lock().unwrap().push(detection.clone())
+            });
+
+        assert!(client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+        assert_eq!(
+            reports.lock().unwrap()[0].correlation_id.as_deref(),
+            Some("upload-42")
+        );
+        assert_eq!(
+            detections.lock().unwrap()[0].correlation_id.as_deref(),
+            Some("upload-42")
+        );
+
+        let (options, reports) = options_with_report();
+        clamav_client::scan_buffer(b"clean data", CLAMD_HOST_TCP, options).unwrap();
+        assert_eq!(reports.lock().unwrap()[0].correlation_id, None);
+    }
+
+    #[test]
+    #[cfg(feature = "cache")]
+    fn memory_cache_evicts_least_recently_used() {
+        use clamav_client::{MemoryCache, ScanCache, ScanOutcome};
+
+        let cache = MemoryCache::new(2);
+        cache.store([1; 32], &ScanOutcome::Clean);
+        cache.store([2; 32], &ScanOutcome::Clean);
+        assert_eq!(cache.lookup(&[1; 32]), Some(ScanOutcome::Clean));
+        cache.store([3; 32], &ScanOutcome::Clean);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.lookup(&[1; 32]), Some(ScanOutcome::Clean));
+        assert_eq!(cache.lookup(&[2; 32]), None);
+        assert_eq!(cache.lookup(&[3; 32]), Some(ScanOutcome::Clean));
+    }
+
+    #[test]
+    #[cfg(feature = "cache")]
+    fn memory_cache_expires_outcomes() {
+        use clamav_client::{MemoryCache, ScanCache, ScanOutcome};
+
+        let cache = MemoryCache::new(2).ttl(std::time::Duration::from_millis(50));
+        cache.store([1; 32], &ScanOutcome::Clean);
+        assert_eq!(cache.lookup(&[1; 32]), Some(ScanOutcome::Clean));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(cache.lookup(&[1; 32]), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "watch")]
+    fn watch_directory_scans_new_files() {
+        let dir_path = watch_test_dir("new-files");
+        let watcher = clamav_client::watch_directory(
+            &dir_path,
+            CLAMD_HOST_TCP,
+            None,
+            clamav_client::WatchOptions::new().debounce(WATCH_DEBOUNCE),
+        )
+        .expect("Could not watch directory");
+
+        std::fs::copy(EICAR_TEST_FILE_PATH, dir_path.join("eicar.txt")).unwrap();
+        let (path, result) = watcher
+            .recv_timeout(WATCH_TIMEOUT)
+            .expect("New file was not scanned");
+        assert_eq!(path.file_name().unwrap(), "eicar.txt");
+        assert_eq!(result.unwrap().signature(), Some("Eicar-Signature"));
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "watch")]
+    fn watch_directory_waits_for_partially_written_files() {
+        use std::io::Write;
+
+        let dir_path = watch_test_dir("partial-files");
+        let watcher = clamav_client::watch_directory(
+            &dir_path,
+            CLAMD_HOST_TCP,
+            None,
+            clamav_client::WatchOptions::new().debounce(WATCH_DEBOUNCE),
+        )
+        .expect("Could not watch directory");
+
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let (head, tail) = eicar.split_at(eicar.len() / 2);
+        let mut file = std::fs::File::create(dir_path.join("eicar.txt")).unwrap();
+        file.write_all(head).unwrap();
+        std::thread::sleep(WATCH_DEBOUNCE / 4);
+        file.write_all(tail).unwrap();
+        drop(file);
+
+        let (_, result) = watcher
+            .recv_timeout(WATCH_TIMEOUT)
+            .expect("Written file was not scanned");
+        assert_eq!(result.unwrap().signature(), Some("Eicar-Signature"));
+        assert!(watcher.recv_timeout(WATCH_DEBOUNCE * 2).is_none());
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "watch")]
+    fn watch_missing_directory() {
+        let result = clamav_client::watch_directory(
+            "tests/data/missing",
+            CLAMD_HOST_TCP,
+            None,
+            clamav_client::WatchOptions::new(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "cache-redis")]
+    fn client_scan_tcp_buffer_with_redis_cache() {
+        let cache = redis_cache();
+        cache
+            .update_database_version(CLAMD_HOST_TCP)
+            .expect("Could not update database version");
+        let cache = std::sync::Arc::new(cache);
+
+        let (options, first_reports) = options_with_report();
+        let first_client = clamav_client::Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .cache(cache.clone());
+        let (options, second_reports) = options_with_report();
+        let second_client = clamav_client::Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .cache(cache);
+
+        let buffer = include_bytes!("data/eicar.txt");
+        let outcome = first_client
+            .scan_buffer(buffer)
+            .expect("Could not scan buffer with Redis cache");
+        let cached_outcome = second_client
+            .scan_buffer(buffer)
+            .expect("Could not scan cached buffer");
+        assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+        assert_eq!(outcome, cached_outcome);
+        assert_eq!(first_reports.lock().unwrap().len(), 1);
+        assert!(second_reports.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "dns-check")]
+    fn database_status_from_responses() {
+        use clamav_client::DatabaseStatus;
+
+        let txt_record = "1.4.1:62:27400:1726480800:1:90:49192:335";
+        let status = DatabaseStatus::from_responses(
+            b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0",
+            txt_record,
+        )
+        .unwrap();
+        assert_eq!(status.installed_version, 27400);
+        assert_eq!(status.current_version, 27400);
+        assert!(status.is_current());
+        assert_eq!(status.versions_behind(), 0);
+
+        let status = DatabaseStatus::from_responses(
+            b"ClamAV 1.4.1/27401/Tue Sep 17 10:00:00 2024\0",
+            txt_record,
+        )
+        .unwrap();
+        assert!(status.is_current());
+        assert_eq!(status.versions_behind(), 0);
+
+        let status = DatabaseStatus::from_responses(
+            b"ClamAV 1.4.1/27390/Fri Sep  6 10:00:00 2024\0",
+            txt_record,
+        )
+        .unwrap();
+        assert!(!status.is_current());
+        assert_eq!(status.versions_behind(), 10);
+
+        for (version_response, txt_record) in [
+            (&b"ClamAV 1.4.1\0"[..], txt_record),
+            (b"ClamAV 1.4.1/daily/Mon Sep 16 10:00:00 2024\0", txt_record),
+            (b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0", "1.4.1:62"),
+            (b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0", ""),
+        ] {
+            assert!(matches!(
+                DatabaseStatus::from_responses(version_response, txt_record),
+                Err(clamav_client::ClamdError::InvalidResponse { .. })
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "cache-redis")]
+    fn redis_cache_is_invalidated_by_database_version() {
+        use clamav_client::{ScanCache, ScanOutcome};
+
+        let cache = redis_cache();
+        cache.store([1; 32], &ScanOutcome::Clean);
+        assert_eq!(cache.lookup(&[1; 32]), None);
+
+        cache
+            .set_database_version(b"ClamAV 1.4.3/27431/Fri Oct 16 08:26:01 2026\0")
+            .unwrap();
+        assert_eq!(cache.database_version().as_deref(), Some("27431"));
+        cache.store([1; 32], &ScanOutcome::Clean);
+        assert_eq!(cache.lookup(&[1; 32]), Some(ScanOutcome::Clean));
+
+        cache
+            .set_database_version(b"ClamAV 1.4.3/27432/Sat Oct 17 08:26:01 2026\0")
+            .unwrap();
+        assert_eq!(cache.lookup(&[1; 32]), None);
+    }
+
+    #[test]
+    #[cfg(feature = "cache-redis")]
+    fn redis_cache_rejects_invalid_version_response() {
+        let result = redis_cache().set_database_version(b"UNKNOWN COMMAND\0");
+        assert!(matches!(
+            result,
+            Err(clamav_client::ClamdError::InvalidResponse { .. })
+        ));
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_tests {
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::tokio::Tcp<&str> = clamav_client::tokio::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    #[cfg(unix)]
+    const CLAMD_HOST_SOCKET: clamav_client::tokio::Socket<&str> = clamav_client::tokio::Socket {
+        socket_path: TEST_SOCKET_PATH,
+    };
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_ping_socket() {
+        let err_msg = format!(
+            "Could not ping clamd via Unix socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::tokio::ping(CLAMD_HOST_SOCKET)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_get_version_socket() {
+        let err_msg = format!(
+            "Could not get ClamAV version via Unix socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::tokio::get_version(CLAMD_HOST_SOCKET)
+            .await
+            .expect(&err_msg);
+        assert!(&response.starts_with(b"ClamAV"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_scan_socket_infected_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response =
+            clamav_client::tokio::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_scan_socket_infected_buffer() {
+        let err_msg = format!(
+            "Could not scan EICAR test string via socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
+        );
+        let buffer = include_bytes!("data/eicar.txt");
+        let response = clamav_client::tokio::scan_buffer(buffer, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_scan_socket_clean_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response =
+            clamav_client::tokio::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_scan_socket_oversized_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response =
+            clamav_client::tokio::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_ping_tcp() {
+        let err_msg = format!(
+            "Could not ping clamd via TCP at {}",
+            CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::tokio::ping(CLAMD_HOST_TCP)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_ping_into_tcp() {
+        let mut response = b"stale".to_vec();
+        clamav_client::tokio::ping_into(CLAMD_HOST_TCP, &mut response)
+            .await
+            .expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_ping_ok_tcp() {
+        clamav_client::tokio::ping_ok(CLAMD_HOST_TCP)
+            .await
+            .expect("Could not ping clamd");
+    }
+
+    #[tokio::test]
+    async fn async_tokio_ping_resolving_tcp() {
+        use clamav_client::tokio::{ResolvingTcp, SystemResolver};
+
+        let clamd_tcp = ResolvingTcp::new("localhost", 3310, SystemResolver);
+        let response = clamav_client::tokio::ping(clamd_tcp)
+            .await
+            .expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_ping_resolving_tcp_with_custom_resolver() {
+        use std::{io, net::SocketAddr};
+
+        struct Localhost;
+
+        impl clamav_client::tokio::Resolve for Localhost {
+            async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+                assert_eq!(host, "clamd.invalid");
+                Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+            }
+        }
+
+        let clamd_tcp = clamav_client::tokio::ResolvingTcp::new("clamd.invalid", 3310, Localhost);
+        let response = clamav_client::tokio::ping(clamd_tcp)
+            .await
+            .expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_caching_resolver() {
+        use std::{
+            io,
+            net::SocketAddr,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            time::Duration,
+        };
+
+        struct Counting(Arc<AtomicUsize>);
+
+        impl clamav_client::tokio::Resolve for Counting {
+            async fn resolve(&self, _host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+            }
+        }
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let resolver =
+            clamav_client::CachingResolver::new(Counting(lookups.clone()), Duration::from_secs(60));
+        let clamd_tcp = clamav_client::tokio::ResolvingTcp::new("clamd.invalid", 3310, &resolver);
+        for _ in 0..3 {
+            clamav_client::tokio::ping(&clamd_tcp)
+                .await
+                .expect("Could not ping clamd");
+        }
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "hickory-dns")]
+    async fn async_tokio_ping_resolving_tcp_with_hickory_resolver() {
+        let resolver = clamav_client::tokio::HickoryResolver::from_system_conf().unwrap();
+        let clamd_tcp = clamav_client::tokio::ResolvingTcp::new("localhost", 3310, resolver);
+        let response = clamav_client::tokio::ping(clamd_tcp)
+            .await
+            .expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_reader_not_unpin() {
+        use std::{
+            io,
+            marker::PhantomPinned,
+            pin::Pin,
+            task::{Context, Poll},
+        };
+        use tokio::io::ReadBuf;
+
+        struct NotUnpin {
+            data: &'static [u8],
+            _pinned: PhantomPinned,
+        }
+
+        impl tokio::io::AsyncRead for NotUnpin {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<io::Result<()>> {
+                // SAFETY: `data` is not structurally pinned
+                let this = unsafe { self.get_unchecked_mut() };
+                let len = this.data.len().min(buf.remaining());
+                buf.put_slice(&this.data[..len]);
+                this.data = &this.data[len..];
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let reader = NotUnpin {
+            data: include_bytes!("data/eicar.txt"),
+            _pinned: PhantomPinned,
+        };
+        let response = clamav_client::tokio::scan_reader(reader, CLAMD_HOST_TCP, Some(16))
+            .await
+            .expect("Could not scan reader");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_buffer_into() {
+        let options = clamav_client::ScanOptions::new().chunk_size(16);
+        let mut response = Vec::new();
+        let buffer = include_bytes!("data/eicar.txt");
+        clamav_client::tokio::scan_buffer_into(buffer, CLAMD_HOST_TCP, &options, &mut response)
+            .await
+            .expect("Could not scan EICAR test string");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+
+        clamav_client::tokio::scan_buffer_into(b"hello", CLAMD_HOST_TCP, &options, &mut response)
+            .await
+            .expect("Could not scan clean buffer");
+        assert_eq!(&response, OK_RESPONSE);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_get_version_tcp() {
+        let err_msg = format!(
+            "Could not get ClamAV version via TCP at {}",
+            CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::tokio::get_version(CLAMD_HOST_TCP)
+            .await
+            .expect(&err_msg);
+        assert!(&response.starts_with(b"ClamAV"));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_reload_tcp() {
+        let response = clamav_client::tokio::reload(CLAMD_HOST_TCP)
+            .await
+            .expect("Could not reload ClamAV");
+        assert_eq!(&response, b"RELOADING\0");
+    }
+
+    #[tokio::test]
+    async fn async_tokio_get_stats_tcp() {
+        let response = clamav_client::tokio::get_stats(CLAMD_HOST_TCP)
+            .await
+            .expect("Could not get ClamAV stats");
+        assert!(response.starts_with(b"POOLS:"));
+        assert!(response.ends_with(b"END\0"));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_infected_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::tokio::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_infected_buffer() {
+        let err_msg = format!(
+            "Could not scan EICAR test string via TCP at {}",
+            CLAMD_HOST_TCP.host_address
+        );
+        let buffer = include_bytes!("data/eicar.txt");
+        let response = clamav_client::tokio::scan_buffer(buffer, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_clean_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::tokio::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_oversized_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response =
+            clamav_client::tokio::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_scan_socket_buffers() {
+        let err_msg = format!(
+            "Could not scan buffers via socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
+        );
+        let outcomes = clamav_client::tokio::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_batch_outcomes(&outcomes);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, feature = "process"))]
+    async fn async_tokio_scan_tcp_command_output() {
+        let mut command = tokio::process::Command::new("cat");
+        command.arg(EICAR_TEST_FILE_PATH);
+        let response =
+            clamav_client::tokio::scan_command_output(&mut command, CLAMD_HOST_TCP, None)
+                .await
+                .expect("Could not scan command output via TCP");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, feature = "process"))]
+    async fn async_tokio_scan_tcp_command_output_with_failing_command() {
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "cat tests/data/eicar.txt; exit 3"]);
+        let err = clamav_client::tokio::scan_command_output(&mut command, CLAMD_HOST_TCP, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, feature = "process"))]
+    async fn async_tokio_scan_tcp_command_output_size_limit() {
+        let mut command = tokio::process::Command::new("cat");
+        command.arg("/dev/zero");
+        let options = clamav_client::ScanOptions::new().max_stream_size(1000);
+        let result =
+            clamav_client::tokio::scan_command_output(&mut command, CLAMD_HOST_TCP, options).await;
+        assert_size_limit_exceeded(result, 1000);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_buffers() {
+        let err_msg = format!(
+            "Could not scan buffers via TCP at {}",
+            CLAMD_HOST_TCP.host_address
+        );
+        let outcomes = clamav_client::tokio::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_batch_outcomes(&outcomes);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_buffers_pipelined() {
+        let options = clamav_client::ScanOptions::new().pipeline_depth(3);
+        let outcomes = clamav_client::tokio::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_TCP, options)
+            .await
+            .expect("Could not scan buffers via TCP");
+        assert_batch_outcomes(&outcomes);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "sink")]
+    async fn async_tokio_scan_tcp_sink() {
+        use futures_util::SinkExt;
+
+        let (options, events) = options_with_progress();
+        let options = options.chunk_size(16);
+        let mut sink = clamav_client::tokio::scan_sink(CLAMD_HOST_TCP, options)
+            .await
+            .expect("Could not start a scan via TCP");
+        let eicar = bytes::Bytes::from_static(include_bytes!("data/eicar.txt"));
+        for part in eicar.chunks(20) {
+            sink.send(eicar.slice_ref(part)).await.unwrap();
+        }
+        assert_eq!(sink.response(), None);
+        sink.close().await.unwrap();
+        assert_eq!(sink.response(), Some(EICAR_FILE_SIGNATURE_FOUND_RESPONSE));
+        assert_progress(&events, eicar.len() as u64, false);
+        assert!(sink.send(eicar).await.is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "sink")]
+    async fn async_tokio_scan_tcp_sink_size_limit() {
+        use futures_util::SinkExt;
+
+        let options = clamav_client::ScanOptions::new().max_stream_size(10);
+        let mut sink = clamav_client::tokio::scan_sink(CLAMD_HOST_TCP, options)
+            .await
+            .unwrap();
+        sink.send(bytes::Bytes::from_static(b"clean"))
+            .await
+            .unwrap();
+        sink.send(bytes::Bytes::from_static(b" data"))
+            .await
+            .unwrap();
+        let result = sink.send(bytes::Bytes::from_static(b"!")).await;
+        assert_size_limit_exceeded(result, 10);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_session_tcp() {
+        let session = clamav_client::tokio::Session::start(CLAMD_HOST_TCP, None)
+            .await
+            .expect("Could not start a session via TCP");
+        let (first, second, third, pong) = tokio::join!(
+            session.scan_buffer(BATCH_BUFFERS[0]),
+            session.scan_buffer(BATCH_BUFFERS[1]),
+            session.scan_file(EICAR_TEST_FILE_PATH),
+            session.ping(),
+        );
+        assert_batch_outcomes(&[first.unwrap(), second.unwrap(), third.unwrap()]);
+        assert_eq!(&pong.unwrap(), clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_session_out_of_order_responses() {
+        use std::io::{BufRead, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host_address = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(&stream);
+            let mut commands = Vec::new();
+            for _ in 0..3 {
+                let mut command = Vec::new();
+                reader.read_until(0, &mut command).unwrap();
+                commands.push(command);
+            }
+            // Answers the VERSION command before the PING command
+            (&stream)
+                .write_all(b"2: ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0")
+                .unwrap();
+            (&stream).write_all(b"1: PONG\0").unwrap();
+            commands
+        });
+
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: host_address.as_str(),
+        };
+        let session = clamav_client::tokio::Session::start(clamd_tcp, None)
+            .await
+            .unwrap();
+        let (pong, version) = tokio::join!(session.ping(), session.get_version());
+        assert_eq!(&pong.unwrap(), clamav_client::PONG);
+        assert_eq!(
+            version.unwrap(),
+            b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0"
+        );
+        let commands = server.join().unwrap();
+        assert_eq!(commands, [&b"zIDSESSION\0"[..], b"zPING\0", b"zVERSION\0"]);
+
+        // The server closed the connection
+        assert!(session.ping().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn async_tokio_session_reconnects_after_connection_loss() {
+        use std::{
+            io::{BufRead, Write},
+            sync::{Arc, Mutex},
+        };
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host_address = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let mut commands = Vec::new();
+            // Closes each connection after answering one request, like a
+            // restarted ClamAV
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = std::io::BufReader::new(&stream);
+                for _ in 0..2 {
+                    let mut command = Vec::new();
+                    reader.read_until(0, &mut command).unwrap();
+                    commands.push(command);
+                }
+                (&stream).write_all(b"1: PONG\0").unwrap();
+            }
+            commands
+        });
+
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: host_address.as_str(),
+        };
+        let notes = Arc::new(Mutex::new(Vec::new()));
+        let session_notes = Arc::clone(&notes);
+        let session = clamav_client::tokio::Session::start(clamd_tcp, None)
+            .await
+            .unwrap()
+            .reconnect(true)
+            .on_reconnect(move |reconnected| session_notes.lock().unwrap().push(*reconnected));
+
+        assert_eq!(&session.ping().await.unwrap(), clamav_client::PONG);
+        assert_eq!(&session.ping().await.unwrap(), clamav_client::PONG);
+        let commands = server.join().unwrap();
+        assert_eq!(
+            commands,
+            [
+                &b"zIDSESSION\0"[..],
+                b"zPING\0",
+                b"zIDSESSION\0",
+                b"zPING\0"
+            ]
+        );
+        let reconnects: Vec<u64> = notes.lock().unwrap().iter().map(|n| n.reconnects).collect();
+        assert_eq!(reconnects, [1]);
+
+        // Reconnecting fails once the server is gone, and the request is not
+        // retried again
+        assert!(session.ping().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_files() {
+        let results =
+            clamav_client::tokio::scan_files(BATCH_FILE_PATHS, CLAMD_HOST_TCP, None, 2).await;
+        assert_batch_file_results(&results);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_file_with_progress() {
+        let (options, events) = options_with_progress();
+        let response =
+            clamav_client::tokio::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+                .await
+                .expect("Could not scan file with progress");
+        assert_eq!(&response, OK_RESPONSE);
+        let file_size = std::fs::metadata(CLEAN_TEST_FILE_PATH).unwrap().len();
+        assert_progress(&events, file_size, true);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_buffer_with_report() {
+        let (options, reports) = options_with_report();
+        let response = clamav_client::tokio::scan_buffer(b"clean data", CLAMD_HOST_TCP, options)
+            .await
+            .expect("Could not scan buffer with report");
+        assert_eq!(&response, OK_RESPONSE);
+        assert_report(&reports, 10);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_buffer_with_max_bytes_per_second() {
+        let start = std::time::Instant::now();
+        let response = clamav_client::tokio::scan_buffer(
+            THROTTLED_BUFFER,
+            CLAMD_HOST_TCP,
+            throttled_options(),
+        )
+        .await
+        .expect("Could not scan throttled buffer");
+        assert_eq!(&response, OK_RESPONSE);
+        assert!(start.elapsed() >= THROTTLED_MIN_DURATION);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_file_exceeding_max_stream_size() {
+        let options = clamav_client::ScanOptions::new().max_stream_size(1_000_000);
+        let result =
+            clamav_client::tokio::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+                .await;
+        assert_size_limit_exceeded(result, 1_000_000);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_buffer_exceeding_max_stream_size() {
+        let buffer = include_bytes!("data/eicar.txt");
+        let options = clamav_client::ScanOptions::new().max_stream_size(16);
+        let result = clamav_client::tokio::scan_buffer(buffer, CLAMD_HOST_TCP, options).await;
+        assert_size_limit_exceeded(result, 16);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_with_huge_chunk_size() {
+        let options = clamav_client::ScanOptions::new().chunk_size(usize::MAX);
+        let buffer = include_bytes!("data/eicar.txt");
+        let response = clamav_client::tokio::scan_buffer(buffer, CLAMD_HOST_TCP, &options)
+            .await
+            .expect("Could not scan buffer");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        let response =
+            clamav_client::tokio::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, &options)
+                .await
+                .expect("Could not scan file");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_with_zero_chunk_size() {
+        let buffer = include_bytes!("data/eicar.txt");
+        let result = clamav_client::tokio::scan_buffer(buffer, CLAMD_HOST_TCP, Some(0)).await;
+        assert_invalid_options(result);
+        let result =
+            clamav_client::tokio::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, Some(0)).await;
+        assert_invalid_options(result);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_client_scan_tcp_file() {
+        let client = clamav_client::tokio::Client::new(CLAMD_HOST_TCP).options(Some(16));
+        let outcome = client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .await
+            .expect("Could not scan file with client");
+        assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "cache")]
+    async fn async_tokio_client_scan_tcp_file_with_cache() {
+        let (options, reports) = options_with_report();
+        let client = clamav_client::tokio::Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .cache(clamav_client::MemoryCache::new(16));
+        let outcome = client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .await
+            .expect("Could not scan file with cache");
+        let cached_outcome = client
+            .scan_buffer(include_bytes!("data/eicar.txt"))
+            .await
+            .expect("Could not scan cached buffer");
+        assert_eq!(outcome, cached_outcome);
+        assert!(outcome.is_infected());
+        assert_eq!(reports.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_client_lifecycle_hooks() {
+        let events = HookEvents::default();
+        let client = record_hooks!(clamav_client::tokio::Client::new(CLAMD_HOST_TCP), events)
+            .options(clamav_client::ScanOptions::new().chunk_size(4));
+        let outcome = client.scan_buffer(b"clean data").await.unwrap();
+        assert!(outcome.is_clean());
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["connect", "chunk 4", "chunk 4", "chunk 2", "response Clean"]
+        );
+    }
+
+    #[tokio::test]
+    async fn async_tokio_client_limiter() {
+        use std::{
+            sync::{Arc, Mutex},
+            time::Duration,
+        };
+
+        let limiter = clamav_client::tokio::ScanLimiter::new(1);
+        let available = Arc::new(Mutex::new(Vec::new()));
+        let (hook_limiter, hook_available) = (limiter.clone(), Arc::clone(&available));
+        let first = clamav_client::tokio::Client::new(CLAMD_HOST_TCP)
+            .limiter(limiter.clone())
+            .on_connect(move |_| {
+                hook_available
+                    .lock()
+                    .unwrap()
+                    .push(hook_limiter.available())
+            });
+        let second = clamav_client::tokio::Client::new(CLAMD_HOST_TCP).limiter(limiter.clone());
+
+        let (first_outcome, second_outcome) = tokio::join!(
+            first.scan_buffer(b"clean data"),
+            second.scan_file(EICAR_TEST_FILE_PATH)
+        );
+        assert!(first_outcome.unwrap().is_clean());
+        assert!(second_outcome.unwrap().is_infected());
+        // The scan held the only permit while it was connected
+        assert_eq!(*available.lock().unwrap(), [0]);
+        assert_eq!(limiter.available(), 1);
+
+        let permit = limiter.acquire().await.unwrap();
+        let queued = clamav_client::tokio::Client::new(CLAMD_HOST_TCP)
+            .limiter(limiter.clone().queue_timeout(Duration::from_millis(50)));
+        let err = queued.ping().await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        drop(permit);
+        assert_eq!(&queued.ping().await.unwrap(), clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_client_stats() {
+        let client = clamav_client::tokio::Client::new(CLAMD_HOST_TCP);
+        assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+        let stats = client.stats();
+        assert_eq!((stats.scans, stats.bytes_sent), (1, 10));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_client_detection_sink() {
+        use std::sync::{Arc, Mutex};
+
+        let signatures = Arc::new(Mutex::new(Vec::new()));
+        let recorded_signatures = signatures.clone();
+        let client = clamav_client::tokio::Client::new(CLAMD_HOST_TCP).detection_sink(
+            move |detection: &clamav_client::Detection| {
+                recorded_signatures
+                    .lock()
+                    .unwrap()
+                    .push(detection.signature.clone())
+            },
+        );
+        let outcome = client.scan_file(EICAR_TEST_FILE_PATH).await.unwrap();
+        assert!(outcome.is_infected());
+        assert_eq!(*signatures.lock().unwrap(), ["Eicar-Signature"]);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_await_scan_queue_handle() {
+        use clamav_client::{ScanJob, ScanQueue, ScanQueueOptions};
+
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: TEST_HOST_ADDRESS,
+        };
+        let queue = ScanQueue::new(clamd_tcp, None, ScanQueueOptions::new()).unwrap();
+        let outcome = queue
+            .submit(ScanJob::file(EICAR_TEST_FILE_PATH))
+            .await
+            .expect("Could not scan file with queue");
+        assert!(outcome.is_infected());
+    }
+
+    #[tokio::test]
+    async fn async_tokio_cancellable_scan_finishes() {
+        let scan = clamav_client::tokio::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None);
+        let response = clamav_client::tokio::cancellable(scan, std::future::pending::<()>())
+            .await
+            .unwrap();
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, feature = "process"))]
+    async fn async_tokio_cancellable_scan_cancelled() {
+        use std::time::{Duration, Instant};
+
+        use clamav_client::ClamdError;
+        use tokio_util::sync::CancellationToken;
+
+        let token = CancellationToken::new();
+        let cancel_token = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel_token.cancel();
+        });
+
+        let start = Instant::now();
+        let mut command = tokio::process::Command::new("sleep");
+        command.arg("10");
+        let scan = clamav_client::tokio::scan_command_output(&mut command, CLAMD_HOST_TCP, None);
+        let err = clamav_client::tokio::cancellable(scan, token.cancelled())
+            .await
+            .unwrap_err();
+        assert!(matches!(ClamdError::from(err), ClamdError::Cancelled));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let client = clamav_client::tokio::Client::new(CLAMD_HOST_TCP);
+        let result =
+            clamav_client::tokio::cancellable(client.scan_buffer(b"clean data"), async {}).await;
+        assert!(matches!(result, Err(ClamdError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_dropped_scan_shuts_down_stream() {
+        use std::{
+            sync::{Arc, Mutex},
+            time::Duration,
+        };
+
+        use tokio::{
+            io::AsyncReadExt,
+            net::{TcpListener, TcpStream},
+        };
+
+        /// Connects to `address` and keeps a duplicate of each connection, so
+        /// that dropping the stream of a scan does not close the connection
+        struct DuplicatingTcp {
+            address: std::net::SocketAddr,
+            duplicates: Arc<Mutex<Vec<std::net::TcpStream>>>,
+        }
+
+        impl clamav_client::tokio::TransportProtocol for DuplicatingTcp {
+            type Stream = TcpStream;
+
+            fn connect(&self) -> impl std::future::Future<Output = std::io::Result<TcpStream>> {
+                let result = std::net::TcpStream::connect(self.address).and_then(|stream| {
+                    self.duplicates.lock().unwrap().push(stream.try_clone()?);
+                    stream.set_nonblocking(true)?;
+                    Ok(stream)
+                });
+                async move { TcpStream::from_std(result?) }
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let transport = DuplicatingTcp {
+            address: listener.local_addr().unwrap(),
+            duplicates: Arc::default(),
+        };
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut received))
+                .await
+                .expect("The write half was not shut down")
+                .unwrap();
+            received
+        });
+
+        // Only the first chunk is sent before the scan is dropped
+        let options = clamav_client::ScanOptions::new()
+            .chunk_size(1024)
+            .max_bytes_per_second(1024);
+        let scan = clamav_client::tokio::scan_buffer(&[1; 4096], &transport, options);
+        assert!(tokio::time::timeout(Duration::from_millis(200), scan)
+            .await
+            .is_err());
+
+        let received = server.await.unwrap();
+        assert!(received.starts_with(b"zINSTREAM\0"));
+        assert_eq!(received.len(), 10 + 4 + 1024);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_owned_buffer_in_task() {
+        let client = clamav_client::tokio::Client::new(CLAMD_HOST_TCP);
+        let buffer = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let outcome = tokio::spawn(async move { client.scan_buffer(buffer).await })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+
+        let response =
+            clamav_client::tokio::scan_buffer(String::from("clean data"), CLAMD_HOST_TCP, None)
+                .await
+                .unwrap();
+        assert_eq!(&response, OK_RESPONSE);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_client_builder_timeout() {
+        use std::time::Duration;
+
+        let client = clamav_client::ClamdClientBuilder::tcp(TEST_HOST_ADDRESS)
+            .timeout(Duration::from_secs(10))
+            .build_tokio();
+        assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+
+        // Accepts connections in its backlog, but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client =
+            clamav_client::ClamdClientBuilder::tcp(listener.local_addr().unwrap().to_string())
+                .timeout(Duration::from_millis(100))
+                .build_tokio();
+        let err = client.scan_buffer(b"data").await.unwrap_err();
+        assert!(matches!(
+            err,
+            clamav_client::ClamdError::Io(ref err) if err.kind() == std::io::ErrorKind::TimedOut
+        ));
+        let err = client.ping().await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn async_tokio_connection_derives() {
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: TEST_HOST_ADDRESS,
+        };
+        assert_eq!(clamd_tcp, CLAMD_HOST_TCP);
+        assert_eq!(
+            format!("{:?}", clamd_tcp),
+            format!("Tcp {{ host_address: {:?} }}", TEST_HOST_ADDRESS)
+        );
+
+        #[cfg(unix)]
+        {
+            let clamd_socket = clamav_client::tokio::Socket {
+                socket_path: TEST_SOCKET_PATH,
+            };
+            assert_eq!(clamd_socket, CLAMD_HOST_SOCKET);
+            assert_eq!(
+                format!("{:?}", clamd_socket),
+                format!("Socket {{ socket_path: {:?} }}", TEST_SOCKET_PATH)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn async_tokio_implements_send_sync_trait() {
+        trait _AssertSendSync: Send + Sync {}
+
+        impl _AssertSendSync for clamav_client::tokio::Tcp<&str> {}
+        assert_implements_send_sync(clamav_client::tokio::scan_buffer(&[], CLAMD_HOST_TCP, None));
+
+        #[cfg(unix)]
+        {
+            impl _AssertSendSync for clamav_client::tokio::Socket<&str> {}
+            assert_implements_send_sync(clamav_client::tokio::scan_buffer(
+                &[],
+                CLAMD_HOST_SOCKET,
+                None,
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn async_tokio_clam_client_trait_object() {
+        use std::{path::Path, sync::Arc};
+
+        use clamav_client::ClamClient;
+
+        let scanners: Vec<Arc<dyn ClamClient>> = vec![
+            Arc::new(clamav_client::tokio::Client::new(CLAMD_HOST_TCP)),
+            #[cfg(unix)]
+            Arc::new(clamav_client::tokio::Client::new(CLAMD_HOST_SOCKET)),
+        ];
+        for scanner in scanners {
+            assert_eq!(&scanner.ping().await.unwrap(), clamav_client::PONG);
+            assert!(scanner.version().await.unwrap().starts_with(b"ClamAV"));
+            let outcome = scanner.scan_bytes(b"clean data").await.unwrap();
+            assert!(outcome.is_clean());
+            let outcome = scanner
+                .scan_file(Path::new(EICAR_TEST_FILE_PATH))
+                .await
+                .unwrap();
+            assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+        }
+    }
+
+    #[tokio::test]
+    async fn async_tokio_clam_client_test_double() {
+        use std::{path::Path, sync::Arc};
+
+        use clamav_client::{BoxFuture, ClamClient, ClamdError, IoResult, ScanOutcome};
+
+        struct AlwaysInfected;
+
+        impl ClamClient for AlwaysInfected {
+            fn scan_bytes<'a>(
+                &'a self,
+                _bytes: &'a [u8],
+            ) -> BoxFuture<'a, Result<ScanOutcome, ClamdError>> {
+                Box::pin(async {
+                    Ok(ScanOutcome::Infected {
+                        signature: "Test-Signature".to_owned(),
+                    })
+                })
+            }
+
+            fn scan_file<'a>(
+                &'a self,
+                _file_path: &'a Path,
+            ) -> BoxFuture<'a, Result<ScanOutcome, ClamdError>> {
+                self.scan_bytes(&[])
+            }
+
+            fn ping(&self) -> BoxFuture<'_, IoResult> {
+                Box::pin(async { Ok(clamav_client::PONG.to_vec()) })
+            }
+
+            fn version(&self) -> BoxFuture<'_, IoResult> {
+                Box::pin(async { Ok(b"ClamAV 1.0.0\0".to_vec()) })
+            }
+        }
+
+        let scanner: Arc<dyn ClamClient> = Arc::new(AlwaysInfected);
+        let outcome = scanner.scan_bytes(b"clean data").await.unwrap();
+        assert_eq!(outcome.signature(), Some("Test-Signature"));
+    }
+}
+
+#[cfg(feature = "tokio-stream")]
+mod tokio_stream_tests {
+    use super::*;
+    use std::path::Path;
+    use tokio::fs::File;
+    use tokio_util::io::ReaderStream;
+
+    const CLAMD_HOST_TCP: clamav_client::tokio::Tcp<&str> = clamav_client::tokio::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    #[cfg(unix)]
+    const CLAMD_HOST_SOCKET: clamav_client::tokio::Socket<&str> = clamav_client::tokio::Socket {
+        socket_path: TEST_SOCKET_PATH,
+    };
+
+    async fn stream_from_file<P: AsRef<Path>>(path: P) -> ReaderStream<File> {
+        let path_str = path.as_ref().to_str().expect("Invalid path");
+        let err_msg = format!("Could not read test file {}", path_str);
+        let file = File::open(path).await.expect(&err_msg);
+        ReaderStream::with_capacity(file, 16)
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_scan_socket_infected_stream() {
+        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
+
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_scan_socket_clean_stream() {
+        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
+
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn async_tokio_scan_socket_oversized_stream() {
+        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
+
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_infected_stream() {
+        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
+
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "reqwest")]
+    async fn async_tokio_scan_tcp_url() {
+        let url = serve_http_once("200 OK", include_bytes!("data/eicar.txt"));
+        let response = clamav_client::tokio::scan_url(url, CLAMD_HOST_TCP, None)
+            .await
+            .expect("Could not scan URL via TCP");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "reqwest")]
+    async fn async_tokio_scan_url_with_error_status() {
+        let url = serve_http_once("404 Not Found", b"not found");
+        let result = clamav_client::tokio::scan_url(url, CLAMD_HOST_TCP, None).await;
+        let err = result.expect_err("Expected an error status to fail the scan");
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "reqwest")]
+    async fn async_tokio_scan_url_exceeding_max_stream_size() {
+        let url = serve_http_once("200 OK", include_bytes!("data/eicar.txt"));
+        let options = clamav_client::ScanOptions::new().max_stream_size(16);
+        let result = clamav_client::tokio::scan_url(url, CLAMD_HOST_TCP, options).await;
+        assert_size_limit_exceeded(result, 16);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "http-body")]
+    async fn async_tokio_scan_tcp_body_stream() {
+        let eicar = include_bytes!("data/eicar.txt");
+        let body = TestBody(vec![Ok(&eicar[..10]), Ok(&[][..]), Ok(&eicar[10..])].into());
+        let stream = clamav_client::BodyStream::new(body);
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect("Could not scan HTTP body via TCP");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "http-body")]
+    async fn async_tokio_scan_tcp_body_stream_with_error() {
+        let body = TestBody(vec![Ok(&b"data"[..]), Err("connection reset".to_owned())].into());
+        let stream = clamav_client::BodyStream::new(body);
+        let err = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert_eq!(err.to_string(), "connection reset");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "http-body")]
+    async fn async_tokio_scan_tcp_http_body_util_full() {
+        let body = http_body_util::Full::new(&include_bytes!("data/eicar.txt")[..]);
+        let stream = clamav_client::BodyStream::new(body);
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect("Could not scan HTTP body via TCP");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "multipart")]
+    async fn async_tokio_scan_tcp_multipart() {
+        let eicar = include_bytes!("data/eicar.txt");
+        let form = multipart_form(
+            &[
+                ("comment", None, b"a comment"),
+                ("upload", Some("clean.txt"), b"clean data"),
+                ("upload", Some("eicar.txt"), eicar),
+            ],
+            7,
+        );
+        let fields = clamav_client::tokio::scan_multipart(form, CLAMD_HOST_TCP, None)
+            .await
+            .expect("Could not scan multipart form via TCP");
+        assert_eq!(fields.len(), 3);
+
+        assert_eq!(fields[0].name.as_deref(), Some("comment"));
+        assert!(fields[0].result.is_none());
+        assert_eq!(fields[0].data.as_deref(), Some(&b"a comment"[..]));
+
+        assert_eq!(fields[1].file_name.as_deref(), Some("clean.txt"));
+        assert!(matches!(
+            fields[1].result,
+            Some(Ok(clamav_client::ScanOutcome::Clean))
+        ));
+        assert_eq!(fields[1].data.as_deref(), Some(&b"clean data"[..]));
+        assert!(fields[1].is_clean());
+
+        assert_eq!(fields[2].file_name.as_deref(), Some("eicar.txt"));
+        assert!(matches!(
+            &fields[2].result,
+            Some(Ok(clamav_client::ScanOutcome::Infected { signature })) if signature == "Eicar-Signature"
+        ));
+        assert!(fields[2].data.is_none());
+        assert!(!fields[2].is_clean());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "multipart")]
+    async fn async_tokio_scan_tcp_multipart_exceeding_max_stream_size() {
+        let form = multipart_form(
+            &[
+                ("upload", Some("large.txt"), &[b'a'; 100]),
+                ("upload", Some("small.txt"), b"clean data"),
+            ],
+            7,
+        );
+        let options = clamav_client::ScanOptions::new().max_stream_size(50);
+        let fields = clamav_client::tokio::scan_multipart(form, CLAMD_HOST_TCP, options)
+            .await
+            .expect("Could not scan multipart form via TCP");
+        assert_eq!(fields.len(), 2);
+        assert!(matches!(
+            fields[0].result,
+            Some(Err(clamav_client::ClamdError::SizeLimitExceeded {
+                limit: 50
+            }))
+        ));
+        assert!(fields[0].data.is_none());
+        assert!(matches!(
+            fields[1].result,
+            Some(Ok(clamav_client::ScanOutcome::Clean))
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "multipart")]
+    async fn async_tokio_scan_malformed_multipart() {
+        let body = vec![Ok::<_, std::io::Error>("--BOUNDARY\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\r\ntruncated")];
+        let form = multer::Multipart::new(tokio_stream::iter(body), "BOUNDARY");
+        let err = clamav_client::tokio::scan_multipart(form, CLAMD_HOST_TCP, None)
+            .await
+            .unwrap_err();
+        match err {
+            clamav_client::ClamdError::Io(err) => {
+                assert_eq!(err.kind(), std::io::ErrorKind::InvalidData)
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "s3")]
+    async fn async_tokio_scan_tcp_s3_byte_stream() {
+        let body =
+            aws_sdk_s3::primitives::ByteStream::from_static(include_bytes!("data/eicar.txt"));
+        let stream = clamav_client::tokio::S3ByteStream::from(body);
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect("Could not scan S3 byte stream via TCP");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "decompress")]
+    async fn async_tokio_scan_tcp_decompressed_stream() {
+        for (encoding, compressed) in compressed_eicar() {
+            // Single-byte chunks split the headers of all formats
+            let chunks = ReaderStream::with_capacity(std::io::Cursor::new(compressed), 1);
+            let stream = clamav_client::DecompressStream::new(chunks, encoding).unwrap();
+            let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
+                .await
+                .expect("Could not scan decompressed stream via TCP");
+            assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        }
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_clean_stream() {
+        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
+
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_stream_with_custom_error() {
+        #[derive(Debug)]
+        struct BodyError;
+
+        impl std::fmt::Display for BodyError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("body error")
+            }
+        }
+
+        impl std::error::Error for BodyError {}
+
+        let chunks: [Result<&[u8], BodyError>; 1] = [Ok(b"clean data")];
+        let response =
+            clamav_client::tokio::scan_stream(tokio_stream::iter(chunks), CLAMD_HOST_TCP, None)
+                .await
+                .unwrap();
+        assert_eq!(&response, OK_RESPONSE);
+
+        let chunks = [Ok(&b"clean data"[..]), Err(BodyError)];
+        let err =
+            clamav_client::tokio::scan_stream(tokio_stream::iter(chunks), CLAMD_HOST_TCP, None)
+                .await
+                .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert!(err.get_ref().unwrap().is::<BodyError>());
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_oversized_stream() {
+        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
+
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_stream_with_progress() {
+        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
+        let (options, events) = options_with_progress();
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, options)
+            .await
+            .expect("Could not scan stream with progress");
+        assert_eq!(&response, OK_RESPONSE);
+        let file_size = std::fs::metadata(CLEAN_TEST_FILE_PATH).unwrap().len();
+        assert_progress(&events, file_size, false);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_stream_with_report() {
+        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
+        let (options, reports) = options_with_report();
+        let response = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, options)
+            .await
+            .expect("Could not scan stream with report");
+        assert_eq!(&response, OK_RESPONSE);
+        let file_size = std::fs::metadata(CLEAN_TEST_FILE_PATH).unwrap().len();
+        assert_report(&reports, file_size);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "sha256")]
+    async fn async_tokio_scan_tcp_stream_with_sha256() {
+        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
+        let (options, reports) = options_with_report();
+        let response =
+            clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, options.sha256(true))
+                .await
+                .expect("Could not scan stream with SHA-256");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        let report = reports.lock().unwrap()[0].clone();
+        assert_eq!(to_hex(&report.sha256.unwrap()), EICAR_TEST_FILE_SHA256);
+        assert_eq!(report.md5, None);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_stream_exceeding_max_stream_size() {
+        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
+        let options = clamav_client::ScanOptions::new().max_stream_size(1_000_000);
+        let result = clamav_client::tokio::scan_stream(stream, CLAMD_HOST_TCP, options).await;
+        assert_size_limit_exceeded(result, 1_000_000);
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_directory_resumes_from_journal() {
+        use tokio_stream::StreamExt;
+
+        let journal_path = journal_test_path("resume-tokio");
+        std::fs::write(&journal_path, INTERRUPTED_JOURNAL).unwrap();
+        let directory_options = clamav_client::DirectoryScanOptions::new()
+            .max_file_size(1_000_000)
+            .journal(&journal_path);
+        let results: Vec<_> = clamav_client::tokio::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
+        )
+        .collect()
+        .await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].1.as_ref().unwrap().is_clean());
+        std::fs::remove_file(&journal_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn async_tokio_scan_tcp_directory() {
+        use tokio_stream::StreamExt;
+
+        let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+        let mut stream = clamav_client::tokio::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
+        );
+        let mut results = Vec::new();
+        let mut summary = clamav_client::ScanSummary::default();
+        while let Some((path, result)) = stream.next().await {
+            summary.add(&result, std::fs::metadata(&path).unwrap().len());
+            results.push((path, result));
+        }
+        assert_directory_results(&clamav_client::ScanResults { results, summary });
+    }
+}
+
+#[cfg(feature = "async-std")]
+mod async_std_tests {
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::async_std::Tcp<&str> = clamav_client::async_std::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    #[cfg(unix)]
+    const CLAMD_HOST_SOCKET: clamav_client::async_std::Socket<&str> =
+        clamav_client::async_std::Socket {
+            socket_path: TEST_SOCKET_PATH,
+        };
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_ping_socket() {
+        let err_msg = format!(
+            "Could not ping clamd via Unix socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::async_std::ping(CLAMD_HOST_SOCKET)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_get_version_socket() {
+        let err_msg = format!(
+            "Could not get ClamAV version via Unix socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::async_std::get_version(CLAMD_HOST_SOCKET)
+            .await
+            .expect(&err_msg);
+        assert!(&response.starts_with(b"ClamAV"));
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_scan_socket_infected_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response =
+            clamav_client::async_std::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_scan_socket_infected_buffer() {
+        let err_msg = format!(
+            "Could not scan EICAR test string via socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
+        );
+        let buffer = include_bytes!("data/eicar.txt");
+        let response = clamav_client::async_std::scan_buffer(buffer, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_scan_socket_clean_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response =
+            clamav_client::async_std::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_scan_socket_oversized_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response =
+            clamav_client::async_std::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    async fn async_std_ping_tcp() {
+        let err_msg = format!(
+            "Could not ping clamd via TCP at {}",
+            CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::async_std::ping(CLAMD_HOST_TCP)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_reader_not_unpin() {
+        use std::{
+            io,
+            marker::PhantomPinned,
+            pin::Pin,
+            task::{Context, Poll},
+        };
+
+        struct NotUnpin {
+            data: &'static [u8],
+            _pinned: PhantomPinned,
+        }
+
+        impl async_std::io::Read for NotUnpin {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                // SAFETY: `data` is not structurally pinned
+                let this = unsafe { self.get_unchecked_mut() };
+                let len = this.data.len().min(buf.len());
+                buf[..len].copy_from_slice(&this.data[..len]);
+                this.data = &this.data[len..];
+                Poll::Ready(Ok(len))
+            }
+        }
+
+        let reader = NotUnpin {
+            data: include_bytes!("data/eicar.txt"),
+            _pinned: PhantomPinned,
+        };
+        let response = clamav_client::async_std::scan_reader(reader, CLAMD_HOST_TCP, Some(16))
+            .await
+            .expect("Could not scan reader");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[async_std::test]
+    async fn async_std_ping_resolving_tcp() {
+        use clamav_client::async_std::{ResolvingTcp, SystemResolver};
+
+        let clamd_tcp = ResolvingTcp::new("localhost", 3310, SystemResolver);
+        let response = clamav_client::async_std::ping(clamd_tcp)
+            .await
+            .expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[async_std::test]
+    async fn async_std_ping_resolving_tcp_with_custom_resolver() {
+        use std::{io, net::SocketAddr};
+
+        struct Localhost;
+
+        impl clamav_client::async_std::Resolve for Localhost {
+            async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+                assert_eq!(host, "clamd.invalid");
+                Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+            }
+        }
+
+        let clamd_tcp =
+            clamav_client::async_std::ResolvingTcp::new("clamd.invalid", 3310, Localhost);
+        let response = clamav_client::async_std::ping(clamd_tcp)
+            .await
+            .expect("Could not ping clamd");
+        assert_eq!(&response, clamav_client::PONG);
+    }
+
+    #[async_std::test]
+    async fn async_std_get_version_tcp() {
+        let err_msg = format!(
+            "Could not get ClamAV version via TCP at {}",
+            CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::async_std::get_version(CLAMD_HOST_TCP)
+            .await
+            .expect(&err_msg);
+        assert!(&response.starts_with(b"ClamAV"));
+    }
+
+    #[async_std::test]
+    async fn async_std_reload_tcp() {
+        let response = clamav_client::async_std::reload(CLAMD_HOST_TCP)
+            .await
+            .expect("Could not reload ClamAV");
+        assert_eq!(&response, b"RELOADING\0");
+    }
+
+    #[async_std::test]
+    async fn async_std_get_stats_tcp() {
+        let response = clamav_client::async_std::get_stats(CLAMD_HOST_TCP)
+            .await
+            .expect("Could not get ClamAV stats");
+        assert!(response.starts_with(b"POOLS:"));
+        assert!(response.ends_with(b"END\0"));
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_infected_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response =
+            clamav_client::async_std::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_infected_buffer() {
+        let err_msg = format!(
+            "Could not scan EICAR test string via TCP at {}",
+            CLAMD_HOST_TCP.host_address
+        );
+        let buffer = include_bytes!("data/eicar.txt");
+        let response = clamav_client::async_std::scan_buffer(buffer, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_clean_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response =
+            clamav_client::async_std::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_oversized_file() {
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response =
+            clamav_client::async_std::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, None)
+                .await
+                .expect(&err_msg);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_scan_socket_buffers() {
+        let err_msg = format!(
+            "Could not scan buffers via socket at {}",
+            CLAMD_HOST_SOCKET.socket_path
+        );
+        let outcomes =
+            clamav_client::async_std::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_SOCKET, None)
+                .await
+                .expect(&err_msg);
+        assert_batch_outcomes(&outcomes);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_buffers() {
+        let err_msg = format!(
+            "Could not scan buffers via TCP at {}",
+            CLAMD_HOST_TCP.host_address
+        );
+        let outcomes = clamav_client::async_std::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_batch_outcomes(&outcomes);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_buffers_pipelined() {
+        let options = clamav_client::ScanOptions::new().pipeline_depth(3);
+        let outcomes =
+            clamav_client::async_std::scan_buffers(BATCH_BUFFERS, CLAMD_HOST_TCP, options)
+                .await
+                .expect("Could not scan buffers via TCP");
+        assert_batch_outcomes(&outcomes);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_files() {
+        let results =
+            clamav_client::async_std::scan_files(BATCH_FILE_PATHS, CLAMD_HOST_TCP, None, 2).await;
+        assert_batch_file_results(&results);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_directory() {
+        use async_std::stream::StreamExt;
+
+        let directory_options = clamav_client::DirectoryScanOptions::new().max_file_size(1_000_000);
+        let mut stream = clamav_client::async_std::scan_directory(
+            TEST_DATA_DIR_PATH,
+            CLAMD_HOST_TCP,
+            None,
+            directory_options,
+        );
+        let mut results = Vec::new();
+        let mut summary = clamav_client::ScanSummary::default();
+        while let Some((path, result)) = stream.next().await {
+            summary.add(&result, std::fs::metadata(&path).unwrap().len());
+            results.push((path, result));
+        }
+        assert_directory_results(&clamav_client::ScanResults { results, summary });
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_file_with_progress() {
+        let (options, events) = options_with_progress();
+        let response =
+            clamav_client::async_std::scan_file(CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+                .await
+                .expect("Could not scan file with progress");
+        assert_eq!(&response, OK_RESPONSE);
+        let file_size = std::fs::metadata(CLEAN_TEST_FILE_PATH).unwrap().len();
+        assert_progress(&events, file_size, true);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_buffer_with_report() {
+        let (options, reports) = options_with_report();
+        let response =
+            clamav_client::async_std::scan_buffer(b"clean data", CLAMD_HOST_TCP, options)
+                .await
+                .expect("Could not scan buffer with report");
+        assert_eq!(&response, OK_RESPONSE);
+        assert_report(&reports, 10);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_buffer_with_max_bytes_per_second() {
+        let start = std::time::Instant::now();
+        let response = clamav_client::async_std::scan_buffer(
+            THROTTLED_BUFFER,
+            CLAMD_HOST_TCP,
+            throttled_options(),
+        )
+        .await
+        .expect("Could not scan throttled buffer");
+        assert_eq!(&response, OK_RESPONSE);
+        assert!(start.elapsed() >= THROTTLED_MIN_DURATION);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_file_exceeding_max_stream_size() {
+        let options = clamav_client::ScanOptions::new().max_stream_size(1_000_000);
+        let result =
+            clamav_client::async_std::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, options)
+                .await;
+        assert_size_limit_exceeded(result, 1_000_000);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_buffer_exceeding_max_stream_size() {
+        let buffer = include_bytes!("data/eicar.txt");
+        let options = clamav_client::ScanOptions::new().max_stream_size(16);
+        let result = clamav_client::async_std::scan_buffer(buffer, CLAMD_HOST_TCP, options).await;
+        assert_size_limit_exceeded(result, 16);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_with_zero_chunk_size() {
+        let buffer = include_bytes!("data/eicar.txt");
+        let result = clamav_client::async_std::scan_buffer(buffer, CLAMD_HOST_TCP, Some(0)).await;
+        assert_invalid_options(result);
+        let result =
+            clamav_client::async_std::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, Some(0))
+                .await;
+        assert_invalid_options(result);
+    }
+
+    #[async_std::test]
+    async fn async_std_client_scan_tcp_file() {
+        let client = clamav_client::async_std::Client::new(CLAMD_HOST_TCP).options(Some(16));
+        let outcome = client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .await
+            .expect("Could not scan file with client");
+        assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+    }
+
+    #[async_std::test]
+    #[cfg(feature = "cache")]
+    async fn async_std_client_scan_tcp_file_with_cache() {
+        let (options, reports) = options_with_report();
+        let client = clamav_client::async_std::Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .cache(clamav_client::MemoryCache::new(16));
+        let outcome = client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .await
+            .expect("Could not scan file with cache");
+        let cached_outcome = client
+            .scan_buffer(include_bytes!("data/eicar.txt"))
+            .await
+            .expect("Could not scan cached buffer");
+        assert_eq!(outcome, cached_outcome);
+        assert!(outcome.is_infected());
+        assert_eq!(reports.lock().unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn async_std_cancellable_scan() {
+        use std::time::{Duration, Instant};
+
+        use clamav_client::ClamdError;
+
+        let scan = clamav_client::async_std::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None);
+        let response = clamav_client::async_std::cancellable(scan, std::future::pending::<()>())
+            .await
+            .unwrap();
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+
+        let (abort, aborted) = async_std::channel::bounded::<()>(1);
+        async_std::task::spawn(async move {
+            async_std::task::sleep(Duration::from_millis(100)).await;
+            abort.send(()).await.unwrap();
+        });
+        let start = Instant::now();
+        let options = clamav_client::ScanOptions::new()
+            .chunk_size(1024)
+            .max_bytes_per_second(1024);
+        let client = clamav_client::async_std::Client::new(CLAMD_HOST_TCP).options(options);
+        let result =
+            clamav_client::async_std::cancellable(client.scan_buffer(&[1; 8192]), aborted.recv())
+                .await;
+        assert!(matches!(result, Err(ClamdError::Cancelled)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[async_std::test]
+    async fn async_std_dropped_scan_terminates_stream() {
+        use std::{
+            sync::{Arc, Mutex},
+            time::Duration,
+        };
+
+        use async_std::{
+            io::ReadExt,
+            net::{TcpListener, TcpStream},
+        };
+
+        /// Connects to `address` and keeps a duplicate of each connection, so
+        /// that dropping the stream of a scan does not close the connection
+        struct DuplicatingTcp {
+            address: std::net::SocketAddr,
+            duplicates: Arc<Mutex<Vec<std::net::TcpStream>>>,
+        }
+
+        impl clamav_client::async_std::TransportProtocol for DuplicatingTcp {
+            type Stream = TcpStream;
+
+            fn connect(&self) -> impl std::future::Future<Output = std::io::Result<TcpStream>> {
+                let result = std::net::TcpStream::connect(self.address).and_then(|stream| {
+                    self.duplicates.lock().unwrap().push(stream.try_clone()?);
+                    Ok(TcpStream::from(stream))
+                });
+                async move { result }
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let transport = DuplicatingTcp {
+            address: listener.local_addr().unwrap(),
+            duplicates: Arc::default(),
+        };
+        let server = async_std::task::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = vec![0; 10 + 4 + 1024 + 4];
+            async_std::future::timeout(Duration::from_secs(5), stream.read_exact(&mut received))
+                .await
+                .expect("The stream was not terminated")
+                .unwrap();
+            received
+        });
+
+        // Only the first chunk is sent before the scan is dropped
+        let options = clamav_client::ScanOptions::new()
+            .chunk_size(1024)
+            .max_bytes_per_second(1024);
+        let scan = clamav_client::async_std::scan_buffer(&[1; 4096], &transport, options);
+        assert!(async_std::future::timeout(Duration::from_millis(200), scan)
+            .await
+            .is_err());
+
+        let received = server.await;
+        assert!(received.starts_with(b"zINSTREAM\0"));
+        assert!(received.ends_with(&[0, 0, 0, 0]));
+    }
+
+    #[async_std::test]
+    async fn async_std_implements_send_sync_trait() {
+        trait _AssertSendSync: Send + Sync {}
+
+        impl _AssertSendSync for clamav_client::async_std::Tcp<&str> {}
+        assert_implements_send_sync(clamav_client::async_std::scan_buffer(
+            &[],
+            CLAMD_HOST_TCP,
+            None,
+        ));
+
+        #[cfg(unix)]
+        {
+            impl _AssertSendSync for clamav_client::async_std::Socket<&str> {}
+            assert_implements_send_sync(clamav_client::async_std::scan_buffer(
+                &[],
+                CLAMD_HOST_SOCKET,
+                None,
+            ));
+        }
+    }
+
+    #[async_std::test]
+    async fn async_std_client_builder_timeout() {
+        use std::time::Duration;
+
+        let client = clamav_client::ClamdClientBuilder::tcp(TEST_HOST_ADDRESS)
+            .timeout(Duration::from_secs(10))
+            .build_async_std();
+        assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+
+        // Accepts connections in its backlog, but never responds
+        let listener = async_std::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let client =
+            clamav_client::ClamdClientBuilder::tcp(listener.local_addr().unwrap().to_string())
+                .timeout(Duration::from_millis(100))
+                .build_async_std();
+        let err = client.scan_buffer(b"data").await.unwrap_err();
+        assert!(matches!(
+            err,
+            clamav_client::ClamdError::Io(ref err) if err.kind() == std::io::ErrorKind::TimedOut
+        ));
+    }
+
+    #[async_std::test]
+    async fn async_std_clam_client_trait_object() {
+        use std::sync::Arc;
+
+        use clamav_client::ClamClient;
+
+        let scanners: Vec<Arc<dyn ClamClient>> = vec![
+            Arc::new(clamav_client::async_std::Client::new(CLAMD_HOST_TCP)),
+            #[cfg(unix)]
+            Arc::new(clamav_client::async_std::Client::new(CLAMD_HOST_SOCKET)),
+        ];
+        for scanner in scanners {
+            assert_eq!(&scanner.ping().await.unwrap(), clamav_client::PONG);
+            let outcome = scanner.scan_bytes(b"clean data").await.unwrap();
+            assert!(outcome.is_clean());
+        }
+    }
+}
+
+#[cfg(feature = "async-std")]
+mod async_std_stream_tests {
+    use super::*;
+    use async_std::{fs::File, path::Path};
+    use async_std_util::io::ReaderStream;
+
+    const CLAMD_HOST_TCP: clamav_client::async_std::Tcp<&str> = clamav_client::async_std::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    #[cfg(unix)]
+    const CLAMD_HOST_SOCKET: clamav_client::async_std::Socket<&str> =
+        clamav_client::async_std::Socket {
+            socket_path: TEST_SOCKET_PATH,
+        };
+
+    async fn stream_from_file<P: AsRef<Path>>(path: P) -> ReaderStream<File> {
+        let path_str = path.as_ref().to_str().expect("Invalid path");
+        let err_msg = format!("Could not read test file {}", path_str);
+        let file = File::open(path).await.expect(&err_msg);
+        ReaderStream::with_capacity(file, 16)
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_scan_socket_infected_stream() {
+        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_scan_socket_clean_stream() {
+        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            CLEAN_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+    }
+
+    #[async_std::test]
+    #[cfg(unix)]
+    async fn async_std_scan_socket_oversized_stream() {
+        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
+        let err_msg = format!(
+            "Could not scan test file {} via socket at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_SOCKET.socket_path
+        );
+        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_SOCKET, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_infected_stream() {
+        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    #[cfg(feature = "decompress")]
+    async fn async_std_scan_tcp_decompressed_stream() {
+        for (encoding, compressed) in compressed_eicar() {
+            let chunks = ReaderStream::with_capacity(async_std::io::Cursor::new(compressed), 16);
+            let stream = clamav_client::DecompressStream::new(chunks, encoding).unwrap();
+            let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, None)
+                .await
+                .expect("Could not scan decompressed stream via TCP");
+            assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        }
+    }
+
+    #[async_std::test]
+    #[cfg(feature = "http-body")]
+    async fn async_std_scan_tcp_body_stream() {
+        let body = http_body_util::Full::new(&include_bytes!("data/eicar.txt")[..]);
+        let stream = clamav_client::BodyStream::new(body);
+        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect("Could not scan HTTP body via TCP");
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_clean_stream() {
+        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, OK_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_oversized_stream() {
+        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
+        let err_msg = format!(
+            "Could not scan test file {} via TCP at {}",
+            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+        );
+        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, None)
+            .await
+            .expect(&err_msg);
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        assert_eq!(clamav_client::clean(&response), Ok(false));
+    }
+
+    #[async_std::test]
+    async fn async_std_scan_tcp_stream_exceeding_max_stream_size() {
+        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
+        let options = clamav_client::ScanOptions::new().max_stream_size(1_000_000);
+        let result = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, options).await;
+        assert_size_limit_exceeded(result, 1_000_000);
+    }
+}
+
+#[cfg(feature = "cli")]
+mod cli_tests {
+    use super::*;
+    use std::process::{Command, Output};
+
+    fn clamav_client(args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_clamav-client"))
+            .args(["--host", TEST_HOST_ADDRESS])
+            .args(args)
+            .output()
+            .expect("Could not run clamav-client")
+    }
+
+    #[test]
+    fn cli_ping() {
+        let output = clamav_client(&["ping"]);
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"PONG\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn cli_ping_socket() {
+        let output = Command::new(env!("CARGO_BIN_EXE_clamav-client"))
+            .args(["--socket", TEST_SOCKET_PATH, "ping"])
+            .output()
+            .expect("Could not run clamav-client");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"PONG\n");
+    }
+
+    #[test]
+    fn cli_version() {
+        let output = clamav_client(&["version"]);
+        assert!(output.status.success());
+        assert!(output.stdout.starts_with(b"ClamAV"));
+    }
+
+    #[test]
+    fn cli_stats() {
+        let output = clamav_client(&["stats"]);
+        assert!(output.status.success());
+        assert!(output.stdout.ends_with(b"END\n"));
+    }
+
+    #[test]
+    fn cli_scan() {
+        let output = clamav_client(&["scan", CLEAN_TEST_FILE_PATH]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{}: OK\n", CLEAN_TEST_FILE_PATH)
+        );
+
+        let output = clamav_client(&["scan", CLEAN_TEST_FILE_PATH, EICAR_TEST_FILE_PATH]);
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!(
+                "{}: OK\n{}: Eicar-Signature FOUND\n",
+                CLEAN_TEST_FILE_PATH, EICAR_TEST_FILE_PATH
+            )
+        );
+    }
+
+    #[test]
+    fn cli_scan_with_error() {
+        let output = clamav_client(&["scan", CLEAN_TEST_FILE_PATH, "tests/data/missing.txt"]);
+        assert_eq!(output.status.code(), Some(2));
+
+        // A virus found takes precedence over errors
+        let output = clamav_client(&["scan", EICAR_TEST_FILE_PATH, "tests/data/missing.txt"]);
+        assert_eq!(output.status.code(), Some(1));
+    }
+
+    #[test]
+    fn cli_scan_directory() {
+        // Without recursion, only the files directly inside are scanned
+        let output = clamav_client(&["scan", "tests"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("tests/clamav_client.rs: OK\n"));
+        assert!(!stdout.contains(EICAR_TEST_FILE_PATH));
+
+        let output = clamav_client(&["scan", "-r", "--exclude", "*.bin", "tests"]);
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("tests/data/eicar.txt: Eicar-Signature FOUND\n"));
+        assert!(!stdout.contains("stream-max-length-test-file.bin"));
+
+        let output = clamav_client(&["scan", "-r", "--exclude", "data", "tests"]);
+        assert_eq!(output.status.code(), Some(0));
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("tests/data"));
+    }
+
+    #[test]
+    fn cli_scan_json() {
+        let output = clamav_client(&[
+            "--output",
+            "json",
+            "scan",
+            EICAR_TEST_FILE_PATH,
+            "tests/data/missing.txt",
+        ]);
+        assert_eq!(output.status.code(), Some(1));
+        let results: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(results.len(), 2);
+
+        assert_eq!(results[0]["path"], EICAR_TEST_FILE_PATH);
+        assert_eq!(results[0]["verdict"], "infected");
+        assert_eq!(results[0]["signature"], "Eicar-Signature");
+        assert!(results[0]["error"].is_null());
+        assert!(results[0]["duration_ms"].as_f64().unwrap() >= 0.0);
+        assert_eq!(
+            results[0]["sha256"],
+            "131f95c51cc819465fa1797f6ccacf9d494aaaff46fa3eac73ae63ffbdfd8267"
+        );
+
+        assert_eq!(results[1]["path"], "tests/data/missing.txt");
+        assert_eq!(results[1]["verdict"], "error");
+        assert!(results[1]["signature"].is_null());
+        assert!(results[1]["error"].is_string());
+        assert!(results[1]["sha256"].is_null());
+    }
+
+    #[test]
+    fn cli_ping_json() {
+        let output = clamav_client(&["--output", "json", "ping"]);
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"{\"response\":\"PONG\"}\n");
+    }
+
+    #[test]
+    fn cli_without_server() {
+        let output = Command::new(env!("CARGO_BIN_EXE_clamav-client"))
+            .args(["--host", "localhost:1", "ping"])
+            .output()
+            .expect("Could not run clamav-client");
+        assert_eq!(output.status.code(), Some(2));
+        assert!(output.stderr.starts_with(b"error: "));
+    }
+}
+
+#[cfg(feature = "axum")]
+mod axum_tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        extract::DefaultBodyLimit,
+        http::{Request, StatusCode},
+        response::IntoResponse,
+        routing::post,
+        Router,
+    };
+    use clamav_client::axum::{ScanLayer, ScanRejection, ScannedBytes, ScannedMultipart, Scanner};
+    use tower::ServiceExt;
+
+    async fn scanned_bytes(ScannedBytes(data): ScannedBytes) -> Vec<u8> {
+        data.to_vec()
+    }
+
+    async fn scanned_multipart(ScannedMultipart(fields): ScannedMultipart) -> String {
+        let names: Vec<_> = fields
+            .iter()
+            .map(|field| field.name.as_deref().unwrap_or_default())
+            .collect();
+        names.join(",")
+    }
+
+    fn router(scanner: Scanner) -> Router {
+        Router::new()
+            .route("/bytes", post(scanned_bytes))
+            .route("/multipart", post(scanned_multipart))
+            .with_state(scanner)
+    }
+
+    fn post_request(uri: &str, body: impl Into<Body>) -> Request<Body> {
+        Request::post(uri).body(body.into()).unwrap()
+    }
+
+    fn multipart_request(fields: &[(&str, Option<&str>, &[u8])]) -> Request<Body> {
+        Request::post("/multipart")
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .body(Body::from(multipart_body(fields)))
+            .unwrap()
+    }
+
+    async fn send(app: Router, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_bytes() {
+        let app = router(Scanner::tcp(TEST_HOST_ADDRESS));
+        let (status, body) = send(app, post_request("/bytes", "clean data")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"clean data");
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_bytes_infected() {
+        let app = router(Scanner::tcp(TEST_HOST_ADDRESS));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let (status, body) = send(app, post_request("/bytes", eicar)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body, b"virus found: Eicar-Signature");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn axum_scanned_bytes_socket() {
+        let app = router(Scanner::socket(TEST_SOCKET_PATH));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let (status, _) = send(app, post_request("/bytes", eicar)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_bytes_exceeding_body_limit() {
+        let app = router(Scanner::tcp(TEST_HOST_ADDRESS)).layer(DefaultBodyLimit::max(5));
+        let (status, _) = send(app, post_request("/bytes", "clean data")).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_bytes_exceeding_max_stream_size() {
+        let options = clamav_client::ScanOptions::new().max_stream_size(5);
+        let app = router(Scanner::tcp(TEST_HOST_ADDRESS).options(options));
+        let (status, body) = send(app, post_request("/bytes", "clean data")).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body, b"request body exceeds the limit of 5 bytes");
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_bytes_without_server() {
+        let app = router(Scanner::tcp("localhost:1"));
+        let (status, _) = send(app, post_request("/bytes", "clean data")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_multipart() {
+        let app = router(Scanner::tcp(TEST_HOST_ADDRESS));
+        let request = multipart_request(&[
+            ("title", None, b"Hello"),
+            ("upload", Some("clean.txt"), b"clean data"),
+        ]);
+        let (status, body) = send(app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"title,upload");
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_multipart_infected() {
+        let app = router(Scanner::tcp(TEST_HOST_ADDRESS));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let request = multipart_request(&[
+            ("upload", Some("clean.txt"), b"clean data"),
+            ("upload", Some("eicar.txt"), &eicar),
+        ]);
+        let (status, body) = send(app, request).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body, b"virus found: Eicar-Signature");
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_multipart_invalid() {
+        let app = router(Scanner::tcp(TEST_HOST_ADDRESS));
+        let (status, _) = send(app.clone(), post_request("/multipart", "clean data")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let request = Request::post("/multipart")
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .body(Body::from("--BOUNDARY\r\nmalformed"))
+            .unwrap();
+        let (status, _) = send(app, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn axum_scanned_multipart_exceeding_body_limit() {
+        let app = router(Scanner::tcp(TEST_HOST_ADDRESS)).layer(DefaultBodyLimit::max(50));
+        let request = multipart_request(&[("upload", Some("clean.txt"), &[b'a'; 100])]);
+        let (status, _) = send(app, request).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    fn layered_router(layer: ScanLayer) -> Router {
+        Router::new()
+            .route("/upload", post(|body: String| async move { body }))
+            .layer(layer)
+    }
+
+    #[tokio::test]
+    async fn axum_scan_layer() {
+        let app = layered_router(ScanLayer::new(Scanner::tcp(TEST_HOST_ADDRESS)));
+        let (status, body) = send(app.clone(), post_request("/upload", "clean data")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"clean data");
+
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let (status, _) = send(app, post_request("/upload", eicar)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn axum_scan_layer_on_rejection() {
+        let layer =
+            ScanLayer::new(Scanner::tcp(TEST_HOST_ADDRESS)).on_rejection(
+                |rejection| match rejection {
+                    ScanRejection::Infected { signature } => {
+                        (StatusCode::FORBIDDEN, signature).into_response()
+                    }
+                    rejection => rejection.into_response(),
+                },
+            );
+        let app = layered_router(layer);
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let (status, body) = send(app, post_request("/upload", eicar)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body, b"Eicar-Signature");
+    }
+
+    #[tokio::test]
+    async fn axum_scan_layer_empty_body() {
+        // Empty bodies are not scanned, so the server is not needed
+        let app = layered_router(ScanLayer::new(Scanner::tcp("localhost:1")));
+        let (status, _) = send(app.clone(), post_request("/upload", Body::empty())).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(app, post_request("/upload", "clean data")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
+
+#[cfg(feature = "actix")]
+mod actix_tests {
+    use super::*;
+    use actix_web::{
+        body::MessageBody,
+        dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+        http::StatusCode,
+        test, web, App, HttpResponse, ResponseError,
+    };
+    use clamav_client::actix::{
+        ScanMiddleware, ScanRejection, ScannedBytes, ScannedMultipart, Scanner,
+    };
+
+    async fn scanned_bytes(ScannedBytes(data): ScannedBytes) -> Vec<u8> {
+        data.to_vec()
+    }
+
+    async fn scanned_multipart(ScannedMultipart(fields): ScannedMultipart) -> String {
+        let names: Vec<_> = fields
+            .iter()
+            .map(|field| field.name.as_deref().unwrap_or_default())
+            .collect();
+        names.join(",")
+    }
+
+    fn app(
+        scanner: Scanner,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        App::new()
+            .app_data(web::Data::new(scanner))
+            .route("/bytes", web::post().to(scanned_bytes))
+            .route("/multipart", web::post().to(scanned_multipart))
+    }
+
+    fn layered_app(
+        middleware: ScanMiddleware,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        App::new().wrap(middleware).route(
+            "/upload",
+            web::post().to(|body: String| async move { body }),
+        )
+    }
+
+    fn post_request(uri: &str, body: impl Into<web::Bytes>) -> test::TestRequest {
+        test::TestRequest::post().uri(uri).set_payload(body.into())
+    }
+
+    fn multipart_request(fields: &[(&str, Option<&str>, &[u8])]) -> test::TestRequest {
+        post_request("/multipart", multipart_body(fields))
+            .insert_header(("content-type", "multipart/form-data; boundary=BOUNDARY"))
+    }
+
+    #[actix_web::test]
+    async fn actix_scanned_bytes() {
+        let app = test::init_service(app(Scanner::tcp(TEST_HOST_ADDRESS))).await;
+        let response =
+            test::call_service(&app, post_request("/bytes", "clean data").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "clean data");
+    }
+
+    #[actix_web::test]
+    async fn actix_scanned_bytes_infected() {
+        let app = test::init_service(app(Scanner::tcp(TEST_HOST_ADDRESS))).await;
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = test::call_service(&app, post_request("/bytes", eicar).to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            test::read_body(response).await,
+            "virus found: Eicar-Signature"
+        );
+    }
+
+    #[cfg(unix)]
+    #[actix_web::test]
+    async fn actix_scanned_bytes_socket() {
+        let app = test::init_service(app(Scanner::socket(TEST_SOCKET_PATH))).await;
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = test::call_service(&app, post_request("/bytes", eicar).to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn actix_scanned_bytes_exceeding_max_stream_size() {
+        let options = clamav_client::ScanOptions::new().max_stream_size(5);
+        let app = test::init_service(app(Scanner::tcp(TEST_HOST_ADDRESS).options(options))).await;
+        let response =
+            test::call_service(&app, post_request("/bytes", "clean data").to_request()).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn actix_scanned_bytes_without_server() {
+        let app = test::init_service(app(Scanner::tcp("localhost:1"))).await;
+        let response =
+            test::call_service(&app, post_request("/bytes", "clean data").to_request()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[actix_web::test]
+    async fn actix_scanned_bytes_without_scanner() {
+        let app =
+            test::init_service(App::new().route("/bytes", web::post().to(scanned_bytes))).await;
+        let response =
+            test::call_service(&app, post_request("/bytes", "clean data").to_request()).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn actix_scanned_multipart() {
+        let app = test::init_service(app(Scanner::tcp(TEST_HOST_ADDRESS))).await;
+        let request = multipart_request(&[
+            ("title", None, b"Hello"),
+            ("upload", Some("clean.txt"), b"clean data"),
+        ]);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "title,upload");
+    }
+
+    #[actix_web::test]
+    async fn actix_scanned_multipart_infected() {
+        let app = test::init_service(app(Scanner::tcp(TEST_HOST_ADDRESS))).await;
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let request = multipart_request(&[
+            ("upload", Some("clean.txt"), b"clean data"),
+            ("upload", Some("eicar.txt"), &eicar),
+        ]);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn actix_scanned_multipart_invalid() {
+        let app = test::init_service(app(Scanner::tcp(TEST_HOST_ADDRESS))).await;
+        let response =
+            test::call_service(&app, post_request("/multipart", "clean data").to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = post_request("/multipart", "--BOUNDARY\r\nmalformed")
+            .insert_header(("content-type", "multipart/form-data; boundary=BOUNDARY"));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn actix_scan_multipart() {
+        let app = test::init_service(App::new().route(
+            "/multipart",
+            web::post().to(|multipart: actix_multipart::Multipart| async move {
+                let clamd_tcp = clamav_client::tokio::Tcp {
+                    host_address: TEST_HOST_ADDRESS,
+                };
+                let fields = clamav_client::actix::scan_multipart(multipart, clamd_tcp, None)
+                    .await
+                    .unwrap();
+                let clean: Vec<_> = fields
+                    .iter()
+                    .map(|field| field.is_clean().to_string())
+                    .collect();
+                clean.join(",")
+            }),
+        ))
+        .await;
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let request = multipart_request(&[
+            ("title", None, b"Hello"),
+            ("upload", Some("eicar.txt"), &eicar),
+            ("upload", Some("clean.txt"), b"clean data"),
+        ]);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(test::read_body(response).await, "true,false,true");
+    }
+
+    #[actix_web::test]
+    async fn actix_scan_middleware() {
+        let app = test::init_service(layered_app(ScanMiddleware::new(Scanner::tcp(
+            TEST_HOST_ADDRESS,
+        ))))
+        .await;
+        let response =
+            test::call_service(&app, post_request("/upload", "clean data").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "clean data");
+
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = test::call_service(&app, post_request("/upload", eicar).to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn actix_scan_middleware_on_rejection() {
+        let middleware =
+            ScanMiddleware::new(Scanner::tcp(TEST_HOST_ADDRESS)).on_rejection(|rejection| {
+                match rejection {
+                    ScanRejection::Infected { signature } => {
+                        HttpResponse::Forbidden().body(signature)
+                    }
+                    rejection => rejection.error_response(),
+                }
+            });
+        let app = test::init_service(layered_app(middleware)).await;
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = test::call_service(&app, post_request("/upload", eicar).to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::read_body(response).await, "Eicar-Signature");
+    }
+
+    #[actix_web::test]
+    async fn actix_scan_middleware_min_size() {
+        // Smaller payloads are not scanned, so the server is not needed
+        let middleware = ScanMiddleware::new(Scanner::tcp("localhost:1")).min_size(100);
+        let app = test::init_service(layered_app(middleware)).await;
+        let response =
+            test::call_service(&app, post_request("/upload", "clean data").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test::call_service(
+            &app,
+            post_request("/upload", [b'a'; 100].to_vec()).to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
+
+#[cfg(feature = "warp")]
+mod warp_tests {
+    use super::*;
+    use clamav_client::warp::{recover, scanned_bytes, scanned_multipart, ScanRejection, Scanner};
+    use warp::{http::StatusCode, test::request, Filter};
+
+    fn multipart_request(fields: &[(&str, Option<&str>, &[u8])]) -> warp::test::RequestBuilder {
+        request()
+            .method("POST")
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .body(multipart_body(fields))
+    }
+
+    #[tokio::test]
+    async fn warp_scanned_bytes() {
+        let filter = scanned_bytes(Scanner::tcp(TEST_HOST_ADDRESS));
+        let data = request()
+            .method("POST")
+            .body("clean data")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(data, "clean data");
+    }
+
+    #[tokio::test]
+    async fn warp_scanned_bytes_infected() {
+        let filter = scanned_bytes(Scanner::tcp(TEST_HOST_ADDRESS));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let rejection = request()
+            .method("POST")
+            .body(eicar)
+            .filter(&filter)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            rejection.find::<ScanRejection>(),
+            Some(&ScanRejection::Infected {
+                signature: "Eicar-Signature".to_owned()
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn warp_scanned_bytes_socket() {
+        let filter = scanned_bytes(Scanner::socket(TEST_SOCKET_PATH));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let rejection = request()
+            .method("POST")
+            .body(eicar)
+            .filter(&filter)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            rejection.find::<ScanRejection>(),
+            Some(ScanRejection::Infected { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn warp_scanned_bytes_exceeding_max_stream_size() {
+        let options = clamav_client::ScanOptions::new().max_stream_size(5);
+        let filter = scanned_bytes(Scanner::tcp(TEST_HOST_ADDRESS).options(options));
+        let rejection = request()
+            .method("POST")
+            .body("clean data")
+            .filter(&filter)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            rejection.find::<ScanRejection>(),
+            Some(&ScanRejection::TooLarge { limit: Some(5) })
+        );
+    }
+
+    #[tokio::test]
+    async fn warp_recover() {
+        let filter = scanned_bytes(Scanner::tcp(TEST_HOST_ADDRESS))
+            .map(|data: bytes::Bytes| data.len().to_string())
+            .recover(recover);
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = request().method("POST").body(eicar).reply(&filter).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.body(), "virus found: Eicar-Signature");
+
+        let filter = scanned_bytes(Scanner::tcp("localhost:1"))
+            .map(|data: bytes::Bytes| data.len().to_string())
+            .recover(recover);
+        let response = request()
+            .method("POST")
+            .body("clean data")
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn warp_scanned_multipart() {
+        let filter = scanned_multipart(Scanner::tcp(TEST_HOST_ADDRESS));
+        let fields = multipart_request(&[
+            ("title", None, b"Hello"),
+            ("upload", Some("clean.txt"), b"clean data"),
+        ])
+        .filter(&filter)
+        .await
+        .unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[1].data.as_deref(), Some(&b"clean data"[..]));
+    }
+
+    #[tokio::test]
+    async fn warp_scanned_multipart_infected() {
+        let filter = scanned_multipart(Scanner::tcp(TEST_HOST_ADDRESS));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let rejection = multipart_request(&[
+            ("upload", Some("clean.txt"), b"clean data"),
+            ("upload", Some("eicar.txt"), &eicar),
+        ])
+        .filter(&filter)
+        .await
+        .unwrap_err();
+        assert_eq!(
+            rejection.find::<ScanRejection>(),
+            Some(&ScanRejection::Infected {
+                signature: "Eicar-Signature".to_owned()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn warp_scanned_multipart_invalid() {
+        let filter = scanned_multipart(Scanner::tcp(TEST_HOST_ADDRESS))
+            .map(|fields: Vec<clamav_client::tokio::MultipartField>| fields.len().to_string())
+            .recover(recover);
+        let response = request()
+            .method("POST")
+            .body("clean data")
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = request()
+            .method("POST")
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .body("--BOUNDARY\r\nmalformed")
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[cfg(feature = "poem")]
+mod poem_tests {
+    use super::*;
+    use clamav_client::poem::{
+        ScanMiddleware, ScanRejection, ScannedBytes, ScannedMultipart, Scanner,
+    };
+    use poem::{
+        error::ResponseError, handler, http::StatusCode, post, test::TestClient, Endpoint,
+        EndpointExt, IntoResponse, Route,
+    };
+
+    #[handler]
+    fn scanned_bytes(ScannedBytes(data): ScannedBytes) -> Vec<u8> {
+        data.to_vec()
+    }
+
+    #[handler]
+    fn scanned_multipart(ScannedMultipart(fields): ScannedMultipart) -> String {
+        let names: Vec<_> = fields
+            .iter()
+            .map(|field| field.name.as_deref().unwrap_or_default())
+            .collect();
+        names.join(",")
+    }
+
+    #[handler]
+    fn echo(body: Vec<u8>) -> Vec<u8> {
+        body
+    }
+
+    fn routes() -> Route {
+        Route::new()
+            .at("/bytes", post(scanned_bytes))
+            .at("/multipart", post(scanned_multipart))
+    }
+
+    fn client(scanner: Scanner) -> TestClient<impl Endpoint> {
+        TestClient::new(routes().data(scanner))
+    }
+
+    fn multipart_content_type() -> &'static str {
+        "multipart/form-data; boundary=BOUNDARY"
+    }
+
+    #[tokio::test]
+    async fn poem_scanned_bytes() {
+        let client = client(Scanner::tcp(TEST_HOST_ADDRESS));
+        let response = client.post("/bytes").body("clean data").send().await;
+        response.assert_status_is_ok();
+        response.assert_text("clean data").await;
+    }
+
+    #[tokio::test]
+    async fn poem_scanned_bytes_infected() {
+        let client = client(Scanner::tcp(TEST_HOST_ADDRESS));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = client.post("/bytes").body(eicar).send().await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        response.assert_text("virus found: Eicar-Signature").await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn poem_scanned_bytes_socket() {
+        let client = client(Scanner::socket(TEST_SOCKET_PATH));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = client.post("/bytes").body(eicar).send().await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn poem_scanned_bytes_exceeding_max_stream_size() {
+        let options = clamav_client::ScanOptions::new().max_stream_size(5);
+        let client = client(Scanner::tcp(TEST_HOST_ADDRESS).options(options));
+        let response = client.post("/bytes").body("clean data").send().await;
+        response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn poem_scanned_bytes_without_clamd() {
+        let client = client(Scanner::tcp("localhost:1"));
+        let response = client.post("/bytes").body("clean data").send().await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn poem_scanned_bytes_without_scanner() {
+        let client = TestClient::new(routes());
+        let response = client.post("/bytes").body("clean data").send().await;
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn poem_scanned_multipart() {
+        let client = client(Scanner::tcp(TEST_HOST_ADDRESS));
+        let body = multipart_body(&[
+            ("title", None, b"Hello"),
+            ("upload", Some("clean.txt"), b"clean data"),
+        ]);
+        let response = client
+            .post("/multipart")
+            .content_type(multipart_content_type())
+            .body(body)
+            .send()
+            .await;
+        response.assert_status_is_ok();
+        response.assert_text("title,upload").await;
+    }
+
+    #[tokio::test]
+    async fn poem_scanned_multipart_infected() {
+        let client = client(Scanner::tcp(TEST_HOST_ADDRESS));
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let body = multipart_body(&[
+            ("upload", Some("clean.txt"), b"clean data"),
+            ("upload", Some("eicar.txt"), &eicar),
+        ]);
+        let response = client
+            .post("/multipart")
+            .content_type(multipart_content_type())
+            .body(body)
+            .send()
+            .await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        response.assert_text("virus found: Eicar-Signature").await;
+    }
+
+    #[tokio::test]
+    async fn poem_scanned_multipart_invalid() {
+        let client = client(Scanner::tcp(TEST_HOST_ADDRESS));
+        let response = client.post("/multipart").body("clean data").send().await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let response = client
+            .post("/multipart")
+            .content_type(multipart_content_type())
+            .body("--BOUNDARY\r\nmalformed")
+            .send()
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn poem_scan_middleware() {
+        let app = Route::new()
+            .at("/echo", post(echo))
+            .with(ScanMiddleware::new(Scanner::tcp(TEST_HOST_ADDRESS)));
+        let client = TestClient::new(app);
+
+        let response = client.post("/echo").body("clean data").send().await;
+        response.assert_status_is_ok();
+        response.assert_text("clean data").await;
+
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = client.post("/echo").body(eicar).send().await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        response.assert_text("virus found: Eicar-Signature").await;
+    }
+
+    #[tokio::test]
+    async fn poem_scan_middleware_empty_body() {
+        // The scanner is never used for empty bodies
+        let app = Route::new()
+            .at("/echo", post(echo))
+            .with(ScanMiddleware::new(Scanner::tcp("localhost:1")));
+        let response = TestClient::new(app).post("/echo").send().await;
+        response.assert_status_is_ok();
+        response.assert_text("").await;
+    }
+
+    #[tokio::test]
+    async fn poem_scan_middleware_on_rejection() {
+        let middleware =
+            ScanMiddleware::new(Scanner::tcp(TEST_HOST_ADDRESS)).on_rejection(|rejection| {
+                match rejection {
+                    ScanRejection::Infected { signature } => {
+                        (StatusCode::FORBIDDEN, signature).into_response()
+                    }
+                    rejection => rejection.as_response(),
+                }
+            });
+        let app = Route::new().at("/echo", post(echo)).with(middleware);
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let response = TestClient::new(app).post("/echo").body(eicar).send().await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        response.assert_text("Eicar-Signature").await;
+    }
+}
+
+#[cfg(feature = "tower")]
+mod tower_tests {
+    use super::*;
+    use clamav_client::{
+        tower::{ClamdService, ScanRequest},
+        ClamdError, ScanOptions, ScanOutcome,
+    };
+    use std::time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+
+    fn eicar_request() -> ScanRequest {
+        ScanRequest::from(std::fs::read(EICAR_TEST_FILE_PATH).unwrap())
+    }
+
+    #[tokio::test]
+    async fn tower_scan_bytes() {
+        let service = ClamdService::tcp(TEST_HOST_ADDRESS);
+        let request = ScanRequest::from(&b"clean data"[..]);
+        let outcome = service.oneshot(request).await.unwrap();
+        assert_eq!(outcome, ScanOutcome::Clean);
+    }
+
+    #[tokio::test]
+    async fn tower_scan_bytes_infected() {
+        let service = ClamdService::tcp(TEST_HOST_ADDRESS);
+        let outcome = service.oneshot(eicar_request()).await.unwrap();
+        assert_eq!(
+            outcome,
+            ScanOutcome::Infected {
+                signature: "Eicar-Signature".to_owned()
+            }
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn tower_scan_bytes_socket() {
+        let service = ClamdService::socket(TEST_SOCKET_PATH);
+        let outcome = service.oneshot(eicar_request()).await.unwrap();
+        assert!(matches!(outcome, ScanOutcome::Infected { .. }));
+    }
+
+    #[tokio::test]
+    async fn tower_scan_stream() {
+        let service = ClamdService::tcp(TEST_HOST_ADDRESS);
+        let file = tokio::fs::File::open(EICAR_TEST_FILE_PATH).await.unwrap();
+        let request = ScanRequest::new(tokio_util::io::ReaderStream::new(file));
+        assert!(request.try_clone().is_none());
+        let outcome = service.oneshot(request).await.unwrap();
+        assert!(matches!(outcome, ScanOutcome::Infected { .. }));
+    }
+
+    #[tokio::test]
+    async fn tower_scan_request_try_clone() {
+        let service = ClamdService::tcp(TEST_HOST_ADDRESS);
+        let request = eicar_request();
+        let clone = request.try_clone().unwrap();
+        let outcome = service.clone().oneshot(request).await.unwrap();
+        assert_eq!(service.oneshot(clone).await.unwrap(), outcome);
+    }
+
+    #[tokio::test]
+    async fn tower_scan_exceeding_max_stream_size() {
+        let options = ScanOptions::new().max_stream_size(5);
+        let service = ClamdService::tcp(TEST_HOST_ADDRESS).options(options);
+        let err = service
+            .oneshot(ScanRequest::from(&b"clean data"[..]))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClamdError::SizeLimitExceeded { limit: 5 }));
+    }
+
+    #[tokio::test]
+    async fn tower_scan_without_clamd() {
+        let service = ClamdService::tcp("localhost:1");
+        let err = service
+            .oneshot(ScanRequest::from(&b"clean data"[..]))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClamdError::Io(_)));
+    }
+
+    #[tokio::test]
+    async fn tower_scan_with_timeout() {
+        let service = ServiceBuilder::new()
+            .timeout(Duration::from_secs(10))
+            .service(ClamdService::tcp(TEST_HOST_ADDRESS));
+        let outcome = service.oneshot(eicar_request()).await.unwrap();
+        assert!(matches!(outcome, ScanOutcome::Infected { .. }));
+    }
+}
+
+#[cfg(feature = "tonic")]
+mod tonic_tests {
+    use super::*;
+    use clamav_client::{
+        tonic::{
+            proto::{clamd_client::ClamdClient, PingRequest, ScanChunk, VersionRequest},
+            ClamdProxy,
+        },
+        ScanOptions, ScanOutcome,
+    };
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{
+        transport::{Channel, Endpoint, Server},
+        Code,
+    };
+
+    async fn serve(proxy: ClamdProxy) -> ClamdClient<Channel> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(proxy.into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let channel = Endpoint::from_shared(format!("http://{}", address))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        ClamdClient::new(channel)
+    }
+
+    fn chunks(data: &[u8]) -> impl tokio_stream::Stream<Item = ScanChunk> {
+        let chunks: Vec<_> = data
+            .chunks(16)
+            .map(|chunk| ScanChunk {
+                data: chunk.to_vec(),
+            })
+            .collect();
+        tokio_stream::iter(chunks)
+    }
+
+    #[tokio::test]
+    async fn tonic_scan() {
+        let mut client = serve(ClamdProxy::tcp(TEST_HOST_ADDRESS)).await;
+        let verdict = client
+            .scan(chunks(b"clean data"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(ScanOutcome::from(verdict), ScanOutcome::Clean);
+    }
+
+    #[tokio::test]
+    async fn tonic_scan_infected() {
+        let mut client = serve(ClamdProxy::tcp(TEST_HOST_ADDRESS)).await;
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let verdict = client.scan(chunks(&eicar)).await.unwrap().into_inner();
+        assert_eq!(verdict.signature, "Eicar-Signature");
+        assert_eq!(
+            ScanOutcome::from(verdict),
+            ScanOutcome::Infected {
+                signature: "Eicar-Signature".to_owned()
+            }
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn tonic_scan_socket() {
+        let mut client = serve(ClamdProxy::socket(TEST_SOCKET_PATH)).await;
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let verdict = client.scan(chunks(&eicar)).await.unwrap().into_inner();
+        assert!(matches!(
+            ScanOutcome::from(verdict),
+            ScanOutcome::Infected { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn tonic_scan_exceeding_max_stream_size() {
+        let options = ScanOptions::new().max_stream_size(5);
+        let mut client = serve(ClamdProxy::tcp(TEST_HOST_ADDRESS).options(options)).await;
+        let status = client.scan(chunks(b"clean data")).await.unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+    }
+
+    #[tokio::test]
+    async fn tonic_scan_without_clamd() {
+        let mut client = serve(ClamdProxy::tcp("localhost:1")).await;
+        let status = client.scan(chunks(b"clean data")).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn tonic_ping() {
+        let mut client = serve(ClamdProxy::tcp(TEST_HOST_ADDRESS)).await;
+        client.ping(PingRequest {}).await.unwrap();
+
+        let mut client = serve(ClamdProxy::tcp("localhost:1")).await;
+        let status = client.ping(PingRequest {}).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn tonic_version() {
+        let mut client = serve(ClamdProxy::tcp(TEST_HOST_ADDRESS)).await;
+        let response = client
+            .version(VersionRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.version.starts_with("ClamAV"));
+        assert!(!response.version.ends_with('\n'));
+    }
+}
+
+#[cfg(feature = "rest-server")]
+mod rest_server_tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpStream,
+        process::{Child, Command, Stdio},
+    };
+
+    struct RestServer {
+        child: Child,
+        address: String,
+    }
+
+    impl RestServer {
+        fn start(args: &[&str]) -> Self {
+            let mut child = Command::new(env!("CARGO_BIN_EXE_clamav-rest-server"))
+                .args(["--listen", "127.0.0.1:0"])
+                .args(args)
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("Could not run clamav-rest-server");
+            let mut line = String::new();
+            BufReader::new(child.stdout.take().unwrap())
+                .read_line(&mut line)
+                .unwrap();
+            let address = line
+                .trim()
+                .strip_prefix("Listening on ")
+                .unwrap()
+                .to_owned();
+            RestServer { child, address }
+        }
+
+        fn request(&self, method: &str, path: &str, body: &[u8]) -> (u16, Value) {
+            let mut stream = TcpStream::connect(&self.address).unwrap();
+            write!(
+                stream,
+                "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                method,
+                path,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+            let response = String::from_utf8(response).unwrap();
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+            (status, serde_json::from_str(body).unwrap())
+        }
+    }
+
+    impl Drop for RestServer {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    #[test]
+    fn rest_server_scan() {
+        let server = RestServer::start(&["--host", TEST_HOST_ADDRESS]);
+        let (status, body) = server.request("POST", "/scan", b"clean data");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            json!({ "verdict": "clean", "signature": null, "error": null })
+        );
+    }
+
+    #[test]
+    fn rest_server_scan_infected() {
+        let server = RestServer::start(&["--host", TEST_HOST_ADDRESS]);
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let (status, body) = server.request("POST", "/scan", &eicar);
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            json!({ "verdict": "infected", "signature": "Eicar-Signature", "error": null })
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn rest_server_scan_socket() {
+        let server = RestServer::start(&["--socket", TEST_SOCKET_PATH]);
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        let (status, body) = server.request("POST", "/scan", &eicar);
+        assert_eq!(status, 200);
+        assert_eq!(body["verdict"], "infected");
+    }
+
+    #[test]
+    fn rest_server_scan_exceeding_max_size() {
+        let server = RestServer::start(&["--host", TEST_HOST_ADDRESS, "--max-size", "5"]);
+        let (status, body) = server.request("POST", "/scan", b"clean data");
+        assert_eq!(status, 413);
+        assert!(body["error"].is_string());
+    }
+
+    #[test]
+    fn rest_server_without_clamd() {
+        let server = RestServer::start(&["--host", "localhost:1"]);
+        let (status, _) = server.request("POST", "/scan", b"clean data");
+        assert_eq!(status, 503);
+        let (status, _) = server.request("GET", "/healthz", b"");
+        assert_eq!(status, 503);
+    }
+
+    #[test]
+    fn rest_server_healthz() {
+        let server = RestServer::start(&["--host", TEST_HOST_ADDRESS]);
+        let (status, body) = server.request("GET", "/healthz", b"");
+        assert_eq!(status, 200);
+        assert_eq!(body, json!({ "status": "ok" }));
+    }
+
+    #[test]
+    fn rest_server_version() {
+        let server = RestServer::start(&["--host", TEST_HOST_ADDRESS]);
+        let (status, body) = server.request("GET", "/version", b"");
+        assert_eq!(status, 200);
+        let version = body["version"].as_str().unwrap();
+        assert!(version.starts_with("ClamAV"));
+        assert!(!version.ends_with('\n'));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn rest_server_shutdown_on_sigterm() {
+        let mut server = RestServer::start(&["--host", TEST_HOST_ADDRESS]);
+        let status = Command::new("kill")
+            .args(["-TERM", &server.child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert!(server.child.wait().unwrap().success());
+    }
+}
+
+#[cfg(feature = "tracing")]
+mod tracing_tests {
+    use std::{
+        collections::HashMap,
+        fmt::Write,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::Tcp<&str> = clamav_client::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    /// A recorded event with the name of its span and its fields
+    #[derive(Debug)]
+    struct RecordedEvent {
+        span: String,
+        target: String,
+        level: tracing::Level,
+        fields: String,
+    }
+
+    impl RecordedEvent {
+        fn has(&self, field: &str) -> bool {
+            self.fields.split(' ').any(|f| f == field)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        next_id: Arc<AtomicU64>,
+        spans: Arc<Mutex<HashMap<u64, String>>>,
+        events: Arc<Mutex<Vec<RecordedEvent>>>,
+    }
+
+    struct FieldsVisitor(String);
+
+    impl Visit for FieldsVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if !self.0.is_empty() {
+                self.0.push(' ');
+            }
+            if field.name() == "message" {
+                write!(self.0, "{:?}", value).unwrap();
+            } else {
+                write!(self.0, "{}={:?}", field.name(), value).unwrap();
+            }
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.record_debug(field, &format_args!("{}", value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+            let mut visitor = FieldsVisitor(attributes.metadata().name().to_owned());
+            attributes.record(&mut visitor);
+            self.spans.lock().unwrap().insert(id, visitor.0);
+            span::Id::from_u64(id)
+        }
+
+        fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let span = event
+                .parent()
+                .and_then(|id| self.spans.lock().unwrap().get(&id.into_u64()).cloned())
+                .unwrap_or_default();
+            let mut visitor = FieldsVisitor(String::new());
+            event.record(&mut visitor);
+            self.events.lock().unwrap().push(RecordedEvent {
+                span,
+                target: event.metadata().target().to_owned(),
+                level: *event.metadata().level(),
+                fields: visitor.0,
+            });
+        }
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    impl Recorder {
+        fn events(&self) -> std::sync::MutexGuard<'_, Vec<RecordedEvent>> {
+            self.events.lock().unwrap()
+        }
+
+        fn find(&self, message: &str) -> Option<usize> {
+            self.events()
+                .iter()
+                .position(|event| event.fields.starts_with(message))
+        }
+    }
+
+    #[test]
+    fn trace_scan_infected() {
+        let recorder = Recorder::default();
+        let response = tracing::subscriber::with_default(recorder.clone(), || {
+            clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None).unwrap()
+        });
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+
+        let connected = recorder.find("connected to ClamAV").unwrap();
+        let chunk = recorder.find("sent chunk").unwrap();
+        let streamed = recorder.find("finished streaming").unwrap();
+        let received = recorder.find("received response").unwrap();
+        assert!(connected < chunk && chunk < streamed && streamed < received);
+
+        let events = recorder.events();
+        assert!(events
+            .iter()
+            .filter(|event| event.target != "clamav_client::wire")
+            .all(|event| event.span == "clamav_client::scan command=INSTREAM"));
+        assert!(events[chunk].has("len=69"));
+        assert_eq!(events[chunk].level, tracing::Level::TRACE);
+        assert!(events[streamed].has("bytes_sent=69"));
+        assert!(events[received].has("verdict=infected"));
+        assert!(events[received].has("signature=Eicar-Signature"));
+    }
+
+    #[test]
+    fn trace_scan_correlation_id() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let options = clamav_client::ScanOptions::new().correlation_id("upload-42");
+            clamav_client::scan_buffer(b"clean data", CLAMD_HOST_TCP, options).unwrap();
+        });
+
+        let received = recorder.find("received response").unwrap();
+        assert_eq!(
+            recorder.events()[received].span,
+            "clamav_client::scan command=INSTREAM correlation_id=upload-42"
+        );
+    }
+
+    #[test]
+    fn trace_scan_excludes_data() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let data = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+            clamav_client::scan_buffer(&data, CLAMD_HOST_TCP, None).unwrap();
+        });
+
+        let events = recorder.events();
+        assert!(!events.is_empty());
+        assert!(events
+            .iter()
+            .all(|event| !event.fields.contains("EICAR-STANDARD-ANTIVIRUS-TEST-FILE")));
+    }
+
+    #[test]
+    fn trace_connect_failure() {
+        let recorder = Recorder::default();
+        let clamd_unavailable = clamav_client::Tcp {
+            host_address: "localhost:1",
+        };
+        let result = tracing::subscriber::with_default(recorder.clone(), || {
+            clamav_client::scan_buffer(b"clean data", clamd_unavailable, None)
+        });
+        assert!(result.is_err());
+
+        assert!(recorder.find("connected to ClamAV").is_none());
+        let failed = recorder.find("could not connect to ClamAV").unwrap();
+        let events = recorder.events();
+        assert_eq!(events[failed].level, tracing::Level::WARN);
+        assert!(events[failed].fields.contains("error="));
+    }
+
+    #[test]
+    fn trace_ping() {
+        let recorder = Recorder::default();
+        let response = tracing::subscriber::with_default(recorder.clone(), || {
+            clamav_client::ping(CLAMD_HOST_TCP).unwrap()
+        });
+        assert_eq!(&response, clamav_client::PONG);
+
+        let sent = recorder.find("sent command").unwrap();
+        let received = recorder.find("received response").unwrap();
+        assert!(sent < received);
+
+        let events = recorder.events();
+        assert_eq!(events[sent].span, "clamav_client::command command=PING");
+        assert!(events[received].has("len=5"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn trace_async_scan_clean() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: TEST_HOST_ADDRESS,
+        };
+        let response = clamav_client::tokio::scan_buffer(b"clean data", clamd_tcp, None)
+            .await
+            .unwrap();
+        assert_eq!(&response, OK_RESPONSE);
+
+        let received = recorder.find("received response").unwrap();
+        let events = recorder.events();
+        assert!(events[received].has("verdict=clean"));
+        assert!(events[received].has("bytes_sent=10"));
+        assert_eq!(
+            events[received].span,
+            "clamav_client::scan command=INSTREAM"
+        );
+    }
+
+    #[test]
+    fn trace_wire_protocol() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let response = clamav_client::ping(CLAMD_HOST_TCP).unwrap();
+            assert_eq!(&response, clamav_client::PONG);
+            clamav_client::scan_buffer(b"clean data", CLAMD_HOST_TCP, None).unwrap();
+        });
+
+        let events = recorder.events();
+        let wire: Vec<&RecordedEvent> = events
+            .iter()
+            .filter(|event| event.target == "clamav_client::wire")
+            .collect();
+        let messages: Vec<&str> = wire.iter().map(|event| event.fields.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                r#"> "zPING\x00""#,
+                r#"< "PONG\x00""#,
+                r#"> "zINSTREAM\x00""#,
+                "> chunk of 10 bytes",
+                "> end of stream",
+                r#"< "stream: OK\x00""#,
+            ]
+        );
+        assert!(wire
+            .iter()
+            .all(|event| event.level == tracing::Level::TRACE));
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod metrics_tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::Tcp<&str> = clamav_client::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    /// The values recorded for a metric, identified by its name and labels
+    #[derive(Default)]
+    struct Values(Mutex<Vec<f64>>);
+
+    impl CounterFn for Values {
+        fn increment(&self, value: u64) {
+            self.0.lock().unwrap().push(value as f64);
+        }
+
+        fn absolute(&self, value: u64) {
+            self.0.lock().unwrap().push(value as f64);
+        }
+    }
+
+    impl HistogramFn for Values {
+        fn record(&self, value: f64) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    #[derive(Default)]
+    struct TestRecorder {
+        metrics: Mutex<HashMap<String, Arc<Values>>>,
+    }
+
+    impl TestRecorder {
+        fn values(&self, key: &Key) -> Arc<Values> {
+            let mut name = key.name().to_owned();
+            for label in key.labels() {
+                name.push_str(&format!(" {}={}", label.key(), label.value()));
+            }
+            let mut metrics = self.metrics.lock().unwrap();
+            Arc::clone(metrics.entry(name).or_default())
+        }
+
+        fn recorded(&self, name: &str) -> Vec<f64> {
+            match self.metrics.lock().unwrap().get(name) {
+                Some(values) => values.0.lock().unwrap().clone(),
+                None => Vec::new(),
+            }
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.values(key))
+        }
+
+        fn register_gauge(&self, _key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.values(key))
+        }
+    }
+
+    #[test]
+    fn metrics_scan_infected() {
+        let recorder = TestRecorder::default();
+        let response = metrics::with_local_recorder(&recorder, || {
+            clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None).unwrap()
+        });
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+
+        assert_eq!(
+            recorder.recorded("clamav_client_scans_total outcome=infected"),
+            vec![1.0]
+        );
+        assert!(recorder
+            .recorded("clamav_client_scans_total outcome=clean")
+            .is_empty());
+        assert_eq!(
+            recorder.recorded("clamav_client_scanned_bytes_total"),
+            vec![69.0]
+        );
+        let durations = recorder.recorded("clamav_client_scan_duration_seconds outcome=infected");
+        assert_eq!(durations.len(), 1);
+        assert!(durations[0] > 0.0);
+    }
+
+    #[test]
+    fn metrics_scan_clean() {
+        let recorder = TestRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            for _ in 0..2 {
+                let response =
+                    clamav_client::scan_buffer(b"clean data", CLAMD_HOST_TCP, None).unwrap();
+                assert_eq!(&response, OK_RESPONSE);
+            }
+        });
+
+        assert_eq!(
+            recorder.recorded("clamav_client_scans_total outcome=clean"),
+            vec![1.0, 1.0]
+        );
+        assert_eq!(
+            recorder.recorded("clamav_client_scanned_bytes_total"),
+            vec![10.0, 10.0]
+        );
+        assert!(recorder
+            .recorded("clamav_client_connect_failures_total")
+            .is_empty());
+    }
+
+    #[test]
+    fn metrics_scan_size_limit_exceeded() {
+        let recorder = TestRecorder::default();
+        let response = metrics::with_local_recorder(&recorder, || {
+            clamav_client::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, None).unwrap()
+        });
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+
+        assert_eq!(
+            recorder.recorded("clamav_client_scans_total outcome=error"),
+            vec![1.0]
+        );
+    }
+
+    #[test]
+    fn metrics_connect_failure() {
+        let recorder = TestRecorder::default();
+        let clamd_unavailable = clamav_client::Tcp {
+            host_address: "localhost:1",
+        };
+        let result = metrics::with_local_recorder(&recorder, || {
+            clamav_client::scan_buffer(b"clean data", clamd_unavailable, None)
+        });
+        assert!(result.is_err());
+
+        assert_eq!(
+            recorder.recorded("clamav_client_connect_failures_total"),
+            vec![1.0]
+        );
+        assert!(recorder
+            .recorded("clamav_client_scanned_bytes_total")
+            .is_empty());
+    }
+}
+
+#[cfg(feature = "prometheus")]
+mod prometheus_tests {
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::Tcp<&str> = clamav_client::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    /// Returns the value of the sample `name` in the current snapshot, or 0 if
+    /// it was not recorded yet
+    ///
+    /// The metrics are shared by all tests, which run concurrently, so the
+    /// tests only assert lower bounds of the changes of the values.
+    fn sample(name: &str) -> f64 {
+        clamav_client::prometheus::text_snapshot()
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .map_or(0.0, |value| value.parse().unwrap())
+    }
+
+    #[test]
+    fn prometheus_register() {
+        let registry = prometheus::Registry::new();
+        clamav_client::prometheus::register(&registry).unwrap();
+        clamav_client::scan_buffer(b"clean data", CLAMD_HOST_TCP, None).unwrap();
+
+        let names: Vec<_> = registry
+            .gather()
+            .iter()
+            .map(|family| family.name().to_owned())
+            .collect();
+        for name in [
+            "clamav_client_scans_total",
+            "clamav_client_scan_duration_seconds",
+            "clamav_client_scanned_bytes_total",
+            "clamav_client_connect_failures_total",
+        ] {
+            assert!(names.iter().any(|n| n == name), "{} is missing", name);
+        }
+
+        assert!(clamav_client::prometheus::register(&registry).is_err());
+        clamav_client::prometheus::register(&prometheus::Registry::new()).unwrap();
+    }
+
+    #[test]
+    fn prometheus_scan_infected() {
+        let infected = sample("clamav_client_scans_total{outcome=\"infected\"}");
+        let durations = sample("clamav_client_scan_duration_seconds_count{outcome=\"infected\"}");
+        let bytes = sample("clamav_client_scanned_bytes_total");
+
+        let response =
+            clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None).unwrap();
+        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+
+        assert!(sample("clamav_client_scans_total{outcome=\"infected\"}") >= infected + 1.0);
+        assert!(
+            sample("clamav_client_scan_duration_seconds_count{outcome=\"infected\"}")
+                >= durations + 1.0
+        );
+        assert!(sample("clamav_client_scanned_bytes_total") >= bytes + 69.0);
+    }
+
+    #[test]
+    fn prometheus_scan_size_limit_exceeded() {
+        let errors = sample("clamav_client_scans_total{outcome=\"error\"}");
+
+        let response =
+            clamav_client::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, None).unwrap();
+        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+
+        assert!(sample("clamav_client_scans_total{outcome=\"error\"}") >= errors + 1.0);
+    }
+
+    #[test]
+    fn prometheus_connect_failure() {
+        let failures = sample("clamav_client_connect_failures_total");
+
+        let clamd_unavailable = clamav_client::Tcp {
+            host_address: "localhost:1",
+        };
+        let result = clamav_client::scan_buffer(b"clean data", clamd_unavailable, None);
+        assert!(result.is_err());
+
+        assert!(sample("clamav_client_connect_failures_total") >= failures + 1.0);
+    }
+}
+
+#[cfg(feature = "webhook")]
+mod webhook_tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread::{self, JoinHandle},
+    };
+
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::Tcp<&str> = clamav_client::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    /// Serves a single HTTP request with `status` and returns the URL and the
+    /// head and body of the request
+    fn serve_once(status: &'static str) -> (String, JoinHandle<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+                head.push_str(&line);
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn webhook_posts_detections() {
+        let (url, server) = serve_once("204 No Content");
+        let sink = clamav_client::WebhookSink::new(url).header("Authorization", "Bearer secret");
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP).detection_sink(sink);
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        assert!(client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+        // Dropping the client waits until the detection is posted
+        drop(client);
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /alerts HTTP/1.1\r\n"));
+        assert!(head.contains("authorization: Bearer secret\r\n"));
+        assert!(head.contains("content-type: application/json\r\n"));
+        let detection: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(detection["signature"], "Eicar-Signature");
+        assert_eq!(detection["path"], EICAR_TEST_FILE_PATH);
+        assert!(detection["detected_at"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn webhook_reports_failures() {
+        let (url, server) = serve_once("500 Internal Server Error");
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let recorded_failures = failures.clone();
+        let sink = clamav_client::WebhookSink::new(url).on_failure(move |detection, err| {
+            recorded_failures
+                .lock()
+                .unwrap()
+                .push((detection.signature.clone(), err.to_string()))
+        });
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP).detection_sink(sink);
+        let eicar = std::fs::read(EICAR_TEST_FILE_PATH).unwrap();
+        assert!(client.scan_buffer(&eicar).unwrap().is_infected());
+        drop(client);
+
+        let (_, body) = server.join().unwrap();
+        assert!(body.contains(r#""path":null"#));
+        let failures = failures.lock().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "Eicar-Signature");
+        assert!(failures[0].1.contains("500"));
+    }
+}
+
+#[cfg(feature = "siem")]
+mod siem_tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use clamav_client::{Detection, EventFormatter};
+
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::Tcp<&str> = clamav_client::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    /// Scans the EICAR test file and returns its detection at a fixed time
+    fn eicar_detection() -> Detection {
+        let detections = Arc::new(Mutex::new(Vec::new()));
+        let recorded_detections = detections.clone();
+        let client = clamav_client::Client::new(CLAMD_HOST_TCP).detection_sink(
+            move |detection: &Detection| {
+                recorded_detections.lock().unwrap().push(detection.clone())
+            },
+        );
+        assert!(client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+        let mut detection = detections.lock().unwrap().pop().unwrap();
+        detection.detected_at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        detection
+    }
+
+    #[test]
+    fn siem_format_cef() {
+        let formatter = EventFormatter::new().clamd_host(TEST_HOST_ADDRESS);
+        assert_eq!(
+            formatter.cef(&eicar_detection()),
+            format!(
+                "CEF:0|ClamAV|clamav-client|{}|Eicar-Signature|Virus found|10|\
+                 rt=1700000000123 cs1Label=signature cs1=Eicar-Signature fname=eicar.txt \
+                 filePath=tests/data/eicar.txt dhost=localhost:3310",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn siem_format_correlation_id() {
+        let mut detection = eicar_detection();
+        detection.correlation_id = Some("upload-42".to_owned());
+        let formatter = EventFormatter::new();
+        assert!(formatter.cef(&detection).ends_with(" externalId=upload-42"));
+        assert!(formatter
+            .leef(&detection)
+            .ends_with("\tcorrelationId=upload-42"));
+        let event: serde_json::Value = serde_json::from_str(&formatter.json(&detection)).unwrap();
+        assert_eq!(event["correlation_id"], "upload-42");
+    }
+
+    #[test]
+    fn siem_format_cef_escapes() {
+        let mut detection = eicar_detection();
+        detection.signature = "Win.Test|a=b\\c".to_owned();
+        detection.path = None;
+        let event = EventFormatter::new().cef(&detection);
+        assert!(event.contains("|Win.Test\\|a=b\\\\c|Virus found|"));
+        assert!(event.ends_with(" cs1=Win.Test|a\\=b\\\\c"));
+    }
+
+    #[test]
+    fn siem_format_leef() {
+        let formatter = EventFormatter::new().clamd_host(TEST_HOST_ADDRESS);
+        assert_eq!(
+            formatter.leef(&eicar_detection()),
+            format!(
+                "LEEF:2.0|ClamAV|clamav-client|{}|VirusFound|x09|cat=Malware\tsev=10\t\
+                 devTime=2023-11-14T22:13:20.123+0000\tdevTimeFormat=yyyy-MM-dd'T'HH:mm:ss.SSSZ\t\
+                 signature=Eicar-Signature\tresource=eicar.txt\tfilePath=tests/data/eicar.txt\t\
+                 clamdHost=localhost:3310",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn siem_format_json() {
+        let mut detection = eicar_detection();
+        let event: serde_json::Value =
+            serde_json::from_str(&EventFormatter::new().json(&detection)).unwrap();
+        assert_eq!(
+            event,
+            serde_json::json!({
+                "event": "virus_found",
+                "timestamp": "2023-11-14T22:13:20.123Z",
+                "signature": "Eicar-Signature",
+                "path": "tests/data/eicar.txt",
+                "file_name": "eicar.txt",
+                "clamd_host": null,
+                "correlation_id": null,
+            })
+        );
+
+        detection.path = None;
+        detection.detected_at = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        let event: serde_json::Value =
+            serde_json::from_str(&EventFormatter::new().json(&detection)).unwrap();
+        assert_eq!(event["timestamp"], "2000-02-29T00:00:00.000Z");
+        assert_eq!(event["file_name"], serde_json::Value::Null);
+    }
+}
+
+#[cfg(feature = "audit")]
+mod audit_tests {
+    use std::{
+        fs, io,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use clamav_client::{AuditLog, ClamdError, Client};
+
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::Tcp<&str> = clamav_client::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    /// Creates an empty directory for the audit logs of the calling test
+    fn audit_test_dir(name: &str) -> PathBuf {
+        let dir_path = std::env::temp_dir().join(format!(
+            "clamav-client-audit-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir_path);
+        fs::create_dir_all(&dir_path).unwrap();
+        dir_path
+    }
+
+    fn read_records(path: &Path) -> Vec<serde_json::Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn audit_log_records_scans() {
+        let log_path = audit_test_dir("records").join("audit.jsonl");
+        let audit_log = std::sync::Arc::new(AuditLog::open(&log_path).unwrap());
+        let client = Client::new(CLAMD_HOST_TCP).audit_log(audit_log.clone());
+        assert!(client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        let client = Client::new(CLAMD_HOST_TCP)
+            .audit_log(audit_log)
+            .options(clamav_client::ScanOptions::new().max_stream_size(4));
+        assert!(client.scan_buffer(b"clean data").is_err());
+
+        let records = read_records(&log_path);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["source"], EICAR_TEST_FILE_PATH);
+        assert_eq!(records[0]["sha256"], EICAR_TEST_FILE_SHA256);
+        assert_eq!(records[0]["size"], 69);
+        assert_eq!(records[0]["outcome"], "infected");
+        assert_eq!(records[0]["signature"], "Eicar-Signature");
+        assert!(records[0]["timestamp"].as_str().unwrap().ends_with('Z'));
+        assert!(records[0]["duration_ms"].is_u64());
+
+        assert_eq!(records[1]["source"], serde_json::Value::Null);
+        assert_eq!(records[1]["size"], 10);
+        assert_eq!(records[1]["outcome"], "clean");
+        assert_eq!(records[1]["signature"], serde_json::Value::Null);
+
+        assert_eq!(records[2]["outcome"], "failed");
+        assert_eq!(records[2]["sha256"], serde_json::Value::Null);
+        assert_eq!(records[2]["error"], "stream size limit of 4 bytes exceeded");
+    }
+
+    #[test]
+    fn audit_log_records_correlation_id() {
+        let log_path = audit_test_dir("correlation").join("audit.jsonl");
+        let client = Client::new(CLAMD_HOST_TCP)
+            .audit_log(AuditLog::open(&log_path).unwrap())
+            .correlation_id("upload-42");
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+
+        let records = read_records(&log_path);
+        assert_eq!(records[0]["correlation_id"], "upload-42");
+    }
+
+    #[test]
+    fn audit_log_keeps_report_callback() {
+        let log_path = audit_test_dir("report").join("audit.jsonl");
+        let (options, reports) = options_with_report();
+        let client = Client::new(CLAMD_HOST_TCP)
+            .options(options)
+            .audit_log(AuditLog::open(&log_path).unwrap());
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+
+        assert_report(&reports, 10);
+        assert_eq!(read_records(&log_path).len(), 1);
+    }
+
+    #[test]
+    fn audit_log_rotates_by_renaming() {
+        let log_path = audit_test_dir("rename").join("audit.jsonl");
+        let audit_log = AuditLog::open(&log_path).unwrap().max_size(1);
+        let client = Client::new(CLAMD_HOST_TCP).audit_log(audit_log);
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        assert!(client
+            .scan_file(EICAR_TEST_FILE_PATH)
+            .unwrap()
+            .is_infected());
+
+        assert!(read_records(&log_path).is_empty());
+        let rotated_records = read_records(&log_path.with_extension("jsonl.1"));
+        assert_eq!(rotated_records.len(), 1);
+        assert_eq!(rotated_records[0]["outcome"], "infected");
+    }
+
+    #[test]
+    fn audit_log_rotates_with_hook() {
+        let dir_path = audit_test_dir("hook");
+        let log_path = dir_path.join("audit.jsonl");
+        let rotations = AtomicUsize::new(0);
+        let rotated_dir = dir_path.clone();
+        let audit_log = AuditLog::open(&log_path)
+            .unwrap()
+            .max_size(300)
+            .on_rotate(move |path| {
+                let n = rotations.fetch_add(1, Ordering::Relaxed);
+                fs::rename(path, rotated_dir.join(format!("audit-{}.jsonl", n)))
+            });
+        let client = Client::new(CLAMD_HOST_TCP).audit_log(audit_log);
+        for _ in 0..4 {
+            assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        }
+
+        // Each record has more than 150 bytes, so every second one rotates
+        assert_eq!(read_records(&dir_path.join("audit-0.jsonl")).len(), 2);
+        assert_eq!(read_records(&dir_path.join("audit-1.jsonl")).len(), 2);
+        assert!(read_records(&log_path).is_empty());
+    }
+
+    #[test]
+    fn audit_log_reopens_moved_log() {
+        let dir_path = audit_test_dir("reopen");
+        let log_path = dir_path.join("audit.jsonl");
+        let audit_log = std::sync::Arc::new(AuditLog::open(&log_path).unwrap());
+        let client = Client::new(CLAMD_HOST_TCP).audit_log(audit_log.clone());
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+
+        let moved_path = dir_path.join("audit-moved.jsonl");
+        fs::rename(&log_path, &moved_path).unwrap();
+        audit_log.reopen().unwrap();
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+
+        assert_eq!(read_records(&moved_path).len(), 1);
+        assert_eq!(read_records(&log_path).len(), 1);
+    }
+
+    #[test]
+    fn audit_log_failure_fails_scan() {
+        let log_path = audit_test_dir("failure").join("audit.jsonl");
+        let audit_log = AuditLog::open(&log_path)
+            .unwrap()
+            .max_size(1)
+            .on_rotate(|_| Err(io::Error::other("rotation failed")));
+        let client = Client::new(CLAMD_HOST_TCP).audit_log(audit_log);
+        let result = client.scan_file(EICAR_TEST_FILE_PATH);
+        assert!(matches!(result, Err(ClamdError::Io(err)) if err.to_string() == "rotation failed"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn async_tokio_audit_log_records_scans() {
+        let log_path = audit_test_dir("tokio").join("audit.jsonl");
+        let client = clamav_client::tokio::Client::new(clamav_client::tokio::Tcp {
+            host_address: TEST_HOST_ADDRESS,
+        })
+        .audit_log(AuditLog::open(&log_path).unwrap());
+        let outcome = client.scan_file(EICAR_TEST_FILE_PATH).await.unwrap();
+        assert!(outcome.is_infected());
+
+        let records = read_records(&log_path);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["sha256"], EICAR_TEST_FILE_SHA256);
+    }
+}
+
+#[cfg(all(feature = "db-update", unix))]
+mod db_update_tests {
+    use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+    use clamav_client::{FreshclamOptions, FreshclamStatus};
+
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::Tcp<&str> = clamav_client::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    /// Writes a fake `freshclam` that prints `output` and exits with
+    /// `exit_code`
+    fn fake_freshclam(name: &str, output: &str, exit_code: i32) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "clamav-client-freshclam-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let script = format!("#!/bin/sh\nprintf '%s' '{}'\nexit {}\n", output, exit_code);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn update_databases_reloads_after_update() {
+        let output = "ClamAV update process started at Mon Sep 16 10:00:00 2024
+daily.cld updated (version: 27401, sigs: 2067925, f-level: 90, builder: raynman)
+main.cvd database is up-to-date (version: 62, sigs: 6647427, f-level: 90, builder: sigmgr)
+";
+        let options = FreshclamOptions::new().freshclam_path(fake_freshclam("updated", output, 0));
+        let report = clamav_client::update_databases(&options, CLAMD_HOST_TCP).unwrap();
+        assert_eq!(report.status, FreshclamStatus::Updated);
+        assert_eq!(report.updated_databases, ["daily.cld"]);
+        assert!(report.reloaded);
+        assert_eq!(report.output, output);
+    }
+
+    #[test]
+    fn update_databases_without_update() {
+        let output = "daily.cld database is up-to-date (version: 27401)\n";
+        let options =
+            FreshclamOptions::new().freshclam_path(fake_freshclam("up-to-date", output, 1));
+        let report = clamav_client::update_databases(&options, CLAMD_HOST_TCP).unwrap();
+        assert_eq!(report.status, FreshclamStatus::UpToDate);
+        assert!(report.updated_databases.is_empty());
+        assert!(!report.reloaded);
+    }
+
+    #[test]
+    fn run_freshclam_failed() {
+        let options = FreshclamOptions::new().freshclam_path(fake_freshclam(
+            "failed",
+            "Can not connect to database.clamav.net",
+            52,
+        ));
+        let report = clamav_client::run_freshclam(&options).unwrap();
+        assert_eq!(
+            report.status,
+            FreshclamStatus::Failed {
+                code: Some(52),
+                reason: "connection problem"
+            }
+        );
+        assert!(!report.is_updated());
+    }
+
+    #[test]
+    fn run_freshclam_not_found() {
+        let options = FreshclamOptions::new().freshclam_path("/nonexistent/freshclam");
+        let err = clamav_client::run_freshclam(&options).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
+
+#[cfg(feature = "test-util")]
+mod mock_tests {
+    use clamav_client::{ClamdError, Client, MockTransport};
+
+    /// Returns the request of an INSTREAM scan of `data` in a single chunk
+    fn instream_request(data: &[u8]) -> Vec<u8> {
+        let mut request = b"zINSTREAM\0".to_vec();
+        request.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        request.extend_from_slice(data);
+        request.extend_from_slice(&[0; 4]);
+        request
+    }
+
+    /// Returns a ZIP archive of `members` empty members that claim to expand
+    /// to `uncompressed_size` bytes each
+    fn zip_archive(members: u16, uncompressed_size: u32) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut directory = Vec::new();
+        for member in 0..members {
+            let name = format!("{}.txt", member);
+            let offset = archive.len() as u32;
+            archive.extend_from_slice(b"PK\x03\x04");
+            archive.extend_from_slice(&[0; 22]);
+            archive.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            archive.extend_from_slice(&[0; 2]);
+            archive.extend_from_slice(name.as_bytes());
+
+            directory.extend_from_slice(b"PK\x01\x02");
+            directory.extend_from_slice(&[0; 20]);
+            directory.extend_from_slice(&uncompressed_size.to_le_bytes());
+            directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = archive.len() as u32;
+        archive.extend_from_slice(&directory);
+        archive.extend_from_slice(b"PK\x05\x06");
+        archive.extend_from_slice(&[0; 4]);
+        archive.extend_from_slice(&members.to_le_bytes());
+        archive.extend_from_slice(&members.to_le_bytes());
+        archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&directory_offset.to_le_bytes());
+        archive.extend_from_slice(&[0; 2]);
+        archive
+    }
+
+    /// Returns a gzip file whose trailer claims that it expands to
+    /// `uncompressed_size` bytes
+    fn gzip_file(uncompressed_size: u32) -> Vec<u8> {
+        let mut file = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\xff\x03\x00".to_vec();
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&uncompressed_size.to_le_bytes());
+        file
+    }
+
+    fn assert_archive_limit_exceeded<T: std::fmt::Debug>(result: std::io::Result<T>) {
+        let err = ClamdError::from(result.unwrap_err());
+        assert!(
+            matches!(err, ClamdError::ArchiveLimitExceeded { .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn mock_transport_archive_limits() {
+        use clamav_client::{ArchiveLimits, ScanOptions};
+
+        let limits = ArchiveLimits {
+            max_ratio: 100,
+            max_members: 10,
+        };
+        let options = ScanOptions::new().archive_limits(limits);
+        let transport = MockTransport::new().clean().clean().clean();
+        for bomb in [
+            gzip_file(1 << 20),
+            zip_archive(11, 0),
+            zip_archive(2, 1 << 30),
+        ] {
+            assert_archive_limit_exceeded(clamav_client::scan_buffer(&bomb, &transport, &options));
+        }
+        let err =
+            clamav_client::scan_buffers([gzip_file(1 << 20)], &transport, &options).unwrap_err();
+        assert!(matches!(err, ClamdError::ArchiveLimitExceeded { .. }));
+        assert!(transport.requests().is_empty());
+
+        let path =
+            std::env::temp_dir().join(format!("clamav-client-archive-{}.zip", std::process::id()));
+        std::fs::write(&path, zip_archive(11, 0)).unwrap();
+        let result = clamav_client::scan_file(&path, &transport, &options);
+        std::fs::remove_file(&path).unwrap();
+        assert_archive_limit_exceeded(result);
+        assert!(transport.requests().is_empty());
+
+        // Archives within the limits, other data and archives scanned without
+        // limits are sent as they are
+        let archive = zip_archive(10, 100);
+        clamav_client::scan_buffer(&archive, &transport, &options).unwrap();
+        clamav_client::scan_buffer(b"PK\x03\x04", &transport, &options).unwrap();
+        clamav_client::scan_buffer(gzip_file(1 << 20), &transport, None).unwrap();
+        assert_eq!(
+            transport.requests()[..2],
+            [instream_request(&archive), instream_request(b"PK\x03\x04")]
+        );
+    }
+
+    #[test]
+    fn mock_transport_archive_limits_large_directory() {
+        use clamav_client::{ArchiveLimits, ScanOptions};
+
+        // The central directory of more than 1 MiB is read in parts, and the
+        // members are counted even though the archive declares only one
+        let mut archive = zip_archive(30_000, 0);
+        let end = archive.len() - 22;
+        archive[end + 8..end + 12].copy_from_slice(&[1, 0, 1, 0]);
+        let transport = MockTransport::new().clean();
+        let options = ScanOptions::new().archive_limits(ArchiveLimits {
+            max_ratio: 100,
+            max_members: 29_999,
+        });
+        assert_archive_limit_exceeded(clamav_client::scan_buffer(&archive, &transport, &options));
+        let options = ScanOptions::new().archive_limits(ArchiveLimits {
+            max_ratio: 100,
+            max_members: 30_000,
+        });
+        clamav_client::scan_buffer(&archive, &transport, &options).unwrap();
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn mock_transport_zero_options() {
+        use clamav_client::ScanOptions;
+
+        for options in [
+            ScanOptions::new().split_size(0),
+            ScanOptions::new().pipeline_depth(0),
+            ScanOptions::new().max_bytes_per_second(0),
+        ] {
+            assert!(matches!(
+                options.validate(),
+                Err(ClamdError::InvalidOptions { .. })
+            ));
+            let transport = MockTransport::new().clean();
+            super::assert_invalid_options(clamav_client::scan_buffer(
+                b"data", &transport, &options,
+            ));
+            let err = clamav_client::scan_buffers([b"data"], &transport, &options).unwrap_err();
+            assert!(matches!(err, ClamdError::InvalidOptions { .. }));
+        }
+    }
+
+    #[test]
+    fn mock_transport_reject_empty() {
+        use clamav_client::ScanOptions;
+
+        let options = ScanOptions::new().reject_empty(true);
+        let transport = MockTransport::new().clean();
+        let err = clamav_client::scan_buffer(b"", &transport, &options).unwrap_err();
+        assert!(matches!(ClamdError::from(err), ClamdError::EmptyInput));
+        let path = std::env::temp_dir().join(format!("clamav-client-empty-{}", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        let result = clamav_client::scan_file(&path, &transport, &options);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            ClamdError::from(result.unwrap_err()),
+            ClamdError::EmptyInput
+        ));
+        assert!(transport.requests().is_empty());
+
+        // Empty inputs are scanned by default
+        let response = clamav_client::scan_buffer(b"", &transport, None).unwrap();
+        assert_eq!(response, b"stream: OK\0");
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn mock_transport_reject_empty_bundled() {
+        use clamav_client::{BundleOptions, ScanOptions};
+
+        let dir_path =
+            std::env::temp_dir().join(format!("clamav-client-empty-bundle-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let file_paths: Vec<_> = ["a.txt", "empty.txt", "b.txt"]
+            .iter()
+            .map(|name| {
+                let file_path = dir_path.join(name);
+                let contents: &[u8] = if *name == "empty.txt" { b"" } else { b"data" };
+                std::fs::write(&file_path, contents).unwrap();
+                file_path
+            })
+            .collect();
+
+        let transport = MockTransport::new().clean();
+        let options = ScanOptions::new().reject_empty(true);
+        let results = clamav_client::scan_files_bundled(
+            &file_paths,
+            &transport,
+            &options,
+            BundleOptions::new(),
+        );
+        std::fs::remove_dir_all(&dir_path).unwrap();
+        assert!(results.results[0].1.as_ref().unwrap().is_clean());
+        assert!(matches!(results.results[1].1, Err(ClamdError::EmptyInput)));
+        assert!(results.results[2].1.as_ref().unwrap().is_clean());
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_reject_empty_tokio() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let options = ScanOptions::new().reject_empty(true);
+        let stream = tokio_stream::iter([Ok::<_, std::io::Error>(bytes::Bytes::new())]);
+        let err = clamav_client::tokio::scan_stream(stream, &clamd_tcp, &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(ClamdError::from(err), ClamdError::EmptyInput));
+
+        let stream = tokio_stream::iter([Ok::<_, std::io::Error>(bytes::Bytes::from("data"))]);
+        let response = clamav_client::tokio::scan_stream(stream, &clamd_tcp, &options)
+            .await
+            .unwrap();
+        assert_eq!(response, b"stream: OK\0");
+    }
+
+    #[test]
+    fn mock_transport_scan_file_range() {
+        use clamav_client::ScanOptions;
+
+        let path =
+            std::env::temp_dir().join(format!("clamav-client-range-{}.bin", std::process::id()));
+        std::fs::write(&path, b"headerembedded objecttrailer").unwrap();
+        let transport = MockTransport::new().clean().clean();
+        let response = clamav_client::scan_file_range(&path, 6, 15, &transport, None).unwrap();
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+        clamav_client::scan_file_range(&path, 28, 0, &transport, None).unwrap();
+
+        // Ranges beyond the end of the file and oversized ranges are not sent
+        let err = clamav_client::scan_file_range(&path, 21, 8, &transport, None).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let err = clamav_client::scan_file_range(&path, u64::MAX, 2, &transport, None).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let options = ScanOptions::new().max_stream_size(10);
+        let err = clamav_client::scan_file_range(&path, 6, 15, &transport, &options).unwrap_err();
+        assert!(matches!(
+            ClamdError::from(err),
+            ClamdError::SizeLimitExceeded { .. }
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            transport.requests(),
+            [
+                instream_request(b"embedded object"),
+                b"zINSTREAM\0\0\0\0\0".to_vec()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_transport_scan_file_range_tokio() {
+        let path = std::env::temp_dir().join(format!(
+            "clamav-client-range-tokio-{}.bin",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"headerembedded objecttrailer").unwrap();
+        let transport = MockTransport::new().clean();
+        let response = clamav_client::tokio::scan_file_range(&path, 6, 15, &transport, None)
+            .await
+            .unwrap();
+        assert_eq!(clamav_client::clean(&response), Ok(true));
+        let err = clamav_client::tokio::scan_file_range(&path, 21, 8, &transport, None)
+            .await
+            .unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(transport.requests(), [instream_request(b"embedded object")]);
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn mock_transport_cli() {
+        use clamav_client::cli::{self, Cli, Command, Output};
+        use clap::Parser;
+        use std::process::ExitCode;
+
+        let cli = Cli::parse_from(["clamav-client", "--chunk-size", "2", "scan", "-r", "tests"]);
+        assert_eq!(cli.client.host, "localhost:3310");
+        assert_eq!(cli.client.output, Output::Text);
+        let scan_args = match cli.command {
+            Command::Scan(scan_args) => scan_args,
+            command => panic!("unexpected command {:?}", command),
+        };
+        assert!(scan_args.recursive);
+
+        let path =
+            std::env::temp_dir().join(format!("clamav-client-cli-{}.txt", std::process::id()));
+        std::fs::write(&path, b"data").unwrap();
+        let transport = MockTransport::new()
+            .respond(b"PONG\0".to_vec())
+            .clean()
+            .infected("Eicar-Signature");
+        let options = cli.client.options();
+        let exit_code = cli::execute(&transport, options.clone(), Command::Ping, Output::Json);
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        let scan_args = Cli::parse_from(["clamav-client", "scan", path.to_str().unwrap()]);
+        let scan_args = match scan_args.command {
+            Command::Scan(scan_args) => scan_args,
+            command => panic!("unexpected command {:?}", command),
+        };
+        let exit_code = cli::scan(&transport, options.clone(), scan_args.clone(), Output::Text);
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        let exit_code = cli::scan(&transport, options, scan_args, Output::Json);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(exit_code, ExitCode::from(1));
+        // The chunk size of the flags is used
+        assert_eq!(
+            transport.requests()[1],
+            b"zINSTREAM\0\0\0\0\x02da\0\0\0\x02ta\0\0\0\0"
+        );
+    }
+
+    #[test]
+    fn mock_transport_ping_ok_and_version() {
+        let transport = MockTransport::new()
+            .respond(b"PONG\0".to_vec())
+            .respond(b"PONGPONG\0".to_vec())
+            .respond(b"ClamAV 1.4.1/27400/Mon Sep 16 10:00:00 2024\0".to_vec())
+            .respond(b"UNKNOWN COMMAND\0".to_vec());
+
+        clamav_client::ping_ok(&transport).unwrap();
+        let err = clamav_client::ping_ok(&transport).unwrap_err();
+        assert!(matches!(err, ClamdError::InvalidResponse { .. }));
+        let version = clamav_client::version(&transport).unwrap();
+        assert_eq!(version.clamav_version, "1.4.1");
+        assert_eq!(version.database_version, Some(27400));
+        let err = clamav_client::version(&transport).unwrap_err();
+        assert!(matches!(err, ClamdError::InvalidResponse { .. }));
+    }
+
+    #[test]
+    fn mock_transport_is_available() {
+        let transport = MockTransport::new()
+            .respond(b"PONG\0".to_vec())
+            .respond(b"UNKNOWN COMMAND\0".to_vec())
+            .refuse();
+        assert!(clamav_client::is_available(&transport));
+        assert!(!clamav_client::is_available(&transport));
+        assert!(!clamav_client::is_available(&transport));
+    }
+
+    #[test]
+    #[cfg(feature = "export")]
+    fn mock_transport_export_reports() {
+        let (options, reports) = super::options_with_report();
+        let transport = MockTransport::new().clean().clean();
+        clamav_client::scan_buffer(b"data", &transport, options.clone()).unwrap();
+        let options = options.correlation_id("upload \"1\", retried");
+        clamav_client::scan_buffer(b"more data", &transport, options).unwrap();
+        let reports = reports.lock().unwrap();
+
+        let mut csv = Vec::new();
+        clamav_client::write_reports_csv(reports.iter(), &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "correlation_id,bytes_sent,connect_time_us,streaming_time_us,total_duration_us,throughput,sha256,md5"
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with(",4,"));
+        assert!(lines[1].ends_with(",,"));
+        assert!(lines[2].starts_with("\"upload \"\"1\"\", retried\",9,"));
+
+        #[cfg(feature = "parquet")]
+        {
+            let mut parquet = Vec::new();
+            clamav_client::write_reports_parquet(reports.iter(), &mut parquet).unwrap();
+            assert!(parquet.starts_with(b"PAR1"));
+            assert!(parquet.ends_with(b"PAR1"));
+        }
+    }
+
+    /// Returns the address of a TCP port that refuses connections
+    fn refusing_address() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn mock_server_validate() {
+        use clamav_client::{ClamdConnection, MockServer, MockServerOptions, Tcp, ValidationCheck};
+        use std::time::Duration;
+
+        let validate = |host_address: &str| {
+            let connection = ClamdConnection::Tcp(Tcp {
+                host_address: host_address.to_owned(),
+            });
+            let checks: Vec<_> = clamav_client::validate(&connection, Duration::from_secs(5))
+                .into_iter()
+                .map(|problem| problem.check)
+                .collect();
+            checks
+        };
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        assert_eq!(validate(server.address()), []);
+        assert_eq!(validate("localhost:clamd"), [ValidationCheck::Resolution]);
+        assert_eq!(validate(&refusing_address()), [ValidationCheck::Connection]);
+        let server = MockServer::start(MockServerOptions::new().truncate_response(2)).unwrap();
+        assert_eq!(validate(server.address()), [ValidationCheck::Ping]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mock_server_validate_socket() {
+        use clamav_client::{
+            ClamdConnection, MockServer, MockServerOptions, Socket, ValidationCheck,
+        };
+        use std::time::Duration;
+
+        let socket_path = std::env::temp_dir().join(format!(
+            "clamav-client-validate-{}.socket",
+            std::process::id()
+        ));
+        let validate = || {
+            let connection = ClamdConnection::Socket(Socket {
+                socket_path: socket_path.clone(),
+            });
+            clamav_client::validate(&connection, Duration::from_secs(5))
+        };
+        let _ = std::fs::remove_file(&socket_path);
+        let problems = validate();
+        assert_eq!(problems[0].check, ValidationCheck::Socket);
+        assert!(problems[0].message.contains("does not exist"));
+
+        std::fs::write(&socket_path, b"").unwrap();
+        let problems = validate();
+        assert_eq!(problems[0].check, ValidationCheck::Socket);
+        assert!(problems[0].message.contains("is not a socket"));
+        std::fs::remove_file(&socket_path).unwrap();
+
+        let _server = MockServer::start_unix(&socket_path, MockServerOptions::new()).unwrap();
+        assert_eq!(validate(), []);
+    }
+
+    #[test]
+    fn mock_server_test_files() {
+        use clamav_client::{MockServer, MockServerOptions, TestFiles};
+
+        let server =
+            MockServer::start(MockServerOptions::new().stream_max_length(1_000_000)).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let files = TestFiles::new().unwrap();
+        let directory = files.path().to_owned();
+        assert!(directory.is_dir());
+
+        let eicar = files.eicar("eicar.txt").unwrap();
+        assert_eq!(std::fs::read(&eicar).unwrap(), TestFiles::EICAR);
+        let response = clamav_client::scan_file(&eicar, clamd_tcp, None).unwrap();
+        assert_eq!(response, b"stream: Win.Test.EICAR_HDB-1 FOUND\0");
+
+        let oversized = files.sparse("oversized.bin", 1_000_001).unwrap();
+        assert_eq!(
+            std::fs::read(&oversized).unwrap(),
+            std::fs::read(super::OVERSIZED_TEST_FILE_PATH).unwrap()
+        );
+        let response = clamav_client::scan_file(&oversized, clamd_tcp, None).unwrap();
+        assert_eq!(response, super::SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+
+        let clean = files.file("clean.txt", b"clean data").unwrap();
+        let response = clamav_client::scan_file(&clean, clamd_tcp, None).unwrap();
+        assert_eq!(response, super::OK_RESPONSE);
+
+        drop(files);
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn mock_server_scan_files_distributed() {
+        use clamav_client::{DistributionOptions, MockServer, MockServerOptions};
+
+        let servers = [
+            MockServer::start(MockServerOptions::new()).unwrap(),
+            MockServer::start(MockServerOptions::new()).unwrap(),
+        ];
+        let mut endpoints: Vec<clamav_client::Tcp<String>> = servers
+            .iter()
+            .map(|server| clamav_client::Tcp {
+                host_address: server.address().to_owned(),
+            })
+            .collect();
+        endpoints.push(clamav_client::Tcp {
+            host_address: refusing_address(),
+        });
+        let file_paths = [
+            super::EICAR_TEST_FILE_PATH,
+            "README.md",
+            "Cargo.toml",
+            "tests/data/missing.txt",
+            "src/lib.rs",
+            "src/distribute.rs",
+        ];
+        let distribution_options = DistributionOptions::new()
+            .workers_per_endpoint(2)
+            .max_endpoint_failures(1);
+        let results = clamav_client::scan_files_distributed(
+            file_paths,
+            endpoints,
+            None,
+            distribution_options,
+        );
+        assert_eq!(results.summary.files_scanned, 6);
+        assert_eq!(results.summary.infected, 1);
+        assert_eq!(results.summary.clean, 4);
+        assert_eq!(results.summary.errored, 1);
+        let paths: Vec<_> = results.results.iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, file_paths.map(std::path::Path::new));
+        match &results.results[3].1 {
+            Err(ClamdError::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn mock_server_scan_buffers_distributed() {
+        use clamav_client::{Distribution, DistributionOptions, MockServer, MockServerOptions};
+
+        let servers = [
+            MockServer::start(MockServerOptions::new()).unwrap(),
+            MockServer::start(MockServerOptions::new()).unwrap(),
+        ];
+        let endpoints = [
+            clamav_client::Tcp {
+                host_address: servers[0].address(),
+            },
+            clamav_client::Tcp {
+                host_address: servers[1].address(),
+            },
+        ];
+        let mut buffers: Vec<Vec<u8>> = (0..20).map(|i| format!("buffer {}", i).into()).collect();
+        buffers[7] = std::fs::read(super::EICAR_TEST_FILE_PATH).unwrap();
+        let distribution_options = DistributionOptions::new().distribution(Distribution::Hashed);
+        let results = clamav_client::scan_buffers_distributed(
+            &buffers,
+            endpoints,
+            None,
+            distribution_options,
+        );
+        assert_eq!(results.summary.clean, 19);
+        assert_eq!(results.summary.infected, 1);
+        for (index, (buffer_index, result)) in results.results.iter().enumerate() {
+            assert_eq!(*buffer_index, index);
+            assert_eq!(result.as_ref().unwrap().is_infected(), index == 7);
+        }
+
+        let endpoints = [
+            clamav_client::Tcp {
+                host_address: refusing_address(),
+            },
+            clamav_client::Tcp {
+                host_address: refusing_address(),
+            },
+        ];
+        let results = clamav_client::scan_buffers_distributed(
+            &buffers,
+            endpoints,
+            None,
+            DistributionOptions::new(),
+        );
+        assert_eq!(results.summary.errored, 20);
+        for (_, result) in results {
+            match result {
+                Err(ClamdError::Io(err)) => assert!(matches!(
+                    err.kind(),
+                    std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::NotConnected
+                )),
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "schedule")]
+    fn mock_server_schedule_scans() {
+        use clamav_client::{MockServer, MockServerOptions, ScheduledScan};
+        use std::time::Duration;
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address().to_owned(),
+        };
+        let (sender, completed) = std::sync::mpsc::channel();
+        let sender = std::sync::Mutex::new(sender);
+        let scans = [
+            ScheduledScan::every("test data", Duration::from_millis(10))
+                .path(super::TEST_DATA_DIR_PATH),
+            ScheduledScan::every("eicar", Duration::from_millis(10))
+                .path(super::EICAR_TEST_FILE_PATH)
+                .on_complete(move |report| {
+                    let _ = sender.lock().unwrap().send(report.results.summary.clone());
+                }),
+        ];
+        let scheduler = clamav_client::schedule_scans(clamd_tcp, scans);
+
+        let report = scheduler.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(report.name, "test data");
+        assert_eq!(report.results.summary.files_scanned, 2);
+        assert_eq!(report.results.summary.infected, 1);
+        let summary = completed.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(summary.files_scanned, 1);
+        assert_eq!(summary.infected, 1);
+        assert_eq!(
+            scheduler
+                .recv_timeout(Duration::from_secs(10))
+                .unwrap()
+                .name,
+            "test data"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio-stream")]
+    async fn mock_server_scan_stream_item_types_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let options = MockServerOptions::new().infected_size(8, "Test-Signature");
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let chunks: Vec<std::io::Result<Vec<u8>>> =
+            vec![Ok(b"abcd".to_vec()), Ok(b"efgh".to_vec())];
+        let response =
+            clamav_client::tokio::scan_stream(tokio_stream::iter(chunks), clamd_tcp, None)
+                .await
+                .unwrap();
+        assert_eq!(response, b"stream: Test-Signature FOUND\0");
+        let chunks: [std::io::Result<&[u8]>; 2] = [Ok(b"clean"), Ok(b" data")];
+        let response =
+            clamav_client::tokio::scan_stream(tokio_stream::iter(chunks), clamd_tcp, None)
+                .await
+                .unwrap();
+        assert_eq!(response, super::OK_RESPONSE);
+    }
+
+    #[async_std::test]
+    #[cfg(feature = "async-std")]
+    async fn mock_server_scan_stream_item_types_async_std() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let options = MockServerOptions::new().infected_size(8, "Test-Signature");
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::async_std::Tcp {
+            host_address: server.address(),
+        };
+        let chunks: Vec<std::io::Result<Vec<u8>>> =
+            vec![Ok(b"abcd".to_vec()), Ok(b"efgh".to_vec())];
+        let response = clamav_client::async_std::scan_stream(
+            async_std::stream::from_iter(chunks),
+            clamd_tcp,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response, b"stream: Test-Signature FOUND\0");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_is_available_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        assert!(clamav_client::tokio::is_available(clamd_tcp).await);
+        let clamd_unavailable = clamav_client::tokio::Tcp {
+            host_address: "localhost:1",
+        };
+        assert!(!clamav_client::tokio::is_available(clamd_unavailable).await);
+    }
+
+    #[async_std::test]
+    #[cfg(feature = "async-std")]
+    async fn mock_server_is_available_async_std() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::async_std::Tcp {
+            host_address: server.address(),
+        };
+        assert!(clamav_client::async_std::is_available(clamd_tcp).await);
+    }
+
+    #[test]
+    fn mock_transport_scripted_responses() {
+        let transport = MockTransport::new()
+            .clean()
+            .infected("Eicar-Signature")
+            .refuse()
+            .respond(b"stream: Can't allocate memory ERROR\0".to_vec());
+        let client = Client::new(transport.clone());
+
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        let outcome = client.scan_buffer(b"infected data").unwrap();
+        assert_eq!(outcome.signature(), Some("Eicar-Signature"));
+        let err = client.scan_buffer(b"data").unwrap_err();
+        assert!(
+            matches!(err, ClamdError::Io(ref err) if err.kind() == std::io::ErrorKind::ConnectionRefused)
+        );
+        assert!(matches!(
+            client.scan_buffer(b"data").unwrap(),
+            clamav_client::ScanOutcome::Error { .. }
+        ));
+        assert!(client.scan_buffer(b"data").is_err());
+
+        assert_eq!(
+            transport.requests(),
+            [
+                instream_request(b"clean data"),
+                instream_request(b"infected data"),
+                instream_request(b"data"),
+            ]
+        );
+        assert_eq!(client.stats().scans, 5);
+    }
+
+    #[test]
+    fn mock_transport_commands() {
+        let transport = MockTransport::new().respond(clamav_client::PONG);
+        let response = clamav_client::ping(&transport).unwrap();
+        assert_eq!(&response, clamav_client::PONG);
+        assert_eq!(transport.requests(), [b"zPING\0".to_vec()]);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_transport_tokio() {
+        let transport = MockTransport::new().infected("Eicar-Signature");
+        let response = clamav_client::tokio::scan_buffer(b"data", &transport, None)
+            .await
+            .unwrap();
+        assert_eq!(&response, b"stream: Eicar-Signature FOUND\0");
+        assert_eq!(transport.requests(), [instream_request(b"data")]);
+        assert!(clamav_client::tokio::ping(&transport).await.is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn scan_path_encoding() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::Path};
+
+        use clamav_client::PathCommand;
+
+        let transport = MockTransport::new()
+            .respond(b"/tmp/dir with spaces/new\nline: OK\0".to_vec())
+            .clean()
+            .clean();
+        let path = Path::new("/tmp/dir with spaces/new\nline");
+        let response = clamav_client::scan_path(path, PathCommand::ContScan, &transport).unwrap();
+        assert_eq!(response, b"/tmp/dir with spaces/new\nline: OK\0");
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/latin1-\xe9.txt"));
+        clamav_client::scan_path(path, PathCommand::MultiScan, &transport).unwrap();
+        clamav_client::scan_path("/tmp", PathCommand::AllMatchScan, &transport).unwrap();
+
+        assert_eq!(
+            transport.requests(),
+            [
+                b"zCONTSCAN /tmp/dir with spaces/new\nline\0".to_vec(),
+                b"zMULTISCAN /tmp/latin1-\xe9.txt\0".to_vec(),
+                b"zALLMATCHSCAN /tmp\0".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn mock_server_verdicts() {
+        use clamav_client::{MockServer, MockServerOptions};
+        use sha2::{Digest, Sha256};
+
+        let options = MockServerOptions::new()
+            .infected_sha256(Sha256::digest(b"known malware").into(), "Known-Malware")
+            .infected_size(4, "Size-Signature");
+        let server = MockServer::start(options).unwrap();
+        let client = Client::new(clamav_client::Tcp {
+            host_address: server.address(),
+        });
+
+        let outcome = client.scan_file(super::EICAR_TEST_FILE_PATH).unwrap();
+        assert_eq!(outcome.signature(), Some("Win.Test.EICAR_HDB-1"));
+        let outcome = client.scan_buffer(b"known malware").unwrap();
+        assert_eq!(outcome.signature(), Some("Known-Malware"));
+        let outcome = client.scan_buffer(b"data").unwrap();
+        assert_eq!(outcome.signature(), Some("Size-Signature"));
+        assert!(client
+            .scan_file(super::CLEAN_TEST_FILE_PATH)
+            .unwrap()
+            .is_clean());
+    }
+
+    #[test]
+    fn mock_server_commands() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new().version("ClamAV 1.0.0")).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        assert_eq!(
+            &clamav_client::ping(clamd_tcp).unwrap(),
+            clamav_client::PONG
+        );
+        assert_eq!(
+            clamav_client::get_version(clamd_tcp).unwrap(),
+            b"ClamAV 1.0.0\0"
+        );
+        assert_eq!(clamav_client::reload(clamd_tcp).unwrap(), b"RELOADING\0");
+        assert!(clamav_client::get_stats(clamd_tcp)
+            .unwrap()
+            .starts_with(b"POOLS:"));
+
+        let address = server.address().to_owned();
+        drop(server);
+        assert!(std::net::TcpStream::connect(address).is_err());
+    }
+
+    #[test]
+    fn mock_server_split_size() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions, ScanOutcome};
+
+        let options = MockServerOptions::new()
+            .stream_max_length(4096)
+            .infected_size(1808, "Split-Part");
+        let server = MockServer::start(options).unwrap();
+        let client = Client::new(clamav_client::Tcp {
+            host_address: server.address(),
+        });
+        let outcome = client.scan_buffer([0; 8192]).unwrap();
+        assert!(matches!(outcome, ScanOutcome::Error { .. }));
+
+        let client = client.options(ScanOptions::new().split_size(4096));
+        assert!(client.scan_buffer([0; 8192]).unwrap().is_clean());
+        let outcome = client.scan_buffer([0; 10_000]).unwrap();
+        assert_eq!(outcome.signature(), Some("Split-Part"));
+
+        let path =
+            std::env::temp_dir().join(format!("clamav-client-split-{}.bin", std::process::id()));
+        std::fs::write(&path, [0; 10_000]).unwrap();
+        let outcome = client.scan_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(outcome.unwrap().signature(), Some("Split-Part"));
+    }
+
+    #[test]
+    fn mock_transport_split_size_stops_at_infected_part() {
+        use clamav_client::ScanOptions;
+
+        let transport = MockTransport::new()
+            .clean()
+            .infected("Eicar-Signature")
+            .clean();
+        let options = ScanOptions::new().chunk_size(4).split_size(4);
+        let response = clamav_client::scan_buffer(b"0123456789", &transport, options).unwrap();
+        assert_eq!(response, b"stream: Eicar-Signature FOUND\0");
+        assert_eq!(
+            transport.requests(),
+            [instream_request(b"0123"), instream_request(b"4567")]
+        );
+
+        let transport = MockTransport::new().clean();
+        let options = ScanOptions::new().split_size(4);
+        let response = clamav_client::scan_buffer(b"0123", &transport, options).unwrap();
+        assert_eq!(response, b"stream: OK\0");
+        assert_eq!(transport.requests(), [instream_request(b"0123")]);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_split_size_tokio() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions};
+
+        let server = MockServer::start(MockServerOptions::new().stream_max_length(4096)).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let options = ScanOptions::new().split_size(4096);
+        let response = clamav_client::tokio::scan_buffer([0; 10_000], clamd_tcp, options)
+            .await
+            .unwrap();
+        assert_eq!(response, b"stream: OK\0");
+    }
+
+    #[async_std::test]
+    #[cfg(feature = "async-std")]
+    async fn mock_server_split_size_async_std() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions};
+
+        let server = MockServer::start(MockServerOptions::new().stream_max_length(4096)).unwrap();
+        let clamd_tcp = clamav_client::async_std::Tcp {
+            host_address: server.address(),
+        };
+        let options = ScanOptions::new().split_size(4096);
+        let response = clamav_client::async_std::scan_buffer([0; 10_000], clamd_tcp, options)
+            .await
+            .unwrap();
+        assert_eq!(response, b"stream: OK\0");
+    }
+
+    #[test]
+    fn mock_server_write_buffer_size() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions};
+        use sha2::{Digest, Sha256};
+
+        let data = vec![7; 100_000];
+        let options =
+            MockServerOptions::new().infected_sha256(Sha256::digest(&data).into(), "Known");
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        for write_buffer_size in [0, 10, 4096, 1 << 20] {
+            let options = ScanOptions::new()
+                .chunk_size(1000)
+                .write_buffer_size(write_buffer_size);
+            let response = clamav_client::scan_buffer(&data, clamd_tcp, &options).unwrap();
+            assert_eq!(response, b"stream: Known FOUND\0");
+            let response = clamav_client::scan_reader(&data[..], clamd_tcp, &options).unwrap();
+            assert_eq!(response, b"stream: Known FOUND\0");
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_write_buffer_size_tokio() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions};
+        use sha2::{Digest, Sha256};
+
+        let data = vec![7; 100_000];
+        let options =
+            MockServerOptions::new().infected_sha256(Sha256::digest(&data).into(), "Known");
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        for write_buffer_size in [0, 10, 4096, 1 << 20] {
+            let options = ScanOptions::new()
+                .chunk_size(1000)
+                .write_buffer_size(write_buffer_size);
+            let response = clamav_client::tokio::scan_buffer(&data, clamd_tcp, &options)
+                .await
+                .unwrap();
+            assert_eq!(response, b"stream: Known FOUND\0");
+        }
+    }
+
+    #[test]
+    fn mock_server_health_check() {
+        use clamav_client::{HealthCheckOptions, HealthStatus, MockServer, MockServerOptions};
+        use std::time::Duration;
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let report =
+            clamav_client::health_check(clamd_tcp, &HealthCheckOptions::new().self_scan(true));
+        assert_eq!(
+            report.status,
+            HealthStatus::Healthy,
+            "{:?}",
+            report.problems
+        );
+        assert!(report.ping_latency.is_some());
+        assert!(report.database_age.is_some());
+        assert_eq!(report.eicar_detected, Some(true));
+        assert!(report.problems.is_empty());
+
+        let options = HealthCheckOptions::new().max_database_age(Duration::from_secs(48 * 3600));
+        let report = clamav_client::health_check(clamd_tcp, &options);
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert!(report.is_ready());
+        assert_eq!(report.eicar_detected, None);
+        assert_eq!(report.problems.len(), 1);
+
+        let server = MockServer::start(
+            MockServerOptions::new()
+                .version("ClamAV 1.4.1")
+                .latency(Duration::from_millis(20)),
+        )
+        .unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let options = HealthCheckOptions::new().max_ping_latency(Duration::from_millis(1));
+        let report = clamav_client::health_check(clamd_tcp, &options);
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert_eq!(report.version.as_deref(), Some(&b"ClamAV 1.4.1\0"[..]));
+        assert_eq!(report.database_age, None);
+        assert_eq!(report.problems.len(), 2);
+
+        drop(server);
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: "127.0.0.1:1",
+        };
+        let report = clamav_client::health_check(clamd_tcp, &HealthCheckOptions::new());
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        assert!(!report.is_ready());
+        assert_eq!(report.ping_latency, None);
+        assert_eq!(report.version, None);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_health_check_tokio() {
+        use clamav_client::{HealthCheckOptions, HealthStatus, MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let options = HealthCheckOptions::new().self_scan(true);
+        let report = clamav_client::tokio::health_check(clamd_tcp, &options).await;
+        assert_eq!(
+            report.status,
+            HealthStatus::Healthy,
+            "{:?}",
+            report.problems
+        );
+        assert_eq!(report.eicar_detected, Some(true));
+    }
+
+    #[test]
+    fn mock_server_deduplicate() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOutcome};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let buffers = [&super::BATCH_BUFFERS[..], &super::BATCH_BUFFERS[..]].concat();
+        for deduplicate in [false, true] {
+            let (options, reports) = super::options_with_report();
+            let options = options.deduplicate(deduplicate).pipeline_depth(2);
+            let outcomes = clamav_client::scan_buffers(&buffers, clamd_tcp, &options).unwrap();
+            let infected = ScanOutcome::Infected {
+                signature: "Win.Test.EICAR_HDB-1".to_owned(),
+            };
+            let expected = [infected.clone(), ScanOutcome::Clean, infected];
+            assert_eq!(outcomes, [&expected[..], &expected[..]].concat());
+            let scans = reports.lock().unwrap().len();
+            assert_eq!(scans, if deduplicate { 2 } else { 6 });
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "sha256")]
+    fn mock_server_deduplicate_directory() {
+        use clamav_client::{DirectoryScanOptions, MockServer, MockServerOptions};
+
+        let dir_path =
+            std::env::temp_dir().join(format!("clamav-client-dedup-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
+        for (name, data) in [("a", &b"same"[..]), ("b", b"same"), ("c", b"other")] {
+            std::fs::write(dir_path.join(name), data).unwrap();
+        }
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let (options, reports) = super::options_with_report();
+        let options = options.deduplicate(true);
+        let results = clamav_client::scan_directory(
+            &dir_path,
+            clamd_tcp,
+            &options,
+            DirectoryScanOptions::new(),
+        );
+        std::fs::remove_dir_all(&dir_path).unwrap();
+        assert_eq!(results.summary.files_scanned, 3);
+        assert_eq!(results.summary.clean, 3);
+        assert_eq!(reports.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "serde", feature = "tokio"))]
+    async fn mock_server_config() {
+        use clamav_client::{ClamdConfig, FailurePolicy, MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let (host, port) = server.address().rsplit_once(':').unwrap();
+        let config: ClamdConfig = serde_json::from_str(&format!(
+            r#"{{ "host": "{}", "port": {}, "timeout_ms": 5000, "chunk_size": 1024,
+                "failure_policy": "fail_closed" }}"#,
+            host, port
+        ))
+        .unwrap();
+        assert_eq!(config.failure_policy, Some(FailurePolicy::FailClosed));
+        let client = config.builder().unwrap().build();
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        let client = config.builder().unwrap().build_tokio();
+        assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+        #[cfg(feature = "async-std")]
+        {
+            let client = config.builder().unwrap().build_async_std();
+            assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+        }
+
+        let config: ClamdConfig =
+            serde_json::from_str(r#"{ "socket_path": "/tmp/clamd.sock" }"#).unwrap();
+        assert!(matches!(
+            config.connection().unwrap(),
+            clamav_client::ClamdConnection::Socket(_)
+        ));
+        for host in ["::1", "[::1]"] {
+            let config: ClamdConfig =
+                serde_json::from_str(&format!(r#"{{ "host": "{}" }}"#, host)).unwrap();
+            assert_eq!(
+                config.connection().unwrap(),
+                clamav_client::ClamdConnection::Tcp(clamav_client::Tcp {
+                    host_address: "[::1]:3310".to_owned()
+                })
+            );
+        }
+        for invalid in [
+            "{}",
+            r#"{ "port": 3310 }"#,
+            r#"{ "host": "localhost", "socket_path": "/tmp/clamd.sock" }"#,
+        ] {
+            let config: ClamdConfig = serde_json::from_str(invalid).unwrap();
+            let err = config.connection().unwrap_err();
+            assert!(matches!(err, ClamdError::InvalidOptions { .. }));
+        }
+        assert!(serde_json::from_str::<ClamdConfig>(r#"{ "hostname": "localhost" }"#).is_err());
+
+        let config: ClamdConfig =
+            serde_json::from_str(&format!(r#"{{ "host": "{}", "port": {} }}"#, host, port))
+                .unwrap();
+        assert_eq!(config.validate(), []);
+        let config: ClamdConfig = serde_json::from_str(r#"{ "chunk_size": 0 }"#).unwrap();
+        let checks: Vec<_> = config.validate().into_iter().map(|p| p.check).collect();
+        assert_eq!(
+            checks,
+            [
+                clamav_client::ValidationCheck::Configuration,
+                clamav_client::ValidationCheck::Configuration
+            ]
+        );
+        let config: ClamdConfig =
+            serde_json::from_str(r#"{ "host": "localhost", "max_bytes_per_second": 0 }"#).unwrap();
+        let checks: Vec<_> = config.validate().into_iter().map(|p| p.check).collect();
+        assert_eq!(checks, [clamav_client::ValidationCheck::Configuration]);
+        let err = config.builder().unwrap_err();
+        assert!(matches!(err, ClamdError::InvalidOptions { .. }));
+    }
+
+    #[test]
+    fn mock_server_directory_state_store() {
+        use clamav_client::{DirectoryScanOptions, MockServer, MockServerOptions};
+
+        let dir_path =
+            std::env::temp_dir().join(format!("clamav-client-state-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
+        std::fs::write(dir_path.join("clean"), b"clean data").unwrap();
+        std::fs::write(dir_path.join("changed"), b"clean data").unwrap();
+        std::fs::copy(super::EICAR_TEST_FILE_PATH, dir_path.join("infected")).unwrap();
+        let state_path = super::journal_test_path("state");
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let (options, reports) = super::options_with_report();
+        let scan = |database_version| {
+            clamav_client::scan_directory(
+                &dir_path,
+                clamd_tcp,
+                &options,
+                DirectoryScanOptions::new().state_store(&state_path, database_version),
+            )
+        };
+
+        let results = scan(1);
+        assert_eq!(results.summary.clean, 2);
+        assert_eq!(results.summary.infected, 1);
+        assert_eq!(reports.lock().unwrap().len(), 3);
+
+        // Only the infected and the changed files are scanned again
+        std::fs::write(dir_path.join("changed"), b"changed data").unwrap();
+        let results = scan(1);
+        assert_eq!(results.summary.clean, 2);
+        assert_eq!(results.summary.infected, 1);
+        assert_eq!(reports.lock().unwrap().len(), 5);
+
+        // New signatures invalidate all recorded states
+        let results = scan(2);
+        assert_eq!(results.summary.files_scanned, 3);
+        assert_eq!(reports.lock().unwrap().len(), 8);
+
+        let results = scan(2);
+        assert_eq!(results.summary.infected, 1);
+        assert_eq!(reports.lock().unwrap().len(), 9);
+        std::fs::remove_dir_all(&dir_path).unwrap();
+        std::fs::remove_file(&state_path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio-stream")]
+    async fn mock_server_directory_max_concurrency_tokio() {
+        use clamav_client::{DirectoryScanOptions, MockServer, MockServerOptions};
+        use std::time::{Duration, Instant};
+        use tokio_stream::StreamExt;
+
+        let dir_path =
+            std::env::temp_dir().join(format!("clamav-client-concurrency-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
+        for i in 0..8 {
+            std::fs::write(dir_path.join(format!("file-{}", i)), b"clean data").unwrap();
+        }
+        std::fs::copy(super::EICAR_TEST_FILE_PATH, dir_path.join("file-3")).unwrap();
+
+        let options = MockServerOptions::new().latency(Duration::from_millis(200));
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address().to_owned(),
+        };
+        let start = Instant::now();
+        let results: Vec<_> = clamav_client::tokio::scan_directory(
+            &dir_path,
+            clamd_tcp.clone(),
+            None,
+            DirectoryScanOptions::new().max_concurrency(4),
+        )
+        .collect()
+        .await;
+        // Eight scans one after another would take 1.6 seconds
+        assert!(start.elapsed() < Duration::from_millis(1200));
+        let names: Vec<_> = results
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        let expected: Vec<_> = (0..8).map(|i| format!("file-{}", i)).collect();
+        assert_eq!(names, expected);
+        for (i, (_, result)) in results.iter().enumerate() {
+            assert_eq!(result.as_ref().unwrap().is_infected(), i == 3);
+        }
+
+        // Dropping the stream cancels the scans in flight
+        let options = MockServerOptions::new().latency(Duration::from_secs(60));
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address().to_owned(),
+        };
+        let mut results = clamav_client::tokio::scan_directory(
+            &dir_path,
+            clamd_tcp,
+            None,
+            DirectoryScanOptions::new().max_concurrency(4),
+        );
+        let next = tokio::time::timeout(Duration::from_millis(100), results.next()).await;
+        assert!(next.is_err());
+        drop(results);
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tokio", feature = "bytes"))]
+    async fn mock_server_scan_bytes() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let options = ScanOptions::new().chunk_size(10);
+        let eicar = bytes::Bytes::from_static(include_bytes!("data/eicar.txt"));
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let response = clamav_client::scan_bytes(eicar.clone(), clamd_tcp, &options).unwrap();
+        assert_eq!(response, b"stream: Win.Test.EICAR_HDB-1 FOUND\0");
+
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address().to_owned(),
+        };
+        let scan = tokio::spawn(clamav_client::tokio::scan_bytes(
+            eicar.slice(..),
+            clamd_tcp,
+            options,
+        ));
+        let response = scan.await.unwrap().unwrap();
+        assert_eq!(response, b"stream: Win.Test.EICAR_HDB-1 FOUND\0");
+    }
+
+    #[test]
+    #[cfg(feature = "bind")]
+    fn mock_server_bound_tcp() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::BoundTcp {
+            host_address: server.address(),
+            local_address: "127.0.0.1:0".parse().unwrap(),
+        };
+        assert_eq!(clamav_client::ping(clamd_tcp).unwrap(), clamav_client::PONG);
+        let response = clamav_client::scan_buffer(b"clean data", clamd_tcp, None).unwrap();
+        assert_eq!(response, b"stream: OK\0");
+
+        let clamd_tcp = clamav_client::BoundTcp {
+            host_address: server.address(),
+            local_address: "[::1]:0".parse().unwrap(),
+        };
+        let err = clamav_client::ping(clamd_tcp).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_bound_tcp_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::BoundTcp {
+            host_address: server.address(),
+            local_address: "127.0.0.1:0".parse().unwrap(),
+        };
+        let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+        assert_eq!(response, clamav_client::PONG);
+
+        let clamd_tcp = clamav_client::tokio::BoundTcp {
+            host_address: server.address(),
+            local_address: "[::1]:0".parse().unwrap(),
+        };
+        let err = clamav_client::tokio::ping(clamd_tcp).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[async_std::test]
+    #[cfg(all(feature = "async-std", feature = "bind"))]
+    async fn mock_server_bound_tcp_async_std() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::async_std::BoundTcp {
+            host_address: server.address(),
+            local_address: "127.0.0.1:0".parse().unwrap(),
+        };
+        let response = clamav_client::async_std::ping(clamd_tcp).await.unwrap();
+        assert_eq!(response, clamav_client::PONG);
+    }
+
+    #[test]
+    fn mock_server_family_tcp() {
+        use clamav_client::{FamilyTcp, IpFamily, MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        for ip_family in [IpFamily::Any, IpFamily::PreferIpv6, IpFamily::Ipv4Only] {
+            let clamd_tcp = FamilyTcp {
+                host_address: server.address(),
+                ip_family,
+            };
+            assert_eq!(clamav_client::ping(clamd_tcp).unwrap(), clamav_client::PONG);
+            let response = clamav_client::scan_buffer(b"clean data", clamd_tcp, None).unwrap();
+            assert_eq!(response, b"stream: OK\0");
+        }
+
+        let clamd_tcp = FamilyTcp {
+            host_address: server.address(),
+            ip_family: IpFamily::Ipv6Only,
+        };
+        let err = clamav_client::ping(clamd_tcp).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let client =
+            clamav_client::Client::new(clamd_tcp).timeout(std::time::Duration::from_secs(1));
+        assert!(client.ping().is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_family_tcp_tokio() {
+        use clamav_client::{IpFamily, MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::FamilyTcp {
+            host_address: server.address(),
+            ip_family: IpFamily::PreferIpv4,
+        };
+        let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+        assert_eq!(response, clamav_client::PONG);
+
+        let clamd_tcp = clamav_client::tokio::FamilyTcp {
+            host_address: server.address(),
+            ip_family: IpFamily::Ipv6Only,
+        };
+        let err = clamav_client::tokio::ping(clamd_tcp).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[async_std::test]
+    #[cfg(feature = "async-std")]
+    async fn mock_server_family_tcp_async_std() {
+        use clamav_client::{IpFamily, MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::async_std::FamilyTcp {
+            host_address: server.address(),
+            ip_family: IpFamily::Ipv4Only,
+        };
+        let response = clamav_client::async_std::ping(clamd_tcp).await.unwrap();
+        assert_eq!(response, clamav_client::PONG);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mock_server_socket_or_tcp() {
+        use clamav_client::TransportProtocol;
+        use clamav_client::{MockServer, MockServerOptions, SocketOrTcp, SocketOrTcpStream};
+
+        let socket_path = std::env::temp_dir().join(format!(
+            "clamav-client-socket-or-tcp-{}.socket",
+            std::process::id()
+        ));
+        let tcp_server = MockServer::start(MockServerOptions::new().version("ClamAV TCP")).unwrap();
+        let clamd = SocketOrTcp {
+            socket_path: &socket_path,
+            host_address: tcp_server.address(),
+        };
+
+        let unix_server = MockServer::start_unix(
+            &socket_path,
+            MockServerOptions::new().version("ClamAV Unix"),
+        )
+        .unwrap();
+        assert!(matches!(
+            clamd.connect().unwrap(),
+            SocketOrTcpStream::Socket(_)
+        ));
+        assert_eq!(clamav_client::get_version(clamd).unwrap(), b"ClamAV Unix\0");
+        drop(unix_server);
+
+        // The socket file does not exist
+        assert!(matches!(
+            clamd.connect().unwrap(),
+            SocketOrTcpStream::Tcp(_)
+        ));
+        assert_eq!(clamav_client::get_version(clamd).unwrap(), b"ClamAV TCP\0");
+
+        // The socket file exists, but refuses connections
+        drop(std::os::unix::net::UnixListener::bind(&socket_path).unwrap());
+        let response = clamav_client::scan_buffer(b"clean data", clamd, None).unwrap();
+        assert_eq!(response, b"stream: OK\0");
+        std::fs::remove_file(&socket_path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, feature = "tokio"))]
+    async fn mock_server_socket_or_tcp_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let socket_path = std::env::temp_dir().join(format!(
+            "clamav-client-socket-or-tcp-tokio-{}.socket",
+            std::process::id()
+        ));
+        let tcp_server = MockServer::start(MockServerOptions::new().version("ClamAV TCP")).unwrap();
+        let clamd = clamav_client::tokio::SocketOrTcp {
+            socket_path: &socket_path,
+            host_address: tcp_server.address(),
+        };
+
+        let unix_server = MockServer::start_unix(
+            &socket_path,
+            MockServerOptions::new().version("ClamAV Unix"),
+        )
+        .unwrap();
+        let response = clamav_client::tokio::get_version(clamd).await.unwrap();
+        assert_eq!(response, b"ClamAV Unix\0");
+        drop(unix_server);
+
+        let response = clamav_client::tokio::get_version(clamd).await.unwrap();
+        assert_eq!(response, b"ClamAV TCP\0");
+        let response = clamav_client::tokio::scan_buffer(b"clean data", clamd, None)
+            .await
+            .unwrap();
+        assert_eq!(response, b"stream: OK\0");
+    }
+
+    #[async_std::test]
+    #[cfg(all(unix, feature = "async-std"))]
+    async fn mock_server_socket_or_tcp_async_std() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let socket_path = std::env::temp_dir().join(format!(
+            "clamav-client-socket-or-tcp-async-std-{}.socket",
+            std::process::id()
+        ));
+        let tcp_server = MockServer::start(MockServerOptions::new().version("ClamAV TCP")).unwrap();
+        let clamd = clamav_client::async_std::SocketOrTcp {
+            socket_path: &socket_path,
+            host_address: tcp_server.address(),
+        };
+
+        let unix_server = MockServer::start_unix(
+            &socket_path,
+            MockServerOptions::new().version("ClamAV Unix"),
+        )
+        .unwrap();
+        let response = clamav_client::async_std::get_version(clamd).await.unwrap();
+        assert_eq!(response, b"ClamAV Unix\0");
+        drop(unix_server);
+
+        let response = clamav_client::async_std::get_version(clamd).await.unwrap();
+        assert_eq!(response, b"ClamAV TCP\0");
+    }
+
+    #[test]
+    fn mock_server_verdict_policy() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOutcome};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let server =
+            MockServer::start(MockServerOptions::new().infected_size(4, "Test-Signature")).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let responses = Arc::new(AtomicUsize::new(0));
+        let detections = Arc::clone(&responses);
+        let client = clamav_client::Client::new(clamd_tcp)
+            .verdict_policy(|outcome| match outcome {
+                ScanOutcome::Infected { signature } if signature == "Test-Signature" => {
+                    ScanOutcome::Clean
+                }
+                outcome => outcome,
+            })
+            .on_response(move |outcome| {
+                if outcome.is_infected() {
+                    detections.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+
+        assert!(client.scan_buffer(b"abcd").unwrap().is_clean());
+        let outcome = client.scan_file(super::EICAR_TEST_FILE_PATH).unwrap();
+        assert_eq!(outcome.signature(), Some("Win.Test.EICAR_HDB-1"));
+        assert_eq!(responses.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_verdict_policy_tokio() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOutcome, VerdictPolicy};
+
+        struct Quarantine;
+
+        impl VerdictPolicy for Quarantine {
+            fn apply(&self, outcome: ScanOutcome) -> ScanOutcome {
+                match outcome {
+                    ScanOutcome::Infected { signature } => ScanOutcome::Infected {
+                        signature: format!("Quarantined.{}", signature),
+                    },
+                    outcome => outcome,
+                }
+            }
+        }
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let client = clamav_client::tokio::Client::new(clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        })
+        .verdict_policy(Quarantine);
+        let outcome = client.scan_file(super::EICAR_TEST_FILE_PATH).await.unwrap();
+        assert_eq!(
+            outcome.signature(),
+            Some("Quarantined.Win.Test.EICAR_HDB-1")
+        );
+        assert!(client.scan_buffer(b"clean data").await.unwrap().is_clean());
+    }
+
+    #[test]
+    fn mock_server_scan_response() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOutcome};
+
+        let server =
+            MockServer::start(MockServerOptions::new().infected_size(4, "Test-Signature")).unwrap();
+        let client = clamav_client::Client::new(clamav_client::Tcp {
+            host_address: server.address(),
+        })
+        .verdict_policy(|outcome| match outcome {
+            ScanOutcome::Infected { .. } => ScanOutcome::Clean,
+            outcome => outcome,
+        });
+
+        let response = client.scan_buffer_response(b"abcd").unwrap();
+        assert!(response.outcome.is_clean());
+        assert_eq!(response.raw, b"stream: Test-Signature FOUND\0");
+        let response = client
+            .scan_file_response(super::CLEAN_TEST_FILE_PATH)
+            .unwrap();
+        assert!(response.outcome.is_clean());
+        assert_eq!(response.raw, super::OK_RESPONSE);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_scan_response_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let client = clamav_client::tokio::Client::new(clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        });
+        let response = client
+            .scan_file_response(super::EICAR_TEST_FILE_PATH)
+            .await
+            .unwrap();
+        assert_eq!(response.outcome.signature(), Some("Win.Test.EICAR_HDB-1"));
+        assert_eq!(response.raw, b"stream: Win.Test.EICAR_HDB-1 FOUND\0");
+        let response = client.scan_buffer_response(b"clean data").await.unwrap();
+        assert_eq!(response.raw, super::OK_RESPONSE);
+    }
+
+    #[test]
+    fn mock_server_signature_allowlist() {
+        use clamav_client::{MockServer, MockServerOptions, SignatureAllowlist};
+
+        let server =
+            MockServer::start(MockServerOptions::new().infected_size(4, "Heuristics.Test"))
+                .unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let client = clamav_client::Client::new(clamd_tcp).verdict_policy(
+            SignatureAllowlist::new()
+                .allow("Win.Test.EICAR_*")
+                .warn("Heuristics.*"),
+        );
+
+        let outcome = client.scan_file(super::EICAR_TEST_FILE_PATH).unwrap();
+        assert!(outcome.is_clean());
+        let outcome = client.scan_buffer(b"abcd").unwrap();
+        assert!(outcome.is_warning());
+        assert_eq!(outcome.signature(), Some("Heuristics.Test"));
+        assert!(client.decide_buffer(b"abcd").unwrap().is_allowed());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_pua_policy_tokio() {
+        use clamav_client::{MockServer, MockServerOptions, PuaPolicy};
+
+        let options = MockServerOptions::new().infected_size(4, "PUA.Doc.Packed.EncryptedDoc");
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let client =
+            clamav_client::tokio::Client::new(clamd_tcp).verdict_policy(PuaPolicy::Warning);
+        let outcome = client.scan_buffer(b"abcd").await.unwrap();
+        assert!(outcome.is_warning() && outcome.is_pua());
+        let outcome = client.scan_file(super::EICAR_TEST_FILE_PATH).await.unwrap();
+        assert!(outcome.is_infected() && !outcome.is_pua());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_archive_limits_tokio() {
+        use clamav_client::{ArchiveLimits, MockServer, MockServerOptions, ScanOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let options = ScanOptions::new().archive_limits(ArchiveLimits::default());
+        let path = std::env::temp_dir().join(format!(
+            "clamav-client-archive-tokio-{}.gz",
+            std::process::id()
+        ));
+        std::fs::write(&path, gzip_file(u32::MAX)).unwrap();
+        let result = clamav_client::tokio::scan_file(&path, &clamd_tcp, &options).await;
+        assert_archive_limit_exceeded(result);
+
+        // The file is rewound after the check, so that all of it is scanned
+        std::fs::write(&path, zip_archive(3, 10)).unwrap();
+        let (options, reports) = super::options_with_report();
+        let options = options.archive_limits(ArchiveLimits::default());
+        let response = clamav_client::tokio::scan_file(&path, &clamd_tcp, &options).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(response.unwrap(), super::OK_RESPONSE);
+        super::assert_report(&reports, zip_archive(3, 10).len() as u64);
+    }
+
+    #[test]
+    fn mock_server_response_buffer() {
+        use clamav_client::{MockServer, MockServerOptions, ResponseBuffer, ScanOptions};
+
+        let options = MockServerOptions::new().infected_size(4, "Test-Signature");
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        for (initial_capacity, read_size) in [(0, 1), (4, 3), (64, 256), (1024, 1)] {
+            let options = ScanOptions::new().response_buffer(ResponseBuffer {
+                initial_capacity,
+                read_size,
+            });
+            let mut response = Vec::new();
+            clamav_client::scan_buffer_into(b"abcd", clamd_tcp, &options, &mut response).unwrap();
+            assert_eq!(response, b"stream: Test-Signature FOUND\0");
+            let capacity = response.capacity();
+            clamav_client::scan_buffer_into(b"clean", clamd_tcp, &options, &mut response).unwrap();
+            assert_eq!(response, b"stream: OK\0");
+            assert_eq!(response.capacity(), capacity);
+        }
+
+        let options = ScanOptions::new().response_buffer(ResponseBuffer {
+            initial_capacity: 64,
+            read_size: 0,
+        });
+        assert!(options.validate().is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_response_buffer_tokio() {
+        use clamav_client::{MockServer, MockServerOptions, ResponseBuffer, ScanOptions};
+
+        let options = MockServerOptions::new().infected_size(4, "Test-Signature");
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let options = ScanOptions::new().response_buffer(ResponseBuffer {
+            initial_capacity: 0,
+            read_size: 1,
+        });
+        let response = clamav_client::tokio::scan_buffer(b"abcd", clamd_tcp, &options)
+            .await
+            .unwrap();
+        assert_eq!(response, b"stream: Test-Signature FOUND\0");
+        let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+        assert_eq!(response, clamav_client::PONG);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_session_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let options = MockServerOptions::new().infected_size(4, "Test-Signature");
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let session = clamav_client::tokio::Session::start(clamd_tcp, None)
+            .await
+            .unwrap();
+        let (clean, infected, eicar) = tokio::join!(
+            session.scan_buffer(b"clean data"),
+            session.scan_buffer(b"data"),
+            session.scan_file(super::EICAR_TEST_FILE_PATH),
+        );
+        assert!(clean.unwrap().is_clean());
+        assert_eq!(infected.unwrap().signature(), Some("Test-Signature"));
+        assert_eq!(eicar.unwrap().signature(), Some("Win.Test.EICAR_HDB-1"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_transport_session_rejected_input_tokio() {
+        use clamav_client::ScanOptions;
+
+        let transport = clamav_client::MockTransport::new().respond(b"1: PONG\0".to_vec());
+        let options = ScanOptions::new().reject_empty(true);
+        let session = clamav_client::tokio::Session::start(transport.clone(), options)
+            .await
+            .unwrap();
+        let err = session.scan_buffer(b"").await.unwrap_err();
+        assert!(matches!(err, ClamdError::EmptyInput));
+
+        // The session is still usable after a local input error
+        assert_eq!(&session.ping().await.unwrap(), clamav_client::PONG);
+        drop(session);
+        assert!(transport.requests()[0].starts_with(b"zIDSESSION\0zPING\0"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_transport_session_close_tokio() {
+        let transport = clamav_client::MockTransport::new()
+            .respond(b"1: PONG\0".to_vec())
+            .respond(Vec::new());
+        let session = clamav_client::tokio::Session::start(transport.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(&session.ping().await.unwrap(), clamav_client::PONG);
+        session.close().await.unwrap();
+
+        let dropped = clamav_client::tokio::Session::start(transport.clone(), None)
+            .await
+            .unwrap();
+        drop(dropped);
+
+        let requests = transport.requests();
+        assert_eq!(requests[0], b"zIDSESSION\0zPING\0zEND\0");
+        assert_eq!(requests[1], b"zIDSESSION\0zEND\0");
+    }
+
+    #[test]
+    fn mock_transport_scan_queue_session_validation() {
+        use clamav_client::{
+            MockTransport, ScanJob, ScanQueue, ScanQueueOptions, SessionValidation,
+        };
+        use std::time::Duration;
+
+        // The first session is closed by ClamAV after one scan
+        let transport = MockTransport::new()
+            .respond(b"1: stream: OK\0".to_vec())
+            .respond(b"1: stream: OK\0".to_vec());
+        let queue_options = ScanQueueOptions::new()
+            .workers(1)
+            .session_validation(SessionValidation::Ping);
+        let queue = ScanQueue::new(transport.clone(), None, queue_options).unwrap();
+        for _ in 0..2 {
+            let scan = queue.submit(ScanJob::stream(&b"clean data"[..]));
+            assert!(scan.wait().unwrap().is_clean());
+        }
+        drop(queue);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].ends_with(b"\0\0\0\0zPING\0"));
+        assert!(requests[1].starts_with(b"zIDSESSION\0zINSTREAM\0"));
+
+        // Without validation, the stream cannot be retried on a new connection
+        let transport = MockTransport::new()
+            .respond(b"1: stream: OK\0".to_vec())
+            .respond(b"1: stream: OK\0".to_vec());
+        let queue_options = ScanQueueOptions::new()
+            .workers(1)
+            .session_validation(SessionValidation::PingIfIdle(Duration::from_secs(3600)));
+        let queue = ScanQueue::new(transport.clone(), None, queue_options).unwrap();
+        let scan = queue.submit(ScanJob::stream(&b"clean data"[..]));
+        assert!(scan.wait().unwrap().is_clean());
+        let scan = queue.submit(ScanJob::stream(&b"clean data"[..]));
+        assert!(scan.wait().is_err());
+        drop(queue);
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn mock_transport_measure_latency() {
+        use clamav_client::{ClamdError, MockTransport};
+
+        let transport = MockTransport::new()
+            .respond(clamav_client::PONG.to_vec())
+            .respond(clamav_client::PONG.to_vec())
+            .respond(clamav_client::PONG.to_vec());
+        let stats = clamav_client::measure_latency(&transport, 3).unwrap();
+        assert_eq!(stats.samples, 3);
+        assert!(stats.min <= stats.avg && stats.avg <= stats.max);
+        assert!(stats.min <= stats.p95 && stats.p95 <= stats.max);
+        assert_eq!(transport.requests(), vec![b"zPING\0".to_vec(); 3]);
+
+        let transport = MockTransport::new().respond(b"UNKNOWN COMMAND\0".to_vec());
+        let err = clamav_client::measure_latency(&transport, 3).unwrap_err();
+        assert!(matches!(err, ClamdError::InvalidResponse { .. }));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_measure_latency_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let stats = clamav_client::tokio::measure_latency(clamd_tcp, 5)
+            .await
+            .unwrap();
+        assert_eq!(stats.samples, 5);
+        assert!(stats.p95 <= stats.max);
+        assert_eq!(stats.total(), stats.connect + stats.avg);
+    }
+
+    #[test]
+    fn mock_transport_server_stats() {
+        use clamav_client::{ClamdError, MockTransport};
+
+        let transport = MockTransport::new()
+            .respond(
+                b"POOLS: 1\n\nSTATE: VALID PRIMARY\n\
+                  THREADS: live 4  idle 1 max 12 idle-timeout 30\nQUEUE: 3 items\n\
+                  \tINSTREAM 0.512345\n\
+                  MEMSTATS: heap 9.082M mmap 0.000M used 6.902M free 2.184M \
+                  releasable 0.129M pools 1 pools_used 565.979M pools_total 565.999M\nEND\0"
+                    .to_vec(),
+            )
+            .respond(b"UNKNOWN COMMAND\0".to_vec());
+        let stats = clamav_client::server_stats(&transport).unwrap();
+        assert_eq!(stats.pools, 1);
+        assert_eq!(stats.queue_length, 3);
+        assert_eq!(
+            (stats.live_threads, stats.idle_threads, stats.max_threads),
+            (4, 1, 12)
+        );
+        assert_eq!(stats.busy_threads(), 3);
+        assert_eq!(stats.mmap_bytes, Some(0));
+        assert_eq!(stats.used_bytes, Some(7_237_272));
+        assert_eq!(stats.pools_used_bytes, Some(593_471_996));
+        assert_eq!(transport.requests(), vec![b"zSTATS\0".to_vec()]);
+
+        let err = clamav_client::server_stats(&transport).unwrap_err();
+        assert!(matches!(err, ClamdError::InvalidResponse { .. }));
+    }
+
+    #[test]
+    fn mock_server_poll_stats() {
+        use clamav_client::{MockServer, MockServerOptions};
+        use std::time::{Duration, Instant};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address().to_owned(),
+        };
+        let poller = clamav_client::poll_stats(clamd_tcp, Duration::from_millis(10));
+        let start = Instant::now();
+        let stats = loop {
+            if let Some(stats) = poller.latest() {
+                break stats;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!((stats.queue_length, stats.max_threads), (0, 10));
+        // The mock server does not report its memory usage
+        assert_eq!(stats.heap_bytes, None);
+        assert!(poller.age().unwrap() < Duration::from_secs(5));
+        assert_eq!(poller.last_error(), None);
+
+        let poller = clamav_client::poll_stats(
+            clamav_client::Tcp {
+                host_address: refusing_address(),
+            },
+            Duration::from_millis(10),
+        );
+        let start = Instant::now();
+        while poller.last_error().is_none() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(poller.latest(), None);
+    }
+
+    #[test]
+    fn mock_server_cross_check() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let default = MockServer::start(MockServerOptions::new()).unwrap();
+        let heuristic =
+            MockServer::start(MockServerOptions::new().infected_size(4, "Heuristics.Test"))
+                .unwrap();
+        let endpoints = [
+            clamav_client::Tcp {
+                host_address: default.address(),
+            },
+            clamav_client::Tcp {
+                host_address: heuristic.address(),
+            },
+        ];
+
+        let check = clamav_client::scan_buffer_cross_checked(b"data", endpoints, None);
+        assert!(check.any_infected());
+        assert!(check.is_disputed());
+        assert_eq!(check.signatures(), ["Heuristics.Test"]);
+
+        let check =
+            clamav_client::scan_file_cross_checked(super::EICAR_TEST_FILE_PATH, endpoints, None);
+        assert!(check.all_infected());
+        assert_eq!(check.signatures(), ["Win.Test.EICAR_HDB-1"]);
+
+        let check = clamav_client::scan_buffer_cross_checked(b"clean data", endpoints, None);
+        assert!(check.all_clean());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_cross_check_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let refusing = refusing_address();
+        let endpoints = [
+            clamav_client::tokio::Tcp {
+                host_address: server.address(),
+            },
+            clamav_client::tokio::Tcp {
+                host_address: refusing.as_str(),
+            },
+        ];
+        let check =
+            clamav_client::tokio::scan_buffer_cross_checked(b"clean data", endpoints, None).await;
+        assert!(check.results[0].as_ref().unwrap().is_clean());
+        assert!(check.results[1].is_err());
+        assert!(!check.all_clean());
+        assert!(check.is_disputed());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio-stream")]
+    async fn mock_server_virus_scan_tokio() {
+        use clamav_client::tokio::ScanStreamExt;
+        use clamav_client::{MockServer, MockServerOptions, ScanOutcome};
+        use tokio_stream::StreamExt;
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let chunks = [
+            &b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR"[..],
+            &b"-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*"[..],
+        ];
+        let response = tokio_stream::iter(chunks)
+            .map(Ok::<_, std::io::Error>)
+            .virus_scan(&clamd_tcp, None)
+            .await
+            .unwrap();
+        assert!(ScanOutcome::from_response(&response).unwrap().is_infected());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_scan_futures_stream_tokio() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOutcome};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+        let chunks = [Ok::<_, std::io::Error>(&b"clean "[..]), Ok(&b"data"[..])];
+        let stream = futures_util::stream::iter(chunks);
+        let response = clamav_client::tokio::scan_stream(stream, clamd_tcp, None)
+            .await
+            .unwrap();
+        assert!(ScanOutcome::from_response(&response).unwrap().is_clean());
+    }
+
+    #[test]
+    fn mock_transport_boxed() {
+        use clamav_client::{BoxedTransport, MockServer, MockServerOptions, MockTransport};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let mock = MockTransport::new().infected("Test-Signature");
+        let transports = [
+            BoxedTransport::new(clamav_client::Tcp {
+                host_address: server.address().to_owned(),
+            }),
+            BoxedTransport::new(mock.clone()),
+        ];
+        let client = clamav_client::Client::new(transports[0].clone());
+        assert!(client.scan_buffer(b"clean data").unwrap().is_clean());
+        let client = clamav_client::Client::new(transports[1].clone());
+        assert!(client.scan_buffer(b"data").unwrap().is_infected());
+        assert!(mock.requests()[0].starts_with(b"zINSTREAM\0"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_boxed_transport_tokio() {
+        use clamav_client::tokio::BoxedTransport;
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let address = server.address().to_owned();
+        let transports = [
+            BoxedTransport::from(clamav_client::tokio::Tcp {
+                host_address: address.clone(),
+            }),
+            BoxedTransport::from_fn(move || tokio::net::TcpStream::connect(address.clone())),
+        ];
+        for transport in transports {
+            assert!(!clamav_client::tokio::TransportProtocol::is_local(
+                &transport
+            ));
+            let client = clamav_client::tokio::Client::new(transport);
+            // The scans of a boxed transport can run on spawned tasks
+            let outcome = tokio::spawn(async move { client.scan_buffer(b"clean data").await })
+                .await
+                .unwrap();
+            assert!(outcome.unwrap().is_clean());
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_transport_from_blocking_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let transport = clamav_client::Tcp {
+            host_address: server.address().to_owned(),
+        };
+        let clamd_tcp = clamav_client::tokio::Tcp::from(transport.clone());
+        assert_eq!(clamd_tcp.host_address, transport.host_address);
+        let response = clamav_client::tokio::ping(clamd_tcp).await.unwrap();
+        assert_eq!(&response, clamav_client::PONG);
+
+        #[cfg(unix)]
+        {
+            let socket = clamav_client::Socket {
+                socket_path: std::path::PathBuf::from(super::TEST_SOCKET_PATH),
+            };
+            let clamd_socket = clamav_client::tokio::Socket::from(socket.clone());
+            assert_eq!(clamd_socket.socket_path, socket.socket_path);
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "reqwest-middleware")]
+    async fn mock_server_reqwest_middleware() {
+        use clamav_client::reqwest_middleware::{ScanError, ScanMiddleware};
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(ScanMiddleware::tcp(server.address()).scan_requests(true))
+            .build();
+
+        let url = super::serve_http_once("200 OK", b"clean data");
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.url().as_str(), url);
+        assert_eq!(response.content_length(), Some(10));
+        assert_eq!(&response.bytes().await.unwrap()[..], b"clean data");
+
+        let url = super::serve_http_once("200 OK", include_bytes!("data/eicar.txt"));
+        let err = client.get(&url).send().await.unwrap_err();
+        let scan_error = ScanError::from_error(&err).unwrap();
+        assert!(matches!(
+            scan_error,
+            ScanError::InfectedResponse { signature } if signature == "Win.Test.EICAR_HDB-1"
+        ));
+
+        // The infected request is not sent, so nothing listens on the URL
+        let err = client
+            .post(format!("http://{}/upload", refusing_address()))
+            .body(&include_bytes!("data/eicar.txt")[..])
+            .send()
+            .await
+            .unwrap_err();
+        let scan_error = ScanError::from_error(&err).unwrap();
+        assert!(matches!(scan_error, ScanError::InfectedRequest { .. }));
+        assert!(scan_error.is_infected());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_scan_and_commit_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let dir_path =
+            std::env::temp_dir().join(format!("clamav-client-spool-tokio-{}", std::process::id()));
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let destination = dir_path.join("upload.txt");
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        };
+
+        let upload =
+            futures_util::stream::iter([Ok::<_, std::io::Error>(&b"clean "[..]), Ok(&b"data"[..])]);
+        let scan = clamav_client::tokio::scan_and_commit(upload, &destination, clamd_tcp, None)
+            .await
+            .unwrap();
+        assert!(scan.outcome.is_clean());
+        assert_eq!(scan.path.as_deref(), Some(destination.as_path()));
+        assert_eq!(std::fs::read(&destination).unwrap(), b"clean data");
+
+        // An infected upload does not replace the committed file
+        let upload = futures_util::stream::iter([Ok::<_, std::io::Error>(
+            &include_bytes!("data/eicar.txt")[..],
+        )]);
+        let scan = clamav_client::tokio::scan_and_commit(upload, &destination, clamd_tcp, None)
+            .await
+            .unwrap();
+        assert!(scan.outcome.is_infected());
+        assert!(!scan.is_committed());
+        assert_eq!(std::fs::read(&destination).unwrap(), b"clean data");
+
+        let upload = futures_util::stream::iter([
+            Ok(&b"partial"[..]),
+            Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset)),
+        ]);
+        let destination = dir_path.join("failed.txt");
+        let result =
+            clamav_client::tokio::scan_and_commit(upload, &destination, clamd_tcp, None).await;
+        assert!(result.is_err());
+        assert!(!destination.exists());
+
+        // No spool files are left behind
+        let entries: Vec<_> = std::fs::read_dir(&dir_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, ["upload.txt"]);
+        std::fs::remove_dir_all(&dir_path).unwrap();
     }
 
     #[async_std::test]
-    async fn async_std_scan_tcp_infected_stream() {
-        let stream = stream_from_file(EICAR_TEST_FILE_PATH).await;
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
-        );
-        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, None)
+    #[cfg(feature = "async-std")]
+    async fn mock_server_scan_and_commit_async_std() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let dir_path = std::env::temp_dir().join(format!(
+            "clamav-client-spool-async-std-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let destination = dir_path.join("upload.txt");
+        let clamd_tcp = clamav_client::async_std::Tcp {
+            host_address: server.address(),
+        };
+
+        let upload = futures_util::stream::iter([Ok::<_, std::io::Error>(&b"clean data"[..])]);
+        let scan = clamav_client::async_std::scan_and_commit(upload, &destination, clamd_tcp, None)
             .await
-            .expect(&err_msg);
-        assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+            .unwrap();
+        assert_eq!(scan.path.as_deref(), Some(destination.as_path()));
+        assert_eq!(std::fs::read(&destination).unwrap(), b"clean data");
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn timeout_resolver_tokio() {
+        use clamav_client::{ClamdError, ErrorPhase};
+        use std::{io, net::SocketAddr, time::Duration};
+
+        struct Unresponsive;
+
+        impl clamav_client::tokio::Resolve for Unresponsive {
+            async fn resolve(&self, _host: &str, _port: u16) -> io::Result<Vec<SocketAddr>> {
+                std::future::pending().await
+            }
+        }
+
+        let resolver = clamav_client::TimeoutResolver::new(Unresponsive, Duration::from_millis(10));
+        let clamd_tcp = clamav_client::tokio::ResolvingTcp::new("clamd.invalid", 3310, resolver);
+        let err = clamav_client::tokio::ping(clamd_tcp).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let err = ClamdError::from(err);
+        assert_eq!(err.phase(), Some(ErrorPhase::Resolve));
+        assert_eq!(err.resource(), Some("clamd.invalid"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mock_server_path_fallback() {
+        use clamav_client::{ClamdError, MockServer, MockServerOptions, ScanOptions};
+
+        let socket_path = std::env::temp_dir().join(format!(
+            "clamav-client-fallback-{}.socket",
+            std::process::id()
+        ));
+        let server = MockServer::start_unix(&socket_path, MockServerOptions::new()).unwrap();
+        let clamd_socket = clamav_client::Socket {
+            socket_path: server.address(),
+        };
+        let options = ScanOptions::new().max_stream_size(10);
+        let err = clamav_client::scan_file(super::EICAR_TEST_FILE_PATH, clamd_socket, &options)
+            .unwrap_err();
+        assert!(matches!(
+            ClamdError::from(err),
+            ClamdError::SizeLimitExceeded { limit: 10 }
+        ));
+
+        let options = options.path_fallback(true);
+        let response =
+            clamav_client::scan_file(super::EICAR_TEST_FILE_PATH, clamd_socket, &options).unwrap();
+        let path = std::fs::canonicalize(super::EICAR_TEST_FILE_PATH).unwrap();
+        let expected = format!("{}: Win.Test.EICAR_HDB-1 FOUND\0", path.display());
+        assert_eq!(response, expected.as_bytes());
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        assert!(
+            clamav_client::scan_file(super::EICAR_TEST_FILE_PATH, clamd_tcp, &options).is_err()
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, feature = "tokio"))]
+    async fn mock_server_path_fallback_tokio() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions};
+
+        let socket_path = std::env::temp_dir().join(format!(
+            "clamav-client-fallback-tokio-{}.socket",
+            std::process::id()
+        ));
+        let server = MockServer::start_unix(&socket_path, MockServerOptions::new()).unwrap();
+        let client = clamav_client::tokio::Client::new(clamav_client::tokio::Socket {
+            socket_path: server.address(),
+        })
+        .options(ScanOptions::new().max_stream_size(10).path_fallback(true));
+        let outcome = client.scan_file(super::EICAR_TEST_FILE_PATH).await.unwrap();
+        assert_eq!(outcome.signature(), Some("Win.Test.EICAR_HDB-1"));
     }
 
     #[async_std::test]
-    async fn async_std_scan_tcp_clean_stream() {
-        let stream = stream_from_file(CLEAN_TEST_FILE_PATH).await;
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            CLEAN_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+    #[cfg(all(unix, feature = "async-std"))]
+    async fn mock_server_path_fallback_async_std() {
+        use clamav_client::{MockServer, MockServerOptions, ScanOptions};
+
+        let socket_path = std::env::temp_dir().join(format!(
+            "clamav-client-fallback-async-std-{}.socket",
+            std::process::id()
+        ));
+        let server = MockServer::start_unix(&socket_path, MockServerOptions::new()).unwrap();
+        let clamd_socket = clamav_client::async_std::Socket {
+            socket_path: server.address(),
+        };
+        let options = ScanOptions::new().max_stream_size(10).path_fallback(true);
+        let response =
+            clamav_client::async_std::scan_file(super::EICAR_TEST_FILE_PATH, clamd_socket, options)
+                .await
+                .unwrap();
+        assert!(response.ends_with(b": Win.Test.EICAR_HDB-1 FOUND\0"));
+    }
+
+    #[test]
+    fn mock_server_database_age() {
+        use clamav_client::{Client, MockServer, MockServerOptions};
+        use std::time::Duration;
+
+        let max_age = Duration::from_secs(48 * 60 * 60);
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        assert!(clamav_client::database_age(clamd_tcp).unwrap() > max_age);
+        assert!(clamav_client::is_database_stale(clamd_tcp, max_age).unwrap());
+
+        let options =
+            MockServerOptions::new().version("ClamAV 1.4.1/27400/Fri Jan  1 00:00:00 2100");
+        let server = MockServer::start(options).unwrap();
+        let client = Client::new(clamav_client::Tcp {
+            host_address: server.address(),
+        });
+        assert_eq!(client.database_age().unwrap(), Duration::ZERO);
+        assert!(!client.is_database_stale(max_age).unwrap());
+
+        let server = MockServer::start(MockServerOptions::new().version("ClamAV 1.4.1")).unwrap();
+        let client = Client::new(clamav_client::Tcp {
+            host_address: server.address(),
+        });
+        assert!(matches!(
+            client.is_database_stale(max_age),
+            Err(clamav_client::ClamdError::InvalidResponse { .. })
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_database_age_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+        use std::time::Duration;
+
+        let max_age = Duration::from_secs(48 * 60 * 60);
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let client = clamav_client::tokio::Client::new(clamav_client::tokio::Tcp {
+            host_address: server.address().to_owned(),
+        });
+        assert!(client.database_age().await.unwrap() > max_age);
+        assert!(client.is_database_stale(max_age).await.unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mock_server_unix_socket() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let socket_path =
+            std::env::temp_dir().join(format!("clamav-client-mock-{}.socket", std::process::id()));
+        let server = MockServer::start_unix(&socket_path, MockServerOptions::new()).unwrap();
+        let clamd_socket = clamav_client::Socket {
+            socket_path: server.address(),
+        };
+        assert_eq!(
+            &clamav_client::ping(clamd_socket).unwrap(),
+            clamav_client::PONG
         );
-        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, None)
+        drop(server);
+        assert!(!socket_path.exists());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_server_tokio() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let server = MockServer::start(MockServerOptions::new()).unwrap();
+        let client = clamav_client::tokio::Client::new(clamav_client::tokio::Tcp {
+            host_address: server.address(),
+        });
+        let outcome = client.scan_file(super::EICAR_TEST_FILE_PATH).await.unwrap();
+        assert!(outcome.is_infected());
+    }
+
+    #[test]
+    fn mock_server_faults() {
+        use std::time::{Duration, Instant};
+
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let options = MockServerOptions::new()
+            .latency(Duration::from_millis(100))
+            .truncate_response(6);
+        let server = MockServer::start(options).unwrap();
+        let clamd_tcp = clamav_client::Tcp {
+            host_address: server.address(),
+        };
+        let start = Instant::now();
+        let response = clamav_client::scan_buffer(b"data", clamd_tcp, None).unwrap();
+        assert_eq!(response, b"stream");
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        let server = MockServer::start(MockServerOptions::new().stream_max_length(4)).unwrap();
+        let client = Client::new(clamav_client::Tcp {
+            host_address: server.address(),
+        });
+        assert!(client.scan_buffer(b"data").unwrap().is_clean());
+        let outcome = client.scan_buffer(b"more data").unwrap();
+        assert_eq!(
+            outcome,
+            clamav_client::ScanOutcome::Error {
+                message: "INSTREAM size limit exceeded.".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn mock_server_reset_mid_stream() {
+        use clamav_client::{MockServer, MockServerOptions};
+
+        let options = MockServerOptions::new()
+            .reset_after(1024)
+            .faulty_connections(1);
+        let server = MockServer::start(options).unwrap();
+        let client = Client::new(clamav_client::Tcp {
+            host_address: server.address(),
+        })
+        .options(clamav_client::ScanOptions::new().chunk_size(512));
+        assert!(client.scan_buffer([0; 4096]).is_err());
+        assert!(client.scan_buffer([0; 4096]).unwrap().is_clean());
+    }
+
+    #[test]
+    fn mock_transport_reset_after() {
+        let transport = MockTransport::new().reset_after(12).clean();
+        let client = Client::new(transport.clone());
+
+        let err = client.scan_buffer(b"data").unwrap_err();
+        assert!(
+            matches!(err, ClamdError::Io(ref err) if err.kind() == std::io::ErrorKind::ConnectionReset)
+        );
+        assert!(client.scan_buffer(b"data").unwrap().is_clean());
+        assert_eq!(transport.requests()[0], b"zINSTREAM\0\0\0");
+    }
+
+    #[test]
+    fn mock_transport_scan_queue_missing_file() {
+        use clamav_client::{ScanJob, ScanQueue, ScanQueueOptions};
+
+        let transport =
+            MockTransport::new().respond([&b"1: stream: OK\0"[..], b"2: stream: OK\0"].concat());
+        let queue_options = ScanQueueOptions::new().workers(1);
+        let queue = ScanQueue::new(transport.clone(), None, queue_options).unwrap();
+        let scan = queue.submit(ScanJob::buffer(&b"clean data"[..]));
+        assert!(scan.wait().unwrap().is_clean());
+        // The missing file is not retried and keeps the session
+        let scan = queue.submit(ScanJob::file("tests/data/missing.txt"));
+        assert!(matches!(
+            scan.wait(),
+            Err(ClamdError::Io(ref err)) if err.kind() == std::io::ErrorKind::NotFound
+        ));
+        let scan = queue.submit(ScanJob::buffer(&b"clean data"[..]));
+        assert!(scan.wait().unwrap().is_clean());
+        drop(queue);
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn mock_transport_scan_queue_zero_workers() {
+        use clamav_client::{ScanQueue, ScanQueueOptions};
+
+        let queue_options = ScanQueueOptions::new().workers(0);
+        assert!(matches!(
+            queue_options.validate(),
+            Err(ClamdError::InvalidOptions { .. })
+        ));
+        let transport = MockTransport::new();
+        let err = ScanQueue::new(transport.clone(), None, queue_options)
+            .err()
+            .expect("Expected the queue to reject zero workers");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(transport.requests().is_empty());
+    }
+
+    #[test]
+    fn mock_transport_scan_queue_warmup() {
+        use clamav_client::{ScanJob, ScanQueue, ScanQueueOptions};
+
+        let transport =
+            MockTransport::new().respond([&b"1: PONG\0"[..], b"2: stream: OK\0"].concat());
+        let queue_options = ScanQueueOptions::new()
+            .workers(1)
+            .warmup(1)
+            .warmup_ping(true);
+        let queue = ScanQueue::new(transport.clone(), None, queue_options).unwrap();
+        let scan = queue.submit(ScanJob::buffer(&b"clean data"[..]));
+        assert!(scan.wait().unwrap().is_clean());
+        drop(queue);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with(b"zIDSESSION\0zPING\0zINSTREAM\0"));
+    }
+
+    #[test]
+    fn mock_transport_error_context() {
+        use clamav_client::ErrorPhase;
+
+        let client = Client::new(MockTransport::new().reset_after(12).clean());
+        let err = client.scan_buffer(b"data").unwrap_err();
+        assert_eq!(err.phase(), Some(ErrorPhase::Stream));
+        assert_eq!(err.resource(), None);
+
+        let err = client.scan_file("tests/data/missing.txt").unwrap_err();
+        assert_eq!(err.phase(), Some(ErrorPhase::Input));
+        assert_eq!(err.resource(), Some("tests/data/missing.txt"));
+        assert!(err
+            .to_string()
+            .starts_with("reading tests/data/missing.txt failed: "));
+
+        let address = refusing_address();
+        let err = ClamdError::from(
+            clamav_client::ping(clamav_client::Tcp {
+                host_address: address.as_str(),
+            })
+            .unwrap_err(),
+        );
+        assert!(
+            matches!(err, ClamdError::Io(ref err) if err.kind() == std::io::ErrorKind::ConnectionRefused)
+        );
+        assert_eq!(err.phase(), Some(ErrorPhase::Connect));
+        assert_eq!(err.resource(), Some(address.as_str()));
+
+        let err = ClamdError::from(
+            clamav_client::ping(clamav_client::Tcp {
+                host_address: "localhost:clamd",
+            })
+            .unwrap_err(),
+        );
+        assert_eq!(err.phase(), Some(ErrorPhase::Resolve));
+
+        #[cfg(unix)]
+        {
+            let socket_path = "tests/data/missing.socket";
+            let err = ClamdError::from(
+                clamav_client::ping(clamav_client::Socket { socket_path }).unwrap_err(),
+            );
+            assert_eq!(err.phase(), Some(ErrorPhase::Connect));
+            assert_eq!(err.resource(), Some(socket_path));
+        }
+
+        let queue_options = clamav_client::ScanQueueOptions::new().workers(1);
+        let queue =
+            clamav_client::ScanQueue::new(MockTransport::new(), None, queue_options).unwrap();
+        let scan = queue.submit(clamav_client::ScanJob::file("tests/data/missing.txt"));
+        let err = scan.wait().unwrap_err();
+        assert_eq!(err.phase(), Some(ErrorPhase::Input));
+        assert_eq!(err.resource(), Some("tests/data/missing.txt"));
+
+        #[cfg(all(feature = "sendfile", target_os = "linux"))]
+        {
+            let address = refusing_address();
+            let err = ClamdError::from(
+                clamav_client::scan_file_zero_copy(
+                    "tests/data/missing.txt",
+                    clamav_client::Tcp {
+                        host_address: address.as_str(),
+                    },
+                    None,
+                )
+                .unwrap_err(),
+            );
+            assert_eq!(err.phase(), Some(ErrorPhase::Input));
+            assert_eq!(err.resource(), Some("tests/data/missing.txt"));
+        }
+
+        let err = ClamdError::SizeLimitExceeded { limit: 16 };
+        assert_eq!(err.phase(), None);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_transport_error_context_tokio() {
+        use clamav_client::ErrorPhase;
+
+        let address = refusing_address();
+        let clamd_tcp = clamav_client::tokio::Tcp {
+            host_address: address.as_str(),
+        };
+        let err = ClamdError::from(clamav_client::tokio::ping(clamd_tcp).await.unwrap_err());
+        assert_eq!(err.phase(), Some(ErrorPhase::Connect));
+        assert_eq!(err.resource(), Some(address.as_str()));
+
+        let err = clamav_client::tokio::scan_file("tests/data/missing.txt", clamd_tcp, None)
             .await
-            .expect(&err_msg);
-        assert_eq!(&response, OK_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(true));
+            .unwrap_err();
+        let err = ClamdError::from(err);
+        assert_eq!(err.phase(), Some(ErrorPhase::Input));
+        assert_eq!(err.resource(), Some("tests/data/missing.txt"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn clamd_process_start_failure() {
+        use clamav_client::{ClamdProcess, ClamdProcessOptions};
+
+        let options = ClamdProcessOptions::new().clamd_path("tests/data/no-such-clamd");
+        let err = ClamdProcess::start(options).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        let options = ClamdProcessOptions::new().clamd_path("false");
+        let err = ClamdProcess::start(options).unwrap_err();
+        assert!(err.to_string().starts_with("clamd exited with"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    #[ignore = "spawns clamd, which takes a while to load its databases"]
+    fn clamd_process() {
+        use clamav_client::{ClamdProcess, ClamdProcessOptions};
+
+        let options = ClamdProcessOptions::new().stream_max_length(4);
+        let clamd = ClamdProcess::start(options).unwrap();
+        assert!(clamd.socket_path().exists());
+        let client = Client::new(clamd.socket());
+        assert!(client.scan_buffer(b"data").unwrap().is_clean());
+        let outcome = client.scan_buffer(b"more data").unwrap();
+        assert!(matches!(outcome, clamav_client::ScanOutcome::Error { .. }));
+
+        let directory = clamd.directory().to_path_buf();
+        clamd.stop().unwrap();
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn mock_transport_failure_policy() {
+        use clamav_client::{ClamdClientBuilder, FailurePolicy};
+
+        let transport = MockTransport::new()
+            .clean()
+            .infected("Eicar-Signature")
+            .refuse()
+            .refuse();
+        let client = Client::new(transport.clone()).failure_policy(FailurePolicy::FailClosed);
+        assert!(client.decide_buffer(b"data").unwrap().is_allowed());
+        let decision = client.decide_buffer(b"data").unwrap();
+        assert_eq!(decision.signature(), Some("Eicar-Signature"));
+        let decision = client.decide_buffer(b"data").unwrap();
+        assert!(!decision.is_allowed() && !decision.is_scanned());
+        let client = client.failure_policy(FailurePolicy::FailOpen);
+        let decision = client.decide_file(super::CLEAN_TEST_FILE_PATH).unwrap();
+        assert!(decision.is_allowed() && !decision.is_scanned());
+
+        let client = ClamdClientBuilder::tcp("127.0.0.1:1")
+            .failure_policy(FailurePolicy::FailOpen)
+            .build();
+        assert!(client.decide_buffer(b"data").unwrap().is_allowed());
+        let client = Client::new(MockTransport::new().refuse());
+        assert!(matches!(
+            client.decide_buffer(b"data"),
+            Err(ClamdError::Io(_))
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn mock_transport_failure_policy_tokio() {
+        use clamav_client::FailurePolicy;
+
+        let transport =
+            MockTransport::new().respond(b"INSTREAM size limit exceeded. ERROR\0".to_vec());
+        let client = clamav_client::tokio::Client::new(transport.clone())
+            .failure_policy(FailurePolicy::FailClosed);
+        let decision = client.decide_buffer(b"data").await.unwrap();
+        assert!(!decision.is_allowed() && !decision.is_scanned());
     }
 
     #[async_std::test]
-    async fn async_std_scan_tcp_oversized_stream() {
-        let stream = stream_from_file(OVERSIZED_TEST_FILE_PATH).await;
-        let err_msg = format!(
-            "Could not scan test file {} via TCP at {}",
-            OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP.host_address
+    #[cfg(feature = "async-std")]
+    async fn mock_transport_async_std() {
+        let transport = MockTransport::new().clean();
+        let client = clamav_client::async_std::Client::new(transport.clone());
+        assert!(client.scan_buffer(b"data").await.unwrap().is_clean());
+        assert_eq!(transport.requests(), [instream_request(b"data")]);
+    }
+}
+
+#[cfg(feature = "log")]
+mod log_tests {
+    use std::{sync::Mutex, thread::ThreadId};
+
+    use log::{Level, Log, Metadata, Record};
+
+    use super::*;
+
+    const CLAMD_HOST_TCP: clamav_client::Tcp<&str> = clamav_client::Tcp {
+        host_address: TEST_HOST_ADDRESS,
+    };
+
+    /// Records the log messages of this crate on all threads, because the
+    /// logger is global
+    struct TestLogger {
+        records: Mutex<Vec<(ThreadId, String, Level, String)>>,
+    }
+
+    impl Log for TestLogger {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            self.records.lock().unwrap().push((
+                std::thread::current().id(),
+                record.target().to_owned(),
+                record.level(),
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: TestLogger = TestLogger {
+        records: Mutex::new(Vec::new()),
+    };
+
+    /// Runs `f` and returns the messages it logged on the current thread with
+    /// the `target`
+    ///
+    /// The events of the "tracing" feature, which are forwarded to `log` if no
+    /// tracing subscriber is set, have other targets.
+    fn capture_logs<F: FnOnce()>(target: &str, f: F) -> Vec<(Level, String)> {
+        // Fails if another test has already set the logger
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+        let thread = std::thread::current().id();
+        f();
+        LOGGER
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, record_target, _, _)| *id == thread && record_target == target)
+            .map(|(_, _, level, message)| (*level, message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn log_scan_infected() {
+        let logs = capture_logs("clamav_client", || {
+            let response =
+                clamav_client::scan_file(EICAR_TEST_FILE_PATH, CLAMD_HOST_TCP, None).unwrap();
+            assert_eq!(&response, EICAR_FILE_SIGNATURE_FOUND_RESPONSE);
+        });
+
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].0, Level::Debug);
+        assert!(logs[0].1.starts_with("Connected to ClamAV in "));
+        assert_eq!(logs[1].0, Level::Info);
+        assert!(logs[1].1.starts_with("Scanned 69 bytes in "));
+        assert!(logs[1].1.ends_with(": Eicar-Signature found"));
+    }
+
+    #[test]
+    fn log_scan_correlation_id() {
+        let logs = capture_logs("clamav_client", || {
+            let options = clamav_client::ScanOptions::new().correlation_id("upload-42");
+            clamav_client::scan_buffer(b"clean data", CLAMD_HOST_TCP, options).unwrap();
+        });
+
+        assert_eq!(logs.len(), 2);
+        assert!(logs[0].1.starts_with("[upload-42] Connected to ClamAV in "));
+        assert!(logs[1].1.starts_with("[upload-42] Scanned 10 bytes in "));
+    }
+
+    #[test]
+    fn log_scan_clean() {
+        let logs = capture_logs("clamav_client", || {
+            let response = clamav_client::scan_buffer(b"clean data", CLAMD_HOST_TCP, None).unwrap();
+            assert_eq!(&response, OK_RESPONSE);
+        });
+
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[1].0, Level::Debug);
+        assert!(logs[1].1.starts_with("Scanned 10 bytes in "));
+        assert!(logs[1].1.ends_with(": clean"));
+    }
+
+    #[test]
+    fn log_scan_error() {
+        let logs = capture_logs("clamav_client", || {
+            let response =
+                clamav_client::scan_file(OVERSIZED_TEST_FILE_PATH, CLAMD_HOST_TCP, None).unwrap();
+            assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
+        });
+
+        let (level, message) = logs.last().unwrap();
+        assert_eq!(*level, Level::Warn);
+        assert!(message.contains("ClamAV reported an error: INSTREAM size limit exceeded."));
+    }
+
+    #[test]
+    fn log_connect_failure() {
+        let clamd_unavailable = clamav_client::Tcp {
+            host_address: "localhost:1",
+        };
+        let logs = capture_logs("clamav_client", || {
+            let result = clamav_client::scan_buffer(b"clean data", clamd_unavailable, None);
+            assert!(result.is_err());
+        });
+
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].0, Level::Warn);
+        assert!(logs[0].1.starts_with("Could not connect to ClamAV: "));
+    }
+
+    #[test]
+    #[cfg(not(feature = "tracing"))]
+    fn log_wire_protocol() {
+        let logs = capture_logs("clamav_client::wire", || {
+            let response = clamav_client::scan_buffer(b"clean data", CLAMD_HOST_TCP, None).unwrap();
+            assert_eq!(&response, OK_RESPONSE);
+        });
+
+        let messages: Vec<&str> = logs.iter().map(|(_, message)| message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                r#"> "zINSTREAM\x00""#,
+                "> chunk of 10 bytes",
+                "> end of stream",
+                r#"< "stream: OK\x00""#,
+            ]
         );
-        let response = clamav_client::async_std::scan_stream(stream, CLAMD_HOST_TCP, None)
-            .await
-            .expect(&err_msg);
-        assert_eq!(&response, SIZE_LIMIT_EXCEEDED_ERROR_RESPONSE);
-        assert_eq!(clamav_client::clean(&response), Ok(false));
+        assert!(logs.iter().all(|(level, _)| *level == Level::Trace));
     }
 }
 